@@ -3,9 +3,30 @@
 //! VersaTiles is a command-line tool for converting, probing, and serving map tiles in various formats.
 //!
 //! ## Subcommands
+//! - **ApplyPatch**: Apply a patch written by `diff --write-patch` to a container.
+//! - **Bundle**: Bundle a MapLibre style with its sprites and glyphs.
+//! - **Checksum**: Write or verify a manifest of tile checksums.
 //! - **Convert**: Convert between different tile containers.
+//! - **Dev**: Tools for developing and testing, e.g. generating fixtures.
+//! - **Diff**: Compare the tiles of two containers.
+//! - **Expire**: Update the tiles of a container listed in a tile expiry file.
+//! - **ExportFeatures**: Export the features of a vector tile container as GeoJSON or FlatGeobuf files.
+//! - **Glyphs**: Convert fonts into SDF glyphs.
+//! - **Info**: Print a quick summary of tile containers.
+//! - **Lint**: Check the tiles of a vector tile container against rules.
+//! - **List**: List the coordinates of the existing tiles of a container.
+//! - **Outline**: Export the tiles or blocks of a container as GeoJSON.
 //! - **Probe**: Show information about a tile container.
+//! - **Query**: Query the features of a vector tile container at a position.
+//! - **QueryElevation**: Query the elevation at a position from terrain-RGB tiles.
+//! - **Schema**: Generate a tileset schema or validate a container against it.
+//! - **Scrape**: Download tiles from an XYZ endpoint into a container.
+//! - **SearchIndex**: Build a search index of the feature names of a vector tile container.
 //! - **Serve**: Serve tiles via HTTP.
+//! - **Sign**: Sign a `*.versatiles` container with an Ed25519 key.
+//! - **Sprites**: Pack SVG icons into sprite sheets.
+//! - **Sync**: Upload only the changed tiles of a container.
+//! - **Verify**: Verify the signature of a `*.versatiles` container.
 //!
 //! ## Usage
 //! ```sh
@@ -30,6 +51,7 @@ mod tools;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use log::LevelFilter;
+use versatiles_core::utils::{get_thread_count, set_blocking_pool_size, set_io_concurrency, set_thread_count};
 
 /// Command-line interface for VersaTiles
 #[derive(Parser, Debug)]
@@ -71,22 +93,130 @@ struct Cli {
 		display_order = 100,
 	)]
 	verbose: u8,
+
+	#[arg(
+		long,
+		global = true,
+		value_name = "COUNT",
+		help = "Number of threads [env: VERSATILES_THREADS]",
+		long_help = "Number of threads for the async runtime and for CPU-heavy work, like recompressing and transforming \
+			tiles. It also limits the number of tiles that are processed at the same time. \
+			Can also be set with the environment variable VERSATILES_THREADS. \
+			Defaults to the number of CPUs.",
+		display_order = 100
+	)]
+	threads: Option<usize>,
+
+	#[arg(
+		long,
+		global = true,
+		value_name = "COUNT",
+		help = "Number of concurrent reads per source [env: VERSATILES_IO_CONCURRENCY]",
+		long_help = "Number of reads that a single source runs at the same time, e.g. range requests to a remote \
+			container or connections to an MBTiles file. \
+			Can also be set with the environment variable VERSATILES_IO_CONCURRENCY. \
+			Defaults to 8.",
+		display_order = 100
+	)]
+	io_concurrency: Option<usize>,
+
+	#[arg(
+		long,
+		global = true,
+		value_name = "COUNT",
+		help = "Maximum number of images that are decoded or encoded at the same time",
+		long_help = "Maximum number of raster images that are decoded, processed or encoded at the same time, \
+			e.g. by the raster operations of VPL pipelines or by the static map API of the server. \
+			This work runs on separate threads, so it does not delay the handling of network requests. \
+			Defaults to the number of threads.",
+		display_order = 100
+	)]
+	image_threads: Option<usize>,
 }
 
 /// Define subcommands for the command-line interface
 #[derive(Subcommand, Debug)]
 enum Commands {
+	/// Apply a patch written by "versatiles diff --write-patch"
+	ApplyPatch(tools::apply_patch::Subcommand),
+
+	/// Bundle a MapLibre style with its sprites and glyphs
+	Bundle(tools::bundle::Subcommand),
+
+	/// Write or verify a manifest of tile checksums
+	Checksum(tools::checksum::Subcommand),
+
+	/// Merge the partial containers written by "versatiles convert --partition"
+	Concat(tools::concat::Subcommand),
+
 	#[clap(alias = "converter")]
 	/// Convert between different tile containers
 	Convert(tools::convert::Subcommand),
 
+	/// Tools for development and testing
+	Dev(tools::dev::Subcommand),
+
+	/// Compare the tiles of two containers
+	Diff(tools::diff::Subcommand),
+
+	/// Update the tiles listed in an osm2pgsql or imposm expiry file
+	Expire(tools::expire::Subcommand),
+
+	/// Export the features of a vector tile container, one GeoJSON or FlatGeobuf file per layer
+	ExportFeatures(tools::export_features::Subcommand),
+
+	/// Export a container as MBTiles, to a file or to stdout
+	ExportMbtiles(tools::export_mbtiles::Subcommand),
+
+	/// Convert fonts into MapLibre SDF glyphs
+	Glyphs(tools::glyphs::Subcommand),
+
+	/// Print a quick summary of tile containers, without reading any tiles
+	Info(tools::info::Subcommand),
+
+	/// Check the tiles of a vector tile container against rules
+	Lint(tools::lint::Subcommand),
+
+	/// List the coordinates of the existing tiles of a container
+	List(tools::list::Subcommand),
+
+	/// Export the outlines of the tiles or blocks of a container as GeoJSON
+	Outline(tools::outline::Subcommand),
+
 	/// Show information about a tile container
 	Probe(tools::probe::Subcommand),
 
+	/// Query the features of a vector tile container at a position
+	Query(tools::query::Subcommand),
+
+	/// Query the elevation at a position from terrain-RGB tiles
+	QueryElevation(tools::query_elevation::Subcommand),
+
+	/// Generate a tileset schema or validate a container against it
+	Schema(tools::schema::Subcommand),
+
+	/// Download tiles from an XYZ endpoint into a container
+	Scrape(tools::scrape::Subcommand),
+
+	/// Build a search index of the feature names of a vector tile container
+	SearchIndex(tools::search_index::Subcommand),
+
 	#[clap(alias = "server")]
 	/// Serve tiles via HTTP
 	Serve(tools::serve::Subcommand),
 
+	/// Sign a versatiles container with an Ed25519 key
+	Sign(tools::sign::Subcommand),
+
+	/// Pack SVG icons into MapLibre sprite sheets
+	Sprites(tools::sprites::Subcommand),
+
+	/// Upload only the changed tiles of a container to a directory or web server
+	Sync(tools::sync::Subcommand),
+
+	/// Verify the signature of a versatiles container
+	Verify(tools::verify::Subcommand),
+
 	/// Show detailed help
 	Help(tools::help::Subcommand),
 }
@@ -116,11 +246,53 @@ fn main() -> Result<()> {
 
 /// Helper function for running subcommands
 fn run(cli: Cli) -> Result<()> {
-	match &cli.command {
-		Commands::Convert(arguments) => tools::convert::run(arguments),
+	if let Some(threads) = cli.threads {
+		set_thread_count(threads)?;
+	}
+	if let Some(io_concurrency) = cli.io_concurrency {
+		set_io_concurrency(io_concurrency)?;
+	}
+	if let Some(image_threads) = cli.image_threads {
+		set_blocking_pool_size(image_threads)?;
+	}
+
+	// all subcommands run on one runtime, sized by the configured number of threads
+	let runtime = tokio::runtime::Builder::new_multi_thread()
+		.worker_threads(get_thread_count())
+		.enable_all()
+		.build()?;
+	runtime.block_on(run_subcommand(&cli.command))
+}
+
+async fn run_subcommand(command: &Commands) -> Result<()> {
+	match command {
+		Commands::ApplyPatch(arguments) => tools::apply_patch::run(arguments).await,
+		Commands::Bundle(arguments) => tools::bundle::run(arguments),
+		Commands::Checksum(arguments) => tools::checksum::run(arguments).await,
+		Commands::Concat(arguments) => tools::concat::run(arguments).await,
+		Commands::Convert(arguments) => tools::convert::run(arguments).await,
+		Commands::Dev(arguments) => tools::dev::run(arguments).await,
+		Commands::Diff(arguments) => tools::diff::run(arguments).await,
+		Commands::Expire(arguments) => tools::expire::run(arguments).await,
+		Commands::ExportFeatures(arguments) => tools::export_features::run(arguments).await,
+		Commands::ExportMbtiles(arguments) => tools::export_mbtiles::run(arguments).await,
+		Commands::Glyphs(arguments) => tools::glyphs::run(arguments),
 		Commands::Help(arguments) => tools::help::run(arguments),
-		Commands::Probe(arguments) => tools::probe::run(arguments),
-		Commands::Serve(arguments) => tools::serve::run(arguments),
+		Commands::Info(arguments) => tools::info::run(arguments).await,
+		Commands::Lint(arguments) => tools::lint::run(arguments).await,
+		Commands::List(arguments) => tools::list::run(arguments).await,
+		Commands::Outline(arguments) => tools::outline::run(arguments).await,
+		Commands::Probe(arguments) => tools::probe::run(arguments).await,
+		Commands::Query(arguments) => tools::query::run(arguments).await,
+		Commands::QueryElevation(arguments) => tools::query_elevation::run(arguments).await,
+		Commands::Schema(arguments) => tools::schema::run(arguments).await,
+		Commands::Scrape(arguments) => tools::scrape::run(arguments).await,
+		Commands::SearchIndex(arguments) => tools::search_index::run(arguments).await,
+		Commands::Serve(arguments) => tools::serve::run(arguments).await,
+		Commands::Sign(arguments) => tools::sign::run(arguments).await,
+		Commands::Sprites(arguments) => tools::sprites::run(arguments),
+		Commands::Sync(arguments) => tools::sync::run(arguments).await,
+		Commands::Verify(arguments) => tools::verify::run(arguments).await,
 	}
 }
 
@@ -146,6 +318,35 @@ mod tests {
 		assert!(err.starts_with("A toolbox for converting, checking and serving map tiles in various formats."));
 	}
 
+	/// Test that the image pool size is checked
+	#[test]
+	fn image_threads() {
+		let err = run_command(vec![
+			"versatiles",
+			"--image-threads",
+			"0",
+			"info",
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap_err()
+		.to_string();
+		assert_eq!(err, "the size of the blocking pool must be at least 1");
+	}
+
+	/// Test that the number of threads and the I/O concurrency are checked
+	#[test]
+	fn threads() {
+		for (option, message) in [
+			("--threads", "the number of threads must be at least 1"),
+			("--io-concurrency", "the I/O concurrency must be at least 1"),
+		] {
+			let err = run_command(vec!["versatiles", option, "0", "info", "../testdata/berlin.mbtiles"])
+				.unwrap_err()
+				.to_string();
+			assert_eq!(err, message);
+		}
+	}
+
 	/// Test for version
 	#[test]
 	fn version() {
@@ -153,6 +354,26 @@ mod tests {
 		assert!(err.starts_with("versatiles "));
 	}
 
+	/// Test for subcommand 'bundle'
+	#[test]
+	fn bundle_subcommand() {
+		let output = run_command(vec!["versatiles", "bundle"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Bundle a MapLibre style with its sprites and glyphs"),
+			"{output}"
+		);
+	}
+
+	/// Test for subcommand 'checksum'
+	#[test]
+	fn checksum_subcommand() {
+		let output = run_command(vec!["versatiles", "checksum"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Write or verify a manifest of tile checksums"),
+			"{output}"
+		);
+	}
+
 	/// Test for subcommand 'convert'
 	#[test]
 	fn convert_subcommand() {
@@ -163,6 +384,35 @@ mod tests {
 		);
 	}
 
+	/// Test for subcommand 'dev'
+	#[test]
+	fn dev_subcommand() {
+		let output = run_command(vec!["versatiles", "dev"]).unwrap_err().to_string();
+		assert!(output.starts_with("Tools for development and testing"), "{output}");
+	}
+
+	/// Test for subcommand 'expire'
+	#[test]
+	fn expire_subcommand() {
+		let output = run_command(vec!["versatiles", "expire"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Update the tiles listed in an osm2pgsql or imposm expiry file"),
+			"{output}"
+		);
+	}
+
+	/// Test for subcommand 'export-features'
+	#[test]
+	fn export_features_subcommand() {
+		let output = run_command(vec!["versatiles", "export-features"])
+			.unwrap_err()
+			.to_string();
+		assert!(
+			output.starts_with("Export the features of a vector tile container, one GeoJSON or FlatGeobuf file per layer"),
+			"{output}"
+		);
+	}
+
 	/// Test for subcommand 'probe'
 	#[test]
 	fn probe_subcommand() {
@@ -173,10 +423,79 @@ mod tests {
 		);
 	}
 
+	/// Test for subcommand 'glyphs'
+	#[test]
+	fn glyphs_subcommand() {
+		let output = run_command(vec!["versatiles", "glyphs"]).unwrap_err().to_string();
+		assert!(output.starts_with("Convert fonts into MapLibre SDF glyphs"), "{output}");
+	}
+
+	/// Test for subcommand 'query'
+	#[test]
+	fn query_subcommand() {
+		let output = run_command(vec!["versatiles", "query"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Query the features of a vector tile container at a position"),
+			"{output}"
+		);
+	}
+
+	/// Test for subcommand 'query-elevation'
+	#[test]
+	fn query_elevation_subcommand() {
+		let output = run_command(vec!["versatiles", "query-elevation"])
+			.unwrap_err()
+			.to_string();
+		assert!(
+			output.starts_with("Query the elevation at a position from terrain-RGB tiles"),
+			"{output}"
+		);
+	}
+
+	/// Test for subcommand 'scrape'
+	#[test]
+	fn scrape_subcommand() {
+		let output = run_command(vec!["versatiles", "scrape"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Download tiles from an XYZ endpoint into a container"),
+			"{output}"
+		);
+	}
+
+	/// Test for subcommand 'search-index'
+	#[test]
+	fn search_index_subcommand() {
+		let output = run_command(vec!["versatiles", "search-index"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Build a search index of the feature names of a vector tile container"),
+			"{output}"
+		);
+	}
+
 	/// Test for subcommand 'serve'
 	#[test]
 	fn serve_subcommand() {
 		let output = run_command(vec!["versatiles", "serve"]).unwrap_err().to_string();
 		assert!(output.starts_with("Serve tiles via HTTP"), "{output}");
 	}
+
+	/// Test for subcommand 'sprites'
+	#[test]
+	fn sprites_subcommand() {
+		let output = run_command(vec!["versatiles", "sprites"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Pack SVG icons into MapLibre sprite sheets"),
+			"{output}"
+		);
+	}
+
+	/// Test for subcommand 'sync'
+	#[test]
+	fn sync_subcommand() {
+		let output = run_command(vec!["versatiles", "sync"]).unwrap_err().to_string();
+		assert!(
+			output.starts_with("Upload only the changed tiles of a container"),
+			"{output}"
+		);
+	}
 }
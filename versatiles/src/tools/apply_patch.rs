@@ -0,0 +1,161 @@
+//! Applies a patch (`*.vpatch`), as written by `versatiles diff --write-patch`, to a `*.versatiles` container.
+//!
+//! The changed tiles of the patch are appended to the container and the removed tiles are dropped from its indexes.
+//! Without `--output`, the container is patched in place. Replaced tiles stay in the file as unused data, so convert
+//! the container from time to time to compact it.
+
+use anyhow::{ensure, Context, Result};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_container::{TempOutputFile, TilePatch, VersaTilesPatcher};
+use versatiles_core::{progress::get_progress_bar, utils::recompress};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// *.versatiles container to patch
+	#[arg(required = true)]
+	container: PathBuf,
+
+	/// patch file, as written by "versatiles diff --write-patch"
+	#[arg(required = true)]
+	patch: PathBuf,
+
+	/// write the patched container to this file instead of patching the container in place
+	#[arg(long, short, value_name = "FILE")]
+	output: Option<PathBuf>,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let patch = TilePatch::read_file(&arguments.patch)?;
+
+	match &arguments.output {
+		Some(output) => {
+			let output_file = TempOutputFile::new(output)?;
+			let temp_path = std::path::absolute(output_file.get_temp_path())?;
+			fs::copy(&arguments.container, &temp_path)
+				.with_context(|| format!("copying {:?} to {temp_path:?}", arguments.container))?;
+			apply_patch(&temp_path, patch).await?;
+			output_file.commit()?;
+		}
+		None => apply_patch(&std::path::absolute(&arguments.container)?, patch).await?,
+	}
+
+	Ok(())
+}
+
+async fn apply_patch(path: &Path, patch: TilePatch) -> Result<()> {
+	let mut patcher = VersaTilesPatcher::open_path(path)
+		.await
+		.with_context(|| format!("opening container {path:?}"))?;
+	ensure!(
+		&patch.tile_format == patcher.get_tile_format(),
+		"tile format of the patch ({:?}) does not match the container ({:?})",
+		patch.tile_format,
+		patcher.get_tile_format()
+	);
+	let compression = *patcher.get_tile_compression();
+
+	eprintln!("apply {} tiles", patch.tiles.len());
+	let mut progress = get_progress_bar("apply patch", patch.tiles.len() as u64);
+	for (coord, blob) in patch.tiles {
+		let blob = match blob {
+			Some(blob) => Some(recompress(blob, &patch.tile_compression, &compression)?),
+			None => None,
+		};
+		patcher.set_tile(&coord, blob).await?;
+		progress.inc(1);
+	}
+	progress.finish();
+
+	patcher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+	use versatiles_container::{get_reader, TilePatchWriter};
+	use versatiles_core::{
+		types::{Blob, TileCompression, TileCoord3, TileFormat},
+		utils::decompress,
+	};
+
+	/// Reads all tiles of a container, decompressed.
+	#[tokio::main]
+	async fn get_tiles(filename: &str) -> Result<Vec<(TileCoord3, Blob)>> {
+		let reader = get_reader(filename).await?;
+		let compression = reader.get_parameters().tile_compression;
+		let mut tiles = Vec::new();
+		for bbox in reader.get_parameters().bbox_pyramid.iter_levels() {
+			for (coord, blob) in reader.get_bbox_tile_stream(bbox.clone()).await.collect().await {
+				tiles.push((coord, decompress(blob, &compression)?));
+			}
+		}
+		tiles.sort_by_key(|(coord, _)| (coord.z, coord.y, coord.x));
+		Ok(tiles)
+	}
+
+	#[test]
+	fn apply_patch() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+		let (old, new, patch, patched) = (
+			path("old.versatiles"),
+			path("new.versatiles"),
+			path("update.vpatch"),
+			path("patched.versatiles"),
+		);
+
+		let berlin = "../testdata/berlin.mbtiles";
+		run_command(vec!["versatiles", "convert", "--max-zoom=4", berlin, &old])?;
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--min-zoom=2",
+			"--max-zoom=5",
+			berlin,
+			&new,
+		])?;
+
+		run_command(vec!["versatiles", "diff", "-q", &old, &new, "--write-patch", &patch])?;
+		run_command(vec!["versatiles", "apply-patch", &old, &patch, "-o", &patched])?;
+		assert_eq!(get_tiles(&patched)?, get_tiles(&new)?);
+		assert_ne!(get_tiles(&old)?, get_tiles(&new)?);
+
+		run_command(vec!["versatiles", "apply-patch", &old, &patch])?;
+		assert_eq!(get_tiles(&old)?, get_tiles(&new)?);
+		Ok(())
+	}
+
+	#[test]
+	fn format_mismatch() -> Result<()> {
+		let dir = TempDir::new()?;
+		let container = dir.path().join("tiles.versatiles");
+		let patch = dir.path().join("update.vpatch");
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--max-zoom=1",
+			"../testdata/berlin.mbtiles",
+			container.to_str().unwrap(),
+		])?;
+		TilePatchWriter::create(&patch, TileFormat::PNG, TileCompression::Uncompressed)?.finish()?;
+
+		let error = run_command(vec![
+			"versatiles",
+			"apply-patch",
+			container.to_str().unwrap(),
+			patch.to_str().unwrap(),
+		])
+		.unwrap_err();
+		assert!(
+			format!("{error:?}").contains("does not match the container"),
+			"{error:?}"
+		);
+		Ok(())
+	}
+}
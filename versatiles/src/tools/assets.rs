@@ -0,0 +1,116 @@
+//! Writes generated frontend assets, like sprites and glyphs, into a directory or a *.tar file.
+
+use anyhow::{Context, Result};
+use std::{
+	collections::BTreeSet,
+	fs::{self, File},
+	io::Read,
+	path::Path,
+};
+use versatiles_core::types::Blob;
+
+/// Writes files into a directory, or adds them to a *.tar file that can be served as static content.
+///
+/// # Arguments
+/// * `output` - A directory, or a filename ending in `.tar`.
+/// * `files` - Relative paths and contents of the files.
+pub fn write_assets(output: &Path, files: Vec<(String, Blob)>) -> Result<()> {
+	if output.extension().is_some_and(|e| e == "tar") {
+		write_to_tar(output, files)
+	} else {
+		write_to_directory(output, files)
+	}
+}
+
+fn write_to_directory(directory: &Path, files: Vec<(String, Blob)>) -> Result<()> {
+	for (name, blob) in files {
+		let path = directory.join(name);
+		fs::create_dir_all(path.parent().unwrap())?;
+		fs::write(&path, blob.as_slice()).with_context(|| format!("writing {path:?}"))?;
+	}
+	Ok(())
+}
+
+/// Adds files to a *.tar file. Existing entries are kept, unless they are replaced.
+fn write_to_tar(filename: &Path, files: Vec<(String, Blob)>) -> Result<()> {
+	let names: BTreeSet<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+
+	let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+	if filename.exists() {
+		let mut archive = tar::Archive::new(File::open(filename)?);
+		for entry in archive.entries()? {
+			let mut entry = entry?;
+			let path = entry.path()?.to_string_lossy().to_string();
+			if !entry.header().entry_type().is_file() || names.contains(path.as_str()) {
+				continue;
+			}
+			let mut data = Vec::new();
+			entry.read_to_end(&mut data)?;
+			entries.push((path, data));
+		}
+	}
+	entries.extend(files.into_iter().map(|(name, blob)| (name, blob.into_vec())));
+
+	let mut builder = tar::Builder::new(File::create(filename)?);
+	for (path, data) in entries {
+		let mut header = tar::Header::new_gnu();
+		header.set_size(data.len() as u64);
+		header.set_mode(0o644);
+		header.set_cksum();
+		builder
+			.append_data(&mut header, &path, data.as_slice())
+			.with_context(|| format!("adding {path:?} to {filename:?}"))?;
+	}
+	builder.finish()?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+
+	fn read_tar(filename: &Path) -> Result<Vec<(String, String)>> {
+		let mut archive = tar::Archive::new(File::open(filename)?);
+		let mut entries = Vec::new();
+		for entry in archive.entries()? {
+			let mut entry = entry?;
+			let mut data = String::new();
+			entry.read_to_string(&mut data)?;
+			entries.push((entry.path()?.to_string_lossy().to_string(), data));
+		}
+		Ok(entries)
+	}
+
+	#[test]
+	fn directory() -> Result<()> {
+		let dir = TempDir::new()?;
+		write_assets(dir.path(), vec![(String::from("a/b.json"), Blob::from("{}"))])?;
+		assert_eq!(fs::read_to_string(dir.path().join("a/b.json"))?, "{}");
+		Ok(())
+	}
+
+	#[test]
+	fn tar() -> Result<()> {
+		let dir = TempDir::new()?;
+		let filename = dir.path().join("assets.tar");
+
+		write_assets(
+			&filename,
+			vec![
+				(String::from("index.html"), Blob::from("<html>")),
+				(String::from("a.json"), Blob::from("1")),
+			],
+		)?;
+		write_assets(&filename, vec![(String::from("a.json"), Blob::from("2"))])?;
+
+		assert_eq!(
+			read_tar(&filename)?,
+			vec![
+				(String::from("index.html"), String::from("<html>")),
+				(String::from("a.json"), String::from("2")),
+			]
+		);
+		Ok(())
+	}
+}
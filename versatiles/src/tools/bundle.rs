@@ -0,0 +1,260 @@
+//! Bundles a MapLibre style with its sprites and glyphs into a frontend container.
+//!
+//! All local references of the style are copied and rewritten to server-relative URLs:
+//! - the style is written to `styles/{name}.json`,
+//! - sprites are written to `sprites/{name}/…`,
+//! - glyphs are written to `glyphs/…`,
+//! - tile sources are pointed to `/tiles/{source id}/…`, where `versatiles serve` serves them.
+//!
+//! Remote URLs (e.g. `https://…`) are kept. When the bundle is served, the server rewrites the
+//! server-relative URLs of the style into absolute URLs, as required by MapLibre.
+
+use super::{
+	assets::write_assets,
+	style::{is_style, rewrite_style_urls, StyleUrl},
+};
+use anyhow::{ensure, Context, Result};
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_core::{json::JsonValue, types::Blob};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+pub struct Subcommand {
+	/// MapLibre style JSON file
+	/// sprites, glyphs and tile sources with local paths are resolved relative to this file
+	#[arg(required = true, verbatim_doc_comment)]
+	style: PathBuf,
+
+	/// output directory, or a *.tar file to add the bundle to
+	#[arg(required = true)]
+	output: PathBuf,
+
+	/// directory inside the output, e.g. "assets"
+	#[arg(long)]
+	prefix: Option<String>,
+}
+
+pub fn run(arguments: &Subcommand) -> Result<()> {
+	let bundle = bundle_style(&arguments.style, arguments.prefix.as_deref().unwrap_or(""))?;
+	eprintln!("bundle {} files", bundle.files.len());
+
+	if !bundle.tile_sources.is_empty() {
+		let sources: Vec<String> = bundle
+			.tile_sources
+			.iter()
+			.map(|(id, path)| format!("\"[{id}]{}\"", path.display()))
+			.collect();
+		eprintln!("serve the tile sources with: versatiles serve {}", sources.join(" "));
+	}
+
+	write_assets(&arguments.output, bundle.files)
+}
+
+struct Bundle {
+	files: Vec<(String, Blob)>,
+	/// Tile sources referenced by the style: source id and path of the container.
+	tile_sources: BTreeMap<String, PathBuf>,
+}
+
+fn bundle_style(filename: &Path, prefix: &str) -> Result<Bundle> {
+	let base = filename.parent().unwrap_or(Path::new(""));
+	let name = filename
+		.file_stem()
+		.context("style must be a file")?
+		.to_string_lossy()
+		.to_string();
+
+	let data = fs::read(filename).with_context(|| format!("reading style {filename:?}"))?;
+	let mut style = JsonValue::parse_blob(&Blob::from(data))
+		.and_then(|json| json.to_object())
+		.with_context(|| format!("parsing style {filename:?}"))?;
+	ensure!(is_style(&style), "{filename:?} is not a MapLibre style");
+
+	let prefix = prefix.trim_matches('/');
+	let target = |path: &str| {
+		if prefix.is_empty() {
+			path.to_owned()
+		} else {
+			format!("{prefix}/{path}")
+		}
+	};
+
+	let mut files: Vec<(String, Blob)> = Vec::new();
+	let mut tile_sources = BTreeMap::new();
+
+	rewrite_style_urls(&mut style, |kind, url| {
+		if url.contains("://") {
+			return Ok(None);
+		}
+
+		Ok(Some(match kind {
+			StyleUrl::Sprite(id) => {
+				let source = base.join(url);
+				let stem = source
+					.file_name()
+					.with_context(|| format!("invalid sprite URL {url:?}"))?
+					.to_string_lossy()
+					.to_string();
+				let path = match id {
+					Some(id) => target(&format!("sprites/{name}/{id}/{stem}")),
+					None => target(&format!("sprites/{name}/{stem}")),
+				};
+
+				let count = files.len();
+				for ratio in ["", "@2x", "@3x"] {
+					for extension in ["json", "png"] {
+						let file = source.with_file_name(format!("{stem}{ratio}.{extension}"));
+						if file.exists() {
+							files.push((format!("{path}{ratio}.{extension}"), Blob::from(fs::read(file)?)));
+						}
+					}
+				}
+				ensure!(files.len() > count, "no sprite files found for {source:?}");
+				format!("/{path}")
+			}
+			StyleUrl::Glyphs => {
+				let index = url
+					.find("{fontstack}")
+					.with_context(|| format!("glyphs URL {url:?} must contain \"{{fontstack}}\""))?;
+				let (directory, template) = url.split_at(index);
+				ensure!(!directory.is_empty(), "glyphs URL {url:?} must start with a directory");
+				let path = target("glyphs");
+				add_directory(&base.join(directory), &path, &mut files)?;
+				format!("/{path}/{template}")
+			}
+			StyleUrl::Source(id) => {
+				tile_sources.insert(id.clone(), base.join(url));
+				format!("/tiles/{id}/tiles.json")
+			}
+			StyleUrl::Tiles(id) => {
+				// the container is everything before the first placeholder, e.g. "tiles/{z}/{x}/{y}.pbf"
+				let container = url.split('{').next().unwrap().trim_end_matches('/');
+				tile_sources.insert(id.clone(), base.join(container));
+				format!("/tiles/{id}/{{z}}/{{x}}/{{y}}")
+			}
+		}))
+	})?;
+
+	files.push((target(&format!("styles/{name}.json")), Blob::from(style.stringify())));
+
+	Ok(Bundle { files, tile_sources })
+}
+
+/// Adds all files of a directory recursively, sorted by path.
+fn add_directory(directory: &Path, path: &str, files: &mut Vec<(String, Blob)>) -> Result<()> {
+	let mut entries: Vec<PathBuf> = fs::read_dir(directory)
+		.with_context(|| format!("reading directory {directory:?}"))?
+		.map(|entry| entry.map(|e| e.path()))
+		.collect::<Result<_, _>>()?;
+	entries.sort();
+
+	for entry in entries {
+		let name = format!("{path}/{}", entry.file_name().unwrap().to_string_lossy());
+		if entry.is_dir() {
+			add_directory(&entry, &name, files)?;
+		} else {
+			files.push((name, Blob::from(fs::read(&entry)?)));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+
+	fn write_style(dir: &Path) -> Result<PathBuf> {
+		fs::create_dir_all(dir.join("sprites"))?;
+		for file in ["sprite.json", "sprite.png", "sprite@2x.json", "sprite@2x.png"] {
+			fs::write(dir.join("sprites").join(file), file)?;
+		}
+		fs::create_dir_all(dir.join("fonts/Noto Sans"))?;
+		fs::write(dir.join("fonts/Noto Sans/0-255.pbf"), "glyphs")?;
+
+		let filename = dir.join("colorful.json");
+		fs::write(
+			&filename,
+			r#"{"version":8,"layers":[],
+			"sprite":"sprites/sprite",
+			"glyphs":"fonts/{fontstack}/{range}.pbf",
+			"sources":{
+				"osm":{"type":"vector","url":"data/osm.versatiles"},
+				"dem":{"type":"raster","tiles":["data/dem/{z}/{x}/{y}.png"]},
+				"remote":{"type":"raster","tiles":["https://example.org/{z}/{x}/{y}.png"]}
+			}}"#,
+		)?;
+		Ok(filename)
+	}
+
+	#[test]
+	fn bundle() -> Result<()> {
+		let dir = TempDir::new()?;
+		let bundle = bundle_style(&write_style(dir.path())?, "/assets/")?;
+
+		let names: Vec<&str> = bundle.files.iter().map(|(name, _)| name.as_str()).collect();
+		assert_eq!(
+			names,
+			vec![
+				"assets/sprites/colorful/sprite.json",
+				"assets/sprites/colorful/sprite.png",
+				"assets/sprites/colorful/sprite@2x.json",
+				"assets/sprites/colorful/sprite@2x.png",
+				"assets/glyphs/Noto Sans/0-255.pbf",
+				"assets/styles/colorful.json",
+			]
+		);
+		assert_eq!(
+			bundle.files[5].1.as_str(),
+			"{\"glyphs\":\"/assets/glyphs/{fontstack}/{range}.pbf\",\"layers\":[],\
+			\"sources\":{\"dem\":{\"tiles\":[\"/tiles/dem/{z}/{x}/{y}\"],\"type\":\"raster\"},\
+			\"osm\":{\"type\":\"vector\",\"url\":\"/tiles/osm/tiles.json\"},\
+			\"remote\":{\"tiles\":[\"https://example.org/{z}/{x}/{y}.png\"],\"type\":\"raster\"}},\
+			\"sprite\":\"/assets/sprites/colorful/sprite\",\"version\":8}"
+		);
+		assert_eq!(
+			bundle.tile_sources,
+			BTreeMap::from([
+				(String::from("dem"), dir.path().join("data/dem")),
+				(String::from("osm"), dir.path().join("data/osm.versatiles")),
+			])
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn missing_sprite() -> Result<()> {
+		let dir = TempDir::new()?;
+		let filename = dir.path().join("style.json");
+		fs::write(&filename, r#"{"version":8,"layers":[],"sources":{},"sprite":"sprite"}"#)?;
+		let error = bundle_style(&filename, "").err().unwrap().to_string();
+		assert!(error.starts_with("no sprite files found"), "{error}");
+
+		fs::write(&filename, r#"{"version":8}"#)?;
+		let error = bundle_style(&filename, "").err().unwrap().to_string();
+		assert!(error.ends_with("is not a MapLibre style"), "{error}");
+		Ok(())
+	}
+
+	#[test]
+	fn command() -> Result<()> {
+		let dir = TempDir::new()?;
+		let style = write_style(dir.path())?;
+		let output = dir.path().join("frontend");
+		run_command(vec![
+			"versatiles",
+			"bundle",
+			style.to_str().unwrap(),
+			output.to_str().unwrap(),
+		])?;
+		assert!(output.join("styles/colorful.json").exists());
+		assert!(output.join("sprites/colorful/sprite@2x.png").exists());
+		assert!(output.join("glyphs/Noto Sans/0-255.pbf").exists());
+		Ok(())
+	}
+}
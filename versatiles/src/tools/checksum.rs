@@ -0,0 +1,448 @@
+use anyhow::{bail, ensure, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+	fs::File,
+	io::{stdout, BufRead, BufReader, BufWriter, Cursor, Lines, Write},
+	path::{Path, PathBuf},
+};
+use versatiles_container::get_reader;
+use versatiles_core::{
+	json::JsonObject,
+	progress::get_progress_bar,
+	types::{Blob, TileCoord3, TilesReaderTrait},
+};
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Size of the blocks in which tiles are sorted. Tiles are ordered by zoom level, then by block, then by row and column.
+const BLOCK_SIZE: u32 = 256;
+
+/// Maximum number of differences that are listed individually during verification.
+const MAX_LISTED_DIFFERENCES: u64 = 10;
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile container you want to checksum
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// write the manifest to this file instead of stdout
+	#[arg(long, short, value_name = "MANIFEST", conflicts_with = "verify")]
+	output: Option<PathBuf>,
+
+	/// verify the container against an existing manifest
+	#[arg(long, value_name = "MANIFEST")]
+	verify: Option<PathBuf>,
+
+	/// hash algorithm
+	#[arg(long, value_enum, default_value_t = HashAlgorithm::Xxh3)]
+	algorithm: HashAlgorithm,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub(super) enum HashAlgorithm {
+	/// 128 bit xxHash3, fast
+	Xxh3,
+	/// SHA-256, cryptographic
+	Sha256,
+}
+
+impl HashAlgorithm {
+	fn as_str(&self) -> &str {
+		match self {
+			HashAlgorithm::Xxh3 => "xxh3",
+			HashAlgorithm::Sha256 => "sha256",
+		}
+	}
+
+	pub(super) fn parse_str(value: &str) -> Result<Self> {
+		Ok(match value {
+			"xxh3" => HashAlgorithm::Xxh3,
+			"sha256" => HashAlgorithm::Sha256,
+			_ => bail!("unknown hash algorithm '{value}'"),
+		})
+	}
+
+	fn hash(&self, blob: &Blob) -> String {
+		match self {
+			HashAlgorithm::Xxh3 => format!("{:032x}", xxh3_128(blob.as_slice())),
+			HashAlgorithm::Sha256 => Sha256::digest(blob.as_slice())
+				.iter()
+				.map(|byte| format!("{byte:02x}"))
+				.collect(),
+		}
+	}
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let reader = get_reader(&arguments.filename).await?;
+
+	if let Some(manifest) = &arguments.verify {
+		eprintln!("verify {:?} against {manifest:?}", arguments.filename);
+		verify_manifest(&*reader, manifest).await
+	} else if let Some(output) = &arguments.output {
+		eprintln!("write checksums of {:?} to {output:?}", arguments.filename);
+		let mut file = BufWriter::new(File::create(output)?);
+		write_manifest(&*reader, arguments.algorithm, &mut file).await?;
+		file.flush()?;
+		Ok(())
+	} else {
+		write_manifest(&*reader, arguments.algorithm, &mut stdout().lock()).await
+	}
+}
+
+/// Returns the key by which the tiles are ordered in a manifest.
+pub(super) fn get_order_key(coord: &TileCoord3) -> (u8, u32, u32, u32, u32) {
+	(coord.z, coord.y / BLOCK_SIZE, coord.x / BLOCK_SIZE, coord.y, coord.x)
+}
+
+/// Builds the header line of a manifest.
+pub(super) fn get_header(reader: &dyn TilesReaderTrait, algorithm: HashAlgorithm) -> JsonObject {
+	let parameters = reader.get_parameters();
+	let mut header = JsonObject::default();
+	header.set("algorithm", algorithm.as_str());
+	header.set("tile_format", parameters.tile_format.as_str());
+	header.set("tile_compression", parameters.tile_compression.as_str());
+	header
+}
+
+/// Streams the hashes of all tiles in a deterministic order: by zoom level, then in blocks of 256x256 tiles.
+///
+/// Only one block is held in memory at a time, so the order does not depend on the container and
+/// arbitrarily large containers can be processed.
+pub(super) async fn for_each_tile_hash<F>(
+	reader: &dyn TilesReaderTrait,
+	algorithm: HashAlgorithm,
+	mut callback: F,
+) -> Result<()>
+where
+	F: FnMut(TileCoord3, String) -> Result<()>,
+{
+	let pyramid = reader.get_parameters().bbox_pyramid.clone();
+	let mut progress = get_progress_bar("hashing tiles", pyramid.count_tiles());
+
+	for level_bbox in pyramid.iter_levels() {
+		for bbox in level_bbox.iter_bbox_grid(BLOCK_SIZE) {
+			let tile_count = bbox.count_tiles();
+			let mut hashes: Vec<(TileCoord3, String)> = Vec::new();
+			reader
+				.get_bbox_tile_stream(bbox)
+				.await
+				.for_each_sync(|(coord, blob)| hashes.push((coord, algorithm.hash(&blob))))
+				.await;
+			hashes.sort_unstable_by_key(|(coord, _)| get_order_key(coord));
+
+			for (coord, hash) in hashes {
+				callback(coord, hash)?;
+			}
+			progress.inc(tile_count);
+		}
+	}
+
+	progress.finish();
+	Ok(())
+}
+
+/// Writes a manifest as newline-delimited JSON: a header line, followed by one line per tile.
+async fn write_manifest(
+	reader: &dyn TilesReaderTrait,
+	algorithm: HashAlgorithm,
+	output: &mut impl Write,
+) -> Result<()> {
+	writeln!(output, "{}", get_header(reader, algorithm).stringify())?;
+
+	for_each_tile_hash(reader, algorithm, |coord, hash| {
+		write_manifest_line(output, &coord, &hash)
+	})
+	.await
+}
+
+/// Writes the line of a tile in a manifest.
+pub(super) fn write_manifest_line(output: &mut impl Write, coord: &TileCoord3, hash: &str) -> Result<()> {
+	writeln!(
+		output,
+		"{{\"z\":{},\"x\":{},\"y\":{},\"hash\":\"{hash}\"}}",
+		coord.z, coord.x, coord.y
+	)?;
+	Ok(())
+}
+
+/// Reads the tile lines of a manifest one by one.
+pub(super) struct ManifestReader {
+	lines: Lines<Box<dyn BufRead>>,
+	line_number: usize,
+}
+
+impl ManifestReader {
+	/// Opens a manifest and parses its header.
+	pub(super) fn open(path: &Path) -> Result<(ManifestReader, JsonObject)> {
+		let file = File::open(path).with_context(|| format!("opening manifest {path:?}"))?;
+		ManifestReader::from_reader(Box::new(BufReader::new(file)))
+	}
+
+	/// Reads a manifest from memory and parses its header.
+	pub(super) fn from_blob(blob: Blob) -> Result<(ManifestReader, JsonObject)> {
+		ManifestReader::from_reader(Box::new(Cursor::new(blob.into_vec())))
+	}
+
+	fn from_reader(reader: Box<dyn BufRead>) -> Result<(ManifestReader, JsonObject)> {
+		let mut reader = ManifestReader {
+			lines: reader.lines(),
+			line_number: 0,
+		};
+		let header = reader.next_object()?.context("manifest is empty")?;
+		Ok((reader, header))
+	}
+
+	fn next_object(&mut self) -> Result<Option<JsonObject>> {
+		for line in self.lines.by_ref() {
+			self.line_number += 1;
+			let line = line?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			let object =
+				JsonObject::parse_str(&line).with_context(|| format!("parsing line {} of manifest", self.line_number))?;
+			return Ok(Some(object));
+		}
+		Ok(None)
+	}
+
+	/// Returns the next tile coordinate and hash.
+	pub(super) fn next_tile(&mut self) -> Result<Option<(TileCoord3, String)>> {
+		let Some(object) = self.next_object()? else {
+			return Ok(None);
+		};
+		let line_number = self.line_number;
+		let get_number = |key: &str| -> Result<u32> {
+			object
+				.get_number::<u32>(key)?
+				.with_context(|| format!("line {line_number} of manifest has no '{key}'"))
+		};
+		let coord = TileCoord3::new(get_number("x")?, get_number("y")?, get_number("z")? as u8)?;
+		let hash = object
+			.get_string("hash")?
+			.with_context(|| format!("line {line_number} of manifest has no 'hash'"))?;
+		Ok(Some((coord, hash)))
+	}
+}
+
+/// A tile that differs between container and manifest.
+#[derive(Debug)]
+enum Difference {
+	/// The hash of the tile differs.
+	Changed,
+	/// The tile is in the manifest, but not in the container.
+	Missing,
+	/// The tile is in the container, but not in the manifest.
+	Unexpected,
+}
+
+/// Counts and lists the differences found during verification.
+#[derive(Debug, Default)]
+struct Differences {
+	changed: u64,
+	missing: u64,
+	unexpected: u64,
+}
+
+impl Differences {
+	fn total(&self) -> u64 {
+		self.changed + self.missing + self.unexpected
+	}
+
+	fn report(&mut self, kind: Difference, coord: &TileCoord3) {
+		if self.total() < MAX_LISTED_DIFFERENCES {
+			eprintln!("{kind:?} tile {}/{}/{}", coord.z, coord.x, coord.y);
+		}
+		match kind {
+			Difference::Changed => self.changed += 1,
+			Difference::Missing => self.missing += 1,
+			Difference::Unexpected => self.unexpected += 1,
+		}
+	}
+}
+
+/// Verifies a container against a manifest.
+///
+/// Since the manifest is sorted in the same order in which the tiles are hashed, both are compared in a single pass
+/// without loading the manifest into memory.
+///
+/// # Errors
+/// Returns an error if the manifest can not be read, or if any tile differs from the manifest.
+async fn verify_manifest(reader: &dyn TilesReaderTrait, manifest: &Path) -> Result<()> {
+	let (mut manifest, header) = ManifestReader::open(manifest)?;
+
+	let algorithm = HashAlgorithm::parse_str(&header.get_string("algorithm")?.context("manifest has no algorithm")?)?;
+	let expected_header = get_header(reader, algorithm);
+	ensure!(
+		header == expected_header,
+		"manifest header {} does not match container {}",
+		header.stringify(),
+		expected_header.stringify()
+	);
+
+	let mut differences = Differences::default();
+	let mut tile_count: u64 = 0;
+	let mut expected = manifest.next_tile()?;
+
+	for_each_tile_hash(reader, algorithm, |coord, hash| {
+		tile_count += 1;
+		let key = get_order_key(&coord);
+
+		// Skip all manifest entries that come before this tile: they are missing in the container.
+		while let Some((expected_coord, _)) = &expected {
+			if get_order_key(expected_coord) >= key {
+				break;
+			}
+			differences.report(Difference::Missing, expected_coord);
+			expected = manifest.next_tile()?;
+		}
+
+		match &expected {
+			Some((expected_coord, expected_hash)) if expected_coord == &coord => {
+				if expected_hash != &hash {
+					differences.report(Difference::Changed, &coord);
+				}
+				expected = manifest.next_tile()?;
+			}
+			_ => differences.report(Difference::Unexpected, &coord),
+		}
+		Ok(())
+	})
+	.await?;
+
+	while let Some((expected_coord, _)) = &expected {
+		differences.report(Difference::Missing, expected_coord);
+		expected = manifest.next_tile()?;
+	}
+
+	ensure!(
+		differences.total() == 0,
+		"verification failed: {} tiles changed, {} missing, {} unexpected",
+		differences.changed,
+		differences.missing,
+		differences.unexpected
+	);
+
+	eprintln!("verified {tile_count} tiles");
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::NamedTempFile;
+	use std::fs;
+	use versatiles_container::{MockTilesReader, MockTilesReaderProfile, MOCK_BYTES_PNG};
+
+	#[test]
+	fn hash() {
+		let blob = Blob::from("hello");
+		assert_eq!(HashAlgorithm::Xxh3.hash(&blob).len(), 32);
+		assert_eq!(
+			HashAlgorithm::Sha256.hash(&blob),
+			"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+		);
+	}
+
+	#[test]
+	fn order_key() -> Result<()> {
+		let mut coords = vec![
+			TileCoord3::new(300, 0, 10)?,
+			TileCoord3::new(0, 1, 10)?,
+			TileCoord3::new(1, 0, 10)?,
+			TileCoord3::new(0, 0, 11)?,
+		];
+		coords.sort_by_key(get_order_key);
+		assert_eq!(
+			coords,
+			vec![
+				TileCoord3::new(1, 0, 10)?,
+				TileCoord3::new(0, 1, 10)?,
+				TileCoord3::new(300, 0, 10)?,
+				TileCoord3::new(0, 0, 11)?,
+			]
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_hashes() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let mut coords = Vec::new();
+		for_each_tile_hash(&reader, HashAlgorithm::Xxh3, |coord, hash| {
+			assert_eq!(hash, HashAlgorithm::Xxh3.hash(&Blob::from(MOCK_BYTES_PNG.to_vec())));
+			coords.push(coord);
+			Ok(())
+		})
+		.await?;
+
+		assert_eq!(coords.len() as u64, reader.get_parameters().bbox_pyramid.count_tiles());
+		assert!(coords.is_sorted_by_key(get_order_key));
+		assert_eq!(coords[0], TileCoord3::new(0, 1, 2)?);
+		Ok(())
+	}
+
+	#[test]
+	fn write_and_verify() -> Result<()> {
+		let manifest = NamedTempFile::new("manifest.json")?;
+		let manifest_path = manifest.path().to_str().unwrap();
+
+		run_command(vec![
+			"versatiles",
+			"checksum",
+			"-q",
+			"--algorithm",
+			"sha256",
+			"--output",
+			manifest_path,
+			"../testdata/berlin.mbtiles",
+		])?;
+
+		let content = fs::read_to_string(manifest.path())?;
+		let mut lines = content.lines();
+		assert_eq!(
+			lines.next().unwrap(),
+			"{\"algorithm\":\"sha256\",\"tile_compression\":\"gzip\",\"tile_format\":\"pbf\"}"
+		);
+		assert!(lines
+			.next()
+			.unwrap()
+			.starts_with("{\"z\":0,\"x\":0,\"y\":0,\"hash\":\""));
+
+		run_command(vec![
+			"versatiles",
+			"checksum",
+			"-q",
+			"--verify",
+			manifest_path,
+			"../testdata/berlin.mbtiles",
+		])?;
+
+		// Modify one hash and drop the last tile
+		let mut lines: Vec<&str> = content.lines().collect();
+		lines.pop();
+		let changed = lines[1].replace("\"hash\":\"", "\"hash\":\"00");
+		lines[1] = &changed;
+		fs::write(manifest.path(), lines.join("\n"))?;
+
+		let error = run_command(vec![
+			"versatiles",
+			"checksum",
+			"-q",
+			"--verify",
+			manifest_path,
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"verification failed: 1 tiles changed, 0 missing, 1 unexpected"
+		);
+
+		Ok(())
+	}
+}
@@ -1,17 +1,264 @@
-use crate::container::get_reader;
+//! Compares the metadata and parameters of two tile containers.
+//!
+//! Every compared property becomes a row of a side-by-side table. Differences that prevent merging the two
+//! containers, or replacing one with the other, are flagged as incompatible: a different tile format, tile grid,
+//! maximum zoom level or list of vector layers.
 
-#[derive(clap::Args)]
-#[command(arg_required_else_help = true, disable_version_flag = true)]
-pub struct Subcommand {
-	file1: String,
-	file2: String,
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::Write,
+};
+use versatiles_core::{
+	json::JsonValue,
+	tilejson::TileJSON,
+	types::{GeoBBox, TileBBox, TilesReaderTrait},
+};
+
+/// Values longer than this are shortened in the table.
+const MAX_VALUE_LENGTH: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+	Equal,
+	Different,
+	Incompatible,
+}
+
+#[derive(Debug)]
+pub struct Row {
+	pub key: String,
+	pub left: Option<String>,
+	pub right: Option<String>,
+	pub status: Status,
+}
+
+#[derive(Debug, Default)]
+pub struct Comparison {
+	pub rows: Vec<Row>,
+}
+
+impl Comparison {
+	pub fn new(left: &dyn TilesReaderTrait, right: &dyn TilesReaderTrait) -> Comparison {
+		let mut comparison = Comparison::default();
+
+		comparison.add(
+			"container",
+			Some(left.get_container_name()),
+			Some(right.get_container_name()),
+			false,
+		);
+
+		let (p1, p2) = (left.get_parameters(), right.get_parameters());
+		comparison.add("tile_format", Some(p1.tile_format), Some(p2.tile_format), true);
+		comparison.add(
+			"tile_compression",
+			Some(p1.tile_compression),
+			Some(p2.tile_compression),
+			false,
+		);
+
+		let (pyramid1, pyramid2) = (&p1.bbox_pyramid, &p2.bbox_pyramid);
+		comparison.add("tile_grid", Some(pyramid1.get_grid()), Some(pyramid2.get_grid()), true);
+		comparison.add("zoom_min", pyramid1.get_zoom_min(), pyramid2.get_zoom_min(), false);
+		comparison.add("zoom_max", pyramid1.get_zoom_max(), pyramid2.get_zoom_max(), true);
+		for level in 0..=31 {
+			let bbox1 = pyramid1.get_level_bbox(level);
+			let bbox2 = pyramid2.get_level_bbox(level);
+			if bbox1.is_empty() && bbox2.is_empty() {
+				continue;
+			}
+			comparison.add(&format!("level {level}"), format_bbox(bbox1), format_bbox(bbox2), false);
+		}
+
+		comparison.add_tilejson(left.get_tilejson(), right.get_tilejson());
+
+		comparison
+	}
+
+	fn add_tilejson(&mut self, tilejson1: &TileJSON, tilejson2: &TileJSON) {
+		self.add(
+			"bounds",
+			tilejson1.bounds.as_ref().map(GeoBBox::as_string_list),
+			tilejson2.bounds.as_ref().map(GeoBBox::as_string_list),
+			false,
+		);
+		self.add(
+			"center",
+			tilejson1.center.map(|c| format!("{c:?}")),
+			tilejson2.center.map(|c| format!("{c:?}")),
+			false,
+		);
+
+		let values1: BTreeMap<String, JsonValue> = tilejson1.values.iter_json_values().collect();
+		let values2: BTreeMap<String, JsonValue> = tilejson2.values.iter_json_values().collect();
+		let keys: BTreeSet<&String> = values1.keys().chain(values2.keys()).collect();
+		for key in keys {
+			self.add(
+				key,
+				values1.get(key).map(JsonValue::stringify),
+				values2.get(key).map(JsonValue::stringify),
+				key == "maxzoom",
+			);
+		}
+
+		let layers1 = &tilejson1.vector_layers.0;
+		let layers2 = &tilejson2.vector_layers.0;
+		if layers1.is_empty() && layers2.is_empty() {
+			return;
+		}
+		self.add(
+			"vector_layers",
+			Some(join_keys(layers1)),
+			Some(join_keys(layers2)),
+			true,
+		);
+		for (id, layer1) in layers1 {
+			let Some(layer2) = layers2.get(id) else {
+				continue;
+			};
+			self.add(
+				&format!("vector_layers.{id}.fields"),
+				Some(join_keys(&layer1.fields)),
+				Some(join_keys(&layer2.fields)),
+				false,
+			);
+			self.add(
+				&format!("vector_layers.{id}.minzoom"),
+				layer1.minzoom,
+				layer2.minzoom,
+				false,
+			);
+			self.add(
+				&format!("vector_layers.{id}.maxzoom"),
+				layer1.maxzoom,
+				layer2.maxzoom,
+				false,
+			);
+		}
+	}
+
+	fn add<T: ToString>(&mut self, key: &str, left: Option<T>, right: Option<T>, incompatible: bool) {
+		let left = left.map(|v| v.to_string());
+		let right = right.map(|v| v.to_string());
+		let status = if left == right {
+			Status::Equal
+		} else if incompatible {
+			Status::Incompatible
+		} else {
+			Status::Different
+		};
+		self.rows.push(Row {
+			key: key.to_string(),
+			left,
+			right,
+			status,
+		});
+	}
+
+	/// Number of rows with the given status.
+	pub fn count(&self, status: Status) -> usize {
+		self.rows.iter().filter(|row| row.status == status).count()
+	}
+
+	/// Renders the comparison as a table. Changed rows are marked with `~`, incompatible rows with `!`.
+	pub fn as_table(&self, name1: &str, name2: &str) -> String {
+		fn shorten(value: &Option<String>) -> String {
+			let value = value.as_deref().unwrap_or("-");
+			if value.chars().count() > MAX_VALUE_LENGTH {
+				format!("{}…", value.chars().take(MAX_VALUE_LENGTH - 1).collect::<String>())
+			} else {
+				value.to_string()
+			}
+		}
+
+		let rows: Vec<(char, &str, String, String)> = self
+			.rows
+			.iter()
+			.map(|row| {
+				let marker = match row.status {
+					Status::Equal => ' ',
+					Status::Different => '~',
+					Status::Incompatible => '!',
+				};
+				(marker, row.key.as_str(), shorten(&row.left), shorten(&row.right))
+			})
+			.collect();
+
+		let w_key = rows.iter().map(|r| r.1.chars().count()).max().unwrap_or(0);
+		let w_left = rows
+			.iter()
+			.map(|r| r.2.chars().count())
+			.chain([name1.chars().count()])
+			.max()
+			.unwrap_or(0);
+
+		let mut table = String::new();
+		writeln!(table, "  {:w_key$} | {:w_left$} | {name2}", "", name1).unwrap();
+		for (marker, key, left, right) in rows {
+			writeln!(table, "{marker} {key:w_key$} | {left:w_left$} | {right}").unwrap();
+		}
+		writeln!(
+			table,
+			"{} different, {} incompatible",
+			self.count(Status::Different),
+			self.count(Status::Incompatible)
+		)
+		.unwrap();
+		table
+	}
 }
 
-#[tokio::main]
-pub async fn run(arguments: &Subcommand) -> Result<()> {
-	eprintln!("compare {:?} with {:?}", arguments.file1, arguments.file2);
+fn format_bbox(bbox: &TileBBox) -> Option<String> {
+	(!bbox.is_empty()).then(|| {
+		format!(
+			"[{},{},{},{}] ({})",
+			bbox.x_min,
+			bbox.y_min,
+			bbox.x_max,
+			bbox.y_max,
+			bbox.count_tiles()
+		)
+	})
+}
+
+fn join_keys<T>(map: &BTreeMap<String, T>) -> String {
+	map.keys().cloned().collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Result;
+	use versatiles_container::{MockTilesReader, MockTilesReaderProfile};
+
+	fn get_row<'a>(comparison: &'a Comparison, key: &str) -> &'a Row {
+		comparison.rows.iter().find(|row| row.key == key).unwrap()
+	}
+
+	#[test]
+	fn equal() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let comparison = Comparison::new(&reader, &reader);
+		assert_eq!(comparison.count(Status::Different), 0);
+		assert_eq!(comparison.count(Status::Incompatible), 0);
+		assert!(comparison.as_table("a", "b").ends_with("0 different, 0 incompatible\n"));
+		Ok(())
+	}
+
+	#[test]
+	fn incompatible() -> Result<()> {
+		let reader1 = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let reader2 = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let comparison = Comparison::new(&reader1, &reader2);
+
+		let row = get_row(&comparison, "tile_format");
+		assert_eq!(row.status, Status::Incompatible);
+		assert_eq!(row.left.as_deref(), Some("pbf"));
+		assert_eq!(row.right.as_deref(), Some("png"));
+		assert_eq!(get_row(&comparison, "container").status, Status::Equal);
 
-	let _reader1 = get_reader(&arguments.file1);
-	let _reader2 = get_reader(&arguments.file2);
-	todo!()
+		let table = comparison.as_table("a.versatiles", "b.versatiles");
+		assert!(table.contains("! tile_format"));
+		Ok(())
+	}
 }
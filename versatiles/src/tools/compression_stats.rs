@@ -0,0 +1,182 @@
+//! Measures how well the tiles of a container are compressed.
+//!
+//! A sample of tiles is read from every zoom level and decompressed, to compare the compressed with the
+//! uncompressed sizes. Tiles whose content is still compressed after decompressing, e.g. gzip compressed PNG
+//! images or tiles that were gzipped twice, are flagged as double compressed: compressing them again only costs
+//! time when reading, without saving any space.
+
+use anyhow::Result;
+use std::{collections::BTreeMap, fmt::Write};
+use versatiles_container::TileSampler;
+use versatiles_core::{
+	types::{Blob, TileCompression, TileCoord3, TileFormat, TilesReaderTrait},
+	utils::{decompress, guess_compression, guess_format},
+};
+
+/// Maximum number of sampled tiles per zoom level.
+const SAMPLES_PER_LEVEL: u64 = 64;
+
+/// Maximum number of coordinates looked up per zoom level, to limit the effort for sparse levels.
+const LOOKUPS_PER_LEVEL: u64 = 1024;
+
+/// Number of double compressed tiles that are listed.
+const MAX_LISTED_TILES: usize = 10;
+
+#[derive(Debug, Default)]
+struct LevelStats {
+	samples: u64,
+	compressed_bytes: u64,
+	uncompressed_bytes: u64,
+	double_compressed: u64,
+	errors: u64,
+}
+
+#[derive(Debug)]
+pub struct CompressionStats {
+	compression: TileCompression,
+	levels: BTreeMap<u8, LevelStats>,
+	/// Double compressed tiles, with a description of their content.
+	double_compressed: Vec<(TileCoord3, String)>,
+}
+
+impl CompressionStats {
+	pub fn new(compression: TileCompression) -> CompressionStats {
+		CompressionStats {
+			compression,
+			levels: BTreeMap::new(),
+			double_compressed: Vec::new(),
+		}
+	}
+
+	pub async fn from_reader(reader: &dyn TilesReaderTrait) -> Result<CompressionStats> {
+		let parameters = reader.get_parameters();
+		let mut stats = CompressionStats::new(parameters.tile_compression);
+
+		let sampler = TileSampler::new(SAMPLES_PER_LEVEL, LOOKUPS_PER_LEVEL);
+		for bbox in parameters.bbox_pyramid.iter_levels() {
+			for (coord, blob) in sampler.sample_level(reader, bbox).await? {
+				stats.add(&coord, &blob);
+			}
+		}
+
+		Ok(stats)
+	}
+
+	pub fn add(&mut self, coord: &TileCoord3, blob: &Blob) {
+		let level = self.levels.entry(coord.z).or_default();
+		level.samples += 1;
+
+		let content = match decompress(blob.clone(), &self.compression) {
+			Ok(content) => content,
+			Err(_) => {
+				level.errors += 1;
+				return;
+			}
+		};
+		level.compressed_bytes += blob.len();
+		level.uncompressed_bytes += content.len();
+
+		if self.compression == TileCompression::Uncompressed {
+			return;
+		}
+		let description = if guess_compression(&content).is_some() {
+			Some(String::from("gzip compressed data"))
+		} else {
+			match guess_format(&content) {
+				Some(format @ (TileFormat::AVIF | TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP)) => {
+					Some(format!("{format} image"))
+				}
+				_ => None,
+			}
+		};
+		if let Some(description) = description {
+			level.double_compressed += 1;
+			self.double_compressed.push((*coord, description));
+		}
+	}
+
+	pub fn count_double_compressed(&self) -> u64 {
+		self.levels.values().map(|level| level.double_compressed).sum()
+	}
+
+	pub fn as_report(&self) -> String {
+		let mut report = String::new();
+
+		writeln!(
+			report,
+			"zoom | samples |   compressed | uncompressed | ratio | double compressed | errors"
+		)
+		.unwrap();
+		for (z, level) in self.levels.iter() {
+			writeln!(
+				report,
+				"{z:4} | {:7} | {:12} | {:12} | {:5.3} | {:17} | {:6}",
+				level.samples,
+				level.compressed_bytes,
+				level.uncompressed_bytes,
+				ratio(level.compressed_bytes, level.uncompressed_bytes),
+				level.double_compressed,
+				level.errors
+			)
+			.unwrap();
+		}
+
+		let double_compressed = self.count_double_compressed();
+		if double_compressed > 0 {
+			writeln!(
+				report,
+				"{double_compressed} sampled tiles are stored with {} compression, but their content is already compressed:",
+				self.compression
+			)
+			.unwrap();
+			for (coord, description) in self.double_compressed.iter().take(MAX_LISTED_TILES) {
+				writeln!(report, "  {}/{}/{}: {description}", coord.z, coord.x, coord.y).unwrap();
+			}
+		}
+
+		report
+	}
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+	if total == 0 {
+		0.0
+	} else {
+		part as f64 / total as f64
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_core::utils::compress_gzip;
+
+	#[test]
+	fn stats() -> Result<()> {
+		let mut stats = CompressionStats::new(TileCompression::Gzip);
+		let text = Blob::from("a".repeat(1000));
+		stats.add(&TileCoord3::new(0, 0, 1)?, &compress_gzip(&text)?);
+		stats.add(&TileCoord3::new(1, 0, 1)?, &compress_gzip(&compress_gzip(&text)?)?);
+		stats.add(&TileCoord3::new(0, 1, 1)?, &Blob::from("not gzip"));
+		assert_eq!(stats.count_double_compressed(), 1);
+
+		let report = stats.as_report();
+		assert!(report.contains("\n   1 |       3 |"), "{report}");
+		assert!(report.contains("|                 1 |      1\n"), "{report}");
+		assert!(
+			report.contains("1 sampled tiles are stored with gzip compression"),
+			"{report}"
+		);
+		assert!(report.ends_with("  1/1/0: gzip compressed data\n"), "{report}");
+		Ok(())
+	}
+
+	#[test]
+	fn uncompressed() -> Result<()> {
+		let mut stats = CompressionStats::new(TileCompression::Uncompressed);
+		stats.add(&TileCoord3::new(0, 0, 0)?, &compress_gzip(&Blob::from("tile"))?);
+		assert_eq!(stats.count_double_compressed(), 0);
+		assert!(stats.as_report().contains("| 1.000 |"));
+		Ok(())
+	}
+}
@@ -0,0 +1,103 @@
+//! Merges the partial containers written by `versatiles convert --partition` into one container.
+
+use anyhow::{ensure, Result};
+use std::path::PathBuf;
+use versatiles_container::{get_reader, ConcatReader, ConversionPipeline, ConversionTarget};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// partial containers, as written by "versatiles convert --partition"
+	#[arg(required = true)]
+	input_files: Vec<String>,
+
+	/// container to write
+	#[arg(long, short, value_name = "FILE", required = true)]
+	output: PathBuf,
+
+	/// replace the output file if it already exists
+	#[arg(long)]
+	overwrite: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	ensure!(
+		arguments.overwrite || !arguments.output.is_file(),
+		"output file {:?} already exists, use --overwrite to replace it",
+		arguments.output
+	);
+
+	let mut readers = Vec::new();
+	for filename in arguments.input_files.iter() {
+		readers.push(get_reader(filename).await?);
+	}
+	eprintln!("concat {} containers to {:?}", readers.len(), arguments.output);
+
+	let reader = ConcatReader::new(readers)?;
+	ConversionPipeline::new(Box::new(reader), ConversionTarget::Path(arguments.output.clone()))
+		.run()
+		.await
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tests::run_command;
+	use anyhow::Result;
+	use std::fs;
+	use versatiles_container::get_reader;
+
+	#[tokio::main]
+	async fn count_tiles(filename: &str) -> Result<u64> {
+		let reader = get_reader(filename).await?;
+		let mut count = 0;
+		for bbox in reader.get_parameters().bbox_pyramid.iter_levels() {
+			count += reader.get_bbox_tile_stream(bbox.clone()).await.drain_and_count().await;
+		}
+		Ok(count)
+	}
+
+	#[test]
+	fn test_concat() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+
+		for partition in ["1/2", "2/2"] {
+			let output = format!("../tmp/concat_part{}.versatiles", &partition[0..1]);
+			run_command(vec![
+				"versatiles",
+				"convert",
+				"--overwrite",
+				"--partition",
+				partition,
+				"../testdata/berlin.mbtiles",
+				&output,
+			])?;
+		}
+
+		run_command(vec![
+			"versatiles",
+			"concat",
+			"--overwrite",
+			"--output=../tmp/concat.versatiles",
+			"../tmp/concat_part1.versatiles",
+			"../tmp/concat_part2.versatiles",
+		])?;
+
+		let count = count_tiles("../testdata/berlin.mbtiles")?;
+		assert_eq!(count_tiles("../tmp/concat.versatiles")?, count);
+		// berlin is in the second half of the world, only level 0 is in the first one
+		assert_eq!(count_tiles("../tmp/concat_part1.versatiles")?, 1);
+		assert_eq!(count_tiles("../tmp/concat_part2.versatiles")?, count - 1);
+
+		// partitions must not overlap
+		assert!(run_command(vec![
+			"versatiles",
+			"concat",
+			"--overwrite",
+			"--output=../tmp/concat2.versatiles",
+			"../tmp/concat_part1.versatiles",
+			"../tmp/concat_part1.versatiles",
+		])
+		.is_err());
+		Ok(())
+	}
+}
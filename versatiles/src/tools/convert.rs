@@ -1,19 +1,45 @@
-use anyhow::{bail, Result};
+use super::expire::parse_expiry_list;
+use anyhow::{bail, ensure, Context, Result};
+use std::path::{Path, PathBuf};
 use versatiles::types::GeoBBox;
-use versatiles_container::{convert_tiles_container, get_reader, TilesConverterParameters};
-use versatiles_core::types::{TileBBoxPyramid, TileCompression};
+use versatiles_container::{
+	get_reader_from_stdin, get_reader_with_options, get_writer_name, sniff_tile_content, ConversionPipeline,
+	ConversionTarget, IndexCompression, ReaderOptions, TarPathTemplate, TileCipher, TileErrorPolicy, TileOrder,
+	TilesConvertReader, TilesConverterParameters, VersaTilesWriterOptions,
+};
+use versatiles_core::{
+	json::{JsonObject, JsonValue},
+	types::{TileBBoxPyramid, TileCompression, TileGrid, TileScheme, TilesReaderTrait},
+	utils::PrettyPrint,
+};
+use versatiles_pipeline::{RetileMode, RetileReader};
 
 #[derive(clap::Args, Debug)]
 #[command(arg_required_else_help = true, disable_version_flag = true)]
 pub struct Subcommand {
 	/// supported container formats: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
-	#[arg()]
+	/// use "-" to read from stdin, together with --input-format
+	#[arg(verbatim_doc_comment)]
 	input_file: String,
 
 	/// supported container formats: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
-	#[arg()]
+	/// use "-" to write to stdout, together with --output-format
+	#[arg(verbatim_doc_comment)]
 	output_file: String,
 
+	/// replace the output file if it already exists
+	#[arg(long, display_order = 0)]
+	overwrite: bool,
+
+	/// container format when reading from stdin: "pmtiles", "tar" or "versatiles"
+	/// the input is read completely into memory, since stdin can't be seeked
+	#[arg(long, value_name = "FORMAT", verbatim_doc_comment, display_order = 0)]
+	input_format: Option<String>,
+
+	/// container format when writing to stdout: "tar" or "mbtiles"
+	#[arg(long, value_name = "FORMAT", display_order = 0)]
+	output_format: Option<String>,
+
 	/// minimum zoom level
 	#[arg(long, value_name = "int", display_order = 1)]
 	min_zoom: Option<u8>,
@@ -32,10 +58,32 @@ pub struct Subcommand {
 	)]
 	bbox: Option<String>,
 
+	/// use only tiles inside a bounding box, starting at a zoom level, e.g. 10:5.8,45.5,10.9,47.9
+	/// lower zoom levels are not restricted. Can be used multiple times, e.g. for
+	/// a wide coverage at low zoom levels and a detailed coverage of a small region.
+	#[arg(
+		long,
+		value_name = "zoom:lon_min,lat_min,lon_max,lat_max",
+		allow_hyphen_values = true,
+		verbatim_doc_comment,
+		display_order = 1
+	)]
+	bbox_zoom: Vec<String>,
+
 	/// also include additional tiles surrounding the bounding box as a border
 	#[arg(long, value_name = "int", display_order = 1)]
 	bbox_border: Option<u32>,
 
+	/// convert only the Nth of M partitions, e.g. "3/8", to spread a conversion across several machines.
+	/// Partitions are vertical strips of every zoom level. Merge the results with "versatiles concat".
+	#[arg(long, value_name = "N/M", verbatim_doc_comment, display_order = 1)]
+	partition: Option<String>,
+
+	/// convert only the tiles listed in this file, one "z/x/y" per line, e.g. an osm2pgsql expiry file
+	/// or the output of "versatiles list". Useful for building small patch containers.
+	#[arg(long, value_name = "FILE", verbatim_doc_comment, display_order = 1)]
+	tile_list: Option<PathBuf>,
+
 	/// set new compression
 	#[arg(long, short, value_enum, display_order = 2)]
 	compress: Option<TileCompression>,
@@ -48,6 +96,11 @@ pub struct Subcommand {
 	#[arg(long, value_enum, value_name = "COMPRESSION", display_order = 2)]
 	override_input_compression: Option<TileCompression>,
 
+	/// detect the tile format and compression of the input from a sample of tiles,
+	/// e.g. to handle containers with missing or wrong metadata. Disagreements are logged as warnings.
+	#[arg(long, verbatim_doc_comment, display_order = 2)]
+	sniff_input: bool,
+
 	/// swap rows and columns, e.g. z/x/y -> z/y/x
 	#[arg(long, display_order = 3)]
 	swap_xy: bool,
@@ -55,36 +108,293 @@ pub struct Subcommand {
 	/// flip input vertically
 	#[arg(long, display_order = 3)]
 	flip_y: bool,
+
+	/// override the y axis orientation of the input source, e.g. to handle *.mbtiles files in xyz or *.tar files in tms order
+	#[arg(long, value_enum, value_name = "SCHEME", display_order = 3)]
+	input_scheme: Option<TileScheme>,
+
+	/// convert raster tiles between the 256 px and the 512 px convention: "512" merges four 256 px tiles
+	/// at level z+1 into one 512 px tile at level z, "256" splits 512 px tiles. Zoom options refer to the output.
+	#[arg(long, value_name = "PIXELS", verbatim_doc_comment, display_order = 3)]
+	retile_to: Option<u32>,
+
+	/// layout of the tile paths when writing a *.tar file: "{z}/{y}/{x}" (default), "{z}/{x}/{y}" or "{z}-{x}-{y}"
+	#[arg(long, value_name = "TEMPLATE", display_order = 3)]
+	tar_path_template: Option<String>,
+
+	/// encrypt the tiles of a *.versatiles file with AES-256-GCM, using a 256 bit key as hex string,
+	/// e.g. created with "openssl rand -hex 32"
+	#[arg(long, value_name = "HEX", verbatim_doc_comment, display_order = 3)]
+	encryption_key: Option<String>,
+
+	/// order of the tiles inside the blocks of a *.versatiles file: "source" (default) keeps the order of the input,
+	/// "hilbert" stores neighbouring tiles close to each other, so that regions need fewer HTTP range requests
+	#[arg(long, value_name = "ORDER", verbatim_doc_comment, display_order = 3)]
+	tile_order: Option<TileOrder>,
+
+	/// compression of the indexes of a *.versatiles file: "fast" (default) or "best", for smaller indexes
+	/// that are faster to download, but slower to write
+	#[arg(long, value_name = "LEVEL", verbatim_doc_comment, display_order = 3)]
+	index_compression: Option<IndexCompression>,
+
+	/// set a metadata (TileJSON) field, e.g. --set-meta name=Berlin
+	/// values are parsed as JSON if possible (e.g. numbers or arrays), otherwise used as a string
+	/// can be used multiple times
+	#[arg(long, value_name = "key=value", verbatim_doc_comment, display_order = 3)]
+	set_meta: Vec<String>,
+
+	/// overwrite metadata (TileJSON) fields with the fields of a JSON file
+	#[arg(long, value_name = "FILE", display_order = 3)]
+	meta_file: Option<String>,
+
+	/// drop tiles smaller than this number of bytes
+	#[arg(long, value_name = "bytes", display_order = 3)]
+	min_tile_size: Option<u64>,
+
+	/// drop tiles larger than this number of bytes
+	#[arg(long, value_name = "bytes", display_order = 3)]
+	max_tile_size: Option<u64>,
+
+	/// only warn about tiles outside of --min-tile-size and --max-tile-size, instead of dropping them
+	#[arg(long, display_order = 3)]
+	warn_tile_size: bool,
+
+	/// fail the conversion if any tile is larger than this number of bytes
+	#[arg(long, value_name = "bytes", display_order = 3)]
+	tile_size_limit: Option<u64>,
+
+	/// what to do with tiles that can't be read or converted: "fail" stops at the first failed tile,
+	/// "skip" skips them with a warning, "report" skips them and lists all of them at the end.
+	/// The conversion exits with an error if any tile failed.
+	#[arg(long, value_name = "POLICY", default_value = "fail", verbatim_doc_comment, display_order = 3)]
+	on_tile_error: TileErrorPolicy,
+
+	/// check every tile against the tile format and compression declared by the input,
+	/// mislabeled tiles are handled like tiles that can't be read (see --on-tile-error)
+	#[arg(long, verbatim_doc_comment, display_order = 3)]
+	verify_content: bool,
+
+	/// limit the tile data held in memory between reading and writing, in megabytes
+	#[arg(long, value_name = "MB", display_order = 4)]
+	memory_limit: Option<u64>,
+
+	/// limit the download rate of remote sources in bytes per second
+	#[arg(long, value_name = "bytes/s", display_order = 4)]
+	max_download_rate: Option<u64>,
+
+	/// show a progress bar of the bytes downloaded from remote sources
+	#[arg(long, display_order = 4)]
+	download_progress: bool,
+
+	/// number of blocks fetched concurrently from remote *.versatiles containers (default: --io-concurrency)
+	#[arg(long, value_name = "int", display_order = 4)]
+	download_concurrency: Option<usize>,
+
+	/// number of SQLite connections for reading *.mbtiles files, which limits the parallel range queries
+	/// (default: --io-concurrency)
+	#[arg(long, value_name = "int", verbatim_doc_comment, display_order = 4)]
+	mbtiles_pool_size: Option<u32>,
+
+	/// write a JSON report of the conversion next to the output file, as "<output_file>.report.json",
+	/// with tile counts per zoom level, dropped and failed tiles, file sizes, wall time and throughput
+	#[arg(long, verbatim_doc_comment, display_order = 5)]
+	report: bool,
+
+	/// only print the effective conversion settings, without writing anything
+	#[arg(long, display_order = 5)]
+	dry_run: bool,
 }
 
-#[tokio::main]
 pub async fn run(arguments: &Subcommand) -> Result<()> {
 	eprintln!("convert from {:?} to {:?}", arguments.input_file, arguments.output_file);
 
-	let mut reader = get_reader(&arguments.input_file).await?;
+	let reader_options = ReaderOptions {
+		max_download_rate: arguments.max_download_rate,
+		show_download_progress: arguments.download_progress,
+		concurrency: arguments.download_concurrency,
+		request_limiter: None,
+		mbtiles_pool_size: arguments.mbtiles_pool_size,
+	};
+
+	let output_format = if arguments.output_file == "-" {
+		ensure!(
+			!arguments.report,
+			"--report can't be used when writing to stdout (\"-\")"
+		);
+		Some(
+			arguments
+				.output_format
+				.as_deref()
+				.context("writing to stdout requires --output-format")?,
+		)
+	} else {
+		ensure!(
+			arguments.output_format.is_none(),
+			"--output-format can only be used when writing to stdout (\"-\")"
+		);
+		ensure!(
+			arguments.overwrite || !Path::new(&arguments.output_file).is_file(),
+			"output file {:?} already exists, use --overwrite to replace it",
+			arguments.output_file
+		);
+		None
+	};
+
+	let mut reader = if arguments.input_file == "-" {
+		let format = arguments
+			.input_format
+			.as_deref()
+			.context("reading from stdin requires --input-format")?;
+		get_reader_from_stdin(format).await?
+	} else {
+		ensure!(
+			arguments.input_format.is_none(),
+			"--input-format can only be used when reading from stdin (\"-\")"
+		);
+		get_reader_with_options(&arguments.input_file, &reader_options).await?
+	};
 
 	if arguments.override_input_compression.is_some() {
 		reader.override_compression(arguments.override_input_compression.unwrap());
 	}
 
-	let cp = TilesConverterParameters::new(
+	if let Some(scheme) = arguments.input_scheme {
+		reader.override_scheme(scheme)?;
+	}
+
+	if arguments.sniff_input {
+		sniff_tile_content(&mut *reader).await?;
+	}
+
+	if let Some(size) = arguments.retile_to {
+		let mode = match size {
+			512 => RetileMode::Merge,
+			256 => RetileMode::Split,
+			_ => bail!("--retile-to must be 256 or 512, but got {size}"),
+		};
+		reader = Box::new(RetileReader::new(reader, mode)?);
+	}
+
+	let input_container = reader.get_container_name().to_string();
+	let input_compression = reader.get_parameters().tile_compression;
+
+	let mut cp = TilesConverterParameters::new(
 		arguments.compress,
-		get_bbox_pyramid(arguments)?,
+		get_bbox_pyramid(arguments, reader.get_parameters().bbox_pyramid.get_grid())?,
 		arguments.force_recompress,
 		arguments.flip_y,
 		arguments.swap_xy,
 	);
-	convert_tiles_container(reader, cp, &arguments.output_file).await?;
+	cp.memory_limit = arguments.memory_limit.map(|mb| mb * 1024 * 1024);
+	cp.min_tile_size = arguments.min_tile_size;
+	cp.max_tile_size = arguments.max_tile_size;
+	cp.warn_tile_size = arguments.warn_tile_size;
+	cp.tile_size_limit = arguments.tile_size_limit;
+	cp.meta_overrides = get_meta_overrides(arguments)?;
+	cp.tile_error_policy = arguments.on_tile_error;
+	cp.verify_content = arguments.verify_content;
+	if let Some(tile_list) = &arguments.tile_list {
+		let text = std::fs::read_to_string(tile_list).with_context(|| format!("reading tile list {tile_list:?}"))?;
+		cp.tile_list = Some(parse_expiry_list(&text, None).with_context(|| format!("parsing tile list {tile_list:?}"))?);
+	}
+
+	let target = match output_format {
+		Some(format) => ConversionTarget::Stdout(format.to_string()),
+		None => ConversionTarget::Path(PathBuf::from(&arguments.output_file)),
+	};
+	let mut pipeline = ConversionPipeline::new(reader, target.clone()).with_parameters(cp);
+	if let Some(template) = &arguments.tar_path_template {
+		pipeline = pipeline.with_tar_path_template(TarPathTemplate::parse_str(template)?);
+	}
+	if let Some(key) = &arguments.encryption_key {
+		pipeline = pipeline.with_cipher(TileCipher::from_hex(key)?);
+	}
+	if arguments.tile_order.is_some() || arguments.index_compression.is_some() {
+		pipeline = pipeline.with_versatiles_options(VersaTilesWriterOptions {
+			tile_order: arguments.tile_order.unwrap_or_default(),
+			index_compression: arguments.index_compression.unwrap_or_default(),
+		});
+	}
+
+	if arguments.report {
+		pipeline = pipeline.with_report(PathBuf::from(format!("{}.report.json", arguments.output_file)));
+	}
+
+	if arguments.dry_run {
+		let converter = pipeline.into_converter()?;
+		let mut print = PrettyPrint::new();
+		let cat = print.get_category("dry run").await;
+		cat.add_key_value("input", &format!("{} ({input_container})", arguments.input_file))
+			.await;
+		let writer_name = match &target {
+			ConversionTarget::Stdout(format) => format.as_str(),
+			ConversionTarget::Path(_) => get_writer_name(&arguments.output_file)?,
+		};
+		cat.add_key_value("output", &format!("{} ({writer_name})", arguments.output_file))
+			.await;
+		print_converter(&converter, input_compression, &cat).await;
+		return Ok(());
+	}
+
+	pipeline.run().await
+}
 
-	Ok(())
+/// Prints the effective settings of a conversion.
+async fn print_converter(converter: &TilesConvertReader, input_compression: TileCompression, print: &PrettyPrint) {
+	let parameters = converter.get_parameters();
+	print.add_key_value("tile format", &parameters.tile_format).await;
+	print
+		.add_key_value(
+			"tile compression",
+			&format!("{input_compression} -> {}", parameters.tile_compression),
+		)
+		.await;
+	print
+		.add_key_value("recompress tiles", &converter.is_recompressing())
+		.await;
+	print.add_key_value("bbox pyramid", &parameters.bbox_pyramid).await;
+	print
+		.add_key_value("estimated tile count", &parameters.bbox_pyramid.count_tiles())
+		.await;
 }
 
-fn get_bbox_pyramid(arguments: &Subcommand) -> Result<Option<TileBBoxPyramid>> {
-	if arguments.min_zoom.is_none() && arguments.max_zoom.is_none() && arguments.bbox.is_none() {
+/// Collects the metadata fields of --meta-file and --set-meta. Fields of --set-meta take precedence.
+fn get_meta_overrides(arguments: &Subcommand) -> Result<Option<JsonObject>> {
+	if arguments.meta_file.is_none() && arguments.set_meta.is_empty() {
 		return Ok(None);
 	}
 
-	let mut bbox_pyramid = TileBBoxPyramid::new_full(32);
+	let mut object = match &arguments.meta_file {
+		Some(filename) => JsonObject::parse_str(
+			&std::fs::read_to_string(filename).with_context(|| format!("reading meta file {filename:?}"))?,
+		)
+		.with_context(|| format!("parsing meta file {filename:?}"))?,
+		None => JsonObject::default(),
+	};
+
+	for entry in arguments.set_meta.iter() {
+		let (key, value) = entry
+			.split_once('=')
+			.with_context(|| format!("--set-meta expects 'key=value', but got {entry:?}"))?;
+		let value = JsonValue::parse_str(value).unwrap_or_else(|_| JsonValue::from(value));
+		object.set(key.trim(), value);
+	}
+
+	Ok(Some(object))
+}
+
+fn get_bbox_pyramid(arguments: &Subcommand, grid: TileGrid) -> Result<Option<TileBBoxPyramid>> {
+	if arguments.min_zoom.is_none()
+		&& arguments.max_zoom.is_none()
+		&& arguments.bbox.is_none()
+		&& arguments.bbox_zoom.is_empty()
+		&& arguments.partition.is_none()
+	{
+		return Ok(None);
+	}
+
+	// the bbox is converted using the tile grid of the input
+	let mut bbox_pyramid = TileBBoxPyramid::new_full_in_grid(grid, 32);
 
 	if let Some(min_zoom) = arguments.min_zoom {
 		bbox_pyramid.set_zoom_min(min_zoom)
@@ -95,27 +405,64 @@ fn get_bbox_pyramid(arguments: &Subcommand) -> Result<Option<TileBBoxPyramid>> {
 	}
 
 	if let Some(bbox) = &arguments.bbox {
-		log::trace!("parsing bbox argument: {:?}", bbox);
-		let values: Vec<f64> = bbox
-			.split(&[' ', ',', ';'])
-			.filter(|s| !s.is_empty())
-			.map(|s| s.parse::<f64>().expect("bbox value is not a number"))
-			.collect();
-
-		if values.len() != 4 {
-			bail!("bbox must contain exactly 4 numbers, but instead i'v got: {bbox:?}");
-		}
+		bbox_pyramid.intersect_geo_bbox(&parse_bbox(bbox)?);
+	}
 
-		bbox_pyramid.intersect_geo_bbox(&GeoBBox::try_from(values)?);
+	for bbox_zoom in &arguments.bbox_zoom {
+		log::trace!("parsing bbox-zoom argument: {:?}", bbox_zoom);
+		let (zoom, bbox) = bbox_zoom
+			.split_once(':')
+			.with_context(|| format!("bbox-zoom must have the form zoom:bbox, but instead i'v got: {bbox_zoom:?}"))?;
+		let zoom = zoom
+			.trim()
+			.parse::<u8>()
+			.with_context(|| format!("invalid zoom level in bbox-zoom {bbox_zoom:?}"))?;
+		bbox_pyramid.intersect_geo_bbox_from_level(zoom, &parse_bbox(bbox)?);
+	}
 
+	if arguments.bbox.is_some() || !arguments.bbox_zoom.is_empty() {
 		if let Some(b) = arguments.bbox_border {
 			bbox_pyramid.add_border(b, b, b, b);
 		}
 	}
 
+	if let Some(partition) = &arguments.partition {
+		let (index, count) = parse_partition(partition)?;
+		bbox_pyramid.intersect_partition(index, count);
+	}
+
 	Ok(Some(bbox_pyramid))
 }
 
+/// Parses a partition like "3/8" into a zero based index and the number of partitions.
+fn parse_partition(partition: &str) -> Result<(u32, u32)> {
+	let parse = |(n, m): (&str, &str)| Some((n.trim().parse::<u32>().ok()?, m.trim().parse::<u32>().ok()?));
+	let (n, m) = partition
+		.split_once('/')
+		.and_then(parse)
+		.with_context(|| format!("--partition must have the form N/M, e.g. 3/8, but got {partition:?}"))?;
+	ensure!(
+		(1..=m).contains(&n),
+		"--partition {partition:?} must be between 1/{m} and {m}/{m}"
+	);
+	Ok((n - 1, m))
+}
+
+pub(super) fn parse_bbox(bbox: &str) -> Result<GeoBBox> {
+	log::trace!("parsing bbox argument: {:?}", bbox);
+	let values: Vec<f64> = bbox
+		.split(&[' ', ',', ';'])
+		.filter(|s| !s.is_empty())
+		.map(|s| s.parse::<f64>().expect("bbox value is not a number"))
+		.collect();
+
+	if values.len() != 4 {
+		bail!("bbox must contain exactly 4 numbers, but instead i'v got: {bbox:?}");
+	}
+
+	GeoBBox::try_from(values)
+}
+
 #[cfg(test)]
 mod tests {
 	use crate::tests::run_command;
@@ -129,6 +476,8 @@ mod tests {
 		run_command(vec![
 			"versatiles",
 			"convert",
+			"--overwrite",
+			"--mbtiles-pool-size=2",
 			"../testdata/berlin.mbtiles",
 			"../tmp/berlin1.versatiles",
 		])?;
@@ -136,6 +485,7 @@ mod tests {
 		run_command(vec![
 			"versatiles",
 			"convert",
+			"--overwrite",
 			"--bbox=13.38,52.46,13.43,52.49",
 			"../tmp/berlin1.versatiles",
 			"../tmp/berlin2.versatiles",
@@ -144,6 +494,7 @@ mod tests {
 		run_command(vec![
 			"versatiles",
 			"convert",
+			"--overwrite",
 			"--min-zoom=1",
 			"--max-zoom=13",
 			"--flip-y",
@@ -152,6 +503,431 @@ mod tests {
 			"../tmp/berlin3.versatiles",
 		])?;
 
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--input-scheme=xyz",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin4.versatiles",
+		])?;
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--sniff-input",
+			"--max-zoom=8",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin9.versatiles",
+		])?;
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--memory-limit=1",
+			"--compress=brotli",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin6.versatiles",
+		])?;
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-tile-size=500000",
+			"--tile-size-limit=1000000",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin7.versatiles",
+		])?;
+
+		let error = run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--tile-size-limit=100",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin8.versatiles",
+		])
+		.unwrap_err();
+		assert!(
+			error
+				.to_string()
+				.ends_with("tiles exceeded the hard size limit of 100 bytes and were dropped"),
+			"{error}"
+		);
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=8",
+			"--tar-path-template={z}-{x}-{y}",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin5.tar",
+		])?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_meta_overrides() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+		fs::write(
+			"../tmp/berlin_meta.json",
+			r#"{"name":"from file","description":"from file"}"#,
+		)?;
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=4",
+			"--meta-file=../tmp/berlin_meta.json",
+			"--set-meta",
+			"name=Berlin",
+			"--set-meta=maxzoom=3",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_meta.versatiles",
+		])?;
+
+		let tilejson = get_meta("../tmp/berlin_meta.versatiles")?;
+		assert_eq!(tilejson.get_str("name"), Some("Berlin"));
+		assert_eq!(tilejson.get_str("description"), Some("from file"));
+		assert_eq!(tilejson.values.get_byte("maxzoom"), Some(3));
+
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--set-meta=name",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_meta.versatiles",
+		])
+		.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bbox_zoom() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=10",
+			"--bbox-zoom=8:13.38,52.46,13.43,52.49",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_bbox_zoom.versatiles",
+		])?;
+
+		let input = get_pyramid("../testdata/berlin.mbtiles")?;
+		let output = get_pyramid("../tmp/berlin_bbox_zoom.versatiles")?;
+		assert_eq!(output.get_level_bbox(7), input.get_level_bbox(7));
+		assert!(output.get_level_bbox(10).count_tiles() < input.get_level_bbox(10).count_tiles());
+
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--bbox-zoom=13.38,52.46,13.43,52.49",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_bbox_zoom.versatiles",
+		])
+		.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_tile_list() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+		fs::write(
+			"../tmp/berlin_tile_list.txt",
+			"14/8802-8803/5373\n13/4401/2686\n0/0/0\n",
+		)?;
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--tile-list=../tmp/berlin_tile_list.txt",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_tile_list.versatiles",
+		])?;
+
+		let pyramid = get_pyramid("../tmp/berlin_tile_list.versatiles")?;
+		assert_eq!(pyramid.count_tiles(), 4);
+		assert_eq!(pyramid.get_zoom_min(), Some(0));
+		assert_eq!(pyramid.get_zoom_max(), Some(14));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_retile() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+
+		run_command(vec![
+			"versatiles",
+			"dev",
+			"generate-fixture",
+			"--format=png",
+			"--compress=uncompressed",
+			"--max-zoom=3",
+			"../tmp/retile_256.versatiles",
+		])?;
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--retile-to=512",
+			"../tmp/retile_256.versatiles",
+			"../tmp/retile_512.versatiles",
+		])?;
+		let pyramid = get_pyramid("../tmp/retile_512.versatiles")?;
+		assert_eq!(pyramid.get_zoom_max(), Some(2));
+		assert_eq!(pyramid.count_tiles(), 21);
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--retile-to=256",
+			"../tmp/retile_512.versatiles",
+			"../tmp/retile_256_again.versatiles",
+		])?;
+		assert_eq!(get_pyramid("../tmp/retile_256_again.versatiles")?.count_tiles(), 85);
+
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--retile-to=512",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_retile.versatiles",
+		])
+		.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_writer_options() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=8",
+			"--tile-order=hilbert",
+			"--index-compression=best",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_hilbert.versatiles",
+		])?;
+		assert_eq!(get_pyramid("../tmp/berlin_hilbert.versatiles")?.get_zoom_max(), Some(8));
+
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--tile-order=hilbert",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_hilbert.pmtiles",
+		])
+		.is_err());
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--tile-order=zorder",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_zorder.versatiles",
+		])
+		.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_encryption() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+		let key = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=4",
+			&format!("--encryption-key={key}"),
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_encrypted.versatiles",
+		])?;
+		assert_eq!(count_decrypted_tiles("../tmp/berlin_encrypted.versatiles", key)?, 5);
+
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=4",
+			&format!("--encryption-key={key}"),
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_encrypted.pmtiles",
+		])
+		.is_err());
+
+		Ok(())
+	}
+
+	#[tokio::main]
+	async fn count_decrypted_tiles(filename: &str, key: &str) -> Result<usize> {
+		use versatiles_container::{TileCipher, VersaTilesReader};
+		use versatiles_core::types::TilesReaderTrait;
+		let mut reader = VersaTilesReader::open_path(&std::path::absolute(filename)?).await?;
+		assert!(reader
+			.get_tile_data(&versatiles_core::types::TileCoord3::new(0, 0, 0)?)
+			.await
+			.is_err());
+		reader.set_cipher(TileCipher::from_hex(key)?)?;
+		let mut count = 0;
+		for bbox in reader.get_parameters().bbox_pyramid.iter_levels() {
+			count += reader.get_bbox_tile_stream(bbox.clone()).await.collect().await.len();
+		}
+		Ok(count)
+	}
+
+	#[tokio::main]
+	async fn get_pyramid(filename: &str) -> Result<versatiles_core::types::TileBBoxPyramid> {
+		let reader = versatiles_container::get_reader(filename).await?;
+		Ok(reader.get_parameters().bbox_pyramid.clone())
+	}
+
+	#[tokio::main]
+	async fn get_meta(filename: &str) -> Result<versatiles_core::tilejson::TileJSON> {
+		let reader = versatiles_container::get_reader(filename).await?;
+		Ok(reader.get_tilejson().clone())
+	}
+
+	#[test]
+	fn test_dry_run() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+		let _ = fs::remove_file("../tmp/berlin_dry_run.versatiles");
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--dry-run",
+			"--max-zoom=10",
+			"--compress=brotli",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_dry_run.versatiles",
+		])?;
+		assert!(!fs::exists("../tmp/berlin_dry_run.versatiles")?);
+
+		// The output format is still validated
+		assert!(run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--dry-run",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_dry_run.unknown",
+		])
+		.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_overwrite() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+		fs::write("../tmp/berlin_overwrite.versatiles", "existing")?;
+
+		let args = vec![
+			"versatiles",
+			"convert",
+			"--max-zoom=2",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_overwrite.versatiles",
+		];
+		assert!(run_command(args.clone()).is_err());
+		assert_eq!(fs::read_to_string("../tmp/berlin_overwrite.versatiles")?, "existing");
+
+		run_command([args, vec!["--overwrite"]].concat())?;
+		assert_eq!(
+			get_pyramid("../tmp/berlin_overwrite.versatiles")?.get_zoom_max(),
+			Some(2)
+		);
+		assert!(!fs::exists("../tmp/berlin_overwrite.versatiles.tmp~")?);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_report() -> Result<()> {
+		use versatiles_core::json::JsonObject;
+
+		fs::create_dir("../tmp/").unwrap_or_default();
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--max-zoom=2",
+			"--report",
+			"--overwrite",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_report.versatiles",
+		])?;
+
+		let report = JsonObject::parse_str(&fs::read_to_string("../tmp/berlin_report.versatiles.report.json")?)?;
+		assert_eq!(report.get_string("status")?.unwrap(), "succeeded");
+		let input = report.get("input").unwrap().as_object()?;
+		assert_eq!(input.get_string("container")?.unwrap(), "mbtiles");
+		let output = report.get("output").unwrap().as_object()?;
+		assert_eq!(output.get_string("container")?.unwrap(), "versatiles");
+		assert_eq!(output.get_number::<u8>("zoom_max")?, Some(2));
+		let tiles = report.get("tiles").unwrap().as_object()?;
+		assert_eq!(tiles.get_array("levels")?.unwrap().0.len(), 3);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_stdio_arguments() {
+		for args in [
+			vec!["-", "../tmp/berlin_stdio.versatiles"],
+			vec![
+				"--input-format=tar",
+				"../testdata/berlin.mbtiles",
+				"../tmp/berlin_stdio.versatiles",
+			],
+			vec!["../testdata/berlin.mbtiles", "-"],
+			vec![
+				"--output-format=tar",
+				"../testdata/berlin.mbtiles",
+				"../tmp/berlin_stdio.tar",
+			],
+			vec!["--output-format=pmtiles", "../testdata/berlin.mbtiles", "-"],
+			vec!["--output-format=tar", "--report", "../testdata/berlin.mbtiles", "-"],
+		] {
+			let command = [vec!["versatiles", "convert", "--max-zoom=2"], args.clone()].concat();
+			assert!(run_command(command).is_err(), "{args:?}");
+		}
+	}
+
+	#[test]
+	fn test_parse_partition() -> Result<()> {
+		use super::parse_partition;
+		assert_eq!(parse_partition("3/8")?, (2, 8));
+		assert_eq!(parse_partition("1/1")?, (0, 1));
+		for partition in ["0/8", "9/8", "3", "a/8", "3/0"] {
+			assert!(parse_partition(partition).is_err(), "{partition}");
+		}
 		Ok(())
 	}
 
@@ -162,11 +938,13 @@ mod tests {
 		run_command(vec![
 			"versatiles",
 			"convert",
+			"--overwrite",
 			"--min-zoom=1",
 			"--max-zoom=2",
 			"--bbox=-180,-85,180,85",
 			"--flip-y",
 			"--force-recompress",
+			"--download-concurrency=4",
 			"https://download.versatiles.org/osm.versatiles",
 			"../tmp/planet2.versatiles",
 		])
@@ -180,6 +958,7 @@ mod tests {
 		run_command(vec![
 			"versatiles",
 			"convert",
+			"--overwrite",
 			"--min-zoom=12",
 			"--bbox=9.14,48.76,9.19,48.79",
 			"--flip-y",
@@ -0,0 +1,141 @@
+//! Renders coverage maps of a tile container.
+//!
+//! For every zoom level a small grayscale PNG is written, with one pixel per tile of the level's bounding box:
+//! existing tiles are white, missing tiles are black. Regions that are missing by mistake are easy to spot
+//! in such an image. An `index.html` lists all maps together with the number of existing tiles per level.
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, GrayImage, Luma};
+use std::{fmt::Write, fs, path::Path};
+use versatiles_core::types::{TileBBox, TilesReaderTrait};
+
+/// Maximum width and height of a coverage map. Larger levels are scaled down, so that a pixel covers multiple tiles.
+const MAX_SIZE: u32 = 4096;
+
+/// The coverage map of a single zoom level.
+#[derive(Debug)]
+pub struct CoverageMap {
+	pub bbox: TileBBox,
+	/// Number of tiles in each direction covered by one pixel.
+	pub scale: u32,
+	/// Number of existing tiles.
+	pub tile_count: u64,
+	pub image: GrayImage,
+}
+
+impl CoverageMap {
+	pub fn new(bbox: TileBBox) -> CoverageMap {
+		let scale = bbox.width().max(bbox.height()).div_ceil(MAX_SIZE).max(1);
+		let image = GrayImage::new(bbox.width().div_ceil(scale), bbox.height().div_ceil(scale));
+		CoverageMap {
+			bbox,
+			scale,
+			tile_count: 0,
+			image,
+		}
+	}
+
+	/// Marks the tile at the given column and row as existing.
+	pub fn add_tile(&mut self, x: u32, y: u32) {
+		let px = (x - self.bbox.x_min) / self.scale;
+		let py = (y - self.bbox.y_min) / self.scale;
+		self.image.put_pixel(px, py, Luma([255]));
+		self.tile_count += 1;
+	}
+}
+
+/// Scans all tiles of a container and renders a coverage map for each zoom level.
+pub async fn get_coverage_maps(reader: &dyn TilesReaderTrait) -> Result<Vec<CoverageMap>> {
+	let mut maps = Vec::new();
+
+	for bbox in reader.get_parameters().bbox_pyramid.iter_levels() {
+		let mut map = CoverageMap::new(bbox.clone());
+		reader
+			.get_bbox_tile_stream(bbox.clone())
+			.await
+			.for_each_sync(|(coord, _blob)| map.add_tile(coord.x, coord.y))
+			.await;
+		maps.push(map);
+	}
+
+	Ok(maps)
+}
+
+/// Writes coverage maps as `z{level}.png` files and an `index.html` report into a directory.
+///
+/// # Arguments
+/// * `reader` - The container to scan.
+/// * `directory` - The output directory. It is created if necessary.
+pub async fn write_coverage_maps(reader: &dyn TilesReaderTrait, directory: &Path) -> Result<()> {
+	fs::create_dir_all(directory).with_context(|| format!("creating directory {directory:?}"))?;
+
+	let maps = get_coverage_maps(reader).await?;
+
+	let mut html = String::from(
+		"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>tile coverage</title>\n\
+		<style>img { image-rendering: pixelated; min-width: 256px; max-width: 100%; border: 1px solid #888; }</style>\n\
+		</head>\n<body>\n",
+	);
+	writeln!(html, "<h1>tile coverage of {}</h1>", reader.get_source_name())?;
+
+	for map in maps {
+		let level = map.bbox.level;
+		let filename = format!("z{level}.png");
+		let blob = versatiles_image::png::image2blob(&DynamicImage::ImageLuma8(map.image), true)?;
+		fs::write(directory.join(&filename), blob.as_slice())?;
+
+		writeln!(html, "<h2>zoom level {level}</h2>")?;
+		writeln!(
+			html,
+			"<p>{} of {} tiles in {:?}, 1 pixel = {}×{} tiles</p>",
+			map.tile_count,
+			map.bbox.count_tiles(),
+			map.bbox,
+			map.scale,
+			map.scale
+		)?;
+		writeln!(html, "<img src=\"{filename}\">")?;
+	}
+
+	html.push_str("</body>\n</html>\n");
+	fs::write(directory.join("index.html"), html)?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_container::MBTilesReader;
+
+	#[test]
+	fn scale() -> Result<()> {
+		let map = CoverageMap::new(TileBBox::new(3, 0, 0, 7, 3)?);
+		assert_eq!(map.scale, 1);
+		assert_eq!(map.image.dimensions(), (8, 4));
+
+		let mut map = CoverageMap::new(TileBBox::new_full(14)?);
+		assert_eq!(map.scale, 4);
+		assert_eq!(map.image.dimensions(), (4096, 4096));
+
+		map.add_tile(7, 9);
+		assert_eq!(map.image.get_pixel(1, 2), &Luma([255]));
+		assert_eq!(map.image.get_pixel(0, 0), &Luma([0]));
+		assert_eq!(map.tile_count, 1);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn berlin() -> Result<()> {
+		let reader = MBTilesReader::open_path(&std::env::current_dir()?.join("../testdata/berlin.mbtiles"))?;
+		let dir = assert_fs::TempDir::new()?;
+		write_coverage_maps(&reader, dir.path()).await?;
+
+		assert!(dir.path().join("z0.png").exists());
+		assert!(dir.path().join("z14.png").exists());
+		let html = fs::read_to_string(dir.path().join("index.html"))?;
+		assert!(html.contains("<img src=\"z14.png\">"));
+		assert!(html.contains(" tiles in 14: [8787,5361,8818,5387] (864), 1 pixel = 1×1 tiles"));
+		Ok(())
+	}
+}
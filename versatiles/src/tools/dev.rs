@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use versatiles_container::{write_fixture, FixtureParameters};
+use versatiles_core::types::{TileCompression, TileFormat};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	#[command(subcommand)]
+	command: Commands,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Commands {
+	/// Generate a small deterministic tileset for tests
+	GenerateFixture(GenerateFixture),
+}
+
+/// Generates checkerboard raster tiles with their coordinates written on them,
+/// or vector tiles with known features.
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+struct GenerateFixture {
+	/// supported container formats: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg()]
+	output_file: String,
+
+	/// tile format
+	#[arg(long, short, value_enum, default_value = "pbf", display_order = 1)]
+	format: TileFormat,
+
+	/// tile compression
+	#[arg(long, short, value_enum, default_value = "gzip", display_order = 1)]
+	compress: TileCompression,
+
+	/// minimum zoom level
+	#[arg(long, value_name = "int", default_value = "0", display_order = 1)]
+	min_zoom: u8,
+
+	/// maximum zoom level
+	#[arg(long, value_name = "int", default_value = "3", display_order = 1)]
+	max_zoom: u8,
+
+	/// use only tiles inside a bounding box
+	#[arg(
+		long,
+		short,
+		value_name = "lon_min,lat_min,lon_max,lat_max",
+		allow_hyphen_values = true,
+		display_order = 1
+	)]
+	bbox: Option<String>,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	match &arguments.command {
+		Commands::GenerateFixture(arguments) => generate_fixture(arguments).await,
+	}
+}
+
+async fn generate_fixture(arguments: &GenerateFixture) -> Result<()> {
+	eprintln!("generate fixture {:?}", arguments.output_file);
+
+	let bbox = match &arguments.bbox {
+		Some(bbox) => {
+			let values = bbox
+				.split(&[' ', ',', ';'])
+				.filter(|s| !s.is_empty())
+				.map(|s| s.parse::<f64>())
+				.collect::<Result<Vec<f64>, _>>()?;
+			let Ok(values) = <[f64; 4]>::try_from(values) else {
+				bail!("bbox must contain exactly 4 numbers, but instead i'v got: {bbox:?}");
+			};
+			Some(values)
+		}
+		None => None,
+	};
+
+	let parameters = FixtureParameters {
+		tile_format: arguments.format,
+		tile_compression: arguments.compress,
+		zoom_min: arguments.min_zoom,
+		zoom_max: arguments.max_zoom,
+		bbox,
+	};
+
+	write_fixture(&parameters, &arguments.output_file).await
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tests::run_command;
+	use anyhow::Result;
+
+	#[tokio::main]
+	async fn count_tiles(filename: &str) -> Result<u64> {
+		let reader = versatiles_container::get_reader(filename).await?;
+		Ok(reader.get_parameters().bbox_pyramid.count_tiles())
+	}
+
+	#[test]
+	fn test_generate_fixture() -> Result<()> {
+		let dir = assert_fs::TempDir::new()?;
+		let filename = dir.path().join("fixture.versatiles");
+		let filename = filename.to_str().unwrap();
+
+		run_command(vec![
+			"versatiles",
+			"dev",
+			"generate-fixture",
+			"--format=png",
+			"--compress=uncompressed",
+			"--max-zoom=2",
+			"--bbox=-180,-85,-1,-1",
+			filename,
+		])?;
+
+		assert_eq!(count_tiles(filename)?, 1 + 1 + 4);
+		Ok(())
+	}
+
+	#[test]
+	fn test_invalid_bbox() {
+		let error = run_command(vec![
+			"versatiles",
+			"dev",
+			"generate-fixture",
+			"--bbox=1,2,3",
+			"fixture.tar",
+		])
+		.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"bbox must contain exactly 4 numbers, but instead i'v got: \"1,2,3\""
+		);
+	}
+}
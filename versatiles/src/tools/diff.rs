@@ -0,0 +1,344 @@
+//! Compares the tiles of two containers.
+//!
+//! Tiles are compared after decompression, so containers with different tile compressions can be compared. Every
+//! tile that differs is listed, together with tiles that exist in only one of the containers. If both containers
+//! hold raster tiles, changed tiles are decoded and compared pixel by pixel: the number of changed pixels and the
+//! PSNR show whether a re-encoding lost visible quality. Optionally, a visual diff is written for every changed
+//! raster tile.
+//!
+//! Vector tiles are decoded and compared feature by feature, ignoring the order of features and the key/value tables
+//! of layers. For every changed tile, the number of added and removed features is listed per layer. Tiles that differ
+//! only in their encoding count as identical.
+//!
+//! Optionally, the differences are written as a patch (`*.vpatch`) that updates the first container to the second
+//! one with `versatiles apply-patch`. The patch contains every tile whose decompressed content changed, so the
+//! patched container matches the second one exactly.
+
+use super::checksum::get_order_key;
+use anyhow::{ensure, Context, Result};
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_container::{get_reader, TempOutputFile, TilePatchWriter};
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{Blob, TileBBox, TileCompression, TileCoord3, TileFormat, TilesReaderTrait},
+	utils::{compress, decompress},
+};
+use versatiles_geometry::vector_tile::{VectorTile, VectorTileDiff};
+use versatiles_image::{
+	diff::ImageDiff,
+	helper::{blob2image, image2blob},
+};
+
+/// Size of the blocks in which tiles are compared, to limit memory usage.
+const BLOCK_SIZE: u32 = 256;
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// first tile container
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	file1: String,
+
+	/// second tile container
+	#[arg(required = true)]
+	file2: String,
+
+	/// write a visual diff of every changed raster tile as PNG into this directory, as {z}/{x}/{y}.png
+	#[arg(long, value_name = "DIRECTORY")]
+	images: Option<PathBuf>,
+
+	/// write a patch that updates the first container to the second one, e.g. "update.vpatch"
+	/// apply it with "versatiles apply-patch"
+	#[arg(long, value_name = "FILE", verbatim_doc_comment)]
+	write_patch: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+	identical: u64,
+	changed: u64,
+	removed: u64,
+	added: u64,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	eprintln!("diff {:?} with {:?}", arguments.file1, arguments.file2);
+
+	let reader1 = get_reader(&arguments.file1).await?;
+	let reader2 = get_reader(&arguments.file2).await?;
+
+	let differ = TileDiffer::new(&*reader1, &*reader2)?;
+	ensure!(
+		differ.kind == ContentKind::Raster || arguments.images.is_none(),
+		"--images requires raster tiles in both containers"
+	);
+
+	let summary = match &arguments.write_patch {
+		Some(path) => {
+			let parameters2 = reader2.get_parameters();
+			ensure!(
+				reader1.get_parameters().tile_format == parameters2.tile_format,
+				"patches require the same tile format in both containers"
+			);
+			let output = TempOutputFile::new(path)?;
+			let mut patch = PatchOutput {
+				writer: TilePatchWriter::create(
+					output.get_temp_path(),
+					parameters2.tile_format,
+					parameters2.tile_compression,
+				)?,
+				compression: parameters2.tile_compression,
+			};
+			let summary = differ.run(arguments.images.as_deref(), Some(&mut patch)).await?;
+			let count = patch.writer.finish()?;
+			output.commit()?;
+			eprintln!("wrote {count} tiles to patch {path:?}");
+			summary
+		}
+		None => differ.run(arguments.images.as_deref(), None).await?,
+	};
+	println!(
+		"{} identical, {} changed, {} only in first, {} only in second",
+		summary.identical, summary.changed, summary.removed, summary.added
+	);
+
+	Ok(())
+}
+
+/// Collects the tiles of the second container that differ from the first one.
+struct PatchOutput {
+	writer: TilePatchWriter,
+	/// Compression of the tiles in the patch.
+	compression: TileCompression,
+}
+
+impl PatchOutput {
+	fn set_tile(&mut self, coord: &TileCoord3, blob: Option<&Blob>) -> Result<()> {
+		match blob {
+			Some(blob) => self
+				.writer
+				.set_tile(coord, Some(&compress(blob.clone(), &self.compression)?)),
+			None => self.writer.set_tile(coord, None),
+		}
+	}
+}
+
+/// How the contents of changed tiles are compared.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ContentKind {
+	/// Decoded and compared pixel by pixel.
+	Raster,
+	/// Decoded and compared feature by feature.
+	Vector,
+	/// Compared byte by byte.
+	Other,
+}
+
+struct TileDiffer<'a> {
+	reader1: &'a dyn TilesReaderTrait,
+	reader2: &'a dyn TilesReaderTrait,
+	kind: ContentKind,
+}
+
+impl<'a> TileDiffer<'a> {
+	fn new(reader1: &'a dyn TilesReaderTrait, reader2: &'a dyn TilesReaderTrait) -> Result<TileDiffer<'a>> {
+		let grid1 = reader1.get_parameters().bbox_pyramid.get_grid();
+		let grid2 = reader2.get_parameters().bbox_pyramid.get_grid();
+		ensure!(
+			grid1 == grid2,
+			"containers use different tile grids: {grid1} and {grid2}"
+		);
+
+		let is_raster = |format: TileFormat| matches!(format, TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP);
+		let format1 = reader1.get_parameters().tile_format;
+		let format2 = reader2.get_parameters().tile_format;
+		let kind = if is_raster(format1) && is_raster(format2) {
+			ContentKind::Raster
+		} else if format1 == TileFormat::PBF && format2 == TileFormat::PBF {
+			ContentKind::Vector
+		} else {
+			ContentKind::Other
+		};
+
+		Ok(TileDiffer { reader1, reader2, kind })
+	}
+
+	async fn run(&self, images: Option<&Path>, mut patch: Option<&mut PatchOutput>) -> Result<Summary> {
+		let pyramid1 = &self.reader1.get_parameters().bbox_pyramid;
+		let pyramid2 = &self.reader2.get_parameters().bbox_pyramid;
+		let mut summary = Summary::default();
+		let mut progress = get_progress_bar("comparing tiles", pyramid1.count_tiles() + pyramid2.count_tiles());
+
+		for level in 0..=31 {
+			let mut bbox = pyramid1.get_level_bbox(level).clone();
+			bbox.include_bbox(pyramid2.get_level_bbox(level))?;
+
+			for block in bbox.iter_bbox_grid(BLOCK_SIZE) {
+				let tiles1 = self.get_tiles(self.reader1, &block).await?;
+				let tiles2 = self.get_tiles(self.reader2, &block).await?;
+
+				let mut coords: Vec<&TileCoord3> = tiles1
+					.keys()
+					.chain(tiles2.keys().filter(|coord| !tiles1.contains_key(coord)))
+					.collect();
+				coords.sort_unstable_by_key(|coord| get_order_key(coord));
+
+				for coord in coords {
+					match (tiles1.get(coord), tiles2.get(coord)) {
+						(Some(blob1), Some(blob2)) => {
+							let change = if blob1 == blob2 {
+								None
+							} else {
+								if let Some(patch) = patch.as_deref_mut() {
+									patch.set_tile(coord, Some(blob2))?;
+								}
+								self.describe_change(coord, blob1, blob2, images)?
+							};
+							if let Some(change) = change {
+								summary.changed += 1;
+								println!("{}: changed, {change}", format_coord(coord));
+							} else {
+								summary.identical += 1;
+							}
+						}
+						(Some(_), None) => {
+							if let Some(patch) = patch.as_deref_mut() {
+								patch.set_tile(coord, None)?;
+							}
+							summary.removed += 1;
+							println!("{}: only in first", format_coord(coord));
+						}
+						(None, Some(blob2)) => {
+							if let Some(patch) = patch.as_deref_mut() {
+								patch.set_tile(coord, Some(blob2))?;
+							}
+							summary.added += 1;
+							println!("{}: only in second", format_coord(coord));
+						}
+						(None, None) => unreachable!(),
+					}
+				}
+				progress.inc((tiles1.len() + tiles2.len()) as u64);
+			}
+		}
+
+		progress.finish();
+		Ok(summary)
+	}
+
+	/// Reads all tiles of a block, decompressed.
+	async fn get_tiles(&self, reader: &dyn TilesReaderTrait, bbox: &TileBBox) -> Result<HashMap<TileCoord3, Blob>> {
+		let compression = reader.get_parameters().tile_compression;
+		let mut tiles = HashMap::new();
+		for (coord, blob) in reader.get_bbox_tile_stream(bbox.clone()).await.collect().await {
+			let blob = decompress(blob, &compression).with_context(|| format!("decompressing tile {coord:?}"))?;
+			tiles.insert(coord, blob);
+		}
+		Ok(tiles)
+	}
+
+	/// Describes how the contents of two tiles differ, or returns `None` if only their encoding differs.
+	fn describe_change(
+		&self,
+		coord: &TileCoord3,
+		blob1: &Blob,
+		blob2: &Blob,
+		images: Option<&Path>,
+	) -> Result<Option<String>> {
+		match self.kind {
+			ContentKind::Raster => self.describe_raster_change(coord, blob1, blob2, images),
+			ContentKind::Vector => describe_vector_change(coord, blob1, blob2),
+			ContentKind::Other => Ok(Some(format!("{} bytes -> {} bytes", blob1.len(), blob2.len()))),
+		}
+	}
+
+	fn describe_raster_change(
+		&self,
+		coord: &TileCoord3,
+		blob1: &Blob,
+		blob2: &Blob,
+		images: Option<&Path>,
+	) -> Result<Option<String>> {
+		let image1 = blob2image(blob1, self.reader1.get_parameters().tile_format)
+			.with_context(|| format!("decoding tile {coord:?} of first container"))?;
+		let image2 = blob2image(blob2, self.reader2.get_parameters().tile_format)
+			.with_context(|| format!("decoding tile {coord:?} of second container"))?;
+		let diff = ImageDiff::new(&image1, &image2).with_context(|| format!("comparing tile {coord:?}"))?;
+		if diff.is_identical() {
+			return Ok(None);
+		}
+
+		if let Some(directory) = images {
+			let path = directory.join(format!("{}.png", format_coord(coord)));
+			fs::create_dir_all(path.parent().unwrap())?;
+			fs::write(&path, image2blob(&diff.render(), TileFormat::PNG)?.as_slice())
+				.with_context(|| format!("writing {path:?}"))?;
+		}
+
+		Ok(Some(format!(
+			"{} of {} pixels ({:.2} %), PSNR {:.1} dB",
+			diff.changed_pixels,
+			diff.pixel_count,
+			100.0 * diff.changed_pixels as f64 / diff.pixel_count as f64,
+			diff.psnr()
+		)))
+	}
+}
+
+/// Compares the features of two vector tiles, ignoring their order and the encoding of properties.
+fn describe_vector_change(coord: &TileCoord3, blob1: &Blob, blob2: &Blob) -> Result<Option<String>> {
+	let tile1 = VectorTile::from_blob(blob1).with_context(|| format!("decoding tile {coord:?} of first container"))?;
+	let tile2 = VectorTile::from_blob(blob2).with_context(|| format!("decoding tile {coord:?} of second container"))?;
+	let diff = VectorTileDiff::new(&tile1, &tile2).with_context(|| format!("comparing tile {coord:?}"))?;
+	if diff.is_unchanged() {
+		return Ok(None);
+	}
+
+	let layers: Vec<String> = diff
+		.iter_changed_layers()
+		.map(|(name, layer)| format!("{name}: +{} -{}", layer.added, layer.removed))
+		.collect();
+	Ok(Some(layers.join(", ")))
+}
+
+fn format_coord(coord: &TileCoord3) -> String {
+	format!("{}/{}/{}", coord.z, coord.x, coord.y)
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tests::run_command;
+
+	#[test]
+	fn test_local() {
+		run_command(vec![
+			"versatiles",
+			"diff",
+			"-q",
+			"../testdata/berlin.mbtiles",
+			"../testdata/berlin.pmtiles",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_images_require_raster() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let error = run_command(vec![
+			"versatiles",
+			"diff",
+			"-q",
+			"--images",
+			dir.path().to_str().unwrap(),
+			"../testdata/berlin.mbtiles",
+			"../testdata/berlin.pmtiles",
+		])
+		.unwrap_err();
+		assert!(error.to_string().contains("raster tiles"));
+	}
+}
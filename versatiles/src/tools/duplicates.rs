@@ -0,0 +1,170 @@
+//! Counts duplicated tiles in a tile container.
+//!
+//! Every tile is hashed. For every zoom level, the report lists the number of tiles, the number of distinct tiles and
+//! the share of duplicates. The most frequently duplicated tiles are listed with their size and an example
+//! coordinate. Storing every distinct tile only once would save the size of all duplicates, which shows whether
+//! deduplicating the container is worthwhile.
+
+use anyhow::Result;
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt::Write,
+};
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{Blob, TileCoord3, TilesReaderTrait},
+};
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Number of most frequently duplicated tiles that are listed.
+const MAX_LISTED_BLOBS: usize = 10;
+
+#[derive(Debug, Default)]
+struct LevelStats {
+	tiles: u64,
+	bytes: u64,
+	distinct: HashSet<u128>,
+}
+
+#[derive(Debug)]
+struct BlobStats {
+	size: u64,
+	count: u64,
+	/// The first coordinate where this tile was found.
+	coord: TileCoord3,
+}
+
+#[derive(Debug, Default)]
+pub struct DuplicateStats {
+	levels: BTreeMap<u8, LevelStats>,
+	blobs: HashMap<u128, BlobStats>,
+}
+
+impl DuplicateStats {
+	pub async fn from_reader(reader: &dyn TilesReaderTrait) -> Result<DuplicateStats> {
+		let pyramid = &reader.get_parameters().bbox_pyramid;
+		let mut progress = get_progress_bar("hashing tiles", pyramid.count_tiles());
+
+		let mut stats = DuplicateStats::default();
+		for bbox in pyramid.iter_levels() {
+			reader
+				.get_bbox_tile_stream(bbox.clone())
+				.await
+				.for_each_sync(|(coord, blob)| stats.add(&coord, &blob))
+				.await;
+			progress.inc(bbox.count_tiles());
+		}
+		progress.finish();
+
+		Ok(stats)
+	}
+
+	pub fn add(&mut self, coord: &TileCoord3, blob: &Blob) {
+		let hash = xxh3_128(blob.as_slice());
+		let size = blob.len();
+
+		let level = self.levels.entry(coord.z).or_default();
+		level.tiles += 1;
+		level.bytes += size;
+		level.distinct.insert(hash);
+
+		self
+			.blobs
+			.entry(hash)
+			.and_modify(|entry| entry.count += 1)
+			.or_insert(BlobStats {
+				size,
+				count: 1,
+				coord: *coord,
+			});
+	}
+
+	pub fn count_tiles(&self) -> u64 {
+		self.levels.values().map(|level| level.tiles).sum()
+	}
+
+	pub fn count_distinct(&self) -> u64 {
+		self.blobs.len() as u64
+	}
+
+	/// Returns the number of bytes that storing every distinct tile only once would save.
+	pub fn savings(&self) -> u64 {
+		self.blobs.values().map(|blob| blob.size * (blob.count - 1)).sum()
+	}
+
+	pub fn as_report(&self) -> String {
+		let mut report = String::new();
+
+		writeln!(report, "zoom |      tiles |   distinct | duplicates").unwrap();
+		for (z, level) in self.levels.iter() {
+			let distinct = level.distinct.len() as u64;
+			writeln!(
+				report,
+				"{z:4} | {:10} | {distinct:10} | {:9.1} %",
+				level.tiles,
+				percent(level.tiles - distinct, level.tiles)
+			)
+			.unwrap();
+		}
+
+		let mut blobs: Vec<(&u128, &BlobStats)> = self.blobs.iter().filter(|(_, blob)| blob.count > 1).collect();
+		blobs.sort_unstable_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+		if !blobs.is_empty() {
+			writeln!(report, "most duplicated tiles:").unwrap();
+			for (hash, blob) in blobs.iter().take(MAX_LISTED_BLOBS) {
+				writeln!(
+					report,
+					"  {hash:032x}: {} times, {} bytes, e.g. {}/{}/{}",
+					blob.count, blob.size, blob.coord.z, blob.coord.x, blob.coord.y
+				)
+				.unwrap();
+			}
+		}
+
+		let tiles = self.count_tiles();
+		let bytes: u64 = self.levels.values().map(|level| level.bytes).sum();
+		let savings = self.savings();
+		writeln!(
+			report,
+			"{} of {tiles} tiles are distinct, deduplication would save {savings} of {bytes} bytes ({:.1} %)",
+			self.count_distinct(),
+			percent(savings, bytes)
+		)
+		.unwrap();
+		report
+	}
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+	if total == 0 {
+		0.0
+	} else {
+		100.0 * part as f64 / total as f64
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn stats() -> Result<()> {
+		let mut stats = DuplicateStats::default();
+		let ocean = Blob::from("ocean");
+		stats.add(&TileCoord3::new(0, 0, 1)?, &ocean);
+		stats.add(&TileCoord3::new(1, 0, 1)?, &ocean);
+		stats.add(&TileCoord3::new(0, 1, 1)?, &Blob::from("land"));
+		stats.add(&TileCoord3::new(0, 0, 2)?, &ocean);
+
+		assert_eq!(stats.count_tiles(), 4);
+		assert_eq!(stats.count_distinct(), 2);
+		assert_eq!(stats.savings(), 10);
+
+		let report = stats.as_report();
+		assert!(report.contains("   1 |          3 |          2 |      33.3 %"));
+		assert!(report.contains("   2 |          1 |          1 |       0.0 %"));
+		assert!(report.contains(": 3 times, 5 bytes, e.g. 1/0/0"));
+		assert!(report.ends_with("2 of 4 tiles are distinct, deduplication would save 10 of 19 bytes (52.6 %)\n"));
+		Ok(())
+	}
+}
@@ -0,0 +1,262 @@
+//! Updates the tiles of a `*.versatiles` container that are listed in a tile expiry file.
+//!
+//! Expiry files are written by osm2pgsql and imposm and list one tile per line as `z/x/y`. The listed tiles are
+//! fetched from a source, either a tile container or an XYZ endpoint (e.g. a tile generator), and patched into the
+//! container in place. Tiles that are missing in the source are removed from the container.
+//!
+//! Only the new tiles and the changed indexes are appended to the container. Replaced tiles stay in the file as unused
+//! data, so convert the container from time to time to compact it.
+
+use super::scrape::{RequestBudget, Scraper, TileUrlTemplate};
+use anyhow::{ensure, Context, Result};
+use futures::{stream, StreamExt};
+use reqwest::Client;
+use std::{fs, path::PathBuf, time::Duration};
+use versatiles_container::{get_reader, VersaTilesPatcher};
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{Blob, TileCompression, TileCoord3, TilesReaderTrait},
+	utils::{compress, recompress},
+};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+pub struct Subcommand {
+	/// *.versatiles container to update
+	#[arg(required = true)]
+	container: PathBuf,
+
+	/// expiry file with one tile per line, e.g. "14/8802/5373"
+	#[arg(required = true)]
+	expiry_file: PathBuf,
+
+	/// source of the fresh tiles: a tile container or a URL template,
+	/// e.g. "https://tiles.example.org/{z}/{x}/{y}.pbf"
+	#[arg(required = true, verbatim_doc_comment)]
+	source: String,
+
+	/// also update the parent tiles of the listed tiles down to this zoom level
+	#[arg(long, value_name = "int", display_order = 1)]
+	min_zoom: Option<u8>,
+
+	/// number of tiles fetched in parallel
+	#[arg(long, value_name = "int", default_value = "4", display_order = 2)]
+	concurrency: usize,
+
+	/// retry failed requests to a URL template this many times
+	#[arg(long, value_name = "int", default_value = "3", display_order = 2)]
+	retries: u32,
+
+	/// user agent sent with every request to a URL template
+	#[arg(long, default_value = concat!("versatiles/", env!("CARGO_PKG_VERSION")), display_order = 2)]
+	user_agent: String,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	ensure!(arguments.concurrency > 0, "concurrency must be at least 1");
+
+	let text = fs::read_to_string(&arguments.expiry_file)
+		.with_context(|| format!("reading expiry file {:?}", arguments.expiry_file))?;
+	let coords = parse_expiry_list(&text, arguments.min_zoom)?;
+
+	let path = std::path::absolute(&arguments.container)?;
+	let mut patcher = VersaTilesPatcher::open_path(&path)
+		.await
+		.with_context(|| format!("opening container {path:?}"))?;
+	let compression = *patcher.get_tile_compression();
+
+	let source = if arguments.source.contains("{z}") {
+		TileSource::Endpoint(Scraper {
+			client: Client::builder()
+				.user_agent(&arguments.user_agent)
+				.timeout(Duration::from_secs(60))
+				.build()?,
+			template: TileUrlTemplate::new(&arguments.source)?,
+			delay: Duration::ZERO,
+			retries: arguments.retries,
+			budget: RequestBudget::new(None),
+		})
+	} else {
+		let reader = get_reader(&arguments.source).await?;
+		let source_format = reader.get_parameters().tile_format;
+		ensure!(
+			&source_format == patcher.get_tile_format(),
+			"tile format of the source ({source_format:?}) does not match the container ({:?})",
+			patcher.get_tile_format()
+		);
+		TileSource::Container(reader)
+	};
+
+	eprintln!("update {} tiles", coords.len());
+	let mut progress = get_progress_bar("update tiles", coords.len() as u64);
+
+	let source = &source;
+	let mut tiles = stream::iter(coords)
+		.map(|coord| async move { (coord, source.get_tile(&coord, &compression).await) })
+		.buffered(arguments.concurrency);
+
+	while let Some((coord, result)) = tiles.next().await {
+		let blob = result.with_context(|| format!("fetching tile {coord:?}"))?;
+		patcher.set_tile(&coord, blob).await?;
+		progress.inc(1);
+	}
+	progress.finish();
+
+	patcher.finish()
+}
+
+/// Where the fresh tiles come from.
+enum TileSource {
+	Container(Box<dyn TilesReaderTrait>),
+	Endpoint(Scraper),
+}
+
+impl TileSource {
+	/// Fetches a tile and compresses it with `compression`. Returns `None` if the source has no such tile.
+	async fn get_tile(&self, coord: &TileCoord3, compression: &TileCompression) -> Result<Option<Blob>> {
+		let blob = match self {
+			TileSource::Container(reader) => match reader.get_tile_data(coord).await? {
+				Some(blob) => Some(recompress(
+					blob,
+					&reader.get_parameters().tile_compression,
+					compression,
+				)?),
+				None => None,
+			},
+			TileSource::Endpoint(scraper) => match scraper.get_tile(coord).await? {
+				Some(blob) => Some(compress(blob, compression)?),
+				None => None,
+			},
+		};
+		Ok(blob)
+	}
+}
+
+/// Parses an expiry list with one `z/x/y` tile per line. Empty lines and lines starting with `#` are ignored.
+/// A line can also list a run of tiles in a row as `z/x_min-x_max/y`, as printed by `versatiles list --format runs`.
+///
+/// If `min_zoom` is set, the parent tiles down to this zoom level are added. The result is sorted and contains
+/// every tile only once.
+pub(super) fn parse_expiry_list(text: &str, min_zoom: Option<u8>) -> Result<Vec<TileCoord3>> {
+	let mut coords: Vec<TileCoord3> = Vec::new();
+
+	for (index, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let parse = || -> Result<Vec<TileCoord3>> {
+			let parts: Vec<&str> = line.split('/').collect();
+			ensure!(parts.len() == 3, "expected \"z/x/y\"");
+			let (x_min, x_max) = match parts[1].split_once('-') {
+				Some((x_min, x_max)) => (x_min.parse()?, x_max.parse()?),
+				None => (parts[1].parse()?, parts[1].parse()?),
+			};
+			ensure!(x_min <= x_max, "run of tiles is empty");
+			let last = TileCoord3::new(x_max, parts[2].parse()?, parts[0].parse()?)?;
+			let max = 1u64 << last.z;
+			ensure!(
+				(last.x as u64) < max && (last.y as u64) < max,
+				"tile is outside of zoom level {}",
+				last.z
+			);
+			(x_min..=x_max).map(|x| TileCoord3::new(x, last.y, last.z)).collect()
+		};
+		let run = parse().with_context(|| format!("invalid tile {line:?} in line {}", index + 1))?;
+
+		for mut coord in run {
+			coords.push(coord);
+			if let Some(min_zoom) = min_zoom {
+				while coord.z > min_zoom {
+					coord = TileCoord3::new(coord.x >> 1, coord.y >> 1, coord.z - 1)?;
+					coords.push(coord);
+				}
+			}
+		}
+	}
+
+	coords.sort_by_key(|c| (c.z, c.y, c.x));
+	coords.dedup();
+	Ok(coords)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+	use versatiles_container::VersaTilesReader;
+	use versatiles_core::utils::decompress;
+
+	#[test]
+	fn expiry_list() -> Result<()> {
+		let coords = parse_expiry_list("14/8802/5373\n\n# comment\n14/8803/5373\n 14/8802/5373 \n", Some(12))?;
+		assert_eq!(
+			format!("{coords:?}"),
+			"[TileCoord3(2200, 1343, 12), TileCoord3(4401, 2686, 13), TileCoord3(8802, 5373, 14), TileCoord3(8803, 5373, 14)]"
+		);
+
+		assert_eq!(parse_expiry_list("3/1/2", None)?.len(), 1);
+		assert_eq!(
+			format!("{:?}", parse_expiry_list("3/1-3/2\n3/5/2", None)?),
+			"[TileCoord3(1, 2, 3), TileCoord3(2, 2, 3), TileCoord3(3, 2, 3), TileCoord3(5, 2, 3)]"
+		);
+		assert!(parse_expiry_list("3/3-1/2", None).is_err());
+
+		let error = format!("{:#}", parse_expiry_list("3/1/2\n3/8/2", None).unwrap_err());
+		assert_eq!(
+			error,
+			"invalid tile \"3/8/2\" in line 2: tile is outside of zoom level 3"
+		);
+
+		let error = format!("{:#}", parse_expiry_list("3,1,2", None).unwrap_err());
+		assert_eq!(error, "invalid tile \"3,1,2\" in line 1: expected \"z/x/y\"");
+		Ok(())
+	}
+
+	#[test]
+	fn expire() -> Result<()> {
+		let dir = TempDir::new()?;
+		let container = dir.path().join("berlin.versatiles");
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"-q",
+			"../testdata/berlin.mbtiles",
+			container.to_str().unwrap(),
+		])?;
+
+		// the source contains only one of the expired tiles
+		let source = dir.path().join("source");
+		fs::create_dir_all(source.join("14/8802"))?;
+		fs::write(source.join("14/8802/5373.pbf"), "fresh tile")?;
+
+		let expiry_file = dir.path().join("expired.txt");
+		fs::write(&expiry_file, "14/8802/5373\n14/8803/5373\n")?;
+
+		run_command(vec![
+			"versatiles",
+			"expire",
+			"-q",
+			container.to_str().unwrap(),
+			expiry_file.to_str().unwrap(),
+			source.to_str().unwrap(),
+			"--min-zoom",
+			"13",
+		])?;
+
+		let runtime = tokio::runtime::Runtime::new()?;
+		runtime.block_on(async {
+			let reader = VersaTilesReader::open_path(&container).await?;
+			let compression = reader.get_parameters().tile_compression;
+
+			let tile = reader.get_tile_data(&TileCoord3::new(8802, 5373, 14)?).await?.unwrap();
+			assert_eq!(decompress(tile, &compression)?.as_str(), "fresh tile");
+			assert_eq!(reader.get_tile_data(&TileCoord3::new(8803, 5373, 14)?).await?, None);
+			assert_eq!(reader.get_tile_data(&TileCoord3::new(4401, 2686, 13)?).await?, None);
+			assert!(reader.get_tile_data(&TileCoord3::new(4400, 2686, 13)?).await?.is_some());
+			Ok(())
+		})
+	}
+}
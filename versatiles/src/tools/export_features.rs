@@ -0,0 +1,190 @@
+//! Exports the features of a vector tile container into one file per layer, to get the data back out of the tiles,
+//! e.g. to analyse it in QGIS.
+//!
+//! All tiles of one zoom level are read, by default the highest. Features are clipped to their tile, so that
+//! features in the buffer around the tiles are exported only once. With `--merge`, the parts of a feature that were
+//! cut at tile boundaries become a single feature again.
+//!
+//! Every layer is written as `<layer>.geojson` or, with `--format flatgeobuf`, as `<layer>.fgb` into the output
+//! directory.
+
+use super::convert::parse_bbox;
+use anyhow::{ensure, Context, Result};
+use std::{
+	fs::{self, File},
+	io::{BufWriter, Write},
+	path::PathBuf,
+};
+use versatiles_container::get_reader;
+use versatiles_core::json::{JsonObject, JsonValue};
+use versatiles_geometry::{write_flatgeobuf, GeoFeature};
+use versatiles_pipeline::{FeatureExportOptions, FeatureExportReader};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile container with vector tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// directory for the exported layers, is created if it doesn't exist
+	#[arg(required = true)]
+	output_directory: PathBuf,
+
+	/// file format of the exported layers
+	#[arg(long, short, value_enum, default_value_t = ExportFormat::Geojson)]
+	format: ExportFormat,
+
+	/// zoom level of the exported tiles, defaults to the highest zoom level of the container
+	#[arg(long, short, value_name = "int")]
+	zoom: Option<u8>,
+
+	/// export only the tiles inside a bounding box
+	#[arg(
+		long,
+		short,
+		value_name = "lon_min,lat_min,lon_max,lat_max",
+		allow_hyphen_values = true
+	)]
+	bbox: Option<String>,
+
+	/// export only these layers, e.g. "water,streets"
+	#[arg(long, value_name = "names", value_delimiter = ',')]
+	layers: Vec<String>,
+
+	/// merge the parts of features that were cut at tile boundaries
+	#[arg(long)]
+	merge: bool,
+
+	/// replace files that already exist
+	#[arg(long)]
+	overwrite: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+	/// GeoJSON feature collections
+	Geojson,
+	/// FlatGeobuf files, without a spatial index
+	Flatgeobuf,
+}
+
+impl ExportFormat {
+	fn extension(&self) -> &str {
+		match self {
+			ExportFormat::Geojson => "geojson",
+			ExportFormat::Flatgeobuf => "fgb",
+		}
+	}
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let reader = FeatureExportReader::new(get_reader(&arguments.filename).await?)?;
+
+	let options = FeatureExportOptions {
+		zoom: arguments.zoom,
+		bbox: arguments.bbox.as_deref().map(parse_bbox).transpose()?,
+		layers: (!arguments.layers.is_empty()).then(|| arguments.layers.clone()),
+		merge: arguments.merge,
+	};
+	let layers = reader.export(&options).await?;
+	if layers.is_empty() {
+		log::warn!("no features found in {:?}", arguments.filename);
+		return Ok(());
+	}
+
+	let directory = &arguments.output_directory;
+	fs::create_dir_all(directory).with_context(|| format!("creating directory {directory:?}"))?;
+
+	let paths: Vec<PathBuf> = layers
+		.iter()
+		.map(|(name, _)| directory.join(format!("{}.{}", get_file_name(name), arguments.format.extension())))
+		.collect();
+	for path in paths.iter() {
+		ensure!(
+			arguments.overwrite || !path.exists(),
+			"file {path:?} already exists, use --overwrite to replace it"
+		);
+	}
+
+	for ((name, features), path) in layers.iter().zip(paths.iter()) {
+		let mut writer = BufWriter::new(File::create(path).with_context(|| format!("creating {path:?}"))?);
+		match arguments.format {
+			ExportFormat::Geojson => writer.write_all(to_feature_collection(features).stringify().as_bytes())?,
+			ExportFormat::Flatgeobuf => write_flatgeobuf(&mut writer, name, features)?,
+		}
+		writer.flush().with_context(|| format!("writing {path:?}"))?;
+		log::info!("exported {} features of layer {name:?} to {path:?}", features.len());
+	}
+	Ok(())
+}
+
+/// Returns a file name for a layer, with all characters that aren't letters, digits, `-` or `_` replaced.
+fn get_file_name(layer_name: &str) -> String {
+	layer_name
+		.chars()
+		.map(|c| {
+			if c.is_alphanumeric() || c == '-' || c == '_' {
+				c
+			} else {
+				'_'
+			}
+		})
+		.collect()
+}
+
+fn to_feature_collection(features: &[GeoFeature]) -> JsonObject {
+	let features = features
+		.iter()
+		.map(|feature| JsonValue::Object(feature.to_json()))
+		.collect::<Vec<_>>();
+
+	let mut json = JsonObject::default();
+	json.set("type", JsonValue::from("FeatureCollection"));
+	json.set("features", JsonValue::from(features));
+	json
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+
+	#[test]
+	fn file_name() {
+		assert_eq!(get_file_name("water_lines"), "water_lines");
+		assert_eq!(get_file_name("../roads 2"), "___roads_2");
+	}
+
+	#[test]
+	fn export() -> Result<()> {
+		let dir = TempDir::new()?;
+		let output = dir.path().to_str().unwrap();
+		let export = |args: &[&str]| {
+			let command = [
+				&["versatiles", "export-features", "../testdata/berlin.mbtiles", output],
+				args,
+			]
+			.concat();
+			run_command(command)
+		};
+
+		export(&["-z", "10", "--layers", "water_polygons,streets"])?;
+		let water = fs::read_to_string(dir.path().join("water_polygons.geojson"))?;
+		assert!(water.starts_with(r#"{"features":[{"geometry":"#));
+		assert!(dir.path().join("streets.geojson").exists());
+		assert!(!dir.path().join("buildings.geojson").exists());
+
+		// files are not replaced by accident
+		assert!(export(&["-z", "10", "--layers", "streets"]).is_err());
+
+		export(&["-z", "10", "--merge", "--format", "flatgeobuf", "--layers", "streets"])?;
+		let streets = fs::read(dir.path().join("streets.fgb"))?;
+		assert_eq!(&streets[0..8], b"fgb\x03fgb\x00");
+
+		assert!(export(&["-z", "20"]).is_err());
+		Ok(())
+	}
+}
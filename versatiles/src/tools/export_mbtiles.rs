@@ -0,0 +1,95 @@
+//! Exports a container as MBTiles, for tools that can only read MBTiles.
+//!
+//! With `--stream`, the MBTiles file is written to stdout, e.g. to pipe it to another machine
+//! without storing a copy locally. SQLite can't write to a stream, so the file is built in the
+//! temporary directory first and copied to stdout when it is complete.
+
+use anyhow::{ensure, Context, Result};
+use std::path::PathBuf;
+use versatiles_container::{get_reader, ConversionPipeline, ConversionTarget};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// container to export, e.g. a *.versatiles file
+	#[arg(required = true)]
+	input_file: String,
+
+	/// *.mbtiles file to write
+	output_file: Option<PathBuf>,
+
+	/// write the MBTiles file to stdout instead of a file
+	#[arg(long)]
+	stream: bool,
+
+	/// replace the output file if it already exists
+	#[arg(long)]
+	overwrite: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let target = if arguments.stream {
+		ensure!(
+			arguments.output_file.is_none(),
+			"--stream writes to stdout, so no output file can be used"
+		);
+		ConversionTarget::Stdout(String::from("mbtiles"))
+	} else {
+		let output_file = arguments
+			.output_file
+			.as_ref()
+			.context("an output file is required, or use --stream to write to stdout")?;
+		ensure!(
+			output_file.extension().is_some_and(|extension| extension == "mbtiles"),
+			"output file {output_file:?} must end with .mbtiles"
+		);
+		ensure!(
+			arguments.overwrite || !output_file.is_file(),
+			"output file {output_file:?} already exists, use --overwrite to replace it"
+		);
+		ConversionTarget::Path(output_file.clone())
+	};
+
+	let reader = get_reader(&arguments.input_file).await?;
+	ConversionPipeline::new(reader, target).run().await
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tests::run_command;
+	use anyhow::Result;
+	use std::fs;
+
+	#[test]
+	fn test_export() -> Result<()> {
+		fs::create_dir("../tmp/").unwrap_or_default();
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=8",
+			"../testdata/berlin.mbtiles",
+			"../tmp/export.versatiles",
+		])?;
+
+		run_command(vec![
+			"versatiles",
+			"export-mbtiles",
+			"--overwrite",
+			"../tmp/export.versatiles",
+			"../tmp/export.mbtiles",
+		])?;
+		assert!(fs::metadata("../tmp/export.mbtiles")?.len() > 0);
+
+		for args in [
+			vec!["../tmp/export.versatiles"],
+			vec!["../tmp/export.versatiles", "../tmp/export.pmtiles"],
+			vec!["--stream", "../tmp/export.versatiles", "../tmp/export.mbtiles"],
+		] {
+			let command = [vec!["versatiles", "export-mbtiles", "--overwrite"], args.clone()].concat();
+			assert!(run_command(command).is_err(), "{args:?}");
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,403 @@
+//! Converts fonts into MapLibre SDF glyphs.
+//!
+//! Every font becomes a font stack, named after the font file. Its glyphs are rendered as signed distance fields
+//! and written as protobuf files in ranges of 256 code points, e.g. `Noto Sans/0-255.pbf`, `Noto Sans/256-511.pbf`.
+//! The files are written into a directory, or added to a *.tar file that can be served as static content.
+//!
+//! The parameters match the glyphs of fontnik: a font size of 24 pixels, a buffer of 3 pixels around every glyph,
+//! a radius of 8 pixels and a cutoff of 0.25.
+
+use super::assets::write_assets;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use anyhow::{ensure, Context, Result};
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_core::{
+	io::{ValueWriter, ValueWriterBlob},
+	types::Blob,
+};
+
+const FONT_SIZE: f32 = 24.0;
+const BUFFER: usize = 3;
+const RADIUS: f64 = 8.0;
+const CUTOFF: f64 = 0.25;
+const RANGE_SIZE: u32 = 256;
+const RANGE_COUNT: u32 = 256;
+const INF: f64 = 1e20;
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+pub struct Subcommand {
+	/// a font file (*.ttf or *.otf) or a directory containing font files
+	/// the font stack names are the filenames without extension
+	#[arg(required = true, verbatim_doc_comment)]
+	input: PathBuf,
+
+	/// output directory, or a *.tar file to add the glyphs to
+	#[arg(required = true)]
+	output: PathBuf,
+
+	/// directory inside the output, e.g. "assets/glyphs"
+	#[arg(long)]
+	prefix: Option<String>,
+}
+
+pub fn run(arguments: &Subcommand) -> Result<()> {
+	let filenames = get_font_filenames(&arguments.input)?;
+
+	let mut files: Vec<(String, Blob)> = Vec::new();
+	for filename in filenames {
+		let name = filename.file_stem().unwrap().to_string_lossy().to_string();
+		eprintln!("render glyphs of {name:?}");
+
+		let data = fs::read(&filename).with_context(|| format!("reading font {filename:?}"))?;
+		let font = FontVec::try_from_vec(data).with_context(|| format!("parsing font {filename:?}"))?;
+
+		let path = match &arguments.prefix {
+			Some(prefix) => format!("{}/{name}", prefix.trim_end_matches('/')),
+			None => name.clone(),
+		};
+		for (range, blob) in render_font(&font, &name)? {
+			files.push((format!("{path}/{range}.pbf"), blob));
+		}
+	}
+
+	write_assets(&arguments.output, files)
+}
+
+/// Returns the font files, sorted by name.
+fn get_font_filenames(input: &Path) -> Result<Vec<PathBuf>> {
+	if !input.is_dir() {
+		return Ok(vec![input.to_path_buf()]);
+	}
+
+	let mut paths: Vec<PathBuf> = fs::read_dir(input)
+		.with_context(|| format!("reading directory {input:?}"))?
+		.map(|entry| entry.map(|e| e.path()))
+		.collect::<Result<_, _>>()?;
+	paths.retain(|p| p.extension().is_some_and(|e| e == "ttf" || e == "otf"));
+	paths.sort();
+	ensure!(!paths.is_empty(), "no *.ttf or *.otf files found in {input:?}");
+	Ok(paths)
+}
+
+/// A rendered glyph, as defined in the MapLibre glyph protobuf.
+#[derive(Debug, PartialEq)]
+struct Glyph {
+	id: u32,
+	/// Signed distance field, including the buffer.
+	bitmap: Vec<u8>,
+	width: u32,
+	height: u32,
+	left: i32,
+	top: i32,
+	advance: u32,
+}
+
+impl Glyph {
+	fn to_blob(&self) -> Result<Blob> {
+		let mut writer = ValueWriterBlob::new_le();
+		writer.write_pbf_key(1, 0)?;
+		writer.write_varint(self.id as u64)?;
+		if !self.bitmap.is_empty() {
+			writer.write_pbf_key(2, 2)?;
+			writer.write_pbf_blob(&Blob::from(&self.bitmap))?;
+		}
+		writer.write_pbf_key(3, 0)?;
+		writer.write_varint(self.width as u64)?;
+		writer.write_pbf_key(4, 0)?;
+		writer.write_varint(self.height as u64)?;
+		writer.write_pbf_key(5, 0)?;
+		writer.write_svarint(self.left as i64)?;
+		writer.write_pbf_key(6, 0)?;
+		writer.write_svarint(self.top as i64)?;
+		writer.write_pbf_key(7, 0)?;
+		writer.write_varint(self.advance as u64)?;
+		Ok(writer.into_blob())
+	}
+}
+
+/// Renders all ranges of a font. Returns the range names, e.g. "0-255", and the encoded glyphs.
+fn render_font(font: &FontVec, name: &str) -> Result<Vec<(String, Blob)>> {
+	let scale = PxScale::from(FONT_SIZE * font.height_unscaled() / font.units_per_em().unwrap_or(1000.0));
+	let font = font.as_scaled(scale);
+
+	let mut ranges: BTreeMap<u32, Vec<Glyph>> = BTreeMap::new();
+	for (_, c) in font.font().codepoint_ids() {
+		let id = c as u32;
+		if id < RANGE_SIZE * RANGE_COUNT {
+			ranges.entry(id / RANGE_SIZE).or_default().push(render_glyph(&font, c));
+		}
+	}
+
+	(0..RANGE_COUNT)
+		.map(|index| {
+			let start = index * RANGE_SIZE;
+			let range = format!("{start}-{}", start + RANGE_SIZE - 1);
+			let mut glyphs = ranges.remove(&index).unwrap_or_default();
+			glyphs.sort_by_key(|g| g.id);
+			let blob = encode_range(name, &range, &glyphs)?;
+			Ok((range, blob))
+		})
+		.collect()
+}
+
+/// Encodes a range of glyphs as a protobuf message with a single font stack.
+fn encode_range(name: &str, range: &str, glyphs: &[Glyph]) -> Result<Blob> {
+	let mut stack = ValueWriterBlob::new_le();
+	stack.write_pbf_key(1, 2)?;
+	stack.write_pbf_string(name)?;
+	stack.write_pbf_key(2, 2)?;
+	stack.write_pbf_string(range)?;
+	for glyph in glyphs {
+		stack.write_pbf_key(3, 2)?;
+		stack.write_pbf_blob(&glyph.to_blob()?)?;
+	}
+
+	let mut writer = ValueWriterBlob::new_le();
+	writer.write_pbf_key(1, 2)?;
+	writer.write_pbf_blob(&stack.into_blob())?;
+	Ok(writer.into_blob())
+}
+
+fn render_glyph<F: Font, SF: ScaleFont<F>>(font: &SF, c: char) -> Glyph {
+	let glyph = font.scaled_glyph(c);
+	let advance = font.h_advance(glyph.id).round() as u32;
+
+	let Some(outline) = font.outline_glyph(glyph) else {
+		// e.g. a space
+		return Glyph {
+			id: c as u32,
+			bitmap: vec![],
+			width: 0,
+			height: 0,
+			left: 0,
+			top: 0,
+			advance,
+		};
+	};
+
+	let bounds = outline.px_bounds();
+	let width = bounds.width() as usize;
+	let height = bounds.height() as usize;
+
+	let buffered_width = width + 2 * BUFFER;
+	let mut alpha = vec![0.0; buffered_width * (height + 2 * BUFFER)];
+	outline.draw(|x, y, coverage| {
+		let index = (y as usize + BUFFER) * buffered_width + x as usize + BUFFER;
+		if let Some(value) = alpha.get_mut(index) {
+			*value = coverage as f64;
+		}
+	});
+
+	Glyph {
+		id: c as u32,
+		bitmap: get_sdf(&alpha, buffered_width),
+		width: width as u32,
+		height: height as u32,
+		left: bounds.min.x as i32,
+		top: -bounds.min.y as i32,
+		advance,
+	}
+}
+
+/// Calculates a signed distance field from an alpha channel (values from 0 to 1), like TinySDF.
+fn get_sdf(alpha: &[f64], width: usize) -> Vec<u8> {
+	let height = alpha.len() / width;
+
+	let mut outer: Vec<f64> = alpha
+		.iter()
+		.map(|&a| match a {
+			a if a >= 1.0 => 0.0,
+			a if a <= 0.0 => INF,
+			a => (0.5 - a).max(0.0).powi(2),
+		})
+		.collect();
+	let mut inner: Vec<f64> = alpha
+		.iter()
+		.map(|&a| match a {
+			a if a >= 1.0 => INF,
+			a if a <= 0.0 => 0.0,
+			a => (a - 0.5).max(0.0).powi(2),
+		})
+		.collect();
+
+	edt(&mut outer, width, height);
+	edt(&mut inner, width, height);
+
+	outer
+		.iter()
+		.zip(inner.iter())
+		.map(|(o, i)| {
+			let d = o.sqrt() - i.sqrt();
+			(255.0 - 255.0 * (d / RADIUS + CUTOFF)).round().clamp(0.0, 255.0) as u8
+		})
+		.collect()
+}
+
+/// Two-dimensional squared Euclidean distance transform, by Felzenszwalb and Huttenlocher.
+fn edt(grid: &mut [f64], width: usize, height: usize) {
+	let size = width.max(height);
+	let mut f = vec![0.0; size];
+	let mut v = vec![0usize; size];
+	let mut z = vec![0.0; size + 1];
+
+	for x in 0..width {
+		edt_1d(grid, x, width, height, &mut f, &mut v, &mut z);
+	}
+	for y in 0..height {
+		edt_1d(grid, y * width, 1, width, &mut f, &mut v, &mut z);
+	}
+}
+
+fn edt_1d(
+	grid: &mut [f64],
+	offset: usize,
+	stride: usize,
+	length: usize,
+	f: &mut [f64],
+	v: &mut [usize],
+	z: &mut [f64],
+) {
+	v[0] = 0;
+	z[0] = -INF;
+	z[1] = INF;
+	f[0] = grid[offset];
+
+	let mut k: usize = 0;
+	for q in 1..length {
+		f[q] = grid[offset + q * stride];
+		let q2 = (q * q) as f64;
+		let mut s;
+		loop {
+			let r = v[k];
+			s = (f[q] - f[r] + q2 - (r * r) as f64) / (q - r) as f64 / 2.0;
+			if s > z[k] || k == 0 {
+				break;
+			}
+			k -= 1;
+		}
+		if s > z[k] {
+			k += 1;
+		}
+		v[k] = q;
+		z[k] = s;
+		z[k + 1] = INF;
+	}
+
+	let mut k = 0;
+	for q in 0..length {
+		while z[k + 1] < q as f64 {
+			k += 1;
+		}
+		let r = v[k];
+		let qr = q.abs_diff(r) as f64;
+		grid[offset + q * stride] = f[r] + qr * qr;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+	use versatiles_core::io::{ValueReader, ValueReaderSlice};
+
+	const FONT: &str = "../versatiles_pipeline/src/operations/read/from_debug/trim.ttf";
+
+	/// Decodes the font stack name, range and glyph ids of an encoded range.
+	fn decode_range(blob: &Blob) -> Result<(String, String, Vec<u32>)> {
+		let mut reader = ValueReaderSlice::new_le(blob.as_slice());
+		assert_eq!(reader.read_pbf_key()?, (1, 2));
+		let mut stack = reader.get_pbf_sub_reader()?;
+
+		let (mut name, mut range, mut ids) = (String::new(), String::new(), Vec::new());
+		while stack.has_remaining() {
+			match stack.read_pbf_key()? {
+				(1, 2) => name = stack.read_pbf_string()?,
+				(2, 2) => range = stack.read_pbf_string()?,
+				(3, 2) => {
+					let mut glyph = stack.get_pbf_sub_reader()?;
+					assert_eq!(glyph.read_pbf_key()?, (1, 0));
+					ids.push(glyph.read_varint()? as u32);
+				}
+				key => panic!("unexpected key {key:?}"),
+			}
+		}
+		Ok((name, range, ids))
+	}
+
+	#[test]
+	fn sdf() {
+		// a filled square in the middle of an empty image
+		let mut alpha = vec![0.0; 20 * 20];
+		for y in 5..15 {
+			for x in 5..15 {
+				alpha[y * 20 + x] = 1.0;
+			}
+		}
+		let sdf = get_sdf(&alpha, 20);
+
+		// inside values are above the edge value of 191, outside values below
+		assert!(sdf[10 * 20 + 10] > 191);
+		assert!(sdf[10 * 20 + 6] > 191);
+		assert!(sdf[10 * 20 + 3] < 191);
+		assert_eq!(sdf[0], 0);
+		// distances grow monotonically towards the center
+		for x in 1..10 {
+			assert!(sdf[10 * 20 + x] >= sdf[10 * 20 + x - 1]);
+		}
+	}
+
+	#[test]
+	fn render() -> Result<()> {
+		let font = FontVec::try_from_vec(fs::read(FONT)?)?;
+		let ranges = render_font(&font, "Trim")?;
+		assert_eq!(ranges.len(), 256);
+		assert_eq!(ranges[0].0, "0-255");
+		assert_eq!(ranges[255].0, "65280-65535");
+
+		let (name, range, ids) = decode_range(&ranges[0].1)?;
+		assert_eq!(name, "Trim");
+		assert_eq!(range, "0-255");
+		assert!(ids.contains(&('A' as u32)));
+		assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+		let scale = PxScale::from(FONT_SIZE * font.height_unscaled() / font.units_per_em().unwrap());
+		let glyph = render_glyph(&font.as_scaled(scale), 'A');
+		assert!(glyph.width > 0 && glyph.height > 0 && glyph.advance > 0);
+		assert_eq!(
+			glyph.bitmap.len(),
+			(glyph.width as usize + 2 * BUFFER) * (glyph.height as usize + 2 * BUFFER)
+		);
+
+		let glyph = render_glyph(&font.as_scaled(scale), ' ');
+		assert!(glyph.bitmap.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn command() -> Result<()> {
+		let dir = TempDir::new()?;
+		let output = dir.path().join("assets.tar");
+		run_command(vec![
+			"versatiles",
+			"glyphs",
+			FONT,
+			output.to_str().unwrap(),
+			"--prefix=glyphs",
+		])?;
+
+		let mut archive = tar::Archive::new(fs::File::open(&output)?);
+		let names: Vec<String> = archive
+			.entries()?
+			.map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+			.collect();
+		assert_eq!(names.len(), 256);
+		assert_eq!(names[0], "glyphs/trim/0-255.pbf");
+		Ok(())
+	}
+}
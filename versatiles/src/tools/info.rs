@@ -0,0 +1,205 @@
+//! Prints a short summary of tile containers, read only from their headers and indexes.
+//!
+//! No tiles are read, so the summary is printed within milliseconds, even for large or remote containers. The tile
+//! count is an upper bound derived from the bounding boxes of the zoom levels.
+
+use anyhow::Result;
+use std::fmt::Write;
+use versatiles_container::get_reader;
+use versatiles_core::{json::JsonObject, types::TilesReaderTrait};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile containers you want to summarize
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, num_args = 1.., verbatim_doc_comment)]
+	filenames: Vec<String>,
+
+	/// print one JSON object per container and line instead of text
+	#[arg(long)]
+	json: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	for (index, filename) in arguments.filenames.iter().enumerate() {
+		let reader = get_reader(filename).await?;
+		let summary = Summary::new(filename, &*reader);
+		if arguments.json {
+			println!("{}", summary.to_json().stringify());
+		} else {
+			if index > 0 {
+				println!();
+			}
+			print!("{}", summary.as_text());
+		}
+	}
+	Ok(())
+}
+
+/// Summary of a tile container.
+#[derive(Debug, PartialEq)]
+struct Summary {
+	filename: String,
+	container: String,
+	tile_format: String,
+	tile_compression: String,
+	zoom_range: Option<(u8, u8)>,
+	/// Upper bound of the number of tiles.
+	max_tile_count: u64,
+	bounds: Option<[f64; 4]>,
+	name: Option<String>,
+	attribution: Option<String>,
+}
+
+impl Summary {
+	fn new(filename: &str, reader: &dyn TilesReaderTrait) -> Summary {
+		let parameters = reader.get_parameters();
+		let pyramid = &parameters.bbox_pyramid;
+		let tilejson = reader.get_tilejson();
+
+		Summary {
+			filename: filename.to_string(),
+			container: reader.get_container_name().to_string(),
+			tile_format: parameters.tile_format.as_str().to_string(),
+			tile_compression: parameters.tile_compression.as_str().to_string(),
+			zoom_range: pyramid.get_zoom_min().zip(pyramid.get_zoom_max()),
+			max_tile_count: pyramid.count_tiles(),
+			bounds: tilejson
+				.bounds
+				.or_else(|| pyramid.get_geo_bbox())
+				.map(|bbox| bbox.as_array()),
+			name: tilejson.get_string("name"),
+			attribution: tilejson.get_string("attribution"),
+		}
+	}
+
+	fn as_text(&self) -> String {
+		let mut text = String::new();
+		let mut add = |key: &str, value: &str| writeln!(text, "{:<12} {value}", format!("{key}:")).unwrap();
+
+		add("file", &self.filename);
+		add("container", &self.container);
+		add("format", &self.tile_format);
+		add("compression", &self.tile_compression);
+		add(
+			"zoom",
+			&match self.zoom_range {
+				Some((min, max)) => format!("{min}-{max}"),
+				None => "no tiles".to_string(),
+			},
+		);
+		add("tiles", &format!("up to {}", self.max_tile_count));
+		if let Some(bounds) = &self.bounds {
+			add(
+				"bounds",
+				&bounds
+					.iter()
+					.map(|value| value.to_string())
+					.collect::<Vec<_>>()
+					.join(", "),
+			);
+		}
+		if let Some(name) = &self.name {
+			add("name", name);
+		}
+		if let Some(attribution) = &self.attribution {
+			add("attribution", attribution);
+		}
+		text
+	}
+
+	fn to_json(&self) -> JsonObject {
+		let mut json = JsonObject::default();
+		json.set("file", &self.filename);
+		json.set("container", &self.container);
+		json.set("tile_format", &self.tile_format);
+		json.set("tile_compression", &self.tile_compression);
+		if let Some((min, max)) = self.zoom_range {
+			json.set("minzoom", min);
+			json.set("maxzoom", max);
+		}
+		json.set("max_tile_count", self.max_tile_count as f64);
+		if let Some(bounds) = &self.bounds {
+			json.set("bounds", bounds.to_vec());
+		}
+		json.set_optional("name", &self.name);
+		json.set_optional("attribution", &self.attribution);
+		json
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use versatiles_core::types::{TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters};
+
+	fn get_summary() -> Summary {
+		Summary {
+			filename: "tiles.versatiles".to_string(),
+			container: "versatiles".to_string(),
+			tile_format: "pbf".to_string(),
+			tile_compression: "gzip".to_string(),
+			zoom_range: Some((0, 3)),
+			max_tile_count: 85,
+			bounds: Some([13.0, 52.0, 14.0, 53.0]),
+			name: Some("Berlin".to_string()),
+			attribution: None,
+		}
+	}
+
+	#[test]
+	fn as_text() {
+		assert_eq!(
+			get_summary().as_text(),
+			[
+				"file:        tiles.versatiles",
+				"container:   versatiles",
+				"format:      pbf",
+				"compression: gzip",
+				"zoom:        0-3",
+				"tiles:       up to 85",
+				"bounds:      13, 52, 14, 53",
+				"name:        Berlin",
+				""
+			]
+			.join("\n")
+		);
+	}
+
+	#[test]
+	fn to_json() {
+		assert_eq!(
+			get_summary().to_json().stringify(),
+			r#"{"bounds":[13,52,14,53],"container":"versatiles","file":"tiles.versatiles","max_tile_count":85,"maxzoom":3,"minzoom":0,"name":"Berlin","tile_compression":"gzip","tile_format":"pbf"}"#
+		);
+	}
+
+	#[test]
+	fn from_reader() -> Result<()> {
+		let reader = versatiles_container::MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_full(2),
+		))?;
+		let summary = Summary::new("mock", &reader);
+		assert_eq!(summary.zoom_range, Some((0, 2)));
+		assert_eq!(summary.max_tile_count, 21);
+		assert_eq!(summary.tile_format, "png");
+		Ok(())
+	}
+
+	#[test]
+	fn run() -> Result<()> {
+		run_command(vec![
+			"versatiles",
+			"info",
+			"../testdata/berlin.mbtiles",
+			"../testdata/berlin.pmtiles",
+		])?;
+		run_command(vec!["versatiles", "info", "--json", "../testdata/berlin.mbtiles"])?;
+		assert!(run_command(vec!["versatiles", "info", "../testdata/missing.mbtiles"]).is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,364 @@
+//! Checks the contents of a vector tile container against a set of rules.
+//!
+//! Every tile is decoded and checked. The report counts the violations of every rule and lists some examples, and
+//! the command fails if any rule is violated, so it can be used as a gate before publishing a tileset.
+
+use anyhow::{bail, ensure, Result};
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display, Write},
+	path::PathBuf,
+};
+use versatiles_container::get_reader;
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{Blob, TileCompression, TileCoord3, TileFormat, TilesReaderTrait},
+	utils::decompress,
+};
+use versatiles_geometry::{
+	vector_tile::{TilesetSchema, VectorTile},
+	GeoValue,
+};
+
+/// Number of violations that are listed per rule.
+const MAX_LISTED_VIOLATIONS: usize = 10;
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile container with vector tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// maximum number of features per layer in a tile
+	#[arg(long, value_name = "int")]
+	max_features: Option<usize>,
+
+	/// maximum length of a string property value, in bytes
+	#[arg(long, value_name = "int")]
+	max_value_length: Option<usize>,
+
+	/// check that every polygon starts with a clockwise wound exterior ring
+	#[arg(long)]
+	check_winding: bool,
+
+	/// tileset schema file, as written by "versatiles schema generate"
+	/// layers, zoom levels and attributes must match the schema
+	#[arg(long, value_name = "FILE", verbatim_doc_comment)]
+	schema: Option<PathBuf>,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let rules = LintRules {
+		max_features: arguments.max_features,
+		max_value_length: arguments.max_value_length,
+		check_winding: arguments.check_winding,
+		schema: match &arguments.schema {
+			Some(path) => Some(TilesetSchema::read_file(path)?),
+			None => None,
+		},
+	};
+	ensure!(
+		rules.has_rules(),
+		"no rules given, use --max-features, --max-value-length, --check-winding or --schema"
+	);
+
+	lint_container(&arguments.filename, &rules).await
+}
+
+/// Lints a container, prints the report and fails if any rule is violated.
+pub(super) async fn lint_container(filename: &str, rules: &LintRules) -> Result<()> {
+	let reader = get_reader(filename).await?;
+	let report = rules.lint(reader.as_ref()).await?;
+	print!("{}", report.as_report());
+
+	let violations = report.count_violations();
+	if violations > 0 {
+		bail!("found {violations} violations in {filename:?}");
+	}
+	Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Rule {
+	Decoding,
+	Schema,
+	FeatureCount,
+	ValueLength,
+	Winding,
+}
+
+impl Display for Rule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Rule::Decoding => "tile can be decoded",
+			Rule::Schema => "tile matches the schema",
+			Rule::FeatureCount => "feature count per layer",
+			Rule::ValueLength => "property value length",
+			Rule::Winding => "polygon winding",
+		})
+	}
+}
+
+#[derive(Debug, Default)]
+pub(super) struct LintRules {
+	pub(super) max_features: Option<usize>,
+	pub(super) max_value_length: Option<usize>,
+	pub(super) check_winding: bool,
+	pub(super) schema: Option<TilesetSchema>,
+}
+
+impl LintRules {
+	fn has_rules(&self) -> bool {
+		self.max_features.is_some() || self.max_value_length.is_some() || self.check_winding || self.schema.is_some()
+	}
+
+	async fn lint(&self, reader: &dyn TilesReaderTrait) -> Result<LintReport> {
+		let parameters = reader.get_parameters();
+		ensure!(
+			parameters.tile_format == TileFormat::PBF,
+			"only vector tiles can be linted"
+		);
+		let compression = parameters.tile_compression;
+		let pyramid = &parameters.bbox_pyramid;
+
+		let mut report = LintReport::default();
+		let mut progress = get_progress_bar("linting tiles", pyramid.count_tiles());
+		for bbox in pyramid.iter_levels() {
+			reader
+				.get_bbox_tile_stream(bbox.clone())
+				.await
+				.for_each_sync(|(coord, blob)| self.check_blob(&coord, blob, &compression, &mut report))
+				.await;
+			progress.inc(bbox.count_tiles());
+		}
+		progress.finish();
+
+		Ok(report)
+	}
+
+	fn check_blob(&self, coord: &TileCoord3, blob: Blob, compression: &TileCompression, report: &mut LintReport) {
+		report.tiles += 1;
+		match decompress(blob, compression).and_then(|blob| VectorTile::from_blob(&blob)) {
+			Ok(tile) => self.check_tile(coord, &tile, report),
+			Err(err) => report.add(Rule::Decoding, coord, format!("{err}")),
+		}
+	}
+
+	fn check_tile(&self, coord: &TileCoord3, tile: &VectorTile, report: &mut LintReport) {
+		if let Some(schema) = &self.schema {
+			match schema.validate_tile(coord.z, tile) {
+				Ok(violations) => {
+					for violation in violations {
+						report.add(Rule::Schema, coord, violation);
+					}
+				}
+				Err(err) => report.add(Rule::Decoding, coord, format!("{err}")),
+			}
+		}
+
+		for layer in tile.layers.iter() {
+			let name = &layer.name;
+
+			if let Some(max_features) = self.max_features {
+				let count = layer.features.len();
+				if count > max_features {
+					report.add(
+						Rule::FeatureCount,
+						coord,
+						format!("layer \"{name}\" has {count} features, more than {max_features}"),
+					);
+				}
+			}
+
+			if let Some(max_value_length) = self.max_value_length {
+				for value in layer.property_manager.iter_val() {
+					if let GeoValue::String(value) = value {
+						if value.len() > max_value_length {
+							report.add(
+								Rule::ValueLength,
+								coord,
+								format!(
+									"layer \"{name}\" has a value of {} bytes, longer than {max_value_length}",
+									value.len()
+								),
+							);
+						}
+					}
+				}
+			}
+
+			if self.check_winding {
+				for (index, feature) in layer.features.iter().enumerate() {
+					match feature.has_valid_winding() {
+						Ok(true) => {}
+						Ok(false) => report.add(
+							Rule::Winding,
+							coord,
+							format!("feature {index} in layer \"{name}\" starts with a counterclockwise ring"),
+						),
+						Err(err) => report.add(
+							Rule::Decoding,
+							coord,
+							format!("feature {index} in layer \"{name}\": {err}"),
+						),
+					}
+				}
+			}
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct RuleViolations {
+	count: u64,
+	/// The first violations, with their coordinates.
+	examples: Vec<(TileCoord3, String)>,
+}
+
+#[derive(Debug, Default)]
+struct LintReport {
+	tiles: u64,
+	violations: BTreeMap<Rule, RuleViolations>,
+}
+
+impl LintReport {
+	fn add(&mut self, rule: Rule, coord: &TileCoord3, message: String) {
+		let violations = self.violations.entry(rule).or_default();
+		violations.count += 1;
+		if violations.examples.len() < MAX_LISTED_VIOLATIONS {
+			violations.examples.push((*coord, message));
+		}
+	}
+
+	fn count_violations(&self) -> u64 {
+		self.violations.values().map(|violations| violations.count).sum()
+	}
+
+	fn as_report(&self) -> String {
+		let mut report = String::new();
+		for (rule, violations) in self.violations.iter() {
+			writeln!(report, "{rule}: {} violations", violations.count).unwrap();
+			for (coord, message) in violations.examples.iter() {
+				writeln!(report, "  {}/{}/{}: {message}", coord.z, coord.x, coord.y).unwrap();
+			}
+		}
+		writeln!(
+			report,
+			"checked {} tiles, found {} violations",
+			self.tiles,
+			self.count_violations()
+		)
+		.unwrap();
+		report
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{tests::run_command, tools::search::tests::make_test_tiles};
+	use assert_fs::TempDir;
+	use std::fs;
+	use versatiles_geometry::{vector_tile::VectorTileLayer, GeoFeature, Geometry};
+
+	fn make_tile(layer_name: &str, rings: Vec<Vec<[i32; 2]>>, name: &str) -> VectorTile {
+		let mut feature = GeoFeature::new(Geometry::new_polygon(rings));
+		feature.set_property("name".to_string(), name);
+		let layer = VectorTileLayer::from_features(layer_name.to_string(), vec![feature], 4096, 1).unwrap();
+		VectorTile::new(vec![layer])
+	}
+
+	#[test]
+	fn check_tile() -> Result<()> {
+		let rules = LintRules {
+			max_features: Some(0),
+			max_value_length: Some(5),
+			check_winding: true,
+			schema: Some(TilesetSchema::parse_str(
+				r#"{"layers":{"water":{"attributes":{"name":{"type":"string"}}}}}"#,
+			)?),
+		};
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let clockwise = vec![[0, 0], [3, 0], [3, 3], [0, 3], [0, 0]];
+		let counterclockwise = clockwise.iter().rev().cloned().collect();
+
+		let mut report = LintReport::default();
+		rules.check_tile(&coord, &make_tile("water", vec![clockwise], "Lake"), &mut report);
+		assert_eq!(report.count_violations(), 1);
+
+		rules.check_tile(
+			&coord,
+			&make_tile("lakes", vec![counterclockwise], "Müggelsee"),
+			&mut report,
+		);
+		assert_eq!(report.count_violations(), 5);
+
+		assert_eq!(
+			report.as_report(),
+			[
+				"tile matches the schema: 1 violations",
+				"  3/1/2: unknown layer \"lakes\"",
+				"feature count per layer: 2 violations",
+				"  3/1/2: layer \"water\" has 1 features, more than 0",
+				"  3/1/2: layer \"lakes\" has 1 features, more than 0",
+				"property value length: 1 violations",
+				"  3/1/2: layer \"lakes\" has a value of 10 bytes, longer than 5",
+				"polygon winding: 1 violations",
+				"  3/1/2: feature 0 in layer \"lakes\" starts with a counterclockwise ring",
+				"checked 0 tiles, found 5 violations",
+				""
+			]
+			.join("\n")
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn lint() -> Result<()> {
+		let dir = TempDir::new()?;
+		let tiles = dir.path().join("tiles");
+		make_test_tiles(&tiles)?;
+		let filename = tiles.to_str().unwrap();
+
+		run_command(vec![
+			"versatiles",
+			"lint",
+			filename,
+			"--max-features=2",
+			"--max-value-length=12",
+			"--check-winding",
+		])?;
+
+		let error = run_command(vec!["versatiles", "lint", filename, "--max-features=1"])
+			.unwrap_err()
+			.to_string();
+		assert!(error.starts_with("found 2 violations in"), "{error}");
+
+		let schema = dir.path().join("schema.json");
+		fs::write(
+			&schema,
+			r#"{"layers":{"place":{"attributes":{"name":{"type":"string"}}}}}"#,
+		)?;
+		run_command(vec![
+			"versatiles",
+			"lint",
+			filename,
+			"--schema",
+			schema.to_str().unwrap(),
+		])?;
+		fs::write(&schema, r#"{"layers":{"water":{}}}"#)?;
+		assert!(run_command(vec![
+			"versatiles",
+			"lint",
+			filename,
+			"--schema",
+			schema.to_str().unwrap()
+		])
+		.is_err());
+
+		assert!(run_command(vec!["versatiles", "lint", filename]).is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,205 @@
+//! Lists the coordinates of all tiles that exist in a tile container.
+//!
+//! The coordinates are printed per zoom level, sorted by row and column, in one of three formats:
+//!
+//! - `zxy`: one tile per line, e.g. `14/8802/5373`, the format of tile expiry files,
+//! - `quadkey`: one Bing Maps quadkey per line, e.g. `12022001`; the tile of zoom level 0 has an empty quadkey,
+//! - `runs`: horizontal runs of neighbouring tiles, e.g. `14/8802-8810/5373`; single tiles are printed as `z/x/y`.
+//!
+//! The `zxy` and `runs` formats can be read by `versatiles convert --tile-list` and `versatiles expire`.
+
+use super::convert::parse_bbox;
+use anyhow::Result;
+use std::io::{self, BufWriter, Write};
+use versatiles_container::get_reader;
+use versatiles_core::types::{TileBBox, TileCoord3, TilesReaderTrait};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile container you want to list
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// list only the tiles of this zoom level
+	#[arg(long, short, value_name = "int")]
+	zoom: Option<u8>,
+
+	/// list only the tiles within this bounding box: "lon_min,lat_min,lon_max,lat_max"
+	#[arg(
+		long,
+		short,
+		value_name = "lon_min,lat_min,lon_max,lat_max",
+		allow_hyphen_values = true
+	)]
+	bbox: Option<String>,
+
+	/// output format
+	#[arg(long, short, value_enum, default_value_t = ListFormat::Zxy)]
+	format: ListFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ListFormat {
+	/// one "z/x/y" per line
+	Zxy,
+	/// one quadkey per line
+	Quadkey,
+	/// horizontal runs of tiles as "z/x_min-x_max/y"
+	Runs,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let reader = get_reader(&arguments.filename).await?;
+
+	let mut bbox_pyramid = reader.get_parameters().bbox_pyramid.clone();
+	if let Some(zoom) = arguments.zoom {
+		bbox_pyramid.set_zoom_min(zoom);
+		bbox_pyramid.set_zoom_max(zoom);
+	}
+	if let Some(bbox) = &arguments.bbox {
+		bbox_pyramid.intersect_geo_bbox(&parse_bbox(bbox)?);
+	}
+
+	let mut output = BufWriter::new(io::stdout().lock());
+	for bbox in bbox_pyramid.iter_levels() {
+		let coords = get_coords(&*reader, bbox.clone()).await;
+		write_coords(&coords, arguments.format, &mut output)?;
+	}
+	output.flush()?;
+
+	Ok(())
+}
+
+/// Returns the coordinates of the existing tiles within a bounding box, sorted by row and column.
+async fn get_coords(reader: &dyn TilesReaderTrait, bbox: TileBBox) -> Vec<TileCoord3> {
+	let mut coords = Vec::new();
+	reader
+		.get_bbox_tile_stream(bbox)
+		.await
+		.for_each_sync(|(coord, _blob)| coords.push(coord))
+		.await;
+	coords.sort_unstable_by_key(|coord| (coord.z, coord.y, coord.x));
+	coords
+}
+
+/// Writes sorted coordinates in the given format.
+fn write_coords(coords: &[TileCoord3], format: ListFormat, output: &mut impl Write) -> Result<()> {
+	match format {
+		ListFormat::Zxy => {
+			for coord in coords {
+				writeln!(output, "{}/{}/{}", coord.z, coord.x, coord.y)?;
+			}
+		}
+		ListFormat::Quadkey => {
+			for coord in coords {
+				writeln!(output, "{}", get_quadkey(coord))?;
+			}
+		}
+		ListFormat::Runs => {
+			let mut index = 0;
+			while index < coords.len() {
+				let first = &coords[index];
+				let mut last = first;
+				while let Some(next) = coords.get(index + 1) {
+					if next.z != first.z || next.y != first.y || next.x != last.x + 1 {
+						break;
+					}
+					last = next;
+					index += 1;
+				}
+				if first.x == last.x {
+					writeln!(output, "{}/{}/{}", first.z, first.x, first.y)?;
+				} else {
+					writeln!(output, "{}/{}-{}/{}", first.z, first.x, last.x, first.y)?;
+				}
+				index += 1;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Returns the Bing Maps quadkey of a tile.
+fn get_quadkey(coord: &TileCoord3) -> String {
+	(1..=coord.z)
+		.rev()
+		.map(|bit| {
+			let mask = 1 << (bit - 1);
+			let digit = u8::from(coord.x & mask != 0) + 2 * u8::from(coord.y & mask != 0);
+			char::from(b'0' + digit)
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+
+	fn to_text(coords: &[(u32, u32, u8)], format: ListFormat) -> Result<String> {
+		let coords = coords
+			.iter()
+			.map(|&(x, y, z)| TileCoord3::new(x, y, z))
+			.collect::<Result<Vec<_>>>()?;
+		let mut output = Vec::new();
+		write_coords(&coords, format, &mut output)?;
+		Ok(String::from_utf8(output)?)
+	}
+
+	#[test]
+	fn quadkey() -> Result<()> {
+		assert_eq!(get_quadkey(&TileCoord3::new(0, 0, 0)?), "");
+		assert_eq!(get_quadkey(&TileCoord3::new(3, 5, 3)?), "213");
+		assert_eq!(get_quadkey(&TileCoord3::new(35210, 21493, 16)?), "1202102332221212");
+		Ok(())
+	}
+
+	#[test]
+	fn formats() -> Result<()> {
+		let coords = [(5, 3, 4), (6, 3, 4), (7, 3, 4), (9, 3, 4), (5, 4, 4), (10, 6, 5)];
+		assert_eq!(
+			to_text(&coords, ListFormat::Zxy)?,
+			"4/5/3\n4/6/3\n4/7/3\n4/9/3\n4/5/4\n5/10/6\n"
+		);
+		assert_eq!(
+			to_text(&coords, ListFormat::Quadkey)?,
+			"0123\n0132\n0133\n1023\n0301\n01230\n"
+		);
+		assert_eq!(to_text(&coords, ListFormat::Runs)?, "4/5-7/3\n4/9/3\n4/5/4\n5/10/6\n");
+		assert_eq!(to_text(&[], ListFormat::Runs)?, "");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn coords() -> Result<()> {
+		let reader = get_reader("../testdata/berlin.mbtiles").await?;
+		let bbox = reader
+			.get_parameters()
+			.bbox_pyramid
+			.iter_levels()
+			.last()
+			.unwrap()
+			.clone();
+		let coords = get_coords(&*reader, bbox).await;
+		assert!(!coords.is_empty());
+		assert!(coords
+			.windows(2)
+			.all(|pair| (pair[0].y, pair[0].x) < (pair[1].y, pair[1].x)));
+		Ok(())
+	}
+
+	#[test]
+	fn run() -> Result<()> {
+		run_command(vec!["versatiles", "list", "-z", "10", "../testdata/berlin.mbtiles"])?;
+		run_command(vec![
+			"versatiles",
+			"list",
+			"--bbox=13.3,52.4,13.5,52.6",
+			"--format=runs",
+			"../testdata/berlin.mbtiles",
+		])?;
+		Ok(())
+	}
+}
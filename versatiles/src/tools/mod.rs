@@ -1,7 +1,37 @@
 //! cli tools
 
+pub mod apply_patch;
+mod assets;
+pub mod bundle;
+pub mod checksum;
+mod compare;
+mod compression_stats;
+pub mod concat;
 pub mod convert;
+mod coverage;
+pub mod dev;
+pub mod diff;
+mod duplicates;
+pub mod expire;
+pub mod export_features;
+pub mod export_mbtiles;
+pub mod glyphs;
 pub mod help;
+pub mod info;
+pub mod lint;
+pub mod list;
+pub mod outline;
 pub mod probe;
+pub mod query;
+pub mod query_elevation;
+pub mod schema;
+pub mod scrape;
+mod search;
+pub mod search_index;
 pub mod serve;
 mod server;
+pub mod sign;
+pub mod sprites;
+mod style;
+pub mod sync;
+pub mod verify;
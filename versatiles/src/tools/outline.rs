@@ -0,0 +1,233 @@
+//! Exports the coverage of a tile container as GeoJSON, e.g. to inspect it in QGIS.
+//!
+//! By default every existing tile becomes a polygon with the properties `z`, `x` and `y`. Since that is a lot of
+//! polygons at higher zoom levels, `--boundary-only` keeps only the tiles at the edge of the coverage, i.e. tiles
+//! with at least one missing neighbour.
+//!
+//! With `--blocks`, the blocks of a `*.versatiles` container are exported instead, with the properties `z`, `x`, `y`
+//! (the block coordinates), `tile_count` and `size` (bytes of tile data), which helps when tuning the block size.
+
+use anyhow::{ensure, Context, Result};
+use std::{collections::HashSet, fs, path::PathBuf};
+use versatiles_container::{get_reader, VersaTilesReader};
+use versatiles_core::{
+	json::{JsonObject, JsonValue},
+	types::{GeoBBox, TileBBox, TilesReaderTrait},
+};
+use versatiles_geometry::{GeoFeature, Geometry};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile container you want to outline
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// write the GeoJSON to this file instead of stdout
+	#[arg(long, short, value_name = "FILE")]
+	output: Option<PathBuf>,
+
+	/// export only this zoom level
+	#[arg(long, short, value_name = "int")]
+	zoom: Option<u8>,
+
+	/// export only the tiles at the edge of the coverage
+	#[arg(long)]
+	boundary_only: bool,
+
+	/// export the blocks of a *.versatiles container instead of the tiles
+	#[arg(long, conflicts_with = "boundary_only")]
+	blocks: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let features = if arguments.blocks {
+		ensure!(
+			arguments.filename.ends_with(".versatiles"),
+			"--blocks can only be used with *.versatiles files"
+		);
+		let reader = VersaTilesReader::open_path(&std::path::absolute(&arguments.filename)?).await?;
+		get_block_features(&reader, arguments.zoom)
+	} else {
+		let reader = get_reader(&arguments.filename).await?;
+		get_tile_features(&*reader, arguments.zoom, arguments.boundary_only).await
+	};
+
+	let json = to_feature_collection(features).stringify();
+	match &arguments.output {
+		Some(path) => fs::write(path, json).with_context(|| format!("writing {path:?}"))?,
+		None => println!("{json}"),
+	}
+	Ok(())
+}
+
+/// Returns a polygon feature for every existing tile.
+async fn get_tile_features(reader: &dyn TilesReaderTrait, zoom: Option<u8>, boundary_only: bool) -> Vec<GeoFeature> {
+	let mut features = Vec::new();
+
+	for bbox in reader.get_parameters().bbox_pyramid.iter_levels() {
+		if zoom.is_some_and(|zoom| zoom != bbox.level) {
+			continue;
+		}
+
+		let mut tiles: HashSet<(u32, u32)> = HashSet::new();
+		reader
+			.get_bbox_tile_stream(bbox.clone())
+			.await
+			.for_each_sync(|(coord, _blob)| {
+				tiles.insert((coord.x, coord.y));
+			})
+			.await;
+
+		let mut tiles: Vec<(u32, u32)> = tiles
+			.iter()
+			.filter(|(x, y)| !boundary_only || is_boundary(&tiles, *x, *y))
+			.copied()
+			.collect();
+		tiles.sort_unstable_by_key(|(x, y)| (*y, *x));
+
+		for (x, y) in tiles {
+			let mut feature = GeoFeature::new(get_polygon(&TileBBox::new(bbox.level, x, y, x, y).unwrap()));
+			feature.set_property("z".to_string(), bbox.level);
+			feature.set_property("x".to_string(), x);
+			feature.set_property("y".to_string(), y);
+			features.push(feature);
+		}
+	}
+
+	features
+}
+
+/// Returns `true` if at least one of the four neighbours of a tile is missing.
+fn is_boundary(tiles: &HashSet<(u32, u32)>, x: u32, y: u32) -> bool {
+	let neighbours = [
+		x.checked_sub(1).map(|x| (x, y)),
+		x.checked_add(1).map(|x| (x, y)),
+		y.checked_sub(1).map(|y| (x, y)),
+		y.checked_add(1).map(|y| (x, y)),
+	];
+	neighbours
+		.iter()
+		.any(|neighbour| neighbour.is_none_or(|neighbour| !tiles.contains(&neighbour)))
+}
+
+/// Returns a polygon feature for every block of a `*.versatiles` container.
+fn get_block_features(reader: &VersaTilesReader, zoom: Option<u8>) -> Vec<GeoFeature> {
+	reader
+		.get_blocks()
+		.into_iter()
+		.filter(|(bbox, _)| zoom.is_none_or(|zoom| zoom == bbox.level))
+		.map(|(bbox, size)| {
+			let mut feature = GeoFeature::new(get_polygon(&bbox));
+			feature.set_property("z".to_string(), bbox.level);
+			feature.set_property("x".to_string(), bbox.x_min >> 8);
+			feature.set_property("y".to_string(), bbox.y_min >> 8);
+			feature.set_property("tile_count".to_string(), bbox.count_tiles());
+			feature.set_property("size".to_string(), size);
+			feature
+		})
+		.collect()
+}
+
+/// Returns the outline of a bounding box of tiles as polygon in longitude and latitude.
+fn get_polygon(bbox: &TileBBox) -> Geometry {
+	let GeoBBox(x_min, y_min, x_max, y_max) = bbox.as_geo_bbox();
+	Geometry::new_polygon(vec![vec![
+		[x_min, y_min],
+		[x_max, y_min],
+		[x_max, y_max],
+		[x_min, y_max],
+		[x_min, y_min],
+	]])
+}
+
+fn to_feature_collection(features: Vec<GeoFeature>) -> JsonObject {
+	let features = features
+		.iter()
+		.map(|feature| JsonValue::Object(feature.to_json()))
+		.collect::<Vec<_>>();
+
+	let mut json = JsonObject::default();
+	json.set("type", JsonValue::from("FeatureCollection"));
+	json.set("features", JsonValue::from(features));
+	json
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+	use versatiles_container::MockTilesReader;
+	use versatiles_core::types::{TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters};
+
+	#[test]
+	fn boundary() {
+		let tiles: HashSet<(u32, u32)> = (0..3).flat_map(|x| (0..3).map(move |y| (x, y))).collect();
+		assert!(!is_boundary(&tiles, 1, 1));
+		assert!(is_boundary(&tiles, 0, 1));
+		assert!(is_boundary(&tiles, 2, 2));
+	}
+
+	#[test]
+	fn polygon() -> Result<()> {
+		assert_eq!(
+			get_polygon(&TileBBox::new(1, 1, 0, 1, 0)?).to_json().stringify(),
+			r#"{"coordinates":[[[0,0],[180,0],[180,85.05112877980659],[0,85.05112877980659],[0,0]]],"type":"Polygon"}"#
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_features() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.include_bbox(&TileBBox::new(3, 2, 2, 5, 4)?);
+		bbox_pyramid.include_bbox(&TileBBox::new(4, 0, 0, 1, 1)?);
+		let reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PBF,
+			TileCompression::Gzip,
+			bbox_pyramid,
+		))?;
+
+		assert_eq!(get_tile_features(&reader, None, false).await.len(), 16);
+		assert_eq!(get_tile_features(&reader, Some(3), false).await.len(), 12);
+		assert_eq!(get_tile_features(&reader, Some(3), true).await.len(), 10);
+
+		let features = get_tile_features(&reader, Some(4), false).await;
+		assert_eq!(
+			features[1].to_json().get("properties").unwrap().stringify(),
+			r#"{"x":1,"y":0,"z":4}"#
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn run() -> Result<()> {
+		let dir = TempDir::new()?;
+		let container = dir.path().join("berlin.versatiles");
+		let container = container.to_str().unwrap();
+		let output = dir.path().join("blocks.geojson");
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--max-zoom=10",
+			"../testdata/berlin.mbtiles",
+			container,
+		])?;
+
+		run_command(vec!["versatiles", "outline", "-z", "10", "--boundary-only", container])?;
+		run_command(vec![
+			"versatiles",
+			"outline",
+			"--blocks",
+			"-o",
+			output.to_str().unwrap(),
+			container,
+		])?;
+		assert!(fs::read_to_string(&output)?.starts_with(r#"{"features":[{"geometry":"#));
+
+		assert!(run_command(vec!["versatiles", "outline", "--blocks", "../testdata/berlin.mbtiles"]).is_err());
+		Ok(())
+	}
+}
@@ -1,6 +1,17 @@
-use anyhow::Result;
+use super::{
+	compare::Comparison, compression_stats::CompressionStats, coverage::write_coverage_maps, duplicates::DuplicateStats,
+};
+use anyhow::{ensure, Result};
+use std::path::PathBuf;
 use versatiles_container::get_reader;
-use versatiles_core::types::ProbeDepth;
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{ProbeDepth, TilesReaderTrait},
+	utils::verify_tile_content,
+};
+
+/// Maximum number of mislabeled tiles that are listed individually.
+const MAX_LISTED_ERRORS: usize = 20;
 
 #[derive(clap::Args, Debug)]
 #[command(arg_required_else_help = true, disable_version_flag = true)]
@@ -16,14 +27,49 @@ pub struct Subcommand {
 	/// -ddd: scans all tile contents
 	#[arg(long, short, action = clap::ArgAction::Count, verbatim_doc_comment)]
 	deep: u8,
+
+	/// load the whole tile index into memory before probing, and report its resident size.
+	/// Optionally, also keep the tiles of this many blocks in memory, starting with the lowest zoom levels.
+	#[arg(long, value_name = "BLOCKS", num_args = 0..=1, default_missing_value = "0", verbatim_doc_comment)]
+	preload: Option<usize>,
+
+	/// render a coverage map per zoom level (one pixel per tile) as PNG files
+	/// together with an index.html into this directory
+	#[arg(long, value_name = "DIRECTORY", verbatim_doc_comment)]
+	coverage: Option<PathBuf>,
+
+	/// compare metadata and parameters with a second tile container,
+	/// flagging differences that prevent merging or swapping them
+	#[arg(long, value_name = "FILENAME", verbatim_doc_comment)]
+	compare: Option<String>,
+
+	/// check every tile against the tile format and compression declared by the container
+	#[arg(long)]
+	verify_content: bool,
+
+	/// sample tiles per zoom level and report the ratio of compressed to uncompressed sizes,
+	/// flagging tiles whose content is already compressed, e.g. gzipped PNG images
+	#[arg(long, verbatim_doc_comment)]
+	compression_stats: bool,
+
+	/// report duplicated tiles per zoom level and estimate the savings of deduplication
+	#[arg(long)]
+	duplicates: bool,
 }
 
-#[tokio::main]
 pub async fn run(arguments: &Subcommand) -> Result<()> {
 	eprintln!("probe {:?}", arguments.filename);
 
 	let mut reader = get_reader(&arguments.filename).await?;
 
+	if let Some(filename) = &arguments.compare {
+		eprintln!("compare with {filename:?}");
+		let other = get_reader(filename).await?;
+		let comparison = Comparison::new(&*reader, &*other);
+		print!("{}", comparison.as_table(&arguments.filename, filename));
+		return Ok(());
+	}
+
 	let level = match arguments.deep {
 		0 => ProbeDepth::Shallow,
 		1 => ProbeDepth::Container,
@@ -31,8 +77,69 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 		3..=255 => ProbeDepth::TileContents,
 	};
 
+	if let Some(pinned_blocks) = arguments.preload {
+		reader.preload(pinned_blocks).await?;
+	}
+
 	reader.probe(level).await?;
 
+	if arguments.verify_content {
+		verify_contents(&*reader).await?;
+	}
+
+	if arguments.compression_stats {
+		let stats = CompressionStats::from_reader(&*reader).await?;
+		print!("{}", stats.as_report());
+	}
+
+	if arguments.duplicates {
+		let stats = DuplicateStats::from_reader(&*reader).await?;
+		print!("{}", stats.as_report());
+	}
+
+	if let Some(directory) = &arguments.coverage {
+		eprintln!("render coverage maps into {directory:?}");
+		write_coverage_maps(&*reader, directory).await?;
+	}
+
+	Ok(())
+}
+
+/// Checks all tiles against the declared tile format and compression, and lists the mislabeled ones.
+async fn verify_contents(reader: &dyn TilesReaderTrait) -> Result<()> {
+	let parameters = reader.get_parameters();
+	let (format, compression) = (parameters.tile_format, parameters.tile_compression);
+	let mut progress = get_progress_bar("verifying tiles", parameters.bbox_pyramid.count_tiles());
+
+	let mut tile_count = 0u64;
+	let mut errors: Vec<String> = Vec::new();
+	for bbox in parameters.bbox_pyramid.iter_levels() {
+		reader
+			.get_bbox_tile_stream(bbox.clone())
+			.await
+			.for_each_sync(|(coord, blob)| {
+				tile_count += 1;
+				if let Err(err) = verify_tile_content(&blob, format, compression) {
+					errors.push(format!("{}/{}/{}: {err}", coord.z, coord.x, coord.y));
+				}
+			})
+			.await;
+		progress.inc(bbox.count_tiles());
+	}
+	progress.finish();
+
+	for error in errors.iter().take(MAX_LISTED_ERRORS) {
+		eprintln!("  - {error}");
+	}
+	if errors.len() > MAX_LISTED_ERRORS {
+		eprintln!("  - ... and {} more", errors.len() - MAX_LISTED_ERRORS);
+	}
+	ensure!(
+		errors.is_empty(),
+		"{} of {tile_count} tiles don't match the declared tile format {format} and compression {compression}",
+		errors.len()
+	);
+	eprintln!("all {tile_count} tiles match the declared tile format {format} and compression {compression}");
 	Ok(())
 }
 
@@ -48,6 +155,99 @@ mod tests {
 
 	#[test]
 
+	fn test_preload() {
+		std::fs::create_dir("../tmp/").unwrap_or_default();
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--overwrite",
+			"--max-zoom=8",
+			"../testdata/berlin.mbtiles",
+			"../tmp/berlin_preload.versatiles",
+		])
+		.unwrap();
+		run_command(vec![
+			"versatiles",
+			"probe",
+			"-d",
+			"--preload=2",
+			"../tmp/berlin_preload.versatiles",
+		])
+		.unwrap();
+	}
+
+	#[test]
+
+	fn test_coverage() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		run_command(vec![
+			"versatiles",
+			"probe",
+			"-q",
+			"--coverage",
+			dir.path().to_str().unwrap(),
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap();
+		assert!(dir.path().join("index.html").exists());
+		assert!(dir.path().join("z14.png").exists());
+	}
+
+	#[test]
+
+	fn test_compare() {
+		run_command(vec![
+			"versatiles",
+			"probe",
+			"-q",
+			"--compare",
+			"../testdata/berlin.pmtiles",
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap();
+	}
+
+	#[test]
+
+	fn test_verify_content() {
+		run_command(vec![
+			"versatiles",
+			"probe",
+			"-q",
+			"--verify-content",
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap();
+	}
+
+	#[test]
+
+	fn test_compression_stats() {
+		run_command(vec![
+			"versatiles",
+			"probe",
+			"-q",
+			"--compression-stats",
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap();
+	}
+
+	#[test]
+
+	fn test_duplicates() {
+		run_command(vec![
+			"versatiles",
+			"probe",
+			"-q",
+			"--duplicates",
+			"../testdata/berlin.mbtiles",
+		])
+		.unwrap();
+	}
+
+	#[test]
+
 	fn test_remote() {
 		run_command(vec![
 			"versatiles",
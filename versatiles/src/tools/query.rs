@@ -0,0 +1,100 @@
+use anyhow::Result;
+use versatiles_container::get_reader;
+use versatiles_core::json::{JsonObject, JsonValue};
+use versatiles_pipeline::{FeatureQueryReader, QueriedFeature};
+
+#[derive(clap::Args, Debug)]
+#[command(
+	arg_required_else_help = true,
+	disable_version_flag = true,
+	allow_negative_numbers = true
+)]
+pub struct Subcommand {
+	/// tile container with vector tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// longitude in degrees
+	#[arg(required = true)]
+	lon: f64,
+
+	/// latitude in degrees
+	#[arg(required = true)]
+	lat: f64,
+
+	/// zoom level of the queried tile, reduced to the highest zoom level of the container
+	#[arg(long, value_name = "int", default_value = "14")]
+	zoom: u8,
+
+	/// also return points and lines within this distance, in pixels of a 256 pixel tile
+	#[arg(long, value_name = "float", default_value = "4")]
+	radius: f64,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let reader = FeatureQueryReader::new(get_reader(&arguments.filename).await?)?;
+
+	let features = reader
+		.query(arguments.lon, arguments.lat, arguments.zoom, arguments.radius)
+		.await?;
+
+	println!("{}", to_feature_collection(&features).stringify());
+	Ok(())
+}
+
+/// Converts the features into a GeoJSON feature collection. The layer of every feature is stored as "layer".
+fn to_feature_collection(features: &[QueriedFeature]) -> JsonObject {
+	let features = features
+		.iter()
+		.map(|f| {
+			let mut json = f.feature.to_json();
+			json.set("layer", JsonValue::from(&f.layer));
+			JsonValue::Object(json)
+		})
+		.collect::<Vec<_>>();
+
+	let mut json = JsonObject::default();
+	json.set("type", JsonValue::from("FeatureCollection"));
+	json.set("features", JsonValue::from(features));
+	json
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use versatiles_geometry::{GeoFeature, Geometry};
+
+	#[test]
+	fn feature_collection() {
+		let mut feature = GeoFeature::new(Geometry::new_point([13.4, 52.5]));
+		feature.set_property("name".to_string(), "Berlin");
+		let features = vec![QueriedFeature {
+			layer: "place".to_string(),
+			feature,
+		}];
+
+		assert_eq!(
+			to_feature_collection(&features).stringify(),
+			"{\"features\":[{\"geometry\":{\"coordinates\":[13.4,52.5],\"type\":\"Point\"},\"layer\":\"place\",\"properties\":{\"name\":\"Berlin\"},\"type\":\"Feature\"}],\"type\":\"FeatureCollection\"}"
+		);
+	}
+
+	#[test]
+	fn query() -> Result<()> {
+		run_command(vec![
+			"versatiles",
+			"query",
+			"../testdata/berlin.mbtiles",
+			"13.3777",
+			"52.5163",
+		])?;
+
+		let error = run_command(vec!["versatiles", "query", "../testdata/berlin.mbtiles", "0", "-91"])
+			.unwrap_err()
+			.to_string();
+		assert!(error.contains("invalid position"), "{error}");
+		Ok(())
+	}
+}
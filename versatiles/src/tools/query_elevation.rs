@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use versatiles_container::get_reader;
+use versatiles_pipeline::{TerrainEncoding, TerrainReader};
+
+#[derive(clap::Args, Debug)]
+#[command(
+	arg_required_else_help = true,
+	disable_version_flag = true,
+	allow_negative_numbers = true
+)]
+pub struct Subcommand {
+	/// tile container with terrain-RGB tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// latitude in degrees
+	#[arg(required = true)]
+	lat: f64,
+
+	/// longitude in degrees
+	#[arg(required = true)]
+	lon: f64,
+
+	/// encoding of the elevations: "mapbox" or "terrarium"
+	#[arg(long, default_value = "mapbox")]
+	encoding: TerrainEncoding,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let reader = TerrainReader::new(get_reader(&arguments.filename).await?, arguments.encoding)?;
+
+	let elevation = reader
+		.get_elevation(arguments.lat, arguments.lon)
+		.await?
+		.with_context(|| format!("no elevation data at {}, {}", arguments.lat, arguments.lon))?;
+
+	println!("{elevation:.1}");
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tests::run_command;
+	use anyhow::Result;
+	use assert_fs::TempDir;
+	use image::{DynamicImage, Rgb, RgbImage};
+	use std::fs;
+
+	#[test]
+	fn query_elevation() -> Result<()> {
+		let dir = TempDir::new()?;
+		fs::create_dir_all(dir.path().join("0/0"))?;
+		let image = RgbImage::from_pixel(256, 256, Rgb([1, 135, 0]));
+		DynamicImage::ImageRgb8(image).save(dir.path().join("0/0/0.png"))?;
+		let filename = dir.path().to_str().unwrap();
+
+		run_command(vec!["versatiles", "query-elevation", filename, "-33.9", "18.4"])?;
+
+		let error = run_command(vec![
+			"versatiles",
+			"query-elevation",
+			filename,
+			"0",
+			"0",
+			"--encoding",
+			"lerc",
+		])
+		.unwrap_err()
+		.to_string();
+		assert!(error.contains("unknown terrain encoding \"lerc\""), "{error}");
+		Ok(())
+	}
+}
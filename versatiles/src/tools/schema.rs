@@ -0,0 +1,143 @@
+use super::lint::{lint_container, LintRules};
+use anyhow::{ensure, Result};
+use std::{fs, path::PathBuf};
+use versatiles_container::get_reader;
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{TileFormat, TilesReaderTrait},
+	utils::decompress,
+};
+use versatiles_geometry::vector_tile::{TilesetSchema, TilesetSchemaBuilder, VectorTile};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	#[command(subcommand)]
+	command: Commands,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Commands {
+	/// Generate a schema from the tiles of a container
+	Generate(Generate),
+
+	/// Validate the tiles of a container against a schema
+	Validate(Validate),
+}
+
+/// Generates a schema of the layers, zoom levels and attributes of a vector tile container.
+/// Attributes found in every feature of a layer are marked as required.
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+struct Generate {
+	/// tile container with vector tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// write the schema to this file instead of stdout
+	#[arg(long, short, value_name = "FILE")]
+	output: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+struct Validate {
+	/// tile container with vector tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// schema file
+	#[arg(required = true)]
+	schema: PathBuf,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	match &arguments.command {
+		Commands::Generate(arguments) => {
+			let reader = get_reader(&arguments.filename).await?;
+			let json = generate_schema(reader.as_ref()).await?.to_json().stringify();
+			match &arguments.output {
+				Some(output) => {
+					fs::write(output, json)?;
+					eprintln!("wrote schema of {:?} to {output:?}", arguments.filename);
+				}
+				None => println!("{json}"),
+			}
+			Ok(())
+		}
+		Commands::Validate(arguments) => {
+			let rules = LintRules {
+				schema: Some(TilesetSchema::read_file(&arguments.schema)?),
+				..Default::default()
+			};
+			lint_container(&arguments.filename, &rules).await
+		}
+	}
+}
+
+async fn generate_schema(reader: &dyn TilesReaderTrait) -> Result<TilesetSchema> {
+	let parameters = reader.get_parameters();
+	ensure!(
+		parameters.tile_format == TileFormat::PBF,
+		"schemas can only be generated for vector tiles"
+	);
+	let compression = parameters.tile_compression;
+	let pyramid = &parameters.bbox_pyramid;
+
+	let mut builder = TilesetSchemaBuilder::new();
+	let mut progress = get_progress_bar("scanning tiles", pyramid.count_tiles());
+	for bbox in pyramid.iter_levels() {
+		let tiles = reader.get_bbox_tile_stream(bbox.clone()).await.collect().await;
+		for (coord, blob) in tiles {
+			let tile = VectorTile::from_blob(&decompress(blob, &compression)?)?;
+			builder.add_tile(coord.z, &tile)?;
+		}
+		progress.inc(bbox.count_tiles());
+	}
+	progress.finish();
+
+	Ok(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{tests::run_command, tools::search::tests::make_test_tiles};
+	use assert_fs::TempDir;
+
+	#[test]
+	fn generate_and_validate() -> Result<()> {
+		let dir = TempDir::new()?;
+		let tiles = dir.path().join("tiles");
+		make_test_tiles(&tiles)?;
+		let filename = tiles.to_str().unwrap();
+		let schema = dir.path().join("schema.json");
+		let schema_filename = schema.to_str().unwrap();
+
+		run_command(vec![
+			"versatiles",
+			"schema",
+			"generate",
+			filename,
+			"-o",
+			schema_filename,
+		])?;
+		assert_eq!(
+			fs::read_to_string(&schema)?,
+			r#"{"layers":{"place":{"attributes":{"name":{"required":true,"type":"string"}},"maxzoom":2,"minzoom":2}}}"#
+		);
+		run_command(vec!["versatiles", "schema", "validate", filename, schema_filename])?;
+
+		fs::write(
+			&schema,
+			r#"{"layers":{"place":{"attributes":{"name":{"type":"number"}},"minzoom":3}}}"#,
+		)?;
+		let error = run_command(vec!["versatiles", "schema", "validate", filename, schema_filename])
+			.unwrap_err()
+			.to_string();
+		assert!(error.starts_with("found 8 violations in"), "{error}");
+		Ok(())
+	}
+}
@@ -0,0 +1,475 @@
+//! Downloads tiles from an XYZ endpoint into a tile container.
+//!
+//! Tiles are downloaded into a directory first (by default `{output}.download`). Each tile is stored as soon as it
+//! is downloaded, so an interrupted or failed run can be resumed by running the same command again. When all tiles
+//! are downloaded, the container is written and the download directory is removed.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use reqwest::{header::CONTENT_ENCODING, Client, StatusCode};
+use std::{
+	fs,
+	path::PathBuf,
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
+use tokio::time::sleep;
+use versatiles_container::{convert_tiles_container, DiskTileCache, TileCacheTrait, TilesConverterParameters};
+use versatiles_core::{
+	progress::get_progress_bar,
+	tilejson::TileJSON,
+	types::{
+		Blob, GeoBBox, TileBBoxPyramid, TileCompression, TileCoord3, TileFormat, TilesReaderParameters, TilesReaderTrait,
+	},
+	utils::decompress,
+};
+
+/// Delay before the first retry of a failed request. It doubles with every retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+pub struct Subcommand {
+	/// URL template of the tiles, e.g. "https://tiles.example.org/{z}/{x}/{y}.png"
+	/// use "{-y}" instead of "{y}" for endpoints in TMS order
+	#[arg(required = true, verbatim_doc_comment)]
+	url_template: String,
+
+	/// supported container formats: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true)]
+	output_file: String,
+
+	/// minimum zoom level
+	#[arg(long, value_name = "int", default_value = "0", display_order = 1)]
+	min_zoom: u8,
+
+	/// maximum zoom level
+	#[arg(long, value_name = "int", display_order = 1)]
+	max_zoom: u8,
+
+	/// download only tiles inside a bounding box
+	#[arg(
+		long,
+		short,
+		value_name = "lon_min,lat_min,lon_max,lat_max",
+		allow_hyphen_values = true,
+		display_order = 1
+	)]
+	bbox: Option<String>,
+
+	/// tile format, guessed from the URL template by default
+	#[arg(long, value_enum, display_order = 1)]
+	format: Option<TileFormat>,
+
+	/// number of parallel requests
+	#[arg(long, value_name = "int", default_value = "4", display_order = 2)]
+	concurrency: usize,
+
+	/// wait this many milliseconds before every request
+	#[arg(long, value_name = "ms", default_value = "0", display_order = 2)]
+	delay: u64,
+
+	/// retry failed requests (network errors, status 429 and 5xx) this many times
+	#[arg(long, value_name = "int", default_value = "3", display_order = 2)]
+	retries: u32,
+
+	/// stop after this many requests in total, including retries
+	/// run the command again to continue
+	#[arg(long, value_name = "int", verbatim_doc_comment, display_order = 2)]
+	max_requests: Option<u64>,
+
+	/// user agent sent with every request
+	#[arg(long, default_value = concat!("versatiles/", env!("CARGO_PKG_VERSION")), display_order = 3)]
+	user_agent: String,
+
+	/// directory for the downloaded tiles, defaults to "{output_file}.download"
+	#[arg(long, value_name = "DIR", display_order = 3)]
+	download_dir: Option<PathBuf>,
+
+	/// keep the download directory after writing the container
+	#[arg(long, display_order = 3)]
+	keep_download_dir: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	ensure!(arguments.concurrency > 0, "concurrency must be at least 1");
+
+	let template = TileUrlTemplate::new(&arguments.url_template)?;
+	let tile_format = match arguments.format {
+		Some(format) => format,
+		None => template.guess_format()?,
+	};
+	let bbox_pyramid = get_bbox_pyramid(arguments)?;
+
+	let download_dir = arguments
+		.download_dir
+		.clone()
+		.unwrap_or_else(|| PathBuf::from(format!("{}.download", arguments.output_file)));
+	let cache = DiskTileCache::new(&download_dir)?;
+
+	let scraper = Scraper {
+		client: Client::builder()
+			.user_agent(&arguments.user_agent)
+			.timeout(Duration::from_secs(60))
+			.build()?,
+		template,
+		delay: Duration::from_millis(arguments.delay),
+		retries: arguments.retries,
+		budget: RequestBudget::new(arguments.max_requests),
+	};
+
+	scraper.download(&cache, &bbox_pyramid, arguments.concurrency).await?;
+
+	eprintln!("write {:?}", arguments.output_file);
+	let reader = DownloadedTilesReader::new(cache, tile_format, bbox_pyramid);
+	convert_tiles_container(
+		reader.boxed(),
+		TilesConverterParameters::new_default(),
+		&arguments.output_file,
+	)
+	.await?;
+
+	if !arguments.keep_download_dir {
+		fs::remove_dir_all(&download_dir)?;
+	}
+
+	Ok(())
+}
+
+fn get_bbox_pyramid(arguments: &Subcommand) -> Result<TileBBoxPyramid> {
+	ensure!(
+		arguments.min_zoom <= arguments.max_zoom,
+		"min zoom must not be greater than max zoom"
+	);
+
+	let mut bbox_pyramid = TileBBoxPyramid::new_full(arguments.max_zoom);
+	bbox_pyramid.set_zoom_min(arguments.min_zoom);
+
+	if let Some(bbox) = &arguments.bbox {
+		let values = bbox
+			.split(&[' ', ',', ';'])
+			.filter(|s| !s.is_empty())
+			.map(|s| s.parse::<f64>())
+			.collect::<Result<Vec<f64>, _>>()
+			.map_err(|_| anyhow!("bbox must contain numbers, but got: {bbox:?}"))?;
+		bbox_pyramid.intersect_geo_bbox(&GeoBBox::try_from(values)?);
+	}
+
+	Ok(bbox_pyramid)
+}
+
+/// A URL template with the placeholders `{z}`, `{x}` and `{y}` or `{-y}`.
+#[derive(Debug)]
+pub(super) struct TileUrlTemplate {
+	template: String,
+}
+
+impl TileUrlTemplate {
+	pub(super) fn new(template: &str) -> Result<TileUrlTemplate> {
+		ensure!(
+			template.starts_with("http://") || template.starts_with("https://"),
+			"URL template must start with http:// or https://"
+		);
+		ensure!(
+			template.contains("{z}") && template.contains("{x}"),
+			"URL template must contain {{z}} and {{x}}"
+		);
+		ensure!(
+			template.contains("{y}") ^ template.contains("{-y}"),
+			"URL template must contain either {{y}} or {{-y}}"
+		);
+		Ok(TileUrlTemplate {
+			template: template.to_owned(),
+		})
+	}
+
+	fn get_url(&self, coord: &TileCoord3) -> String {
+		let max = (1u32 << coord.z) - 1;
+		self
+			.template
+			.replace("{z}", &coord.z.to_string())
+			.replace("{x}", &coord.x.to_string())
+			.replace("{y}", &coord.y.to_string())
+			.replace("{-y}", &(max - coord.y).to_string())
+	}
+
+	/// Guesses the tile format from the file extension, e.g. ".../{y}.png".
+	pub(super) fn guess_format(&self) -> Result<TileFormat> {
+		let path = self.template.split(['?', '#']).next().unwrap();
+		let filename = path.rsplit('/').next().unwrap();
+		match TileFormat::from_filename(&mut filename.to_owned()) {
+			Some(format) => Ok(format),
+			None => bail!("can not guess the tile format from the URL template, please use --format"),
+		}
+	}
+}
+
+/// Limits the total number of requests.
+#[derive(Debug)]
+pub(super) struct RequestBudget {
+	count: AtomicU64,
+	max: Option<u64>,
+}
+
+impl RequestBudget {
+	pub(super) fn new(max: Option<u64>) -> RequestBudget {
+		RequestBudget {
+			count: AtomicU64::new(0),
+			max,
+		}
+	}
+
+	/// Takes one request from the budget. Returns false if the budget is exhausted.
+	fn take(&self) -> bool {
+		let count = self.count.fetch_add(1, Ordering::Relaxed);
+		self.max.is_none_or(|max| count < max)
+	}
+
+	fn is_exhausted(&self) -> bool {
+		self.max.is_some_and(|max| self.count.load(Ordering::Relaxed) >= max)
+	}
+}
+
+#[derive(Debug)]
+pub(super) struct Scraper {
+	pub(super) client: Client,
+	pub(super) template: TileUrlTemplate,
+	pub(super) delay: Duration,
+	pub(super) retries: u32,
+	pub(super) budget: RequestBudget,
+}
+
+impl Scraper {
+	/// Downloads all tiles of the pyramid, that are not yet in the cache.
+	async fn download(&self, cache: &DiskTileCache, bbox_pyramid: &TileBBoxPyramid, concurrency: usize) -> Result<()> {
+		let mut coords: Vec<TileCoord3> = Vec::new();
+		for bbox in bbox_pyramid.iter_levels() {
+			for coord in bbox.iter_coords() {
+				if cache.get(&coord)?.is_none() {
+					coords.push(coord);
+				}
+			}
+		}
+
+		let total = bbox_pyramid.count_tiles();
+		eprintln!("download {} of {total} tiles", coords.len());
+
+		let mut progress = get_progress_bar("download tiles", total);
+		progress.set_position(total - coords.len() as u64);
+
+		let mut results = stream::iter(coords)
+			.map(|coord| async move { (coord, self.get_tile(&coord).await) })
+			.buffer_unordered(concurrency);
+
+		let mut failed: u64 = 0;
+		let mut skipped: u64 = 0;
+		while let Some((coord, result)) = results.next().await {
+			match result {
+				Ok(blob) => cache.set(&coord, &blob)?,
+				Err(_) if self.budget.is_exhausted() => skipped += 1,
+				Err(e) => {
+					log::warn!("failed to download tile {coord:?}: {e:#}");
+					failed += 1;
+				}
+			}
+			progress.inc(1);
+		}
+		progress.finish();
+
+		if skipped > 0 {
+			bail!("request budget exhausted, {skipped} tiles are not downloaded yet. Run the command again to continue.");
+		}
+		if failed > 0 {
+			bail!("{failed} tiles failed to download. Run the command again to retry them.");
+		}
+		Ok(())
+	}
+
+	/// Downloads a tile. Returns `None` if the tile does not exist.
+	pub(super) async fn get_tile(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		let url = self.template.get_url(coord);
+		let mut last_error = anyhow!("no request made for {url}");
+
+		for attempt in 0..=self.retries {
+			if attempt > 0 {
+				sleep(RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+			}
+			ensure!(self.budget.take(), "request budget exhausted");
+			if !self.delay.is_zero() {
+				sleep(self.delay).await;
+			}
+
+			let response = match self.client.get(&url).send().await {
+				Ok(response) => response,
+				Err(e) => {
+					last_error = anyhow!(e).context(format!("requesting {url}"));
+					continue;
+				}
+			};
+
+			let status = response.status();
+			if status == StatusCode::NOT_FOUND || status == StatusCode::NO_CONTENT {
+				return Ok(None);
+			}
+			if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+				last_error = anyhow!("status {status} for {url}");
+				continue;
+			}
+			ensure!(status.is_success(), "status {status} for {url}");
+
+			let compression = match response.headers().get(CONTENT_ENCODING).map(|v| v.as_bytes()) {
+				Some(b"gzip") => TileCompression::Gzip,
+				Some(b"br") => TileCompression::Brotli,
+				_ => TileCompression::Uncompressed,
+			};
+			let blob = Blob::from(response.bytes().await?.to_vec());
+			return Ok(Some(decompress(blob, &compression)?));
+		}
+
+		Err(last_error)
+	}
+}
+
+/// Reads the downloaded tiles from the download directory.
+#[derive(Debug)]
+struct DownloadedTilesReader {
+	cache: DiskTileCache,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl DownloadedTilesReader {
+	fn new(cache: DiskTileCache, tile_format: TileFormat, bbox_pyramid: TileBBoxPyramid) -> DownloadedTilesReader {
+		let mut tilejson = TileJSON::default();
+		tilejson.update_from_pyramid(&bbox_pyramid);
+		DownloadedTilesReader {
+			cache,
+			parameters: TilesReaderParameters::new(tile_format, TileCompression::Uncompressed, bbox_pyramid),
+			tilejson,
+		}
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for DownloadedTilesReader {
+	fn get_source_name(&self) -> &str {
+		"download"
+	}
+
+	fn get_container_name(&self) -> &str {
+		"scrape"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, tile_compression: TileCompression) {
+		self.parameters.tile_compression = tile_compression;
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(self.cache.get(coord)?.flatten())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{tests::run_command, tools::server::TileServer};
+	use assert_fs::TempDir;
+	use versatiles_container::{get_reader, MockTilesReader, MockTilesReaderProfile};
+
+	#[test]
+	fn url_template() -> Result<()> {
+		let template = TileUrlTemplate::new("https://example.org/{z}/{x}/{y}.png?key=abc")?;
+		assert_eq!(
+			template.get_url(&TileCoord3::new(3, 1, 2)?),
+			"https://example.org/2/3/1.png?key=abc"
+		);
+		assert_eq!(template.guess_format()?, TileFormat::PNG);
+
+		let template = TileUrlTemplate::new("http://example.org/tms/{z}/{x}/{-y}")?;
+		assert_eq!(
+			template.get_url(&TileCoord3::new(3, 1, 2)?),
+			"http://example.org/tms/2/3/2"
+		);
+		assert!(template.guess_format().is_err());
+
+		assert!(TileUrlTemplate::new("example.org/{z}/{x}/{y}").is_err());
+		assert!(TileUrlTemplate::new("https://example.org/{z}/{x}").is_err());
+		assert!(TileUrlTemplate::new("https://example.org/{z}/{x}/{y}/{-y}").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn request_budget() {
+		let budget = RequestBudget::new(Some(2));
+		assert!(!budget.is_exhausted());
+		assert!(budget.take());
+		assert!(budget.take());
+		assert!(budget.is_exhausted());
+		assert!(!budget.take());
+
+		let budget = RequestBudget::new(None);
+		assert!((0..100).all(|_| budget.take()));
+		assert!(!budget.is_exhausted());
+	}
+
+	#[test]
+	fn scrape() -> Result<()> {
+		// serve mock tiles in a separate runtime
+		let runtime = tokio::runtime::Runtime::new()?;
+		let mut server = TileServer::new("127.0.0.1", 50010, true, false);
+		server.add_tile_source(
+			"mock",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed(),
+		)?;
+		runtime.block_on(server.start())?;
+
+		let dir = TempDir::new()?;
+		let output = dir.path().join("scraped.versatiles");
+		let output = output.to_str().unwrap();
+		let download_dir = format!("{output}.download");
+		let command = |extra: &[&str]| {
+			let mut args = vec![
+				"versatiles",
+				"scrape",
+				"http://127.0.0.1:50010/tiles/mock/{z}/{x}/{y}.png",
+				output,
+				"--max-zoom=2",
+				"--concurrency=2",
+			];
+			args.extend_from_slice(extra);
+			run_command(args)
+		};
+
+		// stop after 5 of 21 requests and keep the downloaded tiles
+		let error = command(&["--max-requests=5"]).unwrap_err().to_string();
+		assert!(error.starts_with("request budget exhausted, 16 tiles"), "{error}");
+		assert!(PathBuf::from(&download_dir).join("0/0/0.tile").exists());
+
+		// resume
+		command(&[])?;
+		assert!(!PathBuf::from(&download_dir).exists());
+
+		let reader = runtime.block_on(get_reader(output))?;
+		assert_eq!(reader.get_parameters().tile_format, TileFormat::PNG);
+		let count = runtime.block_on(async {
+			reader
+				.get_bbox_tile_stream(reader.get_parameters().bbox_pyramid.get_level_bbox(2).clone())
+				.await
+				.drain_and_count()
+				.await
+		});
+		assert_eq!(count, 16);
+
+		runtime.block_on(server.stop());
+		Ok(())
+	}
+}
@@ -0,0 +1,331 @@
+//! A search index that maps the names of vector tile features to the tiles containing them.
+//!
+//! The index is built by scanning all tiles of one zoom level. Features are grouped by name, layer and
+//! feature id, so a street that is split across many tiles results in one entry listing all of its tiles.
+//!
+//! Indexes are stored as brotli compressed JSON, usually as a sidecar file next to the container,
+//! e.g. "berlin.versatiles.search". `versatiles serve` loads sidecar files automatically and answers
+//! queries at "/search?q=".
+
+use anyhow::{anyhow, ensure, Context, Result};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_core::{
+	json::{JsonArray, JsonObject, JsonValue},
+	progress::get_progress_bar,
+	types::{Blob, TileBBox, TilesReaderTrait},
+	utils::{compress_brotli, decompress, decompress_brotli},
+};
+use versatiles_geometry::{vector_tile::VectorTile, GeoValue};
+
+/// A named feature and the tiles that contain it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchEntry {
+	pub name: String,
+	pub layer: String,
+	pub id: Option<u64>,
+	/// Columns and rows of the tiles at the zoom level of the index.
+	pub tiles: Vec<(u32, u32)>,
+}
+
+/// Name, layer and id of a feature, the key that groups its parts across tiles.
+type FeatureKey = (String, String, Option<u64>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchIndex {
+	pub property: String,
+	pub zoom: u8,
+	/// Entries, sorted by name.
+	pub entries: Vec<SearchEntry>,
+}
+
+impl SearchIndex {
+	/// Scans all tiles of zoom level `zoom` and indexes the string values of `property`.
+	pub async fn build(reader: &dyn TilesReaderTrait, property: &str, zoom: u8) -> Result<SearchIndex> {
+		let parameters = reader.get_parameters();
+		let bbox = parameters.bbox_pyramid.get_level_bbox(zoom).clone();
+		let compression = parameters.tile_compression;
+
+		let mut features: BTreeMap<FeatureKey, BTreeSet<(u32, u32)>> = BTreeMap::new();
+		let mut error: Option<anyhow::Error> = None;
+
+		let mut progress = get_progress_bar("build search index", bbox.count_tiles());
+		reader
+			.get_bbox_tile_stream(bbox)
+			.await
+			.for_each_sync(|(coord, blob)| {
+				progress.inc(1);
+				if error.is_some() {
+					return;
+				}
+
+				let result = (|| -> Result<()> {
+					let tile = VectorTile::from_blob(&decompress(blob, &compression)?)?;
+					for layer in tile.layers.iter() {
+						for feature in layer.features.iter() {
+							let properties = feature.decode_properties(layer)?;
+							if let Some(GeoValue::String(name)) = properties.get(property) {
+								features
+									.entry((name.clone(), layer.name.clone(), feature.id))
+									.or_default()
+									.insert((coord.x, coord.y));
+							}
+						}
+					}
+					Ok(())
+				})();
+
+				if let Err(e) = result {
+					error = Some(e.context(format!("Failed to index tile {coord:?}")));
+				}
+			})
+			.await;
+		progress.finish();
+
+		if let Some(error) = error {
+			return Err(error);
+		}
+
+		Ok(SearchIndex {
+			property: property.to_owned(),
+			zoom,
+			entries: features
+				.into_iter()
+				.map(|((name, layer, id), tiles)| SearchEntry {
+					name,
+					layer,
+					id,
+					tiles: tiles.into_iter().collect(),
+				})
+				.collect(),
+		})
+	}
+
+	/// Returns the path of the sidecar file of a container, e.g. "berlin.versatiles.search".
+	pub fn get_sidecar_path(container: &Path) -> PathBuf {
+		let mut path = container.as_os_str().to_owned();
+		path.push(".search");
+		PathBuf::from(path)
+	}
+
+	/// Returns up to `limit` entries whose name contains `query`, ignoring case.
+	///
+	/// Exact matches are returned first, followed by names starting with the query, names with a word
+	/// starting with the query and all other matches. Shorter names are preferred within each group.
+	pub fn search(&self, query: &str, limit: usize) -> Vec<&SearchEntry> {
+		let query = query.trim().to_lowercase();
+		if query.is_empty() {
+			return Vec::new();
+		}
+
+		let mut matches: Vec<(u8, &SearchEntry)> = self
+			.entries
+			.iter()
+			.filter_map(|entry| {
+				let name = entry.name.to_lowercase();
+				let position = name.find(&query)?;
+				let rank = if name == query {
+					0
+				} else if position == 0 {
+					1
+				} else if name[..position].ends_with(|c: char| !c.is_alphanumeric()) {
+					2
+				} else {
+					3
+				};
+				Some((rank, entry))
+			})
+			.collect();
+
+		matches.sort_by_key(|(rank, entry)| (*rank, entry.name.len()));
+		matches.into_iter().take(limit).map(|(_, entry)| entry).collect()
+	}
+
+	/// Converts an entry into a JSON object with its tiles and the geographic bounding box of its tiles.
+	pub fn entry_to_json(&self, entry: &SearchEntry) -> Result<JsonObject> {
+		let mut bbox = TileBBox::new_empty(self.zoom)?;
+		entry.tiles.iter().for_each(|(x, y)| bbox.include_coord(*x, *y));
+
+		let mut json = JsonObject::default();
+		json.set("name", JsonValue::from(&entry.name));
+		json.set("layer", JsonValue::from(&entry.layer));
+		if let Some(id) = entry.id {
+			json.set("id", JsonValue::from(id as f64));
+		}
+		json.set("zoom", JsonValue::from(self.zoom));
+		json.set(
+			"tiles",
+			JsonValue::from(
+				entry
+					.tiles
+					.iter()
+					.map(|(x, y)| JsonValue::from(vec![*x as f64, *y as f64]))
+					.collect::<Vec<_>>(),
+			),
+		);
+		json.set("bbox", JsonValue::from(bbox.as_geo_bbox().as_vec()));
+		Ok(json)
+	}
+
+	pub fn to_blob(&self) -> Result<Blob> {
+		let entries = self
+			.entries
+			.iter()
+			.map(|entry| {
+				let tiles: Vec<f64> = entry.tiles.iter().flat_map(|(x, y)| [*x as f64, *y as f64]).collect();
+				JsonValue::from(vec![
+					JsonValue::from(&entry.name),
+					JsonValue::from(&entry.layer),
+					entry.id.map_or(JsonValue::Null, |id| JsonValue::from(id as f64)),
+					JsonValue::from(tiles),
+				])
+			})
+			.collect::<Vec<_>>();
+
+		let mut json = JsonObject::default();
+		json.set("property", JsonValue::from(&self.property));
+		json.set("zoom", JsonValue::from(self.zoom));
+		json.set("entries", JsonValue::from(entries));
+
+		compress_brotli(&Blob::from(json.stringify()))
+	}
+
+	pub fn from_blob(blob: &Blob) -> Result<SearchIndex> {
+		let json = JsonObject::parse_str(decompress_brotli(blob)?.as_str())?;
+
+		let property = json.get_string("property")?.ok_or(anyhow!("missing \"property\""))?;
+		let zoom = json.get_number("zoom")?.ok_or(anyhow!("missing \"zoom\""))?;
+		let entries = json
+			.get_array("entries")?
+			.ok_or(anyhow!("missing \"entries\""))?
+			.0
+			.iter()
+			.map(|entry| {
+				let JsonArray(values) = entry.as_array()?;
+				ensure!(values.len() == 4, "an entry must have 4 values");
+				let tiles = values[3].as_array()?.as_number_vec::<u32>()?;
+				ensure!(tiles.len() % 2 == 0, "tiles must be pairs of x and y");
+				Ok(SearchEntry {
+					name: values[0].as_string()?,
+					layer: values[1].as_string()?,
+					id: match &values[2] {
+						JsonValue::Null => None,
+						value => Some(value.as_number()?),
+					},
+					tiles: tiles.chunks(2).map(|c| (c[0], c[1])).collect(),
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+
+		Ok(SearchIndex {
+			property,
+			zoom,
+			entries,
+		})
+	}
+
+	pub fn read_file(path: &Path) -> Result<SearchIndex> {
+		let blob = Blob::from(fs::read(path).with_context(|| format!("reading search index {path:?}"))?);
+		SearchIndex::from_blob(&blob).with_context(|| format!("parsing search index {path:?}"))
+	}
+
+	pub fn write_file(&self, path: &Path) -> Result<()> {
+		fs::write(path, self.to_blob()?.as_slice()).with_context(|| format!("writing search index {path:?}"))
+	}
+}
+
+#[cfg(test)]
+pub mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+	use versatiles_container::get_reader;
+	use versatiles_geometry::{vector_tile::VectorTileLayer, GeoFeature, Geometry};
+
+	/// Writes a directory of vector tiles at zoom level 2 with a few named places.
+	pub fn make_test_tiles(dir: &Path) -> Result<()> {
+		let places = [
+			(1, 1, "Berlin", 1),
+			(1, 1, "Berlin-Mitte", 2),
+			(2, 1, "Berlin", 1),
+			(2, 1, "Neuberlin", 3),
+			(2, 2, "Paris", 4),
+		];
+
+		for (x, y) in [(1, 1), (2, 1), (2, 2)] {
+			let features = places
+				.iter()
+				.filter(|p| p.0 == x && p.1 == y)
+				.map(|(_, _, name, id)| {
+					let mut feature = GeoFeature::new(Geometry::new_point([100, 100]));
+					feature.set_id(GeoValue::from(*id as u64));
+					feature.set_property("name".to_string(), *name);
+					feature
+				})
+				.collect();
+			let layer = VectorTileLayer::from_features("place".to_string(), features, 4096, 1)?;
+			fs::create_dir_all(dir.join(format!("2/{x}")))?;
+			fs::write(
+				dir.join(format!("2/{x}/{y}.pbf")),
+				VectorTile::new(vec![layer]).to_blob()?.as_slice(),
+			)?;
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn build_and_search() -> Result<()> {
+		let dir = TempDir::new()?;
+		make_test_tiles(dir.path())?;
+		let reader = get_reader(dir.path().to_str().unwrap()).await?;
+
+		let index = SearchIndex::build(reader.as_ref(), "name", 2).await?;
+		assert_eq!(index.entries.len(), 4);
+		assert_eq!(
+			index.entries[0],
+			SearchEntry {
+				name: "Berlin".to_string(),
+				layer: "place".to_string(),
+				id: Some(1),
+				tiles: vec![(1, 1), (2, 1)],
+			}
+		);
+
+		let names =
+			|query: &str| -> Vec<String> { index.search(query, 10).iter().map(|entry| entry.name.clone()).collect() };
+		assert_eq!(names("berlin"), ["Berlin", "Berlin-Mitte", "Neuberlin"]);
+		assert_eq!(names("MITTE"), ["Berlin-Mitte"]);
+		assert_eq!(names(" "), Vec::<String>::new());
+		assert_eq!(index.search("berlin", 1).len(), 1);
+
+		let json = index.entry_to_json(&index.entries[0])?;
+		assert_eq!(json.get("tiles").unwrap().stringify(), "[[1,1],[2,1]]");
+		assert_eq!(
+			json.get_number_array::<f64, 4>("bbox")?.unwrap()[0..3],
+			[-90.0, 0.0, 90.0]
+		);
+		assert_eq!(json.get_number::<u64>("id")?, Some(1));
+		Ok(())
+	}
+
+	#[test]
+	fn blob_roundtrip() -> Result<()> {
+		let index = SearchIndex {
+			property: "name".to_string(),
+			zoom: 14,
+			entries: vec![SearchEntry {
+				name: "Alexanderplatz".to_string(),
+				layer: "street_labels".to_string(),
+				id: None,
+				tiles: vec![(8803, 5372), (8803, 5373)],
+			}],
+		};
+		assert_eq!(SearchIndex::from_blob(&index.to_blob()?)?, index);
+
+		let path = SearchIndex::get_sidecar_path(Path::new("/data/berlin.versatiles"));
+		assert_eq!(path, PathBuf::from("/data/berlin.versatiles.search"));
+		Ok(())
+	}
+}
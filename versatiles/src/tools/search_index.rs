@@ -0,0 +1,84 @@
+use super::search::SearchIndex;
+use anyhow::{ensure, Result};
+use std::path::{Path, PathBuf};
+use versatiles_container::get_reader;
+use versatiles_core::types::TileFormat;
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// tile container with vector tiles
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	filename: String,
+
+	/// write the index to this file, defaults to the sidecar file "<FILENAME>.search",
+	/// which is loaded automatically by "versatiles serve"
+	#[arg(long, short, value_name = "FILE", verbatim_doc_comment)]
+	output: Option<PathBuf>,
+
+	/// feature property that contains the names
+	#[arg(long, default_value = "name")]
+	property: String,
+
+	/// zoom level of the scanned tiles, defaults to the highest zoom level of the container
+	#[arg(long, value_name = "int")]
+	zoom: Option<u8>,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let reader = get_reader(&arguments.filename).await?;
+	let parameters = reader.get_parameters();
+	ensure!(
+		parameters.tile_format == TileFormat::PBF,
+		"search indexes can only be built for vector tiles"
+	);
+
+	let bbox_pyramid = &parameters.bbox_pyramid;
+	let zoom = match arguments.zoom {
+		Some(zoom) => zoom,
+		None => bbox_pyramid.get_zoom_max().unwrap_or(0),
+	};
+	ensure!(
+		bbox_pyramid.get_zoom_max().is_some_and(|max| zoom <= max) && !bbox_pyramid.get_level_bbox(zoom).is_empty(),
+		"the container has no tiles at zoom level {zoom}"
+	);
+
+	let index = SearchIndex::build(reader.as_ref(), &arguments.property, zoom).await?;
+
+	let output = match &arguments.output {
+		Some(path) => path.clone(),
+		None => SearchIndex::get_sidecar_path(Path::new(&arguments.filename)),
+	};
+	index.write_file(&output)?;
+	eprintln!("wrote {} names to {output:?}", index.entries.len());
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{tests::run_command, tools::search::tests::make_test_tiles};
+	use assert_fs::TempDir;
+
+	#[test]
+	fn search_index() -> Result<()> {
+		let dir = TempDir::new()?;
+		let tiles = dir.path().join("tiles");
+		make_test_tiles(&tiles)?;
+		let filename = tiles.to_str().unwrap();
+
+		run_command(vec!["versatiles", "search-index", filename])?;
+		let index = SearchIndex::read_file(&dir.path().join("tiles.search"))?;
+		assert_eq!(index.zoom, 2);
+		assert_eq!(index.search("paris", 10).len(), 1);
+
+		let error = run_command(vec!["versatiles", "search-index", filename, "--zoom", "5"])
+			.unwrap_err()
+			.to_string();
+		assert_eq!(error, "the container has no tiles at zoom level 5");
+
+		Ok(())
+	}
+}
@@ -1,11 +1,27 @@
-use super::server::{TileServer, Url};
-use anyhow::Result;
+use super::{
+	search::SearchIndex,
+	server::{
+		CompressionPolicy, ConnectionOptions, CorsRule, ListenAddress, RateLimit, SourceOpener, TileServer,
+		UpstreamLimits, Url,
+	},
+};
+use anyhow::{ensure, Context, Result};
+use futures::future::BoxFuture;
 use regex::Regex;
-use std::path::Path;
+use std::{
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 use tokio::time::{sleep, Duration};
-use versatiles_container::{get_reader, TilesConvertReader, TilesConverterParameters};
+use versatiles_container::{
+	get_reader_with_options, CachePolicy, CachedReader, ReaderOptions, TilesConvertReader, TilesConverterParameters,
+	WatchedPipelineReader,
+};
 use versatiles_core::types::{TileCompression, TilesReaderTrait};
 
+/// Megabytes of tiles that are cached per pipeline, unless "--cache-size" is set.
+const DEFAULT_PIPELINE_CACHE_SIZE: u64 = 256;
+
 #[derive(clap::Args, Debug)]
 #[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
 pub struct Subcommand {
@@ -17,6 +33,11 @@ pub struct Subcommand {
 	///    e.g. ".../ukraine.versatiles" will be served at url "/tiles/ukraine/..."
 	/// You can also configure a different id for each file using:
 	///    "[id]file", "file[id]" or "file#id"
+	/// Pipelines (*.vpl) are executed on request and reloaded whenever the file changes.
+	/// Send SIGHUP to open all containers again, e.g. after they have been replaced.
+	/// Local *.versatiles and *.pmtiles files are also served at "/files/$id.versatiles" or "/files/$id.pmtiles",
+	///    supporting range requests for client-side readers.
+	/// Search indexes of local containers ("file.search", see "versatiles search-index") are served at "/search?q=".
 	#[arg(num_args = 1.., required = true, verbatim_doc_comment)]
 	pub tile_sources: Vec<String>,
 
@@ -28,6 +49,44 @@ pub struct Subcommand {
 	#[arg(short, long, default_value = "8080", display_order = 0)]
 	pub port: u16,
 
+	/// Listen on this address instead of "--ip" and "--port". Can be used multiple times.
+	/// Use e.g. "0.0.0.0:8080", "[::]:8080" or a Unix domain socket like "unix:/run/versatiles.sock".
+	#[arg(short = 'l', long, value_name = "ADDRESS", verbatim_doc_comment, display_order = 0)]
+	pub listen: Vec<String>,
+
+	/// Serve HTTPS with this certificate chain (PEM file), e.g. "fullchain.pem". Requires "--tls-key".
+	/// Browsers use HTTP/2 only over HTTPS, fetching all tiles over one connection.
+	#[arg(
+		long,
+		value_name = "FILE",
+		requires = "tls_key",
+		verbatim_doc_comment,
+		display_order = 0
+	)]
+	pub tls_cert: Option<PathBuf>,
+
+	/// Private key of "--tls-cert" (PEM file), e.g. "privkey.pem".
+	#[arg(long, value_name = "FILE", requires = "tls_cert", display_order = 0)]
+	pub tls_key: Option<PathBuf>,
+
+	/// Close HTTP/1.1 connections that don't send another request within x seconds. 0 disables keep-alive.
+	#[arg(long, value_name = "SECONDS", default_value = "60", display_order = 2)]
+	pub idle_timeout: u64,
+
+	/// Send HTTP/2 pings every x seconds and close connections that don't answer in time.
+	#[arg(long, value_name = "SECONDS", display_order = 2)]
+	pub http2_keep_alive: Option<u64>,
+
+	/// maximum number of requests a client can send at the same time over one HTTP/2 connection.
+	#[arg(long, value_name = "REQUESTS", default_value = "256", display_order = 2)]
+	pub max_concurrent_streams: u32,
+
+	/// Serve the tiles of a source at another URL, given as "id=template", e.g. "osm=/data/osm/{z}/{x}/{y}.pbf".
+	/// The placeholders can be in any order, e.g. "/tiles/osm/{z}/{y}/{x}".
+	/// Use "{-y}" for legacy TMS clients, that count rows from the bottom. Can be used multiple times.
+	#[arg(long, value_name = "ID=TEMPLATE", verbatim_doc_comment, display_order = 1)]
+	pub url_template: Vec<String>,
+
 	/// Serve static content at "http:/.../" from a local folder or a tar file.
 	/// Tar files can be compressed (.tar / .tar.gz / .tar.br).
 	/// If multiple static sources are defined, the first hit will be served.
@@ -35,10 +94,25 @@ pub struct Subcommand {
 	#[arg(short = 's', long = "static", verbatim_doc_comment, display_order = 1)]
 	pub static_content: Vec<String>,
 
+	/// Public URL of the server, e.g. "https://tiles.example.org".
+	/// Server-relative URLs in served styles and TileJSON are made absolute using this URL.
+	/// Defaults to the "X-Forwarded-Proto", "X-Forwarded-Host" and "Host" headers of the request.
+	#[arg(long, value_name = "URL", verbatim_doc_comment, display_order = 1)]
+	pub public_url: Option<String>,
+
 	/// Shutdown server automatically after x milliseconds.
 	#[arg(long, display_order = 4)]
 	pub auto_shutdown: Option<u64>,
 
+	/// export tracing spans of requests to an OpenTelemetry collector, e.g. "http://localhost:4318/v1/traces".
+	#[cfg(feature = "otel")]
+	#[arg(long, value_name = "URL", display_order = 4)]
+	pub otlp_endpoint: Option<String>,
+
+	/// On shutdown, wait up to x seconds for requests in flight to finish.
+	#[arg(long, value_name = "SECONDS", default_value = "30", display_order = 4)]
+	pub shutdown_timeout: u64,
+
 	/// swap rows and columns, e.g. z/x/y -> z/y/x
 	#[arg(long, display_order = 3)]
 	pub swap_xy: bool,
@@ -51,19 +125,145 @@ pub struct Subcommand {
 	#[arg(long, display_order = 2)]
 	pub fast: bool,
 
+	/// send uncompressed tiles smaller than this many bytes without compressing them
+	#[arg(long, value_name = "BYTES", default_value = "0", display_order = 2)]
+	pub compress_min_size: u64,
+
+	/// recompress tiles larger than this many bytes only if the client doesn't accept their stored compression
+	#[arg(long, value_name = "BYTES", display_order = 2)]
+	pub compress_max_size: Option<u64>,
+
+	/// share of one CPU core that may be used to recompress tiles, e.g. 0.5.
+	/// If it is used up, tiles are sent in their stored compression.
+	#[arg(long, value_name = "CORES", verbatim_doc_comment, display_order = 2)]
+	pub compress_cpu_budget: Option<f64>,
+
 	/// disable API
 	#[arg(long, display_order = 4)]
 	pub disable_api: bool,
 
+	/// enable the admin API at "/admin/" to list, add, replace and remove tile sources at runtime.
+	/// Requests must send the token as "Authorization: Bearer <TOKEN>".
+	#[arg(long, value_name = "TOKEN", verbatim_doc_comment, display_order = 4)]
+	pub admin_token: Option<String>,
+
+	/// limit the requests of every client to this many requests per second, answering with "429 Too Many Requests".
+	/// Clients are identified by their IP address or by one of the API keys in "--api-key".
+	#[arg(long, value_name = "REQUESTS", verbatim_doc_comment, display_order = 2)]
+	pub rate_limit: Option<f64>,
+
+	/// API key, sent in the "X-API-Key" header, that gets its own "--rate-limit". Can be used multiple times.
+	#[arg(long, value_name = "KEY", requires = "rate_limit", display_order = 2)]
+	pub api_key: Vec<String>,
+
+	/// number of requests a client can send at once before "--rate-limit" applies. Defaults to twice the rate limit.
+	#[arg(long, value_name = "REQUESTS", requires = "rate_limit", display_order = 2)]
+	pub rate_limit_burst: Option<u32>,
+
+	/// maximum number of requests that are handled at the same time. Further requests get "429 Too Many Requests".
+	#[arg(long, value_name = "REQUESTS", display_order = 2)]
+	pub max_requests: Option<usize>,
+
+	/// maximum number of range requests that are sent at the same time to each remote container (http:// or https://).
+	/// Further requests wait in a queue. The queues can be monitored with the admin API at "/admin/upstream".
+	#[arg(long, value_name = "REQUESTS", verbatim_doc_comment, display_order = 2)]
+	pub max_upstream_requests: Option<usize>,
+
+	/// maximum number of tiles of extracts for offline use at "/extract/{id}". 0 disables extracts. Defaults to 100000.
+	#[arg(long, value_name = "TILES", display_order = 2)]
+	pub max_extract_tiles: Option<u64>,
+
+	/// allow cross-origin requests only from this origin, instead of from all origins. Can be used multiple times.
+	/// Use e.g. "https://example.org", "https://*.example.org" or "*" for all origins.
+	/// To apply it only to a path, add a url prefix like "[/tiles/private/]https://example.org".
+	/// The rule with the longest matching prefix applies, other paths get no CORS headers.
+	#[arg(long, value_name = "ORIGIN", verbatim_doc_comment, display_order = 2)]
+	pub cors_origin: Vec<String>,
+
+	/// additional request headers allowed in cross-origin requests, e.g. "X-API-Key,Authorization"
+	#[arg(long, value_name = "HEADERS", value_delimiter = ',', display_order = 2)]
+	pub cors_headers: Vec<String>,
+
+	/// number of seconds browsers may cache the answers of CORS preflight requests
+	#[arg(long, value_name = "SECONDS", display_order = 2)]
+	pub cors_max_age: Option<u64>,
+
+	/// cache up to this many megabytes of tiles per tile source in memory, e.g. to speed up remote sources.
+	/// Pipelines are always cached, by default up to 256 MB.
+	#[arg(long, value_name = "MB", verbatim_doc_comment, display_order = 2)]
+	pub cache_size: Option<u64>,
+
+	/// load the whole tile index of *.versatiles containers into memory when opening them, for consistently fast lookups.
+	/// Optionally, also keep the tiles of this many blocks in memory, starting with the lowest zoom levels.
+	#[arg(
+		long,
+		value_name = "BLOCKS",
+		num_args = 0..=1,
+		default_missing_value = "0",
+		verbatim_doc_comment,
+		display_order = 2
+	)]
+	pub preload: Option<usize>,
+
+	/// number of SQLite connections per *.mbtiles source, which limits the parallel range queries
+	/// (default: --io-concurrency)
+	#[arg(long, value_name = "int", verbatim_doc_comment, display_order = 2)]
+	pub mbtiles_pool_size: Option<u32>,
+
 	/// override the compression of the input source, e.g. to handle gzipped tiles in a tar, that do not end in .gz
 	/// (deprecated in favor of a better solution that does not yet exist)
 	#[arg(long, value_enum, value_name = "COMPRESSION", display_order = 4)]
 	override_input_compression: Option<TileCompression>,
 }
 
-#[tokio::main]
 pub async fn run(arguments: &Subcommand) -> Result<()> {
+	#[cfg(feature = "otel")]
+	let telemetry = match &arguments.otlp_endpoint {
+		Some(endpoint) => Some(super::server::Telemetry::init(endpoint)?),
+		None => None,
+	};
+
 	let mut server: TileServer = TileServer::new(&arguments.ip, arguments.port, !arguments.fast, !arguments.disable_api);
+	if let Some(public_url) = &arguments.public_url {
+		server.set_public_url(public_url);
+	}
+	for listen in arguments.listen.iter() {
+		server.add_listen_address(listen.parse::<ListenAddress>()?);
+	}
+	server.set_connection_options(get_connection_options(arguments)?);
+	if let Some(cpu_budget) = arguments.compress_cpu_budget {
+		ensure!(cpu_budget >= 0.0, "--compress-cpu-budget must not be negative");
+	}
+	server.set_compression_policy(CompressionPolicy {
+		min_size: arguments.compress_min_size,
+		max_size: arguments.compress_max_size,
+		cpu_budget: arguments.compress_cpu_budget,
+	});
+	let upstream_limits = arguments.max_upstream_requests.map(UpstreamLimits::new).transpose()?;
+	if let Some(limits) = &upstream_limits {
+		server.set_upstream_limits(limits.clone());
+	}
+	server.set_source_opener(get_source_opener(arguments, upstream_limits));
+	if let Some(admin_token) = &arguments.admin_token {
+		server.set_admin_token(admin_token);
+	}
+	if let Some(requests_per_second) = arguments.rate_limit {
+		ensure!(requests_per_second > 0.0, "--rate-limit must be positive");
+		server.set_rate_limit(RateLimit {
+			requests_per_second,
+			burst: arguments
+				.rate_limit_burst
+				.unwrap_or((requests_per_second * 2.0).ceil() as u32),
+			api_keys: arguments.api_key.clone(),
+		});
+	}
+	if let Some(max_requests) = arguments.max_requests {
+		server.set_max_in_flight(max_requests);
+	}
+	if let Some(max_extract_tiles) = arguments.max_extract_tiles {
+		server.set_max_extract_tiles(max_extract_tiles);
+	}
+	server.set_cors_rules(get_cors_rules(arguments));
 
 	let tile_patterns: Vec<Regex> = [
 		r"^\[(?P<id>[^\]]+?)\](?P<url>.*)$",
@@ -99,20 +299,19 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 			Some(m) => m.as_str(),
 		};
 
-		let mut reader = get_reader(url).await?;
+		server.add_tile_source_url(id, url).await?;
 
-		if arguments.override_input_compression.is_some() {
-			reader.override_compression(arguments.override_input_compression.unwrap())
-		}
-
-		if arguments.flip_y || arguments.swap_xy {
-			let mut cp = TilesConverterParameters::new_default();
-			cp.flip_y = arguments.flip_y;
-			cp.swap_xy = arguments.swap_xy;
-			reader = TilesConvertReader::new_from_reader(reader, cp)?.boxed();
+		let sidecar = SearchIndex::get_sidecar_path(Path::new(url));
+		if sidecar.is_file() {
+			server.add_search_index(id, SearchIndex::read_file(&sidecar)?);
 		}
+	}
 
-		server.add_tile_source(id, reader)?;
+	for argument in arguments.url_template.iter() {
+		let (id, template) = argument
+			.split_once('=')
+			.with_context(|| format!("--url-template must have the form id=template, but got {argument:?}"))?;
+		server.set_url_template(id, template)?;
 	}
 
 	for argument in arguments.static_content.iter() {
@@ -143,17 +342,164 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 	if let Some(milliseconds) = arguments.auto_shutdown {
 		sleep(Duration::from_millis(milliseconds)).await
 	} else {
-		loop {
-			sleep(Duration::from_secs(60)).await
+		wait_for_shutdown_signal(&server).await?;
+	}
+
+	server.shutdown(Duration::from_secs(arguments.shutdown_timeout)).await;
+
+	#[cfg(feature = "otel")]
+	if let Some(telemetry) = telemetry {
+		telemetry.shutdown()?;
+	}
+
+	Ok(())
+}
+
+/// Waits for SIGTERM or Ctrl+C. On SIGHUP all containers are opened again.
+#[cfg(unix)]
+async fn wait_for_shutdown_signal(server: &TileServer) -> Result<()> {
+	use tokio::signal::unix::{signal, SignalKind};
+
+	let mut terminate = signal(SignalKind::terminate())?;
+	let mut hangup = signal(SignalKind::hangup())?;
+
+	loop {
+		tokio::select! {
+			_ = terminate.recv() => break,
+			_ = tokio::signal::ctrl_c() => break,
+			_ = hangup.recv() => match server.reload_tile_sources().await {
+				Ok(count) => eprintln!("reloaded {count} tile sources"),
+				Err(err) => eprintln!("reloading tile sources failed, keeping the previous ones: {err:#}"),
+			},
 		}
 	}
 
+	eprintln!("shutting down server");
+	Ok(())
+}
+
+/// Waits for Ctrl+C.
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal(_server: &TileServer) -> Result<()> {
+	tokio::signal::ctrl_c().await?;
+	eprintln!("shutting down server");
 	Ok(())
 }
 
+fn get_connection_options(arguments: &Subcommand) -> Result<ConnectionOptions> {
+	ensure!(
+		arguments.max_concurrent_streams > 0,
+		"--max-concurrent-streams must be positive"
+	);
+
+	let mut options = ConnectionOptions::new(
+		Duration::from_secs(arguments.idle_timeout),
+		arguments.http2_keep_alive.map(Duration::from_secs),
+		arguments.max_concurrent_streams,
+	);
+	if let (Some(cert_file), Some(key_file)) = (&arguments.tls_cert, &arguments.tls_key) {
+		options.set_tls(cert_file, key_file)?;
+	}
+	Ok(options)
+}
+
+/// Groups the "--cors-origin" arguments by their url prefix. Without them, all origins are allowed.
+fn get_cors_rules(arguments: &Subcommand) -> Vec<CorsRule> {
+	let pattern = Regex::new(r"^\[(?P<path>[^\]]+?)\](?P<origin>.*)$").unwrap();
+
+	let mut origins: Vec<(String, String)> = arguments
+		.cors_origin
+		.iter()
+		.map(|argument| match pattern.captures(argument) {
+			Some(capture) => (
+				Url::new(&capture["path"]).as_dir().as_string(),
+				capture["origin"].to_owned(),
+			),
+			None => (String::from("/"), argument.to_owned()),
+		})
+		.collect();
+	if origins.is_empty() {
+		origins.push((String::from("/"), String::from("*")));
+	}
+
+	let mut rules: Vec<CorsRule> = Vec::new();
+	for (path, origin) in origins {
+		match rules.iter_mut().find(|rule| rule.path == path) {
+			Some(rule) => rule.origins.push(origin),
+			None => rules.push(CorsRule {
+				path,
+				origins: vec![origin],
+				headers: arguments.cors_headers.clone(),
+				max_age: arguments.cors_max_age,
+			}),
+		}
+	}
+	rules
+}
+
+/// Returns a function that opens containers with the options of the command line.
+fn get_source_opener(arguments: &Subcommand, upstream_limits: Option<UpstreamLimits>) -> SourceOpener {
+	let cache_size = arguments.cache_size;
+	let preload = arguments.preload;
+	let mbtiles_pool_size = arguments.mbtiles_pool_size;
+	let override_input_compression = arguments.override_input_compression;
+	let flip_y = arguments.flip_y;
+	let swap_xy = arguments.swap_xy;
+
+	Arc::new(
+		move |url: String| -> BoxFuture<'static, Result<Box<dyn TilesReaderTrait>>> {
+			let upstream_limits = upstream_limits.clone();
+			Box::pin(async move {
+				let is_pipeline = url.ends_with(".vpl") && Path::new(&url).is_file();
+				let mut reader = if is_pipeline {
+					let cache_size = cache_size.unwrap_or(DEFAULT_PIPELINE_CACHE_SIZE);
+					WatchedPipelineReader::open_path(Path::new(&url), cache_size * 1024 * 1024)
+						.await?
+						.boxed()
+				} else {
+					let is_remote = url.starts_with("http://") || url.starts_with("https://");
+					let options = ReaderOptions {
+						request_limiter: upstream_limits
+							.filter(|_| is_remote)
+							.map(|limits| limits.get_limiter(&url)),
+						mbtiles_pool_size,
+						..Default::default()
+					};
+					get_reader_with_options(&url, &options).await?
+				};
+
+				if let Some(pinned_blocks) = preload {
+					reader.preload(pinned_blocks).await?;
+				}
+
+				if let Some(compression) = override_input_compression {
+					reader.override_compression(compression)
+				}
+
+				if flip_y || swap_xy {
+					let mut cp = TilesConverterParameters::new_default();
+					cp.flip_y = flip_y;
+					cp.swap_xy = swap_xy;
+					reader = TilesConvertReader::new_from_reader(reader, cp)?.boxed();
+				}
+
+				if let Some(cache_size) = cache_size.filter(|_| !is_pipeline) {
+					let policy = CachePolicy::Memory {
+						max_size: cache_size * 1024 * 1024,
+					};
+					reader = CachedReader::new(reader, policy)?.boxed();
+				}
+
+				Ok(reader)
+			})
+		},
+	)
+}
+
 #[cfg(test)]
 mod tests {
-	use crate::tests::run_command;
+	use crate::{tests::run_command, tools::server::write_test_certificate};
+	use assert_fs::TempDir;
 
 	#[test]
 	fn test_local() {
@@ -171,6 +517,180 @@ mod tests {
 		.unwrap();
 	}
 
+	#[test]
+	fn test_preload() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65009",
+			"--auto-shutdown",
+			"500",
+			"--preload",
+			"10",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_cache_size() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65003",
+			"--auto-shutdown",
+			"500",
+			"--cache-size",
+			"10",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_tls() {
+		let dir = TempDir::new().unwrap();
+		let (cert_file, key_file) = write_test_certificate(dir.path()).unwrap();
+		let (cert_file, key_file) = (cert_file.to_str().unwrap(), key_file.to_str().unwrap());
+
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65010",
+			"--auto-shutdown",
+			"500",
+			"--tls-cert",
+			cert_file,
+			"--tls-key",
+			key_file,
+			"--idle-timeout",
+			"5",
+			"--http2-keep-alive",
+			"10",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+
+		// the certificate requires a key
+		assert!(run_command(vec![
+			"versatiles",
+			"serve",
+			"--tls-cert",
+			cert_file,
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.is_err());
+	}
+
+	#[test]
+	fn test_compression_policy() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65011",
+			"--auto-shutdown",
+			"500",
+			"--compress-min-size",
+			"512",
+			"--compress-max-size",
+			"100000",
+			"--compress-cpu-budget",
+			"0.5",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_rate_limit() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65005",
+			"--auto-shutdown",
+			"500",
+			"--rate-limit",
+			"10",
+			"--api-key",
+			"cheese",
+			"--max-requests",
+			"100",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_cors() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65008",
+			"--auto-shutdown",
+			"500",
+			"--cors-origin",
+			"https://*.example.org",
+			"--cors-origin",
+			"[/tiles/test]https://example.org",
+			"--cors-headers",
+			"X-API-Key,Authorization",
+			"--cors-max-age",
+			"3600",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_listen() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"--listen",
+			"127.0.0.1:65006",
+			"--listen",
+			"127.0.0.1:65007",
+			"--auto-shutdown",
+			"500",
+			"../testdata/berlin.mbtiles[test]",
+		])
+		.unwrap();
+	}
+
+	#[test]
+	fn test_pipeline() {
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"-i",
+			"127.0.0.1",
+			"-p",
+			"65004",
+			"--auto-shutdown",
+			"500",
+			"../testdata/berlin.vpl",
+		])
+		.unwrap();
+	}
+
 	#[test]
 	fn test_remote() {
 		run_command(vec![
@@ -182,6 +702,8 @@ mod tests {
 			"65002",
 			"--auto-shutdown",
 			"500",
+			"--max-upstream-requests",
+			"4",
 			"[test]https://download.versatiles.org/osm.versatiles",
 		])
 		.unwrap();
@@ -0,0 +1,307 @@
+//! Admin API to manage the tile sources of a running server.
+//!
+//! The API is only enabled if an admin token is set. Every request must send the token as
+//! "Authorization: Bearer <token>".
+//!
+//! - `GET /admin/sources`: lists all tile sources.
+//! - `POST /admin/sources`: opens a container and adds it as tile source. The body is a JSON object
+//!   like `{"id":"planet","url":"planet-2024-06.versatiles"}`. A source with the same id is replaced,
+//!   so containers can be rotated without downtime.
+//! - `DELETE /admin/sources/{id}`: removes a tile source.
+//! - `POST /admin/flush`: opens all containers again, which also clears their caches. If a container can't be
+//!   opened, all sources are kept.
+//! - `GET /admin/compression`: counts how tiles were sent: as stored, recompressed or uncompressed.
+//! - `GET /admin/upstream`: lists the running and queued range requests of every remote container.
+
+use super::{
+	compression_policy::TileCompressor,
+	tile_server::{insert_tile_source, ok_json, open_tile_source, reopen_tile_sources, SourceOpener, TileSources},
+	upstream::UpstreamLimits,
+};
+use anyhow::{anyhow, Result};
+use axum::{
+	body::{Body, Bytes},
+	extract::{Path, State},
+	http::{
+		header::{AUTHORIZATION, CONTENT_TYPE},
+		HeaderMap, StatusCode,
+	},
+	response::Response,
+	routing::{delete, get, post},
+	Router,
+};
+use std::sync::Arc;
+use versatiles_core::json::{JsonObject, JsonValue};
+
+#[derive(Clone)]
+pub struct AdminState {
+	pub token: String,
+	pub tile_sources: TileSources,
+	pub opener: Option<SourceOpener>,
+	pub compressor: Arc<TileCompressor>,
+	pub upstream_limits: Option<UpstreamLimits>,
+}
+
+pub fn add_admin_api_to_app(app: Router, state: AdminState) -> Router {
+	let admin_app = Router::new()
+		.route("/admin/sources", get(list_sources).post(add_source))
+		.route("/admin/sources/{id}", delete(remove_source))
+		.route("/admin/flush", post(flush))
+		.route("/admin/compression", get(compression_stats))
+		.route("/admin/upstream", get(upstream_stats))
+		.with_state(state);
+
+	app.merge(admin_app)
+}
+
+async fn list_sources(headers: HeaderMap, State(state): State<AdminState>) -> Response<Body> {
+	if let Some(response) = reject_unauthorized(&headers, &state) {
+		return response;
+	}
+
+	let tile_sources = state.tile_sources.read().unwrap().clone();
+	let mut list = Vec::new();
+	for tile_source in tile_sources.iter() {
+		list.push(JsonValue::Object(tile_source.get_info().await));
+	}
+	ok_json(&JsonValue::from(list).stringify())
+}
+
+async fn add_source(headers: HeaderMap, State(state): State<AdminState>, body: Bytes) -> Response<Body> {
+	if let Some(response) = reject_unauthorized(&headers, &state) {
+		return response;
+	}
+
+	let (id, url) = match parse_source_request(&body) {
+		Ok(request) => request,
+		Err(err) => return json_error(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+	};
+
+	let source = match open_tile_source(&state.opener, &id, &url).await {
+		Ok(source) => source,
+		Err(err) => return json_error(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+	};
+	let info = source.get_info().await;
+
+	if let Err(err) = insert_tile_source(&mut state.tile_sources.write().unwrap(), source, true) {
+		return json_error(StatusCode::CONFLICT, &format!("{err:#}"));
+	}
+
+	log::info!("admin: added source '{id}' from '{url}'");
+	ok_json(&JsonValue::Object(info).stringify())
+}
+
+async fn remove_source(headers: HeaderMap, State(state): State<AdminState>, Path(id): Path<String>) -> Response<Body> {
+	if let Some(response) = reject_unauthorized(&headers, &state) {
+		return response;
+	}
+
+	let mut tile_sources = state.tile_sources.write().unwrap();
+	let Some(index) = tile_sources.iter().position(|source| source.id == id) else {
+		return json_error(StatusCode::NOT_FOUND, &format!("unknown source '{id}'"));
+	};
+	tile_sources.remove(index);
+
+	log::info!("admin: removed source '{id}'");
+	ok_json(&format!("{{\"removed\":{}}}", JsonValue::from(&id).stringify()))
+}
+
+async fn flush(headers: HeaderMap, State(state): State<AdminState>) -> Response<Body> {
+	if let Some(response) = reject_unauthorized(&headers, &state) {
+		return response;
+	}
+
+	let count = match reopen_tile_sources(&state.tile_sources, &state.opener).await {
+		Ok(count) => count,
+		Err(err) => return json_error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{err:#}")),
+	};
+
+	log::info!("admin: reopened {count} sources");
+	ok_json(&format!("{{\"reopened\":{count}}}"))
+}
+
+async fn compression_stats(headers: HeaderMap, State(state): State<AdminState>) -> Response<Body> {
+	if let Some(response) = reject_unauthorized(&headers, &state) {
+		return response;
+	}
+
+	ok_json(&JsonValue::Object(state.compressor.get_stats()).stringify())
+}
+
+async fn upstream_stats(headers: HeaderMap, State(state): State<AdminState>) -> Response<Body> {
+	if let Some(response) = reject_unauthorized(&headers, &state) {
+		return response;
+	}
+
+	let stats = state
+		.upstream_limits
+		.map(|limits| limits.get_stats())
+		.unwrap_or_default();
+	ok_json(&JsonValue::Object(stats).stringify())
+}
+
+/// Returns the id and url of a request to add a source.
+fn parse_source_request(body: &[u8]) -> Result<(String, String)> {
+	let json = JsonObject::parse_str(std::str::from_utf8(body)?)?;
+	let id = json.get_string("id")?.ok_or(anyhow!("missing \"id\""))?;
+	let url = json.get_string("url")?.ok_or(anyhow!("missing \"url\""))?;
+	Ok((id, url))
+}
+
+/// Returns an error response if the request doesn't carry the admin token.
+fn reject_unauthorized(headers: &HeaderMap, state: &AdminState) -> Option<Response<Body>> {
+	let token = headers
+		.get(AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+
+	match token {
+		Some(token) if constant_time_eq(token.as_bytes(), state.token.as_bytes()) => None,
+		_ => Some(json_error(StatusCode::UNAUTHORIZED, "invalid admin token")),
+	}
+}
+
+/// Compares two byte strings in a time that does not depend on their content.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub(super) fn json_error(status: StatusCode, message: &str) -> Response<Body> {
+	let mut json = JsonObject::default();
+	json.set("error", JsonValue::from(message));
+	Response::builder()
+		.status(status)
+		.header(CONTENT_TYPE, "application/json")
+		.body(Body::from(json.stringify()))
+		.expect("should have build admin error")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::TileServer;
+	use super::*;
+	use futures::future::BoxFuture;
+	use std::sync::Arc;
+	use versatiles_container::{MockTilesReader, MockTilesReaderProfile};
+	use versatiles_core::types::TilesReaderTrait;
+
+	const IP: &str = "127.0.0.1";
+	const PORT: u16 = 50009;
+
+	#[test]
+	fn test_constant_time_eq() {
+		assert!(constant_time_eq(b"secret", b"secret"));
+		assert!(!constant_time_eq(b"secret", b"secreT"));
+		assert!(!constant_time_eq(b"secret", b"secret2"));
+	}
+
+	#[test]
+	fn test_parse_source_request() -> Result<()> {
+		let (id, url) = parse_source_request(br#"{"id":"planet","url":"planet.versatiles"}"#)?;
+		assert_eq!(id, "planet");
+		assert_eq!(url, "planet.versatiles");
+		assert!(parse_source_request(br#"{"id":"planet"}"#).is_err());
+		assert!(parse_source_request(b"planet").is_err());
+		Ok(())
+	}
+
+	fn open_mock(url: String) -> BoxFuture<'static, Result<Box<dyn TilesReaderTrait>>> {
+		Box::pin(async move {
+			let profile = match url.as_str() {
+				"mock.pbf" => MockTilesReaderProfile::Pbf,
+				"mock.png" => MockTilesReaderProfile::Png,
+				_ => anyhow::bail!("unknown container '{url}'"),
+			};
+			Ok(MockTilesReader::new_mock_profile(profile)?.boxed())
+		})
+	}
+
+	#[tokio::test]
+	async fn admin_api() -> Result<()> {
+		let mut server = TileServer::new(IP, PORT, true, true);
+		server.set_source_opener(Arc::new(open_mock));
+		server.set_admin_token("secret");
+		server.add_tile_source_url("cheese", "mock.pbf").await?;
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let url = |path: &str| format!("http://{IP}:{PORT}/{path}");
+		let sources = || async {
+			client
+				.get(url("admin/sources"))
+				.bearer_auth("secret")
+				.send()
+				.await?
+				.text()
+				.await
+		};
+
+		let response = client.get(url("admin/sources")).send().await?;
+		assert_eq!(response.status(), 401);
+		let response = client.get(url("admin/sources")).bearer_auth("wrong").send().await?;
+		assert_eq!(response.status(), 401);
+
+		assert_eq!(
+			sources().await?,
+			"[{\"container\":\"dummy_container\",\"id\":\"cheese\",\"prefix\":\"/tiles/cheese/\",\"source\":\"dummy_name\",\"tile_format\":\"pbf\",\"url\":\"mock.pbf\"}]"
+		);
+
+		// add and replace sources
+		let add = |body: &'static str| {
+			client
+				.post(url("admin/sources"))
+				.bearer_auth("secret")
+				.body(body)
+				.send()
+		};
+		assert_eq!(add(r#"{"id":"bread","url":"mock.png"}"#).await?.status(), 200);
+		assert_eq!(reqwest::get(url("tiles/bread/0/0/0")).await?.status(), 200);
+		assert_eq!(add(r#"{"id":"cheese","url":"mock.png"}"#).await?.status(), 200);
+		assert!(sources().await?.contains(
+			"\"id\":\"cheese\",\"prefix\":\"/tiles/cheese/\",\"source\":\"dummy_name\",\"tile_format\":\"png\""
+		));
+		assert_eq!(add(r#"{"id":"wine","url":"missing"}"#).await?.status(), 400);
+		assert_eq!(add(r#"{"id":"wine"}"#).await?.status(), 400);
+		assert_eq!(
+			reqwest::get(url("tiles/index.json")).await?.text().await?,
+			"[\"cheese\",\"bread\"]"
+		);
+
+		// flush
+		let response = client.post(url("admin/flush")).bearer_auth("secret").send().await?;
+		assert_eq!(response.text().await?, "{\"reopened\":2}");
+
+		// the png tile was sent as stored
+		let response = client
+			.get(url("admin/compression"))
+			.bearer_auth("secret")
+			.send()
+			.await?;
+		assert_eq!(
+			response.text().await?,
+			"{\"identity\":0,\"over_budget\":0,\"recompressed\":0,\"stored\":1}"
+		);
+
+		// no remote containers are limited
+		let response = client.get(url("admin/upstream")).bearer_auth("secret").send().await?;
+		assert_eq!(response.text().await?, "{}");
+
+		// remove sources
+		let remove = |id: &str| {
+			client
+				.delete(url(&format!("admin/sources/{id}")))
+				.bearer_auth("secret")
+				.send()
+		};
+		assert_eq!(remove("bread").await?.status(), 200);
+		assert_eq!(remove("bread").await?.status(), 404);
+		assert_eq!(reqwest::get(url("tiles/bread/0/0/0")).await?.status(), 404);
+		assert_eq!(
+			reqwest::get(url("tiles/index.json")).await?.text().await?,
+			"[\"cheese\"]"
+		);
+
+		server.stop().await;
+		Ok(())
+	}
+}
@@ -0,0 +1,286 @@
+//! Decides for every tile response whether to send the stored data, to recompress it to a better
+//! encoding accepted by the client, or to send it uncompressed.
+//!
+//! Recompressing, especially to Brotli, takes much more CPU time than sending stored data, so a
+//! [`CompressionPolicy`] can limit it by tile size and by a CPU budget. The decisions are counted and
+//! can be read with the admin API at "/admin/compression".
+
+use super::{sources::SourceResponse, utils::is_incompressible_mime};
+use anyhow::Result;
+use std::{
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+use tracing::info_span;
+use versatiles_core::{
+	json::{JsonObject, JsonValue},
+	types::TileCompression,
+	utils::{optimize_compression, TargetCompression},
+};
+
+/// Limits when tiles are recompressed. By default, every tile is sent in the best encoding the client accepts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompressionPolicy {
+	/// Uncompressed tiles smaller than this many bytes are sent uncompressed, because compressing them saves only a few bytes.
+	pub min_size: u64,
+	/// Tiles larger than this many bytes (as stored) are only recompressed if the client doesn't accept the stored encoding.
+	pub max_size: Option<u64>,
+	/// Share of one CPU core that may be used for recompression, e.g. 0.5 for 500 ms per second.
+	/// If the budget of the current second is used up, tiles are sent as stored.
+	pub cpu_budget: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompressionDecision {
+	/// The stored data is sent as it is.
+	Stored,
+	/// The data is recompressed to a better encoding accepted by the client.
+	Recompressed(TileCompression),
+	/// The data is decompressed, because the client doesn't accept the stored encoding.
+	Identity,
+}
+
+/// Applies a [`CompressionPolicy`] to tile responses and counts the decisions.
+pub struct TileCompressor {
+	policy: CompressionPolicy,
+	use_best_compression: bool,
+	/// Start of the current second and the recompression time spent in it.
+	budget: Mutex<(Instant, Duration)>,
+	stored: AtomicU64,
+	recompressed: AtomicU64,
+	identity: AtomicU64,
+	over_budget: AtomicU64,
+}
+
+impl TileCompressor {
+	/// Without `use_best_compression`, tiles are only recompressed if the client doesn't accept the stored encoding.
+	pub fn new(policy: CompressionPolicy, use_best_compression: bool) -> TileCompressor {
+		TileCompressor {
+			policy,
+			use_best_compression,
+			budget: Mutex::new((Instant::now(), Duration::ZERO)),
+			stored: AtomicU64::new(0),
+			recompressed: AtomicU64::new(0),
+			identity: AtomicU64::new(0),
+			over_budget: AtomicU64::new(0),
+		}
+	}
+
+	/// Decides how a tile of `size` bytes, stored with `compression`, is sent to a client accepting `accept`.
+	pub fn decide(
+		&self,
+		compression: TileCompression,
+		size: u64,
+		mime: &str,
+		accept: &TargetCompression,
+	) -> CompressionDecision {
+		use TileCompression::*;
+
+		let is_accepted = accept.contains(compression);
+		let fallback = if is_accepted { compression } else { Uncompressed };
+
+		let is_too_small = compression == Uncompressed && size < self.policy.min_size;
+		let best = if is_incompressible_mime(mime) || is_too_small {
+			Uncompressed
+		} else if accept.contains(Brotli) {
+			Brotli
+		} else if accept.contains(Gzip) {
+			Gzip
+		} else {
+			Uncompressed
+		};
+
+		let is_better = rank(best) > rank(fallback) && (self.use_best_compression || !is_accepted);
+		let is_too_large = is_accepted && self.policy.max_size.is_some_and(|max_size| size > max_size);
+		if is_better && !is_too_large {
+			if self.has_budget() {
+				return CompressionDecision::Recompressed(best);
+			}
+			self.over_budget.fetch_add(1, Ordering::Relaxed);
+		}
+
+		if is_accepted {
+			CompressionDecision::Stored
+		} else {
+			CompressionDecision::Identity
+		}
+	}
+
+	/// Returns the response in the encoding chosen by [`TileCompressor::decide`].
+	pub fn compress(&self, response: SourceResponse, accept: &TargetCompression) -> Result<SourceResponse> {
+		let SourceResponse {
+			blob,
+			compression,
+			mime,
+		} = response;
+		let decision = self.decide(compression, blob.len(), &mime, accept);
+		log::trace!("compression decision for \"{mime}\" from \"{compression}\": {decision:?}");
+
+		let (blob, compression) = match decision {
+			CompressionDecision::Stored => {
+				self.stored.fetch_add(1, Ordering::Relaxed);
+				(blob, compression)
+			}
+			CompressionDecision::Recompressed(target) => {
+				self.recompressed.fetch_add(1, Ordering::Relaxed);
+				let start = Instant::now();
+				let mut allowed = TargetCompression::from_none();
+				allowed.insert(target);
+				let span = info_span!("recompress", from = %compression, to = %target);
+				let result = span.in_scope(|| optimize_compression(blob, &compression, &allowed))?;
+				self.spend(start.elapsed());
+				result
+			}
+			CompressionDecision::Identity => {
+				self.identity.fetch_add(1, Ordering::Relaxed);
+				optimize_compression(blob, &compression, &TargetCompression::from_none())?
+			}
+		};
+
+		Ok(SourceResponse {
+			blob,
+			compression,
+			mime,
+		})
+	}
+
+	/// Returns the number of decisions, like `{"identity":0,"over_budget":0,"recompressed":12,"stored":30}`.
+	/// "over_budget" counts the tiles that were not recompressed because the CPU budget was used up.
+	pub fn get_stats(&self) -> JsonObject {
+		let mut stats = JsonObject::default();
+		for (key, counter) in [
+			("stored", &self.stored),
+			("recompressed", &self.recompressed),
+			("identity", &self.identity),
+			("over_budget", &self.over_budget),
+		] {
+			stats.set(key, JsonValue::from(counter.load(Ordering::Relaxed) as f64));
+		}
+		stats
+	}
+
+	fn has_budget(&self) -> bool {
+		let Some(share) = self.policy.cpu_budget else {
+			return true;
+		};
+		let mut budget = self.budget.lock().unwrap();
+		if budget.0.elapsed() >= Duration::from_secs(1) {
+			*budget = (Instant::now(), Duration::ZERO);
+		}
+		budget.1 < Duration::from_secs_f64(share)
+	}
+
+	fn spend(&self, time: Duration) {
+		if self.policy.cpu_budget.is_some() {
+			self.budget.lock().unwrap().1 += time;
+		}
+	}
+}
+
+/// Orders the encodings by how much they usually reduce the size.
+fn rank(compression: TileCompression) -> u8 {
+	match compression {
+		TileCompression::Uncompressed => 0,
+		TileCompression::Gzip => 1,
+		TileCompression::Brotli => 2,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use enumset::{enum_set, EnumSet};
+	use versatiles_core::types::{Blob, TileCompression::*};
+	use CompressionDecision::*;
+
+	const MIME: &str = "application/x-protobuf";
+
+	fn accept(compressions: EnumSet<TileCompression>) -> TargetCompression {
+		TargetCompression::from_set(compressions | Uncompressed)
+	}
+
+	#[test]
+	fn decide() {
+		let compressor = TileCompressor::new(CompressionPolicy::default(), true);
+		let decide = |compression, accepted| compressor.decide(compression, 1000, MIME, &accept(accepted));
+
+		assert_eq!(decide(Uncompressed, enum_set!(Gzip | Brotli)), Recompressed(Brotli));
+		assert_eq!(decide(Gzip, enum_set!(Gzip | Brotli)), Recompressed(Brotli));
+		assert_eq!(decide(Brotli, enum_set!(Gzip | Brotli)), Stored);
+		assert_eq!(decide(Gzip, enum_set!(Gzip)), Stored);
+		assert_eq!(decide(Brotli, enum_set!(Gzip)), Recompressed(Gzip));
+		assert_eq!(decide(Brotli, enum_set!()), Identity);
+		assert_eq!(decide(Uncompressed, enum_set!()), Stored);
+
+		assert_eq!(
+			compressor.decide(Uncompressed, 1000, "image/png", &accept(enum_set!(Brotli))),
+			Stored
+		);
+	}
+
+	#[test]
+	fn decide_fast() {
+		let compressor = TileCompressor::new(CompressionPolicy::default(), false);
+		let decide = |compression, accepted| compressor.decide(compression, 1000, MIME, &accept(accepted));
+
+		assert_eq!(decide(Uncompressed, enum_set!(Gzip | Brotli)), Stored);
+		assert_eq!(decide(Gzip, enum_set!(Gzip | Brotli)), Stored);
+		assert_eq!(decide(Brotli, enum_set!(Gzip)), Recompressed(Gzip));
+	}
+
+	#[test]
+	fn decide_by_size() {
+		let policy = CompressionPolicy {
+			min_size: 100,
+			max_size: Some(10_000),
+			cpu_budget: None,
+		};
+		let compressor = TileCompressor::new(policy, true);
+		let decide = |compression, size| compressor.decide(compression, size, MIME, &accept(enum_set!(Gzip | Brotli)));
+
+		assert_eq!(decide(Uncompressed, 50), Stored);
+		assert_eq!(decide(Uncompressed, 100), Recompressed(Brotli));
+		assert_eq!(decide(Gzip, 50), Recompressed(Brotli));
+		assert_eq!(decide(Gzip, 20_000), Stored);
+
+		// large tiles are still decompressed or recompressed if the client doesn't accept them
+		assert_eq!(
+			compressor.decide(Brotli, 20_000, MIME, &accept(enum_set!(Gzip))),
+			Recompressed(Gzip)
+		);
+	}
+
+	#[test]
+	fn cpu_budget() -> Result<()> {
+		let policy = CompressionPolicy {
+			cpu_budget: Some(0.0),
+			..Default::default()
+		};
+		let compressor = TileCompressor::new(policy, true);
+		let response = || SourceResponse {
+			blob: Blob::from(vec![0u8; 1000]),
+			compression: Uncompressed,
+			mime: String::from(MIME),
+		};
+
+		let result = compressor.compress(response(), &accept(enum_set!(Brotli)))?;
+		assert_eq!(result.compression, Uncompressed);
+		assert_eq!(result.blob.len(), 1000);
+
+		let compressor = TileCompressor::new(CompressionPolicy::default(), true);
+		let result = compressor.compress(response(), &accept(enum_set!(Brotli)))?;
+		assert_eq!(result.compression, Brotli);
+		assert!(result.blob.len() < 1000);
+		let result = compressor.compress(response(), &accept(enum_set!()))?;
+		assert_eq!(result.compression, Uncompressed);
+
+		assert_eq!(
+			compressor.get_stats().stringify(),
+			"{\"identity\":0,\"over_budget\":0,\"recompressed\":1,\"stored\":1}"
+		);
+		Ok(())
+	}
+}
@@ -0,0 +1,260 @@
+//! Serves the connections of a listener with HTTP/1.1 and HTTP/2, optionally encrypted with TLS.
+//!
+//! Browsers use HTTP/2 only over TLS. There, all tile requests of a map share one connection,
+//! instead of waiting for one of six HTTP/1.1 connections.
+
+use anyhow::{Context, Result};
+use axum::{response::Response, serve::Listener};
+use hyper::{body::Incoming, Request};
+use hyper_util::{
+	rt::{TokioExecutor, TokioIo, TokioTimer},
+	server::conn::auto::Builder,
+	service::TowerToHyperService,
+};
+use std::{convert::Infallible, future::poll_fn, path::Path, sync::Arc, time::Duration};
+use tokio::{
+	io::{AsyncRead, AsyncWrite},
+	sync::watch::{self, Receiver},
+};
+use tokio_rustls::{
+	rustls::{
+		pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+		ServerConfig,
+	},
+	TlsAcceptor,
+};
+use tower_service::Service;
+
+/// Idle HTTP/1.1 connections are closed after this time, unless set otherwise.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Maximum number of requests per HTTP/2 connection at the same time, unless set otherwise.
+pub const DEFAULT_MAX_CONCURRENT_STREAMS: u32 = 256;
+
+#[derive(Clone)]
+pub struct ConnectionOptions {
+	/// Closes HTTP/1.1 connections that don't send the next request within this time.
+	/// Zero disables keep-alive, so that every connection handles only one request.
+	pub idle_timeout: Duration,
+	/// Sends HTTP/2 pings in this interval and closes connections that don't answer within the same time.
+	pub keep_alive_interval: Option<Duration>,
+	/// Maximum number of requests that a client can send at the same time over one HTTP/2 connection.
+	pub max_concurrent_streams: u32,
+	tls: Option<TlsAcceptor>,
+}
+
+impl Default for ConnectionOptions {
+	fn default() -> Self {
+		ConnectionOptions::new(DEFAULT_IDLE_TIMEOUT, None, DEFAULT_MAX_CONCURRENT_STREAMS)
+	}
+}
+
+impl ConnectionOptions {
+	/// Creates unencrypted connection options. Use [`ConnectionOptions::set_tls`] to enable TLS.
+	pub fn new(idle_timeout: Duration, keep_alive_interval: Option<Duration>, max_concurrent_streams: u32) -> Self {
+		ConnectionOptions {
+			idle_timeout,
+			keep_alive_interval,
+			max_concurrent_streams,
+			tls: None,
+		}
+	}
+
+	/// Encrypts all connections with the certificate chain and the private key of the PEM files.
+	/// Clients can choose between HTTP/2 and HTTP/1.1 during the TLS handshake.
+	pub fn set_tls(&mut self, cert_file: &Path, key_file: &Path) -> Result<()> {
+		let certs = CertificateDer::pem_file_iter(cert_file)
+			.and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+			.with_context(|| format!("reading certificates from {cert_file:?}"))?;
+		let key =
+			PrivateKeyDer::from_pem_file(key_file).with_context(|| format!("reading private key from {key_file:?}"))?;
+
+		let mut config = ServerConfig::builder()
+			.with_no_client_auth()
+			.with_single_cert(certs, key)
+			.context("invalid certificate or private key")?;
+		config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+		self.tls = Some(TlsAcceptor::from(Arc::new(config)));
+		Ok(())
+	}
+
+	pub fn is_tls(&self) -> bool {
+		self.tls.is_some()
+	}
+
+	/// Clients have to finish the TLS handshake within the idle timeout.
+	fn get_handshake_timeout(&self) -> Duration {
+		if self.idle_timeout.is_zero() {
+			DEFAULT_IDLE_TIMEOUT
+		} else {
+			self.idle_timeout
+		}
+	}
+
+	fn get_builder(&self) -> Builder<TokioExecutor> {
+		let mut builder = Builder::new(TokioExecutor::new());
+		builder
+			.http1()
+			.timer(TokioTimer::new())
+			.keep_alive(!self.idle_timeout.is_zero())
+			.header_read_timeout(Some(self.idle_timeout).filter(|timeout| !timeout.is_zero()));
+		builder
+			.http2()
+			.timer(TokioTimer::new())
+			.max_concurrent_streams(self.max_concurrent_streams)
+			.keep_alive_interval(self.keep_alive_interval);
+		if let Some(interval) = self.keep_alive_interval {
+			builder.http2().keep_alive_timeout(interval);
+		}
+		builder
+	}
+
+	/// Accepts connections of `listener` until `exit` changes, and waits until all of them are closed.
+	/// `make_service` creates the service of every connection from the address of the client.
+	pub(super) async fn serve<L, M, S>(&self, mut listener: L, mut make_service: M, mut exit: Receiver<bool>)
+	where
+		L: Listener,
+		M: Service<L::Addr, Response = S, Error = Infallible>,
+		S: Service<Request<Incoming>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+		S::Future: Send + 'static,
+	{
+		let builder = Arc::new(self.get_builder());
+		// every connection holds a receiver, so that closing them all can be awaited
+		let (close_tx, close_rx) = watch::channel(());
+
+		loop {
+			let (io, remote_addr) = tokio::select! {
+				connection = listener.accept() => connection,
+				_ = exit.changed() => break,
+			};
+
+			poll_fn(|cx| make_service.poll_ready(cx))
+				.await
+				.unwrap_or_else(|error| match error {});
+			let service = make_service
+				.call(remote_addr)
+				.await
+				.unwrap_or_else(|error| match error {});
+
+			let (builder, tls, mut exit, close_rx) = (builder.clone(), self.tls.clone(), exit.clone(), close_rx.clone());
+			let handshake_timeout = self.get_handshake_timeout();
+			tokio::spawn(async move {
+				match tls {
+					Some(acceptor) => tokio::select! {
+						result = tokio::time::timeout(handshake_timeout, acceptor.accept(io)) => match result {
+							Ok(Ok(stream)) => serve_connection(&builder, stream, service, exit.clone()).await,
+							Ok(Err(error)) => log::debug!("TLS handshake failed: {error}"),
+							Err(_) => log::debug!("TLS handshake timed out"),
+						},
+						_ = exit.changed() => {}
+					},
+					None => serve_connection(&builder, io, service, exit).await,
+				}
+				drop(close_rx);
+			});
+		}
+
+		drop(close_rx);
+		drop(listener);
+		close_tx.closed().await;
+	}
+}
+
+/// Serves one connection. When `exit` changes, the requests in flight are finished and the connection is closed.
+async fn serve_connection<I, S>(builder: &Builder<TokioExecutor>, io: I, service: S, mut exit: Receiver<bool>)
+where
+	I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+	S: Service<Request<Incoming>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+	S::Future: Send + 'static,
+{
+	let connection = builder.serve_connection_with_upgrades(TokioIo::new(io), TowerToHyperService::new(service));
+	tokio::pin!(connection);
+
+	let result = tokio::select! {
+		result = connection.as_mut() => result,
+		_ = exit.changed() => {
+			connection.as_mut().graceful_shutdown();
+			connection.await
+		}
+	};
+	if let Err(error) = result {
+		log::debug!("connection failed: {error}");
+	}
+}
+
+/// Writes a new self-signed certificate for "localhost" and its private key as PEM files into `dir`,
+/// and returns the paths of both files.
+#[cfg(test)]
+pub fn write_test_certificate(dir: &Path) -> Result<(std::path::PathBuf, std::path::PathBuf)> {
+	let certified_key = rcgen::generate_simple_self_signed(vec![String::from("localhost")])?;
+	let cert_file = dir.join("localhost.crt");
+	let key_file = dir.join("localhost.key");
+	std::fs::write(&cert_file, certified_key.cert.pem())?;
+	std::fs::write(&key_file, certified_key.key_pair.serialize_pem())?;
+	Ok((cert_file, key_file))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+	use tokio::{
+		io::AsyncReadExt,
+		net::{TcpListener, TcpStream},
+		time::timeout,
+	};
+
+	#[test]
+	fn set_tls() -> Result<()> {
+		let dir = TempDir::new()?;
+		let (cert_file, key_file) = write_test_certificate(dir.path())?;
+		let (cert_file, key_file) = (cert_file.as_path(), key_file.as_path());
+
+		let mut options = ConnectionOptions::default();
+		assert!(!options.is_tls());
+		options.set_tls(cert_file, key_file)?;
+		assert!(options.is_tls());
+
+		assert!(options.set_tls(&dir.path().join("missing.crt"), key_file).is_err());
+		assert!(options.set_tls(key_file, cert_file).is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tls_handshake_timeout() -> Result<()> {
+		let dir = TempDir::new()?;
+		let (cert_file, key_file) = write_test_certificate(dir.path())?;
+		let mut options = ConnectionOptions::new(Duration::from_millis(200), None, DEFAULT_MAX_CONCURRENT_STREAMS);
+		options.set_tls(&cert_file, &key_file)?;
+
+		let serve = |options: ConnectionOptions| async move {
+			let listener = TcpListener::bind("127.0.0.1:0").await?;
+			let addr = listener.local_addr()?;
+			let (exit_tx, exit_rx) = watch::channel(false);
+			let task = tokio::spawn(async move {
+				options
+					.serve(listener, axum::Router::new().into_make_service(), exit_rx)
+					.await
+			});
+			// a client that never starts the TLS handshake
+			let client = TcpStream::connect(addr).await?;
+			Ok::<_, anyhow::Error>((task, exit_tx, client))
+		};
+
+		// the handshake times out, even without shutdown
+		let (task, exit_tx, mut client) = serve(options.clone()).await?;
+		let mut buffer = [0u8; 1];
+		let read = timeout(Duration::from_secs(5), client.read(&mut buffer)).await?;
+		assert_eq!(read?, 0);
+		exit_tx.send(true)?;
+		timeout(Duration::from_secs(5), task).await??;
+
+		// shutdown doesn't wait for the handshake
+		options.idle_timeout = Duration::from_secs(3600);
+		let (task, exit_tx, _client) = serve(options).await?;
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		exit_tx.send(true)?;
+		timeout(Duration::from_secs(5), task).await??;
+		Ok(())
+	}
+}
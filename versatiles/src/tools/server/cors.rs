@@ -0,0 +1,208 @@
+//! Cross-origin resource sharing (CORS) for the responses of the server.
+//!
+//! Every rule applies to a URL prefix, like "/tiles/osm/", and the rule with the longest matching prefix
+//! is used. Origins can be given exactly ("https://example.org"), with a wildcard ("https://*.example.org")
+//! or as "*" to allow every origin. Preflight requests ("OPTIONS" with "Access-Control-Request-Method")
+//! are answered directly and never reach the handlers. Requests without a matching rule get no CORS headers.
+
+use axum::{
+	body::Body,
+	extract::{Request, State},
+	http::{
+		header::{
+			ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+			ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+		},
+		HeaderValue, Method, StatusCode,
+	},
+	middleware::Next,
+	response::Response,
+};
+use std::sync::Arc;
+
+/// Methods that are allowed for cross-origin requests.
+const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorsRule {
+	/// URL prefix the rule applies to, e.g. "/tiles/osm/".
+	pub path: String,
+	/// Allowed origins, e.g. "https://example.org", "https://*.example.org" or "*".
+	pub origins: Vec<String>,
+	/// Request headers clients may send besides the CORS-safelisted ones.
+	pub headers: Vec<String>,
+	/// Seconds browsers may cache the answer of a preflight request.
+	pub max_age: Option<u64>,
+}
+
+impl CorsRule {
+	/// A rule that allows every origin for every path, which is the default of the server.
+	pub fn allow_all() -> CorsRule {
+		CorsRule {
+			path: String::from("/"),
+			origins: vec![String::from("*")],
+			headers: Vec::new(),
+			max_age: None,
+		}
+	}
+
+	fn allows_all_origins(&self) -> bool {
+		self.origins.iter().any(|pattern| pattern == "*")
+	}
+
+	/// Returns the value of "Access-Control-Allow-Origin" for a request from `origin`,
+	/// or `None` if the origin is not allowed.
+	fn get_allowed_origin(&self, origin: Option<&str>) -> Option<String> {
+		if self.allows_all_origins() {
+			return Some(String::from("*"));
+		}
+		let origin = origin?;
+		self
+			.origins
+			.iter()
+			.any(|pattern| matches_origin(pattern, origin))
+			.then(|| origin.to_owned())
+	}
+}
+
+pub struct Cors {
+	rules: Vec<CorsRule>,
+}
+
+impl Cors {
+	pub fn new(mut rules: Vec<CorsRule>) -> Cors {
+		// the longest prefix has to be found first
+		rules.sort_by_key(|rule| std::cmp::Reverse(rule.path.len()));
+		Cors { rules }
+	}
+
+	fn get_rule(&self, path: &str) -> Option<&CorsRule> {
+		self.rules.iter().find(|rule| path.starts_with(&rule.path))
+	}
+}
+
+/// Checks whether `origin` matches `pattern`. A "*" in the pattern matches any sequence of characters,
+/// e.g. "https://*.example.org" matches "https://maps.example.org".
+fn matches_origin(pattern: &str, origin: &str) -> bool {
+	let origin = origin.to_ascii_lowercase();
+	let pattern = pattern.to_ascii_lowercase();
+
+	let Some((prefix, suffix)) = pattern.split_once('*') else {
+		return origin == pattern;
+	};
+	if !origin.starts_with(prefix) {
+		return false;
+	}
+	let rest = &origin[prefix.len()..];
+	if suffix.contains('*') {
+		return (0..=rest.len())
+			.filter(|i| rest.is_char_boundary(*i))
+			.any(|i| matches_origin(suffix, &rest[i..]));
+	}
+	rest.ends_with(suffix)
+}
+
+/// Middleware that adds the CORS headers of the matching [`CorsRule`] and answers preflight requests.
+pub async fn handle_cors(State(cors): State<Arc<Cors>>, request: Request, next: Next) -> Response<Body> {
+	let Some(rule) = cors.get_rule(request.uri().path()) else {
+		return next.run(request).await;
+	};
+
+	let origin = request.headers().get(ORIGIN).and_then(|value| value.to_str().ok());
+	let allowed_origin = rule.get_allowed_origin(origin);
+
+	if request.method() == Method::OPTIONS && request.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD) {
+		let Some(allowed_origin) = allowed_origin else {
+			log::warn!("send 403 for preflight request from origin {origin:?}");
+			return Response::builder()
+				.status(StatusCode::FORBIDDEN)
+				.header(VARY, "origin")
+				.body(Body::empty())
+				.expect("should have build a body");
+		};
+
+		let mut response = Response::builder()
+			.status(StatusCode::NO_CONTENT)
+			.header(ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin)
+			.header(ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS)
+			.header(VARY, "origin");
+		if !rule.headers.is_empty() {
+			response = response.header(ACCESS_CONTROL_ALLOW_HEADERS, rule.headers.join(", "));
+		}
+		if let Some(max_age) = rule.max_age {
+			response = response.header(ACCESS_CONTROL_MAX_AGE, max_age);
+		}
+		return response.body(Body::empty()).expect("should have build a body");
+	}
+
+	let mut response = next.run(request).await;
+	let headers = response.headers_mut();
+	if !rule.allows_all_origins() {
+		headers.append(VARY, HeaderValue::from_static("origin"));
+	}
+	if let Some(allowed_origin) = allowed_origin.and_then(|value| HeaderValue::from_str(&value).ok()) {
+		headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+	}
+	response
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rule(path: &str, origins: &[&str]) -> CorsRule {
+		CorsRule {
+			path: path.to_owned(),
+			origins: origins.iter().map(|origin| origin.to_string()).collect(),
+			headers: Vec::new(),
+			max_age: None,
+		}
+	}
+
+	#[test]
+	fn origin_patterns() {
+		assert!(matches_origin("https://example.org", "https://example.org"));
+		assert!(matches_origin("https://example.org", "HTTPS://Example.org"));
+		assert!(!matches_origin("https://example.org", "https://example.org.evil.com"));
+		assert!(matches_origin("https://*.example.org", "https://maps.example.org"));
+		assert!(matches_origin("https://*.example.org", "https://a.b.example.org"));
+		assert!(!matches_origin("https://*.example.org", "https://example.org"));
+		assert!(!matches_origin("https://*.example.org", "https://evil.com/.example.org.com"));
+		assert!(matches_origin("http://localhost:*", "http://localhost:8080"));
+		assert!(matches_origin("https://*.example.*", "https://maps.example.com"));
+	}
+
+	#[test]
+	fn allowed_origin() {
+		let all = CorsRule::allow_all();
+		assert_eq!(all.get_allowed_origin(None), Some(String::from("*")));
+		assert_eq!(all.get_allowed_origin(Some("https://a.org")), Some(String::from("*")));
+
+		let some = rule("/", &["https://a.org", "https://*.b.org"]);
+		assert_eq!(some.get_allowed_origin(None), None);
+		assert_eq!(
+			some.get_allowed_origin(Some("https://a.org")),
+			Some(String::from("https://a.org"))
+		);
+		assert_eq!(
+			some.get_allowed_origin(Some("https://x.b.org")),
+			Some(String::from("https://x.b.org"))
+		);
+		assert_eq!(some.get_allowed_origin(Some("https://c.org")), None);
+	}
+
+	#[test]
+	fn longest_prefix_wins() {
+		let cors = Cors::new(vec![
+			rule("/", &["*"]),
+			rule("/tiles/private/", &["https://a.org"]),
+			rule("/tiles/", &["https://b.org"]),
+		]);
+		assert_eq!(cors.get_rule("/index.html").unwrap().path, "/");
+		assert_eq!(cors.get_rule("/tiles/osm/1/2/3").unwrap().path, "/tiles/");
+		assert_eq!(cors.get_rule("/tiles/private/1/2/3").unwrap().path, "/tiles/private/");
+
+		let cors = Cors::new(vec![rule("/tiles/", &["*"])]);
+		assert!(cors.get_rule("/status").is_none());
+	}
+}
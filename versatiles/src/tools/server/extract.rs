@@ -0,0 +1,400 @@
+//! Extracts of tile sources for offline use.
+//!
+//! - `GET /extract/{id}?bbox=west,south,east,north&min_zoom=0&max_zoom=14`: returns the tiles of the source
+//!   within the bounding box and zoom range as a `*.versatiles` container. Use `format=tar` for a tar file.
+//!   All parameters are optional and default to the whole source.
+//! - `GET /extract/{id}?…&estimate`: returns the number of tiles and the estimated size of the extract as JSON,
+//!   without building it, e.g. to ask users before they download a large area.
+//!
+//! Extracts are built in memory, so their number of tiles is limited.
+
+use super::{
+	admin::json_error,
+	sources::TileSource,
+	tile_server::{ok_json, TileSources},
+	utils::get_query_parameter,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use axum::{
+	body::Body,
+	extract::{Path, State},
+	http::{
+		header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+		StatusCode, Uri,
+	},
+	response::Response,
+	routing::get,
+	Router,
+};
+use futures::{stream, StreamExt};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use versatiles_container::{TarPathTemplate, TarTilesWriter, TilesWriterTrait, VersaTilesWriter};
+use versatiles_core::{
+	io::DataWriterBlob,
+	json::JsonObject,
+	tilejson::TileJSON,
+	types::{
+		Blob, GeoBBox, TileBBox, TileBBoxPyramid, TileCompression, TileCoord3, TileStream, TilesReaderParameters,
+		TilesReaderTrait, TryTileStream,
+	},
+};
+
+/// Maximum number of tiles of an extract, unless set with [`super::TileServer::set_max_extract_tiles`].
+pub const DEFAULT_MAX_EXTRACT_TILES: u64 = 100_000;
+
+#[derive(Clone)]
+pub struct ExtractState {
+	pub tile_sources: TileSources,
+	pub max_tiles: u64,
+}
+
+pub fn add_extract_api_to_app(app: Router, state: ExtractState) -> Router {
+	let extract_app = Router::new()
+		.route("/extract/{id}", get(serve_extract))
+		.with_state(state);
+
+	app.merge(extract_app)
+}
+
+#[derive(Debug, PartialEq)]
+enum ExtractFormat {
+	Tar,
+	VersaTiles,
+}
+
+#[derive(Debug)]
+struct ExtractRequest {
+	bbox: Option<GeoBBox>,
+	min_zoom: Option<u8>,
+	max_zoom: Option<u8>,
+	format: ExtractFormat,
+	estimate: bool,
+}
+
+impl ExtractRequest {
+	fn parse(query: &str) -> Result<ExtractRequest> {
+		let bbox = get_query_parameter(query, "bbox")
+			.map(|bbox| {
+				let values = bbox
+					.split(',')
+					.map(|value| value.trim().parse::<f64>())
+					.collect::<Result<Vec<f64>, _>>()
+					.context("bbox must contain 4 numbers: west,south,east,north")?;
+				let bbox = GeoBBox::try_from(values)?;
+				bbox.check()?;
+				Ok::<_, anyhow::Error>(bbox)
+			})
+			.transpose()?;
+
+		let zoom = |name: &str| {
+			get_query_parameter(query, name)
+				.map(|value| {
+					value
+						.parse::<u8>()
+						.with_context(|| format!("{name} must be a zoom level"))
+				})
+				.transpose()
+		};
+
+		let format = match get_query_parameter(query, "format").as_deref() {
+			None | Some("versatiles") => ExtractFormat::VersaTiles,
+			Some("tar") => ExtractFormat::Tar,
+			Some(format) => bail!("unknown format \"{format}\", use \"versatiles\" or \"tar\""),
+		};
+
+		Ok(ExtractRequest {
+			bbox,
+			min_zoom: zoom("min_zoom")?,
+			max_zoom: zoom("max_zoom")?,
+			format,
+			estimate: get_query_parameter(query, "estimate").is_some(),
+		})
+	}
+
+	/// Returns the tiles of the source that are part of the extract.
+	fn get_bbox_pyramid(&self, source_pyramid: &TileBBoxPyramid) -> TileBBoxPyramid {
+		let mut bbox_pyramid = source_pyramid.clone();
+		if let Some(bbox) = &self.bbox {
+			bbox_pyramid.intersect_geo_bbox(bbox);
+		}
+		if let Some(min_zoom) = self.min_zoom {
+			bbox_pyramid.set_zoom_min(min_zoom);
+		}
+		if let Some(max_zoom) = self.max_zoom {
+			bbox_pyramid.set_zoom_max(max_zoom);
+		}
+		bbox_pyramid
+	}
+}
+
+async fn serve_extract(uri: Uri, Path(id): Path<String>, State(state): State<ExtractState>) -> Response<Body> {
+	log::debug!("handle extract request: {uri}");
+
+	let tile_source = state
+		.tile_sources
+		.read()
+		.unwrap()
+		.iter()
+		.find(|source| source.id == id)
+		.cloned();
+	let Some(tile_source) = tile_source else {
+		return json_error(StatusCode::NOT_FOUND, &format!("unknown source '{id}'"));
+	};
+
+	let request = match ExtractRequest::parse(uri.query().unwrap_or("")) {
+		Ok(request) => request,
+		Err(err) => return json_error(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+	};
+
+	let reader = ExtractReader::new(&tile_source, &request).await;
+	let tile_count = reader.parameters.bbox_pyramid.count_tiles();
+
+	if request.estimate {
+		let mut json = JsonObject::default();
+		json.set("tiles", tile_count as f64);
+		json.set("estimated_size", reader.estimate_size().await as f64);
+		json.set("max_tiles", state.max_tiles as f64);
+		return ok_json(&json.stringify());
+	}
+
+	if tile_count > state.max_tiles {
+		return json_error(
+			StatusCode::BAD_REQUEST,
+			&format!(
+				"the extract contains {tile_count} tiles, but only {} are allowed. Use a smaller area or zoom range",
+				state.max_tiles
+			),
+		);
+	}
+
+	let (blob, mime, extension) = match build_extract(reader, &request.format).await {
+		Ok(result) => result,
+		Err(err) => {
+			log::warn!("could not build extract of '{id}': {err:#}");
+			return json_error(StatusCode::INTERNAL_SERVER_ERROR, &format!("{err:#}"));
+		}
+	};
+
+	log::info!("send extract of '{id}' with {tile_count} tiles");
+	Response::builder()
+		.status(200)
+		.header(CONTENT_TYPE, mime)
+		.header(
+			CONTENT_DISPOSITION,
+			format!("attachment; filename=\"{id}.{extension}\""),
+		)
+		.body(Body::from(blob.into_vec()))
+		.expect("should have build a body")
+}
+
+/// Writes the extract as container, and returns it with its MIME type and file extension.
+async fn build_extract(
+	mut reader: ExtractReader,
+	format: &ExtractFormat,
+) -> Result<(Blob, &'static str, &'static str)> {
+	Ok(match format {
+		ExtractFormat::VersaTiles => {
+			let mut writer = DataWriterBlob::new()?;
+			VersaTilesWriter::write_to_writer(&mut reader, &mut writer).await?;
+			(writer.into_blob(), "application/octet-stream", "versatiles")
+		}
+		ExtractFormat::Tar => {
+			let mut buffer = Vec::new();
+			TarTilesWriter::write_to_stream(&mut reader, &mut buffer, TarPathTemplate::default()).await?;
+			(Blob::from(buffer), "application/x-tar", "tar")
+		}
+	})
+}
+
+/// Reads the part of a tile source that belongs to an extract.
+///
+/// The reader of the source is shared with the server, so it is only locked while a block of tiles is read.
+#[derive(Debug)]
+struct ExtractReader {
+	reader: Arc<Mutex<Box<dyn TilesReaderTrait>>>,
+	name: String,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl ExtractReader {
+	async fn new(tile_source: &TileSource, request: &ExtractRequest) -> ExtractReader {
+		let reader = tile_source.get_reader();
+		let (name, mut parameters, mut tilejson) = {
+			let reader = reader.lock().await;
+			(
+				reader.get_source_name().to_owned(),
+				reader.get_parameters().clone(),
+				reader.get_tilejson().clone(),
+			)
+		};
+		parameters.bbox_pyramid = request.get_bbox_pyramid(&parameters.bbox_pyramid);
+		tilejson.update_from_pyramid(&parameters.bbox_pyramid);
+
+		ExtractReader {
+			reader,
+			name,
+			parameters,
+			tilejson,
+		}
+	}
+
+	/// Estimates the size of the extract in bytes, using the tile in the center of every zoom level as sample.
+	async fn estimate_size(&self) -> u64 {
+		let mut sizes = Vec::new();
+		for bbox in self.parameters.bbox_pyramid.iter_levels() {
+			let Ok(coord) = TileCoord3::new((bbox.x_min + bbox.x_max) / 2, (bbox.y_min + bbox.y_max) / 2, bbox.level)
+			else {
+				continue;
+			};
+			if let Ok(Some(blob)) = self.get_tile_data(&coord).await {
+				sizes.push(blob.len());
+			}
+		}
+		if sizes.is_empty() {
+			return 0;
+		}
+		let average = sizes.iter().sum::<u64>() as f64 / sizes.len() as f64;
+		(average * self.parameters.bbox_pyramid.count_tiles() as f64).round() as u64
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for ExtractReader {
+	fn get_source_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_container_name(&self) -> &str {
+		"extract"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, tile_compression: TileCompression) {
+		self.parameters.tile_compression = tile_compression;
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		if !self.parameters.bbox_pyramid.contains_coord(coord) {
+			return Ok(None);
+		}
+		self.reader.lock().await.get_tile_data(coord).await
+	}
+
+	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		self
+			.get_bbox_tile_try_stream(bbox)
+			.await
+			.into_tile_stream(|err| log::warn!("skipping tile: {err:#}"))
+	}
+
+	async fn get_bbox_tile_try_stream(&self, mut bbox: TileBBox) -> TryTileStream {
+		if bbox.intersect_pyramid(&self.parameters.bbox_pyramid).is_err() || bbox.is_empty() {
+			return TryTileStream::from_stream(stream::empty().boxed());
+		}
+		let tiles = {
+			let reader = self.reader.lock().await;
+			let stream = reader.get_bbox_tile_try_stream(bbox).await;
+			stream.stream.collect::<Vec<_>>().await
+		};
+		TryTileStream::from_stream(stream::iter(tiles).boxed())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::TileServer;
+	use super::*;
+	use versatiles_container::{MockTilesReader, MockTilesReaderProfile};
+	use versatiles_core::json::JsonValue;
+
+	const IP: &str = "127.0.0.1";
+	const PORT: u16 = 50021;
+
+	#[test]
+	fn parse_request() -> Result<()> {
+		let request = ExtractRequest::parse("bbox=13.0,52.3,13.8,52.7&max_zoom=10&format=tar&estimate")?;
+		assert_eq!(request.bbox, Some(GeoBBox(13.0, 52.3, 13.8, 52.7)));
+		assert_eq!(request.min_zoom, None);
+		assert_eq!(request.max_zoom, Some(10));
+		assert_eq!(request.format, ExtractFormat::Tar);
+		assert!(request.estimate);
+
+		let request = ExtractRequest::parse("")?;
+		assert_eq!(request.bbox, None);
+		assert_eq!(request.format, ExtractFormat::VersaTiles);
+		assert!(!request.estimate);
+
+		assert!(ExtractRequest::parse("bbox=13,52,14").is_err());
+		assert!(ExtractRequest::parse("bbox=13,52,a,53").is_err());
+		assert!(ExtractRequest::parse("bbox=14,52,13,53").is_err());
+		assert!(ExtractRequest::parse("min_zoom=-1").is_err());
+		assert!(ExtractRequest::parse("format=zip").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn bbox_pyramid() -> Result<()> {
+		let request = ExtractRequest::parse("bbox=1,1,89,59&min_zoom=1&max_zoom=3")?;
+		let bbox_pyramid = request.get_bbox_pyramid(&TileBBoxPyramid::new_full(8));
+		assert_eq!(bbox_pyramid.get_zoom_min(), Some(1));
+		assert_eq!(bbox_pyramid.get_zoom_max(), Some(3));
+		assert_eq!(bbox_pyramid.count_tiles(), 1 + 1 + 4);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn extract_api() -> Result<()> {
+		let mut server = TileServer::new(IP, PORT, true, true);
+		server.add_tile_source(
+			"cheese",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed(),
+		)?;
+		server.set_max_extract_tiles(20);
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let get = |query: &str| client.get(format!("http://{IP}:{PORT}/extract/{query}")).send();
+
+		let estimate = JsonValue::parse_str(&get("cheese?estimate").await?.text().await?)?.to_object()?;
+		assert_eq!(estimate.get_number::<u64>("tiles")?, Some(34));
+		assert_eq!(estimate.get_number::<u64>("max_tiles")?, Some(20));
+		assert!(estimate.get_number::<u64>("estimated_size")?.unwrap() > 0);
+
+		let response = get("cheese?max_zoom=2").await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			response.headers()["content-disposition"],
+			"attachment; filename=\"cheese.versatiles\""
+		);
+		assert!(response.bytes().await?.starts_with(b"versatiles_v02"));
+
+		let response = get("cheese?max_zoom=2&format=tar").await?;
+		assert_eq!(response.headers()["content-type"], "application/x-tar");
+		let bytes = response.bytes().await?;
+		let names = tar::Archive::new(bytes.as_ref())
+			.entries()?
+			.map(|entry| Ok(entry?.path()?.to_string_lossy().to_string()))
+			.collect::<Result<Vec<_>>>()?;
+		// the metadata and the 9 tiles of zoom level 2
+		assert!(names.contains(&String::from("tiles.json")));
+		assert_eq!(names.iter().filter(|name| name.ends_with(".png")).count(), 9);
+		assert_eq!(names.len(), 10);
+
+		assert_eq!(get("cheese").await?.status(), 400);
+		assert_eq!(get("cheese?bbox=1,2").await?.status(), 400);
+		assert_eq!(get("cake").await?.status(), 404);
+
+		server.stop().await;
+		Ok(())
+	}
+}
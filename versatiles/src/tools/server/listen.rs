@@ -0,0 +1,97 @@
+//! Addresses the server listens on: TCP sockets like "0.0.0.0:8080" or "[::]:8080",
+//! and Unix domain sockets like "unix:/run/versatiles.sock".
+
+use super::ConnectionOptions;
+use anyhow::{ensure, Context, Result};
+use axum::Router;
+use std::{fmt::Display, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+use tokio::{net::TcpListener, sync::watch::Receiver, task::JoinHandle};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddress {
+	Tcp(SocketAddr),
+	#[cfg(unix)]
+	Unix(PathBuf),
+}
+
+impl ListenAddress {
+	/// Binds the address and serves `router` until `exit` changes.
+	pub(super) async fn serve(
+		&self,
+		router: Router,
+		options: Arc<ConnectionOptions>,
+		exit: Receiver<bool>,
+	) -> Result<JoinHandle<()>> {
+		Ok(match self {
+			ListenAddress::Tcp(addr) => {
+				let listener = TcpListener::bind(addr)
+					.await
+					.with_context(|| format!("binding {addr}"))?;
+				let make_service = router.into_make_service_with_connect_info::<SocketAddr>();
+				tokio::spawn(async move { options.serve(listener, make_service, exit).await })
+			}
+			#[cfg(unix)]
+			ListenAddress::Unix(path) => {
+				use std::os::unix::fs::FileTypeExt;
+
+				// remove the socket of a previous run
+				if let Ok(metadata) = std::fs::symlink_metadata(path) {
+					ensure!(metadata.file_type().is_socket(), "{path:?} exists and is not a socket");
+					std::fs::remove_file(path)?;
+				}
+
+				let listener = tokio::net::UnixListener::bind(path).with_context(|| format!("binding {path:?}"))?;
+				tokio::spawn(async move { options.serve(listener, router.into_make_service(), exit).await })
+			}
+		})
+	}
+}
+
+impl FromStr for ListenAddress {
+	type Err = anyhow::Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		if let Some(path) = value.strip_prefix("unix:") {
+			ensure!(!path.is_empty(), "missing path of the unix socket in \"{value}\"");
+			#[cfg(unix)]
+			return Ok(ListenAddress::Unix(PathBuf::from(path)));
+			#[cfg(not(unix))]
+			anyhow::bail!("unix sockets are not supported on this platform");
+		}
+
+		let addr = value.parse::<SocketAddr>().with_context(|| {
+			format!("invalid address \"{value}\", use e.g. \"0.0.0.0:8080\", \"[::]:8080\" or \"unix:/path/to.sock\"")
+		})?;
+		Ok(ListenAddress::Tcp(addr))
+	}
+}
+
+impl Display for ListenAddress {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ListenAddress::Tcp(addr) => write!(f, "{addr}"),
+			#[cfg(unix)]
+			ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse() -> Result<()> {
+		let parse = |value: &str| -> Result<String> { Ok(value.parse::<ListenAddress>()?.to_string()) };
+
+		assert_eq!(parse("0.0.0.0:8080")?, "0.0.0.0:8080");
+		assert_eq!(parse("[::]:8080")?, "[::]:8080");
+		#[cfg(unix)]
+		assert_eq!(parse("unix:/run/versatiles.sock")?, "unix:/run/versatiles.sock");
+
+		assert!(parse("unix:").is_err());
+		assert!(parse("localhost").is_err());
+		assert!(parse("0.0.0.0").is_err());
+		Ok(())
+	}
+}
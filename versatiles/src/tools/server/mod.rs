@@ -1,8 +1,29 @@
 //! server implementation
 
+mod admin;
+mod compression_policy;
+mod connection;
+mod cors;
+mod extract;
+mod listen;
+mod rate_limit;
 mod sources;
+mod static_map;
+#[cfg(feature = "otel")]
+mod telemetry;
 mod tile_server;
+mod upstream;
 mod utils;
 
+pub use compression_policy::CompressionPolicy;
+pub use connection::ConnectionOptions;
+#[cfg(test)]
+pub use connection::write_test_certificate;
+pub use cors::CorsRule;
+pub use listen::ListenAddress;
+pub use rate_limit::RateLimit;
+#[cfg(feature = "otel")]
+pub use telemetry::Telemetry;
 pub use tile_server::*;
+pub use upstream::UpstreamLimits;
 pub use utils::Url;
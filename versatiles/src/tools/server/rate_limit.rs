@@ -0,0 +1,211 @@
+//! Limits the number of requests per client and the number of requests handled at the same time.
+//!
+//! Every client gets a token bucket that is refilled at a fixed rate. A client is identified by the API
+//! key sent in the "X-API-Key" header, if the key is one of the configured API keys, and otherwise by its
+//! IP address. Requests that exceed the limits are answered with "429 Too Many Requests".
+//!
+//! Connections via Unix domain sockets come from a reverse proxy, so the IP address is taken from the
+//! "X-Forwarded-For" header instead.
+
+use axum::{
+	body::Body,
+	extract::{ConnectInfo, Request, State},
+	http::{header::RETRY_AFTER, StatusCode},
+	middleware::Next,
+	response::Response,
+};
+use std::{
+	collections::HashMap,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+use tokio::sync::Semaphore;
+
+/// Header with the API key of a client.
+const API_KEY_HEADER: &str = "x-api-key";
+/// Header with the IP address of a client, set by reverse proxies.
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+/// Number of buckets, above which buckets of inactive clients are removed.
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+	/// Sustained number of requests per second of a client.
+	pub requests_per_second: f64,
+	/// Number of requests a client can send at once.
+	pub burst: u32,
+	/// API keys that get their own limits. Requests with other keys are limited by IP address.
+	pub api_keys: Vec<String>,
+}
+
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+pub struct RateLimiter {
+	rate_limit: Option<RateLimit>,
+	buckets: Mutex<HashMap<String, Bucket>>,
+	in_flight: Option<Arc<Semaphore>>,
+}
+
+impl RateLimiter {
+	pub fn new(rate_limit: Option<RateLimit>, max_in_flight: Option<usize>) -> RateLimiter {
+		RateLimiter {
+			rate_limit,
+			buckets: Mutex::new(HashMap::new()),
+			in_flight: max_in_flight.map(|n| Arc::new(Semaphore::new(n))),
+		}
+	}
+
+	/// Takes a token from the bucket of a client. Returns the seconds until the next token is available,
+	/// if the bucket is empty.
+	fn take_token(&self, client: &str, now: Instant) -> Result<(), f64> {
+		let Some(rate_limit) = &self.rate_limit else {
+			return Ok(());
+		};
+		let burst = rate_limit.burst.max(1) as f64;
+		let rate = rate_limit.requests_per_second;
+
+		let mut buckets = self.buckets.lock().unwrap();
+		if buckets.len() >= MAX_BUCKETS {
+			// buckets that would be full again can be forgotten
+			buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < burst);
+		}
+
+		let bucket = buckets.entry(client.to_owned()).or_insert(Bucket {
+			tokens: burst,
+			updated: now,
+		});
+		bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+		bucket.updated = now;
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			Ok(())
+		} else {
+			Err((1.0 - bucket.tokens) / rate)
+		}
+	}
+
+	/// Identifies the client of a request by a known API key or by its IP address.
+	fn get_client(&self, request: &Request) -> String {
+		let api_keys = self.rate_limit.as_ref().map_or(&[][..], |r| &r.api_keys);
+		if let Some(key) = request.headers().get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+			if api_keys.iter().any(|k| k == key) {
+				return format!("key:{key}");
+			}
+		}
+		match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+			Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+			None => match request
+				.headers()
+				.get(FORWARDED_FOR_HEADER)
+				.and_then(|v| v.to_str().ok())
+			{
+				Some(forwarded) => format!("ip:{}", forwarded.split(',').next().unwrap_or_default().trim()),
+				None => String::from("unknown"),
+			},
+		}
+	}
+}
+
+/// Middleware that applies the limits of a [`RateLimiter`].
+pub async fn limit_requests(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response<Body> {
+	let client = limiter.get_client(&request);
+
+	if let Err(seconds) = limiter.take_token(&client, Instant::now()) {
+		log::warn!("send 429 to {client}: rate limit exceeded");
+		return too_many_requests(seconds.ceil() as u64);
+	}
+
+	let _permit = match &limiter.in_flight {
+		Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+			Ok(permit) => Some(permit),
+			Err(_) => {
+				log::warn!("send 429 to {client}: too many requests in flight");
+				return too_many_requests(1);
+			}
+		},
+		None => None,
+	};
+
+	next.run(request).await
+}
+
+fn too_many_requests(retry_after: u64) -> Response<Body> {
+	Response::builder()
+		.status(StatusCode::TOO_MANY_REQUESTS)
+		.header(RETRY_AFTER, retry_after.max(1))
+		.body(Body::from("Too Many Requests"))
+		.expect("should have build a body")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn token_bucket() {
+		let limiter = RateLimiter::new(
+			Some(RateLimit {
+				requests_per_second: 2.0,
+				burst: 3,
+				api_keys: vec![],
+			}),
+			None,
+		);
+		let start = Instant::now();
+
+		for _ in 0..3 {
+			assert_eq!(limiter.take_token("a", start), Ok(()));
+		}
+		assert_eq!(limiter.take_token("a", start), Err(0.5));
+		assert_eq!(limiter.take_token("b", start), Ok(()));
+
+		let later = start + Duration::from_millis(500);
+		assert_eq!(limiter.take_token("a", later), Ok(()));
+		assert!(limiter.take_token("a", later).is_err());
+
+		// the bucket is never filled above the burst size
+		let much_later = start + Duration::from_secs(60);
+		for _ in 0..3 {
+			assert_eq!(limiter.take_token("a", much_later), Ok(()));
+		}
+		assert!(limiter.take_token("a", much_later).is_err());
+	}
+
+	#[test]
+	fn no_rate_limit() {
+		let limiter = RateLimiter::new(None, Some(1));
+		for _ in 0..100 {
+			assert_eq!(limiter.take_token("a", Instant::now()), Ok(()));
+		}
+	}
+
+	#[test]
+	fn get_client() {
+		let limiter = RateLimiter::new(
+			Some(RateLimit {
+				requests_per_second: 1.0,
+				burst: 1,
+				api_keys: vec![String::from("cheese")],
+			}),
+			None,
+		);
+		let client = |api_key: Option<&str>| {
+			let mut request = Request::builder().header(FORWARDED_FOR_HEADER, "10.0.0.1, 10.0.0.2");
+			if let Some(api_key) = api_key {
+				request = request.header(API_KEY_HEADER, api_key);
+			}
+			limiter.get_client(&request.body(Body::empty()).unwrap())
+		};
+
+		assert_eq!(client(None), "ip:10.0.0.1");
+		assert_eq!(client(Some("cheese")), "key:cheese");
+		// unknown API keys don't get their own limits
+		assert_eq!(client(Some("bread")), "ip:10.0.0.1");
+	}
+}
@@ -1,8 +1,14 @@
 //! implementation of different sources (tile containers, folders, tar files)
 
+mod pan_detector;
+use pan_detector::PanDetector;
+
 mod response;
 pub use response::SourceResponse;
 
+mod static_compression;
+pub use static_compression::StaticCompression;
+
 mod static_source;
 pub use static_source::StaticSource;
 
@@ -0,0 +1,206 @@
+//! Detects clients that pan the map, from the tiles they request, so that the tiles they will see next can be
+//! prefetched.
+//!
+//! The recent tile requests of a zoom level make up the viewport. A request outside of the viewport moves its
+//! edge. Once the edge has moved several times in the same direction, the client is panning, and the tiles
+//! beyond the moving edge are predicted.
+
+use std::{collections::VecDeque, sync::Mutex};
+use versatiles_core::types::{TileBBox, TileCoord3};
+
+/// Number of recent tile requests that make up the viewport.
+const HISTORY_SIZE: usize = 32;
+
+/// Minimum number of tile requests before the viewport is used.
+const MIN_HISTORY_SIZE: usize = 4;
+
+/// Number of times the viewport must move in the same direction, before it counts as panning.
+const MIN_MOVES: usize = 2;
+
+/// Number of rows or columns of tiles beyond the moving edge that are predicted.
+const PREFETCH_DEPTH: u32 = 2;
+
+#[derive(Debug, Default)]
+struct PanState {
+	history: VecDeque<TileCoord3>,
+	direction: (i8, i8),
+	moves: usize,
+	last_prediction: Option<TileBBox>,
+}
+
+/// Tracks the tile requests of one source.
+///
+/// Requests of all clients are tracked together, so several clients panning at the same time on the same zoom
+/// level disturb each other's detection. In that case nothing is predicted, which is harmless.
+#[derive(Debug, Default)]
+pub struct PanDetector {
+	state: Mutex<PanState>,
+}
+
+impl PanDetector {
+	/// Records a tile request and returns the tiles that will probably be requested next, if the client pans.
+	///
+	/// The same prediction is only returned once.
+	pub fn add(&self, coord: &TileCoord3) -> Option<TileBBox> {
+		let mut state = self.state.lock().unwrap();
+
+		if state.history.front().is_some_and(|c| c.z != coord.z) {
+			*state = PanState::default();
+		}
+
+		let viewport = get_viewport(&state.history);
+		state.history.push_back(*coord);
+		if state.history.len() > HISTORY_SIZE {
+			state.history.pop_front();
+		}
+		let viewport = viewport?;
+
+		let direction = (
+			get_direction(coord.x, viewport.x_min, viewport.x_max),
+			get_direction(coord.y, viewport.y_min, viewport.y_max),
+		);
+		if direction == (0, 0) {
+			return None;
+		}
+		if direction == state.direction {
+			state.moves += 1;
+		} else {
+			state.direction = direction;
+			state.moves = 1;
+		}
+		if state.moves < MIN_MOVES {
+			return None;
+		}
+
+		let mut viewport = viewport;
+		viewport.include_coord(coord.x, coord.y);
+		let prediction = predict(&viewport, direction)?;
+		if state.last_prediction.as_ref() == Some(&prediction) {
+			return None;
+		}
+		state.last_prediction = Some(prediction.clone());
+		Some(prediction)
+	}
+}
+
+/// Returns the bounding box of the recent requests, if there are enough of them.
+fn get_viewport(history: &VecDeque<TileCoord3>) -> Option<TileBBox> {
+	if history.len() < MIN_HISTORY_SIZE {
+		return None;
+	}
+	let mut viewport = TileBBox::new_empty(history[0].z).ok()?;
+	for coord in history {
+		viewport.include_coord(coord.x, coord.y);
+	}
+	Some(viewport)
+}
+
+fn get_direction(value: u32, min: u32, max: u32) -> i8 {
+	if value < min {
+		-1
+	} else if value > max {
+		1
+	} else {
+		0
+	}
+}
+
+/// Returns the tiles beyond the edges of the viewport that move in the given direction.
+///
+/// If the viewport moves along one axis, only the strip beyond its edge is returned. If it moves diagonally,
+/// the viewport is extended on both moving edges.
+fn predict(viewport: &TileBBox, direction: (i8, i8)) -> Option<TileBBox> {
+	let diagonal = direction.0 != 0 && direction.1 != 0;
+	let extend = |min: u32, max: u32, limit: u32, direction: i8| -> Option<(u32, u32)> {
+		let (start, end) = match direction {
+			-1 => (min.saturating_sub(PREFETCH_DEPTH), min.checked_sub(1)?),
+			1 if max < limit => (max + 1, (max + PREFETCH_DEPTH).min(limit)),
+			1 => return None,
+			_ => return Some((min, max)),
+		};
+		Some(if diagonal {
+			(start.min(min), end.max(max))
+		} else {
+			(start, end)
+		})
+	};
+
+	let (level, grid) = (viewport.level, viewport.grid);
+	let (x_min, x_max) = extend(viewport.x_min, viewport.x_max, grid.max_column(level), direction.0)?;
+	let (y_min, y_max) = extend(viewport.y_min, viewport.y_max, grid.max_row(level), direction.1)?;
+	TileBBox::new_in_grid(grid, level, x_min, y_min, x_max, y_max).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn add(detector: &PanDetector, x: u32, y: u32, z: u8) -> Option<String> {
+		detector
+			.add(&TileCoord3::new(x, y, z).unwrap())
+			.map(|bbox| format!("{bbox:?}"))
+	}
+
+	/// Requests a viewport of 3×2 tiles, with its upper left corner at x, y.
+	fn add_viewport(detector: &PanDetector, x: u32, y: u32) -> Vec<String> {
+		let mut predictions = Vec::new();
+		for dy in 0..2 {
+			for dx in 0..3 {
+				predictions.extend(add(detector, x + dx, y + dy, 10));
+			}
+		}
+		predictions
+	}
+
+	#[test]
+	fn panning_right() {
+		let detector = PanDetector::default();
+		assert!(add_viewport(&detector, 100, 50).is_empty());
+		assert!(add_viewport(&detector, 101, 50).is_empty());
+		assert_eq!(add_viewport(&detector, 102, 50), ["10: [105,50,106,51] (4)"]);
+		assert_eq!(add_viewport(&detector, 103, 50), ["10: [106,50,107,51] (4)"]);
+	}
+
+	#[test]
+	fn panning_up_left() {
+		let detector = PanDetector::default();
+		assert!(add_viewport(&detector, 100, 50).is_empty());
+		assert!(add_viewport(&detector, 99, 49).is_empty());
+		assert_eq!(add_viewport(&detector, 98, 48), ["10: [96,46,102,51] (42)"]);
+	}
+
+	#[test]
+	fn no_panning() {
+		let detector = PanDetector::default();
+		for _ in 0..3 {
+			assert!(add_viewport(&detector, 100, 50).is_empty());
+		}
+
+		// moving back and forth is not panning
+		assert!(add_viewport(&detector, 101, 50).is_empty());
+		assert!(add_viewport(&detector, 100, 51).is_empty());
+		assert!(add_viewport(&detector, 99, 50).is_empty());
+	}
+
+	#[test]
+	fn zooming_resets() {
+		let detector = PanDetector::default();
+		assert!(add_viewport(&detector, 100, 50).is_empty());
+		assert!(add_viewport(&detector, 101, 50).is_empty());
+		assert_eq!(add(&detector, 0, 0, 3), None);
+		assert_eq!(add(&detector, 1, 0, 3), None);
+	}
+
+	#[test]
+	fn edge_of_the_world() {
+		assert_eq!(predict(&TileBBox::new(10, 0, 0, 2, 1).unwrap(), (-1, 0)), None);
+		assert_eq!(predict(&TileBBox::new(10, 1021, 0, 1023, 1).unwrap(), (1, 0)), None);
+		assert_eq!(
+			format!(
+				"{:?}",
+				predict(&TileBBox::new(10, 1, 0, 3, 1).unwrap(), (-1, 0)).unwrap()
+			),
+			"10: [0,0,0,1] (2)"
+		);
+	}
+}
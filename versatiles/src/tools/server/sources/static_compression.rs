@@ -0,0 +1,118 @@
+use super::{super::utils::is_incompressible_mime, SourceResponse};
+use anyhow::Result;
+use std::{mem::size_of, sync::Mutex};
+use versatiles_core::{
+	types::{Blob, LimitedCache, TileCompression},
+	utils::{optimize_compression, TargetCompression},
+};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Number of compressed static files that are kept in memory.
+const CACHE_ENTRIES: usize = 1024;
+
+/// Hash of the content, and whether Brotli and Gzip are accepted.
+type CacheKey = (u64, bool, bool);
+
+/// Compresses static files with the best compression the client accepts and caches the result,
+/// so every file is only compressed on the first request.
+pub struct StaticCompression {
+	cache: Mutex<LimitedCache<CacheKey, (Blob, TileCompression)>>,
+}
+
+impl StaticCompression {
+	pub fn new() -> StaticCompression {
+		StaticCompression {
+			cache: Mutex::new(LimitedCache::with_maximum_size(
+				CACHE_ENTRIES * size_of::<(CacheKey, (Blob, TileCompression))>(),
+			)),
+		}
+	}
+
+	pub fn compress(&self, response: SourceResponse, accept: &TargetCompression) -> Result<SourceResponse> {
+		use TileCompression::*;
+
+		if is_incompressible_mime(&response.mime) {
+			return Ok(response);
+		}
+
+		let key = (
+			xxh3_64(response.blob.as_slice()),
+			accept.contains(Brotli),
+			accept.contains(Gzip),
+		);
+		if let Some((blob, compression)) = self.cache.lock().unwrap().get(&key) {
+			return Ok(SourceResponse {
+				blob,
+				compression,
+				mime: response.mime,
+			});
+		}
+
+		let mut target = TargetCompression::from_none();
+		for compression in [Brotli, Gzip] {
+			if accept.contains(compression) {
+				target.insert(compression);
+			}
+		}
+		let (blob, compression) = optimize_compression(response.blob, &response.compression, &target)?;
+		self.cache.lock().unwrap().add(key, (blob.clone(), compression));
+
+		Ok(SourceResponse {
+			blob,
+			compression,
+			mime: response.mime,
+		})
+	}
+}
+
+impl Default for StaticCompression {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use enumset::enum_set;
+	use versatiles_core::utils::decompress_brotli;
+
+	#[test]
+	fn compress_and_cache() -> Result<()> {
+		use TileCompression::*;
+
+		let cache = StaticCompression::new();
+		let text = "body { color: black; } ".repeat(100);
+		let css = || SourceResponse::new_some(Blob::from(text.as_str()), &Uncompressed, "text/css").unwrap();
+
+		let brotli = TargetCompression::from_set(enum_set!(Uncompressed | Gzip | Brotli));
+		let response = cache.compress(css(), &brotli)?;
+		assert_eq!(response.compression, Brotli);
+		assert_eq!(decompress_brotli(&response.blob)?.as_str(), text);
+
+		// the second request is served from the cache
+		assert_eq!(
+			cache
+				.cache
+				.lock()
+				.unwrap()
+				.get(&(xxh3_64(text.as_bytes()), true, true))
+				.unwrap()
+				.1,
+			Brotli
+		);
+		assert_eq!(cache.compress(css(), &brotli)?.blob, response.blob);
+
+		let gzip = TargetCompression::from_set(enum_set!(Uncompressed | Gzip));
+		assert_eq!(cache.compress(css(), &gzip)?.compression, Gzip);
+
+		let none = TargetCompression::from_none();
+		assert_eq!(cache.compress(css(), &none)?.compression, Uncompressed);
+
+		// images are not compressed
+		let png = SourceResponse::new_some(Blob::from("png"), &Uncompressed, "image/png").unwrap();
+		assert_eq!(cache.compress(png, &brotli)?.compression, Uncompressed);
+
+		Ok(())
+	}
+}
@@ -60,7 +60,7 @@ impl StaticSourceTrait for Folder {
 
 	// Gets the data at the given path and responds with a compressed or uncompressed version
 	// based on the accept header
-	fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Option<SourceResponse> {
+	fn get_data(&self, url: &Url, accept: &TargetCompression) -> Option<SourceResponse> {
 		let mut local_path = url.as_path(&self.folder);
 
 		// If the path is a directory, append 'index.html'
@@ -75,17 +75,19 @@ impl StaticSourceTrait for Folder {
 
 		let mime = guess_mime(&local_path);
 
-		// Check for compressed versions first (".br" and ".gz"), falling back to uncompressed if neither is found
-
-		let (file, compression) = if let Ok(file) = File::open(&local_path) {
-			(file, TileCompression::Uncompressed)
-		} else if let Ok(file) = File::open(format!("{}.br", local_path.display())) {
-			(file, TileCompression::Brotli)
-		} else if let Ok(file) = File::open(format!("{}.gz", local_path.display())) {
-			(file, TileCompression::Gzip)
-		} else {
-			return None;
+		// Prefer precompressed versions (".br" and ".gz") that are accepted by the client, then the uncompressed
+		// file, then any compressed version
+		use TileCompression::*;
+		let open = |compression: TileCompression| {
+			File::open(format!("{}{}", local_path.display(), compression.extension()))
+				.ok()
+				.map(|file| (file, compression))
 		};
+		let (file, compression) = [Brotli, Gzip]
+			.into_iter()
+			.filter(|compression| accept.contains(*compression))
+			.chain([Uncompressed, Brotli, Gzip])
+			.find_map(open)?;
 
 		let mut buffer = Vec::new();
 		BufReader::new(file).read_to_end(&mut buffer).unwrap();
@@ -194,4 +196,31 @@ mod tests {
 		// Cleanup
 		temp_dir.close().unwrap();
 	}
+
+	#[tokio::test]
+	async fn test_precompressed_siblings() {
+		use enumset::enum_set;
+		use TileCompression::*;
+
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(temp_dir.path().join("style.json"), b"plain").unwrap();
+		std::fs::write(temp_dir.path().join("style.json.br"), b"brotli").unwrap();
+		std::fs::write(temp_dir.path().join("style.json.gz"), b"gzip").unwrap();
+		let folder = Folder::from(temp_dir.path()).unwrap();
+
+		let get = |accept: TargetCompression| {
+			let response = folder.get_data(&Url::new("style.json"), &accept).unwrap();
+			(response.blob.as_str().to_owned(), response.compression)
+		};
+
+		assert_eq!(
+			get(TargetCompression::from_set(enum_set!(Uncompressed | Gzip | Brotli))),
+			("brotli".to_owned(), Brotli)
+		);
+		assert_eq!(
+			get(TargetCompression::from_set(enum_set!(Uncompressed | Gzip))),
+			("gzip".to_owned(), Gzip)
+		);
+		assert_eq!(get(TargetCompression::from_none()), ("plain".to_owned(), Uncompressed));
+	}
 }
@@ -1,9 +1,19 @@
-use super::{super::utils::Url, SourceResponse};
-use anyhow::{ensure, Result};
-use std::{fmt::Debug, sync::Arc};
+use super::{
+	super::utils::{Url, UrlTemplate},
+	PanDetector, SourceResponse,
+};
+use anyhow::Result;
+use std::{
+	fmt::Debug,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::SystemTime,
+};
 use tokio::sync::Mutex;
+use tracing::{info_span, Instrument};
 use versatiles_core::{
-	types::{Blob, TileCompression, TileCoord3, TilesReaderTrait},
+	json::{JsonObject, JsonValue},
+	types::{Blob, TileCompression, TileCoord3, TileFormat, TilesReaderTrait},
 	utils::TargetCompression,
 };
 
@@ -11,8 +21,14 @@ use versatiles_core::{
 #[derive(Clone)]
 pub struct TileSource {
 	pub prefix: Url,
+	pub url_template: UrlTemplate,
 	pub id: String,
+	/// Path or URL of the container, if the source can be opened again.
+	pub url: Option<String>,
 	reader: Arc<Mutex<Box<dyn TilesReaderTrait>>>,
+	/// Predicts the next tiles of panning clients, so that they can be prefetched.
+	pan_detector: Arc<PanDetector>,
+	pub tile_format: TileFormat,
 	pub tile_mime: String,
 	pub compression: TileCompression,
 }
@@ -21,13 +37,20 @@ impl TileSource {
 	// Constructor function for creating a TileSource instance
 	pub fn from(reader: Box<dyn TilesReaderTrait>, id: &str) -> Result<TileSource> {
 		let parameters = reader.get_parameters();
-		let tile_mime = parameters.tile_format.as_mime_str().to_string();
+		let tile_format = parameters.tile_format;
+		let tile_mime = tile_format.as_mime_str().to_string();
 		let compression = parameters.tile_compression;
 
+		let url_template = UrlTemplate::new_default(id);
+
 		Ok(TileSource {
-			prefix: Url::new(&format!("/tiles/{id}/")).as_dir(),
+			prefix: url_template.prefix.clone(),
+			url_template,
 			id: id.to_owned(),
+			url: None,
 			reader: Arc::new(Mutex::new(reader)),
+			pan_detector: Arc::new(PanDetector::default()),
+			tile_format,
 			tile_mime,
 			compression,
 		})
@@ -38,31 +61,113 @@ impl TileSource {
 		reader.get_source_name().to_owned()
 	}
 
-	// Retrieve the tile data as an HTTP response
-	pub async fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Result<Option<SourceResponse>> {
-		let parts: Vec<String> = url.as_vec();
+	/// Returns the reader of the container, which is shared by all requests.
+	pub fn get_reader(&self) -> Arc<Mutex<Box<dyn TilesReaderTrait>>> {
+		self.reader.clone()
+	}
+
+	/// Returns the absolute path of the container file, if it is a local `*.versatiles` or `*.pmtiles` file,
+	/// which can be read directly by clients.
+	pub fn get_container_path(&self) -> Option<PathBuf> {
+		let path = Path::new(self.url.as_ref()?);
+		let extension = path.extension()?.to_str()?;
+		if !matches!(extension, "versatiles" | "pmtiles") || !path.is_file() {
+			return None;
+		}
+		path.canonicalize().ok()
+	}
+
+	/// Reads the first tile of the lowest zoom level, to check that the container responds.
+	pub async fn probe(&self) -> Result<()> {
+		let reader = self.reader.lock().await;
+		let Some(bbox) = reader.get_parameters().bbox_pyramid.iter_levels().next() else {
+			return Ok(());
+		};
+		let coord = TileCoord3::new(bbox.x_min, bbox.y_min, bbox.level)?;
+		reader.get_tile_data(&coord).await?;
+		Ok(())
+	}
+
+	/// Describes the source for the admin API.
+	pub async fn get_info(&self) -> JsonObject {
+		let reader = self.reader.lock().await;
+		let parameters = reader.get_parameters();
+
+		let mut info = JsonObject::default();
+		info.set("id", JsonValue::from(&self.id));
+		if let Some(url) = &self.url {
+			info.set("url", JsonValue::from(url));
+		}
+		info.set("source", JsonValue::from(reader.get_source_name()));
+		info.set("container", JsonValue::from(reader.get_container_name()));
+		info.set("tile_format", JsonValue::from(parameters.tile_format.as_str()));
+		info.set("prefix", JsonValue::from(&self.prefix.str));
+		info
+	}
 
-		if parts.len() >= 3 {
-			// Parse the tile coordinates
-			let z = parts[0].parse::<u8>();
-			let x = parts[1].parse::<u32>();
-			let y: String = parts[2].chars().take_while(|c| c.is_numeric()).collect();
-			let y = y.parse::<u32>();
+	/// Changes the URL of the tiles, see [`UrlTemplate`].
+	pub fn set_url_template(&mut self, url_template: UrlTemplate) {
+		self.prefix = url_template.prefix.clone();
+		self.url_template = url_template;
+	}
+
+	/// Parses the tile coordinates of a URL relative to the prefix, using the [`UrlTemplate`] of the source.
+	///
+	/// Returns `None` if the URL doesn't address a tile, e.g. "meta.json".
+	///
+	/// # Errors
+	/// Returns an error if the coordinates are not valid numbers.
+	pub fn get_coord(&self, url: &Url) -> Result<Option<TileCoord3>> {
+		self.url_template.get_coord(url)
+	}
+
+	/// Checks whether a tile is inside of the bbox pyramid of the container.
+	pub async fn contains_coord(&self, coord: &TileCoord3) -> bool {
+		let reader = self.reader.lock().await;
+		reader.get_parameters().bbox_pyramid.contains_coord(coord)
+	}
 
-			// Check for parsing errors
-			ensure!(z.is_ok(), "value for z is not a number");
-			ensure!(x.is_ok(), "value for x is not a number");
-			ensure!(y.is_ok(), "value for y is not a number");
+	/// Checks whether a tile exists, without reading it if the container supports that.
+	pub async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		let reader = self.reader.lock().await;
+		reader.has_tile(coord).await
+	}
+
+	/// Returns the time a tile was last modified, if the container knows it.
+	pub async fn get_tile_mtime(&self, coord: &TileCoord3) -> Result<Option<SystemTime>> {
+		let reader = self.reader.lock().await;
+		reader.get_tile_mtime(coord).await
+	}
 
-			// Create a TileCoord3 instance
-			let coord = TileCoord3::new(x?, y?, z?)?;
+	/// Reads several tiles at once, in the compression of the container.
+	/// Missing tiles and tiles outside of the bbox pyramid of the container are skipped.
+	pub async fn get_tiles(&self, coords: &[TileCoord3]) -> Result<Vec<(TileCoord3, Blob)>> {
+		let reader = self.reader.lock().await;
+		let pyramid = &reader.get_parameters().bbox_pyramid;
+		let mut tiles = Vec::new();
+		for coord in coords.iter().filter(|coord| pyramid.contains_coord(coord)) {
+			if let Some(blob) = reader.get_tile_data(coord).await? {
+				tiles.push((*coord, blob));
+			}
+		}
+		Ok(tiles)
+	}
 
+	// Retrieve the tile data as an HTTP response
+	pub async fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Result<Option<SourceResponse>> {
+		if let Some(coord) = self.get_coord(url)? {
 			log::debug!("get tile, prefix: {}, coord: {}", self.prefix, coord.as_json());
 
 			// Get tile data
-			let reader = self.reader.lock().await;
-			let tile = reader.get_tile_data(&coord).await;
-			drop(reader);
+			let span = info_span!("read tile", source = %self.id, z = coord.z, x = coord.x, y = coord.y);
+			let tile = async {
+				let reader = self.reader.lock().await;
+				reader.get_tile_data(&coord).await
+			}
+			.instrument(span)
+			.await;
+
+			self.prefetch_if_panning(&coord);
 
 			// If tile data is not found, return a not found response
 			if tile.is_err() {
@@ -75,7 +180,10 @@ impl TileSource {
 			} else {
 				Ok(None)
 			};
-		} else if (parts[0] == "meta.json") || (parts[0] == "tiles.json") {
+		}
+
+		let parts: Vec<String> = url.as_vec();
+		if (parts[0] == "meta.json") || (parts[0] == "tiles.json") {
 			// Get metadata
 			let tile_json = self.build_tile_json().await?;
 
@@ -90,6 +198,20 @@ impl TileSource {
 		Ok(None)
 	}
 
+	/// Prefetches the tiles that a panning client will probably request next, in the background.
+	fn prefetch_if_panning(&self, coord: &TileCoord3) {
+		let Some(bbox) = self.pan_detector.add(coord) else {
+			return;
+		};
+		let reader = self.reader.clone();
+		tokio::spawn(async move {
+			let reader = reader.lock().await;
+			if let Err(err) = reader.prefetch(&bbox).await {
+				log::debug!("prefetching tiles {bbox:?} failed: {err:#}");
+			}
+		});
+	}
+
 	async fn build_tile_json(&self) -> Result<Blob> {
 		let reader = self.reader.lock().await;
 		let mut tilejson = reader.get_tilejson().clone();
@@ -99,9 +221,12 @@ impl TileSource {
 		tilejson.set_string("type", parameters.tile_format.as_type_str())?;
 		tilejson.set_string("name", self.id.as_str())?;
 		tilejson.set_string("format", parameters.tile_format.as_str())?;
+		tilejson.set_tile_grid(&parameters.bbox_pyramid.get_grid())?;
 
-		let tiles_url = format!("{}{{z}}/{{x}}/{{y}}", self.prefix.as_string());
-		tilejson.set_list("tiles", vec![tiles_url])?;
+		tilejson.set_list("tiles", vec![self.url_template.as_tilejson_url()])?;
+		if self.url_template.is_flipped() {
+			tilejson.set_string("scheme", "tms")?;
+		}
 
 		Ok(tilejson.into())
 	}
@@ -123,6 +248,7 @@ mod tests {
 	use super::*;
 	use anyhow::Result;
 	use versatiles_container::{MockTilesReader, MockTilesReaderProfile};
+	use versatiles_core::types::{TileBBoxPyramid, TileGrid, TilesReaderParameters};
 
 	// Test the constructor function for TileSource
 	#[tokio::test]
@@ -136,6 +262,55 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn tile_json_with_url_template() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let mut container = TileSource::from(reader.boxed(), "prefix")?;
+		container.set_url_template(UrlTemplate::parse("/tms/prefix/{z}/{x}/{-y}.png")?);
+		assert_eq!(container.prefix.str, "/tms/prefix/");
+
+		let tilejson = container.build_tile_json().await?;
+		assert!(tilejson
+			.as_str()
+			.contains("\"tiles\":[\"/tms/prefix/{z}/{x}/{y}.png\"]"));
+		assert!(tilejson.as_str().contains("\"scheme\":\"tms\""));
+
+		assert_eq!(
+			container.get_coord(&Url::new("3/2/1.png"))?,
+			Some(TileCoord3::new(2, 6, 3)?)
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_json_with_tile_grid() -> Result<()> {
+		let reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_full_in_grid(TileGrid::Wgs84, 1),
+		))?;
+		let container = TileSource::from(reader.boxed(), "prefix")?;
+
+		let tilejson = container.build_tile_json().await?;
+		assert!(tilejson.as_str().contains("\"bounds\":[-180,-90,180,90]"));
+		assert!(tilejson.as_str().contains("\"tile_grid\":\"wgs84\""));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn get_tiles() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let container = TileSource::from(reader.boxed(), "prefix")?;
+
+		let coords = [TileCoord3::new(1, 2, 3)?, TileCoord3::new(0, 0, 5)?];
+		let tiles = container.get_tiles(&coords).await?;
+		assert_eq!(tiles.len(), 1);
+		assert_eq!(tiles[0].0, coords[0]);
+		assert_eq!(&tiles[0].1.as_slice()[0..4], b"\x89PNG");
+		Ok(())
+	}
+
 	// Test the debug function
 	#[test]
 	fn debug() -> Result<()> {
@@ -207,4 +382,22 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn tile_container_has_tile() -> Result<()> {
+		let c = TileSource::from(
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed(),
+			"prefix",
+		)?;
+
+		let coord = c.get_coord(&Url::new("3/2/4.png"))?.unwrap();
+		assert_eq!(coord, TileCoord3::new(2, 4, 3)?);
+		assert!(c.has_tile(&coord).await?);
+		assert_eq!(c.get_tile_mtime(&coord).await?, None);
+
+		assert!(c.get_coord(&Url::new("meta.json"))?.is_none());
+		assert!(c.get_coord(&Url::new("x/0/0.png")).is_err());
+
+		Ok(())
+	}
 }
@@ -0,0 +1,344 @@
+//! Static map images stitched from raster tiles.
+//!
+//! `GET /static/{lon},{lat},{zoom}/{width}x{height}.png` returns a PNG of the area around the center, using the
+//! tiles of the first raster source. Optional query parameters:
+//! - `source={id}`: use the tile source `id`.
+//! - `marker`: draw a marker at the center, or with `marker={lon},{lat}` at another position.
+
+use super::{admin::json_error, sources::TileSource, tile_server::TileSources, utils::get_query_parameter};
+use anyhow::{bail, ensure, Context, Result};
+use axum::{
+	body::Body,
+	extract::{Path, State},
+	http::{
+		header::{CACHE_CONTROL, CONTENT_TYPE},
+		StatusCode, Uri,
+	},
+	response::Response,
+	routing::get,
+	Router,
+};
+use futures::future::try_join_all;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use versatiles_core::{
+	types::{Blob, TileCoord3, TileFormat},
+	utils::{decompress, run_blocking},
+};
+use versatiles_image::helper::blob2image_blocking;
+
+/// Maximum width and height of static map images.
+const MAX_SIZE: u32 = 2048;
+/// Tile size, if it can't be determined from the tile in the center.
+const DEFAULT_TILE_SIZE: u32 = 256;
+/// Radius of the marker in pixels.
+const MARKER_RADIUS: f64 = 8.0;
+
+pub fn add_static_map_api_to_app(app: Router, tile_sources: TileSources) -> Router {
+	let static_map_app = Router::new()
+		.route("/static/{center}/{size}", get(serve_static_map))
+		.with_state(tile_sources);
+
+	app.merge(static_map_app)
+}
+
+#[derive(Debug, PartialEq)]
+struct StaticMapRequest {
+	lon: f64,
+	lat: f64,
+	zoom: u8,
+	width: u32,
+	height: u32,
+	source: Option<String>,
+	marker: Option<[f64; 2]>,
+}
+
+impl StaticMapRequest {
+	fn parse(center: &str, size: &str, query: &str) -> Result<StaticMapRequest> {
+		let values = center.split(',').collect::<Vec<_>>();
+		ensure!(values.len() == 3, "center must have the form {{lon}},{{lat}},{{zoom}}");
+		let lon = values[0].parse::<f64>().context("longitude must be a number")?;
+		let lat = values[1].parse::<f64>().context("latitude must be a number")?;
+		let zoom = values[2].parse::<u8>().context("zoom must be a zoom level")?;
+		ensure!(
+			(-180.0..=180.0).contains(&lon),
+			"longitude must be between -180 and 180"
+		);
+		ensure!(
+			(-85.06..=85.06).contains(&lat),
+			"latitude must be between -85.06 and 85.06"
+		);
+
+		let Some((width, height)) = size.strip_suffix(".png").and_then(|size| size.split_once('x')) else {
+			bail!("size must have the form {{width}}x{{height}}.png");
+		};
+		let width = width.parse::<u32>().context("width must be a number")?;
+		let height = height.parse::<u32>().context("height must be a number")?;
+		ensure!(
+			(1..=MAX_SIZE).contains(&width) && (1..=MAX_SIZE).contains(&height),
+			"width and height must be between 1 and {MAX_SIZE}"
+		);
+
+		let marker = match get_query_parameter(query, "marker").as_deref() {
+			None => None,
+			Some("") => Some([lon, lat]),
+			Some(marker) => {
+				let (lon, lat) = marker
+					.split_once(',')
+					.context("marker must have the form {lon},{lat}")?;
+				Some([
+					lon.parse::<f64>().context("marker longitude must be a number")?,
+					lat.parse::<f64>().context("marker latitude must be a number")?,
+				])
+			}
+		};
+
+		Ok(StaticMapRequest {
+			lon,
+			lat,
+			zoom,
+			width,
+			height,
+			source: get_query_parameter(query, "source"),
+			marker,
+		})
+	}
+}
+
+async fn serve_static_map(
+	uri: Uri,
+	Path((center, size)): Path<(String, String)>,
+	State(tile_sources): State<TileSources>,
+) -> Response<Body> {
+	log::debug!("handle static map request: {uri}");
+
+	let request = match StaticMapRequest::parse(&center, &size, uri.query().unwrap_or("")) {
+		Ok(request) => request,
+		Err(err) => return json_error(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+	};
+
+	let tile_source = tile_sources
+		.read()
+		.unwrap()
+		.iter()
+		.find(|source| match &request.source {
+			Some(id) => &source.id == id,
+			None => is_raster(source.tile_format),
+		})
+		.cloned();
+	let Some(tile_source) = tile_source else {
+		return json_error(StatusCode::NOT_FOUND, "no raster tile source found");
+	};
+
+	let blob = match render(&tile_source, &request).await {
+		Ok(blob) => blob,
+		Err(err) => return json_error(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+	};
+
+	log::info!("send static map: {uri}");
+	Response::builder()
+		.status(200)
+		.header(CONTENT_TYPE, "image/png")
+		.header(CACHE_CONTROL, "public, max-age=86400")
+		.body(Body::from(blob.into_vec()))
+		.expect("should have build a body")
+}
+
+fn is_raster(tile_format: TileFormat) -> bool {
+	matches!(tile_format, TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP)
+}
+
+/// Stitches the tiles around the center and crops them to the requested size.
+async fn render(tile_source: &TileSource, request: &StaticMapRequest) -> Result<Blob> {
+	ensure!(
+		is_raster(tile_source.tile_format),
+		"tile source '{}' does not contain raster tiles",
+		tile_source.id
+	);
+	let (grid, zoom_max) = {
+		let reader = tile_source.get_reader();
+		let reader = reader.lock().await;
+		let bbox_pyramid = &reader.get_parameters().bbox_pyramid;
+		(bbox_pyramid.get_grid(), bbox_pyramid.get_zoom_max())
+	};
+	ensure!(
+		zoom_max.is_some_and(|zoom_max| request.zoom <= zoom_max),
+		"zoom must not be larger than {}",
+		zoom_max.unwrap_or_default()
+	);
+
+	let [center_x, center_y] = grid.geo_to_tile(request.lon, request.lat, request.zoom);
+	let columns = grid.get_base_size().0 << request.zoom;
+	let rows = grid.get_base_size().1 << request.zoom;
+
+	let center = TileCoord3::new(
+		(center_x as u32).min(columns - 1),
+		(center_y as u32).min(rows - 1),
+		request.zoom,
+	)?;
+	let tile_size = match read_tiles(tile_source, &[center]).await?.first() {
+		Some((_, image)) => image.width(),
+		None => DEFAULT_TILE_SIZE,
+	};
+
+	// pixel position of the top left corner of the image
+	let left = center_x * tile_size as f64 - request.width as f64 / 2.0;
+	let top = center_y * tile_size as f64 - request.height as f64 / 2.0;
+
+	let mut coords = Vec::new();
+	let mut offsets = Vec::new();
+	let tile_range = |start: f64, length: u32| {
+		(start / tile_size as f64).floor() as i64..=((start + length as f64) / tile_size as f64).floor() as i64
+	};
+	let x_range = tile_range(left, request.width);
+	let y_range = tile_range(top, request.height);
+	for y in y_range {
+		if y < 0 || y >= rows as i64 {
+			continue;
+		}
+		for x in x_range.clone() {
+			// repeat the world horizontally
+			let coord = TileCoord3::new(x.rem_euclid(columns as i64) as u32, y as u32, request.zoom)?;
+			offsets.push((
+				coord,
+				(
+					x * tile_size as i64 - left.round() as i64,
+					y * tile_size as i64 - top.round() as i64,
+				),
+			));
+			coords.push(coord);
+		}
+	}
+	coords.dedup();
+
+	let tiles = read_tiles(tile_source, &coords).await?;
+	let (width, height, zoom, marker) = (request.width, request.height, request.zoom, request.marker);
+
+	// stitching and encoding the image is too slow for the async runtime
+	run_blocking(move || {
+		let mut canvas = RgbaImage::new(width, height);
+		for (coord, (dx, dy)) in offsets {
+			if let Some((_, image)) = tiles.iter().find(|(c, _)| *c == coord) {
+				imageops::overlay(&mut canvas, &image.to_rgba8(), dx, dy);
+			}
+		}
+
+		if let Some([lon, lat]) = marker {
+			let [x, y] = grid.geo_to_tile(lon, lat, zoom);
+			draw_marker(&mut canvas, x * tile_size as f64 - left, y * tile_size as f64 - top);
+		}
+
+		versatiles_image::png::image2blob(&DynamicImage::ImageRgba8(canvas), false)
+	})
+	.await
+}
+
+/// Reads and decodes the tiles at the coordinates. Missing tiles are skipped.
+async fn read_tiles(tile_source: &TileSource, coords: &[TileCoord3]) -> Result<Vec<(TileCoord3, DynamicImage)>> {
+	let tiles = tile_source.get_tiles(coords).await?;
+	try_join_all(tiles.into_iter().map(|(coord, blob)| async move {
+		let blob = decompress(blob, &tile_source.compression)?;
+		Ok((coord, blob2image_blocking(blob, tile_source.tile_format).await?))
+	}))
+	.await
+}
+
+/// Draws a red circle with a white border, centered at the pixel position.
+fn draw_marker(canvas: &mut RgbaImage, x: f64, y: f64) {
+	let radius = MARKER_RADIUS + 2.0;
+	let x_min = (x - radius).floor().max(0.0) as u32;
+	let y_min = (y - radius).floor().max(0.0) as u32;
+	let x_max = ((x + radius).ceil() as i64).clamp(0, canvas.width() as i64) as u32;
+	let y_max = ((y + radius).ceil() as i64).clamp(0, canvas.height() as i64) as u32;
+	for py in y_min..y_max {
+		for px in x_min..x_max {
+			let distance = (px as f64 + 0.5 - x).hypot(py as f64 + 0.5 - y);
+			if distance <= MARKER_RADIUS {
+				canvas.put_pixel(px, py, Rgba([220, 30, 30, 255]));
+			} else if distance <= radius {
+				canvas.put_pixel(px, py, Rgba([255, 255, 255, 255]));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::TileServer;
+	use super::*;
+	use versatiles_container::{MockTilesReader, MockTilesReaderProfile};
+	use versatiles_core::types::TilesReaderTrait;
+
+	const IP: &str = "127.0.0.1";
+	const PORT: u16 = 50022;
+
+	#[test]
+	fn parse_request() -> Result<()> {
+		let request = StaticMapRequest::parse("13.4,52.5,3", "300x200.png", "marker")?;
+		assert_eq!(
+			request,
+			StaticMapRequest {
+				lon: 13.4,
+				lat: 52.5,
+				zoom: 3,
+				width: 300,
+				height: 200,
+				source: None,
+				marker: Some([13.4, 52.5]),
+			}
+		);
+
+		let request = StaticMapRequest::parse("0,0,0", "10x10.png", "source=osm&marker=1,2")?;
+		assert_eq!(request.source.as_deref(), Some("osm"));
+		assert_eq!(request.marker, Some([1.0, 2.0]));
+
+		assert!(StaticMapRequest::parse("0,0", "10x10.png", "").is_err());
+		assert!(StaticMapRequest::parse("0,100,0", "10x10.png", "").is_err());
+		assert!(StaticMapRequest::parse("0,0,0", "10x10.jpg", "").is_err());
+		assert!(StaticMapRequest::parse("0,0,0", "0x10.png", "").is_err());
+		assert!(StaticMapRequest::parse("0,0,0", "10x5000.png", "").is_err());
+		assert!(StaticMapRequest::parse("0,0,0", "10x10.png", "marker=1").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn marker() {
+		let mut canvas = RgbaImage::new(40, 40);
+		draw_marker(&mut canvas, 20.0, 20.0);
+		assert_eq!(canvas.get_pixel(20, 20), &Rgba([220, 30, 30, 255]));
+		assert_eq!(canvas.get_pixel(20, 29), &Rgba([255, 255, 255, 255]));
+		assert_eq!(canvas.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+
+		// markers at the border are clipped
+		draw_marker(&mut canvas, -5.0, 45.0);
+	}
+
+	#[tokio::test]
+	async fn static_map_api() -> Result<()> {
+		let mut server = TileServer::new(IP, PORT, true, true);
+		server.add_tile_source(
+			"cheese",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?.boxed(),
+		)?;
+		server.add_tile_source(
+			"bread",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed(),
+		)?;
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let get = |path: &str| client.get(format!("http://{IP}:{PORT}/static/{path}")).send();
+
+		let response = get("-90,0,3/300x200.png?marker").await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "image/png");
+		let image = versatiles_image::png::blob2image(&Blob::from(response.bytes().await?.to_vec()))?;
+		assert_eq!((image.width(), image.height()), (300, 200));
+
+		assert_eq!(get("0,0,4/300x200.png").await?.status(), 400);
+		assert_eq!(get("0,0,3/300x200.png?source=cheese").await?.status(), 400);
+		assert_eq!(get("0,0,3/300x200.png?source=cake").await?.status(), 404);
+
+		server.stop().await;
+		Ok(())
+	}
+}
@@ -0,0 +1,44 @@
+//! Exports the tracing spans of the server via OpenTelemetry (OTLP over HTTP), e.g. to Jaeger or Grafana Tempo.
+//!
+//! Every request gets a "request" span. Reading tiles, looking up tile indexes, fetching byte ranges
+//! and recompressing tiles are recorded as child spans, so the latency of a request can be decomposed.
+
+use anyhow::{anyhow, Context, Result};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+pub struct Telemetry {
+	provider: SdkTracerProvider,
+}
+
+impl Telemetry {
+	/// Starts exporting spans to an OTLP endpoint, e.g. "http://localhost:4318/v1/traces".
+	pub fn init(endpoint: &str) -> Result<Telemetry> {
+		let endpoint = endpoint.to_owned();
+
+		// the blocking HTTP client of the exporter must not be created within the async runtime
+		let exporter = std::thread::spawn(move || SpanExporter::builder().with_http().with_endpoint(endpoint).build())
+			.join()
+			.map_err(|_| anyhow!("creating the OTLP exporter failed"))?
+			.context("creating the OTLP exporter")?;
+
+		let provider = SdkTracerProvider::builder()
+			.with_batch_exporter(exporter)
+			.with_resource(Resource::builder().with_service_name("versatiles").build())
+			.build();
+
+		tracing_subscriber::registry()
+			.with(tracing_opentelemetry::layer().with_tracer(provider.tracer("versatiles")))
+			.try_init()
+			.context("initializing tracing")?;
+
+		Ok(Telemetry { provider })
+	}
+
+	/// Exports the remaining spans.
+	pub fn shutdown(self) -> Result<()> {
+		self.provider.shutdown().context("shutting down the OTLP exporter")
+	}
+}
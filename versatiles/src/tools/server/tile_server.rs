@@ -1,35 +1,94 @@
 use super::{
-	sources::{SourceResponse, StaticSource, TileSource},
-	utils::Url,
+	super::{
+		search::SearchIndex,
+		style::{is_style, rewrite_style_urls},
+	},
+	admin::{add_admin_api_to_app, AdminState},
+	compression_policy::{CompressionPolicy, TileCompressor},
+	connection::ConnectionOptions,
+	cors::{handle_cors, Cors, CorsRule},
+	extract::{add_extract_api_to_app, ExtractState, DEFAULT_MAX_EXTRACT_TILES},
+	listen::ListenAddress,
+	rate_limit::{limit_requests, RateLimit, RateLimiter},
+	sources::{SourceResponse, StaticCompression, StaticSource, TileSource},
+	static_map::add_static_map_api_to_app,
+	upstream::UpstreamLimits,
+	utils::{get_query_parameter, is_incompressible_mime, parse_range_header, RangeRequest, Url, UrlTemplate},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Context, Result};
 use axum::{
-	body::Body,
-	extract::State,
+	body::{Body, Bytes},
+	extract::{Request, State},
 	http::{
-		header::{ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE},
-		HeaderMap, Uri,
+		header::{
+			ACCEPT_ENCODING, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+			HOST, IF_MODIFIED_SINCE, LAST_MODIFIED, RANGE,
+		},
+		HeaderMap, HeaderValue, Method, StatusCode, Uri,
 	},
+	middleware::{self, Next},
 	response::Response,
 	routing::get,
 	Router,
 };
-use hyper::header::{ACCESS_CONTROL_ALLOW_ORIGIN, VARY};
-use std::path::Path;
-use tokio::sync::oneshot::Sender;
+use futures::{future::BoxFuture, stream};
+use httpdate::{fmt_http_date, parse_http_date};
+use hyper::header::VARY;
+use std::{
+	path::Path,
+	sync::{Arc, RwLock},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::watch, task::JoinHandle, time::Duration};
+use tracing::{field, info_span, Instrument};
 use versatiles_core::{
-	types::{Blob, TileCompression, TilesReaderTrait},
-	utils::{optimize_compression, TargetCompression},
+	io::{DataReaderFile, DataReaderTrait},
+	json::{JsonObject, JsonValue},
+	types::{Blob, ByteRange, TileCompression, TileCoord3, TilesReaderTrait},
+	utils::{decompress, optimize_compression, TargetCompression},
 };
 
+/// Number of results of a search request, unless a "limit" is given.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+/// Maximum number of results of a search request.
+const MAX_SEARCH_LIMIT: usize = 100;
+/// Maximum time a tile source may take to answer the probe of a readiness check.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of bytes that are read at once when sending container files.
+const FILE_CHUNK_SIZE: u64 = 1024 * 1024;
+/// Maximum number of tiles of a batch request.
+const MAX_BATCH_SIZE: usize = 1000;
+
+/// The tile sources of a server, shared with the request handlers, so that sources can be changed at runtime.
+pub type TileSources = Arc<RwLock<Vec<TileSource>>>;
+
+/// Opens the container at a path or URL.
+pub type SourceOpener = Arc<dyn Fn(String) -> BoxFuture<'static, Result<Box<dyn TilesReaderTrait>>> + Send + Sync>;
+
+/// Search indexes with the id of the source they belong to.
+type SearchIndexes = Vec<(String, Arc<SearchIndex>)>;
+
 pub struct TileServer {
 	ip: String,
 	port: u16,
-	tile_sources: Vec<TileSource>,
+	listen_addresses: Vec<ListenAddress>,
+	connection_options: ConnectionOptions,
+	tile_sources: TileSources,
+	source_opener: Option<SourceOpener>,
+	admin_token: Option<String>,
+	rate_limit: Option<RateLimit>,
+	max_in_flight: Option<usize>,
+	max_extract_tiles: u64,
+	cors_rules: Vec<CorsRule>,
 	static_sources: Vec<StaticSource>,
-	exit_signal: Option<Sender<()>>,
+	search_indexes: SearchIndexes,
+	exit_signal: Option<watch::Sender<bool>>,
+	server_tasks: Vec<JoinHandle<()>>,
 	use_best_compression: bool,
+	compressor: Arc<TileCompressor>,
+	upstream_limits: Option<UpstreamLimits>,
 	use_api: bool,
+	public_url: Option<String>,
 }
 
 impl TileServer {
@@ -37,32 +96,138 @@ impl TileServer {
 		TileServer {
 			ip: ip.to_owned(),
 			port,
-			tile_sources: Vec::new(),
+			listen_addresses: Vec::new(),
+			connection_options: ConnectionOptions::default(),
+			tile_sources: Arc::new(RwLock::new(Vec::new())),
+			source_opener: None,
+			admin_token: None,
+			rate_limit: None,
+			max_in_flight: None,
+			max_extract_tiles: DEFAULT_MAX_EXTRACT_TILES,
+			cors_rules: vec![CorsRule::allow_all()],
 			static_sources: Vec::new(),
+			search_indexes: Vec::new(),
 			exit_signal: None,
+			server_tasks: Vec::new(),
 			use_best_compression,
+			compressor: Arc::new(TileCompressor::new(CompressionPolicy::default(), use_best_compression)),
+			upstream_limits: None,
 			use_api,
+			public_url: None,
 		}
 	}
 
+	/// Adds the tiles of a reader as tile source `id`.
 	pub fn add_tile_source(&mut self, id: &str, reader: Box<dyn TilesReaderTrait>) -> Result<()> {
 		log::info!("add source: id='{}', source={:?}", id, reader);
 
 		let source = TileSource::from(reader, id)?;
-		let url_prefix = &source.prefix;
-
-		for other_tile_source in self.tile_sources.iter() {
-			let other_prefix = &other_tile_source.prefix;
-			if other_prefix.starts_with(url_prefix) || url_prefix.starts_with(other_prefix) {
-				bail!("multiple sources with the prefix '{url_prefix}' and '{other_prefix}' are defined");
-			};
-		}
+		insert_tile_source(&mut self.tile_sources.write().unwrap(), source, false)
+	}
 
-		self.tile_sources.push(source);
+	/// Opens a container with the source opener and adds it as tile source `id`.
+	/// Sources added this way can be reopened by the admin API.
+	pub async fn add_tile_source_url(&mut self, id: &str, url: &str) -> Result<()> {
+		log::info!("add source: id='{id}', url='{url}'");
+
+		let reader = open_reader(&self.source_opener, url).await?;
+		self.add_tile_source(id, reader)?;
+
+		// remember the url, so that the source can be reopened
+		let mut tile_sources = self.tile_sources.write().unwrap();
+		let source = tile_sources
+			.iter_mut()
+			.find(|source| source.id == id)
+			.expect("source should have been added");
+		source.url = Some(url.to_owned());
+		Ok(())
+	}
 
+	/// Changes the URL of the tiles of the source `id`, e.g. to "/data/osm/{z}/{x}/{y}.pbf", see [`UrlTemplate`].
+	/// Sources outside of "/tiles/" must be configured before the server is started.
+	pub fn set_url_template(&mut self, id: &str, template: &str) -> Result<()> {
+		let url_template = UrlTemplate::parse(template)?;
+		let mut tile_sources = self.tile_sources.write().unwrap();
+		let index = tile_sources
+			.iter()
+			.position(|source| source.id == id)
+			.with_context(|| format!("unknown tile source '{id}'"))?;
+		let mut source = tile_sources[index].clone();
+		source.set_url_template(url_template);
+
+		// the prefix of the other sources must not overlap with the new prefix
+		let mut others = tile_sources.clone();
+		others.remove(index);
+		insert_tile_source(&mut others, source, false)?;
+		*tile_sources = others;
 		Ok(())
 	}
 
+	/// Sets the function that opens containers by path or URL.
+	pub fn set_source_opener(&mut self, opener: SourceOpener) {
+		self.source_opener = Some(opener);
+	}
+
+	/// Listens on `address` instead of the IP and port given to [`TileServer::new`].
+	/// Can be called multiple times to listen on several addresses.
+	pub fn add_listen_address(&mut self, address: ListenAddress) {
+		self.listen_addresses.push(address);
+	}
+
+	/// Sets TLS, keep-alive and HTTP/2 options of all connections, see [`ConnectionOptions`].
+	pub fn set_connection_options(&mut self, options: ConnectionOptions) {
+		self.connection_options = options;
+	}
+
+	/// Limits when tiles are recompressed, see [`CompressionPolicy`].
+	pub fn set_compression_policy(&mut self, policy: CompressionPolicy) {
+		self.compressor = Arc::new(TileCompressor::new(policy, self.use_best_compression));
+	}
+
+	/// Shows the requests to remote containers in the admin API at "/admin/upstream".
+	/// The limits must also be used by the source opener, see [`UpstreamLimits`].
+	pub fn set_upstream_limits(&mut self, limits: UpstreamLimits) {
+		self.upstream_limits = Some(limits);
+	}
+
+	/// Enables the admin API at "/admin/". Requests must send the token as "Authorization: Bearer <token>".
+	pub fn set_admin_token(&mut self, token: &str) {
+		self.admin_token = Some(token.to_owned());
+	}
+
+	/// Limits the requests of every client, see [`RateLimit`].
+	pub fn set_rate_limit(&mut self, rate_limit: RateLimit) {
+		self.rate_limit = Some(rate_limit);
+	}
+
+	/// Limits the number of requests that are handled at the same time.
+	pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+		self.max_in_flight = Some(max_in_flight);
+	}
+
+	/// Limits the number of tiles of extracts at "/extract/{id}". 0 disables extracts.
+	pub fn set_max_extract_tiles(&mut self, max_tiles: u64) {
+		self.max_extract_tiles = max_tiles;
+	}
+
+	/// Replaces the CORS rules, which allow every origin by default, see [`CorsRule`].
+	/// Without rules no CORS headers are sent.
+	pub fn set_cors_rules(&mut self, rules: Vec<CorsRule>) {
+		self.cors_rules = rules;
+	}
+
+	/// Adds a search index for the tile source `id`. All search indexes are queried at "/search?q=".
+	pub fn add_search_index(&mut self, id: &str, index: SearchIndex) {
+		log::info!("add search index: id='{id}', entries={}", index.entries.len());
+		self.search_indexes.push((id.to_owned(), Arc::new(index)));
+	}
+
+	/// Sets the public URL of the server, e.g. "https://tiles.example.org".
+	/// It is used to make the URLs of served styles absolute. Otherwise the "Host" header of the request is used.
+	pub fn set_public_url(&mut self, url: &str) {
+		self.public_url = Some(url.trim_end_matches('/').to_owned());
+	}
+
 	pub fn add_static_source(&mut self, path: &Path, url_prefix: Url) -> Result<()> {
 		let url_prefix = url_prefix.as_dir();
 
@@ -81,26 +246,67 @@ impl TileServer {
 		// Initialize App
 		let mut router = Router::new().route("/status", get(|| async { "ready!" }));
 
+		router = self.add_health_to_app(router);
 		router = self.add_tile_sources_to_app(router);
+		router = self.add_container_files_to_app(router);
 		if self.use_api {
 			router = self.add_api_to_app(router).await?;
+			router = add_static_map_api_to_app(router, self.tile_sources.clone());
+			if self.max_extract_tiles > 0 {
+				router = add_extract_api_to_app(
+					router,
+					ExtractState {
+						tile_sources: self.tile_sources.clone(),
+						max_tiles: self.max_extract_tiles,
+					},
+				);
+			}
+		}
+		if let Some(token) = &self.admin_token {
+			router = add_admin_api_to_app(
+				router,
+				AdminState {
+					token: token.clone(),
+					tile_sources: self.tile_sources.clone(),
+					opener: self.source_opener.clone(),
+					compressor: self.compressor.clone(),
+					upstream_limits: self.upstream_limits.clone(),
+				},
+			);
 		}
 		router = self.add_static_sources_to_app(router);
 
-		let addr = format!("{}:{}", self.ip, self.port);
-		eprintln!("server starts listening on {}", addr);
-
-		let listener = tokio::net::TcpListener::bind(addr).await?;
-		let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+		if self.rate_limit.is_some() || self.max_in_flight.is_some() {
+			let limiter = RateLimiter::new(self.rate_limit.clone(), self.max_in_flight);
+			router = router.layer(middleware::from_fn_with_state(Arc::new(limiter), limit_requests));
+		}
+		if !self.cors_rules.is_empty() {
+			let cors = Cors::new(self.cors_rules.clone());
+			router = router.layer(middleware::from_fn_with_state(Arc::new(cors), handle_cors));
+		}
+		router = router.layer(middleware::from_fn(trace_request));
+
+		let mut addresses = self.listen_addresses.clone();
+		if addresses.is_empty() {
+			let addr = format!("{}:{}", self.ip, self.port);
+			let socket_addr = tokio::net::lookup_host(&addr)
+				.await?
+				.next()
+				.with_context(|| format!("resolving {addr}"))?;
+			addresses.push(ListenAddress::Tcp(socket_addr));
+		}
 
-		tokio::spawn(async {
-			axum::serve(listener, router.into_make_service())
-				.with_graceful_shutdown(async {
-					rx.await.ok();
-				})
-				.await
-				.expect("should start server")
-		});
+		let options = Arc::new(self.connection_options.clone());
+		let (tx, rx) = watch::channel(false);
+		for address in addresses.iter() {
+			if options.is_tls() {
+				eprintln!("server starts listening on {} with TLS", address);
+			} else {
+				eprintln!("server starts listening on {}", address);
+			}
+			let server_task = address.serve(router.clone(), options.clone(), rx.clone()).await?;
+			self.server_tasks.push(server_task);
+		}
 
 		self.exit_signal = Some(tx);
 
@@ -118,70 +324,332 @@ impl TileServer {
 			.exit_signal
 			.take()
 			.expect("should have exit signal")
-			.send(())
+			.send(true)
 			.expect("should habe send exit signal");
 	}
 
-	fn add_tile_sources_to_app(&self, mut app: Router) -> Router {
-		for tile_source in self.tile_sources.iter() {
-			let route = tile_source.prefix.join_as_string("{*path}");
+	/// Stops accepting connections and waits up to `timeout` for the requests in flight to finish.
+	/// Connections that are still open afterwards are closed.
+	pub async fn shutdown(&mut self, timeout: Duration) {
+		self.stop().await;
+
+		let mut server_tasks = std::mem::take(&mut self.server_tasks);
+		let all_finished = futures::future::join_all(server_tasks.iter_mut());
+		if tokio::time::timeout(timeout, all_finished).await.is_err() {
+			log::warn!("requests did not finish within {timeout:?}, closing connections");
+			server_tasks.iter().for_each(|task| task.abort());
+		} else {
+			log::info!("server stopped");
+		}
+	}
+
+	/// Opens all containers again, e.g. after they have been replaced, and swaps all of them at once.
+	/// If a container can't be opened, all previous sources are kept.
+	/// Returns the number of reopened sources.
+	pub async fn reload_tile_sources(&self) -> Result<usize> {
+		reopen_tile_sources(&self.tile_sources, &self.source_opener).await
+	}
+
+	/// Adds "/healthz", which answers as long as the process runs, and "/readyz", which checks that all
+	/// tile sources respond to a probe tile request.
+	fn add_health_to_app(&self, app: Router) -> Router {
+		let health_app = Router::new()
+			.route("/healthz", get(|| async { ok_json("{\"status\":\"ok\"}") }))
+			.route("/readyz", get(readyz))
+			.with_state(self.tile_sources.clone());
+
+		return app.merge(health_app);
+
+		async fn readyz(State(tile_sources): State<TileSources>) -> Response<Body> {
+			let tile_sources: Vec<TileSource> = tile_sources.read().unwrap().clone();
+
+			let mut ready = true;
+			let mut sources = Vec::new();
+			for tile_source in tile_sources.iter() {
+				let result = match tokio::time::timeout(PROBE_TIMEOUT, tile_source.probe()).await {
+					Ok(result) => result,
+					Err(_) => Err(anyhow::anyhow!("probe timed out after {PROBE_TIMEOUT:?}")),
+				};
+
+				let mut source = JsonObject::default();
+				source.set("id", JsonValue::from(&tile_source.id));
+				source.set("ready", JsonValue::from(result.is_ok()));
+				if let Err(err) = result {
+					log::warn!("tile source '{}' is not ready: {err:#}", tile_source.id);
+					source.set("error", JsonValue::from(format!("{err:#}")));
+					ready = false;
+				}
+				sources.push(JsonValue::Object(source));
+			}
+
+			let mut json = JsonObject::default();
+			json.set("status", JsonValue::from(if ready { "ready" } else { "not ready" }));
+			json.set("sources", JsonValue::from(sources));
+
+			let mut response = ok_json(&json.stringify());
+			if !ready {
+				*response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+			}
+			response
+		}
+	}
+
+	fn add_tile_sources_to_app(&self, app: Router) -> Router {
+		let mut tile_app = Router::new().route("/tiles/{*path}", get(serve_tile).post(serve_batch));
+		// sources with a custom url template can be served outside of "/tiles/"
+		let prefixes: Vec<String> = self
+			.tile_sources
+			.read()
+			.unwrap()
+			.iter()
+			.map(|source| source.prefix.as_string())
+			.filter(|prefix| !prefix.starts_with("/tiles/"))
+			.collect();
+		for prefix in prefixes {
+			tile_app = tile_app.route(&format!("{prefix}{{*path}}"), get(serve_tile).post(serve_batch));
+		}
+		let tile_app = tile_app.with_state((
+			self.tile_sources.clone(),
+			self.compressor.clone(),
+			self.public_url.clone(),
+		));
 
-			let tile_app = Router::new()
-				.route(&route, get(serve_tile))
-				.with_state((tile_source.clone(), self.use_best_compression));
+		return app.merge(tile_app);
 
-			app = app.merge(tile_app);
+		/// Answers a POST request to "/tiles/{id}/batch" with a JSON list of tile coordinates like
+		/// `[[z,x,y], …]` with a tar file containing the existing tiles as "{z}/{x}/{y}.{format}[.{compression}]".
+		async fn serve_batch(
+			uri: Uri,
+			State((tile_sources, _, _)): State<(TileSources, Arc<TileCompressor>, Option<String>)>,
+			body: Bytes,
+		) -> Response<Body> {
+			let path = Url::new(uri.path());
 
-			async fn serve_tile(
-				uri: Uri,
-				headers: HeaderMap,
-				State((tile_source, use_best_compression)): State<(TileSource, bool)>,
-			) -> Response<Body> {
-				let path = Url::new(uri.path());
+			log::debug!("handle batch request: {path}");
 
-				log::debug!("handle tile request: {path}");
+			let tile_source = tile_sources
+				.read()
+				.unwrap()
+				.iter()
+				.find(|source| path.starts_with(&source.prefix))
+				.cloned();
+			let Some(tile_source) = tile_source else {
+				log::warn!("send 404 for batch request: {path}");
+				return error_404();
+			};
+			if !path
+				.strip_prefix(&tile_source.prefix)
+				.is_ok_and(|url| url.as_vec() == ["batch"])
+			{
+				log::warn!("send 404 for batch request: {path}");
+				return error_404();
+			}
 
-				let mut target_compressions = get_encoding(headers);
-				if !use_best_compression {
-					target_compressions.set_fast_compression();
+			let result = async {
+				let coords = parse_batch_request(&body)?;
+				let tiles = tile_source.get_tiles(&coords).await?;
+				build_batch_tar(&tiles, &tile_source)
+			}
+			.await;
+
+			match result {
+				Ok(blob) => {
+					log::info!("send response for batch request: {path}");
+					Response::builder()
+						.status(200)
+						.header(CONTENT_TYPE, "application/x-tar")
+						.body(Body::from(blob.into_vec()))
+						.expect("should have build a body")
 				}
+				Err(err) => {
+					log::warn!("send 400 for batch request: {path}. Reason: {err}");
+					error_400()
+				}
+			}
+		}
+
+		async fn serve_tile(
+			method: Method,
+			uri: Uri,
+			headers: HeaderMap,
+			State((tile_sources, compressor, public_url)): State<(TileSources, Arc<TileCompressor>, Option<String>)>,
+		) -> Response<Body> {
+			let path = Url::new(uri.path());
 
-				let response = tile_source
-					.get_data(
-						&path
-							.strip_prefix(&tile_source.prefix)
-							.expect("should start with prefix"),
-						&target_compressions,
-					)
-					.await;
-
-				if let Ok(Some(response)) = response {
-					log::info!("send response for tile request: {path}");
-					ok_data(response, target_compressions)
-				} else if let Err(err) = response {
+			log::debug!("handle tile request: {path}");
+
+			let tile_source = tile_sources
+				.read()
+				.unwrap()
+				.iter()
+				.find(|source| path.starts_with(&source.prefix))
+				.cloned();
+			let Some(tile_source) = tile_source else {
+				log::warn!("send 404 for tile request: {path}");
+				return error_404();
+			};
+			let url = path
+				.strip_prefix(&tile_source.prefix)
+				.expect("should start with prefix");
+
+			let coord = match tile_source.get_coord(&url) {
+				Ok(coord) => coord,
+				Err(err) => {
 					log::warn!("send 400 for tile request: {path}. Reason: {err}");
-					error_400()
-				} else {
+					return error_400();
+				}
+			};
+
+			// custom templates can swap the placeholders, like "{z}/{y}/{x}",
+			// so tiles outside of the bbox pyramid are not read, but answered with 404
+			if let Some(coord) = &coord {
+				if tile_source.url_template.is_custom() && !tile_source.contains_coord(coord).await {
 					log::warn!("send 404 for tile request: {path}");
-					error_404()
+					return error_404();
 				}
 			}
+
+			// answer conditional and HEAD requests without reading the tile
+			let mut last_modified = None;
+			if let Some(coord) = &coord {
+				last_modified = tile_source.get_tile_mtime(coord).await.unwrap_or_else(|err| {
+					log::warn!("could not get modification time for tile request: {path}. Reason: {err}");
+					None
+				});
+
+				if let Some(last_modified) = last_modified {
+					if is_not_modified(&headers, last_modified) {
+						log::info!("send 304 for tile request: {path}");
+						return not_modified(last_modified);
+					}
+				}
+
+				if method == Method::HEAD {
+					return if let Ok(true) = tile_source.has_tile(coord).await {
+						log::info!("send head response for tile request: {path}");
+						ok_head(&tile_source.tile_mime, last_modified)
+					} else {
+						log::warn!("send 404 for tile request: {path}");
+						error_404()
+					};
+				}
+			}
+
+			let base_url = public_url.or_else(|| get_base_url(&headers, &uri));
+
+			let target_compressions = get_encoding(headers);
+
+			let response = match (tile_source.get_data(&url, &target_compressions).await, &base_url) {
+				(Ok(Some(result)), Some(base_url)) if coord.is_none() => {
+					Ok(Some(make_tilejson_urls_absolute(result, base_url)))
+				}
+				(response, _) => response,
+			};
+
+			let response = response.and_then(|response| {
+				response
+					.map(|response| compressor.compress(response, &target_compressions))
+					.transpose()
+			});
+
+			if let Ok(Some(response)) = response {
+				log::info!("send response for tile request: {path}");
+				let mut response = ok_encoded(response);
+				if let Some(last_modified) = last_modified {
+					response.headers_mut().insert(
+						LAST_MODIFIED,
+						HeaderValue::from_str(&fmt_http_date(last_modified)).expect("should be a valid header value"),
+					);
+				}
+				response
+			} else if let Err(err) = response {
+				log::warn!("send 400 for tile request: {path}. Reason: {err}");
+				error_400()
+			} else {
+				log::warn!("send 404 for tile request: {path}");
+				error_404()
+			}
 		}
+	}
+
+	/// Serves local `*.versatiles` and `*.pmtiles` containers as files at "/files/{id}.{extension}",
+	/// with support for range requests, so they can also be read by client-side readers.
+	fn add_container_files_to_app(&self, app: Router) -> Router {
+		let file_app = Router::new()
+			.route("/files/{name}", get(serve_file))
+			.with_state(self.tile_sources.clone());
+
+		return app.merge(file_app);
+
+		async fn serve_file(uri: Uri, headers: HeaderMap, State(tile_sources): State<TileSources>) -> Response<Body> {
+			let name = uri.path().trim_start_matches("/files/");
+
+			log::debug!("handle file request: {name}");
+
+			let path = name.rsplit_once('.').and_then(|(id, extension)| {
+				let tile_sources = tile_sources.read().unwrap();
+				let path = tile_sources.iter().find(|s| s.id == id)?.get_container_path()?;
+				(path.extension()? == extension).then_some(path)
+			});
+			let Some(path) = path else {
+				log::warn!("send 404 for file request: {name}");
+				return error_404();
+			};
+
+			let reader = match DataReaderFile::open(&path) {
+				Ok(reader) => reader,
+				Err(err) => {
+					log::warn!("send 404 for file request: {name}. Reason: {err}");
+					return error_404();
+				}
+			};
+			let size = reader.get_size();
+
+			let range = headers.get(RANGE).and_then(|value| value.to_str().ok());
+			let response = Response::builder()
+				.header(ACCEPT_RANGES, "bytes")
+				.header(CONTENT_TYPE, "application/octet-stream");
+
+			let (response, range) = match parse_range_header(range, size) {
+				RangeRequest::Full => (response.status(200), ByteRange::new(0, size)),
+				RangeRequest::Partial(range) => (
+					response.status(206).header(
+						CONTENT_RANGE,
+						format!("bytes {}-{}/{size}", range.offset, range.offset + range.length - 1),
+					),
+					range,
+				),
+				RangeRequest::Unsatisfiable => {
+					log::warn!("send 416 for file request: {name}");
+					return response
+						.status(416)
+						.header(CONTENT_RANGE, format!("bytes */{size}"))
+						.body(Body::empty())
+						.expect("should have build a body");
+				}
+			};
 
-		app
+			log::info!("send response for file request: {name} {range:?}");
+			response
+				.header(CONTENT_LENGTH, range.length)
+				.body(stream_file_range(reader, range))
+				.expect("should have build a body")
+		}
 	}
 
 	fn add_static_sources_to_app(&self, app: Router) -> Router {
-		let static_app = Router::new()
-			.fallback(get(serve_static))
-			.with_state((self.static_sources.clone(), self.use_best_compression));
+		let static_app = Router::new().fallback(get(serve_static)).with_state((
+			self.static_sources.clone(),
+			Arc::new(StaticCompression::new()),
+			self.public_url.clone(),
+		));
 
 		return app.merge(static_app);
 
 		async fn serve_static(
 			uri: Uri,
 			headers: HeaderMap,
-			State((sources, use_best_compression)): State<(Vec<StaticSource>, bool)>,
+			State((sources, compression, public_url)): State<(Vec<StaticSource>, Arc<StaticCompression>, Option<String>)>,
 		) -> Response<Body> {
 			let mut url = Url::new(uri.path());
 
@@ -191,13 +659,23 @@ impl TileServer {
 				url.push("index.html");
 			}
 
-			let mut target_compressions = get_encoding(headers);
-			if !use_best_compression {
-				target_compressions.set_fast_compression();
-			}
+			let base_url = public_url.or_else(|| get_base_url(&headers, &uri));
+
+			// static files are always compressed as good as possible, because the results are cached
+			let target_compressions = get_encoding(headers);
 
 			for source in sources.iter() {
-				if let Some(result) = source.get_data(&url, &target_compressions) {
+				if let Some(mut result) = source.get_data(&url, &target_compressions) {
+					if let Some(base_url) = &base_url {
+						result = make_style_urls_absolute(result, base_url);
+					}
+					let result = match compression.compress(result, &target_compressions) {
+						Ok(result) => result,
+						Err(err) => {
+							log::warn!("send 400 to static request: {url}. Reason: {err}");
+							return error_400();
+						}
+					};
 					log::info!("send response to static request: {url}");
 					return ok_data(result, target_compressions);
 				}
@@ -211,24 +689,57 @@ impl TileServer {
 	async fn add_api_to_app(&self, app: Router) -> Result<Router> {
 		let mut api_app = Router::new();
 
-		let tiles_index_json: String = format!(
-			"[{}]",
-			self
-				.tile_sources
-				.iter()
-				.map(|s| format!("\"{}\"", s.id))
-				.collect::<Vec<String>>()
-				.join(","),
+		let tile_sources = self.tile_sources.clone();
+		api_app = api_app.route(
+			"/tiles/index.json",
+			get(|| async move {
+				let ids: Vec<String> = tile_sources.read().unwrap().iter().map(|s| s.id.clone()).collect();
+				ok_json(&JsonValue::from(ids).stringify())
+			}),
 		);
 
-		api_app = api_app.route("/tiles/index.json", get(|| async move { ok_json(&tiles_index_json) }));
+		let search_app = Router::new()
+			.route("/search", get(search))
+			.with_state(Arc::new(self.search_indexes.clone()));
+		api_app = api_app.merge(search_app);
+
+		return Ok(app.merge(api_app));
+
+		async fn search(uri: Uri, State(indexes): State<Arc<SearchIndexes>>) -> Response<Body> {
+			let query = uri.query().unwrap_or("");
+			let Some(text) = get_query_parameter(query, "q") else {
+				log::warn!("send 400 for search request without \"q\"");
+				return error_400();
+			};
+			let limit = match get_query_parameter(query, "limit") {
+				None => DEFAULT_SEARCH_LIMIT,
+				Some(limit) => match limit.parse::<usize>() {
+					Ok(limit) => limit.min(MAX_SEARCH_LIMIT),
+					Err(_) => return error_400(),
+				},
+			};
+
+			let mut results = Vec::new();
+			for (id, index) in indexes.iter() {
+				for entry in index.search(&text, limit) {
+					let Ok(mut json) = index.entry_to_json(entry) else {
+						continue;
+					};
+					json.set("source", JsonValue::from(id));
+					results.push(JsonValue::Object(json));
+				}
+			}
+			results.truncate(limit);
 
-		Ok(app.merge(api_app))
+			log::info!("send {} results for search request: {text}", results.len());
+			ok_json(&JsonValue::from(results).stringify())
+		}
 	}
 
 	pub async fn get_url_mapping(&self) -> Vec<(String, String)> {
+		let tile_sources = self.tile_sources.read().unwrap().clone();
 		let mut result = Vec::new();
-		for tile_source in self.tile_sources.iter() {
+		for tile_source in tile_sources.iter() {
 			let id = tile_source.get_source_name().await;
 			result.push((tile_source.prefix.as_string(), id.to_owned()))
 		}
@@ -236,10 +747,151 @@ impl TileServer {
 	}
 }
 
+/// Opens a container with the source opener.
+async fn open_reader(opener: &Option<SourceOpener>, url: &str) -> Result<Box<dyn TilesReaderTrait>> {
+	let Some(opener) = opener else {
+		bail!("opening containers is not supported by this server");
+	};
+	opener(url.to_owned())
+		.await
+		.with_context(|| format!("opening container '{url}'"))
+}
+
+/// Opens a container with `opener` as tile source `id`.
+pub(super) async fn open_tile_source(opener: &Option<SourceOpener>, id: &str, url: &str) -> Result<TileSource> {
+	let mut source = TileSource::from(open_reader(opener, url).await?, id)?;
+	source.url = Some(url.to_owned());
+	Ok(source)
+}
+
+/// Opens all tile sources with a path or URL again and replaces them, see [`TileServer::reload_tile_sources`].
+pub(super) async fn reopen_tile_sources(tile_sources: &TileSources, opener: &Option<SourceOpener>) -> Result<usize> {
+	let current: Vec<TileSource> = tile_sources.read().unwrap().clone();
+
+	let mut reopened = Vec::new();
+	for tile_source in current.iter() {
+		if let Some(url) = &tile_source.url {
+			let mut source = open_tile_source(opener, &tile_source.id, url).await?;
+			source.set_url_template(tile_source.url_template.clone());
+			reopened.push(source);
+		}
+	}
+
+	// sources that have been removed in the meantime stay removed
+	let mut count = 0;
+	let mut tile_sources = tile_sources.write().unwrap();
+	for source in reopened {
+		if let Some(index) = tile_sources.iter().position(|other| other.id == source.id) {
+			tile_sources[index] = source;
+			count += 1;
+		}
+	}
+	Ok(count)
+}
+
+/// Adds a tile source. If `replace` is set, a source with the same id is replaced and keeps its url template.
+pub(super) fn insert_tile_source(
+	tile_sources: &mut Vec<TileSource>,
+	mut source: TileSource,
+	replace: bool,
+) -> Result<()> {
+	if replace {
+		if let Some(index) = tile_sources.iter().position(|other| other.id == source.id) {
+			source.set_url_template(tile_sources[index].url_template.clone());
+			tile_sources[index] = source;
+			return Ok(());
+		}
+	}
+
+	let url_prefix = &source.prefix;
+	for other_tile_source in tile_sources.iter() {
+		let other_prefix = &other_tile_source.prefix;
+		if other_prefix.starts_with(url_prefix) || url_prefix.starts_with(other_prefix) {
+			bail!("multiple sources with the prefix '{url_prefix}' and '{other_prefix}' are defined");
+		};
+	}
+
+	tile_sources.push(source);
+	Ok(())
+}
+
+/// Streams a range of a file in chunks, so large files are not loaded into memory.
+fn stream_file_range(reader: Box<DataReaderFile>, range: ByteRange) -> Body {
+	Body::from_stream(stream::unfold((reader, range), |(reader, mut range)| async move {
+		if range.length == 0 {
+			return None;
+		}
+		let chunk = ByteRange::new(range.offset, range.length.min(FILE_CHUNK_SIZE));
+		range.offset += chunk.length;
+		range.length -= chunk.length;
+
+		let result = reader.read_range(&chunk).await.map(Blob::into_vec);
+		if result.is_err() {
+			range.length = 0;
+		}
+		Some((result, (reader, range)))
+	}))
+}
+
+/// Wraps the handling of every request in a tracing span.
+async fn trace_request(request: Request, next: Next) -> Response<Body> {
+	let span = info_span!(
+		"request",
+		method = %request.method(),
+		path = %request.uri().path(),
+		status = field::Empty
+	);
+	let response = next.run(request).instrument(span.clone()).await;
+	span.record("status", response.status().as_u16());
+	response
+}
+
+/// Parses the body of a batch request: a JSON list of tile coordinates like `[[z,x,y], …]`.
+fn parse_batch_request(body: &[u8]) -> Result<Vec<TileCoord3>> {
+	let json = JsonValue::parse_str(std::str::from_utf8(body)?)?;
+	let list = json
+		.as_array()
+		.context("batch request must be a list of tile coordinates")?;
+	ensure!(
+		list.0.len() <= MAX_BATCH_SIZE,
+		"batch request contains {} tiles, but only {MAX_BATCH_SIZE} are allowed",
+		list.0.len()
+	);
+	list
+		.0
+		.iter()
+		.map(|entry| {
+			let [z, x, y] = entry.as_array()?.as_number_array::<u32, 3>()?;
+			let coord = TileCoord3::new(x, y, u8::try_from(z)?)?;
+			ensure!(coord.is_valid(), "tile {z}/{x}/{y} is outside of the tile grid");
+			Ok(coord)
+		})
+		.collect::<Result<Vec<_>>>()
+		.context("tile coordinates must be lists like [z,x,y]")
+}
+
+/// Packs the tiles of a batch request into a tar file.
+fn build_batch_tar(tiles: &[(TileCoord3, Blob)], tile_source: &TileSource) -> Result<Blob> {
+	let extension = format!(
+		"{}{}",
+		tile_source.tile_format.extension(),
+		tile_source.compression.extension()
+	);
+	let mut builder = tar::Builder::new(Vec::new());
+	for (coord, blob) in tiles {
+		let mut header = tar::Header::new_gnu();
+		header.set_size(blob.len());
+		header.set_mode(0o644);
+		header.set_cksum();
+		let path = format!("{}/{}/{}{extension}", coord.z, coord.x, coord.y);
+		builder.append_data(&mut header, path, blob.as_slice())?;
+	}
+	Ok(Blob::from(builder.into_inner()?))
+}
+
 fn error_400() -> Response<Body> {
 	Response::builder()
 		.status(400)
-		.header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
 		.body(Body::from("Bad Request"))
 		.expect("should have build a body")
 }
@@ -247,36 +899,80 @@ fn error_400() -> Response<Body> {
 fn error_404() -> Response<Body> {
 	Response::builder()
 		.status(404)
-		.header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
 		.body(Body::from("Not Found"))
 		.expect("should have build a body")
 }
 
-fn ok_data(result: SourceResponse, mut target_compressions: TargetCompression) -> Response<Body> {
-	if matches!(
-		result.mime.as_str(),
-		"image/png" | "image/jpeg" | "image/webp" | "image/avif"
-	) {
-		target_compressions.set_incompressible();
-	}
+/// Checks whether the "If-Modified-Since" header of a request is not older than `last_modified`.
+/// HTTP dates have a precision of one second, so fractions of seconds are ignored.
+fn is_not_modified(headers: &HeaderMap, last_modified: SystemTime) -> bool {
+	let Some(since) = headers
+		.get(IF_MODIFIED_SINCE)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| parse_http_date(value).ok())
+	else {
+		return false;
+	};
+	let as_secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs());
+	as_secs(last_modified) <= as_secs(since)
+}
 
+fn not_modified(last_modified: SystemTime) -> Response<Body> {
+	Response::builder()
+		.status(304)
+		.header(LAST_MODIFIED, fmt_http_date(last_modified))
+		.header(CACHE_CONTROL, "public, max-age=2419200, no-transform")
+		.header(VARY, "accept-encoding")
+		.body(Body::empty())
+		.expect("should have build a body")
+}
+
+/// Answers a HEAD request for a tile with the headers of a GET request, except for the content length
+/// and encoding, which are only known after reading the tile.
+fn ok_head(mime: &str, last_modified: Option<SystemTime>) -> Response<Body> {
 	let mut response = Response::builder()
 		.status(200)
-		.header(CONTENT_TYPE, result.mime)
+		.header(CONTENT_TYPE, mime)
 		.header(CACHE_CONTROL, "public, max-age=2419200, no-transform")
-		.header(VARY, "accept-encoding")
-		.header(ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+		.header(VARY, "accept-encoding");
+	if let Some(last_modified) = last_modified {
+		response = response.header(LAST_MODIFIED, fmt_http_date(last_modified));
+	}
+	response.body(Body::empty()).expect("should have build a body")
+}
+
+fn ok_data(result: SourceResponse, mut target_compressions: TargetCompression) -> Response<Body> {
+	if is_incompressible_mime(&result.mime) {
+		target_compressions.set_incompressible();
+	}
 
 	log::trace!(
 		"optimize_compression from \"{}\" to {:?}",
 		result.compression,
 		target_compressions
 	);
-	let (blob, compression) = optimize_compression(result.blob, &result.compression, &target_compressions)
+	let span = info_span!("recompress", from = %result.compression);
+	let (blob, compression) = span
+		.in_scope(|| optimize_compression(result.blob, &result.compression, &target_compressions))
 		.expect("should have optimized compression");
 
+	ok_encoded(SourceResponse {
+		blob,
+		compression,
+		mime: result.mime,
+	})
+}
+
+/// Sends the data in the encoding it has.
+fn ok_encoded(result: SourceResponse) -> Response<Body> {
+	let mut response = Response::builder()
+		.status(200)
+		.header(CONTENT_TYPE, result.mime)
+		.header(CACHE_CONTROL, "public, max-age=2419200, no-transform")
+		.header(VARY, "accept-encoding");
+
 	use TileCompression::*;
-	match compression {
+	match result.compression {
 		Uncompressed => {}
 		Gzip => response = response.header(CONTENT_ENCODING, "gzip"),
 		Brotli => response = response.header(CONTENT_ENCODING, "br"),
@@ -285,11 +981,11 @@ fn ok_data(result: SourceResponse, mut target_compressions: TargetCompression) -
 	log::trace!("send repsonse using headers: {:?}", response.headers_ref());
 
 	response
-		.body(Body::from(blob.into_vec()))
+		.body(Body::from(result.blob.into_vec()))
 		.expect("should have build a body")
 }
 
-fn ok_json(message: &str) -> Response<Body> {
+pub(super) fn ok_json(message: &str) -> Response<Body> {
 	ok_data(
 		SourceResponse {
 			blob: Blob::from(message),
@@ -300,6 +996,92 @@ fn ok_json(message: &str) -> Response<Body> {
 	)
 }
 
+/// Makes the server-relative URLs of a style absolute, because MapLibre requires absolute URLs.
+/// Other responses are returned unchanged.
+fn make_style_urls_absolute(result: SourceResponse, base_url: &str) -> SourceResponse {
+	if result.mime != "application/json" {
+		return result;
+	}
+	let Ok(blob) = decompress(result.blob.clone(), &result.compression) else {
+		return result;
+	};
+	let Ok(JsonValue::Object(mut style)) = JsonValue::parse_blob(&blob) else {
+		return result;
+	};
+	if !is_style(&style) {
+		return result;
+	}
+
+	let rewritten = rewrite_style_urls(&mut style, |_, url| {
+		Ok((url.starts_with('/') && !url.starts_with("//")).then(|| format!("{base_url}{url}")))
+	});
+	if rewritten.is_err() {
+		return result;
+	}
+
+	SourceResponse {
+		blob: Blob::from(style.stringify()),
+		compression: TileCompression::Uncompressed,
+		mime: result.mime,
+	}
+}
+
+/// Makes the server-relative "tiles" URLs of a TileJSON absolute, so that clients behind reverse proxies
+/// request the tiles from the right host. Other responses are returned unchanged.
+fn make_tilejson_urls_absolute(result: SourceResponse, base_url: &str) -> SourceResponse {
+	if result.mime != "application/json" {
+		return result;
+	}
+	let Ok(blob) = decompress(result.blob.clone(), &result.compression) else {
+		return result;
+	};
+	let Ok(JsonValue::Object(mut tilejson)) = JsonValue::parse_blob(&blob) else {
+		return result;
+	};
+	let Ok(Some(tiles)) = tilejson.get_string_vec("tiles") else {
+		return result;
+	};
+
+	let tiles: Vec<String> = tiles
+		.into_iter()
+		.map(|url| {
+			if url.starts_with('/') && !url.starts_with("//") {
+				format!("{base_url}{url}")
+			} else {
+				url
+			}
+		})
+		.collect();
+	tilejson.set("tiles", tiles);
+
+	SourceResponse {
+		blob: Blob::from(tilejson.stringify()),
+		compression: TileCompression::Uncompressed,
+		mime: result.mime,
+	}
+}
+
+/// Returns the URL of the server, as seen by the client, e.g. "http://localhost:8080".
+/// Headers of reverse proxies ("X-Forwarded-Proto", "X-Forwarded-Host") are respected.
+/// HTTP/2 requests have no "Host" header, but the scheme and the authority are part of the URI.
+fn get_base_url(headers: &HeaderMap, uri: &Uri) -> Option<String> {
+	let get_header = |name: &str| {
+		headers
+			.get(name)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.split(',').next())
+			.map(|value| value.trim())
+	};
+
+	let host = get_header("x-forwarded-host")
+		.or_else(|| get_header(HOST.as_str()))
+		.or_else(|| uri.authority().map(|authority| authority.as_str()))?;
+	let protocol = get_header("x-forwarded-proto")
+		.or_else(|| uri.scheme_str())
+		.unwrap_or("http");
+	Some(format!("{protocol}://{host}"))
+}
+
 fn get_encoding(headers: HeaderMap) -> TargetCompression {
 	let mut encoding_set: TargetCompression = TargetCompression::from_none();
 	let encoding_option = headers.get(ACCEPT_ENCODING);
@@ -318,6 +1100,7 @@ fn get_encoding(headers: HeaderMap) -> TargetCompression {
 
 #[cfg(test)]
 mod tests {
+	use super::super::connection::write_test_certificate;
 	use super::*;
 	use axum::http::{header::ACCEPT_ENCODING, HeaderMap};
 	use enumset::{enum_set, EnumSet};
@@ -380,7 +1163,7 @@ mod tests {
 
 		assert_eq!(get("tiles/cheese/brum.json").await, "Not Found");
 
-		let meta = "{\"bounds\":[-180,-79.17133464081944,45,66.51326044311185],\"format\":\"pbf\",\"maxzoom\":3,\"minzoom\":2,\"name\":\"cheese\",\"tilejson\":\"3.0.0\",\"tiles\":[\"/tiles/cheese/{z}/{x}/{y}\"],\"type\":\"vector\"}";
+		let meta = "{\"bounds\":[-180,-79.17133464081944,45,66.51326044311185],\"format\":\"pbf\",\"maxzoom\":3,\"minzoom\":2,\"name\":\"cheese\",\"tilejson\":\"3.0.0\",\"tiles\":[\"http://127.0.0.1:50001/tiles/cheese/{z}/{x}/{y}\"],\"type\":\"vector\"}";
 		assert_eq!(get("tiles/cheese/meta.json").await, meta);
 		assert_eq!(get("tiles/cheese/tiles.json").await, meta);
 		assert!(get("tiles/cheese/0/0/0.png").await.starts_with("\u{1a}4\n\u{5}ocean"));
@@ -390,6 +1173,638 @@ mod tests {
 		server.stop().await;
 	}
 
+	#[test]
+	fn test_get_base_url() {
+		let test = |headers: &[(&'static str, &'static str)]| {
+			let mut map = HeaderMap::new();
+			for (key, value) in headers {
+				map.insert(*key, value.parse().unwrap());
+			}
+			get_base_url(&map, &Uri::from_static("/tiles/osm/meta.json"))
+		};
+
+		assert_eq!(test(&[]), None);
+		assert_eq!(test(&[("host", "localhost:8080")]).unwrap(), "http://localhost:8080");
+		assert_eq!(
+			test(&[
+				("host", "localhost:8080"),
+				("x-forwarded-host", "tiles.example.org, proxy"),
+				("x-forwarded-proto", "https")
+			])
+			.unwrap(),
+			"https://tiles.example.org"
+		);
+
+		let uri = Uri::from_static("https://localhost:8443/tiles/osm/meta.json");
+		assert_eq!(get_base_url(&HeaderMap::new(), &uri).unwrap(), "https://localhost:8443");
+	}
+
+	#[test]
+	fn test_make_style_urls_absolute() {
+		let response = |json: &str, mime: &str| SourceResponse {
+			blob: Blob::from(json),
+			compression: Uncompressed,
+			mime: mime.to_owned(),
+		};
+		let style = r#"{"version":8,"layers":[],"sources":{"osm":{"type":"vector","url":"/tiles/osm/tiles.json"}},"sprite":"//cdn.example.org/sprite"}"#;
+
+		let result = make_style_urls_absolute(response(style, "application/json"), "https://example.org");
+		assert_eq!(
+			result.blob.as_str(),
+			"{\"layers\":[],\"sources\":{\"osm\":{\"type\":\"vector\",\"url\":\"https://example.org/tiles/osm/tiles.json\"}},\"sprite\":\"//cdn.example.org/sprite\",\"version\":8}"
+		);
+
+		// other JSON files and other types are not changed
+		let result = make_style_urls_absolute(response(style, "text/plain"), "https://example.org");
+		assert_eq!(result.blob.as_str(), style);
+		let json = r#"{"url":"/tiles/osm/tiles.json"}"#;
+		let result = make_style_urls_absolute(response(json, "application/json"), "https://example.org");
+		assert_eq!(result.blob.as_str(), json);
+	}
+
+	#[test]
+	fn test_make_tilejson_urls_absolute() {
+		let response = |json: &str| SourceResponse {
+			blob: Blob::from(json),
+			compression: Uncompressed,
+			mime: String::from("application/json"),
+		};
+
+		let tilejson = r#"{"tilejson":"3.0.0","tiles":["/tiles/osm/{z}/{x}/{y}","https://other.org/{z}/{x}/{y}"]}"#;
+		let result = make_tilejson_urls_absolute(response(tilejson), "https://example.org");
+		assert_eq!(
+			result.blob.as_str(),
+			"{\"tilejson\":\"3.0.0\",\"tiles\":[\"https://example.org/tiles/osm/{z}/{x}/{y}\",\"https://other.org/{z}/{x}/{y}\"]}"
+		);
+
+		// other JSON files are not changed
+		let json = r#"{"url":"/tiles/osm/tiles.json"}"#;
+		let result = make_tilejson_urls_absolute(response(json), "https://example.org");
+		assert_eq!(result.blob.as_str(), json);
+	}
+
+	#[tokio::test]
+	async fn serve_style() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(
+			dir.path().join("style.json"),
+			r#"{"version":8,"layers":[],"sources":{},"glyphs":"/glyphs/{fontstack}/{range}.pbf"}"#,
+		)
+		.unwrap();
+
+		let mut server = TileServer::new(IP, 50006, true, true);
+		server.add_static_source(dir.path(), Url::new("")).unwrap();
+		server.start().await.unwrap();
+		let style = reqwest::get(format!("http://{IP}:50006/style.json"))
+			.await
+			.unwrap()
+			.text()
+			.await
+			.unwrap();
+		assert_eq!(
+			style,
+			"{\"glyphs\":\"http://127.0.0.1:50006/glyphs/{fontstack}/{range}.pbf\",\"layers\":[],\"sources\":{},\"version\":8}"
+		);
+		server.stop().await;
+
+		let mut server = TileServer::new(IP, 50007, true, true);
+		server.add_static_source(dir.path(), Url::new("")).unwrap();
+		server.set_public_url("https://tiles.example.org/");
+		server.start().await.unwrap();
+		let style = reqwest::get(format!("http://{IP}:50007/style.json"))
+			.await
+			.unwrap()
+			.text()
+			.await
+			.unwrap();
+		assert!(
+			style.starts_with("{\"glyphs\":\"https://tiles.example.org/glyphs/"),
+			"{style}"
+		);
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn search() -> Result<()> {
+		let dir = assert_fs::TempDir::new()?;
+		crate::tools::search::tests::make_test_tiles(dir.path())?;
+		let reader = versatiles_container::get_reader(dir.path().to_str().unwrap()).await?;
+		let index = SearchIndex::build(reader.as_ref(), "name", 2).await?;
+
+		let mut server = TileServer::new(IP, 50008, true, true);
+		server.add_search_index("places", index);
+		server.start().await?;
+
+		let get = |query: &str| reqwest::get(format!("http://{IP}:50008/search?{query}"));
+
+		let results = JsonValue::parse_str(&get("q=berlin&limit=2").await?.text().await?)?;
+		let results = results.as_array()?;
+		assert_eq!(results.0.len(), 2);
+		let result = results.0[0].as_object()?;
+		assert_eq!(result.get_string("name")?.unwrap(), "Berlin");
+		assert_eq!(result.get_string("source")?.unwrap(), "places");
+
+		assert_eq!(
+			get("q=Berlin%2DMitte").await?.text().await?.matches("\"name\"").count(),
+			1
+		);
+		assert_eq!(get("q=rome").await?.text().await?, "[]");
+		assert_eq!(get("limit=2").await?.status(), 400);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn container_files() -> Result<()> {
+		let mut server = TileServer::new(IP, 50012, true, true);
+		server.set_source_opener(Arc::new(
+			|url: String| -> BoxFuture<'static, Result<Box<dyn TilesReaderTrait>>> {
+				Box::pin(async move { versatiles_container::get_reader(&url).await })
+			},
+		));
+		server
+			.add_tile_source_url("berlin", "../testdata/berlin.pmtiles")
+			.await?;
+		server
+			.add_tile_source_url("mbtiles", "../testdata/berlin.mbtiles")
+			.await?;
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let get = |name: &str, range: Option<&str>| {
+			let mut request = client.get(format!("http://{IP}:50012/files/{name}"));
+			if let Some(range) = range {
+				request = request.header(RANGE, range);
+			}
+			request.send()
+		};
+
+		let response = get("berlin.pmtiles", None).await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()[ACCEPT_RANGES], "bytes");
+		assert_eq!(response.bytes().await?.len(), 25871996);
+
+		let response = get("berlin.pmtiles", Some("bytes=0-6")).await?;
+		assert_eq!(response.status(), 206);
+		assert_eq!(response.headers()[CONTENT_RANGE], "bytes 0-6/25871996");
+		assert_eq!(response.text().await?, "PMTiles");
+
+		let response = get("berlin.pmtiles", Some("bytes=-4")).await?;
+		assert_eq!(response.headers()[CONTENT_RANGE], "bytes 25871992-25871995/25871996");
+		assert_eq!(response.bytes().await?.len(), 4);
+
+		let response = get("berlin.pmtiles", Some("bytes=25871996-")).await?;
+		assert_eq!(response.status(), 416);
+		assert_eq!(response.headers()[CONTENT_RANGE], "bytes */25871996");
+
+		assert_eq!(get("berlin.versatiles", None).await?.status(), 404);
+		assert_eq!(get("mbtiles.mbtiles", None).await?.status(), 404);
+		assert_eq!(get("unknown.pmtiles", None).await?.status(), 404);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn rate_limit() -> Result<()> {
+		let mut server = TileServer::new(IP, 50013, true, true);
+		server.set_rate_limit(RateLimit {
+			requests_per_second: 0.1,
+			burst: 2,
+			api_keys: vec![String::from("cheese"), String::from("bread")],
+		});
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let get = |api_key: Option<&str>| {
+			let mut request = client.get(format!("http://{IP}:50013/status"));
+			if let Some(api_key) = api_key {
+				request = request.header("X-API-Key", api_key);
+			}
+			request.send()
+		};
+
+		assert_eq!(get(None).await?.status(), 200);
+		assert_eq!(get(None).await?.status(), 200);
+		let response = get(None).await?;
+		assert_eq!(response.status(), 429);
+		assert_eq!(response.headers()["retry-after"], "10");
+
+		// API keys have their own limits
+		assert_eq!(get(Some("cheese")).await?.status(), 200);
+		assert_eq!(get(Some("cheese")).await?.status(), 200);
+		assert_eq!(get(Some("cheese")).await?.status(), 429);
+		assert_eq!(get(Some("bread")).await?.status(), 200);
+
+		// unknown API keys are limited by IP address
+		assert_eq!(get(Some("butter")).await?.status(), 429);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn reload_and_shutdown() -> Result<()> {
+		let mut server = TileServer::new(IP, 50014, true, true);
+		server.set_source_opener(Arc::new(
+			|url: String| -> BoxFuture<'static, Result<Box<dyn TilesReaderTrait>>> {
+				Box::pin(async move {
+					anyhow::ensure!(url != "broken", "broken container");
+					Ok(MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?.boxed())
+				})
+			},
+		));
+		server.add_tile_source_url("cheese", "cheese.pbf").await?;
+		server.add_tile_source(
+			"bread",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed(),
+		)?;
+		server.start().await?;
+
+		assert_eq!(server.reload_tile_sources().await?, 1);
+
+		server.tile_sources.write().unwrap()[0].url = Some(String::from("broken"));
+		assert!(server.reload_tile_sources().await.is_err());
+		assert_eq!(server.tile_sources.read().unwrap().len(), 2);
+
+		let url = format!("http://{IP}:50014/status");
+		assert_eq!(reqwest::get(&url).await?.text().await?, "ready!");
+		server.shutdown(Duration::from_secs(5)).await;
+		assert!(server.server_tasks.is_empty());
+		assert!(reqwest::get(&url).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn multiple_listen_addresses() -> Result<()> {
+		let dir = assert_fs::TempDir::new()?;
+		let socket = dir.path().join("versatiles.sock");
+
+		let mut server = TileServer::new(IP, 0, true, true);
+		server.add_listen_address("127.0.0.1:50015".parse()?);
+		server.add_listen_address("127.0.0.1:50016".parse()?);
+		#[cfg(unix)]
+		server.add_listen_address(format!("unix:{}", socket.display()).parse()?);
+		server.start().await?;
+
+		assert_eq!(
+			reqwest::get(format!("http://{IP}:50015/status")).await?.text().await?,
+			"ready!"
+		);
+		assert_eq!(
+			reqwest::get(format!("http://{IP}:50016/status")).await?.text().await?,
+			"ready!"
+		);
+
+		#[cfg(unix)]
+		{
+			use std::{
+				io::{Read, Write},
+				os::unix::net::UnixStream,
+			};
+
+			let response = tokio::task::spawn_blocking(move || -> Result<String> {
+				let mut stream = UnixStream::connect(socket)?;
+				stream.write_all(b"GET /status HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")?;
+				let mut response = String::new();
+				stream.read_to_string(&mut response)?;
+				Ok(response)
+			})
+			.await??;
+			assert!(response.starts_with("HTTP/1.1 200 OK"));
+			assert!(response.ends_with("ready!"));
+		}
+
+		server.shutdown(Duration::from_secs(5)).await;
+		assert!(reqwest::get(format!("http://{IP}:50016/status")).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn health_and_readiness() -> Result<()> {
+		use async_trait::async_trait;
+		use versatiles_core::{tilejson::TileJSON, types::*};
+
+		#[derive(Debug)]
+		struct BrokenReader {
+			parameters: TilesReaderParameters,
+			tilejson: TileJSON,
+		}
+
+		#[async_trait]
+		impl TilesReaderTrait for BrokenReader {
+			fn get_source_name(&self) -> &str {
+				"broken"
+			}
+			fn get_container_name(&self) -> &str {
+				"broken"
+			}
+			fn get_parameters(&self) -> &TilesReaderParameters {
+				&self.parameters
+			}
+			fn override_compression(&mut self, _tile_compression: TileCompression) {}
+			fn get_tilejson(&self) -> &TileJSON {
+				&self.tilejson
+			}
+			async fn get_tile_data(&self, _coord: &TileCoord3) -> Result<Option<Blob>> {
+				bail!("disk error")
+			}
+		}
+
+		let mut server = TileServer::new(IP, 50017, true, true);
+		server.add_tile_source(
+			"cheese",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?.boxed(),
+		)?;
+		server.start().await?;
+
+		let get = |path: &str| reqwest::get(format!("http://{IP}:50017/{path}"));
+
+		assert_eq!(get("healthz").await?.text().await?, "{\"status\":\"ok\"}");
+
+		let response = get("readyz").await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			response.text().await?,
+			"{\"sources\":[{\"id\":\"cheese\",\"ready\":true}],\"status\":\"ready\"}"
+		);
+
+		let broken = BrokenReader {
+			parameters: TilesReaderParameters::new(TileFormat::PNG, Uncompressed, TileBBoxPyramid::new_full(2)),
+			tilejson: TileJSON::default(),
+		};
+		insert_tile_source(
+			&mut server.tile_sources.write().unwrap(),
+			TileSource::from(Box::new(broken), "broken")?,
+			false,
+		)?;
+
+		let response = get("readyz").await?;
+		assert_eq!(response.status(), 503);
+		let json = JsonObject::parse_str(&response.text().await?)?;
+		assert_eq!(json.get_string("status")?.unwrap(), "not ready");
+		assert_eq!(
+			json.get("sources").unwrap().stringify(),
+			"[{\"id\":\"cheese\",\"ready\":true},{\"error\":\"disk error\",\"id\":\"broken\",\"ready\":false}]"
+		);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn cors() -> Result<()> {
+		let mut server = TileServer::new(IP, 50018, true, true);
+		server.add_tile_source(
+			"cheese",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?.boxed(),
+		)?;
+		server.set_cors_rules(vec![
+			CorsRule::allow_all(),
+			CorsRule {
+				path: String::from("/tiles/"),
+				origins: vec![String::from("https://*.example.org")],
+				headers: vec![String::from("X-API-Key")],
+				max_age: Some(600),
+			},
+		]);
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let request = |method: reqwest::Method, path: &str, origin: &str| {
+			client
+				.request(method, format!("http://{IP}:50018/{path}"))
+				.header("Origin", origin)
+		};
+
+		let response = request(reqwest::Method::GET, "status", "https://other.org").send().await?;
+		assert_eq!(response.headers()["access-control-allow-origin"], "*");
+
+		let response = request(reqwest::Method::GET, "tiles/cheese/0/0/0", "https://maps.example.org")
+			.send()
+			.await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			response.headers()["access-control-allow-origin"],
+			"https://maps.example.org"
+		);
+
+		let response = request(reqwest::Method::GET, "tiles/cheese/0/0/0", "https://other.org")
+			.send()
+			.await?;
+		assert_eq!(response.status(), 200);
+		assert!(response.headers().get("access-control-allow-origin").is_none());
+
+		let response = request(reqwest::Method::OPTIONS, "tiles/cheese/0/0/0", "https://maps.example.org")
+			.header("Access-Control-Request-Method", "GET")
+			.send()
+			.await?;
+		assert_eq!(response.status(), 204);
+		assert_eq!(response.headers()["access-control-allow-headers"], "X-API-Key");
+		assert_eq!(response.headers()["access-control-max-age"], "600");
+
+		let response = request(reqwest::Method::OPTIONS, "tiles/cheese/0/0/0", "https://other.org")
+			.header("Access-Control-Request-Method", "GET")
+			.send()
+			.await?;
+		assert_eq!(response.status(), 403);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn head_and_conditional_requests() -> Result<()> {
+		use assert_fs::{prelude::*, TempDir};
+		use versatiles_container::DirectoryTilesReader;
+
+		let dir = TempDir::new()?;
+		dir.child("3/2/1.png").write_str("tile at 3/2/1")?;
+
+		let mut server = TileServer::new(IP, 50019, true, true);
+		server.add_tile_source("cheese", Box::new(DirectoryTilesReader::open_path(&dir)?))?;
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let url = |path: &str| format!("http://{IP}:50019/tiles/cheese/{path}");
+
+		let response = client.head(url("3/2/1.png")).send().await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "image/png");
+		let last_modified = response.headers()["last-modified"].to_str()?.to_owned();
+
+		assert_eq!(client.head(url("3/2/2.png")).send().await?.status(), 404);
+		assert_eq!(client.head(url("x/2/2.png")).send().await?.status(), 400);
+
+		let response = client.get(url("3/2/1.png")).send().await?;
+		assert_eq!(response.headers()["last-modified"], last_modified.as_str());
+		assert_eq!(response.text().await?, "tile at 3/2/1");
+
+		let response = client
+			.get(url("3/2/1.png"))
+			.header("If-Modified-Since", &last_modified)
+			.send()
+			.await?;
+		assert_eq!(response.status(), 304);
+
+		let response = client
+			.get(url("3/2/1.png"))
+			.header("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")
+			.send()
+			.await?;
+		assert_eq!(response.status(), 200);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[test]
+	fn test_parse_batch_request() -> Result<()> {
+		let coords = parse_batch_request(b"[[3,1,2],[0,0,0]]")?;
+		assert_eq!(coords, vec![TileCoord3::new(1, 2, 3)?, TileCoord3::new(0, 0, 0)?]);
+		assert_eq!(parse_batch_request(b"[]")?, vec![]);
+
+		assert!(parse_batch_request(b"{}").is_err());
+		assert!(parse_batch_request(b"[[3,1]]").is_err());
+		assert!(parse_batch_request(b"[[3,8,0]]").is_err());
+		assert!(parse_batch_request(b"[[300,0,0]]").is_err());
+
+		let too_many = format!("[{}]", vec!["[0,0,0]"; MAX_BATCH_SIZE + 1].join(","));
+		assert!(parse_batch_request(too_many.as_bytes()).is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn batch() -> Result<()> {
+		let mut server = TileServer::new(IP, 50020, true, true);
+		server.add_tile_source(
+			"cheese",
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed(),
+		)?;
+		server.start().await?;
+
+		let client = reqwest::Client::new();
+		let post =
+			|path: &str, body: &'static str| client.post(format!("http://{IP}:50020/tiles/{path}")).body(body).send();
+
+		let response = post("cheese/batch", "[[3,1,2],[2,0,1],[5,0,0]]").await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/x-tar");
+		let bytes = response.bytes().await?;
+		let mut archive = tar::Archive::new(bytes.as_ref());
+		let names = archive
+			.entries()?
+			.map(|entry| Ok(entry?.path()?.to_string_lossy().to_string()))
+			.collect::<Result<Vec<_>>>()?;
+		assert_eq!(names, vec!["3/1/2.png", "2/0/1.png"]);
+
+		assert_eq!(post("cheese/batch", "[[3,1]]").await?.status(), 400);
+		assert_eq!(post("cheese/0/0/0", "[]").await?.status(), 404);
+		assert_eq!(post("cake/batch", "[]").await?.status(), 404);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn url_templates() -> Result<()> {
+		let mut server = TileServer::new(IP, 50023, true, true);
+		let mock = || MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png).map(|reader| reader.boxed());
+		server.add_tile_source("cheese", mock()?)?;
+		server.add_tile_source("bread", mock()?)?;
+		server.add_tile_source("cake", mock()?)?;
+		server.set_url_template("cheese", "/data/cheese/{z}/{x}/{y}.png")?;
+		server.set_url_template("bread", "/tiles/bread/{z}/{y}/{x}")?;
+		server.set_url_template("cake", "/tms/{z}/{x}/{-y}.png")?;
+		assert!(server.set_url_template("cake", "/data/{z}/{x}/{y}").is_err());
+		assert!(server.set_url_template("pie", "/pie/{z}/{x}/{y}").is_err());
+		server.start().await?;
+
+		let get = |path: &str| reqwest::get(format!("http://{IP}:50023/{path}"));
+		assert_eq!(get("data/cheese/3/2/4.png").await?.status(), 200);
+		assert_eq!(get("tiles/cheese/3/2/4.png").await?.status(), 404);
+		assert_eq!(get("tiles/bread/3/6/0").await?.status(), 200);
+		assert_eq!(get("tiles/bread/3/0/6").await?.status(), 404);
+		assert_eq!(get("tms/3/2/3.png").await?.status(), 200);
+		assert_eq!(get("tms/3/2/9.png").await?.status(), 400);
+
+		let meta = get("tms/meta.json").await?.text().await?;
+		assert!(meta.contains("\"scheme\":\"tms\""), "{meta}");
+		assert!(
+			meta.contains("\"tiles\":[\"http://127.0.0.1:50023/tms/{z}/{x}/{y}.png\"]"),
+			"{meta}"
+		);
+
+		// behind a reverse proxy
+		let meta = reqwest::Client::new()
+			.get(format!("http://{IP}:50023/tms/meta.json"))
+			.header("X-Forwarded-Proto", "https")
+			.header("X-Forwarded-Host", "tiles.example.org")
+			.send()
+			.await?
+			.text()
+			.await?;
+		assert!(
+			meta.contains("\"tiles\":[\"https://tiles.example.org/tms/{z}/{x}/{y}.png\"]"),
+			"{meta}"
+		);
+
+		server.stop().await;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn http2_with_tls() -> Result<()> {
+		let dir = assert_fs::TempDir::new()?;
+		let (cert_file, key_file) = write_test_certificate(dir.path())?;
+		let mut options = ConnectionOptions::default();
+		options.set_tls(&cert_file, &key_file)?;
+		options.idle_timeout = Duration::from_secs(5);
+		options.max_concurrent_streams = 100;
+
+		let mut server = TileServer::new(IP, 50024, true, true);
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.boxed();
+		server.add_tile_source("cheese", reader)?;
+		server.set_connection_options(options);
+		server.start().await?;
+
+		let client = reqwest::Client::builder().danger_accept_invalid_certs(true).build()?;
+		let response = client
+			.get(format!("https://{IP}:50024/tiles/cheese/0/0/0"))
+			.send()
+			.await?;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.version(), reqwest::Version::HTTP_2);
+
+		// TileJSON uses the scheme and authority of HTTP/2 requests
+		let meta = client
+			.get(format!("https://{IP}:50024/tiles/cheese/meta.json"))
+			.send()
+			.await?
+			.text()
+			.await?;
+		assert!(
+			meta.contains("https://127.0.0.1:50024/tiles/cheese/{z}/{x}/{y}"),
+			"{meta}"
+		);
+
+		// clients that don't support HTTP/2 get HTTP/1.1
+		let client = reqwest::Client::builder()
+			.danger_accept_invalid_certs(true)
+			.http1_only()
+			.build()?;
+		let response = client.get(format!("https://{IP}:50024/status")).send().await?;
+		assert_eq!(response.version(), reqwest::Version::HTTP_11);
+		assert_eq!(response.text().await?, "ready!");
+
+		// unencrypted requests are rejected
+		assert!(reqwest::get(format!("http://{IP}:50024/status")).await.is_err());
+
+		server.shutdown(Duration::from_secs(5)).await;
+		Ok(())
+	}
+
 	#[tokio::test]
 	#[should_panic]
 	async fn same_prefix_twice() {
@@ -411,7 +1826,7 @@ mod tests {
 		let mut server = TileServer::new(IP, 50003, true, true);
 		assert_eq!(server.ip, IP);
 		assert_eq!(server.port, 50003);
-		assert_eq!(server.tile_sources.len(), 0);
+		assert_eq!(server.tile_sources.read().unwrap().len(), 0);
 		assert_eq!(server.static_sources.len(), 0);
 		assert!(server.exit_signal.is_none());
 
@@ -431,8 +1846,9 @@ mod tests {
 			.boxed();
 		server.add_tile_source("cheese", reader).unwrap();
 
-		assert_eq!(server.tile_sources.len(), 1);
-		assert_eq!(server.tile_sources[0].prefix.str, "/tiles/cheese/");
+		let tile_sources = server.tile_sources.read().unwrap();
+		assert_eq!(tile_sources.len(), 1);
+		assert_eq!(tile_sources[0].prefix.str, "/tiles/cheese/");
 	}
 
 	#[tokio::test]
@@ -0,0 +1,84 @@
+//! Limits the concurrent range requests to remote containers, e.g. on "https://...".
+//!
+//! Every remote container gets its own [`RequestLimiter`], which is kept when the container is opened again,
+//! e.g. after SIGHUP. Requests above the limit wait in a queue, so that a traffic spike on the server doesn't
+//! turn into a request storm on the upstream server. The running and waiting requests can be read with the
+//! admin API at "/admin/upstream".
+
+use anyhow::Result;
+use std::{
+	collections::BTreeMap,
+	sync::{Arc, Mutex},
+};
+use versatiles_core::{
+	io::RequestLimiter,
+	json::{JsonObject, JsonValue},
+};
+
+/// The request limiters of all remote containers, shared by the source opener and the admin API.
+#[derive(Clone)]
+pub struct UpstreamLimits {
+	max_requests: usize,
+	limiters: Arc<Mutex<BTreeMap<String, Arc<RequestLimiter>>>>,
+}
+
+impl UpstreamLimits {
+	/// Allows up to `max_requests` concurrent range requests per remote container.
+	pub fn new(max_requests: usize) -> Result<UpstreamLimits> {
+		// validates max_requests
+		RequestLimiter::new(max_requests)?;
+		Ok(UpstreamLimits {
+			max_requests,
+			limiters: Arc::new(Mutex::new(BTreeMap::new())),
+		})
+	}
+
+	/// Returns the limiter of the container at `url`. The same URL always gets the same limiter.
+	pub fn get_limiter(&self, url: &str) -> Arc<RequestLimiter> {
+		self
+			.limiters
+			.lock()
+			.unwrap()
+			.entry(url.to_owned())
+			.or_insert_with(|| Arc::new(RequestLimiter::new(self.max_requests).unwrap()))
+			.clone()
+	}
+
+	/// Returns the requests of every container, like `{"https://example.org/osm.versatiles":{"active":2,"max":8,"queued":0}}`.
+	pub fn get_stats(&self) -> JsonObject {
+		let mut stats = JsonObject::default();
+		for (url, limiter) in self.limiters.lock().unwrap().iter() {
+			let mut entry = JsonObject::default();
+			entry.set("active", JsonValue::from(limiter.get_active_requests() as f64));
+			entry.set("max", JsonValue::from(limiter.get_max_requests() as f64));
+			entry.set("queued", JsonValue::from(limiter.get_queue_depth() as f64));
+			stats.set(url, JsonValue::Object(entry));
+		}
+		stats
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn upstream_limits() -> Result<()> {
+		assert!(UpstreamLimits::new(0).is_err());
+
+		let limits = UpstreamLimits::new(2)?;
+		let limiter = limits.get_limiter("https://example.org/a.versatiles");
+		assert!(Arc::ptr_eq(
+			&limiter,
+			&limits.clone().get_limiter("https://example.org/a.versatiles")
+		));
+		limits.get_limiter("https://example.org/b.versatiles");
+
+		let _permit = limiter.acquire().await;
+		assert_eq!(
+			JsonValue::Object(limits.get_stats()).stringify(),
+			"{\"https://example.org/a.versatiles\":{\"active\":1,\"max\":2,\"queued\":0},\"https://example.org/b.versatiles\":{\"active\":0,\"max\":2,\"queued\":0}}"
+		);
+		Ok(())
+	}
+}
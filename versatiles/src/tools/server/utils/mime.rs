@@ -1,6 +1,11 @@
 use std::path::Path;
+use versatiles_core::types::TileFormat;
 
 pub fn guess_mime(path: &Path) -> String {
+	// mime_guess doesn't know TopoJSON
+	if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("topojson")) {
+		return TileFormat::TOPOJSON.as_mime_str().to_owned();
+	}
 	let mime = mime_guess::from_path(path)
 		.first_or_octet_stream()
 		.essence_str()
@@ -12,6 +17,11 @@ pub fn guess_mime(path: &Path) -> String {
 	}
 }
 
+/// Returns whether files of this type are already compressed, like most image formats.
+pub fn is_incompressible_mime(mime: &str) -> bool {
+	matches!(mime, "image/png" | "image/jpeg" | "image/webp" | "image/avif")
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -24,6 +34,7 @@ mod tests {
 		};
 
 		test("fluffy.css", "text/css; charset=utf-8");
+		test("fluffy.geojson", "application/geo+json");
 		test("fluffy.gif", "image/gif");
 		test("fluffy.htm", "text/html; charset=utf-8");
 		test("fluffy.html", "text/html; charset=utf-8");
@@ -34,5 +45,7 @@ mod tests {
 		test("fluffy.pbf", "application/octet-stream");
 		test("fluffy.png", "image/png");
 		test("fluffy.svg", "image/svg+xml");
+		test("fluffy.topojson", "application/topo+json");
+		test("fluffy.TopoJSON", "application/topo+json");
 	}
 }
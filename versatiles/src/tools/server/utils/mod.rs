@@ -1,7 +1,11 @@
-//! helper function for handling URLs and MIME
+//! helper function for handling URLs, MIME and byte ranges
 
 mod mime;
+mod range;
 mod url;
+mod url_template;
 
 pub use mime::*;
+pub use range::*;
 pub use url::*;
+pub use url_template::UrlTemplate;
@@ -0,0 +1,84 @@
+use versatiles_core::types::ByteRange;
+
+/// The part of a file that is requested by a "Range" header.
+#[derive(Debug, PartialEq)]
+pub enum RangeRequest {
+	/// The whole file, because no range or an unsupported range was requested.
+	Full,
+	/// A single range of bytes.
+	Partial(ByteRange),
+	/// The range lies outside the file.
+	Unsatisfiable,
+}
+
+/// Parses the value of a "Range" header for a file of `size` bytes.
+///
+/// Only single byte ranges are supported, e.g. "bytes=0-99", "bytes=100-" or "bytes=-100".
+/// Invalid headers and multiple ranges are ignored, so the whole file is sent, as allowed by RFC 9110.
+pub fn parse_range_header(value: Option<&str>, size: u64) -> RangeRequest {
+	let Some(spec) = value.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+		return RangeRequest::Full;
+	};
+	if spec.contains(',') {
+		return RangeRequest::Full;
+	}
+	let Some((start, end)) = spec.trim().split_once('-') else {
+		return RangeRequest::Full;
+	};
+
+	let (first, last) = if start.is_empty() {
+		// suffix range: the last bytes of the file
+		let Ok(length) = end.parse::<u64>() else {
+			return RangeRequest::Full;
+		};
+		if length == 0 || size == 0 {
+			return RangeRequest::Unsatisfiable;
+		}
+		(size.saturating_sub(length), size - 1)
+	} else {
+		let Ok(first) = start.parse::<u64>() else {
+			return RangeRequest::Full;
+		};
+		let last = if end.is_empty() {
+			u64::MAX
+		} else {
+			match end.parse::<u64>() {
+				Ok(last) if last >= first => last,
+				_ => return RangeRequest::Full,
+			}
+		};
+		if first >= size {
+			return RangeRequest::Unsatisfiable;
+		}
+		(first, last.min(size - 1))
+	};
+
+	RangeRequest::Partial(ByteRange::new(first, last - first + 1))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_range_header() {
+		let parse = |value: &str| parse_range_header(Some(value), 1000);
+		let partial = |offset, length| RangeRequest::Partial(ByteRange::new(offset, length));
+
+		assert_eq!(parse_range_header(None, 1000), RangeRequest::Full);
+		assert_eq!(parse("bytes=0-99"), partial(0, 100));
+		assert_eq!(parse("bytes=100-"), partial(100, 900));
+		assert_eq!(parse("bytes=900-2000"), partial(900, 100));
+		assert_eq!(parse("bytes=-100"), partial(900, 100));
+		assert_eq!(parse("bytes=-2000"), partial(0, 1000));
+		assert_eq!(parse("bytes=999-999"), partial(999, 1));
+
+		assert_eq!(parse("bytes=1000-"), RangeRequest::Unsatisfiable);
+		assert_eq!(parse("bytes=-0"), RangeRequest::Unsatisfiable);
+
+		assert_eq!(parse("bytes=0-1,5-9"), RangeRequest::Full);
+		assert_eq!(parse("bytes=9-5"), RangeRequest::Full);
+		assert_eq!(parse("bytes=a-b"), RangeRequest::Full);
+		assert_eq!(parse("items=0-9"), RangeRequest::Full);
+	}
+}
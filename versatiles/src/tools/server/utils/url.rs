@@ -67,6 +67,39 @@ impl Url {
 	}
 }
 
+/// Returns the decoded value of a parameter of a query string like "q=Berlin%20Mitte&limit=5".
+pub fn get_query_parameter(query: &str, name: &str) -> Option<String> {
+	query.split('&').find_map(|pair| {
+		let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+		(decode_query_component(key) == name).then(|| decode_query_component(value))
+	})
+}
+
+/// Decodes "+" and percent-encoded bytes of a query string component. Invalid escape sequences are kept.
+fn decode_query_component(text: &str) -> String {
+	let bytes = text.as_bytes();
+	let mut result = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		match bytes[i] {
+			b'+' => result.push(b' '),
+			b'%' if i + 2 < bytes.len() => {
+				let hex = |b: u8| (b as char).to_digit(16);
+				match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+					(Some(high), Some(low)) => {
+						result.push((high * 16 + low) as u8);
+						i += 2;
+					}
+					_ => result.push(b'%'),
+				}
+			}
+			byte => result.push(byte),
+		}
+		i += 1;
+	}
+	String::from_utf8_lossy(&result).into_owned()
+}
+
 impl std::fmt::Display for Url {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.write_str(&self.str)
@@ -146,6 +179,17 @@ mod tests {
 		assert_eq!(path, PathBuf::from("/base/test/dir/file"));
 	}
 
+	#[test]
+	fn test_get_query_parameter() {
+		let query = "q=Berlin%20Mitte&limit=5&empty&name=a+b%2Bc&bad=%zz%4";
+		assert_eq!(get_query_parameter(query, "q").unwrap(), "Berlin Mitte");
+		assert_eq!(get_query_parameter(query, "limit").unwrap(), "5");
+		assert_eq!(get_query_parameter(query, "empty").unwrap(), "");
+		assert_eq!(get_query_parameter(query, "name").unwrap(), "a b+c");
+		assert_eq!(get_query_parameter(query, "bad").unwrap(), "%zz%4");
+		assert_eq!(get_query_parameter(query, "missing"), None);
+	}
+
 	#[test]
 	fn test_join_as_string() {
 		assert_eq!(Url::new("/test/dir/").join_as_string("file"), "/test/dir/file");
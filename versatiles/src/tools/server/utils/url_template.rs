@@ -0,0 +1,209 @@
+use super::Url;
+use anyhow::{bail, ensure, Context, Result};
+use versatiles_core::types::TileCoord3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Placeholder {
+	Z,
+	X,
+	Y,
+	/// Rows counted from the bottom, like in TMS.
+	FlippedY,
+}
+
+/// The public URL of the tiles of a source, like "/tiles/osm/{z}/{x}/{y}" or "/data/osm/{z}/{x}/{y}.pbf".
+///
+/// The placeholders can be in any order, e.g. "/tiles/osm/{z}/{y}/{x}", and "{-y}" counts rows from
+/// the bottom for legacy TMS clients. Everything before the placeholders is the prefix of the source.
+#[derive(Clone)]
+pub struct UrlTemplate {
+	pub prefix: Url,
+	order: [Placeholder; 3],
+	suffix: String,
+	is_custom: bool,
+}
+
+impl UrlTemplate {
+	/// The default template of a source: "/tiles/{id}/{z}/{x}/{y}"
+	pub fn new_default(id: &str) -> UrlTemplate {
+		UrlTemplate {
+			prefix: Url::new(&format!("/tiles/{id}/")).as_dir(),
+			order: [Placeholder::Z, Placeholder::X, Placeholder::Y],
+			suffix: String::new(),
+			is_custom: false,
+		}
+	}
+
+	pub fn parse(template: &str) -> Result<UrlTemplate> {
+		let segments = template.trim_start_matches('/').split('/').collect::<Vec<_>>();
+		let count = segments
+			.iter()
+			.position(|segment| segment.contains('{'))
+			.context("the url template must contain the placeholders {z}, {x} and {y}")?;
+		ensure!(
+			segments.len() == count + 3,
+			"the url template must end with 3 segments like {{z}}/{{x}}/{{y}}, but got '{template}'"
+		);
+		ensure!(
+			count > 0,
+			"the url template '{template}' must start with a prefix like /tiles/osm/"
+		);
+		ensure!(
+			!segments[..count].iter().any(|segment| segment.is_empty()),
+			"the prefix of the url template '{template}' contains empty segments"
+		);
+
+		let mut order = Vec::new();
+		let mut suffix = String::new();
+		for (index, segment) in segments[count..].iter().enumerate() {
+			let (name, rest) = segment
+				.strip_prefix('{')
+				.and_then(|segment| segment.split_once('}'))
+				.with_context(|| format!("'{segment}' must be a placeholder like {{z}}, {{x}} or {{y}}"))?;
+			ensure!(
+				rest.is_empty() || index == 2,
+				"only the last placeholder of '{template}' can have a suffix"
+			);
+			suffix = rest.to_owned();
+			order.push(match name {
+				"z" => Placeholder::Z,
+				"x" => Placeholder::X,
+				"y" => Placeholder::Y,
+				"-y" => Placeholder::FlippedY,
+				_ => bail!("unknown placeholder {{{name}}} in '{template}', use {{z}}, {{x}}, {{y}} or {{-y}}"),
+			});
+		}
+
+		let is_used = |placeholders: &[Placeholder]| order.iter().any(|p| placeholders.contains(p));
+		ensure!(
+			is_used(&[Placeholder::Z]) && is_used(&[Placeholder::X]) && is_used(&[Placeholder::Y, Placeholder::FlippedY]),
+			"the url template '{template}' must contain the placeholders {{z}}, {{x}} and {{y}}"
+		);
+
+		Ok(UrlTemplate {
+			prefix: Url::new(&segments[..count].join("/")).as_dir(),
+			order: order.try_into().expect("should have 3 placeholders"),
+			suffix,
+			is_custom: true,
+		})
+	}
+
+	/// Returns true if the template was parsed from a user defined string, instead of being the default template.
+	pub fn is_custom(&self) -> bool {
+		self.is_custom
+	}
+
+	/// Returns true if rows are counted from the bottom, like in TMS.
+	pub fn is_flipped(&self) -> bool {
+		self.order.contains(&Placeholder::FlippedY)
+	}
+
+	/// Parses the tile coordinates of a URL relative to the prefix, like "{z}/{x}/{y}.png".
+	///
+	/// Returns `None` if the URL doesn't address a tile, e.g. "meta.json".
+	///
+	/// # Errors
+	/// Returns an error if the coordinates are not valid numbers.
+	pub fn get_coord(&self, url: &Url) -> Result<Option<TileCoord3>> {
+		let parts: Vec<String> = url.as_vec();
+		if parts.len() < 3 {
+			return Ok(None);
+		}
+
+		let (mut x, mut y, mut z, mut flipped) = (None, None, None, false);
+		for (placeholder, part) in self.order.iter().zip(parts) {
+			// the last part can have an extension like ".png"
+			let digits = part.chars().take_while(|c| c.is_numeric()).collect::<String>();
+			match placeholder {
+				Placeholder::Z => z = Some(digits.parse::<u8>().context("value for z is not a number")?),
+				Placeholder::X => x = Some(digits.parse::<u32>().context("value for x is not a number")?),
+				Placeholder::Y | Placeholder::FlippedY => {
+					y = Some(digits.parse::<u32>().context("value for y is not a number")?);
+					flipped = *placeholder == Placeholder::FlippedY;
+				}
+			}
+		}
+		let (x, mut y, z) = (x.unwrap(), y.unwrap(), z.unwrap());
+
+		if flipped {
+			ensure!(z < 32, "z ({z}) must be < 32");
+			y = (1u32 << z)
+				.checked_sub(y)
+				.and_then(|rows| rows.checked_sub(1))
+				.context("value for y is too large for the zoom level")?;
+		}
+
+		Ok(Some(TileCoord3::new(x, y, z)?))
+	}
+
+	/// Returns the URL of the tiles for TileJSON. Flipped rows are marked with `"scheme": "tms"` in TileJSON,
+	/// so "{-y}" is written as "{y}".
+	pub fn as_tilejson_url(&self) -> String {
+		let placeholders = self
+			.order
+			.iter()
+			.map(|placeholder| match placeholder {
+				Placeholder::Z => "{z}",
+				Placeholder::X => "{x}",
+				Placeholder::Y | Placeholder::FlippedY => "{y}",
+			})
+			.collect::<Vec<_>>();
+		format!("{}{}{}", self.prefix.as_string(), placeholders.join("/"), self.suffix)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coord(template: &UrlTemplate, url: &str) -> Result<Option<TileCoord3>> {
+		template.get_coord(&Url::new(url))
+	}
+
+	#[test]
+	fn default() -> Result<()> {
+		let template = UrlTemplate::new_default("osm");
+		assert_eq!(template.prefix.str, "/tiles/osm/");
+		assert_eq!(template.as_tilejson_url(), "/tiles/osm/{z}/{x}/{y}");
+		assert!(!template.is_flipped());
+		assert!(!template.is_custom());
+
+		assert_eq!(coord(&template, "3/2/4.png")?, Some(TileCoord3::new(2, 4, 3)?));
+		assert_eq!(coord(&template, "meta.json")?, None);
+		assert!(coord(&template, "x/0/0.png").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn custom() -> Result<()> {
+		let template = UrlTemplate::parse("/data/osm/{z}/{y}/{x}.pbf")?;
+		assert_eq!(template.prefix.str, "/data/osm/");
+		assert_eq!(template.as_tilejson_url(), "/data/osm/{z}/{y}/{x}.pbf");
+		assert!(template.is_custom());
+		assert_eq!(coord(&template, "3/2/4.pbf")?, Some(TileCoord3::new(4, 2, 3)?));
+
+		let template = UrlTemplate::parse("tms/{z}/{x}/{-y}.png")?;
+		assert_eq!(template.prefix.str, "/tms/");
+		assert_eq!(template.as_tilejson_url(), "/tms/{z}/{x}/{y}.png");
+		assert!(template.is_flipped());
+		assert_eq!(coord(&template, "3/2/0.png")?, Some(TileCoord3::new(2, 7, 3)?));
+		assert!(coord(&template, "3/2/8.png").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn invalid() {
+		for template in [
+			"/tiles/osm",
+			"/tiles/osm/{z}/{x}",
+			"/tiles/osm/{z}/{x}/{y}/tile",
+			"/tiles/osm/{z}/{x}/{z}",
+			"/tiles/osm/{z}.png/{x}/{y}",
+			"/tiles/osm/{z}/{x}/{w}",
+			"/tiles//osm/{z}/{x}/{y}",
+			"/{z}/{x}/{y}",
+		] {
+			assert!(UrlTemplate::parse(template).is_err(), "{template}");
+		}
+	}
+}
@@ -0,0 +1,50 @@
+//! Signs a `*.versatiles` container with an Ed25519 key.
+//!
+//! The signature is written next to the container as `<container>.sig` and covers the header, the meta data, the
+//! block index and the hashes of all tiles. Clients verify it with the public key, using `versatiles verify`.
+//! `VersaTilesReader::open_path_with_verified_index` checks only the header, the meta data and the block index.
+
+use anyhow::{ensure, Context, Result};
+use std::{fs, path::PathBuf};
+use versatiles_container::{format_public_key, generate_signing_key, ContainerSignature, VersaTilesReader};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// *.versatiles container to sign
+	#[arg(required = true)]
+	container: PathBuf,
+
+	/// Ed25519 private key in PKCS#8 format
+	#[arg(long, value_name = "FILE", required = true)]
+	key: PathBuf,
+
+	/// generate a new private key and write it to the file given by --key
+	#[arg(long)]
+	generate_key: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let private_key = if arguments.generate_key {
+		ensure!(
+			!arguments.key.exists(),
+			"key file {:?} already exists, remove it or omit --generate-key",
+			arguments.key
+		);
+		let private_key = generate_signing_key()?;
+		fs::write(&arguments.key, &private_key).with_context(|| format!("writing key {:?}", arguments.key))?;
+		eprintln!("wrote new private key to {:?}", arguments.key);
+		private_key
+	} else {
+		fs::read(&arguments.key).with_context(|| format!("reading key {:?}", arguments.key))?
+	};
+
+	let reader = VersaTilesReader::open_path(&std::path::absolute(&arguments.container)?).await?;
+	let signature = ContainerSignature::sign(&reader, &private_key).await?;
+	let path = ContainerSignature::get_path(&arguments.container);
+	signature.write_file(&path)?;
+
+	eprintln!("wrote signature to {path:?}");
+	println!("public key: {}", format_public_key(&signature.public_key));
+	Ok(())
+}
@@ -0,0 +1,265 @@
+//! Packs a directory of SVG icons into MapLibre sprite sheets.
+//!
+//! For every pixel ratio a PNG image and a JSON index are written, e.g. `sprite.png`/`sprite.json` for 1x and
+//! `sprite@2x.png`/`sprite@2x.json` for 2x. The files are written into a directory, or added to a *.tar file
+//! that can be served as static content.
+
+use super::assets::write_assets;
+use anyhow::{ensure, Context, Result};
+use image::{DynamicImage, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_core::{json::JsonObject, types::Blob};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+pub struct Subcommand {
+	/// directory containing the icons as *.svg files
+	/// the icon names are the filenames without extension
+	#[arg(required = true, verbatim_doc_comment)]
+	input_directory: PathBuf,
+
+	/// output directory, or a *.tar file to add the sprites to
+	#[arg(required = true)]
+	output: PathBuf,
+
+	/// base name of the sprite files, may contain a path, e.g. "sprites/basics/sprite"
+	#[arg(long, default_value = "sprite")]
+	name: String,
+
+	/// pixel ratios to render, separated by commas
+	#[arg(long, value_delimiter = ',', default_value = "1,2")]
+	pixel_ratio: Vec<u8>,
+}
+
+pub fn run(arguments: &Subcommand) -> Result<()> {
+	let icons = read_icons(&arguments.input_directory)?;
+	eprintln!("pack {} icons from {:?}", icons.len(), arguments.input_directory);
+
+	let mut files: Vec<(String, Blob)> = Vec::new();
+	for &ratio in arguments.pixel_ratio.iter() {
+		ensure!(ratio > 0, "pixel ratio must be at least 1");
+		let sheet = render_sprite_sheet(&icons, ratio)?;
+		let name = if ratio == 1 {
+			arguments.name.clone()
+		} else {
+			format!("{}@{ratio}x", arguments.name)
+		};
+		files.push((format!("{name}.png"), sheet.png));
+		files.push((format!("{name}.json"), Blob::from(sheet.index.stringify())));
+	}
+
+	write_assets(&arguments.output, files)
+}
+
+/// An icon, parsed from an SVG file.
+struct Icon {
+	name: String,
+	tree: usvg::Tree,
+}
+
+impl Icon {
+	/// Size of the rendered icon in pixels.
+	fn get_size(&self, ratio: u8) -> (u32, u32) {
+		let size = self.tree.size();
+		(
+			(size.width() * ratio as f32).ceil() as u32,
+			(size.height() * ratio as f32).ceil() as u32,
+		)
+	}
+}
+
+/// Reads all *.svg files of a directory, sorted by name.
+fn read_icons(directory: &Path) -> Result<Vec<Icon>> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(directory)
+		.with_context(|| format!("reading directory {directory:?}"))?
+		.map(|entry| entry.map(|e| e.path()))
+		.collect::<Result<_, _>>()?;
+	paths.retain(|p| p.extension().is_some_and(|e| e == "svg"));
+	paths.sort();
+	ensure!(!paths.is_empty(), "no *.svg files found in {directory:?}");
+
+	let options = usvg::Options::default();
+	paths
+		.iter()
+		.map(|path| {
+			let data = fs::read(path)?;
+			let tree = usvg::Tree::from_data(&data, &options).with_context(|| format!("parsing SVG {path:?}"))?;
+			let name = path.file_stem().unwrap().to_string_lossy().to_string();
+			Ok(Icon { name, tree })
+		})
+		.collect()
+}
+
+/// A rendered sprite sheet.
+struct SpriteSheet {
+	png: Blob,
+	/// Position and size of every icon, as expected by MapLibre.
+	index: JsonObject,
+}
+
+/// Renders all icons into a single image.
+fn render_sprite_sheet(icons: &[Icon], ratio: u8) -> Result<SpriteSheet> {
+	let sizes: Vec<(u32, u32)> = icons.iter().map(|icon| icon.get_size(ratio)).collect();
+	let (width, height, positions) = pack(&sizes);
+
+	let mut pixmap = tiny_skia::Pixmap::new(width, height).context("creating sprite image")?;
+	let mut index = JsonObject::default();
+
+	for ((icon, &(w, h)), &(x, y)) in icons.iter().zip(sizes.iter()).zip(positions.iter()) {
+		let transform = tiny_skia::Transform::from_scale(ratio as f32, ratio as f32).post_translate(x as f32, y as f32);
+		resvg::render(&icon.tree, transform, &mut pixmap.as_mut());
+
+		index.set(
+			&icon.name,
+			vec![
+				("width", w as f64),
+				("height", h as f64),
+				("x", x as f64),
+				("y", y as f64),
+				("pixelRatio", ratio as f64),
+			],
+		);
+	}
+
+	// tiny-skia uses premultiplied alpha
+	let data = pixmap
+		.pixels()
+		.iter()
+		.flat_map(|p| {
+			let c = p.demultiply();
+			[c.red(), c.green(), c.blue(), c.alpha()]
+		})
+		.collect();
+	let image = RgbaImage::from_raw(width, height, data).unwrap();
+	let png = versatiles_image::png::image2blob(&DynamicImage::ImageRgba8(image), true)?;
+
+	Ok(SpriteSheet { png, index })
+}
+
+/// Packs rectangles into rows ("shelves"), tallest first.
+///
+/// Returns the width and height of the sheet, and the position of every rectangle.
+fn pack(sizes: &[(u32, u32)]) -> (u32, u32, Vec<(u32, u32)>) {
+	let area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+	let max_width = sizes.iter().map(|&(w, _)| w).max().unwrap_or(0);
+	let sheet_width = ((area as f64).sqrt().ceil() as u32).max(max_width).max(1);
+
+	let mut order: Vec<usize> = (0..sizes.len()).collect();
+	order.sort_by_key(|&i| (std::cmp::Reverse(sizes[i].1), i));
+
+	let mut positions = vec![(0, 0); sizes.len()];
+	let (mut x, mut y, mut row_height, mut width) = (0u32, 0u32, 0u32, 0u32);
+	for i in order {
+		let (w, h) = sizes[i];
+		if x + w > sheet_width {
+			x = 0;
+			y += row_height;
+			row_height = 0;
+		}
+		positions[i] = (x, y);
+		x += w;
+		width = width.max(x);
+		row_height = row_height.max(h);
+	}
+
+	(width.max(1), (y + row_height).max(1), positions)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+	use std::fs::File;
+
+	fn write_icons(dir: &Path) -> Result<()> {
+		let svg = |w: u32, h: u32| {
+			format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\"><rect width=\"{w}\" height=\"{h}\" fill=\"#f00\"/></svg>")
+		};
+		fs::write(dir.join("tall.svg"), svg(10, 12))?;
+		fs::write(dir.join("square.svg"), svg(8, 8))?;
+		fs::write(dir.join("wide.svg"), svg(20, 5))?;
+		fs::write(dir.join("readme.txt"), "not an icon")?;
+		Ok(())
+	}
+
+	#[test]
+	fn pack_shelves() {
+		let (width, height, positions) = pack(&[(10, 12), (8, 8), (20, 5)]);
+		assert_eq!((width, height), (20, 17));
+		assert_eq!(positions, vec![(0, 0), (10, 0), (0, 12)]);
+
+		// rectangles never overlap
+		let sizes: Vec<(u32, u32)> = (1..30).map(|i| (i * 7 % 23 + 1, i * 5 % 17 + 1)).collect();
+		let (width, height, positions) = pack(&sizes);
+		for (i, (&(w1, h1), &(x1, y1))) in sizes.iter().zip(positions.iter()).enumerate() {
+			assert!(x1 + w1 <= width && y1 + h1 <= height);
+			for (&(w2, h2), &(x2, y2)) in sizes.iter().zip(positions.iter()).skip(i + 1) {
+				assert!(x1 + w1 <= x2 || x2 + w2 <= x1 || y1 + h1 <= y2 || y2 + h2 <= y1);
+			}
+		}
+	}
+
+	#[test]
+	fn render() -> Result<()> {
+		let dir = TempDir::new()?;
+		write_icons(dir.path())?;
+		let icons = read_icons(dir.path())?;
+		assert_eq!(icons.len(), 3);
+
+		let sheet = render_sprite_sheet(&icons, 2)?;
+		assert_eq!(
+			sheet.index.stringify(),
+			"{\"square\":{\"height\":16,\"pixelRatio\":2,\"width\":16,\"x\":20,\"y\":0},\
+			\"tall\":{\"height\":24,\"pixelRatio\":2,\"width\":20,\"x\":0,\"y\":0},\
+			\"wide\":{\"height\":10,\"pixelRatio\":2,\"width\":40,\"x\":0,\"y\":24}}"
+		);
+
+		let image = versatiles_image::png::blob2image(&sheet.png)?.to_rgba8();
+		assert_eq!(image.dimensions(), (40, 34));
+		assert_eq!(image.get_pixel(25, 5).0, [255, 0, 0, 255]);
+		assert_eq!(image.get_pixel(39, 20).0, [0, 0, 0, 0]);
+		Ok(())
+	}
+
+	#[test]
+	fn command() -> Result<()> {
+		let dir = TempDir::new()?;
+		let icons = dir.path().join("icons");
+		fs::create_dir(&icons)?;
+		write_icons(&icons)?;
+
+		let output = dir.path().join("output");
+		run_command(vec![
+			"versatiles",
+			"sprites",
+			icons.to_str().unwrap(),
+			output.to_str().unwrap(),
+			"--name=sprites/sprite",
+		])?;
+		for name in ["sprite.png", "sprite.json", "sprite@2x.png", "sprite@2x.json"] {
+			assert!(output.join("sprites").join(name).exists(), "{name}");
+		}
+
+		let tar_file = dir.path().join("frontend.tar");
+		write_assets(&tar_file, vec![(String::from("index.html"), Blob::from("<html>"))])?;
+		run_command(vec![
+			"versatiles",
+			"sprites",
+			icons.to_str().unwrap(),
+			tar_file.to_str().unwrap(),
+			"--pixel-ratio=1",
+		])?;
+		let mut archive = tar::Archive::new(File::open(&tar_file)?);
+		let names: Vec<String> = archive
+			.entries()?
+			.map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+			.collect();
+		assert_eq!(names, vec!["index.html", "sprite.png", "sprite.json"]);
+		Ok(())
+	}
+}
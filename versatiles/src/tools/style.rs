@@ -0,0 +1,127 @@
+//! Helpers for the URLs referenced by MapLibre styles.
+//!
+//! A style references sprites (`sprite`), glyphs (`glyphs`) and tile sources (`sources.*.url` and
+//! `sources.*.tiles`). These helpers are used to bundle styles and to serve them with absolute URLs.
+
+use anyhow::Result;
+use versatiles_core::json::{JsonObject, JsonValue};
+
+/// The kind of a URL in a style.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StyleUrl {
+	/// Base URL of a sprite, with the id of the sprite, if the style uses multiple sprites.
+	Sprite(Option<String>),
+	/// URL template of the glyphs, containing `{fontstack}` and `{range}`.
+	Glyphs,
+	/// URL of the TileJSON of a source, with the id of the source.
+	Source(String),
+	/// URL template of the tiles of a source, with the id of the source.
+	Tiles(String),
+}
+
+/// Returns true, if the JSON looks like a MapLibre style.
+pub fn is_style(json: &JsonObject) -> bool {
+	json.get("version").is_some() && json.get("sources").is_some() && json.get("layers").is_some()
+}
+
+/// Calls `callback` for every URL of a style. If it returns a new URL, the URL will be replaced.
+pub fn rewrite_style_urls<F>(style: &mut JsonObject, mut callback: F) -> Result<()>
+where
+	F: FnMut(StyleUrl, &str) -> Result<Option<String>>,
+{
+	let mut rewrite = |value: &mut JsonValue, kind: StyleUrl| -> Result<()> {
+		if let JsonValue::String(url) = value {
+			if let Some(new_url) = callback(kind, url)? {
+				*url = new_url;
+			}
+		}
+		Ok(())
+	};
+
+	if let Some(sprite) = style.0.get_mut("sprite") {
+		match sprite {
+			JsonValue::Array(sprites) => {
+				for entry in sprites.0.iter_mut() {
+					if let JsonValue::Object(entry) = entry {
+						let id = entry.get_string("id")?;
+						if let Some(url) = entry.0.get_mut("url") {
+							rewrite(url, StyleUrl::Sprite(id))?;
+						}
+					}
+				}
+			}
+			value => rewrite(value, StyleUrl::Sprite(None))?,
+		}
+	}
+
+	if let Some(glyphs) = style.0.get_mut("glyphs") {
+		rewrite(glyphs, StyleUrl::Glyphs)?;
+	}
+
+	if let Some(JsonValue::Object(sources)) = style.0.get_mut("sources") {
+		for (id, source) in sources.0.iter_mut() {
+			let JsonValue::Object(source) = source else { continue };
+			if let Some(url) = source.0.get_mut("url") {
+				rewrite(url, StyleUrl::Source(id.clone()))?;
+			}
+			if let Some(JsonValue::Array(tiles)) = source.0.get_mut("tiles") {
+				for url in tiles.0.iter_mut() {
+					rewrite(url, StyleUrl::Tiles(id.clone()))?;
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rewrite() -> Result<()> {
+		let mut style = JsonObject::parse_str(
+			r#"{"version":8,"layers":[],
+			"sprite":[{"id":"basics","url":"/sprites/basics"},{"id":"remote","url":"https://example.org/sprite"}],
+			"glyphs":"/glyphs/{fontstack}/{range}.pbf",
+			"sources":{
+				"osm":{"type":"vector","url":"/tiles/osm/tiles.json"},
+				"dem":{"type":"raster","tiles":["/tiles/dem/{z}/{x}/{y}"]},
+				"geojson":{"type":"geojson","data":"/data.geojson"}
+			}}"#,
+		)?;
+		assert!(is_style(&style));
+
+		let mut kinds = Vec::new();
+		rewrite_style_urls(&mut style, |kind, url| {
+			kinds.push(kind);
+			Ok(url
+				.strip_prefix('/')
+				.map(|path| format!("https://tiles.example.org/{path}")))
+		})?;
+
+		assert_eq!(
+			kinds,
+			vec![
+				StyleUrl::Sprite(Some(String::from("basics"))),
+				StyleUrl::Sprite(Some(String::from("remote"))),
+				StyleUrl::Glyphs,
+				StyleUrl::Tiles(String::from("dem")),
+				StyleUrl::Source(String::from("osm")),
+			]
+		);
+		assert_eq!(
+			style.stringify(),
+			"{\"glyphs\":\"https://tiles.example.org/glyphs/{fontstack}/{range}.pbf\",\"layers\":[],\
+			\"sources\":{\"dem\":{\"tiles\":[\"https://tiles.example.org/tiles/dem/{z}/{x}/{y}\"],\"type\":\"raster\"},\
+			\"geojson\":{\"data\":\"/data.geojson\",\"type\":\"geojson\"},\
+			\"osm\":{\"type\":\"vector\",\"url\":\"https://tiles.example.org/tiles/osm/tiles.json\"}},\
+			\"sprite\":[{\"id\":\"basics\",\"url\":\"https://tiles.example.org/sprites/basics\"},\
+			{\"id\":\"remote\",\"url\":\"https://example.org/sprite\"}],\"version\":8}"
+		);
+
+		assert!(!is_style(&JsonObject::parse_str(r#"{"version":8}"#)?));
+		Ok(())
+	}
+}
@@ -0,0 +1,532 @@
+//! Synchronizes a tile container into a directory, locally or on a remote server.
+//!
+//! The destination uses the layout of a directory container (`{z}/{x}/{y}.{format}{compression}`), so it can be
+//! served as static files or read by VersaTiles. Next to the tiles, a manifest of tile checksums (see
+//! `versatiles checksum`) is stored as `manifest.jsonl`. On the next sync, only tiles whose checksum changed are
+//! uploaded, and tiles that no longer exist are deleted.
+//!
+//! Remote destinations are written with HTTP PUT and DELETE requests, e.g. to WebDAV servers or to S3 compatible
+//! buckets with presigned or public write access. Authentication headers can be added with `--header`, and the
+//! upload rate can be limited with `--max-upload-rate`.
+
+use super::checksum::{
+	for_each_tile_hash, get_header, get_order_key, write_manifest_line, HashAlgorithm, ManifestReader,
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use reqwest::{
+	header::{HeaderMap, HeaderName, HeaderValue},
+	Client, StatusCode,
+};
+use std::{
+	env, fs,
+	io::{BufWriter, Write},
+	path::{Path, PathBuf},
+};
+use versatiles_container::get_reader;
+use versatiles_core::{
+	io::BandwidthLimiter,
+	progress::get_progress_bar,
+	types::{Blob, TileCoord3, TilesReaderTrait},
+	utils::compress,
+};
+
+/// Name of the manifest in the destination.
+const MANIFEST_NAME: &str = "manifest.jsonl";
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true, verbatim_doc_comment)]
+pub struct Subcommand {
+	/// source container
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	source: String,
+
+	/// destination: a local directory, or a URL (http://... or https://...) that accepts PUT and DELETE requests
+	#[arg(required = true)]
+	destination: String,
+
+	/// read and write the manifest of the destination from this local file, instead of the destination itself
+	/// recommended for large containers, since remote manifests are held in memory
+	#[arg(long, value_name = "FILE", verbatim_doc_comment)]
+	manifest: Option<PathBuf>,
+
+	/// hash algorithm, if the destination has no manifest yet
+	#[arg(long, value_enum, default_value_t = HashAlgorithm::Xxh3)]
+	algorithm: HashAlgorithm,
+
+	/// number of parallel uploads
+	#[arg(long, value_name = "int", default_value = "8")]
+	concurrency: usize,
+
+	/// add an HTTP header to all requests to remote destinations, e.g. "Authorization: Bearer ..."
+	/// can be used multiple times
+	#[arg(long, value_name = "NAME: VALUE", verbatim_doc_comment)]
+	header: Vec<String>,
+
+	/// limit the upload rate to remote destinations in bytes per second
+	#[arg(long, value_name = "bytes/s")]
+	max_upload_rate: Option<u64>,
+
+	/// only show what would be uploaded and deleted
+	#[arg(long)]
+	dry_run: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	ensure!(arguments.concurrency > 0, "concurrency must be at least 1");
+
+	let reader = get_reader(&arguments.source).await?;
+	let target: Box<dyn SyncTarget> =
+		if arguments.destination.starts_with("http://") || arguments.destination.starts_with("https://") {
+			Box::new(HttpTarget::new(
+				&arguments.destination,
+				&arguments.header,
+				arguments.max_upload_rate,
+			)?)
+		} else {
+			Box::new(LocalTarget::new(Path::new(&arguments.destination))?)
+		};
+
+	eprintln!("sync {:?} to {:?}", arguments.source, arguments.destination);
+
+	// the new manifest is written to a temporary file, and only replaces the old one after a successful sync
+	let manifest_path = match &arguments.manifest {
+		Some(path) => path.with_extension("tmp"),
+		None => env::temp_dir().join(format!("versatiles-sync-{}.jsonl", std::process::id())),
+	};
+	let changes = get_changes(&*reader, &*target, arguments, &manifest_path).await;
+	let changes = match changes {
+		Ok(changes) => changes,
+		Err(e) => {
+			fs::remove_file(&manifest_path).ok();
+			return Err(e);
+		}
+	};
+
+	eprintln!(
+		"{} tiles changed, {} deleted, {} unchanged",
+		changes.upload.len(),
+		changes.delete.len(),
+		changes.unchanged
+	);
+
+	if arguments.dry_run {
+		fs::remove_file(&manifest_path)?;
+		return Ok(());
+	}
+
+	apply_changes(&*reader, &*target, &changes, arguments.concurrency).await?;
+
+	match &arguments.manifest {
+		Some(path) => fs::rename(&manifest_path, path)?,
+		None => {
+			let blob = Blob::from(fs::read(&manifest_path)?);
+			fs::remove_file(&manifest_path)?;
+			target.write(MANIFEST_NAME, blob).await?;
+		}
+	}
+
+	Ok(())
+}
+
+/// The tiles that have to be uploaded and deleted.
+#[derive(Debug, Default)]
+struct Changes {
+	upload: Vec<TileCoord3>,
+	delete: Vec<TileCoord3>,
+	unchanged: u64,
+}
+
+/// Compares the hashes of the source with the old manifest of the destination and writes the new manifest.
+async fn get_changes(
+	reader: &dyn TilesReaderTrait,
+	target: &dyn SyncTarget,
+	arguments: &Subcommand,
+	new_manifest: &Path,
+) -> Result<Changes> {
+	let old_manifest = match &arguments.manifest {
+		Some(path) if path.exists() => Some(ManifestReader::open(path)?),
+		Some(_) => None,
+		None => match target.read(MANIFEST_NAME).await? {
+			Some(blob) => Some(ManifestReader::from_blob(blob)?),
+			None => None,
+		},
+	};
+
+	let (mut old_manifest, algorithm) = match old_manifest {
+		Some((manifest, header)) => {
+			let algorithm =
+				HashAlgorithm::parse_str(&header.get_string("algorithm")?.context("manifest has no algorithm")?)?;
+			if header == get_header(reader, algorithm) {
+				(Some(manifest), algorithm)
+			} else {
+				eprintln!("tile format or compression changed, upload all tiles");
+				(None, algorithm)
+			}
+		}
+		None => (None, arguments.algorithm),
+	};
+
+	let mut output = BufWriter::new(fs::File::create(new_manifest)?);
+	writeln!(output, "{}", get_header(reader, algorithm).stringify())?;
+
+	let mut changes = Changes::default();
+	let mut old = next_tile(&mut old_manifest)?;
+
+	for_each_tile_hash(reader, algorithm, |coord, hash| {
+		write_manifest_line(&mut output, &coord, &hash)?;
+		let key = get_order_key(&coord);
+
+		// all old tiles before this tile do not exist anymore
+		while let Some((old_coord, _)) = &old {
+			if get_order_key(old_coord) >= key {
+				break;
+			}
+			changes.delete.push(*old_coord);
+			old = next_tile(&mut old_manifest)?;
+		}
+
+		match &old {
+			Some((old_coord, old_hash)) if old_coord == &coord => {
+				if old_hash == &hash {
+					changes.unchanged += 1;
+				} else {
+					changes.upload.push(coord);
+				}
+				old = next_tile(&mut old_manifest)?;
+			}
+			_ => changes.upload.push(coord),
+		}
+		Ok(())
+	})
+	.await?;
+
+	while let Some((old_coord, _)) = old {
+		changes.delete.push(old_coord);
+		old = next_tile(&mut old_manifest)?;
+	}
+
+	output.flush()?;
+	Ok(changes)
+}
+
+/// Returns the next tile of the old manifest, if there is one.
+fn next_tile(manifest: &mut Option<ManifestReader>) -> Result<Option<(TileCoord3, String)>> {
+	match manifest {
+		Some(manifest) => manifest.next_tile(),
+		None => Ok(None),
+	}
+}
+
+/// Uploads and deletes the changed tiles, and updates the metadata.
+async fn apply_changes(
+	reader: &dyn TilesReaderTrait,
+	target: &dyn SyncTarget,
+	changes: &Changes,
+	concurrency: usize,
+) -> Result<()> {
+	let parameters = reader.get_parameters();
+	let extension = format!(
+		"{}{}",
+		parameters.tile_format.extension(),
+		parameters.tile_compression.extension()
+	);
+	let get_path = |coord: &TileCoord3| format!("{}/{}/{}{extension}", coord.z, coord.x, coord.y);
+
+	let tilejson = compress(reader.get_tilejson().as_blob(), &parameters.tile_compression)?;
+	target
+		.write(
+			&format!("tiles.json{}", parameters.tile_compression.extension()),
+			tilejson,
+		)
+		.await?;
+
+	let mut progress = get_progress_bar("sync tiles", (changes.upload.len() + changes.delete.len()) as u64);
+
+	let mut uploads = stream::iter(changes.upload.iter())
+		.map(|coord| async move {
+			let blob = reader
+				.get_tile_data(coord)
+				.await?
+				.with_context(|| format!("tile {coord:?} disappeared from the source"))?;
+			target.write(&get_path(coord), blob).await
+		})
+		.buffer_unordered(concurrency);
+	while let Some(result) = uploads.next().await {
+		result?;
+		progress.inc(1);
+	}
+
+	let mut deletions = stream::iter(changes.delete.iter())
+		.map(|coord| target.delete(get_path(coord)))
+		.buffer_unordered(concurrency);
+	while let Some(result) = deletions.next().await {
+		result?;
+		progress.inc(1);
+	}
+
+	progress.finish();
+	Ok(())
+}
+
+/// A destination of a sync.
+#[async_trait]
+trait SyncTarget: Send + Sync {
+	/// Reads a file. Returns `None` if it does not exist.
+	async fn read(&self, path: &str) -> Result<Option<Blob>>;
+	async fn write(&self, path: &str, blob: Blob) -> Result<()>;
+	/// Deletes a file. Missing files are ignored.
+	async fn delete(&self, path: String) -> Result<()>;
+}
+
+/// Writes into a local directory.
+struct LocalTarget {
+	directory: PathBuf,
+}
+
+impl LocalTarget {
+	fn new(directory: &Path) -> Result<LocalTarget> {
+		fs::create_dir_all(directory).with_context(|| format!("creating directory {directory:?}"))?;
+		Ok(LocalTarget {
+			directory: directory.to_path_buf(),
+		})
+	}
+}
+
+#[async_trait]
+impl SyncTarget for LocalTarget {
+	async fn read(&self, path: &str) -> Result<Option<Blob>> {
+		let path = self.directory.join(path);
+		Ok(if path.exists() {
+			Some(Blob::from(fs::read(path)?))
+		} else {
+			None
+		})
+	}
+
+	async fn write(&self, path: &str, blob: Blob) -> Result<()> {
+		let path = self.directory.join(path);
+		fs::create_dir_all(path.parent().unwrap())?;
+		fs::write(&path, blob.as_slice()).with_context(|| format!("writing {path:?}"))
+	}
+
+	async fn delete(&self, path: String) -> Result<()> {
+		let path = self.directory.join(path);
+		if path.exists() {
+			fs::remove_file(&path).with_context(|| format!("deleting {path:?}"))?;
+		}
+		Ok(())
+	}
+}
+
+/// Writes to a web server with PUT and DELETE requests.
+struct HttpTarget {
+	client: Client,
+	base_url: String,
+	limiter: Option<BandwidthLimiter>,
+}
+
+impl HttpTarget {
+	fn new(base_url: &str, headers: &[String], max_upload_rate: Option<u64>) -> Result<HttpTarget> {
+		let mut header_map = HeaderMap::new();
+		for header in headers {
+			let (name, value) = header
+				.split_once(':')
+				.with_context(|| format!("--header expects 'name: value', but got {header:?}"))?;
+			header_map.insert(
+				HeaderName::from_bytes(name.trim().as_bytes())?,
+				HeaderValue::from_str(value.trim())?,
+			);
+		}
+
+		Ok(HttpTarget {
+			client: Client::builder().default_headers(header_map).build()?,
+			base_url: base_url.trim_end_matches('/').to_owned(),
+			limiter: max_upload_rate.map(BandwidthLimiter::new).transpose()?,
+		})
+	}
+
+	fn get_url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url)
+	}
+}
+
+#[async_trait]
+impl SyncTarget for HttpTarget {
+	async fn read(&self, path: &str) -> Result<Option<Blob>> {
+		let url = self.get_url(path);
+		let response = self.client.get(&url).send().await?;
+		match response.status() {
+			StatusCode::NOT_FOUND => Ok(None),
+			status if status.is_success() => Ok(Some(Blob::from(response.bytes().await?.to_vec()))),
+			status => bail!("status {status} when reading {url}"),
+		}
+	}
+
+	async fn write(&self, path: &str, blob: Blob) -> Result<()> {
+		let url = self.get_url(path);
+		if let Some(limiter) = &self.limiter {
+			limiter.throttle(blob.len()).await;
+		}
+		let status = self.client.put(&url).body(blob.into_vec()).send().await?.status();
+		ensure!(status.is_success(), "status {status} when writing {url}");
+		Ok(())
+	}
+
+	async fn delete(&self, path: String) -> Result<()> {
+		let url = self.get_url(&path);
+		let status = self.client.delete(&url).send().await?.status();
+		ensure!(
+			status.is_success() || status == StatusCode::NOT_FOUND,
+			"status {status} when deleting {url}"
+		);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::run_command;
+	use assert_fs::TempDir;
+	use axum::{
+		body::Bytes,
+		extract::{Path as UrlPath, State},
+		http::StatusCode as AxumStatusCode,
+		routing::get,
+		Router,
+	};
+	use std::{
+		collections::HashMap,
+		sync::{Arc, Mutex},
+	};
+
+	const SOURCE: &str = "../testdata/berlin.mbtiles";
+
+	fn sync(destination: &str, extra: &[&str]) -> Result<()> {
+		let mut args = vec!["versatiles", "sync", "-q", SOURCE, destination];
+		args.extend_from_slice(extra);
+		run_command(args)?;
+		Ok(())
+	}
+
+	fn count_files(dir: &Path) -> usize {
+		fs::read_dir(dir)
+			.unwrap()
+			.map(|entry| {
+				let path = entry.unwrap().path();
+				if path.is_dir() {
+					count_files(&path)
+				} else {
+					1
+				}
+			})
+			.sum()
+	}
+
+	#[test]
+	fn local() -> Result<()> {
+		let dir = TempDir::new()?;
+		let destination = dir.path().join("berlin");
+		let destination_str = destination.to_str().unwrap();
+
+		sync(destination_str, &[])?;
+		let manifest = fs::read_to_string(destination.join(MANIFEST_NAME))?;
+		let tile_count = manifest.lines().count() - 1;
+		// tiles, tiles.json and manifest
+		assert_eq!(count_files(&destination), tile_count + 2);
+
+		// the destination is a directory container
+		let reader = tokio::runtime::Runtime::new()?.block_on(get_reader(destination_str))?;
+		assert_eq!(reader.get_parameters().tile_format.as_str(), "pbf");
+
+		// change the hash of one tile, remove another tile and add an unknown tile to the manifest
+		let mut lines: Vec<String> = manifest.lines().map(String::from).collect();
+		lines[1] = lines[1].replace("\"hash\":\"", "\"hash\":\"00");
+		lines.remove(2);
+		lines.push(String::from("{\"z\":20,\"x\":0,\"y\":0,\"hash\":\"00\"}"));
+		fs::write(destination.join(MANIFEST_NAME), lines.join("\n"))?;
+		fs::create_dir_all(destination.join("20/0"))?;
+		fs::write(destination.join("20/0/0.pbf.gz"), "old")?;
+
+		sync(destination_str, &["--dry-run"])?;
+		assert!(destination.join("20/0/0.pbf.gz").exists());
+
+		sync(destination_str, &[])?;
+		assert!(!destination.join("20/0/0.pbf.gz").exists());
+		assert_eq!(fs::read_to_string(destination.join(MANIFEST_NAME))?, manifest);
+		assert_eq!(count_files(&destination), tile_count + 2);
+		Ok(())
+	}
+
+	#[test]
+	fn local_manifest() -> Result<()> {
+		let dir = TempDir::new()?;
+		let destination = dir.path().join("berlin");
+		let manifest = dir.path().join("berlin.jsonl");
+		let manifest_str = manifest.to_str().unwrap();
+
+		sync(destination.to_str().unwrap(), &["--manifest", manifest_str])?;
+		assert!(manifest.exists());
+		assert!(!destination.join(MANIFEST_NAME).exists());
+
+		// nothing changed, so no tile is written
+		fs::remove_dir_all(&destination)?;
+		sync(destination.to_str().unwrap(), &["--manifest", manifest_str])?;
+		assert_eq!(count_files(&destination), 1);
+		Ok(())
+	}
+
+	#[test]
+	fn http() -> Result<()> {
+		type Files = Arc<Mutex<HashMap<String, Bytes>>>;
+		let files: Files = Files::default();
+
+		async fn get_file(UrlPath(path): UrlPath<String>, State(files): State<Files>) -> (AxumStatusCode, Bytes) {
+			match files.lock().unwrap().get(path.trim_start_matches('/')) {
+				Some(data) => (AxumStatusCode::OK, data.clone()),
+				None => (AxumStatusCode::NOT_FOUND, Bytes::new()),
+			}
+		}
+		async fn put_file(UrlPath(path): UrlPath<String>, State(files): State<Files>, body: Bytes) -> AxumStatusCode {
+			files
+				.lock()
+				.unwrap()
+				.insert(path.trim_start_matches('/').to_owned(), body);
+			AxumStatusCode::CREATED
+		}
+		async fn delete_file(UrlPath(path): UrlPath<String>, State(files): State<Files>) -> AxumStatusCode {
+			files.lock().unwrap().remove(path.trim_start_matches('/'));
+			AxumStatusCode::NO_CONTENT
+		}
+
+		let runtime = tokio::runtime::Runtime::new()?;
+		let app = Router::new()
+			.route("/bucket/{*path}", get(get_file).put(put_file).delete(delete_file))
+			.with_state(files.clone());
+		runtime.spawn(async {
+			let listener = tokio::net::TcpListener::bind("127.0.0.1:50011").await.unwrap();
+			axum::serve(listener, app).await.unwrap();
+		});
+
+		sync(
+			"http://127.0.0.1:50011/bucket/",
+			&["--header", "Authorization: Bearer secret"],
+		)?;
+		let count = files.lock().unwrap().len();
+		assert!(count > 2);
+		assert!(files.lock().unwrap().contains_key(MANIFEST_NAME));
+		assert!(files.lock().unwrap().contains_key("tiles.json.gz"));
+
+		// a second sync only reads the manifest and rewrites the metadata
+		files.lock().unwrap().remove("0/0/0.pbf.gz");
+		sync("http://127.0.0.1:50011/bucket", &["--max-upload-rate", "100000000"])?;
+		assert_eq!(files.lock().unwrap().len(), count - 1);
+
+		assert!(HttpTarget::new("http://localhost", &[String::from("invalid")], None).is_err());
+		assert!(HttpTarget::new("http://localhost", &[], Some(0)).is_err());
+		Ok(())
+	}
+}
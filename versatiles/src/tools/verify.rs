@@ -0,0 +1,85 @@
+//! Verifies the signature of a `*.versatiles` container, as written by `versatiles sign`.
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use versatiles_container::{parse_public_key, ContainerSignature, VersaTilesReader};
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// signed *.versatiles container
+	#[arg(required = true)]
+	container: PathBuf,
+
+	/// trusted Ed25519 public key as hex string, as printed by "versatiles sign"
+	#[arg(long, value_name = "HEX", required = true)]
+	public_key: String,
+
+	/// signature file, defaults to "<container>.sig"
+	#[arg(long, value_name = "FILE")]
+	signature: Option<PathBuf>,
+
+	/// only verify the header, the meta data and the block index, but not the tiles
+	#[arg(long)]
+	index_only: bool,
+}
+
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	let public_key = parse_public_key(&arguments.public_key)?;
+	let signature_path = match &arguments.signature {
+		Some(path) => path.clone(),
+		None => ContainerSignature::get_path(&arguments.container),
+	};
+	let signature = ContainerSignature::read_file(&signature_path)?;
+
+	let reader = VersaTilesReader::open_path(&std::path::absolute(&arguments.container)?).await?;
+	signature
+		.verify(&reader, &public_key, !arguments.index_only)
+		.await
+		.with_context(|| format!("verifying {:?}", arguments.container))?;
+
+	eprintln!("signature of {:?} is valid", arguments.container);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tests::run_command;
+	use anyhow::Result;
+	use assert_fs::TempDir;
+	use std::fs;
+	use versatiles_container::{format_public_key, get_public_key};
+
+	#[test]
+	fn sign_and_verify() -> Result<()> {
+		let dir = TempDir::new()?;
+		let container = dir.path().join("berlin.versatiles");
+		let container = container.to_str().unwrap();
+		let key = dir.path().join("key.pk8");
+		let key = key.to_str().unwrap();
+
+		run_command(vec![
+			"versatiles",
+			"convert",
+			"--max-zoom=3",
+			"../testdata/berlin.mbtiles",
+			container,
+		])?;
+		run_command(vec!["versatiles", "sign", container, "--key", key, "--generate-key"])?;
+		assert!(run_command(vec!["versatiles", "sign", container, "--key", key, "--generate-key"]).is_err());
+
+		let public_key = format_public_key(&get_public_key(&fs::read(key)?)?);
+		run_command(vec!["versatiles", "verify", container, "--public-key", &public_key])?;
+
+		let other_key = "00".repeat(32);
+		assert!(run_command(vec!["versatiles", "verify", container, "--public-key", &other_key]).is_err());
+
+		// flip a bit in the middle of the container
+		let mut data = fs::read(container)?;
+		let index = data.len() / 2;
+		data[index] ^= 1;
+		fs::write(container, data)?;
+		assert!(run_command(vec!["versatiles", "verify", container, "--public-key", &public_key]).is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,117 @@
+//! A tile cache that stores tiles as files in a directory.
+//!
+//! Tiles are stored as `{z}/{x}/{y}.tile`. Tiles that are missing in the source are marked by an empty
+//! `{z}/{x}/{y}.none` file. The cache is not cleaned up, so the directory can be reused between runs.
+
+use super::TileCacheTrait;
+use anyhow::{Context, Result};
+use std::{
+	fs,
+	io::ErrorKind,
+	path::{Path, PathBuf},
+	process,
+	sync::atomic::{AtomicU64, Ordering},
+};
+use versatiles_core::types::{Blob, TileCoord3};
+
+/// Numbers the temporary files, so that concurrent writes of the same tile don't share one.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Stores tiles as files in a directory.
+#[derive(Debug)]
+pub struct DiskTileCache {
+	directory: PathBuf,
+}
+
+impl DiskTileCache {
+	/// Creates a new cache. The directory is created if necessary.
+	pub fn new(directory: &Path) -> Result<DiskTileCache> {
+		fs::create_dir_all(directory).with_context(|| format!("creating cache directory {directory:?}"))?;
+		Ok(DiskTileCache {
+			directory: directory.to_path_buf(),
+		})
+	}
+
+	fn get_path(&self, coord: &TileCoord3, extension: &str) -> PathBuf {
+		self
+			.directory
+			.join(coord.z.to_string())
+			.join(coord.x.to_string())
+			.join(format!("{}.{extension}", coord.y))
+	}
+}
+
+impl TileCacheTrait for DiskTileCache {
+	fn get(&self, coord: &TileCoord3) -> Result<Option<Option<Blob>>> {
+		match fs::read(self.get_path(coord, "tile")) {
+			Ok(data) => return Ok(Some(Some(Blob::from(data)))),
+			Err(e) if e.kind() == ErrorKind::NotFound => {}
+			Err(e) => return Err(e).with_context(|| format!("reading cached tile {coord:?}")),
+		}
+
+		Ok(if self.get_path(coord, "none").exists() {
+			Some(None)
+		} else {
+			None
+		})
+	}
+
+	fn set(&self, coord: &TileCoord3, blob: &Option<Blob>) -> Result<()> {
+		let path = self.get_path(coord, if blob.is_some() { "tile" } else { "none" });
+		fs::create_dir_all(path.parent().unwrap())?;
+
+		// write to a temporary file first, so that concurrent readers never see partial tiles
+		let temp_number = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+		let temp_path = path.with_extension(format!("{}.{temp_number}.tmp", process::id()));
+		fs::write(&temp_path, blob.as_ref().map_or(&[] as &[u8], |b| b.as_slice()))
+			.with_context(|| format!("writing cached tile {coord:?}"))?;
+		fs::rename(&temp_path, &path)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+
+	#[test]
+	fn get_and_set() -> Result<()> {
+		let dir = TempDir::new()?;
+		let cache = DiskTileCache::new(&dir.path().join("cache"))?;
+		let coord = TileCoord3::new(3, 4, 5)?;
+
+		assert_eq!(cache.get(&coord)?, None);
+		cache.set(&coord, &Some(Blob::from("tile")))?;
+		assert_eq!(cache.get(&coord)?, Some(Some(Blob::from("tile"))));
+		assert!(dir.path().join("cache/5/3/4.tile").exists());
+
+		let coord = TileCoord3::new(0, 0, 0)?;
+		cache.set(&coord, &None)?;
+		assert_eq!(cache.get(&coord)?, Some(None));
+
+		// a second cache instance reuses the directory
+		let cache = DiskTileCache::new(&dir.path().join("cache"))?;
+		assert_eq!(cache.get(&coord)?, Some(None));
+		Ok(())
+	}
+
+	#[test]
+	fn concurrent_set() -> Result<()> {
+		let dir = TempDir::new()?;
+		let cache = DiskTileCache::new(dir.path())?;
+		let coord = TileCoord3::new(1, 2, 3)?;
+
+		std::thread::scope(|scope| {
+			let handles: Vec<_> = (0..8)
+				.map(|_| scope.spawn(|| (0..50).try_for_each(|_| cache.set(&coord, &Some(Blob::from("tile"))))))
+				.collect();
+			handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+		})?;
+
+		assert_eq!(cache.get(&coord)?, Some(Some(Blob::from("tile"))));
+		// no temporary files are left behind
+		assert_eq!(fs::read_dir(dir.path().join("3/1"))?.count(), 1);
+		Ok(())
+	}
+}
@@ -0,0 +1,145 @@
+//! An in-memory tile cache with a size limit in bytes.
+
+use super::TileCacheTrait;
+use anyhow::Result;
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::Mutex,
+};
+use versatiles_core::types::{Blob, TileCoord3};
+
+/// Estimated memory usage of an entry, in addition to the tile data.
+const ENTRY_OVERHEAD: u64 = 64;
+
+#[derive(Debug, Default)]
+struct Entries {
+	/// Cached tiles and the index of their last access.
+	tiles: HashMap<TileCoord3, (Option<Blob>, u64)>,
+	/// Access indices in order, to find the least recently used tiles.
+	order: BTreeMap<u64, TileCoord3>,
+	last_index: u64,
+	size: u64,
+}
+
+/// Keeps tiles in memory and evicts the least recently used tiles when the size limit is exceeded.
+#[derive(Debug)]
+pub struct MemoryTileCache {
+	max_size: u64,
+	entries: Mutex<Entries>,
+}
+
+impl MemoryTileCache {
+	/// Creates a new cache.
+	///
+	/// # Arguments
+	/// * `max_size` - The maximum size of all cached tiles in bytes.
+	pub fn new(max_size: u64) -> MemoryTileCache {
+		MemoryTileCache {
+			max_size,
+			entries: Mutex::new(Entries::default()),
+		}
+	}
+
+	/// Returns the estimated size of all cached tiles in bytes.
+	pub fn size(&self) -> u64 {
+		self.entries.lock().unwrap().size
+	}
+}
+
+fn entry_size(blob: &Option<Blob>) -> u64 {
+	ENTRY_OVERHEAD + blob.as_ref().map_or(0, |b| b.len())
+}
+
+impl TileCacheTrait for MemoryTileCache {
+	fn get(&self, coord: &TileCoord3) -> Result<Option<Option<Blob>>> {
+		let mut entries = self.entries.lock().unwrap();
+		entries.last_index += 1;
+		let index = entries.last_index;
+
+		let Some((blob, old_index)) = entries.tiles.get_mut(coord) else {
+			return Ok(None);
+		};
+		let old_index = std::mem::replace(old_index, index);
+		let blob = blob.clone();
+
+		entries.order.remove(&old_index);
+		entries.order.insert(index, *coord);
+		Ok(Some(blob))
+	}
+
+	fn set(&self, coord: &TileCoord3, blob: &Option<Blob>) -> Result<()> {
+		let size = entry_size(blob);
+		if size > self.max_size {
+			return Ok(());
+		}
+
+		let mut entries = self.entries.lock().unwrap();
+		entries.last_index += 1;
+		let index = entries.last_index;
+
+		if let Some((old_blob, old_index)) = entries.tiles.insert(*coord, (blob.clone(), index)) {
+			entries.order.remove(&old_index);
+			entries.size -= entry_size(&old_blob);
+		}
+		entries.order.insert(index, *coord);
+		entries.size += size;
+
+		while entries.size > self.max_size {
+			let (_, oldest) = entries.order.pop_first().unwrap();
+			let (old_blob, _) = entries.tiles.remove(&oldest).unwrap();
+			entries.size -= entry_size(&old_blob);
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coord(x: u32) -> TileCoord3 {
+		TileCoord3::new(x, 0, 10).unwrap()
+	}
+
+	#[test]
+	fn get_and_set() -> Result<()> {
+		let cache = MemoryTileCache::new(1000);
+		assert_eq!(cache.get(&coord(0))?, None);
+
+		cache.set(&coord(0), &Some(Blob::from("tile")))?;
+		cache.set(&coord(1), &None)?;
+		assert_eq!(cache.get(&coord(0))?, Some(Some(Blob::from("tile"))));
+		assert_eq!(cache.get(&coord(1))?, Some(None));
+		assert_eq!(cache.size(), 2 * ENTRY_OVERHEAD + 4);
+		Ok(())
+	}
+
+	#[test]
+	fn evicts_least_recently_used() -> Result<()> {
+		let cache = MemoryTileCache::new(3 * (ENTRY_OVERHEAD + 10));
+		let blob = Some(Blob::from(vec![0u8; 10]));
+
+		cache.set(&coord(0), &blob)?;
+		cache.set(&coord(1), &blob)?;
+		cache.set(&coord(2), &blob)?;
+		cache.get(&coord(0))?;
+		cache.set(&coord(3), &blob)?;
+
+		assert!(cache.get(&coord(0))?.is_some());
+		assert!(cache.get(&coord(1))?.is_none());
+		assert!(cache.get(&coord(2))?.is_some());
+		assert!(cache.get(&coord(3))?.is_some());
+		assert_eq!(cache.size(), 3 * (ENTRY_OVERHEAD + 10));
+		Ok(())
+	}
+
+	#[test]
+	fn ignores_oversized_tiles() -> Result<()> {
+		let cache = MemoryTileCache::new(100);
+		cache.set(&coord(0), &Some(Blob::from(vec![0u8; 100])))?;
+		assert_eq!(cache.get(&coord(0))?, None);
+		assert_eq!(cache.size(), 0);
+		Ok(())
+	}
+}
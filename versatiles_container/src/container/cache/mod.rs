@@ -0,0 +1,45 @@
+//! Caching of tiles
+//!
+//! This module provides [`CachedReader`], a reader that wraps another reader and caches its tiles,
+//! e.g. to speed up a slow remote source. Missing tiles are cached as well.
+//!
+//! ## Submodules
+//! - `memory`: An in-memory cache that evicts the least recently used tiles.
+//! - `disk`: A cache that stores tiles as files in a directory.
+//! - `reader`: The caching reader.
+//!
+//! ## Usage
+//!
+//! ```rust
+//! use versatiles_container::{CachePolicy, CachedReader};
+//! use versatiles_core::types::TilesReaderTrait;
+//!
+//! fn add_cache(reader: Box<dyn TilesReaderTrait>) -> anyhow::Result<Box<dyn TilesReaderTrait>> {
+//!     Ok(CachedReader::new(reader, CachePolicy::Memory { max_size: 100_000_000 })?.boxed())
+//! }
+//! ```
+
+mod disk;
+mod memory;
+mod reader;
+
+pub use disk::*;
+pub use memory::*;
+pub use reader::*;
+
+use anyhow::Result;
+use std::fmt::Debug;
+use versatiles_core::types::{Blob, TileCoord3};
+
+/// A storage for cached tiles.
+///
+/// Implement this trait to plug a custom backend into a [`CachedReader`].
+pub trait TileCacheTrait: Debug + Send + Sync {
+	/// Returns the cached entry of a tile.
+	///
+	/// `None` if the tile is not cached, `Some(None)` if the tile is cached as missing.
+	fn get(&self, coord: &TileCoord3) -> Result<Option<Option<Blob>>>;
+
+	/// Stores a tile, or `None` if the tile is missing in the source.
+	fn set(&self, coord: &TileCoord3, blob: &Option<Blob>) -> Result<()>;
+}
@@ -0,0 +1,178 @@
+//! A reader that caches the tiles of another reader.
+
+use super::{DiskTileCache, MemoryTileCache, TileCacheTrait};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::warn;
+use std::{path::PathBuf, time::SystemTime};
+use versatiles_core::{tilejson::TileJSON, types::*};
+
+/// Defines where a [`CachedReader`] keeps its tiles.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CachePolicy {
+	/// Keeps tiles in memory, up to `max_size` bytes. The least recently used tiles are evicted first.
+	Memory { max_size: u64 },
+	/// Stores tiles as files in a directory.
+	Disk { directory: PathBuf },
+}
+
+/// Wraps a reader and caches the tiles requested with [`get_tile_data`](TilesReaderTrait::get_tile_data).
+///
+/// Tile streams are passed through to the inner reader without caching, since they are typically
+/// used to read each tile only once.
+#[derive(Debug)]
+pub struct CachedReader {
+	inner: Box<dyn TilesReaderTrait>,
+	cache: Box<dyn TileCacheTrait>,
+}
+
+impl CachedReader {
+	/// Creates a new cached reader with one of the built-in cache backends.
+	pub fn new(inner: Box<dyn TilesReaderTrait>, policy: CachePolicy) -> Result<CachedReader> {
+		let cache: Box<dyn TileCacheTrait> = match policy {
+			CachePolicy::Memory { max_size } => Box::new(MemoryTileCache::new(max_size)),
+			CachePolicy::Disk { directory } => Box::new(DiskTileCache::new(&directory)?),
+		};
+		Ok(CachedReader::new_with_cache(inner, cache))
+	}
+
+	/// Creates a new cached reader with a custom cache backend.
+	pub fn new_with_cache(inner: Box<dyn TilesReaderTrait>, cache: Box<dyn TileCacheTrait>) -> CachedReader {
+		CachedReader { inner, cache }
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for CachedReader {
+	fn get_source_name(&self) -> &str {
+		self.inner.get_source_name()
+	}
+
+	fn get_container_name(&self) -> &str {
+		self.inner.get_container_name()
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		self.inner.get_parameters()
+	}
+
+	fn override_compression(&mut self, tile_compression: TileCompression) {
+		self.inner.override_compression(tile_compression)
+	}
+
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.inner.override_format(tile_format)
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		self.inner.get_tilejson()
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		match self.cache.get(coord) {
+			Ok(Some(entry)) => return Ok(entry),
+			Ok(None) => {}
+			Err(e) => warn!("failed to read tile {coord:?} from cache: {e:#}"),
+		}
+
+		let blob = self.inner.get_tile_data(coord).await?;
+
+		if let Err(e) = self.cache.set(coord, &blob) {
+			warn!("failed to write tile {coord:?} to cache: {e:#}");
+		}
+		Ok(blob)
+	}
+
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		self.inner.has_tile(coord).await
+	}
+
+	async fn get_tile_mtime(&self, coord: &TileCoord3) -> Result<Option<SystemTime>> {
+		self.inner.get_tile_mtime(coord).await
+	}
+
+	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		self.inner.get_bbox_tile_stream(bbox).await
+	}
+
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
+		self.inner.get_bbox_tile_try_stream(bbox).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{MockTilesReader, MockTilesReaderProfile};
+	use std::sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	};
+
+	/// Counts the requests to the inner reader.
+	#[derive(Debug)]
+	struct CountingReader {
+		inner: MockTilesReader,
+		count: Arc<AtomicU64>,
+	}
+
+	#[async_trait]
+	impl TilesReaderTrait for CountingReader {
+		fn get_source_name(&self) -> &str {
+			self.inner.get_source_name()
+		}
+		fn get_container_name(&self) -> &str {
+			self.inner.get_container_name()
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			self.inner.get_parameters()
+		}
+		fn override_compression(&mut self, tile_compression: TileCompression) {
+			self.inner.override_compression(tile_compression)
+		}
+		fn get_tilejson(&self) -> &TileJSON {
+			self.inner.get_tilejson()
+		}
+		async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+			self.count.fetch_add(1, Ordering::Relaxed);
+			self.inner.get_tile_data(coord).await
+		}
+	}
+
+	async fn test_policy(policy: CachePolicy) -> Result<()> {
+		let count = Arc::new(AtomicU64::new(0));
+		let counting_reader = CountingReader {
+			inner: MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?,
+			count: count.clone(),
+		};
+		let reader = CachedReader::new(Box::new(counting_reader), policy)?;
+		assert_eq!(reader.get_container_name(), "dummy_container");
+
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let blob1 = reader.get_tile_data(&coord).await?;
+		let blob2 = reader.get_tile_data(&coord).await?;
+		assert!(blob1.is_some());
+		assert_eq!(blob1, blob2);
+
+		let invalid = TileCoord3 { x: 9, y: 9, z: 1 };
+		assert_eq!(reader.get_tile_data(&invalid).await?, None);
+		assert_eq!(reader.get_tile_data(&invalid).await?, None);
+
+		assert_eq!(count.load(Ordering::Relaxed), 2);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn memory() -> Result<()> {
+		test_policy(CachePolicy::Memory { max_size: 1_000_000 }).await
+	}
+
+	#[tokio::test]
+	async fn disk() -> Result<()> {
+		let dir = assert_fs::TempDir::new()?;
+		test_policy(CachePolicy::Disk {
+			directory: dir.path().to_path_buf(),
+		})
+		.await
+	}
+}
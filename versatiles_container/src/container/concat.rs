@@ -0,0 +1,186 @@
+//! A reader that combines containers covering disjoint parts of a tileset.
+//!
+//! Use it to merge the partial containers of a conversion that was spread across several machines
+//! with `versatiles convert --partition`.
+
+use anyhow::{ensure, Result};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use versatiles_core::{tilejson::TileJSON, types::*};
+
+/// Reads the tiles of several containers as one container.
+///
+/// The containers must have the same tile format and compression, and their bounding box pyramids
+/// must not overlap, so every tile is read from exactly one container.
+#[derive(Debug)]
+pub struct ConcatReader {
+	readers: Vec<Box<dyn TilesReaderTrait>>,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl ConcatReader {
+	pub fn new(readers: Vec<Box<dyn TilesReaderTrait>>) -> Result<ConcatReader> {
+		ensure!(!readers.is_empty(), "at least one container is required");
+
+		let first = readers[0].get_parameters();
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty_in_grid(first.bbox_pyramid.get_grid());
+		let mut tilejson = TileJSON::default();
+
+		for (index, reader) in readers.iter().enumerate() {
+			let name = reader.get_source_name();
+			let parameters = reader.get_parameters();
+			ensure!(
+				parameters.tile_format == first.tile_format,
+				"tile format of {name} ({}) differs from {} ({})",
+				parameters.tile_format,
+				readers[0].get_source_name(),
+				first.tile_format
+			);
+			ensure!(
+				parameters.tile_compression == first.tile_compression,
+				"tile compression of {name} ({}) differs from {} ({})",
+				parameters.tile_compression,
+				readers[0].get_source_name(),
+				first.tile_compression
+			);
+			for other in readers.iter().take(index) {
+				let mut overlap = parameters.bbox_pyramid.clone();
+				overlap.intersect(&other.get_parameters().bbox_pyramid);
+				ensure!(
+					overlap.is_empty(),
+					"{name} and {} overlap, so they are not partitions of the same conversion",
+					other.get_source_name()
+				);
+			}
+
+			bbox_pyramid.include_bbox_pyramid(&parameters.bbox_pyramid);
+			tilejson.merge(reader.get_tilejson())?;
+		}
+		tilejson.update_from_pyramid(&bbox_pyramid);
+
+		Ok(ConcatReader {
+			parameters: TilesReaderParameters::new(first.tile_format, first.tile_compression, bbox_pyramid),
+			readers,
+			tilejson,
+		})
+	}
+
+	/// Returns the readers with tiles in the bounding box, together with the part of the bounding box they cover.
+	fn get_parts(&self, bbox: &TileBBox) -> Vec<(&dyn TilesReaderTrait, TileBBox)> {
+		let mut parts = Vec::new();
+		for reader in self.readers.iter() {
+			let mut part = bbox.clone();
+			if part.intersect_pyramid(&reader.get_parameters().bbox_pyramid).is_ok() && !part.is_empty() {
+				parts.push((reader.as_ref(), part));
+			}
+		}
+		parts
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for ConcatReader {
+	fn get_source_name(&self) -> &str {
+		self.readers[0].get_source_name()
+	}
+
+	fn get_container_name(&self) -> &str {
+		"concat"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, tile_compression: TileCompression) {
+		for reader in self.readers.iter_mut() {
+			reader.override_compression(tile_compression);
+		}
+		self.parameters.tile_compression = tile_compression;
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		for reader in self.readers.iter() {
+			if reader.get_parameters().bbox_pyramid.contains_coord(coord) {
+				return reader.get_tile_data(coord).await;
+			}
+		}
+		Ok(None)
+	}
+
+	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let mut streams = Vec::new();
+		for (reader, part) in self.get_parts(&bbox) {
+			streams.push(reader.get_bbox_tile_stream(part).await.stream);
+		}
+		TileStream::from_stream(stream::iter(streams).flatten().boxed())
+	}
+
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
+		let mut streams = Vec::new();
+		for (reader, part) in self.get_parts(&bbox) {
+			streams.push(reader.get_bbox_tile_try_stream(part).await.stream);
+		}
+		TryTileStream::from_stream(stream::iter(streams).flatten().boxed())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::MockTilesReader;
+
+	fn partition(index: u32, count: u32, tile_compression: TileCompression) -> Result<Box<dyn TilesReaderTrait>> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_full(4);
+		bbox_pyramid.intersect_partition(index, count);
+		let parameters = TilesReaderParameters::new(TileFormat::PBF, tile_compression, bbox_pyramid);
+		Ok(MockTilesReader::new_mock(parameters)?.boxed())
+	}
+
+	#[tokio::test]
+	async fn concat() -> Result<()> {
+		let readers = (0..3)
+			.map(|index| partition(index, 3, TileCompression::Gzip))
+			.collect::<Result<Vec<_>>>()?;
+		let reader = ConcatReader::new(readers)?;
+		assert_eq!(reader.get_container_name(), "concat");
+		assert_eq!(reader.get_parameters().bbox_pyramid, TileBBoxPyramid::new_full(4));
+
+		let bbox = TileBBox::new_full(4)?;
+		let tiles = reader.get_bbox_tile_stream(bbox.clone()).await.collect().await;
+		assert_eq!(tiles.len(), 256);
+
+		let tiles = reader
+			.get_bbox_tile_try_stream(bbox)
+			.await
+			.stream
+			.collect::<Vec<_>>()
+			.await;
+		assert_eq!(tiles.len(), 256);
+
+		assert!(reader.get_tile_data(&TileCoord3::new(15, 3, 4)?).await?.is_some());
+		assert!(reader.get_tile_data(&TileCoord3::new(0, 0, 5)?).await?.is_none());
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_partitions() -> Result<()> {
+		assert!(ConcatReader::new(vec![]).is_err());
+		assert!(ConcatReader::new(vec![
+			partition(0, 2, TileCompression::Gzip)?,
+			partition(1, 2, TileCompression::Brotli)?
+		])
+		.is_err());
+		assert!(ConcatReader::new(vec![
+			partition(0, 2, TileCompression::Gzip)?,
+			partition(0, 2, TileCompression::Gzip)?
+		])
+		.is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,547 @@
+//! A builder to run conversions from applications, with the same features as `versatiles convert`.
+//!
+//! A [`ConversionPipeline`] reads tiles from a source, converts them according to [`TilesConverterParameters`],
+//! runs optional [`TileTransform`]s and writes them to a [`ConversionTarget`]. Progress can be followed with a
+//! [`ProgressObserver`], and a running conversion can be stopped with a [`CancellationToken`]. A JSON report of
+//! every conversion can be written with [`ConversionPipeline::with_report`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use anyhow::Result;
+//! use versatiles_container::{get_reader, CancellationToken, ConversionPipeline, ConversionTarget};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let reader = get_reader("../testdata/berlin.mbtiles").await?;
+//!     let cancellation = CancellationToken::new();
+//!     let temp_path = std::env::temp_dir().join("conversion_pipeline.versatiles");
+//!
+//!     ConversionPipeline::new(reader, ConversionTarget::Path(temp_path))
+//!         .with_concurrency(2)
+//!         .with_cancellation(cancellation.clone())
+//!         .run()
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+
+use super::{conversion_report::ConversionReport, getters::write_to_path, *};
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use std::{
+	fmt::Debug,
+	io::BufWriter,
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+};
+use versatiles_core::types::{Blob, TileCoord3, TilesReaderTrait};
+
+/// Is notified about the progress of a conversion.
+pub trait ProgressObserver: Debug + Send + Sync {
+	/// Called once before the first tile, with the upper bound of the number of tiles.
+	fn on_start(&self, _max_tile_count: u64) {}
+
+	/// Called for every tile that is handed over to the writer, with its size in bytes.
+	fn on_tile(&self, coord: &TileCoord3, size: u64);
+
+	/// Called once after the output has been written completely.
+	fn on_finish(&self) {}
+}
+
+/// Cancels a running conversion. Clones share the same state.
+///
+/// A cancelled conversion stops after the tiles in flight and returns an error. Files are not replaced by the
+/// unfinished output, but a directory keeps the tiles that have been written so far.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+	cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+	pub fn new() -> CancellationToken {
+		CancellationToken::default()
+	}
+
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Relaxed)
+	}
+}
+
+/// Where a conversion writes its tiles.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConversionTarget {
+	/// A file or a directory. The container format is derived from the file extension, see `get_writer_name`.
+	Path(PathBuf),
+	/// Stdout, in the container format "tar" or "mbtiles", see `write_to_stdout`.
+	Stdout(String),
+}
+
+/// Converts the tiles of a source and writes them to a target.
+#[derive(Debug)]
+pub struct ConversionPipeline {
+	reader: Box<dyn TilesReaderTrait>,
+	target: ConversionTarget,
+	parameters: TilesConverterParameters,
+	transforms: Vec<Arc<dyn TileTransform>>,
+	tar_path_template: Option<TarPathTemplate>,
+	cipher: Option<TileCipher>,
+	versatiles_options: Option<VersaTilesWriterOptions>,
+	report_path: Option<PathBuf>,
+}
+
+impl ConversionPipeline {
+	/// Creates a pipeline that copies all tiles of `reader` to `target`.
+	pub fn new(reader: Box<dyn TilesReaderTrait>, target: ConversionTarget) -> ConversionPipeline {
+		ConversionPipeline {
+			reader,
+			target,
+			parameters: TilesConverterParameters::new_default(),
+			transforms: Vec::new(),
+			tar_path_template: None,
+			cipher: None,
+			versatiles_options: None,
+			report_path: None,
+		}
+	}
+
+	/// Replaces the converter parameters, e.g. compression, bounding boxes or tile size limits.
+	/// The transforms, concurrency, observer and cancellation set on the pipeline are kept.
+	pub fn with_parameters(mut self, parameters: TilesConverterParameters) -> ConversionPipeline {
+		let tile_transform = self.parameters.tile_transform.take();
+		let concurrency = self.parameters.concurrency.take();
+		let progress_observer = self.parameters.progress_observer.take();
+		let cancellation = self.parameters.cancellation.take();
+
+		self.parameters = TilesConverterParameters {
+			tile_transform: tile_transform.or(parameters.tile_transform),
+			concurrency: concurrency.or(parameters.concurrency),
+			progress_observer: progress_observer.or(parameters.progress_observer),
+			cancellation: cancellation.or(parameters.cancellation),
+			..parameters
+		};
+		self
+	}
+
+	/// Adds a transform. Transforms run in the order they were added, a dropped tile skips the following ones.
+	pub fn with_transform(mut self, transform: Arc<dyn TileTransform>) -> ConversionPipeline {
+		self.transforms.push(transform);
+		self
+	}
+
+	/// Sets the number of tiles that are recompressed and transformed concurrently.
+	pub fn with_concurrency(mut self, concurrency: usize) -> ConversionPipeline {
+		self.parameters.concurrency = Some(concurrency);
+		self
+	}
+
+	pub fn with_progress_observer(mut self, observer: Arc<dyn ProgressObserver>) -> ConversionPipeline {
+		self.parameters.progress_observer = Some(observer);
+		self
+	}
+
+	pub fn with_cancellation(mut self, cancellation: CancellationToken) -> ConversionPipeline {
+		self.parameters.cancellation = Some(cancellation);
+		self
+	}
+
+	/// Sets the layout of the tile paths. Only supported when writing a *.tar file.
+	pub fn with_tar_path_template(mut self, template: TarPathTemplate) -> ConversionPipeline {
+		self.tar_path_template = Some(template);
+		self
+	}
+
+	/// Encrypts the tiles. Only supported when writing a *.versatiles file.
+	pub fn with_cipher(mut self, cipher: TileCipher) -> ConversionPipeline {
+		self.cipher = Some(cipher);
+		self
+	}
+
+	/// Sets the internal layout, e.g. the tile order. Only supported when writing a *.versatiles file.
+	pub fn with_versatiles_options(mut self, options: VersaTilesWriterOptions) -> ConversionPipeline {
+		self.versatiles_options = Some(options);
+		self
+	}
+
+	/// Writes a JSON report to `path` after the conversion, also if it failed. It lists the parameters of the input
+	/// and the output, the written tiles per zoom level, the dropped and failed tiles, the file sizes, the wall time
+	/// and the throughput.
+	pub fn with_report(mut self, path: PathBuf) -> ConversionPipeline {
+		self.report_path = Some(path);
+		self
+	}
+
+	/// Returns the converter without writing anything, e.g. to inspect the effective settings.
+	pub fn into_converter(self) -> Result<TilesConvertReader> {
+		self.check_target()?;
+		get_converter(self.reader, self.parameters, self.transforms)
+	}
+
+	/// Runs the conversion.
+	///
+	/// # Errors
+	/// Returns an error if writing fails, if the conversion was cancelled, or if any tile failed,
+	/// see [`TilesConvertReader::finish`].
+	pub async fn run(mut self) -> Result<()> {
+		self.check_target()?;
+
+		let report = self.report_path.take().map(|path| {
+			let observer = self.parameters.progress_observer.take();
+			let report = Arc::new(ConversionReport::new(path, &*self.reader, &self.target, observer));
+			self.parameters.progress_observer = Some(report.clone());
+			report
+		});
+
+		let result = self.convert(report.as_deref()).await;
+		if let Some(report) = report {
+			report.write(&result)?;
+		}
+		result
+	}
+
+	async fn convert(self, report: Option<&ConversionReport>) -> Result<()> {
+		let ConversionPipeline {
+			reader,
+			target,
+			parameters,
+			transforms,
+			tar_path_template,
+			cipher,
+			versatiles_options,
+			report_path: _,
+		} = self;
+		let observer = parameters.progress_observer.clone();
+		let mut converter = get_converter(reader, parameters, transforms)?;
+
+		if let Some(report) = report {
+			report.set_output(&converter, get_target_writer_name(&target)?);
+		}
+
+		if let Some(observer) = &observer {
+			observer.on_start(converter.get_parameters().bbox_pyramid.count_tiles());
+		}
+
+		let result = write_to_target(&mut converter, target, tar_path_template, cipher, versatiles_options).await;
+		if let Some(report) = report {
+			report.set_tile_errors(&converter);
+		}
+		result?;
+
+		if let Some(observer) = &observer {
+			observer.on_finish();
+		}
+		converter.finish()
+	}
+
+	/// Checks that the tar path template, the cipher and the versatiles options are supported by the target.
+	fn check_target(&self) -> Result<()> {
+		let writer_name = get_target_writer_name(&self.target)?;
+		if self.tar_path_template.is_some() {
+			ensure!(
+				writer_name == "tar",
+				"a tar path template can only be used when writing a *.tar file"
+			);
+		}
+		if self.cipher.is_some() {
+			ensure!(
+				matches!(self.target, ConversionTarget::Path(_)) && writer_name == "versatiles",
+				"encryption can only be used when writing a *.versatiles file"
+			);
+		}
+		if self.versatiles_options.is_some() {
+			ensure!(
+				matches!(self.target, ConversionTarget::Path(_)) && writer_name == "versatiles",
+				"versatiles writer options can only be used when writing a *.versatiles file"
+			);
+		}
+		Ok(())
+	}
+}
+
+/// Returns the container format of the target, e.g. "versatiles".
+fn get_target_writer_name(target: &ConversionTarget) -> Result<&str> {
+	match target {
+		ConversionTarget::Stdout(format) => Ok(format.as_str()),
+		ConversionTarget::Path(path) => get_writer_name(path.to_str().context("output path is not valid UTF-8")?),
+	}
+}
+
+/// Writes the tiles of the converter to the target.
+async fn write_to_target(
+	converter: &mut TilesConvertReader,
+	target: ConversionTarget,
+	tar_path_template: Option<TarPathTemplate>,
+	cipher: Option<TileCipher>,
+	versatiles_options: Option<VersaTilesWriterOptions>,
+) -> Result<()> {
+	match target {
+		ConversionTarget::Stdout(format) => match tar_path_template {
+			Some(template) => {
+				TarTilesWriter::write_to_stream(converter, BufWriter::new(std::io::stdout()), template).await?
+			}
+			None => write_to_stdout(converter, &format).await?,
+		},
+		ConversionTarget::Path(path) => {
+			let path = std::path::absolute(&path)?;
+			let writer_name = get_writer_name(path.to_str().context("output path is not valid UTF-8")?)?;
+			if writer_name == "directory" {
+				DirectoryTilesWriter::write_to_path(converter, &path).await?;
+			} else {
+				let output = TempOutputFile::new(&path)?;
+				let temp_path = output.get_temp_path();
+				match (tar_path_template, &cipher, versatiles_options) {
+					(Some(template), _, _) => {
+						TarTilesWriter::write_to_path_with_template(converter, temp_path, template).await?
+					}
+					(None, None, None) => write_to_path(converter, temp_path, writer_name).await?,
+					(None, cipher, options) => {
+						let options = options.unwrap_or_default();
+						VersaTilesWriter::write_to_path_with_options(converter, temp_path, &options, cipher.as_ref()).await?
+					}
+				}
+				// an unfinished output must not replace an existing file
+				ensure!(!converter.is_cancelled(), "conversion was cancelled");
+				output.commit()?;
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Creates the converter, with all transforms combined into one.
+fn get_converter(
+	reader: Box<dyn TilesReaderTrait>,
+	mut parameters: TilesConverterParameters,
+	mut transforms: Vec<Arc<dyn TileTransform>>,
+) -> Result<TilesConvertReader> {
+	if let Some(transform) = parameters.tile_transform.take() {
+		transforms.insert(0, transform);
+	}
+	parameters.tile_transform = match transforms.len() {
+		0 => None,
+		1 => transforms.pop(),
+		_ => Some(Arc::new(TransformChain(transforms))),
+	};
+	TilesConvertReader::new_from_reader(reader, parameters)
+}
+
+/// Runs several transforms one after another.
+#[derive(Debug)]
+struct TransformChain(Vec<Arc<dyn TileTransform>>);
+
+#[async_trait]
+impl TileTransform for TransformChain {
+	async fn transform(&self, coord: TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+		let mut blob = blob;
+		for transform in &self.0 {
+			match transform.transform(coord, blob).await? {
+				Some(b) => blob = b,
+				None => return Ok(None),
+			}
+		}
+		Ok(Some(blob))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::MockTilesReader;
+	use assert_fs::TempDir;
+	use std::sync::atomic::AtomicU64;
+	use versatiles_core::{
+		json::JsonObject,
+		types::{TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters},
+	};
+
+	#[derive(Debug, Default)]
+	struct CountingObserver {
+		max_tile_count: AtomicU64,
+		tile_count: AtomicU64,
+	}
+
+	impl ProgressObserver for CountingObserver {
+		fn on_start(&self, max_tile_count: u64) {
+			self.max_tile_count.store(max_tile_count, Ordering::Relaxed);
+		}
+		fn on_tile(&self, _coord: &TileCoord3, _size: u64) {
+			self.tile_count.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	/// Drops the tiles of column 0 and appends a suffix to all other tiles.
+	#[derive(Debug)]
+	struct ColumnTransform(&'static str);
+
+	#[async_trait]
+	impl TileTransform for ColumnTransform {
+		async fn transform(&self, coord: TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+			let mut data = blob.into_vec();
+			data.extend_from_slice(self.0.as_bytes());
+			Ok((coord.x != 0).then(|| Blob::from(data)))
+		}
+	}
+
+	fn get_mock_reader() -> Box<dyn TilesReaderTrait> {
+		let parameters = TilesReaderParameters::new(
+			TileFormat::PBF,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_full(2),
+		);
+		MockTilesReader::new_mock(parameters).unwrap().boxed()
+	}
+
+	#[tokio::test]
+	async fn run() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("tiles.versatiles");
+		let observer = Arc::new(CountingObserver::default());
+
+		ConversionPipeline::new(get_mock_reader(), ConversionTarget::Path(path.clone()))
+			.with_transform(Arc::new(ColumnTransform("a")))
+			.with_transform(Arc::new(ColumnTransform("b")))
+			.with_concurrency(2)
+			.with_progress_observer(observer.clone())
+			.run()
+			.await?;
+
+		assert_eq!(observer.max_tile_count.load(Ordering::Relaxed), 21);
+		assert_eq!(observer.tile_count.load(Ordering::Relaxed), 14);
+
+		let reader = VersaTilesReader::open_path(&path).await?;
+		assert_eq!(reader.get_tile_data(&TileCoord3::new(0, 0, 1)?).await?, None);
+		let blob = reader.get_tile_data(&TileCoord3::new(1, 0, 1)?).await?.unwrap();
+		assert!(blob.as_slice().ends_with(b"ab"));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn cancellation() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("tiles.versatiles");
+		let cancellation = CancellationToken::new();
+		cancellation.cancel();
+
+		let error = ConversionPipeline::new(get_mock_reader(), ConversionTarget::Path(path.clone()))
+			.with_cancellation(cancellation)
+			.run()
+			.await
+			.unwrap_err();
+		assert_eq!(error.to_string(), "conversion was cancelled");
+		assert!(!path.exists());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn report() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("tiles.versatiles");
+		let report_path = dir.path().join("report.json");
+		let observer = Arc::new(CountingObserver::default());
+
+		ConversionPipeline::new(get_mock_reader(), ConversionTarget::Path(path.clone()))
+			.with_transform(Arc::new(ColumnTransform("a")))
+			.with_progress_observer(observer.clone())
+			.with_report(report_path.clone())
+			.run()
+			.await?;
+		assert_eq!(observer.tile_count.load(Ordering::Relaxed), 14);
+
+		let report = JsonObject::parse_str(&std::fs::read_to_string(&report_path)?)?;
+		assert_eq!(report.get_string("status")?.unwrap(), "succeeded");
+		assert!(report.get_number::<f64>("seconds")?.unwrap() >= 0.0);
+
+		let input = report.get("input").unwrap().as_object()?;
+		assert_eq!(input.get_string("container")?.unwrap(), "dummy_container");
+		assert_eq!(input.get_number::<u64>("max_tile_count")?, Some(21));
+
+		let output = report.get("output").unwrap().as_object()?;
+		assert_eq!(output.get_string("container")?.unwrap(), "versatiles");
+		assert_eq!(output.get_string("tile_format")?.unwrap(), "pbf");
+		assert_eq!(output.get_number::<u64>("size")?, Some(std::fs::metadata(&path)?.len()));
+
+		let tiles = report.get("tiles").unwrap().as_object()?;
+		assert_eq!(tiles.get_number::<u64>("count")?, Some(14));
+		assert_eq!(tiles.get_number::<u64>("dropped")?, Some(0));
+		assert_eq!(tiles.get_number::<u64>("failed")?, Some(0));
+		let levels: Vec<String> = tiles
+			.get_array("levels")?
+			.unwrap()
+			.0
+			.iter()
+			.map(|level| level.stringify())
+			.collect();
+		assert_eq!(
+			levels,
+			[
+				"{\"count\":2,\"size\":110,\"zoom\":1}",
+				"{\"count\":12,\"size\":660,\"zoom\":2}"
+			]
+		);
+
+		// the report is written, also if the conversion fails
+		let cancellation = CancellationToken::new();
+		cancellation.cancel();
+		let path = dir.path().join("cancelled.versatiles");
+		assert!(ConversionPipeline::new(get_mock_reader(), ConversionTarget::Path(path))
+			.with_cancellation(cancellation)
+			.with_report(report_path.clone())
+			.run()
+			.await
+			.is_err());
+		let report = JsonObject::parse_str(&std::fs::read_to_string(&report_path)?)?;
+		assert_eq!(report.get_string("status")?.unwrap(), "failed");
+		assert_eq!(report.get_string("error")?.unwrap(), "conversion was cancelled");
+		assert!(report.get("output").unwrap().as_object()?.get("size").is_none());
+		Ok(())
+	}
+
+	#[test]
+	fn check_target() -> Result<()> {
+		let target = ConversionTarget::Path("tiles.versatiles".into());
+		let error = ConversionPipeline::new(get_mock_reader(), target.clone())
+			.with_tar_path_template(TarPathTemplate::default())
+			.into_converter()
+			.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"a tar path template can only be used when writing a *.tar file"
+		);
+
+		let cipher = TileCipher::new(&TileCipher::generate_key()?)?;
+		let error = ConversionPipeline::new(get_mock_reader(), ConversionTarget::Stdout("tar".to_string()))
+			.with_cipher(cipher)
+			.into_converter()
+			.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"encryption can only be used when writing a *.versatiles file"
+		);
+
+		let error = ConversionPipeline::new(get_mock_reader(), ConversionTarget::Path("tiles.pmtiles".into()))
+			.with_versatiles_options(VersaTilesWriterOptions::default())
+			.into_converter()
+			.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"versatiles writer options can only be used when writing a *.versatiles file"
+		);
+
+		let mut parameters = TilesConverterParameters::new_default();
+		parameters.tile_compression = Some(TileCompression::Gzip);
+		let converter = ConversionPipeline::new(get_mock_reader(), target)
+			.with_concurrency(3)
+			.with_parameters(parameters)
+			.into_converter()?;
+		assert_eq!(converter.get_parameters().tile_compression, TileCompression::Gzip);
+		assert!(converter.is_recompressing());
+		Ok(())
+	}
+}
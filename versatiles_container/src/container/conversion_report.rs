@@ -0,0 +1,197 @@
+//! A machine-readable report of a conversion, for provenance tracking in data pipelines.
+//!
+//! The report is a JSON object with the parameters of the input and the output, the number and size of the
+//! written tiles per zoom level, the dropped and failed tiles, the sizes of the files, the wall time and the
+//! throughput. It is written after every conversion, also if the conversion failed.
+
+use super::{ConversionTarget, ProgressObserver, TilesConvertReader};
+use anyhow::{Context, Result};
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use versatiles_core::{
+	json::{JsonObject, JsonValue},
+	types::{TileCoord3, TilesReaderParameters, TilesReaderTrait},
+};
+
+/// Collects the statistics of a conversion. As a [`ProgressObserver`], it counts the written tiles and passes
+/// all calls on to the observer of the conversion.
+#[derive(Debug)]
+pub(super) struct ConversionReport {
+	path: PathBuf,
+	input: JsonObject,
+	output: Mutex<JsonObject>,
+	/// Number and size of the written tiles per zoom level.
+	levels: Mutex<BTreeMap<u8, (u64, u64)>>,
+	/// Number of dropped and failed tiles, known once the tiles have been written.
+	dropped_and_failed: Mutex<Option<(u64, u64)>>,
+	observer: Option<Arc<dyn ProgressObserver>>,
+	started: SystemTime,
+	timer: Instant,
+}
+
+impl ConversionReport {
+	/// Starts a report, that will be written to `path`.
+	pub(super) fn new(
+		path: PathBuf,
+		reader: &dyn TilesReaderTrait,
+		target: &ConversionTarget,
+		observer: Option<Arc<dyn ProgressObserver>>,
+	) -> ConversionReport {
+		let mut input = JsonObject::default();
+		input.set("source", reader.get_source_name());
+		input.set("container", reader.get_container_name());
+		add_parameters(&mut input, reader.get_parameters());
+		add_file_size(&mut input, Path::new(reader.get_source_name()));
+
+		let mut output = JsonObject::default();
+		match target {
+			ConversionTarget::Path(path) => output.set("path", path.to_string_lossy().to_string()),
+			ConversionTarget::Stdout(format) => {
+				output.set("path", "-");
+				output.set("container", format.as_str());
+			}
+		}
+
+		ConversionReport {
+			path,
+			input,
+			output: Mutex::new(output),
+			levels: Mutex::new(BTreeMap::new()),
+			dropped_and_failed: Mutex::new(None),
+			observer,
+			started: SystemTime::now(),
+			timer: Instant::now(),
+		}
+	}
+
+	/// Records the parameters of the output.
+	pub(super) fn set_output(&self, converter: &TilesConvertReader, container: &str) {
+		let mut output = self.output.lock().unwrap();
+		output.set("container", container);
+		add_parameters(&mut output, converter.get_parameters());
+	}
+
+	/// Records the number of dropped and failed tiles, after all tiles have been written.
+	pub(super) fn set_tile_errors(&self, converter: &TilesConvertReader) {
+		*self.dropped_and_failed.lock().unwrap() =
+			Some((converter.get_dropped_tile_count(), converter.get_failed_tile_count()));
+	}
+
+	/// Writes the report as JSON, together with the result of the conversion.
+	pub(super) fn write(&self, result: &Result<()>) -> Result<()> {
+		std::fs::write(&self.path, self.as_json(result).stringify())
+			.with_context(|| format!("writing the conversion report {:?}", self.path))
+	}
+
+	fn as_json(&self, result: &Result<()>) -> JsonObject {
+		let seconds = self.timer.elapsed().as_secs_f64();
+
+		let mut report = JsonObject::default();
+		match result {
+			Ok(()) => report.set("status", "succeeded"),
+			Err(err) => {
+				report.set("status", "failed");
+				report.set("error", format!("{err:#}"));
+			}
+		}
+		let started = self.started.duration_since(UNIX_EPOCH).unwrap_or_default();
+		report.set("started", started.as_secs_f64());
+		report.set("seconds", seconds);
+
+		report.set("input", JsonValue::Object(self.input.clone()));
+
+		let mut output = self.output.lock().unwrap().clone();
+		if let Some(path) = output.get_string("path").ok().flatten() {
+			add_file_size(&mut output, Path::new(&path));
+		}
+		report.set("output", JsonValue::Object(output));
+
+		let levels = self.levels.lock().unwrap();
+		let (count, size) = levels
+			.values()
+			.fold((0, 0), |(count, size), level| (count + level.0, size + level.1));
+		let mut tiles = JsonObject::default();
+		tiles.set("count", count as f64);
+		tiles.set("size", size as f64);
+		if let Some((dropped, failed)) = *self.dropped_and_failed.lock().unwrap() {
+			tiles.set("dropped", dropped as f64);
+			tiles.set("failed", failed as f64);
+		}
+		let levels: Vec<JsonValue> = levels
+			.iter()
+			.map(|(level, (count, size))| {
+				JsonValue::from(vec![
+					("zoom", *level as f64),
+					("count", *count as f64),
+					("size", *size as f64),
+				])
+			})
+			.collect();
+		tiles.set("levels", levels);
+		report.set("tiles", JsonValue::Object(tiles));
+
+		if seconds > 0.0 {
+			report.set(
+				"throughput",
+				vec![
+					("tiles_per_second", count as f64 / seconds),
+					("bytes_per_second", size as f64 / seconds),
+				],
+			);
+		}
+
+		report
+	}
+}
+
+impl ProgressObserver for ConversionReport {
+	fn on_start(&self, max_tile_count: u64) {
+		if let Some(observer) = &self.observer {
+			observer.on_start(max_tile_count);
+		}
+	}
+
+	fn on_tile(&self, coord: &TileCoord3, size: u64) {
+		let mut levels = self.levels.lock().unwrap();
+		let level = levels.entry(coord.z).or_default();
+		level.0 += 1;
+		level.1 += size;
+		drop(levels);
+
+		if let Some(observer) = &self.observer {
+			observer.on_tile(coord, size);
+		}
+	}
+
+	fn on_finish(&self) {
+		if let Some(observer) = &self.observer {
+			observer.on_finish();
+		}
+	}
+}
+
+/// Adds the tile format, the compression and the covered area.
+fn add_parameters(object: &mut JsonObject, parameters: &TilesReaderParameters) {
+	let pyramid = &parameters.bbox_pyramid;
+	object.set("tile_format", parameters.tile_format.as_str());
+	object.set("tile_compression", parameters.tile_compression.as_str());
+	object.set_optional("zoom_min", &pyramid.get_zoom_min());
+	object.set_optional("zoom_max", &pyramid.get_zoom_max());
+	if let Some(bbox) = pyramid.get_geo_bbox() {
+		object.set("bbox", vec![bbox.0, bbox.1, bbox.2, bbox.3]);
+	}
+	object.set("max_tile_count", pyramid.count_tiles() as f64);
+}
+
+/// Adds the size of the file in bytes, if the path is a file.
+fn add_file_size(object: &mut JsonObject, path: &Path) {
+	if let Ok(metadata) = std::fs::metadata(path) {
+		if metadata.is_file() {
+			object.set("size", metadata.len() as f64);
+		}
+	}
+}
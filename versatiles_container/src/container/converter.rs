@@ -33,10 +33,60 @@
 //! }
 //! ```
 
-use super::{tile_converter::TileConverter, write_to_filename};
-use anyhow::Result;
+use super::{
+	tile_converter::TileConverter, tile_error_handler::TileErrorHandler, tile_size_filter::TileSizeFilter,
+	write_to_filename, CancellationToken, ProgressObserver, TileErrorPolicy, TileTransform,
+};
+use anyhow::{ensure, Context, Result};
 use async_trait::async_trait;
-use versatiles_core::{tilejson::TileJSON, types::*, utils::TransformCoord};
+use futures::{
+	future::ready,
+	stream::{self, BoxStream},
+	FutureExt, StreamExt,
+};
+use std::sync::Arc;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use versatiles_core::{
+	json::JsonObject,
+	tilejson::TileJSON,
+	types::*,
+	utils::{get_thread_count, verify_tile_content, TransformCoord},
+};
+
+/// Number of tiles that can be queued between two stages of the conversion pipeline.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Limits the number of tile bytes in flight, with one semaphore permit per byte.
+#[derive(Clone, Debug)]
+struct MemoryBudget {
+	semaphore: Arc<Semaphore>,
+	size: usize,
+}
+
+impl MemoryBudget {
+	fn new(limit: u64) -> MemoryBudget {
+		let size = usize::try_from(limit)
+			.unwrap_or(usize::MAX)
+			.clamp(1, Semaphore::MAX_PERMITS);
+		MemoryBudget {
+			semaphore: Arc::new(Semaphore::new(size)),
+			size,
+		}
+	}
+
+	/// Waits until enough memory is available for a tile. The memory is released when the permit is dropped.
+	///
+	/// Tiles larger than the whole budget acquire all permits, so that they can still pass.
+	async fn acquire(&self, blob: &Blob) -> OwnedSemaphorePermit {
+		let cost = (blob.len() as usize).clamp(1, self.size.min(u32::MAX as usize)) as u32;
+		self
+			.semaphore
+			.clone()
+			.acquire_many_owned(cost)
+			.await
+			.expect("memory budget semaphore closed")
+	}
+}
 
 /// Parameters for tile conversion.
 #[derive(Debug)]
@@ -46,6 +96,34 @@ pub struct TilesConverterParameters {
 	pub force_recompress: bool,
 	pub flip_y: bool,
 	pub swap_xy: bool,
+	/// Maximum number of tile bytes held in memory between reading and writing.
+	pub memory_limit: Option<u64>,
+	/// Tiles smaller than this number of bytes are dropped.
+	pub min_tile_size: Option<u64>,
+	/// Tiles larger than this number of bytes are dropped.
+	pub max_tile_size: Option<u64>,
+	/// Only warn about tiles outside of `min_tile_size` and `max_tile_size`, instead of dropping them.
+	pub warn_tile_size: bool,
+	/// Tiles larger than this number of bytes are dropped, and the conversion fails.
+	pub tile_size_limit: Option<u64>,
+	/// Fields that overwrite the metadata (TileJSON) of the source.
+	pub meta_overrides: Option<JsonObject>,
+	/// What to do with tiles that can't be read or converted.
+	pub tile_error_policy: TileErrorPolicy,
+	/// Check every tile against the tile format and compression declared by the source.
+	/// Mislabeled tiles are handled like tiles that can't be read.
+	pub verify_content: bool,
+	/// Convert only these tiles, e.g. to build a small patch container.
+	/// The coordinates refer to the output, i.e. after flipping and swapping.
+	pub tile_list: Option<Vec<TileCoord3>>,
+	/// Custom logic that runs on every tile, after recompression. See [`TileTransform`].
+	pub tile_transform: Option<Arc<dyn TileTransform>>,
+	/// Number of tiles that are recompressed and transformed concurrently. Defaults to the number of threads.
+	pub concurrency: Option<usize>,
+	/// Is notified about every tile that is handed over to the writer.
+	pub progress_observer: Option<Arc<dyn ProgressObserver>>,
+	/// Stops the conversion after the tiles in flight, when it is cancelled.
+	pub cancellation: Option<CancellationToken>,
 }
 
 impl TilesConverterParameters {
@@ -63,6 +141,19 @@ impl TilesConverterParameters {
 			force_recompress,
 			flip_y,
 			swap_xy,
+			memory_limit: None,
+			min_tile_size: None,
+			max_tile_size: None,
+			warn_tile_size: false,
+			tile_size_limit: None,
+			meta_overrides: None,
+			tile_error_policy: TileErrorPolicy::Fail,
+			verify_content: false,
+			tile_list: None,
+			tile_transform: None,
+			concurrency: None,
+			progress_observer: None,
+			cancellation: None,
 		}
 	}
 
@@ -74,6 +165,19 @@ impl TilesConverterParameters {
 			force_recompress: false,
 			flip_y: false,
 			swap_xy: false,
+			memory_limit: None,
+			min_tile_size: None,
+			max_tile_size: None,
+			warn_tile_size: false,
+			tile_size_limit: None,
+			meta_overrides: None,
+			tile_error_policy: TileErrorPolicy::Fail,
+			verify_content: false,
+			tile_list: None,
+			tile_transform: None,
+			concurrency: None,
+			progress_observer: None,
+			cancellation: None,
 		}
 	}
 }
@@ -85,7 +189,8 @@ pub async fn convert_tiles_container(
 	filename: &str,
 ) -> Result<()> {
 	let mut converter = TilesConvertReader::new_from_reader(reader, cp)?;
-	write_to_filename(&mut converter, filename).await
+	write_to_filename(&mut converter, filename).await?;
+	converter.finish()
 }
 
 /// A reader that converts tiles from one format to another.
@@ -96,6 +201,12 @@ pub struct TilesConvertReader {
 	reader_parameters: TilesReaderParameters,
 	container_name: String,
 	tile_recompressor: Option<TileConverter>,
+	memory_budget: Option<MemoryBudget>,
+	tile_size_filter: Arc<TileSizeFilter>,
+	tile_error_handler: Arc<TileErrorHandler>,
+	/// The tiles of `TilesConverterParameters::tile_list`, sorted by zoom level, row and column.
+	tile_list: Option<Vec<TileCoord3>>,
+	tilejson: TileJSON,
 	name: String,
 }
 
@@ -108,20 +219,48 @@ impl TilesConvertReader {
 		let container_name = format!("converter({})", reader.get_container_name());
 		let name = format!("converter({})", reader.get_source_name());
 
+		ensure!(cp.concurrency != Some(0), "the concurrency must be at least 1");
+
 		let rp: TilesReaderParameters = reader.get_parameters().to_owned();
 		let mut new_rp: TilesReaderParameters = rp.clone();
 
+		let grid = rp.bbox_pyramid.get_grid();
 		if cp.flip_y {
+			ensure!(
+				grid.get_base_size().1 == 1,
+				"flipping y is not supported for the tile grid {grid}"
+			);
 			new_rp.bbox_pyramid.flip_y();
 		}
 		if cp.swap_xy {
+			ensure!(
+				grid.is_square(),
+				"swapping x and y is not supported for the tile grid {grid}"
+			);
 			new_rp.bbox_pyramid.swap_xy();
 		}
 
 		if let Some(bbox_pyramid) = &cp.bbox_pyramid {
+			ensure!(
+				bbox_pyramid.get_grid() == grid,
+				"the bbox filter uses the tile grid {}, but the tiles use {grid}",
+				bbox_pyramid.get_grid()
+			);
 			new_rp.bbox_pyramid.intersect(bbox_pyramid);
 		}
 
+		let tile_list = cp.tile_list.as_ref().map(|coords| {
+			let mut coords = coords.clone();
+			coords.sort_by_key(get_sort_key);
+			coords.dedup();
+			coords
+		});
+		if let Some(tile_list) = &tile_list {
+			let mut bbox_pyramid = TileBBoxPyramid::new_empty_in_grid(grid);
+			tile_list.iter().for_each(|coord| bbox_pyramid.include_coord(coord));
+			new_rp.bbox_pyramid.intersect(&bbox_pyramid);
+		}
+
 		new_rp.tile_format = rp.tile_format;
 		new_rp.tile_compression = cp.tile_compression.unwrap_or(rp.tile_compression);
 
@@ -131,15 +270,118 @@ impl TilesConvertReader {
 			cp.force_recompress,
 		)?);
 
+		let memory_budget = cp.memory_limit.map(MemoryBudget::new);
+
+		let mut tilejson = reader.get_tilejson().clone();
+		if cp.bbox_pyramid.is_some() || tile_list.is_some() {
+			// keep minzoom, maxzoom, bounds and center consistent with the clamped pyramid
+			tilejson.update_from_pyramid(&new_rp.bbox_pyramid);
+		}
+		if let Some(meta_overrides) = &cp.meta_overrides {
+			tilejson.assign(meta_overrides)?;
+		}
+		tilejson.set_tile_grid(&grid)?;
+
+		let tile_size_filter = Arc::new(TileSizeFilter::new(
+			cp.min_tile_size,
+			cp.max_tile_size,
+			cp.warn_tile_size,
+			cp.tile_size_limit,
+		)?);
+
+		let tile_error_handler = Arc::new(TileErrorHandler::new(cp.tile_error_policy));
+
 		Ok(TilesConvertReader {
 			reader,
 			converter_parameters: cp,
 			reader_parameters: new_rp,
 			container_name,
 			tile_recompressor,
+			memory_budget,
+			tile_size_filter,
+			tile_error_handler,
+			tile_list,
+			tilejson,
 			name,
 		})
 	}
+
+	/// Reports the tiles outside of the allowed size range and the tiles that failed.
+	/// Call it after all tiles have been written.
+	///
+	/// # Errors
+	/// Returns an error if the conversion was cancelled, or if any tile exceeded the hard size limit or could not
+	/// be read or converted.
+	pub fn finish(&self) -> Result<()> {
+		ensure!(!self.is_cancelled(), "conversion was cancelled");
+		self.tile_size_filter.finish()?;
+		self.tile_error_handler.finish()
+	}
+
+	/// Returns the number of tiles that have been dropped, because of their size.
+	pub fn get_dropped_tile_count(&self) -> u64 {
+		self.tile_size_filter.get_dropped_count()
+	}
+
+	/// Returns the number of tiles that could not be read or converted.
+	pub fn get_failed_tile_count(&self) -> u64 {
+		self.tile_error_handler.get_error_count()
+	}
+
+	/// Returns `false` if a tile list is set and the tile is not listed.
+	fn is_listed(&self, coord: &TileCoord3) -> bool {
+		self
+			.tile_list
+			.as_ref()
+			.is_none_or(|list| list.binary_search_by_key(&get_sort_key(coord), get_sort_key).is_ok())
+	}
+
+	/// Reads the listed tiles within a bounding box one by one, instead of all tiles of the bounding box.
+	/// Like the tiles of the source, they are returned with the coordinates of the source.
+	fn get_listed_tile_stream<'a>(
+		&'a self,
+		tile_list: &[TileCoord3],
+		bbox: &TileBBox,
+	) -> BoxStream<'a, Result<(TileCoord3, Blob)>> {
+		let coords: Vec<TileCoord3> = tile_list
+			.iter()
+			.filter(|coord| bbox.contains3(coord))
+			.map(|coord| {
+				let mut coord = *coord;
+				if self.converter_parameters.swap_xy {
+					coord.swap_xy();
+				}
+				if self.converter_parameters.flip_y {
+					coord.flip_y();
+				}
+				coord
+			})
+			.collect();
+
+		TryTileStream::from_coord_vec_async(coords, move |coord| async move {
+			self
+				.reader
+				.get_tile_data(&coord)
+				.await
+				.with_context(|| format!("reading tile {coord:?}"))
+				.map(|blob| blob.map(|blob| (coord, blob)))
+		})
+		.stream
+	}
+
+	/// Returns `true` if the conversion has been cancelled with its [`CancellationToken`].
+	pub fn is_cancelled(&self) -> bool {
+		self
+			.converter_parameters
+			.cancellation
+			.as_ref()
+			.is_some_and(|cancellation| cancellation.is_cancelled())
+	}
+
+	/// Returns `true` if tiles are decompressed and/or compressed during the conversion.
+	pub fn is_recompressing(&self) -> bool {
+		self.tile_recompressor.as_ref().is_some_and(|c| !c.is_empty())
+	}
 }
 
 #[async_trait]
@@ -161,10 +403,14 @@ impl TilesReaderTrait for TilesConvertReader {
 	}
 
 	fn get_tilejson(&self) -> &TileJSON {
-		self.reader.get_tilejson()
+		&self.tilejson
 	}
 
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		if !self.is_listed(coord) {
+			return Ok(None);
+		}
+		let output_coord = *coord;
 		let mut coord = *coord;
 		if self.converter_parameters.flip_y {
 			coord.flip_y();
@@ -174,16 +420,87 @@ impl TilesReaderTrait for TilesConvertReader {
 		}
 		let mut blob = self.reader.get_tile_data(&coord).await?;
 
+		if self.converter_parameters.verify_content {
+			if let Some(b) = &blob {
+				let rp = self.reader.get_parameters();
+				verify_tile_content(b, rp.tile_format, rp.tile_compression)
+					.with_context(|| format!("verifying tile {coord:?}"))?;
+			}
+		}
+
 		if let Some(tile_recompressor) = &self.tile_recompressor {
 			if let Some(b) = blob {
 				blob = Some(tile_recompressor.process_blob(b)?);
 			}
 		}
 
-		Ok(blob)
+		if let Some(tile_transform) = &self.converter_parameters.tile_transform {
+			if let Some(b) = blob {
+				blob = tile_transform
+					.transform(output_coord, b)
+					.await
+					.with_context(|| format!("transforming tile {output_coord:?}"))?;
+			}
+		}
+
+		Ok(blob.filter(|blob| self.tile_size_filter.check(&coord, blob.len())))
 	}
 
+	/// Passes the hint on to the source, with the bounding box in the orientation of the source.
+	async fn prefetch(&self, bbox: &TileBBox) -> Result<()> {
+		let mut bbox = bbox.clone();
+		if self.converter_parameters.swap_xy {
+			bbox.swap_xy();
+		}
+		if self.converter_parameters.flip_y {
+			bbox.flip_y();
+		}
+		self.reader.prefetch(&bbox).await
+	}
+
+	/// Returns a stream of converted tiles.
+	///
+	/// Tiles that can't be read or converted are handled according to the [`TileErrorPolicy`]:
+	/// The stream either ends at the first failed tile, or failed tiles are skipped.
+	/// The stream also ends when the conversion is cancelled.
+	/// Call [`TilesConvertReader::finish`] afterwards to find out whether any tile failed.
 	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let handler = self.tile_error_handler.clone();
+		let stream = self
+			.get_bbox_tile_try_stream(bbox)
+			.await
+			.into_tile_stream(move |err| handler.handle(err));
+
+		let handler = self.tile_error_handler.clone();
+		let cancellation = self.converter_parameters.cancellation.clone();
+		let mut stream = stream
+			.stream
+			.take_while(move |_| {
+				ready(!handler.should_stop() && cancellation.as_ref().is_none_or(|c| !c.is_cancelled()))
+			})
+			.boxed();
+
+		if let Some(observer) = self.converter_parameters.progress_observer.clone() {
+			stream = stream
+				.inspect(move |(coord, blob)| observer.on_tile(coord, blob.len()))
+				.boxed();
+		}
+
+		TileStream::from_stream(stream)
+	}
+
+	/// Returns a stream of converted tiles, with an error for every tile that can't be read or converted.
+	///
+	/// The conversion runs as a pipeline of stages: reading tiles from the source, transforming them
+	/// (e.g. recompressing) in parallel, and handing them over to the writer. The stages are connected
+	/// by a bounded channel, so reading can run ahead of writing, but only by `CHANNEL_CAPACITY` tiles.
+	/// If a memory limit is set, the number of tile bytes between reading and writing is capped as well.
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
+		let listed_stream = self
+			.tile_list
+			.as_ref()
+			.map(|tile_list| self.get_listed_tile_stream(tile_list, &bbox));
+
 		let mut bbox = bbox.clone();
 		if self.converter_parameters.swap_xy {
 			bbox.swap_xy();
@@ -192,31 +509,146 @@ impl TilesReaderTrait for TilesConvertReader {
 			bbox.flip_y();
 		}
 
-		let mut stream = self.reader.get_bbox_tile_stream(bbox).await;
+		let mut stream = match listed_stream {
+			Some(stream) => stream,
+			None => self.reader.get_bbox_tile_try_stream(bbox).await.stream,
+		};
 
 		let flip_y = self.converter_parameters.flip_y;
 		let swap_xy = self.converter_parameters.swap_xy;
 
 		if flip_y || swap_xy {
-			stream = stream.map_coord(move |mut coord| {
-				if flip_y {
-					coord.flip_y()
-				}
-				if swap_xy {
-					coord.swap_xy()
-				}
-				coord
-			});
+			stream = stream
+				.map(move |item| {
+					item.map(|(mut coord, blob)| {
+						if flip_y {
+							coord.flip_y()
+						}
+						if swap_xy {
+							coord.swap_xy()
+						}
+						(coord, blob)
+					})
+				})
+				.boxed();
 		}
 
-		if let Some(tile_recompressor) = &self.tile_recompressor {
-			stream = tile_recompressor.process_stream(stream);
+		let concurrency = self.converter_parameters.concurrency.unwrap_or_else(get_thread_count);
+		let mut pipeline = connect_stages(stream, self.memory_budget.clone());
+
+		if self.converter_parameters.verify_content {
+			let rp = self.reader.get_parameters();
+			let (tile_format, tile_compression) = (rp.tile_format, rp.tile_compression);
+			pipeline = pipeline
+				.map(move |item| {
+					let (coord, blob, permit) = item?;
+					verify_tile_content(&blob, tile_format, tile_compression)
+						.with_context(|| format!("verifying tile {coord:?}"))?;
+					Ok((coord, blob, permit))
+				})
+				.boxed();
 		}
 
-		stream
+		if let Some(tile_recompressor) = self.tile_recompressor.clone().filter(|c| !c.is_empty()) {
+			pipeline = pipeline
+				.map(move |item| {
+					let tile_recompressor = tile_recompressor.clone();
+					tokio::spawn(async move {
+						let (coord, blob, permit) = item?;
+						let blob = tile_recompressor
+							.process_blob(blob)
+							.with_context(|| format!("recompressing tile {coord:?}"))?;
+						Ok((coord, blob, permit))
+					})
+				})
+				.buffer_unordered(concurrency)
+				.map(|result| result.expect("spawned task panicked"))
+				.boxed();
+		}
+
+		if let Some(tile_transform) = self.converter_parameters.tile_transform.clone() {
+			pipeline = pipeline
+				.map(move |item| {
+					let tile_transform = tile_transform.clone();
+					tokio::spawn(async move {
+						let (coord, blob, permit) = item?;
+						let blob = tile_transform
+							.transform(coord, blob)
+							.await
+							.with_context(|| format!("transforming tile {coord:?}"))?;
+						Ok(blob.map(|blob| (coord, blob, permit)))
+					})
+				})
+				.buffer_unordered(concurrency)
+				.filter_map(|result| ready(result.expect("spawned task panicked").transpose()))
+				.boxed();
+		}
+
+		if !self.tile_size_filter.is_empty() {
+			let tile_size_filter = self.tile_size_filter.clone();
+			pipeline = pipeline
+				.filter(move |item| {
+					ready(match item {
+						Ok((coord, blob, _permit)) => tile_size_filter.check(coord, blob.len()),
+						Err(_) => true,
+					})
+				})
+				.boxed();
+		}
+
+		// The permit is dropped as soon as the tile is handed over to the writer.
+		TryTileStream::from_stream(
+			pipeline
+				.map(|item| item.map(|(coord, blob, _permit)| (coord, blob)))
+				.boxed(),
+		)
 	}
 }
 
+/// Sorts tiles by zoom level, row and column.
+fn get_sort_key(coord: &TileCoord3) -> (u8, u32, u32) {
+	(coord.z, coord.y, coord.x)
+}
+
+/// Connects the reading stage to the next stage of the conversion pipeline using a bounded channel.
+///
+/// The returned stream drives `source` as producer, which sends the tiles into a channel of
+/// `CHANNEL_CAPACITY` tiles. If `budget` is set, every tile acquires memory for its size before it
+/// is sent, so the producer pauses as soon as too many bytes are in flight.
+fn connect_stages<'a>(
+	source: BoxStream<'a, Result<(TileCoord3, Blob)>>,
+	budget: Option<MemoryBudget>,
+) -> BoxStream<'a, Result<(TileCoord3, Blob, Option<OwnedSemaphorePermit>)>> {
+	let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+
+	let producer = async move {
+		let mut source = source;
+		while let Some(item) = source.next().await {
+			let item = match item {
+				Ok((coord, blob)) => {
+					let permit = match &budget {
+						Some(budget) => Some(budget.acquire(&blob).await),
+						None => None,
+					};
+					Ok((coord, blob, permit))
+				}
+				Err(err) => Err(err),
+			};
+			if sender.send(item).await.is_err() {
+				// The consumer was dropped.
+				break;
+			}
+		}
+	};
+
+	let consumer = stream::unfold(receiver, |mut receiver| async move {
+		receiver.recv().await.map(|item| (item, receiver))
+	});
+
+	// The producer yields no items, it only has to be polled together with the consumer.
+	stream::select(producer.into_stream().filter_map(|_| ready(None)), consumer).boxed()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -240,6 +672,19 @@ mod tests {
 			force_recompress,
 			flip_y: false,
 			swap_xy: false,
+			memory_limit: None,
+			min_tile_size: None,
+			max_tile_size: None,
+			warn_tile_size: false,
+			tile_size_limit: None,
+			meta_overrides: None,
+			tile_error_policy: TileErrorPolicy::Fail,
+			verify_content: false,
+			tile_list: None,
+			tile_transform: None,
+			concurrency: None,
+			progress_observer: None,
+			cancellation: None,
 		}
 	}
 
@@ -318,6 +763,217 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn memory_limit() -> Result<()> {
+		let reader = get_mock_reader(PBF, Gzip);
+		let mut cp = get_converter_parameters(Brotli, false);
+		cp.memory_limit = Some(1);
+		let tcr = TilesConvertReader::new_from_reader(reader.boxed(), cp)?;
+
+		let tiles = tcr.get_bbox_tile_stream(TileBBox::new_full(1)?).await.collect().await;
+		assert_eq!(tiles.len(), 4);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_size_filter() -> Result<()> {
+		async fn count_tiles(f: impl FnOnce(&mut TilesConverterParameters)) -> Result<(usize, Result<()>)> {
+			let mut cp = get_converter_parameters(Uncompressed, false);
+			f(&mut cp);
+			let tcr = TilesConvertReader::new_from_reader(get_mock_reader(PBF, Uncompressed).boxed(), cp)?;
+			let tiles = tcr.get_bbox_tile_stream(TileBBox::new_full(1)?).await.collect().await;
+			Ok((tiles.len(), tcr.finish()))
+		}
+
+		let (count, result) = count_tiles(|_| {}).await?;
+		assert_eq!(count, 4);
+		assert!(result.is_ok());
+
+		let (count, result) = count_tiles(|cp| cp.max_tile_size = Some(1)).await?;
+		assert_eq!(count, 0);
+		assert!(result.is_ok());
+
+		let (count, result) = count_tiles(|cp| {
+			cp.max_tile_size = Some(1);
+			cp.warn_tile_size = true;
+		})
+		.await?;
+		assert_eq!(count, 4);
+		assert!(result.is_ok());
+
+		let (count, result) = count_tiles(|cp| cp.tile_size_limit = Some(1)).await?;
+		assert_eq!(count, 0);
+		assert!(result.is_err());
+
+		let mut cp = get_converter_parameters(Uncompressed, false);
+		cp.tile_size_limit = Some(1);
+		let tcr = TilesConvertReader::new_from_reader(get_mock_reader(PBF, Uncompressed).boxed(), cp)?;
+		assert_eq!(tcr.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_errors() -> Result<()> {
+		#[derive(Debug)]
+		struct BrokenReader {
+			parameters: TilesReaderParameters,
+			tilejson: TileJSON,
+		}
+
+		#[async_trait]
+		impl TilesReaderTrait for BrokenReader {
+			fn get_source_name(&self) -> &str {
+				"broken"
+			}
+			fn get_container_name(&self) -> &str {
+				"broken"
+			}
+			fn get_parameters(&self) -> &TilesReaderParameters {
+				&self.parameters
+			}
+			fn override_compression(&mut self, _tile_compression: TileCompression) {}
+			fn get_tilejson(&self) -> &TileJSON {
+				&self.tilejson
+			}
+			async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+				anyhow::ensure!(coord.x != 1, "disk error");
+				Ok(Some(Blob::from("tile")))
+			}
+		}
+
+		async fn convert(policy: TileErrorPolicy) -> Result<(usize, Result<()>)> {
+			let reader = BrokenReader {
+				parameters: TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_full(2)),
+				tilejson: TileJSON::default(),
+			};
+			let mut cp = get_converter_parameters(Uncompressed, false);
+			cp.tile_error_policy = policy;
+			let tcr = TilesConvertReader::new_from_reader(Box::new(reader), cp)?;
+			let tiles = tcr.get_bbox_tile_stream(TileBBox::new_full(2)?).await.collect().await;
+			Ok((tiles.len(), tcr.finish()))
+		}
+
+		let (count, result) = convert(TileErrorPolicy::Fail).await?;
+		assert_eq!(count, 1);
+		assert_eq!(
+			result.unwrap_err().to_string(),
+			"conversion stopped, because a tile failed: reading tile TileCoord3(1, 0, 2): disk error"
+		);
+
+		let (count, result) = convert(TileErrorPolicy::Skip).await?;
+		assert_eq!(count, 12);
+		assert_eq!(result.unwrap_err().to_string(), "4 tiles failed and were skipped");
+
+		let (count, result) = convert(TileErrorPolicy::Report).await?;
+		assert_eq!(count, 12);
+		assert!(result
+			.unwrap_err()
+			.to_string()
+			.contains("  - reading tile TileCoord3(1, 3, 2): disk error"));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn verify_content() -> Result<()> {
+		/// Returns gzip compressed vector tiles, regardless of the declared compression.
+		#[derive(Debug)]
+		struct MislabeledReader {
+			parameters: TilesReaderParameters,
+			tilejson: TileJSON,
+		}
+
+		#[async_trait]
+		impl TilesReaderTrait for MislabeledReader {
+			fn get_source_name(&self) -> &str {
+				"mislabeled"
+			}
+			fn get_container_name(&self) -> &str {
+				"mislabeled"
+			}
+			fn get_parameters(&self) -> &TilesReaderParameters {
+				&self.parameters
+			}
+			fn override_compression(&mut self, _tile_compression: TileCompression) {}
+			fn get_tilejson(&self) -> &TileJSON {
+				&self.tilejson
+			}
+			async fn get_tile_data(&self, _coord: &TileCoord3) -> Result<Option<Blob>> {
+				Ok(Some(versatiles_core::utils::compress_gzip(&Blob::from(
+					&[0x1a, 0x03, 0x0a, 0x01, b'a'][..],
+				))?))
+			}
+		}
+
+		async fn convert(declared: TileCompression, verify_content: bool) -> Result<(usize, Result<()>)> {
+			let reader = MislabeledReader {
+				parameters: TilesReaderParameters::new(PBF, declared, TileBBoxPyramid::new_full(1)),
+				tilejson: TileJSON::default(),
+			};
+			let mut cp = get_converter_parameters(declared, false);
+			cp.tile_error_policy = TileErrorPolicy::Skip;
+			cp.verify_content = verify_content;
+			let tcr = TilesConvertReader::new_from_reader(Box::new(reader), cp)?;
+			let tiles = tcr.get_bbox_tile_stream(TileBBox::new_full(1)?).await.collect().await;
+			Ok((tiles.len(), tcr.finish()))
+		}
+
+		let (count, result) = convert(Gzip, true).await?;
+		assert_eq!(count, 4);
+		assert!(result.is_ok());
+
+		let (count, result) = convert(Uncompressed, false).await?;
+		assert_eq!(count, 4);
+		assert!(result.is_ok());
+
+		let (count, result) = convert(Uncompressed, true).await?;
+		assert_eq!(count, 0);
+		assert_eq!(result.unwrap_err().to_string(), "4 tiles failed and were skipped");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn connect_stages_with_budget() -> Result<()> {
+		let tiles = (0..4)
+			.map(|x| (TileCoord3::new(x, 0, 2).unwrap(), Blob::from("1234")))
+			.collect();
+		let budget = MemoryBudget::new(10);
+		let mut stream = connect_stages(
+			TileStream::from_vec(tiles).into_try_stream().stream,
+			Some(budget.clone()),
+		);
+
+		// Only two tiles fit into the budget, so the producer waits until the first one is released.
+		let first = stream.next().await.unwrap();
+		let second = stream.next().await.unwrap();
+		assert!(stream.next().now_or_never().is_none());
+		drop(first);
+		drop(second);
+
+		let mut count = 2;
+		while let Some(item) = stream.next().await {
+			count += 1;
+			drop(item);
+		}
+		assert_eq!(count, 4);
+		assert_eq!(budget.semaphore.available_permits(), 10);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn connect_stages_oversized_tile() -> Result<()> {
+		let tiles = vec![(TileCoord3::new(0, 0, 0)?, Blob::from("larger than the budget"))];
+		let budget = MemoryBudget::new(4);
+		let items: Vec<_> = connect_stages(TileStream::from_vec(tiles).into_try_stream().stream, Some(budget))
+			.map(|item| item.map(|(coord, blob, _permit)| (coord, blob)))
+			.collect()
+			.await;
+		assert_eq!(items.len(), 1);
+		Ok(())
+	}
+
 	#[test]
 	fn test_tiles_converter_parameters_new() {
 		let cp = TilesConverterParameters::new(Some(Gzip), Some(TileBBoxPyramid::new_full(1)), true, true, true);
@@ -327,6 +983,7 @@ mod tests {
 		assert!(cp.force_recompress);
 		assert!(cp.flip_y);
 		assert!(cp.swap_xy);
+		assert_eq!(cp.memory_limit, None);
 	}
 
 	#[test]
@@ -366,6 +1023,125 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_tile_list() -> Result<()> {
+		let parameters = TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_full(4));
+		let reader = MockTilesReader::new_mock(parameters)?;
+		let mut cp = TilesConverterParameters::new_default();
+		cp.tile_list = Some(vec![
+			TileCoord3::new(5, 2, 3)?,
+			TileCoord3::new(1, 0, 1)?,
+			TileCoord3::new(7, 9, 4)?,
+			TileCoord3::new(5, 2, 3)?,
+		]);
+		let tcr = TilesConvertReader::new_from_reader(reader.boxed(), cp)?;
+		assert_eq!(tcr.get_parameters().bbox_pyramid.count_tiles(), 3);
+
+		assert!(tcr.get_tile_data(&TileCoord3::new(5, 2, 3)?).await?.is_some());
+		assert!(tcr.get_tile_data(&TileCoord3::new(4, 2, 3)?).await?.is_none());
+
+		let mut coords = Vec::new();
+		for bbox in tcr.get_parameters().bbox_pyramid.iter_levels() {
+			for (coord, _blob) in tcr.get_bbox_tile_stream(bbox.clone()).await.collect().await {
+				coords.push(coord);
+			}
+		}
+		assert_eq!(
+			coords,
+			vec![
+				TileCoord3::new(1, 0, 1)?,
+				TileCoord3::new(5, 2, 3)?,
+				TileCoord3::new(7, 9, 4)?
+			]
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_transform() -> Result<()> {
+		/// Drops the tiles of column 0, fails on column 1 and replaces all other tiles.
+		#[derive(Debug)]
+		struct ColumnTransform;
+
+		#[async_trait]
+		impl TileTransform for ColumnTransform {
+			async fn transform(&self, coord: TileCoord3, _blob: Blob) -> Result<Option<Blob>> {
+				anyhow::ensure!(coord.x != 1, "service unavailable");
+				Ok((coord.x != 0).then(|| Blob::from("transformed")))
+			}
+		}
+
+		let mut cp = get_converter_parameters(Uncompressed, false);
+		cp.tile_transform = Some(Arc::new(ColumnTransform));
+		cp.tile_error_policy = TileErrorPolicy::Skip;
+		let parameters = TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_full(2));
+		let tcr = TilesConvertReader::new_from_reader(MockTilesReader::new_mock(parameters)?.boxed(), cp)?;
+
+		let tiles = tcr.get_bbox_tile_stream(TileBBox::new_full(2)?).await.collect().await;
+		assert_eq!(tiles.len(), 8);
+		assert!(tiles
+			.iter()
+			.all(|(coord, blob)| coord.x >= 2 && blob.as_str() == "transformed"));
+		assert_eq!(tcr.finish().unwrap_err().to_string(), "4 tiles failed and were skipped");
+
+		assert_eq!(tcr.get_tile_data(&TileCoord3::new(0, 0, 2)?).await?, None);
+		assert!(tcr.get_tile_data(&TileCoord3::new(1, 0, 2)?).await.is_err());
+		assert_eq!(
+			tcr.get_tile_data(&TileCoord3::new(2, 0, 2)?).await?,
+			Some(Blob::from("transformed"))
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn test_is_recompressing() -> Result<()> {
+		let tcr = |c_in: TileCompression, c_out: TileCompression, force: bool| {
+			let cp = get_converter_parameters(c_out, force);
+			TilesConvertReader::new_from_reader(get_mock_reader(PBF, c_in).boxed(), cp)
+		};
+		assert!(!tcr(Gzip, Gzip, false)?.is_recompressing());
+		assert!(tcr(Gzip, Gzip, true)?.is_recompressing());
+		assert!(tcr(Gzip, Brotli, false)?.is_recompressing());
+		assert!(!tcr(Uncompressed, Uncompressed, true)?.is_recompressing());
+		Ok(())
+	}
+
+	#[test]
+	fn test_meta_overrides() -> Result<()> {
+		let mut cp = TilesConverterParameters::new_default();
+		cp.meta_overrides = Some(JsonObject::parse_str(r#"{"name":"renamed","attribution":"me"}"#)?);
+		let tcr = TilesConvertReader::new_from_reader(get_mock_reader(PBF, Uncompressed).boxed(), cp)?;
+		assert_eq!(tcr.get_tilejson().get_str("name"), Some("renamed"));
+		assert_eq!(tcr.get_tilejson().get_str("attribution"), Some("me"));
+
+		let mut cp = TilesConverterParameters::new_default();
+		cp.meta_overrides = Some(JsonObject::parse_str(r#"{"center":"invalid"}"#)?);
+		assert!(TilesConvertReader::new_from_reader(get_mock_reader(PBF, Uncompressed).boxed(), cp).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn test_zoom_clamping_updates_tilejson() -> Result<()> {
+		let parameters = TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_full(8));
+		let reader = MockTilesReader::new_mock(parameters)?;
+		let mut tilejson = reader.get_tilejson().clone();
+		tilejson.center = Some(GeoCenter(13.4, 52.5, 12));
+		let reader = reader.with_tilejson(tilejson);
+
+		let mut bbox_pyramid = TileBBoxPyramid::new_full(32);
+		bbox_pyramid.set_zoom_min(2);
+		bbox_pyramid.set_zoom_max(6);
+		let mut cp = TilesConverterParameters::new_default();
+		cp.bbox_pyramid = Some(bbox_pyramid);
+
+		let tcr = TilesConvertReader::new_from_reader(reader.boxed(), cp)?;
+		let tilejson = tcr.get_tilejson();
+		assert_eq!(tilejson.values.get_byte("minzoom"), Some(2));
+		assert_eq!(tilejson.values.get_byte("maxzoom"), Some(6));
+		assert_eq!(tilejson.center, Some(GeoCenter(13.4, 52.5, 6)));
+		Ok(())
+	}
+
 	#[test]
 	fn test_get_name() {
 		let reader = get_mock_reader(PBF, Uncompressed);
@@ -411,4 +1187,104 @@ mod tests {
 
 		Ok(())
 	}
+
+	/// Passes everything on to a mock reader and records the prefetched bounding boxes.
+	#[derive(Debug)]
+	struct PrefetchRecorder {
+		inner: MockTilesReader,
+		prefetched: Arc<std::sync::Mutex<Vec<String>>>,
+	}
+
+	#[async_trait]
+	impl TilesReaderTrait for PrefetchRecorder {
+		fn get_source_name(&self) -> &str {
+			self.inner.get_source_name()
+		}
+		fn get_container_name(&self) -> &str {
+			self.inner.get_container_name()
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			self.inner.get_parameters()
+		}
+		fn override_compression(&mut self, tile_compression: TileCompression) {
+			self.inner.override_compression(tile_compression)
+		}
+		fn get_tilejson(&self) -> &TileJSON {
+			self.inner.get_tilejson()
+		}
+		async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+			self.inner.get_tile_data(coord).await
+		}
+		async fn prefetch(&self, bbox: &TileBBox) -> Result<()> {
+			self.prefetched.lock().unwrap().push(format!("{bbox:?}"));
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn prefetch() -> Result<()> {
+		let prefetched = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let get_reader = || {
+			let parameters = TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_full(2));
+			PrefetchRecorder {
+				inner: MockTilesReader::new_mock(parameters).unwrap(),
+				prefetched: prefetched.clone(),
+			}
+			.boxed()
+		};
+
+		// the hint is passed on in the orientation of the source
+		let cp = TilesConverterParameters::new(None, None, false, true, false);
+		let tcr = TilesConvertReader::new_from_reader(get_reader(), cp)?;
+		tcr.prefetch(&TileBBox::new(2, 0, 0, 1, 0)?).await?;
+		assert_eq!(
+			prefetched.lock().unwrap().drain(..).collect::<Vec<_>>(),
+			["2: [0,3,1,3] (2)"]
+		);
+
+		// the versatiles writer prefetches every block before writing it
+		let temp_file = NamedTempFile::new("test.versatiles")?;
+		let cp = get_converter_parameters(Uncompressed, false);
+		convert_tiles_container(get_reader(), cp, temp_file.to_str().unwrap()).await?;
+		assert_eq!(
+			prefetched.lock().unwrap().clone(),
+			["1: [0,0,1,1] (4)", "2: [0,0,3,3] (16)"]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_tile_grid() -> Result<()> {
+		let get_reader = || {
+			let parameters =
+				TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_full_in_grid(TileGrid::Wgs84, 2));
+			MockTilesReader::new_mock(parameters).unwrap().boxed()
+		};
+
+		let tcr = TilesConvertReader::new_from_reader(get_reader(), TilesConverterParameters::new_default())?;
+		assert_eq!(tcr.get_parameters().bbox_pyramid.get_grid(), TileGrid::Wgs84);
+		assert_eq!(tcr.get_tilejson().get_tile_grid()?, TileGrid::Wgs84);
+
+		// flipping y is fine, because the grid has only one row at zoom level 0
+		let cp = TilesConverterParameters::new(None, None, false, true, false);
+		assert!(TilesConvertReader::new_from_reader(get_reader(), cp).is_ok());
+
+		let cp = TilesConverterParameters::new(None, None, false, false, true);
+		assert!(TilesConvertReader::new_from_reader(get_reader(), cp).is_err());
+
+		let cp = TilesConverterParameters::new(None, Some(TileBBoxPyramid::new_full(2)), false, false, false);
+		assert!(TilesConvertReader::new_from_reader(get_reader(), cp).is_err());
+
+		let cp = TilesConverterParameters::new(
+			None,
+			Some(TileBBoxPyramid::new_full_in_grid(TileGrid::Wgs84, 1)),
+			false,
+			false,
+			false,
+		);
+		let tcr = TilesConvertReader::new_from_reader(get_reader(), cp)?;
+		assert_eq!(tcr.get_parameters().bbox_pyramid.count_tiles(), 2 + 8);
+		Ok(())
+	}
 }
@@ -51,6 +51,7 @@ use std::{
 	fmt::Debug,
 	fs,
 	path::{Path, PathBuf},
+	time::SystemTime,
 };
 use versatiles_core::{tilejson::TileJSON, types::*, utils::*};
 
@@ -91,7 +92,6 @@ impl DirectoryTilesReader {
 		let mut tile_map = HashMap::new();
 		let mut container_form: Option<TileFormat> = None;
 		let mut container_comp: Option<TileCompression> = None;
-		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
 
 		for result1 in fs::read_dir(dir)? {
 			// z level
@@ -153,9 +153,7 @@ impl DirectoryTilesReader {
 							bail!("found multiple tile compressions: {list:?}");
 						}
 
-						let coord3 = TileCoord3::new(x, y, z)?;
-						bbox_pyramid.include_coord(&coord3);
-						tile_map.insert(coord3, entry3.path());
+						tile_map.insert(TileCoord3::new(x, y, z)?, entry3.path());
 					}
 				}
 			} else {
@@ -187,6 +185,12 @@ impl DirectoryTilesReader {
 		let tile_format = container_form.context("tile format must be specified")?;
 		let tile_compression = container_comp.context("tile compression must be specified")?;
 
+		// the tile grid is defined in the meta data, which may be read after the tiles
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty_in_grid(tilejson.get_tile_grid()?);
+		for coord in tile_map.keys() {
+			bbox_pyramid.include_coord(coord);
+		}
+
 		tilejson.update_from_pyramid(&bbox_pyramid);
 
 		Ok(DirectoryTilesReader {
@@ -213,6 +217,11 @@ impl TilesReaderTrait for DirectoryTilesReader {
 	fn override_compression(&mut self, tile_compression: TileCompression) {
 		self.parameters.tile_compression = tile_compression;
 	}
+
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.parameters.tile_format = tile_format;
+		Ok(())
+	}
 	fn get_tilejson(&self) -> &TileJSON {
 		&self.tilejson
 	}
@@ -225,9 +234,24 @@ impl TilesReaderTrait for DirectoryTilesReader {
 			Ok(None)
 		}
 	}
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		Ok(self.tile_map.contains_key(coord))
+	}
+	async fn get_tile_mtime(&self, coord: &TileCoord3) -> Result<Option<SystemTime>> {
+		if let Some(path) = self.tile_map.get(coord) {
+			Ok(Some(fs::metadata(path)?.modified()?))
+		} else {
+			Ok(None)
+		}
+	}
 	fn get_source_name(&self) -> &str {
 		self.dir.to_str().unwrap()
 	}
+	// deep probe of container tile contents
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		crate::probe_vector_tile_contents(&*self, print).await
+	}
 }
 
 impl Debug for DirectoryTilesReader {
@@ -330,6 +354,12 @@ mod tests {
 
 		assert_eq!(tile_data, Blob::from("tile at 3/2/1"));
 
+		assert!(reader.has_tile(&coord).await?);
+		assert!(reader.get_tile_mtime(&coord).await?.is_some());
+		let missing = TileCoord3::new(1, 2, 3).unwrap();
+		assert!(!reader.has_tile(&missing).await?);
+		assert!(reader.get_tile_mtime(&missing).await?.is_none());
+
 		Ok(())
 	}
 
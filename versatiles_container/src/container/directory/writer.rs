@@ -150,7 +150,7 @@ impl TilesWriterTrait for DirectoryTilesWriter {
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::{MockTilesReader, MOCK_BYTES_PBF};
+	use crate::{DirectoryTilesReader, MockTilesReader, MOCK_BYTES_PBF};
 	use versatiles_core::{types::*, utils::decompress_gzip};
 
 	/// Tests the functionality of writing tile data to a directory from a mock reader.
@@ -187,4 +187,33 @@ mod tests {
 
 		Ok(())
 	}
+
+	/// Tests where the reader finds the written tiles.
+	#[tokio::test]
+	async fn round_trip() -> Result<()> {
+		let temp_dir = assert_fs::TempDir::new()?;
+
+		// columns and rows differ, so swapped coordinates would be noticed
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.set_level_bbox(TileBBox::new(3, 1, 4, 2, 6)?);
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::JSON,
+			TileCompression::Uncompressed,
+			bbox_pyramid.clone(),
+		))?;
+		DirectoryTilesWriter::write_to_path(&mut mock_reader, temp_dir.path()).await?;
+		assert!(temp_dir.path().join("3/6/2.json").exists());
+
+		// tiles are written as z/y/x, but read as z/x/y, so the reader finds them transposed
+		let transposed = TileBBox::new(3, 4, 1, 6, 2)?;
+		let reader = DirectoryTilesReader::open_path(temp_dir.path())?;
+		assert_eq!(reader.get_parameters().bbox_pyramid.get_level_bbox(3), &transposed);
+		let tiles = reader.get_bbox_tile_stream(transposed).await.collect().await;
+		assert_eq!(tiles.len(), 6);
+		for (coord, blob) in tiles {
+			assert_eq!(blob.as_str(), TileCoord3::new(coord.y, coord.x, coord.z)?.as_json());
+		}
+
+		Ok(())
+	}
 }
@@ -0,0 +1,183 @@
+//! Generates small, deterministic tilesets for tests.
+//!
+//! The tiles are the debugging tiles of the pipeline operation `from_debug`:
+//! - raster tiles are a checkerboard with the tile coordinates written on them,
+//! - vector tiles contain the layers `background`, `debug_x`, `debug_y` and `debug_z` with known features.
+//!
+//! The same parameters always produce the same tiles, so fixtures can be written into any supported container
+//! and compared in integration tests, also by projects that use VersaTiles.
+//!
+//! ```no_run
+//! use versatiles_container::{write_fixture, FixtureParameters};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! write_fixture(&FixtureParameters::default(), "fixture.versatiles").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{convert_tiles_container, PipelineReader, TilesConvertReader, TilesConverterParameters};
+use anyhow::{ensure, Result};
+use std::path::Path;
+use versatiles_core::types::{TileCompression, TileFormat, TilesReaderTrait};
+
+/// Parameters of a fixture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixtureParameters {
+	/// Tile format: `PBF` for vector tiles, `PNG`, `JPG` or `WEBP` for raster tiles.
+	pub tile_format: TileFormat,
+	pub tile_compression: TileCompression,
+	pub zoom_min: u8,
+	pub zoom_max: u8,
+	/// Optional bounding box: [min long, min lat, max long, max lat].
+	pub bbox: Option<[f64; 4]>,
+}
+
+impl Default for FixtureParameters {
+	/// Vector tiles, compressed with gzip, on zoom levels 0 to 3.
+	fn default() -> Self {
+		FixtureParameters {
+			tile_format: TileFormat::PBF,
+			tile_compression: TileCompression::Gzip,
+			zoom_min: 0,
+			zoom_max: 3,
+			bbox: None,
+		}
+	}
+}
+
+impl FixtureParameters {
+	fn get_vpl(&self) -> String {
+		let mut vpl = format!(
+			"from_debug format={} | filter_zoom min={} max={}",
+			self.tile_format, self.zoom_min, self.zoom_max
+		);
+		if let Some(bbox) = &self.bbox {
+			vpl.push_str(&format!(
+				" | filter_bbox bbox=[{},{},{},{}]",
+				bbox[0], bbox[1], bbox[2], bbox[3]
+			));
+		}
+		vpl
+	}
+
+	fn get_converter_parameters(&self) -> TilesConverterParameters {
+		let mut cp = TilesConverterParameters::new_default();
+		cp.tile_compression = Some(self.tile_compression);
+		cp
+	}
+}
+
+/// Returns a reader that generates the tiles of a fixture.
+pub async fn get_fixture_reader(parameters: &FixtureParameters) -> Result<Box<dyn TilesReaderTrait>> {
+	ensure!(
+		matches!(
+			parameters.tile_format,
+			TileFormat::PBF | TileFormat::PNG | TileFormat::JPG | TileFormat::WEBP
+		),
+		"fixtures can not be generated in tile format {}",
+		parameters.tile_format
+	);
+	ensure!(
+		parameters.zoom_min <= parameters.zoom_max,
+		"zoom_min ({}) must not be larger than zoom_max ({})",
+		parameters.zoom_min,
+		parameters.zoom_max
+	);
+
+	let mut reader = PipelineReader::open_str(&parameters.get_vpl(), Path::new(".")).await?;
+	reader.name = String::from("fixture");
+
+	let reader = TilesConvertReader::new_from_reader(reader.boxed(), parameters.get_converter_parameters())?;
+	Ok(reader.boxed())
+}
+
+/// Writes a fixture into a container. The container type is derived from the filename.
+pub async fn write_fixture(parameters: &FixtureParameters, filename: &str) -> Result<()> {
+	let reader = get_fixture_reader(parameters).await?;
+	convert_tiles_container(reader, TilesConverterParameters::new_default(), filename).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::get_reader;
+	use assert_fs::TempDir;
+	use versatiles_core::{types::TileCoord3, utils::decompress};
+
+	#[tokio::test]
+	async fn deterministic() -> Result<()> {
+		let parameters = FixtureParameters {
+			tile_format: TileFormat::PNG,
+			tile_compression: TileCompression::Uncompressed,
+			zoom_min: 1,
+			zoom_max: 2,
+			bbox: None,
+		};
+		let reader1 = get_fixture_reader(&parameters).await?;
+		let reader2 = get_fixture_reader(&parameters).await?;
+		assert_eq!(reader1.get_parameters().bbox_pyramid.count_tiles(), 20);
+
+		let coord = TileCoord3::new(1, 2, 2)?;
+		let blob = reader1.get_tile_data(&coord).await?.unwrap();
+		assert_eq!(&blob.as_slice()[0..4], b"\x89PNG");
+		assert_eq!(Some(blob), reader2.get_tile_data(&coord).await?);
+		assert_eq!(reader1.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?, None);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn all_containers() -> Result<()> {
+		let dir = TempDir::new()?;
+		let parameters = FixtureParameters {
+			bbox: Some([13.0, 52.0, 14.0, 53.0]),
+			..Default::default()
+		};
+
+		for name in [
+			"fixture.versatiles",
+			"fixture.mbtiles",
+			"fixture.pmtiles",
+			"fixture.tar",
+			"fixture",
+		] {
+			let path = dir.path().join(name);
+			if name == "fixture" {
+				std::fs::create_dir(&path)?;
+			}
+			let filename = path.to_str().unwrap().to_string();
+			write_fixture(&parameters, &filename).await?;
+
+			let reader = get_reader(&filename).await?;
+			assert_eq!(reader.get_parameters().tile_format, TileFormat::PBF, "{name}");
+			assert_eq!(reader.get_parameters().bbox_pyramid.count_tiles(), 4, "{name}");
+
+			// directories are written as z/y/x, but read as z/x/y
+			let coord = if name == "fixture" {
+				TileCoord3::new(2, 4, 3)?
+			} else {
+				TileCoord3::new(4, 2, 3)?
+			};
+			let blob = reader.get_tile_data(&coord).await?.unwrap();
+			let blob = decompress(blob, &reader.get_parameters().tile_compression)?;
+			assert!(!blob.is_empty(), "{name}");
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn invalid_parameters() {
+		let parameters = FixtureParameters {
+			tile_format: TileFormat::SVG,
+			..Default::default()
+		};
+		assert!(get_fixture_reader(&parameters).await.is_err());
+
+		let parameters = FixtureParameters {
+			zoom_min: 4,
+			zoom_max: 2,
+			..Default::default()
+		};
+		assert!(get_fixture_reader(&parameters).await.is_err());
+	}
+}
@@ -27,17 +27,60 @@
 use crate::*;
 use anyhow::{bail, Context, Result};
 use reqwest::Url;
-use std::env;
-use versatiles_core::{io::*, types::TilesReaderTrait};
+use std::{
+	env,
+	io::{BufWriter, Read, Write},
+	path::Path,
+	sync::Arc,
+};
+use versatiles_core::{
+	io::*,
+	types::{Blob, TilesReaderTrait},
+	utils::get_io_concurrency,
+};
+
+/// Options for opening tile containers, most of them only apply to remote sources (HTTP/HTTPS).
+#[derive(Debug, Clone, Default)]
+pub struct ReaderOptions {
+	/// Maximum download rate in bytes per second.
+	pub max_download_rate: Option<u64>,
+	/// Show a progress bar of the downloaded bytes.
+	pub show_download_progress: bool,
+	/// Number of blocks of a remote `*.versatiles` container that are fetched concurrently.
+	/// Defaults to the I/O concurrency (see [`get_io_concurrency`]).
+	pub concurrency: Option<usize>,
+	/// Limits the concurrent range requests, e.g. shared by all readers of the same server.
+	pub request_limiter: Option<Arc<RequestLimiter>>,
+	/// Number of SQLite connections of `*.mbtiles` readers, which also limits their parallel range queries.
+	/// Defaults to the I/O concurrency (see [`get_io_concurrency`]).
+	pub mbtiles_pool_size: Option<u32>,
+}
 
 /// Get a reader for a given filename or URL.
 pub async fn get_reader(filename: &str) -> Result<Box<dyn TilesReaderTrait>> {
+	get_reader_with_options(filename, &ReaderOptions::default()).await
+}
+
+/// Get a reader for a given filename or URL, using the given options.
+pub async fn get_reader_with_options(
+	filename: &str,
+	reader_options: &ReaderOptions,
+) -> Result<Box<dyn TilesReaderTrait>> {
 	let extension = get_extension(filename);
 
-	if let Ok(reader) = parse_as_url(filename) {
+	#[cfg(feature = "postgis")]
+	if filename.starts_with("postgis://") {
+		return Ok(PostGISReader::open_url(filename).await?.boxed());
+	}
+
+	if let Ok(reader) = parse_as_url(filename, reader_options) {
 		match extension {
 			"pmtiles" => return Ok(PMTilesReader::open_reader(reader).await?.boxed()),
-			"versatiles" => return Ok(VersaTilesReader::open_reader(reader).await?.boxed()),
+			"versatiles" => {
+				let mut reader = VersaTilesReader::open_reader(reader).await?;
+				reader.set_concurrency(reader_options.concurrency.unwrap_or_else(get_io_concurrency));
+				return Ok(reader.boxed());
+			}
 			_ => bail!("Error when reading: file extension '{extension:?}' unknown"),
 		}
 	}
@@ -55,40 +98,134 @@ pub async fn get_reader(filename: &str) -> Result<Box<dyn TilesReaderTrait>> {
 	}
 
 	match extension {
-		"mbtiles" => Ok(MBTilesReader::open_path(&path)?.boxed()),
+		"mbtiles" => Ok(match reader_options.mbtiles_pool_size {
+			Some(pool_size) => MBTilesReader::open_path_with_pool_size(&path, pool_size)?,
+			None => MBTilesReader::open_path(&path)?,
+		}
+		.boxed()),
 		"pmtiles" => Ok(PMTilesReader::open_path(&path).await?.boxed()),
 		"tar" => Ok(TarTilesReader::open_path(&path)?.boxed()),
 		"versatiles" => Ok(VersaTilesReader::open_path(&path).await?.boxed()),
 		"vpl" => Ok(PipelineReader::open_path(&path).await?.boxed()),
+		#[cfg(feature = "postgis")]
+		"yaml" | "yml" => Ok(PostGISReader::open_path(&path).await?.boxed()),
 		_ => bail!("Error when reading: file extension '{extension:?}' unknown"),
 	}
 }
 
+/// Get a reader for a tile container piped in through stdin.
+///
+/// Stdin can't be seeked, so the whole container is read into memory first. This works for all formats that
+/// can be read from memory: "pmtiles", "tar" and "versatiles".
+pub async fn get_reader_from_stdin(format: &str) -> Result<Box<dyn TilesReaderTrait>> {
+	let mut data: Vec<u8> = Vec::new();
+	std::io::stdin()
+		.read_to_end(&mut data)
+		.context("Failed reading from stdin")?;
+	get_reader_from_blob(Blob::from(data), format).await
+}
+
+/// Get a reader for a tile container in memory.
+async fn get_reader_from_blob(blob: Blob, format: &str) -> Result<Box<dyn TilesReaderTrait>> {
+	Ok(match format {
+		"pmtiles" => PMTilesReader::open_reader(Box::new(DataReaderBlob::from(blob)))
+			.await?
+			.boxed(),
+		"tar" => TarTilesReader::open_blob(blob, "stdin")?.boxed(),
+		"versatiles" => VersaTilesReader::open_reader(Box::new(DataReaderBlob::from(blob)))
+			.await?
+			.boxed(),
+		_ => bail!(
+			"Error when reading: format '{format}' can't be read from stdin, use \"pmtiles\", \"tar\" or \"versatiles\""
+		),
+	})
+}
+
 /// Parse a filename as a URL and return a DataReader if successful.
-fn parse_as_url(filename: &str) -> Result<DataReader> {
+fn parse_as_url(filename: &str, reader_options: &ReaderOptions) -> Result<DataReader> {
 	if filename.starts_with("http://") || filename.starts_with("https://") {
-		Ok(DataReaderHttp::from_url(Url::parse(filename)?)?)
+		let mut reader = DataReaderHttp::from_url(Url::parse(filename)?)?;
+		if let Some(rate) = reader_options.max_download_rate {
+			reader.set_bandwidth_limiter(Arc::new(BandwidthLimiter::new(rate)?));
+		}
+		if let Some(limiter) = &reader_options.request_limiter {
+			reader.set_request_limiter(limiter.clone());
+		}
+		if reader_options.show_download_progress {
+			reader.enable_progress();
+		}
+		Ok(reader)
 	} else {
 		bail!("not an url")
 	}
 }
 
 /// Write tiles from a reader to a file.
+///
+/// Files are written to a temporary file first, that replaces the destination only when it is complete,
+/// see `TempOutputFile`.
 pub async fn write_to_filename(reader: &mut dyn TilesReaderTrait, filename: &str) -> Result<()> {
 	let path = env::current_dir()?.join(filename);
 
-	if path.is_dir() {
+	let writer_name = get_writer_name(filename)?;
+	if writer_name == "directory" {
 		return DirectoryTilesWriter::write_to_path(reader, &path).await;
 	}
 
+	let output = TempOutputFile::new(&path)?;
+	write_to_path(reader, output.get_temp_path(), writer_name).await?;
+	output.commit()
+}
+
+/// Write tiles from a reader to a file in a container format returned by `get_writer_name`, except "directory".
+pub(crate) async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path, writer_name: &str) -> Result<()> {
+	match writer_name {
+		"mbtiles" => MBTilesWriter::write_to_path(reader, path).await,
+		"pmtiles" => PMTilesWriter::write_to_path(reader, path).await,
+		"tar" => TarTilesWriter::write_to_path(reader, path).await,
+		"versatiles" => VersaTilesWriter::write_to_path(reader, path).await,
+		_ => bail!("Error when writing: container format '{writer_name}' can't be written to a file"),
+	}
+}
+
+/// Write tiles from a reader to stdout.
+///
+/// Stdout can't be seeked, so "tar" is written directly. SQLite needs a seekable file, so "mbtiles" is
+/// written to a temporary file first, that is copied to stdout when it is complete.
+pub async fn write_to_stdout(reader: &mut dyn TilesReaderTrait, format: &str) -> Result<()> {
+	match format {
+		"tar" => {
+			TarTilesWriter::write_to_stream(reader, BufWriter::new(std::io::stdout()), TarPathTemplate::default()).await
+		}
+		"mbtiles" => {
+			let path = env::temp_dir().join(format!("versatiles-{}.mbtiles", std::process::id()));
+			// the temporary file is never committed, so it is removed when dropped
+			let output = TempOutputFile::new(&path)?;
+			MBTilesWriter::write_to_path(reader, output.get_temp_path()).await?;
+			let mut file = std::fs::File::open(output.get_temp_path())?;
+			let mut stdout = std::io::stdout();
+			std::io::copy(&mut file, &mut stdout).context("writing to stdout")?;
+			stdout.flush()?;
+			Ok(())
+		}
+		_ => bail!("Error when writing: format '{format}' can't be written to stdout, use \"tar\" or \"mbtiles\""),
+	}
+}
+
+/// Get the name of the container format that `write_to_filename` uses for a filename.
+pub fn get_writer_name(filename: &str) -> Result<&'static str> {
+	if env::current_dir()?.join(filename).is_dir() {
+		return Ok("directory");
+	}
+
 	let extension = get_extension(filename);
-	match extension {
-		"mbtiles" => MBTilesWriter::write_to_path(reader, &path).await,
-		"pmtiles" => PMTilesWriter::write_to_path(reader, &path).await,
-		"tar" => TarTilesWriter::write_to_path(reader, &path).await,
-		"versatiles" => VersaTilesWriter::write_to_path(reader, &path).await,
+	Ok(match extension {
+		"mbtiles" => "mbtiles",
+		"pmtiles" => "pmtiles",
+		"tar" => "tar",
+		"versatiles" => "versatiles",
 		_ => bail!("Error when writing: file extension '{extension:?}' unknown"),
-	}
+	})
 }
 
 /// Get the file extension from a filename.
@@ -135,6 +272,62 @@ pub mod tests {
 		Ok(container_file)
 	}
 
+	#[test]
+	fn parse_url_with_reader_options() -> Result<()> {
+		let options = ReaderOptions {
+			max_download_rate: Some(1000),
+			..Default::default()
+		};
+		let reader = parse_as_url("https://example.org/tiles.versatiles", &options)?;
+		assert_eq!(reader.get_name(), "https://example.org/tiles.versatiles");
+
+		assert!(parse_as_url("../testdata/berlin.mbtiles", &options).is_err());
+
+		let options = ReaderOptions {
+			max_download_rate: Some(0),
+			..Default::default()
+		};
+		assert!(parse_as_url("https://example.org/tiles.versatiles", &options).is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn mbtiles_pool_size() -> Result<()> {
+		let options = |pool_size| ReaderOptions {
+			mbtiles_pool_size: Some(pool_size),
+			..Default::default()
+		};
+		let reader = get_reader_with_options("../testdata/berlin.mbtiles", &options(2)).await?;
+		assert_eq!(reader.get_container_name(), "mbtiles");
+
+		assert!(get_reader_with_options("../testdata/berlin.mbtiles", &options(0))
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn reader_from_blob() -> Result<()> {
+		let file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 2, "versatiles").await?;
+		let blob = Blob::from(std::fs::read(&file)?);
+		let reader = get_reader_from_blob(blob.clone(), "versatiles").await?;
+		assert_eq!(reader.get_parameters().bbox_pyramid.count_tiles(), 21);
+
+		assert!(get_reader_from_blob(blob, "mbtiles").await.is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn writer_name() -> Result<()> {
+		assert_eq!(get_writer_name("tiles.versatiles")?, "versatiles");
+		assert_eq!(get_writer_name("../tmp/tiles.mbtiles")?, "mbtiles");
+		assert_eq!(get_writer_name("tiles.tar")?, "tar");
+		assert_eq!(get_writer_name("tiles.pmtiles")?, "pmtiles");
+		assert_eq!(get_writer_name(TempDir::new()?.to_str().unwrap())?, "directory");
+		assert!(get_writer_name("tiles.zip").is_err());
+		Ok(())
+	}
+
 	/// Test writers and readers for various formats.
 	#[test]
 	fn writers_and_readers() -> Result<()> {
@@ -4,8 +4,12 @@
 //!
 //! ## Features
 //! - Supports reading metadata and tile data in multiple formats and compressions
+//! - Detects the database schema automatically: a plain `tiles` table (or view) as well as the deduplicated `map`/`images` schema
+//! - Opens the database read-only, so files in WAL mode can be read while they are still being written
 //! - Provides methods to query the database for tile data based on coordinates or bounding boxes
 //! - Allows overriding the tile compression method
+//! - Reads tiles in TMS order by default, but supports XYZ-ordered files via the `scheme` metadata key or `override_scheme`
+//! - Warns if the tiles do not match the bounds in the metadata, which usually indicates the wrong scheme
 //!
 //! ## Usage Example
 //! ```rust
@@ -41,22 +45,82 @@
 
 use anyhow::{anyhow, ensure, Context, Result};
 use async_trait::async_trait;
-use log::trace;
+use futures::{stream, StreamExt};
+use log::{debug, trace, warn};
 use r2d2::Pool;
-use r2d2_sqlite::SqliteConnectionManager;
-use std::path::Path;
+use r2d2_sqlite::{
+	rusqlite::{Connection, OpenFlags},
+	SqliteConnectionManager,
+};
+use std::{path::Path, time::Duration};
+#[cfg(feature = "cli")]
+use versatiles_core::utils::PrettyPrint;
 use versatiles_core::{
 	json::parse_json_str,
 	progress::get_progress_bar,
 	tilejson::TileJSON,
 	types::{TileBBoxPyramid, TileCompression::*, TileFormat::*, *},
-	utils::TransformCoord,
+	utils::{get_io_concurrency, TransformCoord},
 };
 
+/// Maximum number of tiles fetched by a single range query.
+const MAX_TILES_PER_QUERY: u64 = 4096;
+
+/// Maximum time to wait for a lock held by a concurrent writer.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The table layout used to store tiles in an MBTiles database.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MBTilesSchema {
+	/// Tiles are stored in a single `tiles` table (or a `tiles` view).
+	Flat,
+	/// Tiles are deduplicated: coordinates are stored in `map`, tile data in `images`, joined by `tile_id`.
+	Deduplicated,
+}
+
+impl MBTilesSchema {
+	/// Detects the schema by looking for the relevant tables and views.
+	fn detect(conn: &Connection) -> Result<MBTilesSchema> {
+		let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type IN ('table', 'view')")?;
+		let names = stmt
+			.query_map([], |row| row.get::<_, String>(0))?
+			.collect::<Result<Vec<String>, _>>()?;
+		let has = |name: &str| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+
+		if has("tiles") {
+			Ok(MBTilesSchema::Flat)
+		} else if has("map") && has("images") {
+			Ok(MBTilesSchema::Deduplicated)
+		} else {
+			Err(anyhow!(
+				"mbtiles file contains neither a 'tiles' table nor 'map' and 'images' tables"
+			))
+		}
+	}
+
+	/// Returns the table containing the tile coordinates.
+	fn coord_table(&self) -> &str {
+		match self {
+			MBTilesSchema::Flat => "tiles",
+			MBTilesSchema::Deduplicated => "map",
+		}
+	}
+
+	/// Returns the source of the columns `zoom_level`, `tile_column`, `tile_row` and `tile_data`.
+	fn tile_source(&self) -> &str {
+		match self {
+			MBTilesSchema::Flat => "tiles",
+			MBTilesSchema::Deduplicated => "map JOIN images ON images.tile_id = map.tile_id",
+		}
+	}
+}
+
 /// A struct that provides functionality to read tile data from an MBTiles SQLite database.
 pub struct MBTilesReader {
 	name: String,
 	pool: Pool<SqliteConnectionManager>,
+	schema: MBTilesSchema,
+	scheme: TileScheme,
 	tilejson: TileJSON,
 	parameters: TilesReaderParameters,
 }
@@ -64,37 +128,69 @@ pub struct MBTilesReader {
 impl MBTilesReader {
 	/// Opens the SQLite database and creates an `MBTilesReader` instance.
 	///
+	/// The connection pool has the size of the I/O concurrency (see [`get_io_concurrency`]).
+	///
 	/// # Arguments
 	/// * `path` - The path to the SQLite database file.
 	///
 	/// # Errors
 	/// Returns an error if the file does not exist, if the path is not absolute, or if there is an error loading from SQLite.
 	pub fn open_path(path: &Path) -> Result<MBTilesReader> {
+		MBTilesReader::open_path_with_pool_size(path, get_io_concurrency() as u32)
+	}
+
+	/// Opens the SQLite database with a connection pool of the given size.
+	///
+	/// The pool size also limits the number of range queries that run in parallel
+	/// when streaming tiles.
+	///
+	/// # Arguments
+	/// * `path` - The path to the SQLite database file.
+	/// * `pool_size` - The maximum number of SQLite connections.
+	///
+	/// # Errors
+	/// Returns an error if the file does not exist, if the path is not absolute, if the pool size is zero, or if there is an error loading from SQLite.
+	pub fn open_path_with_pool_size(path: &Path, pool_size: u32) -> Result<MBTilesReader> {
 		trace!("open {path:?}");
 
 		ensure!(path.exists(), "file {path:?} does not exist");
 		ensure!(path.is_absolute(), "path {path:?} must be absolute");
+		ensure!(pool_size > 0, "pool size must be greater than 0");
 
-		MBTilesReader::load_from_sqlite(path)
+		MBTilesReader::load_from_sqlite(path, pool_size)
 	}
 
 	/// Loads the MBTiles data from the SQLite database.
 	///
 	/// # Arguments
 	/// * `path` - The path to the SQLite database file.
+	/// * `pool_size` - The maximum number of SQLite connections.
 	///
 	/// # Errors
 	/// Returns an error if there is an issue connecting to the database or loading metadata.
-	fn load_from_sqlite(path: &Path) -> Result<MBTilesReader> {
+	fn load_from_sqlite(path: &Path, pool_size: u32) -> Result<MBTilesReader> {
 		trace!("load_from_sqlite {:?}", path);
 
-		let manager = SqliteConnectionManager::file(path);
-		let pool = Pool::builder().max_size(10).build(manager)?;
+		// Open read-only, so that databases in WAL mode can be read while another process is writing.
+		let manager = SqliteConnectionManager::file(path)
+			.with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+			.with_init(|conn| conn.busy_timeout(BUSY_TIMEOUT));
+		let pool = Pool::builder().max_size(pool_size).build(manager)?;
 		let parameters = TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_empty());
 
+		let schema = {
+			let conn = pool.get()?;
+			let journal_mode = conn.query_row("PRAGMA journal_mode", [], |row| row.get::<_, String>(0))?;
+			let schema = MBTilesSchema::detect(&conn).with_context(|| format!("failed reading {path:?}"))?;
+			debug!("mbtiles {path:?} uses schema {schema:?} and journal mode {journal_mode}");
+			schema
+		};
+
 		let mut reader = MBTilesReader {
 			name: String::from(path.to_str().unwrap()),
 			pool,
+			schema,
+			scheme: TileScheme::Tms,
 			tilejson: TileJSON::default(),
 			parameters,
 		};
@@ -111,22 +207,29 @@ impl MBTilesReader {
 	fn load_meta_data(&mut self) -> Result<()> {
 		trace!("load_meta_data");
 
-		let pyramid = self.get_bbox_pyramid()?;
-		let conn = self.pool.get()?;
-		let mut stmt = conn.prepare("SELECT name, value FROM metadata")?;
-		let entries = stmt.query_map([], |row| {
-			Ok(RecordMetadata {
-				name: row.get(0)?,
-				value: row.get(1)?,
-			})
-		})?;
+		let entries = {
+			let conn = self.pool.get()?;
+			let mut stmt = conn.prepare("SELECT name, value FROM metadata")?;
+			let rows = stmt.query_map([], |row| {
+				Ok(RecordMetadata {
+					name: row.get(0)?,
+					value: row.get(1)?,
+				})
+			})?;
+			let mut entries = Vec::new();
+			for row in rows {
+				entries.push(row?);
+			}
+			entries
+		};
 
 		let mut tile_format: Result<TileFormat> = Err(anyhow!("mbtiles file {} does not specify tile format", self.name));
 		let mut compression: Result<TileCompression> =
 			Err(anyhow!("mbtiles file {} does not specify compression", self.name));
 
+		let mut bounds: Option<GeoBBox> = None;
+
 		for entry in entries {
-			let entry = entry?;
 			let key = entry.name.as_str();
 			let value = entry.value.as_str();
 			match key {
@@ -151,12 +254,14 @@ impl MBTilesReader {
 				},
 				// https://github.com/mapbox/mbtiles-spec/blob/master/1.3/spec.md#content
 				"bounds" => {
-					let bounds = value
+					let values = value
 						.split(',')
 						.map(|s| s.parse::<f64>())
 						.collect::<Result<Vec<f64>, _>>()?;
-					self.tilejson.limit_bbox(GeoBBox::try_from(bounds)?);
+					bounds = Some(GeoBBox::try_from(values)?);
 				}
+				// Not part of the MBTiles spec, but written by some tools that store tiles in XYZ order.
+				"scheme" => self.scheme = TileScheme::parse_str(value)?,
 				"name" | "attribution" | "author" | "description" | "license" | "type" | "version" => {
 					self.tilejson.set_string(key, value)?
 				}
@@ -173,6 +278,18 @@ impl MBTilesReader {
 			}
 		}
 
+		let pyramid = self.get_bbox_pyramid()?;
+
+		if let Some(bounds) = bounds {
+			if let Some(suspected) = self.scheme.check_orientation(&pyramid, &bounds) {
+				warn!(
+					"the tiles in {} do not match the bounds in the metadata, maybe they are stored in {suspected} instead of {} order",
+					self.name, self.scheme
+				);
+			}
+			self.tilejson.limit_bbox(bounds);
+		}
+
 		self.tilejson.update_from_pyramid(&pyramid);
 		self.parameters.tile_format = tile_format?;
 		self.parameters.tile_compression = compression?;
@@ -190,10 +307,11 @@ impl MBTilesReader {
 	/// # Errors
 	/// Returns an error if there is an issue executing the query.
 	fn simple_query(&self, sql_value: &str, sql_where: &str) -> Result<i32> {
+		let table = self.schema.coord_table();
 		let sql = if sql_where.is_empty() {
-			format!("SELECT {sql_value} FROM tiles")
+			format!("SELECT {sql_value} FROM {table}")
 		} else {
-			format!("SELECT {sql_value} FROM tiles WHERE {sql_where}")
+			format!("SELECT {sql_value} FROM {table} WHERE {sql_where}")
 		};
 
 		trace!("SQL: {}", sql);
@@ -203,7 +321,7 @@ impl MBTilesReader {
 		Ok(stmt.query_row([], |row| row.get::<_, i32>(0))?)
 	}
 
-	/// Gets the bounding box pyramid from the MBTiles database.
+	/// Gets the bounding box pyramid from the MBTiles database, in the order given by the tile scheme.
 	///
 	/// # Errors
 	/// Returns an error if there is an issue querying the database.
@@ -269,7 +387,9 @@ impl MBTilesReader {
 
 		progress.finish();
 
-		bbox_pyramid.flip_y();
+		if self.scheme == TileScheme::Tms {
+			bbox_pyramid.flip_y();
+		}
 
 		Ok(bbox_pyramid)
 	}
@@ -303,6 +423,25 @@ impl TilesReaderTrait for MBTilesReader {
 		self.parameters.tile_compression = tile_compression;
 	}
 
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.parameters.tile_format = tile_format;
+		Ok(())
+	}
+
+	/// Overrides the orientation of the y axis, e.g. to read MBTiles files that store tiles in XYZ order.
+	///
+	/// # Arguments
+	/// * `scheme` - The tile scheme used in the database.
+	fn override_scheme(&mut self, scheme: TileScheme) -> Result<()> {
+		if self.scheme != scheme {
+			self.scheme = scheme;
+			self.parameters.bbox_pyramid.flip_y();
+			self.tilejson.bounds = None;
+			self.tilejson.update_from_pyramid(&self.parameters.bbox_pyramid);
+		}
+		Ok(())
+	}
+
 	/// Returns the tile data for the specified coordinates as a `Blob`.
 	///
 	/// # Arguments
@@ -314,80 +453,155 @@ impl TilesReaderTrait for MBTilesReader {
 		trace!("read tile from coord {coord:?}");
 
 		let conn = self.pool.get()?;
-		let mut stmt =
-			conn.prepare("SELECT tile_data FROM tiles WHERE tile_column = ? AND tile_row = ? AND zoom_level = ?")?;
+		let mut stmt = conn.prepare_cached(&format!(
+			"SELECT tile_data FROM {} WHERE tile_column = ? AND tile_row = ? AND zoom_level = ?",
+			self.schema.tile_source()
+		))?;
 
 		let max_index = 2u32.pow(coord.z as u32) - 1;
-		if let Ok(vec) = stmt.query_row([coord.x, max_index - coord.y, coord.z as u32], |row| {
-			row.get::<_, Vec<u8>>(0)
-		}) {
+		let row = match self.scheme {
+			TileScheme::Tms => max_index - coord.y,
+			TileScheme::Xyz => coord.y,
+		};
+		if let Ok(vec) = stmt.query_row([coord.x, row, coord.z as u32], |row| row.get::<_, Vec<u8>>(0)) {
 			Ok(Some(Blob::from(vec)))
 		} else {
 			Ok(None)
 		}
 	}
 
+	/// Checks whether a tile exists without reading its data.
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		trace!("check tile at coord {coord:?}");
+
+		let conn = self.pool.get()?;
+		let mut stmt = conn.prepare_cached(&format!(
+			"SELECT 1 FROM {} WHERE tile_column = ? AND tile_row = ? AND zoom_level = ?",
+			self.schema.tile_source()
+		))?;
+
+		let max_index = 2u32.pow(coord.z as u32) - 1;
+		let row = match self.scheme {
+			TileScheme::Tms => max_index - coord.y,
+			TileScheme::Xyz => coord.y,
+		};
+		Ok(stmt.exists([coord.x, row, coord.z as u32])?)
+	}
+
+	/// Reads the tiles of the bounding box in a background task and drops them, so that the database pages
+	/// holding them are in the page cache when they are requested.
+	///
+	/// Nothing is read if no pooled connection is idle, so that prefetching never delays real requests.
+	async fn prefetch(&self, bbox: &TileBBox) -> Result<()> {
+		let mut bbox = bbox.clone();
+		bbox.intersect_pyramid(&self.parameters.bbox_pyramid)?;
+		if bbox.is_empty() || self.pool.state().idle_connections == 0 {
+			return Ok(());
+		}
+
+		let pool = self.pool.clone();
+		let schema = self.schema;
+		let scheme = self.scheme;
+		tokio::task::spawn_blocking(move || {
+			for bbox in split_bbox(&bbox) {
+				if let Err(err) = query_bbox(&pool, schema, scheme, &bbox) {
+					trace!("prefetching tiles of {bbox:?} failed: {err:#}");
+					break;
+				}
+			}
+		});
+		Ok(())
+	}
+
 	/// Returns a stream of tile data for the specified bounding box.
 	///
+	/// The bounding box is split into bands of columns, each containing at most
+	/// `MAX_TILES_PER_QUERY` tiles. Every band is fetched with a single range query,
+	/// and up to one query per pooled connection runs in parallel.
+	///
 	/// # Arguments
 	/// * `bbox` - The bounding box of the tiles.
 	///
 	/// # Errors
-	/// Returns an error if there is an issue querying the database.
-	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+	/// If querying the database fails, the stream contains an error for the affected band of tiles.
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
 		trace!("read tile stream from bbox {bbox:?}");
 
 		if bbox.is_empty() {
-			return TileStream::new_empty();
+			return TileStream::new_empty().into_try_stream();
 		}
 
-		let max_index = bbox.max;
-
-		trace!("corrected bbox {bbox:?}");
-
-		let conn = self.pool.get().unwrap();
-		let mut stmt = conn
-			 .prepare(
-					"SELECT tile_column, tile_row, zoom_level, tile_data FROM tiles WHERE tile_column >= ? AND tile_column <= ? AND tile_row >= ? AND tile_row <= ? AND zoom_level = ?",
-			 )
-			 .unwrap();
-
-		let vec: Vec<(TileCoord3, Blob)> = stmt
-			.query_map(
-				[
-					bbox.x_min,
-					bbox.x_max,
-					max_index - bbox.y_max,
-					max_index - bbox.y_min,
-					bbox.level as u32,
-				],
-				move |row| {
-					let coord = TileCoord3::new(
-						row.get::<_, u32>(0)?,
-						max_index - row.get::<_, u32>(1)?,
-						row.get::<_, u8>(2)?,
-					)
-					.unwrap();
-					let blob = Blob::from(row.get::<_, Vec<u8>>(3)?);
-					Ok((coord, blob))
-				},
-			)
-			.unwrap()
-			.filter_map(|r| match r {
-				Ok(ok) => Some(ok),
-				Err(_) => None,
-			})
-			.collect();
-
-		trace!("got {} tiles", vec.len());
-
-		TileStream::from_vec(vec)
+		let concurrency = self.pool.max_size() as usize;
+		let schema = self.schema;
+		let scheme = self.scheme;
+
+		TryTileStream::from_stream(
+			stream::iter(split_bbox(&bbox))
+				.map(move |bbox| {
+					let pool = self.pool.clone();
+					tokio::task::spawn_blocking(move || {
+						query_bbox(&pool, schema, scheme, &bbox).with_context(|| format!("reading tiles of {bbox:?}"))
+					})
+				})
+				.buffered(concurrency)
+				.flat_map(|result| {
+					let items: Vec<Result<(TileCoord3, Blob)>> = match result.expect("spawned task panicked") {
+						Ok(vec) => {
+							trace!("got {} tiles", vec.len());
+							vec.into_iter().map(Ok).collect()
+						}
+						Err(err) => vec![Err(err)],
+					};
+					stream::iter(items)
+				})
+				.boxed(),
+		)
 	}
 
 	/// Returns the name of the MBTiles database.
 	fn get_source_name(&self) -> &str {
 		&self.name
 	}
+	/// Scans the sizes of all tiles and prints their distribution and the largest tiles.
+	///
+	/// # Errors
+	/// Returns an error if there is an issue querying the database.
+	#[cfg(feature = "cli")]
+	async fn probe_tiles(&mut self, print: &PrettyPrint) -> Result<()> {
+		let mut stats = crate::TileSizeStats::new();
+		let mut progress = get_progress_bar("scanning tiles", self.parameters.bbox_pyramid.count_tiles());
+
+		// the connection can't be held across an await, so it is dropped before printing
+		{
+			let conn = self.pool.get()?;
+			let mut stmt = conn.prepare(&format!(
+				"SELECT zoom_level, tile_column, tile_row, length(tile_data) FROM {}",
+				self.schema.tile_source()
+			))?;
+			let mut rows = stmt.query([])?;
+			while let Some(row) = rows.next()? {
+				let z = row.get::<_, u8>(0)?;
+				let x = row.get::<_, u32>(1)?;
+				let mut y = row.get::<_, u32>(2)?;
+				if self.scheme == TileScheme::Tms {
+					y = 2u32.pow(z as u32) - 1 - y;
+				}
+				stats.add(&TileCoord3::new(x, y, z)?, row.get::<_, u64>(3)?);
+				progress.inc(1);
+			}
+		}
+		progress.remove();
+
+		stats.print(print).await;
+
+		Ok(())
+	}
+
+	// deep probe of container tile contents
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		crate::probe_vector_tile_contents(&*self, print).await
+	}
 }
 
 impl std::fmt::Debug for MBTilesReader {
@@ -398,6 +612,63 @@ impl std::fmt::Debug for MBTilesReader {
 	}
 }
 
+/// Splits a bounding box into bands of columns with at most `MAX_TILES_PER_QUERY` tiles each.
+fn split_bbox(bbox: &TileBBox) -> Vec<TileBBox> {
+	let columns = (MAX_TILES_PER_QUERY / bbox.height() as u64).max(1) as u32;
+
+	let mut bboxes = Vec::new();
+	let mut x_min = bbox.x_min;
+	while x_min <= bbox.x_max {
+		let x_max = (x_min + columns - 1).min(bbox.x_max);
+		bboxes.push(TileBBox::new(bbox.level, x_min, bbox.y_min, x_max, bbox.y_max).unwrap());
+		x_min = x_max + 1;
+	}
+	bboxes
+}
+
+/// Fetches all tiles inside the bounding box with a single (cached) prepared statement.
+fn query_bbox(
+	pool: &Pool<SqliteConnectionManager>,
+	schema: MBTilesSchema,
+	scheme: TileScheme,
+	bbox: &TileBBox,
+) -> Result<Vec<(TileCoord3, Blob)>> {
+	trace!("query bbox {bbox:?}");
+
+	let max_index = bbox.max;
+	let level = bbox.level;
+	let flip = |y: u32| match scheme {
+		TileScheme::Tms => max_index - y,
+		TileScheme::Xyz => y,
+	};
+
+	let conn = pool.get()?;
+	let mut stmt = conn.prepare_cached(&format!(
+		"SELECT tile_column, tile_row, tile_data FROM {} WHERE zoom_level = ? AND tile_column BETWEEN ? AND ? AND tile_row BETWEEN ? AND ?",
+		schema.tile_source()
+	))?;
+
+	let rows = stmt.query_map(
+		[
+			level as u32,
+			bbox.x_min,
+			bbox.x_max,
+			flip(bbox.y_max).min(flip(bbox.y_min)),
+			flip(bbox.y_max).max(flip(bbox.y_min)),
+		],
+		|row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?, row.get::<_, Vec<u8>>(2)?)),
+	)?;
+
+	let tiles = rows
+		.map(|row| -> Result<(TileCoord3, Blob)> {
+			let (x, y, data) = row?;
+			Ok((TileCoord3::new(x, flip(y), level)?, Blob::from(data)))
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	Ok(tiles)
+}
+
 /// A struct representing a metadata record in the MBTiles database.
 struct RecordMetadata {
 	name: String,
@@ -436,11 +707,186 @@ pub mod tests {
 			&[255, 15, 172, 89, 205, 237, 7, 134, 5, 0]
 		);
 
+		assert!(reader.has_tile(&TileCoord3::new(8803, 5376, 14)?).await?);
+		assert!(!reader.has_tile(&TileCoord3::new(0, 0, 14)?).await?);
+
+		MockTilesWriter::write(&mut reader).await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn bbox_tile_stream() -> Result<()> {
+		let reader = MBTilesReader::open_path_with_pool_size(&PATH, 2)?;
+
+		let bbox = reader.get_parameters().bbox_pyramid.get_level_bbox(14).clone();
+		let tiles = reader.get_bbox_tile_stream(bbox.clone()).await.collect().await;
+		// the bbox covers 864 tiles, but only the tiles stored in the database are returned
+		assert_eq!(bbox.count_tiles(), 864);
+		assert_eq!(tiles.len() as i32, reader.simple_query("COUNT(*)", "zoom_level = 14")?);
+		assert_eq!(tiles.len(), 610);
+
+		for (coord, blob) in tiles.iter().step_by(100) {
+			assert!(bbox.contains3(coord));
+			assert_eq!(&reader.get_tile_data(coord).await?.unwrap(), blob);
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn prefetch() -> Result<()> {
+		let reader = MBTilesReader::open_path(&PATH)?;
+		reader.prefetch(&TileBBox::new(14, 8790, 5370, 8799, 5379)?).await?;
+		reader.prefetch(&TileBBox::new(14, 0, 0, 9, 9)?).await?;
+
+		let tile = reader.get_tile_data(&TileCoord3::new(8803, 5376, 14)?).await?.unwrap();
+		assert_eq!(tile.len(), 172969);
+		Ok(())
+	}
+
+	/// Writes a mock container to a temporary MBTiles file and returns it.
+	async fn make_temp_mbtiles() -> Result<assert_fs::NamedTempFile> {
+		use crate::{MBTilesWriter, MockTilesReader, TilesWriterTrait};
+
+		let mut mock_reader =
+			MockTilesReader::new_mock(TilesReaderParameters::new(PBF, Gzip, TileBBoxPyramid::new_full(3)))?;
+		let file = assert_fs::NamedTempFile::new("temp.mbtiles")?;
+		MBTilesWriter::write_to_path(&mut mock_reader, &file).await?;
+		Ok(file)
+	}
+
+	#[tokio::test]
+	async fn deduplicated_schema() -> Result<()> {
+		let file = make_temp_mbtiles().await?;
+		let expected = MBTilesReader::open_path(&file)?
+			.get_tile_data(&TileCoord3::new(3, 4, 3)?)
+			.await?;
+
+		Connection::open(file.path())?.execute_batch(
+			"CREATE TABLE map (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_id TEXT);
+			CREATE TABLE images (tile_data BLOB, tile_id TEXT);
+			INSERT INTO map SELECT zoom_level, tile_column, tile_row, zoom_level || '/' || tile_column || '/' || tile_row FROM tiles;
+			INSERT INTO images SELECT tile_data, zoom_level || '/' || tile_column || '/' || tile_row FROM tiles;
+			DROP TABLE tiles;",
+		)?;
+
+		let mut reader = MBTilesReader::open_path(&file)?;
+		assert_eq!(reader.schema, MBTilesSchema::Deduplicated);
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64)]"
+		);
+		assert_eq!(reader.get_tile_data(&TileCoord3::new(3, 4, 3)?).await?, expected);
+
+		let bbox = reader.get_parameters().bbox_pyramid.get_level_bbox(3).clone();
+		assert_eq!(reader.get_bbox_tile_stream(bbox).await.drain_and_count().await, 64);
+
 		MockTilesWriter::write(&mut reader).await?;
 
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn unknown_schema() -> Result<()> {
+		let file = make_temp_mbtiles().await?;
+		Connection::open(file.path())?.execute_batch("DROP TABLE tiles;")?;
+		assert!(MBTilesReader::open_path(&file).is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn wal_while_writing() -> Result<()> {
+		let file = make_temp_mbtiles().await?;
+
+		// Keep a connection with an open write transaction, like a generator still writing the file.
+		let writer = Connection::open(file.path())?;
+		writer.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+		writer.execute_batch(
+			"BEGIN; INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (4, 0, 0, x'00');",
+		)?;
+
+		let reader = MBTilesReader::open_path(&file)?;
+		assert_eq!(reader.get_parameters().bbox_pyramid.get_zoom_max(), Some(3));
+		assert!(reader.get_tile_data(&TileCoord3::new(1, 1, 1)?).await?.is_some());
+
+		writer.execute_batch("COMMIT;")?;
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn xyz_scheme() -> Result<()> {
+		let file = make_temp_mbtiles().await?;
+		let coord = TileCoord3::new(3, 1, 3)?;
+		let expected = MBTilesReader::open_path(&file)?.get_tile_data(&coord).await?;
+
+		// Store the tiles in XYZ order and declare it in the metadata.
+		Connection::open(file.path())?.execute_batch(
+			"UPDATE tiles SET tile_row = tile_row + 1000;
+			UPDATE tiles SET tile_row = (1 << zoom_level) - 1 - (tile_row - 1000);
+			INSERT INTO metadata (name, value) VALUES ('scheme', 'xyz');",
+		)?;
+
+		let mut reader = MBTilesReader::open_path(&file)?;
+		assert_eq!(reader.scheme, TileScheme::Xyz);
+		assert_eq!(reader.get_tile_data(&coord).await?, expected);
+
+		let bbox = TileBBox::new(3, 0, 0, 7, 3)?;
+		let tiles = reader.get_bbox_tile_stream(bbox.clone()).await.collect().await;
+		assert_eq!(tiles.len(), 32);
+		assert!(tiles.iter().all(|(c, _)| bbox.contains3(c)));
+
+		MockTilesWriter::write(&mut reader).await?;
+
+		// Overriding the scheme flips the rows again.
+		reader.override_scheme(TileScheme::Tms)?;
+		assert_eq!(reader.get_tile_data(&TileCoord3::new(3, 6, 3)?).await?, expected);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn override_scheme() -> Result<()> {
+		let mut reader = MBTilesReader::open_path(&PATH)?;
+		let coord = TileCoord3::new(8803, 5376, 14)?;
+		let tile = reader.get_tile_data(&coord).await?;
+		assert!(tile.is_some());
+
+		reader.override_scheme(TileScheme::Xyz)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid.get_level_bbox(14)),
+			"14: [8787,10996,8818,11022] (864)"
+		);
+
+		let mut flipped = coord;
+		flipped.flip_y();
+		assert_eq!(reader.get_tile_data(&flipped).await?, tile);
+
+		reader.override_scheme(TileScheme::Tms)?;
+		assert_eq!(reader.get_tile_data(&coord).await?, tile);
+
+		Ok(())
+	}
+
+	#[test]
+	fn open_with_invalid_pool_size() {
+		assert!(MBTilesReader::open_path_with_pool_size(&PATH, 0).is_err());
+	}
+
+	#[test]
+	fn split_bbox_into_bands() -> Result<()> {
+		let bbox = TileBBox::new(14, 100, 200, 9099, 299)?;
+		let bands = split_bbox(&bbox);
+		assert_eq!(bands.len(), 225);
+		assert_eq!(bands[0], TileBBox::new(14, 100, 200, 139, 299)?);
+		assert_eq!(bands[224], TileBBox::new(14, 9060, 200, 9099, 299)?);
+		assert_eq!(bands.iter().map(|b| b.count_tiles()).sum::<u64>(), bbox.count_tiles());
+
+		let bbox = TileBBox::new(14, 0, 0, 2, 9999)?;
+		assert_eq!(split_bbox(&bbox).len(), 3);
+		Ok(())
+	}
+
 	// Test tile fetching
 	#[cfg(feature = "cli")]
 	#[tokio::test]
@@ -458,10 +904,11 @@ pub mod tests {
 
 		let mut printer = PrettyPrint::new();
 		reader.probe_tiles(&printer.get_category("tiles").await).await?;
-		assert_eq!(
-			printer.as_string().await,
-			"tiles:\n   deep tiles probing is not implemented for this container format\n"
-		);
+		let text = printer.as_string().await;
+		assert!(text.starts_with("tiles:\n   average tile size: "), "{text}");
+		assert!(text.contains("\n   level 14: Level { count: "), "{text}");
+		assert!(text.contains("\n   #1 biggest tile: Entry { size: "), "{text}");
+		assert!(text.contains("\n   #20 biggest tile: Entry { size: "), "{text}");
 
 		Ok(())
 	}
@@ -29,7 +29,7 @@
 //! This module includes comprehensive tests to ensure the correct functionality of writing metadata, handling different file formats, and verifying the database structure.
 
 use crate::TilesWriterTrait;
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use async_trait::async_trait;
 use r2d2::Pool;
 use r2d2_sqlite::{rusqlite::params, SqliteConnectionManager};
@@ -117,9 +117,14 @@ impl TilesWriterTrait for MBTilesWriter {
 		use TileCompression::*;
 		use TileFormat::*;
 
-		let mut writer = MBTilesWriter::new(path)?;
-
 		let parameters = reader.get_parameters().clone();
+		ensure!(
+			parameters.bbox_pyramid.get_grid().is_web_mercator(),
+			"MBTiles supports only the Web Mercator tile grid, but the tiles use {}",
+			parameters.bbox_pyramid.get_grid()
+		);
+
+		let mut writer = MBTilesWriter::new(path)?;
 
 		let format = match (parameters.tile_format, parameters.tile_compression) {
 			(JPG, Uncompressed) => "jpg",
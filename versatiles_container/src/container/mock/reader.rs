@@ -7,6 +7,7 @@
 //!
 //! ## Usage
 //! These mocks can be used to simulate tile reading operations in tests, allowing verification of code behavior under controlled conditions.
+//! Artificial latency, random errors and sparse coverage can be added to exercise retry and error handling code.
 //!
 //! ```rust
 //! use versatiles_container::{MockTilesReader, MockTilesReaderProfile};
@@ -22,8 +23,12 @@
 //! }
 //! ```
 
-use anyhow::Result;
+use anyhow::{bail, ensure, Result};
 use async_trait::async_trait;
+use std::{
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
 use versatiles_core::{tilejson::TileJSON, types::*, utils::compress};
 
 /// Enum representing different mock profiles for tile data.
@@ -37,6 +42,17 @@ pub enum MockTilesReaderProfile {
 	Pbf,
 }
 
+/// Enum defining which tiles exist in a mock reader.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockCoverage {
+	/// Every valid tile exists.
+	Full,
+	/// Only tiles with an even sum of x and y exist.
+	Checkerboard,
+	/// Each tile exists with the given probability. The pattern depends only on the seed and is stable between calls.
+	Random(f64),
+}
+
 pub const MOCK_BYTES_JPG: &[u8; 671] = include_bytes!("./mock_tiles/mock.jpg");
 pub const MOCK_BYTES_PBF: &[u8; 54] = include_bytes!("./mock_tiles/mock.pbf");
 pub const MOCK_BYTES_PNG: &[u8; 103] = include_bytes!("./mock_tiles/mock.png");
@@ -46,6 +62,11 @@ pub const MOCK_BYTES_WEBP: &[u8; 44] = include_bytes!("./mock_tiles/mock.webp");
 pub struct MockTilesReader {
 	parameters: TilesReaderParameters,
 	tilejson: TileJSON,
+	latency: Option<Duration>,
+	error_rate: f64,
+	coverage: MockCoverage,
+	seed: u64,
+	request_count: AtomicU64,
 }
 
 impl MockTilesReader {
@@ -72,10 +93,86 @@ impl MockTilesReader {
 	pub fn new_mock(parameters: TilesReaderParameters) -> Result<MockTilesReader> {
 		let mut tilejson = TileJSON::default();
 		tilejson.set_string("type", "dummy")?;
-		Ok(MockTilesReader { parameters, tilejson })
+		tilejson.set_tile_grid(&parameters.bbox_pyramid.get_grid())?;
+		Ok(MockTilesReader {
+			parameters,
+			tilejson,
+			latency: None,
+			error_rate: 0.0,
+			coverage: MockCoverage::Full,
+			seed: 0,
+			request_count: AtomicU64::new(0),
+		})
+	}
+
+	/// Delays every tile request by the given duration.
+	pub fn with_latency(mut self, latency: Duration) -> MockTilesReader {
+		self.latency = Some(latency);
+		self
+	}
+
+	/// Lets tile requests fail with the given probability (0.0 to 1.0).
+	///
+	/// Whether a request fails is decided for each request, so retrying a failed request can succeed.
+	pub fn with_error_rate(mut self, error_rate: f64) -> Result<MockTilesReader> {
+		ensure!(
+			(0.0..=1.0).contains(&error_rate),
+			"error rate must be between 0 and 1, but is {error_rate}"
+		);
+		self.error_rate = error_rate;
+		Ok(self)
+	}
+
+	/// Defines which tiles exist.
+	pub fn with_coverage(mut self, coverage: MockCoverage) -> Result<MockTilesReader> {
+		if let MockCoverage::Random(probability) = coverage {
+			ensure!(
+				(0.0..=1.0).contains(&probability),
+				"probability must be between 0 and 1, but is {probability}"
+			);
+		}
+		self.coverage = coverage;
+		Ok(self)
+	}
+
+	/// Sets the seed for random errors and random coverage, to get reproducible tests.
+	pub fn with_seed(mut self, seed: u64) -> MockTilesReader {
+		self.seed = seed;
+		self
+	}
+
+	/// Replaces the TileJSON metadata.
+	pub fn with_tilejson(mut self, tilejson: TileJSON) -> MockTilesReader {
+		self.tilejson = tilejson;
+		self
+	}
+
+	/// Returns the number of tile requests so far.
+	pub fn get_request_count(&self) -> u64 {
+		self.request_count.load(Ordering::Relaxed)
+	}
+
+	fn has_tile(&self, coord: &TileCoord3) -> bool {
+		match self.coverage {
+			MockCoverage::Full => true,
+			MockCoverage::Checkerboard => (coord.x + coord.y).is_multiple_of(2),
+			MockCoverage::Random(probability) => random(self.seed, coord.get_sort_index()) < probability,
+		}
 	}
 }
 
+/// Returns a pseudo random number in the range [0, 1), derived from a seed and a value (SplitMix64).
+fn random(seed: u64, value: u64) -> f64 {
+	let mut z = seed
+		.wrapping_add(value)
+		.wrapping_add(1)
+		.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^= z >> 31;
+	(z >> 11) as f64 / (1u64 << 53) as f64
+}
+
 #[async_trait]
 impl TilesReaderTrait for MockTilesReader {
 	fn get_container_name(&self) -> &str {
@@ -94,6 +191,11 @@ impl TilesReaderTrait for MockTilesReader {
 		self.parameters.tile_compression = tile_compression;
 	}
 
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.parameters.tile_format = tile_format;
+		Ok(())
+	}
+
 	fn get_tilejson(&self) -> &TileJSON {
 		&self.tilejson
 	}
@@ -101,7 +203,17 @@ impl TilesReaderTrait for MockTilesReader {
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
 		use TileFormat::*;
 
-		if !coord.is_valid() {
+		let request_index = self.request_count.fetch_add(1, Ordering::Relaxed);
+
+		if let Some(latency) = self.latency {
+			tokio::time::sleep(latency).await;
+		}
+
+		if self.error_rate > 0.0 && random(!self.seed, request_index) < self.error_rate {
+			bail!("simulated error while reading tile {coord:?}");
+		}
+
+		if !coord.is_valid() || !self.has_tile(coord) {
 			return Ok(None);
 		}
 
@@ -122,9 +234,19 @@ impl TilesReaderTrait for MockTilesReader {
 
 impl std::fmt::Debug for MockTilesReader {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		f.debug_struct("MockTilesReader")
-			.field("parameters", &self.get_parameters())
-			.finish()
+		let mut debug = f.debug_struct("MockTilesReader");
+		debug.field("parameters", &self.get_parameters());
+		// the injected faults are only listed if they are used
+		if let Some(latency) = &self.latency {
+			debug.field("latency", latency);
+		}
+		if self.error_rate > 0.0 {
+			debug.field("error_rate", &self.error_rate);
+		}
+		if self.coverage != MockCoverage::Full {
+			debug.field("coverage", &self.coverage);
+		}
+		debug.finish()
 	}
 }
 
@@ -177,6 +299,81 @@ mod tests {
 		test(MockTilesReaderProfile::Json, Blob::from("{x:23,y:45,z:6}")).await;
 	}
 
+	#[tokio::test]
+	async fn latency() -> Result<()> {
+		let reader =
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.with_latency(Duration::from_millis(50));
+		let start = std::time::Instant::now();
+		reader.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?;
+		assert!(start.elapsed() >= Duration::from_millis(50));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn error_rate() -> Result<()> {
+		let coord = TileCoord3::new(0, 0, 0)?;
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.with_error_rate(1.0)?;
+		assert_eq!(
+			reader.get_tile_data(&coord).await.unwrap_err().to_string(),
+			"simulated error while reading tile TileCoord3(0, 0, 0)"
+		);
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?
+			.with_error_rate(0.5)?
+			.with_seed(42);
+		let mut error_count = 0;
+		for _ in 0..1000 {
+			if reader.get_tile_data(&coord).await.is_err() {
+				error_count += 1;
+			}
+		}
+		assert!((400..600).contains(&error_count), "{error_count}");
+		assert_eq!(reader.get_request_count(), 1000);
+
+		assert!(MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?
+			.with_error_rate(1.5)
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn coverage() -> Result<()> {
+		let count_tiles = |coverage| async move {
+			let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)
+				.unwrap()
+				.with_coverage(coverage)
+				.unwrap();
+			let bbox = TileBBox::new_full(4).unwrap();
+			reader.get_bbox_tile_stream(bbox).await.drain_and_count().await
+		};
+
+		assert_eq!(count_tiles(MockCoverage::Full).await, 256);
+		assert_eq!(count_tiles(MockCoverage::Checkerboard).await, 128);
+		assert_eq!(count_tiles(MockCoverage::Random(0.0)).await, 0);
+		let count = count_tiles(MockCoverage::Random(0.25)).await;
+		assert!((40..90).contains(&count), "{count}");
+
+		let reader =
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?.with_coverage(MockCoverage::Random(0.5))?;
+		let coord = TileCoord3::new(3, 5, 7)?;
+		assert_eq!(
+			reader.get_tile_data(&coord).await?.is_some(),
+			reader.get_tile_data(&coord).await?.is_some()
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn debug() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		assert!(format!("{reader:?}").ends_with("tile_format: PNG } }"));
+
+		let reader = reader.with_coverage(MockCoverage::Checkerboard)?;
+		assert!(format!("{reader:?}").ends_with("tile_format: PNG }, coverage: Checkerboard }"));
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn convert_from() -> Result<()> {
 		let mut reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
@@ -10,6 +10,7 @@
 //! | `*.tar`        | ✅   | ✅     | `full`    |
 //! | directory      | ✅   | ✅     | `default` |
 //! | pipeline       | ✅   | ❌     | `full`    |
+//! | `postgis://`   | ✅   | ❌     | `postgis` |
 //!
 //! This module provides a unified interface for reading and writing various tile container formats.
 //! Depending on the enabled features, it supports different formats with corresponding read and write capabilities.
@@ -17,13 +18,30 @@
 mod pipeline;
 pub use pipeline::*;
 
+mod cache;
+pub use cache::*;
+
+mod concat;
+pub use concat::ConcatReader;
+
+mod conversion_pipeline;
+pub use conversion_pipeline::*;
+
+mod conversion_report;
+
 mod converter;
 pub use converter::*;
 
+mod fixtures;
+pub use fixtures::*;
+
 mod getters;
 #[cfg(test)]
 pub use getters::tests::*;
-pub use getters::{get_reader, write_to_filename};
+pub use getters::{
+	get_reader, get_reader_from_stdin, get_reader_with_options, get_writer_name, write_to_filename, write_to_stdout,
+	ReaderOptions,
+};
 
 mod mbtiles;
 pub use mbtiles::*;
@@ -33,14 +51,44 @@ mod mock;
 #[cfg(any(test, feature = "test"))]
 pub use mock::*;
 
+mod patch;
+pub use patch::{TilePatch, TilePatchWriter};
+
 mod pmtiles;
 pub use pmtiles::*;
 
+#[cfg(feature = "postgis")]
+mod postgis;
+#[cfg(feature = "postgis")]
+pub use postgis::*;
+
+#[cfg(feature = "cli")]
+mod probe;
+#[cfg(feature = "cli")]
+pub use probe::*;
+
+mod sampler;
+pub use sampler::*;
+
+mod sniff;
+pub use sniff::*;
+
 mod tar;
 pub use tar::*;
 
+mod temp_output;
+pub use temp_output::TempOutputFile;
+
 pub mod tile_converter;
 
+mod tile_error_handler;
+pub use tile_error_handler::TileErrorPolicy;
+
+mod tile_size_filter;
+
+mod tile_transform;
+pub use tile_transform::TileTransform;
+
 mod directory;
 pub use directory::*;
 
@@ -0,0 +1,189 @@
+//! `*.vpatch` files hold the tiles that changed between two versions of a tileset.
+//!
+//! A patch contains every tile that was added or changed, and marks every tile that was removed. Applying it to
+//! the old version yields the new version, so clients can update offline tilesets by downloading only the patch.
+//!
+//! # File layout
+//!
+//! All numbers are big endian.
+//!
+//! | Bytes | Content                                                                     |
+//! |-------|-----------------------------------------------------------------------------|
+//! | 16    | magic word `versatiles_patch`                                               |
+//! | 4     | length of the header                                                        |
+//! | n     | header as JSON, e.g. `{"tile_compression":"gzip","tile_format":"pbf"}`     |
+//!
+//! followed by one entry per tile, until the end of the file:
+//!
+//! | Bytes | Content                                                      |
+//! |-------|--------------------------------------------------------------|
+//! | 1     | zoom level                                                   |
+//! | 4     | x                                                            |
+//! | 4     | y                                                            |
+//! | 8     | length of the tile, or `u64::MAX` if the tile was removed    |
+//! | n     | tile data, compressed with the compression of the header     |
+
+use anyhow::{ensure, Context, Result};
+use std::{
+	fs::{self, File},
+	io::{BufWriter, Write},
+	path::Path,
+};
+use versatiles_core::{
+	io::{ValueReader, ValueReaderSlice, ValueWriter, ValueWriterBlob},
+	json::JsonObject,
+	types::{Blob, TileCompression, TileCoord3, TileFormat},
+};
+
+const MAGIC_WORD: &[u8; 16] = b"versatiles_patch";
+
+/// Length that marks a removed tile.
+const REMOVED: u64 = u64::MAX;
+
+/// Writes a `*.vpatch` file entry by entry.
+pub struct TilePatchWriter {
+	writer: BufWriter<File>,
+	count: u64,
+}
+
+impl TilePatchWriter {
+	/// Creates a patch file. All tiles must use the given format and compression.
+	pub fn create(path: &Path, tile_format: TileFormat, tile_compression: TileCompression) -> Result<TilePatchWriter> {
+		let mut header = JsonObject::default();
+		header.set("tile_format", tile_format.as_str());
+		header.set("tile_compression", tile_compression.as_str());
+		let header = header.stringify();
+
+		let mut writer = ValueWriterBlob::new_be();
+		writer.write_slice(MAGIC_WORD)?;
+		writer.write_u32(header.len() as u32)?;
+		writer.write_string(&header)?;
+
+		let mut file = BufWriter::new(File::create(path).with_context(|| format!("creating patch {path:?}"))?);
+		file.write_all(writer.into_blob().as_slice())?;
+		Ok(TilePatchWriter { writer: file, count: 0 })
+	}
+
+	/// Adds a changed tile, or marks a tile as removed if `blob` is `None`.
+	pub fn set_tile(&mut self, coord: &TileCoord3, blob: Option<&Blob>) -> Result<()> {
+		let mut writer = ValueWriterBlob::new_be();
+		writer.write_u8(coord.z)?;
+		writer.write_u32(coord.x)?;
+		writer.write_u32(coord.y)?;
+		match blob {
+			Some(blob) => {
+				writer.write_u64(blob.len())?;
+				writer.write_blob(blob)?;
+			}
+			None => writer.write_u64(REMOVED)?,
+		}
+		self.writer.write_all(writer.into_blob().as_slice())?;
+		self.count += 1;
+		Ok(())
+	}
+
+	/// Flushes the file and returns the number of entries.
+	pub fn finish(mut self) -> Result<u64> {
+		self.writer.flush()?;
+		Ok(self.count)
+	}
+}
+
+/// The contents of a `*.vpatch` file.
+#[derive(Debug)]
+pub struct TilePatch {
+	pub tile_format: TileFormat,
+	pub tile_compression: TileCompression,
+	/// Changed tiles, or `None` for removed tiles.
+	pub tiles: Vec<(TileCoord3, Option<Blob>)>,
+}
+
+impl TilePatch {
+	pub fn read_file(path: &Path) -> Result<TilePatch> {
+		let data = fs::read(path).with_context(|| format!("reading patch {path:?}"))?;
+		TilePatch::from_slice(&data).with_context(|| format!("parsing patch {path:?}"))
+	}
+
+	pub fn from_slice(data: &[u8]) -> Result<TilePatch> {
+		let mut reader = ValueReaderSlice::new_be(data);
+		ensure!(
+			reader.remaining() >= 20 && reader.read_blob(16)?.as_slice() == MAGIC_WORD,
+			"not a versatiles patch"
+		);
+		let header_length = reader.read_u32()? as u64;
+		let header = JsonObject::parse_str(&reader.read_string(header_length)?)?;
+		let tile_format = TileFormat::parse_str(&header.get_string("tile_format")?.context("missing tile_format")?)?;
+		let tile_compression = TileCompression::parse_str(
+			&header
+				.get_string("tile_compression")?
+				.context("missing tile_compression")?,
+		)?;
+
+		let mut tiles = Vec::new();
+		while reader.has_remaining() {
+			let z = reader.read_u8()?;
+			let x = reader.read_u32()?;
+			let y = reader.read_u32()?;
+			let coord = TileCoord3::new(x, y, z)?;
+			let blob = match reader.read_u64()? {
+				REMOVED => None,
+				length => {
+					ensure!(length <= reader.remaining(), "tile {coord:?} is truncated");
+					Some(reader.read_blob(length)?)
+				}
+			};
+			tiles.push((coord, blob));
+		}
+
+		Ok(TilePatch {
+			tile_format,
+			tile_compression,
+			tiles,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+
+	#[test]
+	fn round_trip() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("update.vpatch");
+
+		let mut writer = TilePatchWriter::create(&path, TileFormat::PBF, TileCompression::Gzip)?;
+		writer.set_tile(&TileCoord3::new(1, 2, 3)?, Some(&Blob::from("new tile")))?;
+		writer.set_tile(&TileCoord3::new(4, 5, 6)?, None)?;
+		assert_eq!(writer.finish()?, 2);
+
+		let patch = TilePatch::read_file(&path)?;
+		assert_eq!(patch.tile_format, TileFormat::PBF);
+		assert_eq!(patch.tile_compression, TileCompression::Gzip);
+		assert_eq!(
+			patch.tiles,
+			vec![
+				(TileCoord3::new(1, 2, 3)?, Some(Blob::from("new tile"))),
+				(TileCoord3::new(4, 5, 6)?, None)
+			]
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn invalid() -> Result<()> {
+		assert!(TilePatch::from_slice(b"versatiles").is_err());
+
+		let dir = TempDir::new()?;
+		let path = dir.path().join("update.vpatch");
+		let mut writer = TilePatchWriter::create(&path, TileFormat::PNG, TileCompression::Uncompressed)?;
+		writer.set_tile(&TileCoord3::new(0, 0, 0)?, Some(&Blob::from("tile")))?;
+		writer.finish()?;
+
+		let mut data = fs::read(&path)?;
+		data.pop();
+		assert!(TilePatch::from_slice(&data).is_err());
+		Ok(())
+	}
+}
@@ -4,3 +4,6 @@ The `pipeline` module provides functionality for reading, processing, and compos
 
 mod reader;
 pub use reader::PipelineReader;
+
+mod watcher;
+pub use watcher::WatchedPipelineReader;
@@ -48,7 +48,16 @@ impl<'a> PipelineReader {
 			.with_context(|| format!("failed parsing {} as VPL", reader.get_name()))
 	}
 
-	#[cfg(test)]
+	/// Opens a PipelineReader from a vpl string.
+	///
+	/// # Arguments
+	///
+	/// * `vpl` - The vpl configuration.
+	/// * `dir` - The directory that filenames in the configuration are relative to.
+	///
+	/// # Returns
+	///
+	/// * `Result<PipelineReader>` - The constructed PipelineReader or an error if the configuration is invalid.
 	pub async fn open_str(vpl: &str, dir: &Path) -> Result<PipelineReader> {
 		Self::from_str(vpl, "from str", dir)
 			.await
@@ -109,6 +118,11 @@ impl TilesReaderTrait for PipelineReader {
 	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
 		self.operation.get_tile_stream(bbox).await
 	}
+
+	/// Operations can't report errors of single tiles, so every tile of the stream is `Ok`.
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
+		self.get_bbox_tile_stream(bbox).await.into_try_stream()
+	}
 }
 
 impl std::fmt::Debug for PipelineReader {
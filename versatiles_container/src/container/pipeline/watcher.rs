@@ -0,0 +1,199 @@
+//! A pipeline reader that is reloaded whenever its VPL file changes.
+//!
+//! Tiles are generated on request and kept in an in-memory cache that evicts the least recently used tiles.
+//! The modification time of the VPL file is checked at most once per second. When the file has
+//! changed, the pipeline is parsed again and the cache is cleared, so property filters and overlays can be
+//! tweaked while the server is running.
+//!
+//! Parameters and metadata are taken from the first version of the pipeline. If the tile format or compression
+//! of a new version differs, the new version is ignored until the reader is opened again.
+
+use super::PipelineReader;
+use crate::{CachePolicy, CachedReader};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	time::{Duration, Instant, SystemTime},
+};
+use versatiles_core::{tilejson::TileJSON, types::*};
+
+/// Minimal time between two checks of the modification time of the VPL file.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+struct WatchState {
+	modified: Option<SystemTime>,
+	next_check: Instant,
+	reader: Arc<CachedReader>,
+}
+
+pub struct WatchedPipelineReader {
+	path: PathBuf,
+	cache_size: u64,
+	name: String,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+	state: Mutex<WatchState>,
+}
+
+impl WatchedPipelineReader {
+	/// Opens a VPL file.
+	///
+	/// # Arguments
+	///
+	/// * `path` - The path to the VPL file.
+	/// * `cache_size` - Maximum size of the cached tiles in bytes.
+	pub async fn open_path(path: &Path, cache_size: u64) -> Result<WatchedPipelineReader> {
+		let modified = get_modified(path);
+		let reader = open_cached(path, cache_size).await?;
+
+		Ok(WatchedPipelineReader {
+			path: path.to_path_buf(),
+			cache_size,
+			name: reader.get_source_name().to_owned(),
+			parameters: reader.get_parameters().clone(),
+			tilejson: reader.get_tilejson().clone(),
+			state: Mutex::new(WatchState {
+				modified,
+				next_check: Instant::now() + CHECK_INTERVAL,
+				reader,
+			}),
+		})
+	}
+
+	/// Returns the current version of the pipeline, after reloading it if the VPL file has changed.
+	async fn get_reader(&self) -> Arc<CachedReader> {
+		let reader = {
+			let mut state = self.state.lock().unwrap();
+			if Instant::now() < state.next_check {
+				return state.reader.clone();
+			}
+			state.next_check = Instant::now() + CHECK_INTERVAL;
+
+			let modified = get_modified(&self.path);
+			if modified == state.modified {
+				return state.reader.clone();
+			}
+			// update the modification time first, so that concurrent requests don't reload the pipeline again
+			state.modified = modified;
+			state.reader.clone()
+		};
+
+		let new_reader = match open_cached(&self.path, self.cache_size).await {
+			Ok(new_reader) => new_reader,
+			Err(e) => {
+				warn!(
+					"failed to reload pipeline {:?}, keeping the previous version: {e:#}",
+					self.path
+				);
+				return reader;
+			}
+		};
+
+		let parameters = new_reader.get_parameters();
+		if parameters.tile_format != self.parameters.tile_format
+			|| parameters.tile_compression != self.parameters.tile_compression
+		{
+			warn!(
+				"pipeline {:?} changed the tile format or compression, restart to apply the changes",
+				self.path
+			);
+			return reader;
+		}
+
+		info!("reloaded pipeline {:?}", self.path);
+		self.state.lock().unwrap().reader = new_reader.clone();
+		new_reader
+	}
+}
+
+async fn open_cached(path: &Path, cache_size: u64) -> Result<Arc<CachedReader>> {
+	let reader = PipelineReader::open_path(path).await?;
+	let policy = CachePolicy::Memory { max_size: cache_size };
+	Ok(Arc::new(CachedReader::new(reader.boxed(), policy)?))
+}
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[async_trait]
+impl TilesReaderTrait for WatchedPipelineReader {
+	fn get_source_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_container_name(&self) -> &str {
+		"pipeline"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, _tile_compression: TileCompression) {
+		panic!("you can't override the compression of pipeline")
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		self.get_reader().await.get_tile_data(coord).await
+	}
+}
+
+impl std::fmt::Debug for WatchedPipelineReader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("WatchedPipelineReader")
+			.field("path", &self.path)
+			.field("parameters", &self.parameters)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+
+	#[tokio::test]
+	async fn reload() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("debug.vpl");
+		fs::write(&path, "from_debug format=png | filter_zoom max=3")?;
+
+		let reader = WatchedPipelineReader::open_path(&path, 10_000_000).await?;
+		assert_eq!(reader.get_container_name(), "pipeline");
+		let coord = TileCoord3::new(1, 2, 5)?;
+		assert_eq!(reader.get_tile_data(&coord).await?, None);
+
+		let force_check = || {
+			let mut state = reader.state.lock().unwrap();
+			state.modified = None;
+			state.next_check = Instant::now();
+		};
+
+		// the changed file is only read after the check interval
+		fs::write(&path, "from_debug format=png | filter_zoom max=6")?;
+		assert_eq!(reader.get_tile_data(&coord).await?, None);
+		force_check();
+		assert!(reader.get_tile_data(&coord).await?.is_some());
+
+		// invalid pipelines and other tile formats are ignored
+		fs::write(&path, "from_debug format=png | filter_zoom max=")?;
+		force_check();
+		assert!(reader.get_tile_data(&coord).await?.is_some());
+
+		fs::write(&path, "from_debug format=webp")?;
+		force_check();
+		let tile = reader.get_tile_data(&coord).await?.unwrap();
+		assert_eq!(&tile.as_slice()[1..4], b"PNG");
+
+		Ok(())
+	}
+}
@@ -113,6 +113,41 @@ impl PMTilesReader {
 			root_bytes_uncompressed: Arc::new(root_bytes_uncompressed),
 		})
 	}
+
+	/// Finds the byte range of a tile in the data section by walking through the root and leaf directories.
+	///
+	/// Returns `None` if the tile does not exist.
+	async fn find_tile_range(&self, coord: &TileCoord3) -> Result<Option<ByteRange>> {
+		let tile_id: u64 = coord.get_tile_id()?;
+		let mut dir_bytes = self.root_bytes_uncompressed.clone();
+
+		for _depth in 0..3 {
+			let entries = EntriesV3::from_blob(&dir_bytes)?;
+			let entry = if let Some(entry) = entries.find_tile(tile_id) {
+				entry
+			} else {
+				return Ok(None);
+			};
+
+			if entry.range.length == 0 {
+				return Ok(None);
+			}
+
+			if entry.run_length > 0 {
+				return Ok(Some(entry.range.get_shifted_forward(self.header.tile_data.offset)));
+			}
+
+			let range = entry.range;
+			let mut cache = self.leaves_cache.lock().await;
+			dir_bytes = cache.get_or_set(&range, || {
+				let mut blob = self.leaves_bytes.read_range(&range)?;
+				blob = decompress(blob, &self.internal_compression)?;
+				Ok(Arc::new(blob))
+			})?;
+		}
+
+		bail!("not found")
+	}
 }
 
 /// Calculates the bounding box pyramid from the provided data.
@@ -173,6 +208,11 @@ impl TilesReaderTrait for PMTilesReader {
 		self.parameters.tile_compression = tile_compression;
 	}
 
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.parameters.tile_format = tile_format;
+		Ok(())
+	}
+
 	/// Returns the metadata as a `Blob`.
 	///
 	/// # Errors
@@ -196,42 +236,15 @@ impl TilesReaderTrait for PMTilesReader {
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
 		log::trace!("get_tile_data {:?}", coord);
 
-		let tile_id: u64 = coord.get_tile_id()?;
-		let mut dir_bytes = self.root_bytes_uncompressed.clone();
-
-		for _depth in 0..3 {
-			let entries = EntriesV3::from_blob(&dir_bytes)?;
-			let entry = entries.find_tile(tile_id);
-
-			let entry = if let Some(entry) = entry {
-				entry
-			} else {
-				return Ok(None);
-			};
-
-			if entry.range.length > 0 {
-				if entry.run_length > 0 {
-					return Ok(Some(
-						self
-							.data_reader
-							.read_range(&entry.range.get_shifted_forward(self.header.tile_data.offset))
-							.await?,
-					));
-				} else {
-					let range = entry.range;
-					let mut cache = self.leaves_cache.lock().await;
-					dir_bytes = cache.get_or_set(&range, || {
-						let mut blob = self.leaves_bytes.read_range(&range)?;
-						blob = decompress(blob, &self.internal_compression)?;
-						Ok(Arc::new(blob))
-					})?;
-				}
-			} else {
-				return Ok(None);
-			}
-		}
+		Ok(match self.find_tile_range(coord).await? {
+			Some(range) => Some(self.data_reader.read_range(&range).await?),
+			None => None,
+		})
+	}
 
-		bail!("not found")
+	/// Checks whether a tile exists, using only the directories.
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		Ok(self.find_tile_range(coord).await?.is_some())
 	}
 
 	// deep probe of container meta
@@ -241,6 +254,11 @@ impl TilesReaderTrait for PMTilesReader {
 
 		Ok(())
 	}
+	// deep probe of container tile contents
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		crate::probe_vector_tile_contents(&*self, print).await
+	}
 }
 
 #[cfg(test)]
@@ -290,6 +308,9 @@ mod tests {
 
 		assert!(reader.get_tile_data(&TileCoord3::new(0, 0, 16)?).await?.is_none());
 
+		assert!(reader.has_tile(&TileCoord3::new(8800, 5370, 14)?).await?);
+		assert!(!reader.has_tile(&TileCoord3::new(0, 0, 14)?).await?);
+
 		Ok(())
 	}
 }
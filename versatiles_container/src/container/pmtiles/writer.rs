@@ -30,7 +30,7 @@
 
 use super::types::{EntriesV3, EntryV3, HeaderV3, PMTilesCompression, TileId};
 use crate::TilesWriterTrait;
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use async_trait::async_trait;
 use versatiles_core::{io::DataWriterTrait, progress::get_progress_bar, types::*, utils::compress};
 
@@ -52,6 +52,11 @@ impl TilesWriterTrait for PMTilesWriter {
 
 		let parameters = reader.get_parameters().clone();
 		let pyramid = &parameters.bbox_pyramid;
+		ensure!(
+			pyramid.get_grid().is_web_mercator(),
+			"PMTiles supports only the Web Mercator tile grid, but the tiles use {}",
+			pyramid.get_grid()
+		);
 
 		let mut blocks: Vec<TileBBox> = pyramid
 			.iter_levels()
@@ -140,4 +145,21 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn reject_wgs84_grid() -> Result<()> {
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters {
+			bbox_pyramid: TileBBoxPyramid::new_full_in_grid(TileGrid::Wgs84, 2),
+			tile_compression: TileCompression::Gzip,
+			tile_format: TileFormat::PBF,
+		})?;
+
+		let mut data_writer = DataWriterBlob::new()?;
+		let error = PMTilesWriter::write_to_writer(&mut mock_reader, &mut data_writer)
+			.await
+			.unwrap_err();
+		assert!(error.to_string().contains("Web Mercator"));
+
+		Ok(())
+	}
 }
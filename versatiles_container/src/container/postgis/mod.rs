@@ -0,0 +1,13 @@
+//! PostGIS database `postgis://...` as tile source
+//!
+//! This module provides a reader that generates vector tiles on request with `ST_AsMVT` queries,
+//! so that live data can be served and archived into tile containers.
+//!
+//! The main components of this module are:
+//! - `PostGISReader`: Generates vector tiles from tables, functions or a custom query of a PostGIS database,
+//!   configured by a `postgis://` URL or a Martin configuration file.
+
+mod config;
+mod reader;
+
+pub use reader::PostGISReader;
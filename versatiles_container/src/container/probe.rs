@@ -0,0 +1,276 @@
+//! Shared implementations of deep probing, used by the container readers.
+//!
+//! `TileSizeStats` collects the sizes of all tiles and prints their distribution per zoom level, together with
+//! the largest tiles, since oversized tiles are the most common cause of slow rendering in clients.
+//!
+//! `probe_vector_tile_contents` samples tiles of every zoom level, decodes them and prints per-layer
+//! statistics: feature counts, geometry types, vertex counts and the most frequently used attribute keys.
+
+use super::TileSampler;
+use anyhow::Result;
+use std::{
+	cmp::Reverse,
+	collections::{BTreeMap, BinaryHeap},
+};
+use versatiles_core::{
+	progress::get_progress_bar,
+	types::{TileCoord3, TileFormat, TilesReaderTrait},
+	utils::{decompress, PrettyPrint},
+};
+use versatiles_geometry::vector_tile::{VectorTile, VectorTileStats};
+
+/// Maximum number of decoded tiles per zoom level.
+const SAMPLES_PER_LEVEL: u64 = 32;
+
+/// Maximum number of coordinates looked up per zoom level, to limit the effort for sparse levels.
+const LOOKUPS_PER_LEVEL: u64 = 1024;
+
+/// Number of attribute keys listed per layer.
+const TOP_KEYS: usize = 10;
+
+/// Number of largest tiles listed by `TileSizeStats`.
+const LARGEST_TILES: usize = 20;
+
+/// Collects tile sizes and reports their distribution per zoom level and the largest tiles.
+#[derive(Debug, Default)]
+pub struct TileSizeStats {
+	sizes: BTreeMap<u8, Vec<u64>>,
+	largest: BinaryHeap<Reverse<(u64, u8, u32, u32)>>,
+}
+
+impl TileSizeStats {
+	pub fn new() -> TileSizeStats {
+		TileSizeStats::default()
+	}
+
+	/// Adds the size of a tile.
+	pub fn add(&mut self, coord: &TileCoord3, size: u64) {
+		self.sizes.entry(coord.z).or_default().push(size);
+
+		self.largest.push(Reverse((size, coord.z, coord.x, coord.y)));
+		if self.largest.len() > LARGEST_TILES {
+			self.largest.pop();
+		}
+	}
+
+	/// Prints the average tile size, the size distribution per zoom level and the largest tiles.
+	pub async fn print(self, print: &PrettyPrint) {
+		#[derive(Debug)]
+		#[allow(dead_code)]
+		struct Level {
+			count: usize,
+			min: u64,
+			median: u64,
+			p95: u64,
+			max: u64,
+		}
+
+		#[derive(Debug)]
+		#[allow(dead_code)]
+		struct Entry {
+			size: u64,
+			x: u32,
+			y: u32,
+			z: u8,
+		}
+
+		let tile_count: u64 = self.sizes.values().map(|sizes| sizes.len() as u64).sum();
+		if tile_count == 0 {
+			print.add_warning("no tiles found").await;
+			return;
+		}
+
+		let size_sum: u64 = self.sizes.values().flatten().sum();
+		print
+			.add_key_value("average tile size", &size_sum.div_euclid(tile_count))
+			.await;
+
+		for (level, mut sizes) in self.sizes.into_iter() {
+			sizes.sort_unstable();
+			print
+				.add_key_value(
+					&format!("level {level}"),
+					&Level {
+						count: sizes.len(),
+						min: sizes[0],
+						median: percentile(&sizes, 50),
+						p95: percentile(&sizes, 95),
+						max: sizes[sizes.len() - 1],
+					},
+				)
+				.await;
+		}
+
+		for (index, Reverse((size, z, x, y))) in self.largest.into_sorted_vec().into_iter().enumerate() {
+			print
+				.add_key_value(&format!("#{} biggest tile", index + 1), &Entry { size, x, y, z })
+				.await;
+		}
+	}
+}
+
+/// Returns the nearest-rank percentile of a sorted, non-empty list.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+	let rank = (sorted.len() * percent).div_ceil(100).max(1);
+	sorted[rank - 1]
+}
+
+/// Samples vector tiles of every zoom level and prints statistics about their layers.
+///
+/// Tiles are sampled evenly distributed over the bounding box of each zoom level.
+///
+/// # Arguments
+/// * `reader` - The reader to probe.
+/// * `print` - The output for the statistics.
+pub async fn probe_vector_tile_contents(reader: &dyn TilesReaderTrait, print: &PrettyPrint) -> Result<()> {
+	#[derive(Debug)]
+	#[allow(dead_code)]
+	struct Layer {
+		tiles: u64,
+		features: u64,
+		points: u64,
+		lines: u64,
+		polygons: u64,
+		vertices: u64,
+		top_keys: Vec<(String, u64)>,
+	}
+
+	let parameters = reader.get_parameters();
+	if parameters.tile_format != TileFormat::PBF {
+		print
+			.add_warning(&format!(
+				"tile contents probing is only implemented for vector tiles, not for {}",
+				parameters.tile_format
+			))
+			.await;
+		return Ok(());
+	}
+
+	let sampler = TileSampler::new(SAMPLES_PER_LEVEL, LOOKUPS_PER_LEVEL);
+	let mut stats = VectorTileStats::new();
+	let mut broken_tiles: u64 = 0;
+
+	let levels: Vec<_> = parameters.bbox_pyramid.iter_levels().collect();
+	let mut progress = get_progress_bar("sampling tiles", levels.len() as u64);
+
+	for bbox in levels {
+		for (coord, blob) in sampler.sample_level(reader, bbox).await? {
+			let result = decompress(blob, &parameters.tile_compression)
+				.and_then(|blob| VectorTile::from_blob(&blob))
+				.and_then(|tile| stats.add_tile(&tile));
+			if let Err(err) = result {
+				log::debug!("failed to decode tile {coord:?}: {err}");
+				broken_tiles += 1;
+			}
+		}
+
+		progress.inc(1);
+	}
+
+	progress.finish();
+
+	print.add_key_value("sampled tiles", &stats.tile_count).await;
+	if broken_tiles > 0 {
+		print
+			.add_warning(&format!("{broken_tiles} tiles could not be decoded"))
+			.await;
+	}
+
+	for (name, layer) in stats.layers.iter() {
+		let top_keys = layer
+			.get_top_keys(TOP_KEYS)
+			.into_iter()
+			.map(|(key, count)| (key.to_string(), count))
+			.collect();
+		print
+			.add_key_value(
+				&format!("layer {name:?}"),
+				&Layer {
+					tiles: layer.tile_count,
+					features: layer.feature_count,
+					points: layer.point_count,
+					lines: layer.line_count,
+					polygons: layer.polygon_count,
+					vertices: layer.vertex_count,
+					top_keys,
+				},
+			)
+			.await;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{MBTilesReader, MockTilesReader};
+	use versatiles_core::types::{TileBBoxPyramid, TileCompression, TilesReaderParameters};
+
+	#[test]
+	fn test_percentile() {
+		let sizes: Vec<u64> = (1..=100).collect();
+		assert_eq!(percentile(&sizes, 50), 50);
+		assert_eq!(percentile(&sizes, 95), 95);
+		assert_eq!(percentile(&[7], 50), 7);
+		assert_eq!(percentile(&[1, 2, 3], 95), 3);
+	}
+
+	#[tokio::test]
+	async fn tile_size_stats() -> Result<()> {
+		let mut stats = TileSizeStats::new();
+		for size in 1..=30 {
+			stats.add(&TileCoord3::new(size, 0, 5)?, size as u64 * 10);
+		}
+		stats.add(&TileCoord3::new(0, 0, 0)?, 1000);
+
+		let mut printer = PrettyPrint::new();
+		stats.print(&printer.get_category("tiles").await).await;
+		let text = printer.as_string().await;
+		assert!(text.starts_with(
+			"tiles:\n   average tile size: 182\n   level 0: Level { count: 1, min: 1000, median: 1000, p95: 1000, max: 1000 }\n   level 5: Level { count: 30, min: 10, median: 150, p95: 290, max: 300 }\n   #1 biggest tile: Entry { size: 1000, x: 0, y: 0, z: 0 }\n   #2 biggest tile: Entry { size: 300, x: 30, y: 0, z: 5 }\n"
+		), "{text}");
+		assert!(text.contains("#20 biggest tile"), "{text}");
+		assert!(!text.contains("#21 biggest tile"), "{text}");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tile_size_stats_empty() {
+		let mut printer = PrettyPrint::new();
+		TileSizeStats::new().print(&printer.get_category("tiles").await).await;
+		assert_eq!(printer.as_string().await, "tiles:\n   no tiles found\n");
+	}
+
+	#[tokio::test]
+	async fn vector_tiles() -> Result<()> {
+		let path = std::env::current_dir()?.join("../testdata/berlin.mbtiles");
+		let reader = MBTilesReader::open_path(&path)?;
+
+		let mut printer = PrettyPrint::new();
+		probe_vector_tile_contents(&reader, &printer.get_category("tile contents").await).await?;
+
+		let text = printer.as_string().await;
+		assert!(text.starts_with("tile contents:\n   sampled tiles: "), "{text}");
+		assert!(text.contains(": Layer { tiles: "), "{text}");
+		assert!(!text.contains("could not be decoded"), "{text}");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn raster_tiles() -> Result<()> {
+		let reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_full(2),
+		))?;
+
+		let mut printer = PrettyPrint::new();
+		probe_vector_tile_contents(&reader, &printer.get_category("tile contents").await).await?;
+		assert_eq!(
+			printer.as_string().await,
+			"tile contents:\n   tile contents probing is only implemented for vector tiles, not for png\n"
+		);
+		Ok(())
+	}
+}
@@ -0,0 +1,74 @@
+//! Reads a sample of tiles per zoom level, for analyses that don't need every tile, like probing and sniffing.
+//!
+//! The looked up coordinates are spread evenly over the bbox of a level. Missing tiles are skipped, so the number
+//! of lookups is limited separately, to keep the effort low for sparse levels.
+
+use anyhow::Result;
+use versatiles_core::types::{Blob, TileBBox, TileCoord3, TilesReaderTrait};
+
+/// Samples up to `samples_per_level` existing tiles of a zoom level, looking up at most `lookups_per_level`
+/// coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct TileSampler {
+	pub samples_per_level: u64,
+	pub lookups_per_level: u64,
+}
+
+impl TileSampler {
+	pub fn new(samples_per_level: u64, lookups_per_level: u64) -> TileSampler {
+		TileSampler {
+			samples_per_level,
+			lookups_per_level,
+		}
+	}
+
+	/// Reads the sampled tiles of one zoom level.
+	///
+	/// # Errors
+	/// Returns an error if a tile can't be read.
+	pub async fn sample_level(&self, reader: &dyn TilesReaderTrait, bbox: &TileBBox) -> Result<Vec<(TileCoord3, Blob)>> {
+		let count = bbox.count_tiles();
+		let step = count.div_ceil(self.lookups_per_level).max(1);
+		let width = bbox.width() as u64;
+		let mut samples = Vec::new();
+
+		for index in (0..count).step_by(step as usize) {
+			if samples.len() as u64 >= self.samples_per_level {
+				break;
+			}
+			let x = bbox.x_min + (index % width) as u32;
+			let y = bbox.y_min + (index / width) as u32;
+			let coord = TileCoord3::new(x, y, bbox.level)?;
+			if let Some(blob) = reader.get_tile_data(&coord).await? {
+				samples.push((coord, blob));
+			}
+		}
+
+		Ok(samples)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{MockCoverage, MockTilesReader, MockTilesReaderProfile};
+
+	#[tokio::test]
+	async fn sample_level() -> Result<()> {
+		let reader =
+			MockTilesReader::new_mock_profile(MockTilesReaderProfile::Json)?.with_coverage(MockCoverage::Checkerboard)?;
+		let bbox = TileBBox::new(3, 0, 2, 4, 6)?;
+		let (reader, bbox) = (&reader, &bbox);
+		let coords = |sampler: TileSampler| async move {
+			let samples = sampler.sample_level(reader, bbox).await?;
+			Ok::<_, anyhow::Error>(samples.into_iter().map(|(c, _)| (c.x, c.y)).collect::<Vec<_>>())
+		};
+
+		// missing tiles are skipped until enough samples are read
+		assert_eq!(coords(TileSampler::new(3, 1024)).await?, [(0, 2), (2, 2), (4, 2)]);
+
+		// lookups are spread over the whole bbox, here one per row
+		assert_eq!(coords(TileSampler::new(32, 5)).await?, [(0, 2), (0, 4), (0, 6)]);
+		Ok(())
+	}
+}
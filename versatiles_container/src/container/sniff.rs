@@ -0,0 +1,154 @@
+//! Detects the tile format and compression of a container from a sample of its tiles.
+//!
+//! Some containers lack this metadata or declare wrong values, e.g. `*.mbtiles` files with gzip compressed vector
+//! tiles that are declared as uncompressed. Sniffing reads a sample of tiles, guesses the format and compression of
+//! each one from its content, and overrides the declared values with the most frequent guess. Every disagreement is
+//! logged as a warning.
+
+use super::TileSampler;
+use anyhow::Result;
+use log::warn;
+use std::collections::BTreeMap;
+use versatiles_core::{
+	types::{TileCompression, TileFormat, TilesReaderTrait},
+	utils::sniff_tile,
+};
+
+/// Maximum number of sampled tiles per zoom level.
+const SAMPLES_PER_LEVEL: u64 = 8;
+
+/// Maximum number of coordinates looked up per zoom level, to limit the effort for sparse levels.
+const LOOKUPS_PER_LEVEL: u64 = 256;
+
+/// Sniffs the tile format and compression from a sample of tiles and overrides the values declared by the reader.
+///
+/// Returns the detected format and compression, or `None` if no sampled tile could be recognized.
+///
+/// # Errors
+/// Returns an error if a tile can't be read, or if the reader does not support overriding the detected values.
+pub async fn sniff_tile_content(reader: &mut dyn TilesReaderTrait) -> Result<Option<(TileFormat, TileCompression)>> {
+	let parameters = reader.get_parameters();
+	let (declared_format, declared_compression) = (parameters.tile_format, parameters.tile_compression);
+
+	let sampler = TileSampler::new(SAMPLES_PER_LEVEL, LOOKUPS_PER_LEVEL);
+	let mut votes: BTreeMap<(TileFormat, TileCompression), u64> = BTreeMap::new();
+	let mut unknown = 0u64;
+	for bbox in parameters.bbox_pyramid.iter_levels() {
+		for (_, blob) in sampler.sample_level(&*reader, bbox).await? {
+			match sniff_tile(&blob) {
+				Some(guess) => *votes.entry(guess).or_default() += 1,
+				None => unknown += 1,
+			}
+		}
+	}
+
+	let sample_count = votes.values().sum::<u64>() + unknown;
+	if unknown > 0 {
+		warn!("{unknown} of {sample_count} sampled tiles have an unknown format");
+	}
+
+	let mut votes: Vec<((TileFormat, TileCompression), u64)> = votes.into_iter().collect();
+	votes.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	let Some(&((mut format, compression), _)) = votes.first() else {
+		warn!("could not detect the tile format of any sampled tile");
+		return Ok(None);
+	};
+
+	if votes.len() > 1 {
+		let list: Vec<String> = votes
+			.iter()
+			.map(|((format, compression), count)| format!("{count} {format}/{compression}"))
+			.collect();
+		warn!("sampled tiles disagree: {}", list.join(", "));
+	}
+
+	// GeoJSON and TopoJSON are JSON too
+	if declared_format == TileFormat::JSON && matches!(format, TileFormat::GEOJSON | TileFormat::TOPOJSON) {
+		format = declared_format;
+	}
+
+	if format != declared_format {
+		warn!("container declares the tile format {declared_format}, but the sampled tiles are {format}");
+		reader.override_format(format)?;
+	}
+	if compression != declared_compression {
+		warn!("container declares the tile compression {declared_compression}, but the sampled tiles are {compression}");
+		reader.override_compression(compression);
+	}
+
+	Ok(Some((format, compression)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{MockTilesReader, MockTilesReaderProfile};
+	use async_trait::async_trait;
+	use versatiles_core::{
+		tilejson::TileJSON,
+		types::{Blob, TileBBoxPyramid, TileCoord3, TilesReaderParameters},
+		utils::compress_gzip,
+	};
+
+	/// Returns gzip compressed vector tiles, regardless of the declared format and compression.
+	#[derive(Debug)]
+	struct MislabeledReader {
+		parameters: TilesReaderParameters,
+		tilejson: TileJSON,
+	}
+
+	#[async_trait]
+	impl TilesReaderTrait for MislabeledReader {
+		fn get_source_name(&self) -> &str {
+			"mislabeled"
+		}
+		fn get_container_name(&self) -> &str {
+			"mislabeled"
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			&self.parameters
+		}
+		fn override_compression(&mut self, tile_compression: TileCompression) {
+			self.parameters.tile_compression = tile_compression;
+		}
+		fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+			self.parameters.tile_format = tile_format;
+			Ok(())
+		}
+		fn get_tilejson(&self) -> &TileJSON {
+			&self.tilejson
+		}
+		async fn get_tile_data(&self, _coord: &TileCoord3) -> Result<Option<Blob>> {
+			Ok(Some(compress_gzip(&Blob::from(&[0x1a, 0x03, 0x0a, 0x01, b'a'][..]))?))
+		}
+	}
+
+	#[tokio::test]
+	async fn mislabeled() -> Result<()> {
+		let mut reader = MislabeledReader {
+			parameters: TilesReaderParameters::new(
+				TileFormat::BIN,
+				TileCompression::Uncompressed,
+				TileBBoxPyramid::new_full(3),
+			),
+			tilejson: TileJSON::default(),
+		};
+
+		let result = sniff_tile_content(&mut reader).await?;
+		assert_eq!(result, Some((TileFormat::PBF, TileCompression::Gzip)));
+		assert_eq!(reader.parameters.tile_format, TileFormat::PBF);
+		assert_eq!(reader.parameters.tile_compression, TileCompression::Gzip);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn correctly_labeled() -> Result<()> {
+		let mut reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let parameters = reader.get_parameters().clone();
+
+		let result = sniff_tile_content(&mut reader).await?;
+		assert_eq!(result, Some((parameters.tile_format, parameters.tile_compression)));
+		assert_eq!(reader.get_parameters(), &parameters);
+		Ok(())
+	}
+}
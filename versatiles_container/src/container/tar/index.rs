@@ -0,0 +1,252 @@
+//! Provides a sidecar index for tar archives.
+//!
+//! Opening a tar archive requires scanning all entry headers, which takes a while for multi-GB archives.
+//! The `TarIndex` stores the result of this scan in a sidecar file next to the archive (`*.tar.index`).
+//! The sidecar is keyed by the size and modification time of the archive, so it is ignored and rebuilt
+//! as soon as the archive changes.
+
+use super::path_template::TarPathTemplate;
+use anyhow::{ensure, Result};
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	time::UNIX_EPOCH,
+};
+use versatiles_core::{io::*, tilejson::TileJSON, types::*};
+
+const MAGIC: &[u8] = b"VTARIDX1";
+
+/// Identifies a specific version of a tar archive by its size and modification time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ArchiveKey {
+	size: u64,
+	mtime_secs: u64,
+	mtime_nanos: u32,
+}
+
+impl ArchiveKey {
+	fn from_path(path: &Path) -> Result<ArchiveKey> {
+		let meta = fs::metadata(path)?;
+		let mtime = meta.modified()?.duration_since(UNIX_EPOCH)?;
+		Ok(ArchiveKey {
+			size: meta.len(),
+			mtime_secs: mtime.as_secs(),
+			mtime_nanos: mtime.subsec_nanos(),
+		})
+	}
+}
+
+/// The result of scanning a tar archive: the positions of all tiles, their format and the metadata.
+#[derive(Debug)]
+pub struct TarIndex {
+	pub tile_map: HashMap<TileCoord3, ByteRange>,
+	pub tile_format: TileFormat,
+	pub tile_compression: TileCompression,
+	pub tilejson: TileJSON,
+	pub template: TarPathTemplate,
+}
+
+impl TarIndex {
+	/// Returns the path of the sidecar file, e.g. `world.tar.index` for `world.tar`.
+	pub fn get_sidecar_path(path: &Path) -> PathBuf {
+		let mut filename = path.as_os_str().to_owned();
+		filename.push(".index");
+		PathBuf::from(filename)
+	}
+
+	/// Loads the sidecar index of a tar archive.
+	///
+	/// # Arguments
+	/// * `path` - The path to the tar archive (not to the sidecar).
+	///
+	/// # Returns
+	/// `None` if there is no sidecar, or if it belongs to a different version of the archive.
+	///
+	/// # Errors
+	/// Returns an error if the sidecar can not be read or is corrupt.
+	pub fn load(path: &Path) -> Result<Option<TarIndex>> {
+		let sidecar = TarIndex::get_sidecar_path(path);
+		if !sidecar.exists() {
+			return Ok(None);
+		}
+
+		let blob = Blob::from(fs::read(&sidecar)?);
+		TarIndex::from_blob(blob, &ArchiveKey::from_path(path)?)
+	}
+
+	/// Saves the index as a sidecar file next to the tar archive.
+	///
+	/// # Arguments
+	/// * `path` - The path to the tar archive (not to the sidecar).
+	///
+	/// # Errors
+	/// Returns an error if the sidecar can not be written, e.g. because the directory is read-only.
+	pub fn save(&self, path: &Path) -> Result<()> {
+		let blob = self.to_blob(&ArchiveKey::from_path(path)?)?;
+
+		// Write to a temporary file first, so that concurrent readers never see a partial index.
+		let sidecar = TarIndex::get_sidecar_path(path);
+		let mut temp = sidecar.clone().into_os_string();
+		temp.push(".tmp");
+		fs::write(&temp, blob.as_slice())?;
+		fs::rename(&temp, &sidecar)?;
+		Ok(())
+	}
+
+	/// Calculates the bbox pyramid of all indexed tiles, in the tile grid of the metadata.
+	pub fn get_bbox_pyramid(&self) -> Result<TileBBoxPyramid> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty_in_grid(self.tilejson.get_tile_grid()?);
+		for coord in self.tile_map.keys() {
+			bbox_pyramid.include_coord(coord);
+		}
+		Ok(bbox_pyramid)
+	}
+
+	fn to_blob(&self, key: &ArchiveKey) -> Result<Blob> {
+		let mut writer = ValueWriterBlob::new_le();
+		writer.write_slice(MAGIC)?;
+		writer.write_u64(key.size)?;
+		writer.write_u64(key.mtime_secs)?;
+		writer.write_u32(key.mtime_nanos)?;
+
+		for text in [
+			self.tile_format.as_str(),
+			self.tile_compression.as_str(),
+			self.tilejson.as_string().as_str(),
+			self.template.as_str(),
+		] {
+			writer.write_varint(text.len() as u64)?;
+			writer.write_string(text)?;
+		}
+
+		writer.write_varint(self.tile_map.len() as u64)?;
+		for (coord, range) in self.tile_map.iter() {
+			writer.write_u8(coord.z)?;
+			writer.write_u32(coord.x)?;
+			writer.write_u32(coord.y)?;
+			writer.write_range(range)?;
+		}
+
+		Ok(writer.into_blob())
+	}
+
+	fn from_blob(blob: Blob, key: &ArchiveKey) -> Result<Option<TarIndex>> {
+		let mut reader = ValueReaderBlob::new_le(blob);
+		ensure!(
+			reader.read_blob(MAGIC.len() as u64)?.as_slice() == MAGIC,
+			"unknown tar index format"
+		);
+
+		let stored_key = ArchiveKey {
+			size: reader.read_u64()?,
+			mtime_secs: reader.read_u64()?,
+			mtime_nanos: reader.read_u32()?,
+		};
+		if &stored_key != key {
+			return Ok(None);
+		}
+
+		let mut read_text = || -> Result<String> {
+			let length = reader.read_varint()?;
+			reader.read_string(length)
+		};
+		let tile_format = TileFormat::parse_str(&read_text()?)?;
+		let tile_compression = TileCompression::parse_str(&read_text()?)?;
+		let tilejson = TileJSON::try_from(&read_text()?)?;
+		let template = TarPathTemplate::parse_str(&read_text()?)?;
+
+		let count = reader.read_varint()?;
+		let mut tile_map = HashMap::with_capacity(count as usize);
+		for _ in 0..count {
+			let z = reader.read_u8()?;
+			let x = reader.read_u32()?;
+			let y = reader.read_u32()?;
+			tile_map.insert(TileCoord3::new(x, y, z)?, reader.read_range()?);
+		}
+		ensure!(!reader.has_remaining(), "tar index contains unexpected trailing data");
+
+		Ok(Some(TarIndex {
+			tile_map,
+			tile_format,
+			tile_compression,
+			tilejson,
+			template,
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn make_index() -> TarIndex {
+		let mut tilejson = TileJSON::default();
+		tilejson.set_string("name", "test").unwrap();
+		TarIndex {
+			tile_map: HashMap::from([
+				(TileCoord3::new(0, 0, 0).unwrap(), ByteRange::new(512, 100)),
+				(TileCoord3::new(1, 0, 1).unwrap(), ByteRange::new(1536, 200)),
+			]),
+			tile_format: TileFormat::PBF,
+			tile_compression: TileCompression::Gzip,
+			tilejson,
+			template: TarPathTemplate::Flat,
+		}
+	}
+
+	#[test]
+	fn sidecar_path() {
+		assert_eq!(
+			TarIndex::get_sidecar_path(Path::new("/data/world.tar")),
+			PathBuf::from("/data/world.tar.index")
+		);
+	}
+
+	#[test]
+	fn blob_roundtrip() -> Result<()> {
+		let key = ArchiveKey {
+			size: 1234,
+			mtime_secs: 1_700_000_000,
+			mtime_nanos: 42,
+		};
+		let index = make_index();
+		let blob = index.to_blob(&key)?;
+
+		let loaded = TarIndex::from_blob(blob.clone(), &key)?.unwrap();
+		assert_eq!(loaded.tile_map, index.tile_map);
+		assert_eq!(loaded.tile_format, TileFormat::PBF);
+		assert_eq!(loaded.tile_compression, TileCompression::Gzip);
+		assert_eq!(loaded.tilejson.as_string(), index.tilejson.as_string());
+		assert_eq!(loaded.template, TarPathTemplate::Flat);
+		assert_eq!(
+			format!("{:?}", loaded.get_bbox_pyramid()?),
+			"[0: [0,0,0,0] (1), 1: [1,0,1,0] (1)]"
+		);
+
+		// A changed archive invalidates the index
+		let other_key = ArchiveKey { size: 1235, ..key };
+		assert!(TarIndex::from_blob(blob, &other_key)?.is_none());
+
+		// Garbage is rejected
+		assert!(TarIndex::from_blob(Blob::from("not an index"), &key).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn save_and_load() -> Result<()> {
+		let file = assert_fs::NamedTempFile::new("tiles.tar")?;
+		fs::write(file.path(), b"dummy archive")?;
+
+		assert!(TarIndex::load(file.path())?.is_none());
+
+		make_index().save(file.path())?;
+		assert!(TarIndex::get_sidecar_path(file.path()).exists());
+		let loaded = TarIndex::load(file.path())?.unwrap();
+		assert_eq!(loaded.tile_map.len(), 2);
+
+		fs::write(file.path(), b"modified dummy archive")?;
+		assert!(TarIndex::load(file.path())?.is_none());
+		Ok(())
+	}
+}
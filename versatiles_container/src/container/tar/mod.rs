@@ -36,9 +36,15 @@
 //!
 //! The above example demonstrates how to read from an existing tar archive containing tile data
 //! and how to write tile data to a new tar archive using `TarTilesReader` and `TarTilesWriter` respectively.
+//!
+//! When reading, the positions of all tiles are cached in a sidecar file next to the archive (e.g. `world.tar.index`),
+//! so that opening large archives a second time does not require scanning all headers again.
 
+mod index;
+mod path_template;
 mod reader;
 mod writer;
 
+pub use path_template::TarPathTemplate;
 pub use reader::TarTilesReader;
 pub use writer::TarTilesWriter;
@@ -0,0 +1,147 @@
+//! Defines the layouts of tile entries inside a tar archive.
+//!
+//! Tar archives produced by different tools use different conventions for the paths of the tiles:
+//!
+//! | template            | example          |
+//! |---------------------|------------------|
+//! | `{z}/{y}/{x}.{ext}` | `5/11/17.pbf.gz` |
+//! | `{z}/{x}/{y}.{ext}` | `5/17/11.pbf.gz` |
+//! | `{z}-{x}-{y}.{ext}` | `5-17-11.pbf.gz` |
+
+use anyhow::{bail, Result};
+use std::fmt::Display;
+use versatiles_core::types::{TileCompression, TileCoord3, TileFormat};
+
+/// The layout of the tile paths inside a tar archive.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TarPathTemplate {
+	/// `{z}/{y}/{x}.{ext}`
+	#[default]
+	ZYX,
+	/// `{z}/{x}/{y}.{ext}`
+	ZXY,
+	/// `{z}-{x}-{y}.{ext}`
+	Flat,
+}
+
+impl TarPathTemplate {
+	pub fn as_str(&self) -> &str {
+		match self {
+			TarPathTemplate::ZYX => "{z}/{y}/{x}",
+			TarPathTemplate::ZXY => "{z}/{x}/{y}",
+			TarPathTemplate::Flat => "{z}-{x}-{y}",
+		}
+	}
+
+	/// Parses a template, either written as a pattern like `{z}/{x}/{y}` or as a short name like `zxy` or `flat`.
+	pub fn parse_str(value: &str) -> Result<Self> {
+		let value = value.trim().to_lowercase();
+		let value = value.trim_end_matches(".{ext}");
+		Ok(match value {
+			"zyx" | "{z}/{y}/{x}" => TarPathTemplate::ZYX,
+			"zxy" | "{z}/{x}/{y}" => TarPathTemplate::ZXY,
+			"flat" | "{z}-{x}-{y}" => TarPathTemplate::Flat,
+			_ => bail!(
+				"Unknown tar path template '{value}'. Expected {{z}}/{{y}}/{{x}}, {{z}}/{{x}}/{{y}} or {{z}}-{{x}}-{{y}}"
+			),
+		})
+	}
+
+	/// Builds the path of a tile entry.
+	///
+	/// # Arguments
+	/// * `coord` - The coordinate of the tile.
+	/// * `extension` - The file extension including the leading dot, e.g. `.pbf.gz`.
+	pub fn format_path(&self, coord: &TileCoord3, extension: &str) -> String {
+		let TileCoord3 { x, y, z } = coord;
+		match self {
+			TarPathTemplate::ZYX => format!("{z}/{y}/{x}{extension}"),
+			TarPathTemplate::ZXY => format!("{z}/{x}/{y}{extension}"),
+			TarPathTemplate::Flat => format!("{z}-{x}-{y}{extension}"),
+		}
+	}
+
+	/// Parses the path of a tar entry.
+	///
+	/// # Arguments
+	/// * `path` - The path of the entry, with components separated by `/` and without a leading `./`.
+	///
+	/// # Returns
+	/// The coordinate, format and compression of the tile, or `None` if the path does not match the template.
+	pub fn parse_path(&self, path: &str) -> Option<(TileCoord3, TileFormat, TileCompression)> {
+		let mut filename = String::from(path);
+		let compression = TileCompression::from_filename(&mut filename);
+		let format = TileFormat::from_filename(&mut filename)?;
+
+		let separator = match self {
+			TarPathTemplate::ZYX | TarPathTemplate::ZXY => '/',
+			TarPathTemplate::Flat => '-',
+		};
+		let parts = filename
+			.split(separator)
+			.map(|part| part.parse::<u32>().ok())
+			.collect::<Option<Vec<u32>>>()?;
+		if parts.len() != 3 {
+			return None;
+		}
+
+		let z = u8::try_from(parts[0]).ok()?;
+		let coord = match self {
+			TarPathTemplate::ZYX => TileCoord3::new(parts[2], parts[1], z),
+			TarPathTemplate::ZXY | TarPathTemplate::Flat => TileCoord3::new(parts[1], parts[2], z),
+		};
+		Some((coord.ok()?, format, compression))
+	}
+}
+
+impl Display for TarPathTemplate {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use TarPathTemplate::*;
+
+	#[test]
+	fn parse_str() {
+		assert_eq!(TarPathTemplate::parse_str("zyx").unwrap(), ZYX);
+		assert_eq!(TarPathTemplate::parse_str("{z}/{x}/{y}.{ext}").unwrap(), ZXY);
+		assert_eq!(TarPathTemplate::parse_str(" FLAT ").unwrap(), Flat);
+		assert!(TarPathTemplate::parse_str("{x}/{y}/{z}").is_err());
+	}
+
+	#[test]
+	fn format_path() -> Result<()> {
+		let coord = TileCoord3::new(17, 11, 5)?;
+		assert_eq!(ZYX.format_path(&coord, ".pbf.gz"), "5/11/17.pbf.gz");
+		assert_eq!(ZXY.format_path(&coord, ".pbf.gz"), "5/17/11.pbf.gz");
+		assert_eq!(Flat.format_path(&coord, ".png"), "5-17-11.png");
+		Ok(())
+	}
+
+	#[test]
+	fn roundtrip() -> Result<()> {
+		let coord = TileCoord3::new(17, 11, 5)?;
+		for template in [ZYX, ZXY, Flat] {
+			let path = template.format_path(&coord, ".pbf.br");
+			assert_eq!(
+				template.parse_path(&path),
+				Some((coord, TileFormat::PBF, TileCompression::Brotli))
+			);
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn parse_path_mismatch() {
+		assert_eq!(ZYX.parse_path("5-17-11.pbf"), None);
+		assert_eq!(Flat.parse_path("5/11/17.pbf"), None);
+		assert_eq!(ZYX.parse_path("tiles.json"), None);
+		assert_eq!(ZYX.parse_path("5/11/17.unknown"), None);
+		assert_eq!(ZYX.parse_path("5/a/17.pbf"), None);
+	}
+}
@@ -1,37 +1,156 @@
 //! Provides functionality for reading tile data from a tar archive.
-
-use anyhow::{bail, Result};
+//!
+//! Tiles are expected at `z/y/x.ext`, `z/x/y.ext` or `z-x-y.ext` (see `TarPathTemplate`) in XYZ order.
+//! The layout is detected automatically: flat and nested paths are distinguished by their shape, and nested
+//! paths are read as `z/y/x.ext`, unless the bounds in the metadata indicate that x and y are swapped.
+//! Archives in TMS order are supported, if they declare
+//! `"scheme": "tms"` in their TileJSON or if the scheme is overridden with `override_scheme`.
+//!
+//! The positions of all tiles are cached in a sidecar file (`*.tar.index`), see `TarIndex`.
+
+use super::{index::TarIndex, path_template::TarPathTemplate};
+use anyhow::{bail, ensure, Context, Result};
 use async_trait::async_trait;
 use std::{collections::HashMap, fmt::Debug, io::Read, path::Path};
 use tar::{Archive, EntryType};
-use versatiles_core::{io::*, tilejson::TileJSON, types::*, utils::decompress};
+#[cfg(feature = "cli")]
+use versatiles_core::utils::PrettyPrint;
+use versatiles_core::{
+	io::*,
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, TransformCoord},
+};
 
 /// A struct that provides functionality to read tile data from a tar archive.
 pub struct TarTilesReader {
 	tilejson: TileJSON,
 	name: String,
-	reader: Box<DataReaderFile>,
+	reader: DataReader,
 	tile_map: HashMap<TileCoord3, ByteRange>,
 	parameters: TilesReaderParameters,
+	scheme: TileScheme,
 }
 
 impl TarTilesReader {
 	/// Creates a new `TarTilesReader` from a given file path.
 	///
+	/// If a valid sidecar index exists, it is used instead of scanning the archive.
+	/// Otherwise the archive is scanned and the sidecar index is written, if possible.
+	///
 	/// # Arguments
 	/// * `path` - The path to the tar archive file.
 	///
 	/// # Errors
 	/// Returns an error if the file cannot be opened or read.
 	pub fn open_path(path: &Path) -> Result<TarTilesReader> {
+		TarTilesReader::open_path_with_template(path, None)
+	}
+
+	/// Creates a new `TarTilesReader` from a given file path, using a specific layout of the tile paths.
+	///
+	/// # Arguments
+	/// * `path` - The path to the tar archive file.
+	/// * `template` - The layout of the tile paths, or `None` to detect it automatically.
+	///
+	/// # Errors
+	/// Returns an error if the file cannot be opened or read.
+	pub fn open_path_with_template(path: &Path, template: Option<TarPathTemplate>) -> Result<TarTilesReader> {
 		let mut reader = DataReaderFile::open(path)?;
-		let mut archive = Archive::new(&mut reader);
+
+		let index = match TarIndex::load(path) {
+			Ok(Some(index)) if template.is_none_or(|t| t == index.template) => {
+				log::debug!("using tar index {:?}", TarIndex::get_sidecar_path(path));
+				index
+			}
+			result => {
+				if let Err(err) = result {
+					log::warn!("ignoring invalid tar index for {path:?}: {err}");
+				}
+				let index = TarTilesReader::scan_archive(&mut reader, template)?;
+				if let Err(err) = index.save(path) {
+					log::warn!("could not write tar index for {path:?}: {err}");
+				}
+				index
+			}
+		};
+
+		TarTilesReader::from_index(index, reader, path.to_str().unwrap().to_string())
+	}
+
+	/// Creates a new `TarTilesReader` from a tar archive in memory, e.g. an archive piped in through stdin.
+	///
+	/// # Arguments
+	/// * `blob` - The content of the tar archive.
+	/// * `name` - The name of the source, e.g. `"stdin"`.
+	///
+	/// # Errors
+	/// Returns an error if the archive cannot be read.
+	pub fn open_blob(blob: Blob, name: &str) -> Result<TarTilesReader> {
+		let mut reader = Box::new(DataReaderBlob::from(blob));
+		let index = TarTilesReader::scan_archive(&mut *reader, None)?;
+		TarTilesReader::from_index(index, reader, name.to_string())
+	}
+
+	fn from_index(index: TarIndex, reader: DataReader, name: String) -> Result<TarTilesReader> {
+		let mut bbox_pyramid = index.get_bbox_pyramid()?;
+		let TarIndex {
+			tile_map,
+			tile_format,
+			tile_compression,
+			tilejson,
+			template,
+		} = index;
+		log::debug!("tar {name:?} uses the path template {template}");
+
+		// The tile map always uses the coordinates of the paths, only the pyramid is converted to XYZ.
+		let scheme = match tilejson.get_str("scheme") {
+			Some(value) => TileScheme::parse_str(value)?,
+			None => TileScheme::Xyz,
+		};
+		if scheme == TileScheme::Tms {
+			ensure!(
+				bbox_pyramid.get_grid().get_base_size().1 == 1,
+				"the tms scheme is not supported for the tile grid {}",
+				bbox_pyramid.get_grid()
+			);
+			bbox_pyramid.flip_y();
+		}
+
+		if let Some(bounds) = &tilejson.bounds {
+			if let Some(suspected) = scheme.check_orientation(&bbox_pyramid, bounds) {
+				log::warn!(
+					"the tiles in {name} do not match the bounds in the metadata, maybe they are stored in {suspected} instead of {scheme} order"
+				);
+			}
+		}
+
+		Ok(TarTilesReader {
+			tilejson,
+			name,
+			parameters: TilesReaderParameters::new(tile_format, tile_compression, bbox_pyramid),
+			reader,
+			tile_map,
+			scheme,
+		})
+	}
+
+	/// Scans all entries of the tar archive and collects the positions of the tiles and the metadata.
+	///
+	/// # Arguments
+	/// * `reader` - The tar archive.
+	/// * `template` - The layout of the tile paths, or `None` to detect it automatically.
+	///
+	/// # Errors
+	/// Returns an error if the archive cannot be read or contains tiles of different formats or layouts.
+	fn scan_archive(reader: &mut impl Read, template: Option<TarPathTemplate>) -> Result<TarIndex> {
+		let mut archive = Archive::new(reader);
 
 		let mut tilejson = TileJSON::default();
 		let mut tile_map = HashMap::new();
 		let mut tile_format: Option<TileFormat> = None;
 		let mut tile_compression: Option<TileCompression> = None;
-		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		let mut detected_template: Option<TarPathTemplate> = template;
 
 		for entry in archive.entries()? {
 			let mut entry = entry?;
@@ -51,20 +170,19 @@ impl TarTilesReader {
 			drop(path);
 			let path_vec: Vec<&str> = path_tmp_string.split('/').collect();
 
-			if path_vec.len() == 3 {
-				let z = path_vec[0].parse::<u8>()?;
-				let y = path_vec[1].parse::<u32>()?;
-
-				let mut filename: String = String::from(path_vec[2]);
-				let this_compression = TileCompression::from_filename(&mut filename);
-				let this_format = TileFormat::from_filename(&mut filename);
+			let tile = match template {
+				Some(template) => template.parse_path(&path_tmp_string).map(|tile| (template, tile)),
+				None => [TarPathTemplate::ZYX, TarPathTemplate::Flat]
+					.into_iter()
+					.find_map(|template| template.parse_path(&path_tmp_string).map(|tile| (template, tile))),
+			};
 
-				if this_format.is_none() {
-					continue;
+			if let Some((this_template, (coord3, this_format, this_compression))) = tile {
+				if detected_template.is_none() {
+					detected_template = Some(this_template);
+				} else if detected_template != Some(this_template) {
+					bail!("tar contains tiles with different path layouts, e.g. {path_tmp_string:?}");
 				}
-				let this_format = this_format.unwrap();
-
-				let x = filename.parse::<u32>()?;
 
 				if tile_format.is_none() {
 					tile_format = Some(this_format);
@@ -81,8 +199,6 @@ impl TarTilesReader {
 				let offset = entry.raw_file_position();
 				let length = entry.size();
 
-				let coord3 = TileCoord3::new(x, y, z)?;
-				bbox_pyramid.include_coord(&coord3);
 				tile_map.insert(coord3, ByteRange { offset, length });
 				continue;
 			}
@@ -120,14 +236,71 @@ impl TarTilesReader {
 			log::warn!("unknown file in tar: {path_tmp_string:?}");
 		}
 
-		Ok(TarTilesReader {
-			tilejson,
-			name: path.to_str().unwrap().to_string(),
-			parameters: TilesReaderParameters::new(tile_format.unwrap(), tile_compression.unwrap(), bbox_pyramid),
-			reader,
+		let mut index_template = detected_template.unwrap_or_default();
+		if template.is_none()
+			&& detected_template == Some(TarPathTemplate::ZYX)
+			&& template_is_swapped(&tile_map, &tilejson)
+		{
+			log::info!("the tiles do not match the bounds in the metadata, reading them as {{z}}/{{x}}/{{y}}");
+			index_template = TarPathTemplate::ZXY;
+			tile_map = tile_map
+				.into_iter()
+				.map(|(mut coord, range)| {
+					coord.swap_xy();
+					(coord, range)
+				})
+				.collect();
+		}
+
+		Ok(TarIndex {
 			tile_map,
+			tile_format: tile_format.context("no tiles found in tar")?,
+			tile_compression: tile_compression.context("no tiles found in tar")?,
+			tilejson,
+			template: index_template,
 		})
 	}
+
+	/// Returns the byte range of a tile in the archive, respecting the tile scheme.
+	fn get_tile_range(&self, coord: &TileCoord3) -> Option<&ByteRange> {
+		match self.scheme {
+			TileScheme::Xyz => self.tile_map.get(coord),
+			TileScheme::Tms => {
+				let mut coord = *coord;
+				coord.flip_y();
+				self.tile_map.get(&coord)
+			}
+		}
+	}
+}
+
+/// Checks whether the tiles match the bounds in the metadata only if x and y are swapped.
+fn template_is_swapped(tile_map: &HashMap<TileCoord3, ByteRange>, tilejson: &TileJSON) -> bool {
+	let Some(bounds) = &tilejson.bounds else {
+		return false;
+	};
+	// x and y can only be swapped in grids with as many columns as rows
+	let grid = match tilejson.get_tile_grid() {
+		Ok(grid) if grid.is_square() => grid,
+		_ => return false,
+	};
+
+	let mut bbox_pyramid = TileBBoxPyramid::new_empty_in_grid(grid);
+	for coord in tile_map.keys() {
+		bbox_pyramid.include_coord(coord);
+	}
+	let Some(level) = bbox_pyramid.get_zoom_max() else {
+		return false;
+	};
+	let Ok(expected) = TileBBox::from_geo_in_grid(grid, level, bounds) else {
+		return false;
+	};
+
+	let bbox = bbox_pyramid.get_level_bbox(level).clone();
+	let mut swapped = bbox.clone();
+	swapped.swap_xy();
+
+	!bbox.overlaps_bbox(&expected).unwrap_or(true) && swapped.overlaps_bbox(&expected).unwrap_or(false)
 }
 
 #[async_trait]
@@ -150,6 +323,23 @@ impl TilesReaderTrait for TarTilesReader {
 		self.parameters.tile_compression = tile_compression;
 	}
 
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.parameters.tile_format = tile_format;
+		Ok(())
+	}
+
+	/// Overrides the orientation of the y axis, e.g. to read archives that store tiles in TMS order.
+	///
+	/// # Arguments
+	/// * `scheme` - The tile scheme used in the archive.
+	fn override_scheme(&mut self, scheme: TileScheme) -> Result<()> {
+		if self.scheme != scheme {
+			self.scheme = scheme;
+			self.parameters.bbox_pyramid.flip_y();
+		}
+		Ok(())
+	}
+
 	/// Returns the metadata as a `Blob`.
 	///
 	/// # Errors
@@ -168,7 +358,7 @@ impl TilesReaderTrait for TarTilesReader {
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
 		log::trace!("get_tile_data {:?}", coord);
 
-		let range = self.tile_map.get(coord);
+		let range = self.get_tile_range(coord);
 
 		if let Some(range) = range {
 			let blob = self.reader.read_range(range).await?;
@@ -178,10 +368,20 @@ impl TilesReaderTrait for TarTilesReader {
 		}
 	}
 
+	/// Checks whether a tile exists, using only the index of the archive.
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		Ok(self.get_tile_range(coord).is_some())
+	}
+
 	/// Returns the name of the tar archive.
 	fn get_source_name(&self) -> &str {
 		&self.name
 	}
+	// deep probe of container tile contents
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		crate::probe_vector_tile_contents(&*self, print).await
+	}
 }
 
 impl Debug for TarTilesReader {
@@ -221,6 +421,131 @@ pub mod tests {
 		let tile = reader.get_tile_data(&TileCoord3::new(6, 2, 3)?).await?.unwrap();
 		assert_eq!(decompress_gzip(&tile)?.as_slice(), MOCK_BYTES_PBF);
 
+		assert!(reader.has_tile(&TileCoord3::new(6, 2, 3)?).await?);
+		assert!(!reader.has_tile(&TileCoord3::new(6, 2, 4)?).await?);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn sidecar_index() -> Result<()> {
+		let temp_file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 3, "tar").await?;
+		let sidecar = TarIndex::get_sidecar_path(&temp_file);
+		assert!(!sidecar.exists());
+
+		// The first open scans the archive and writes the index
+		let reader1 = TarTilesReader::open_path(&temp_file)?;
+		assert!(sidecar.exists());
+
+		// The second open uses the index
+		let reader2 = TarTilesReader::open_path(&temp_file)?;
+		assert_eq!(reader1.tile_map, reader2.tile_map);
+		assert_eq!(format!("{reader1:?}"), format!("{reader2:?}"));
+		assert_eq!(reader1.get_tilejson().as_string(), reader2.get_tilejson().as_string());
+
+		let coord = TileCoord3::new(6, 2, 3)?;
+		assert_eq!(
+			reader1.get_tile_data(&coord).await?,
+			reader2.get_tile_data(&coord).await?
+		);
+
+		// A corrupt index is ignored and replaced
+		std::fs::write(&sidecar, b"garbage")?;
+		let reader3 = TarTilesReader::open_path(&temp_file)?;
+		assert_eq!(reader1.tile_map, reader3.tile_map);
+		assert!(TarIndex::load(&temp_file)?.is_some());
+
+		Ok(())
+	}
+
+	/// Writes a tar archive with the given entries.
+	fn make_tar(entries: &[(&str, &[u8])]) -> Result<assert_fs::NamedTempFile> {
+		let file = assert_fs::NamedTempFile::new("layout.tar")?;
+		let mut builder = tar::Builder::new(std::fs::File::create(file.path())?);
+		for (path, data) in entries {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(data.len() as u64);
+			header.set_mode(0o644);
+			builder.append_data(&mut header, path, *data)?;
+		}
+		builder.finish()?;
+		Ok(file)
+	}
+
+	#[tokio::test]
+	async fn detect_flat_template() -> Result<()> {
+		let file = make_tar(&[("tiles.json", b"{}"), ("3-5-1.png", b"a"), ("3-6-1.png", b"b")])?;
+		let reader = TarTilesReader::open_path(&file)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[3: [5,1,6,1] (2)]"
+		);
+		assert_eq!(
+			reader.get_tile_data(&TileCoord3::new(6, 1, 3)?).await?,
+			Some(Blob::from("b"))
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn detect_swapped_template() -> Result<()> {
+		// Berlin at zoom level 10 is x = 549..551, y = 335..336
+		let tilejson = br#"{"bounds":[13.08,52.33,13.76,52.67]}"#;
+		let file = make_tar(&[("tiles.json", tilejson), ("10/550/336.pbf", b"a")])?;
+
+		let reader = TarTilesReader::open_path(&file)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[10: [550,336,550,336] (1)]"
+		);
+
+		// Without bounds, nested paths are read as z/y/x
+		let file = make_tar(&[("10/550/336.pbf", b"a")])?;
+		let reader = TarTilesReader::open_path(&file)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[10: [336,550,336,550] (1)]"
+		);
+
+		// An explicit template ignores the existing index
+		let reader = TarTilesReader::open_path_with_template(&file, Some(TarPathTemplate::ZXY))?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[10: [550,336,550,336] (1)]"
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn mixed_templates() -> Result<()> {
+		let file = make_tar(&[("3/1/5.png", b"a"), ("3-6-1.png", b"b")])?;
+		assert!(TarTilesReader::open_path(&file).is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn override_scheme() -> Result<()> {
+		let temp_file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 3, "tar").await?;
+		let mut reader = TarTilesReader::open_path(&temp_file)?;
+		reader.parameters.bbox_pyramid = TileBBoxPyramid::new_empty();
+		reader
+			.parameters
+			.bbox_pyramid
+			.include_bbox(&TileBBox::new(3, 0, 0, 7, 1)?);
+
+		reader.override_scheme(TileScheme::Tms)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid.get_level_bbox(3)),
+			"3: [0,6,7,7] (16)"
+		);
+		assert!(reader.get_tile_data(&TileCoord3::new(6, 2, 3)?).await?.is_some());
+
+		reader.override_scheme(TileScheme::Xyz)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid.get_level_bbox(3)),
+			"3: [0,0,7,1] (16)"
+		);
+
 		Ok(())
 	}
 
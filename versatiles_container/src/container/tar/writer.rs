@@ -1,10 +1,14 @@
 //! Provides functionality for writing tile data to a tar archive.
+//!
+//! Tiles are written as `z/y/x.ext` by default, other layouts can be selected with `write_to_path_with_template`.
 
+use super::TarPathTemplate;
 use crate::TilesWriterTrait;
 use anyhow::{bail, Result};
 use async_trait::async_trait;
 use std::{
 	fs::File,
+	io::Write,
 	path::{Path, PathBuf},
 };
 use tar::{Builder, Header};
@@ -13,26 +17,48 @@ use versatiles_core::{io::DataWriterTrait, progress::get_progress_bar, types::Ti
 /// A struct that provides functionality to write tile data to a tar archive.
 pub struct TarTilesWriter {}
 
-#[async_trait]
-impl TilesWriterTrait for TarTilesWriter {
-	/// Writes the tile data from the `TilesReader` to a tar archive at the specified path.
+impl TarTilesWriter {
+	/// Writes the tile data from the `TilesReader` to a tar archive, using a specific layout of the tile paths.
 	///
 	/// # Arguments
 	/// * `reader` - The `TilesReader` instance containing the tile data.
 	/// * `path` - The path to the output tar archive file.
+	/// * `template` - The layout of the tile paths.
 	///
 	/// # Errors
 	/// Returns an error if there is an issue creating the tar archive or writing the data.
-	async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
-		let file = File::create(path)?;
-		let mut builder = Builder::new(file);
+	pub async fn write_to_path_with_template(
+		reader: &mut dyn TilesReaderTrait,
+		path: &Path,
+		template: TarPathTemplate,
+	) -> Result<()> {
+		TarTilesWriter::write_to_stream(reader, File::create(path)?, template).await
+	}
+
+	/// Writes the tile data from the `TilesReader` as a tar archive to a stream, e.g. stdout.
+	///
+	/// Tar archives are written strictly sequentially, so the stream does not have to be seekable.
+	///
+	/// # Arguments
+	/// * `reader` - The `TilesReader` instance containing the tile data.
+	/// * `stream` - The stream the tar archive is written to.
+	/// * `template` - The layout of the tile paths.
+	///
+	/// # Errors
+	/// Returns an error if there is an issue writing the data.
+	pub async fn write_to_stream(
+		reader: &mut dyn TilesReaderTrait,
+		stream: impl Write + Send,
+		template: TarPathTemplate,
+	) -> Result<()> {
+		let mut builder = Builder::new(stream);
 
 		let parameters = reader.get_parameters();
 		let tile_format = &parameters.tile_format.clone();
 		let tile_compression = &parameters.tile_compression.clone();
 		let bbox_pyramid = reader.get_parameters().bbox_pyramid.clone();
 
-		let extension_format = tile_format.extension();
+		let extension = format!("{}{}", tile_format.extension(), tile_compression.extension());
 		let extension_compression = tile_compression.extension();
 
 		let meta_data = compress(reader.get_tilejson().into(), tile_compression)?;
@@ -50,10 +76,7 @@ impl TilesWriterTrait for TarTilesWriter {
 			while let Some((coord, blob)) = stream.next().await {
 				progress.inc(1);
 
-				let filename = format!(
-					"./{}/{}/{}{}{}",
-					coord.z, coord.y, coord.x, extension_format, extension_compression
-				);
+				let filename = format!("./{}", template.format_path(&coord, &extension));
 				let path = PathBuf::from(&filename);
 
 				// Build header
@@ -67,10 +90,25 @@ impl TilesWriterTrait for TarTilesWriter {
 		}
 
 		progress.finish();
-		builder.finish()?;
+		builder.into_inner()?.flush()?;
 
 		Ok(())
 	}
+}
+
+#[async_trait]
+impl TilesWriterTrait for TarTilesWriter {
+	/// Writes the tile data from the `TilesReader` to a tar archive at the specified path.
+	///
+	/// # Arguments
+	/// * `reader` - The `TilesReader` instance containing the tile data.
+	/// * `path` - The path to the output tar archive file.
+	///
+	/// # Errors
+	/// Returns an error if there is an issue creating the tar archive or writing the data.
+	async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
+		TarTilesWriter::write_to_path_with_template(reader, path, TarPathTemplate::default()).await
+	}
 
 	/// Writes the tile data from the `TilesReader` to the specified `DataWriterTrait`.
 	///
@@ -109,6 +147,60 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn stream() -> Result<()> {
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters {
+			bbox_pyramid: TileBBoxPyramid::new_full(3),
+			tile_compression: TileCompression::Gzip,
+			tile_format: TileFormat::PBF,
+		})?;
+
+		let mut data: Vec<u8> = Vec::new();
+		TarTilesWriter::write_to_stream(&mut mock_reader, &mut data, TarPathTemplate::default()).await?;
+
+		let mut reader = TarTilesReader::open_blob(Blob::from(data), "stdin")?;
+		assert_eq!(reader.get_source_name(), "stdin");
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64)]"
+		);
+		MockTilesWriter::write(&mut reader).await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn path_templates() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.include_bbox(&TileBBox::new(3, 0, 0, 3, 1)?);
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters {
+			bbox_pyramid,
+			tile_compression: TileCompression::Gzip,
+			tile_format: TileFormat::PBF,
+		})?;
+
+		for template in [TarPathTemplate::ZYX, TarPathTemplate::ZXY, TarPathTemplate::Flat] {
+			let temp_path = NamedTempFile::new("test_template.tar")?;
+			TarTilesWriter::write_to_path_with_template(&mut mock_reader, &temp_path, template).await?;
+
+			let entries = tar::Archive::new(File::open(&temp_path)?)
+				.entries()?
+				.map(|entry| Ok(entry?.path()?.to_str().unwrap().to_string()))
+				.collect::<Result<Vec<_>>>()?;
+			let expected = template.format_path(&TileCoord3::new(2, 1, 3)?, ".pbf.gz");
+			assert!(entries.contains(&expected), "{expected} not in {entries:?}");
+
+			let mut reader = TarTilesReader::open_path_with_template(&temp_path, Some(template))?;
+			assert_eq!(
+				format!("{:?}", reader.get_parameters().bbox_pyramid),
+				"[3: [0,0,3,1] (8)]"
+			);
+			MockTilesWriter::write(&mut reader).await?;
+		}
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_meta_data() -> Result<()> {
 		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters {
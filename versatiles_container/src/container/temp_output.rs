@@ -0,0 +1,107 @@
+//! Writes output files atomically.
+//!
+//! A container is written to a temporary file next to its destination (`<filename>.tmp~`) and renamed to the
+//! destination only after it has been written completely. Renaming within the same directory is atomic, so an
+//! interrupted conversion never leaves a truncated file at the destination, e.g. one that a server would serve.
+
+use anyhow::{Context, Result};
+use std::{
+	ffi::OsString,
+	fs,
+	path::{Path, PathBuf},
+};
+
+/// A temporary output file that replaces its destination when it is committed.
+///
+/// If it is dropped without being committed, e.g. because writing failed, the temporary file is removed and the
+/// destination stays untouched.
+#[derive(Debug)]
+pub struct TempOutputFile {
+	path: PathBuf,
+	temp_path: PathBuf,
+	committed: bool,
+}
+
+impl TempOutputFile {
+	/// Creates a temporary output file for the destination `path`. A stale temporary file is removed.
+	pub fn new(path: &Path) -> Result<TempOutputFile> {
+		let mut file_name: OsString = path
+			.file_name()
+			.with_context(|| format!("output path {path:?} has no file name"))?
+			.to_owned();
+		file_name.push(".tmp~");
+		let temp_path = path.with_file_name(file_name);
+
+		if temp_path.exists() {
+			fs::remove_file(&temp_path).with_context(|| format!("removing stale temporary file {temp_path:?}"))?;
+		}
+
+		Ok(TempOutputFile {
+			path: path.to_path_buf(),
+			temp_path,
+			committed: false,
+		})
+	}
+
+	/// Returns the path of the temporary file, that the output should be written to.
+	pub fn get_temp_path(&self) -> &Path {
+		&self.temp_path
+	}
+
+	/// Renames the temporary file to the destination, replacing an existing file.
+	pub fn commit(mut self) -> Result<()> {
+		fs::rename(&self.temp_path, &self.path)
+			.with_context(|| format!("renaming {:?} to {:?}", self.temp_path, self.path))?;
+		self.committed = true;
+		Ok(())
+	}
+}
+
+impl Drop for TempOutputFile {
+	fn drop(&mut self) {
+		if !self.committed && self.temp_path.exists() {
+			if let Err(err) = fs::remove_file(&self.temp_path) {
+				log::warn!("could not remove temporary file {:?}: {err}", self.temp_path);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+
+	#[test]
+	fn commit() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("tiles.versatiles");
+		fs::write(&path, "old")?;
+
+		let output = TempOutputFile::new(&path)?;
+		assert_eq!(output.get_temp_path(), dir.path().join("tiles.versatiles.tmp~"));
+		fs::write(output.get_temp_path(), "new")?;
+		assert_eq!(fs::read_to_string(&path)?, "old");
+
+		output.commit()?;
+		assert_eq!(fs::read_to_string(&path)?, "new");
+		assert!(!dir.path().join("tiles.versatiles.tmp~").exists());
+		Ok(())
+	}
+
+	#[test]
+	fn drop_without_commit() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("tiles.versatiles");
+		fs::write(&path, "old")?;
+
+		let output = TempOutputFile::new(&path)?;
+		let temp_path = output.get_temp_path().to_path_buf();
+		fs::write(&temp_path, "truncat")?;
+		drop(output);
+
+		assert!(!temp_path.exists());
+		assert_eq!(fs::read_to_string(&path)?, "old");
+		Ok(())
+	}
+}
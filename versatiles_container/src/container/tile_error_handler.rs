@@ -0,0 +1,170 @@
+//! Handles tiles that can't be read or converted during a conversion.
+//!
+//! Depending on the [`TileErrorPolicy`], the conversion stops at the first failed tile, or failed tiles are
+//! skipped and counted, or collected into a report. In every case the conversion fails at the end, so that
+//! no incomplete container is silently published.
+
+use anyhow::{anyhow, bail, Error, Result};
+use log::warn;
+use std::{
+	fmt::Display,
+	str::FromStr,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex,
+	},
+};
+
+/// What to do when a tile can't be read or converted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TileErrorPolicy {
+	/// Stop the conversion at the first failed tile.
+	#[default]
+	Fail,
+	/// Skip failed tiles with a warning and count them.
+	Skip,
+	/// Skip failed tiles and list all of them at the end.
+	Report,
+}
+
+impl FromStr for TileErrorPolicy {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		Ok(match s.to_lowercase().as_str() {
+			"fail" => TileErrorPolicy::Fail,
+			"skip" => TileErrorPolicy::Skip,
+			"report" => TileErrorPolicy::Report,
+			_ => bail!("unknown tile error policy '{s}', use 'fail', 'skip' or 'report'"),
+		})
+	}
+}
+
+impl Display for TileErrorPolicy {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			TileErrorPolicy::Fail => "fail",
+			TileErrorPolicy::Skip => "skip",
+			TileErrorPolicy::Report => "report",
+		})
+	}
+}
+
+/// Counts the errors of a conversion and decides whether to continue.
+#[derive(Debug, Default)]
+pub struct TileErrorHandler {
+	policy: TileErrorPolicy,
+	error_count: AtomicU64,
+	errors: Mutex<Vec<String>>,
+}
+
+impl TileErrorHandler {
+	pub fn new(policy: TileErrorPolicy) -> TileErrorHandler {
+		TileErrorHandler {
+			policy,
+			..Default::default()
+		}
+	}
+
+	/// Handles the error of a tile.
+	pub fn handle(&self, err: Error) {
+		self.error_count.fetch_add(1, Ordering::Relaxed);
+		match self.policy {
+			TileErrorPolicy::Fail => {
+				let mut errors = self.errors.lock().unwrap();
+				if errors.is_empty() {
+					errors.push(format!("{err:#}"));
+				}
+			}
+			TileErrorPolicy::Skip => warn!("skipping tile: {err:#}"),
+			TileErrorPolicy::Report => self.errors.lock().unwrap().push(format!("{err:#}")),
+		}
+	}
+
+	/// Returns the number of tiles that failed so far.
+	pub fn get_error_count(&self) -> u64 {
+		self.error_count.load(Ordering::Relaxed)
+	}
+
+	/// Returns `true` if the conversion has to stop.
+	pub fn should_stop(&self) -> bool {
+		self.policy == TileErrorPolicy::Fail && self.error_count.load(Ordering::Relaxed) > 0
+	}
+
+	/// Reports the results after the conversion.
+	///
+	/// # Errors
+	/// Returns an error if any tile failed.
+	pub fn finish(&self) -> Result<()> {
+		let error_count = self.error_count.load(Ordering::Relaxed);
+		if error_count == 0 {
+			return Ok(());
+		}
+
+		let errors = self.errors.lock().unwrap();
+		Err(match self.policy {
+			TileErrorPolicy::Fail => anyhow!("conversion stopped, because a tile failed: {}", errors[0]),
+			TileErrorPolicy::Skip => anyhow!("{error_count} tiles failed and were skipped"),
+			TileErrorPolicy::Report => {
+				let list: Vec<String> = errors.iter().map(|err| format!("  - {err}")).collect();
+				anyhow!("{error_count} tiles failed and were skipped:\n{}", list.join("\n"))
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_policy() -> Result<()> {
+		assert_eq!("fail".parse::<TileErrorPolicy>()?, TileErrorPolicy::Fail);
+		assert_eq!("Skip".parse::<TileErrorPolicy>()?, TileErrorPolicy::Skip);
+		assert_eq!("report".parse::<TileErrorPolicy>()?, TileErrorPolicy::Report);
+		assert!("ignore".parse::<TileErrorPolicy>().is_err());
+		assert_eq!(TileErrorPolicy::Report.to_string(), "report");
+		Ok(())
+	}
+
+	#[test]
+	fn no_errors() {
+		let handler = TileErrorHandler::new(TileErrorPolicy::Fail);
+		assert!(!handler.should_stop());
+		assert!(handler.finish().is_ok());
+	}
+
+	#[test]
+	fn fail() {
+		let handler = TileErrorHandler::new(TileErrorPolicy::Fail);
+		handler.handle(anyhow!("first"));
+		handler.handle(anyhow!("second"));
+		assert!(handler.should_stop());
+		assert_eq!(
+			handler.finish().unwrap_err().to_string(),
+			"conversion stopped, because a tile failed: first"
+		);
+	}
+
+	#[test]
+	fn skip() {
+		let handler = TileErrorHandler::new(TileErrorPolicy::Skip);
+		handler.handle(anyhow!("first"));
+		handler.handle(anyhow!("second"));
+		assert!(!handler.should_stop());
+		assert_eq!(handler.get_error_count(), 2);
+		assert_eq!(handler.finish().unwrap_err().to_string(), "2 tiles failed and were skipped");
+	}
+
+	#[test]
+	fn report() {
+		let handler = TileErrorHandler::new(TileErrorPolicy::Report);
+		handler.handle(anyhow!("first"));
+		handler.handle(anyhow!("second"));
+		assert!(!handler.should_stop());
+		assert_eq!(
+			handler.finish().unwrap_err().to_string(),
+			"2 tiles failed and were skipped:\n  - first\n  - second"
+		);
+	}
+}
@@ -0,0 +1,166 @@
+//! Checks the sizes of tiles during a conversion.
+//!
+//! Tiles outside of a byte range are either dropped or reported. Additionally, a hard limit can be set:
+//! Tiles exceeding it are always dropped, and the conversion fails at the end, so that no oversized tile
+//! is silently published.
+
+use anyhow::{ensure, Result};
+use log::warn;
+use std::sync::atomic::{AtomicU64, Ordering};
+use versatiles_core::types::TileCoord3;
+
+/// Checks tile sizes and counts the tiles that are outside of the allowed range.
+#[derive(Debug, Default)]
+pub struct TileSizeFilter {
+	min_size: Option<u64>,
+	max_size: Option<u64>,
+	warn_only: bool,
+	hard_limit: Option<u64>,
+	outside_count: AtomicU64,
+	over_limit_count: AtomicU64,
+}
+
+impl TileSizeFilter {
+	/// Creates a new filter.
+	///
+	/// # Arguments
+	/// * `min_size` - Tiles smaller than this number of bytes are outside of the range.
+	/// * `max_size` - Tiles larger than this number of bytes are outside of the range.
+	/// * `warn_only` - Only warn about tiles outside of the range, instead of dropping them.
+	/// * `hard_limit` - Tiles larger than this number of bytes are dropped and let the conversion fail.
+	pub fn new(
+		min_size: Option<u64>,
+		max_size: Option<u64>,
+		warn_only: bool,
+		hard_limit: Option<u64>,
+	) -> Result<TileSizeFilter> {
+		if let (Some(min_size), Some(max_size)) = (min_size, max_size) {
+			ensure!(
+				min_size <= max_size,
+				"minimum tile size ({min_size}) must not be larger than maximum tile size ({max_size})"
+			);
+		}
+		Ok(TileSizeFilter {
+			min_size,
+			max_size,
+			warn_only,
+			hard_limit,
+			..Default::default()
+		})
+	}
+
+	/// Returns `true` if no limits are set.
+	pub fn is_empty(&self) -> bool {
+		self.min_size.is_none() && self.max_size.is_none() && self.hard_limit.is_none()
+	}
+
+	/// Checks the size of a tile.
+	///
+	/// # Returns
+	/// `true` if the tile should be kept.
+	pub fn check(&self, coord: &TileCoord3, size: u64) -> bool {
+		if self.hard_limit.is_some_and(|limit| size > limit) {
+			warn!("dropping tile {coord:?}, its size of {size} bytes exceeds the hard limit");
+			self.over_limit_count.fetch_add(1, Ordering::Relaxed);
+			return false;
+		}
+
+		let is_outside = self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max);
+		if !is_outside {
+			return true;
+		}
+
+		self.outside_count.fetch_add(1, Ordering::Relaxed);
+		if self.warn_only {
+			warn!("tile {coord:?} has a size of {size} bytes, which is outside of the allowed range");
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Returns the number of tiles that have been dropped so far.
+	pub fn get_dropped_count(&self) -> u64 {
+		let outside_count = if self.warn_only {
+			0
+		} else {
+			self.outside_count.load(Ordering::Relaxed)
+		};
+		outside_count + self.over_limit_count.load(Ordering::Relaxed)
+	}
+
+	/// Reports the results after the conversion.
+	///
+	/// # Errors
+	/// Returns an error if any tile exceeded the hard limit.
+	pub fn finish(&self) -> Result<()> {
+		let outside_count = self.outside_count.load(Ordering::Relaxed);
+		if outside_count > 0 {
+			if self.warn_only {
+				warn!("{outside_count} tiles are outside of the allowed size range");
+			} else {
+				warn!("dropped {outside_count} tiles outside of the allowed size range");
+			}
+		}
+
+		let over_limit_count = self.over_limit_count.load(Ordering::Relaxed);
+		ensure!(
+			over_limit_count == 0,
+			"{over_limit_count} tiles exceeded the hard size limit of {} bytes and were dropped",
+			self.hard_limit.unwrap_or_default()
+		);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn coord() -> TileCoord3 {
+		TileCoord3::new(1, 2, 3).unwrap()
+	}
+
+	#[test]
+	fn drop_outside() -> Result<()> {
+		let filter = TileSizeFilter::new(Some(10), Some(100), false, None)?;
+		assert!(!filter.check(&coord(), 9));
+		assert!(filter.check(&coord(), 10));
+		assert!(filter.check(&coord(), 100));
+		assert!(!filter.check(&coord(), 101));
+		assert!(filter.finish().is_ok());
+		assert_eq!(filter.outside_count.load(Ordering::Relaxed), 2);
+		assert_eq!(filter.get_dropped_count(), 2);
+		Ok(())
+	}
+
+	#[test]
+	fn warn_only() -> Result<()> {
+		let filter = TileSizeFilter::new(None, Some(100), true, None)?;
+		assert!(filter.check(&coord(), 101));
+		assert!(filter.finish().is_ok());
+		assert_eq!(filter.outside_count.load(Ordering::Relaxed), 1);
+		assert_eq!(filter.get_dropped_count(), 0);
+		Ok(())
+	}
+
+	#[test]
+	fn hard_limit() -> Result<()> {
+		let filter = TileSizeFilter::new(None, None, true, Some(500))?;
+		assert!(!filter.is_empty());
+		assert!(filter.check(&coord(), 500));
+		assert!(!filter.check(&coord(), 501));
+		assert_eq!(filter.get_dropped_count(), 1);
+		assert_eq!(
+			filter.finish().unwrap_err().to_string(),
+			"1 tiles exceeded the hard size limit of 500 bytes and were dropped"
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_range() {
+		assert!(TileSizeFilter::new(Some(100), Some(10), false, None).is_err());
+		assert!(TileSizeFilter::new(None, None, false, None).unwrap().is_empty());
+	}
+}
@@ -0,0 +1,47 @@
+//! A hook to run custom code on every tile of a conversion.
+//!
+//! Implement [`TileTransform`] and attach it with [`TilesConverterParameters::tile_transform`](super::TilesConverterParameters)
+//! to filter or modify tiles, e.g. by calling an external service, without writing a pipeline operation.
+//!
+//! # Example
+//!
+//! ```rust
+//! use anyhow::Result;
+//! use async_trait::async_trait;
+//! use std::sync::Arc;
+//! use versatiles_container::{TileTransform, TilesConverterParameters};
+//! use versatiles_core::types::{Blob, TileCoord3};
+//!
+//! /// Drops all tiles of the western hemisphere.
+//! #[derive(Debug)]
+//! struct EastOnly;
+//!
+//! #[async_trait]
+//! impl TileTransform for EastOnly {
+//!     async fn transform(&self, coord: TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+//!         Ok((coord.x >= (1 << coord.z) / 2).then_some(blob))
+//!     }
+//! }
+//!
+//! let mut parameters = TilesConverterParameters::new_default();
+//! parameters.tile_transform = Some(Arc::new(EastOnly));
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::fmt::Debug;
+use versatiles_core::types::{Blob, TileCoord3};
+
+/// Custom per-tile logic of a conversion.
+///
+/// The transform is called for every tile after it has been recompressed, so the blob is in the output
+/// compression, and the coordinates are those of the output, i.e. after flipping and swapping.
+/// Tiles are transformed in parallel and possibly out of order.
+#[async_trait]
+pub trait TileTransform: Debug + Send + Sync {
+	/// Returns the new content of a tile, or `None` to drop it.
+	///
+	/// An error is handled like a tile that can't be read, according to the
+	/// [`TileErrorPolicy`](super::TileErrorPolicy).
+	async fn transform(&self, coord: TileCoord3, blob: Blob) -> Result<Option<Blob>>;
+}
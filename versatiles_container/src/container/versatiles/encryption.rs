@@ -0,0 +1,232 @@
+//! AES-256-GCM encryption of the tiles of `*.versatiles` containers.
+//!
+//! Every tile is encrypted on its own with a random nonce, stored as `nonce (12 bytes) | ciphertext | tag (16 bytes)`.
+//! The coordinates of the tile are authenticated as additional data, so a tile can not be decrypted at other
+//! coordinates, e.g. after its index entry was swapped with that of another tile.
+//! The header, the meta data and the indexes stay unencrypted, so an encrypted container can still be probed.
+//!
+//! The meta data of an encrypted container contains the field `"encryption":"aes-256-gcm"` and a short
+//! encrypted check value, so that a wrong key is detected when it is set, instead of failing on every tile.
+//! Both fields are removed from the TileJSON returned by the reader.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use versatiles_container::{get_reader, TileCipher, VersaTilesReader, VersaTilesWriter};
+//! use versatiles_core::types::{TileCoord3, TilesReaderTrait};
+//! use anyhow::Result;
+//! use std::path::Path;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let key = TileCipher::generate_key()?;
+//!     let path = Path::new("licensed.versatiles");
+//!
+//!     let mut reader = get_reader("../testdata/berlin.mbtiles").await?;
+//!     VersaTilesWriter::write_to_path_encrypted(&mut *reader, path, &TileCipher::new(&key)?).await?;
+//!
+//!     let mut reader = VersaTilesReader::open_path(path).await?;
+//!     reader.set_cipher(TileCipher::new(&key)?)?;
+//!     let tile = reader.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?;
+//!     Ok(())
+//! }
+//! ```
+
+use super::signature::{parse_hex, to_hex};
+use anyhow::{anyhow, ensure, Context, Result};
+use ring::{
+	aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+	rand::{SecureRandom, SystemRandom},
+};
+use std::fmt::Debug;
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::{Blob, TileCoord3},
+};
+
+/// Name of the encryption algorithm in the meta data.
+const ALGORITHM: &str = "aes-256-gcm";
+
+/// Plain text of the check value in the meta data.
+const CHECK_TEXT: &[u8] = b"versatiles";
+
+/// Length of a key in bytes.
+pub const KEY_LENGTH: usize = 32;
+
+/// Encrypts and decrypts tiles with AES-256-GCM.
+pub struct TileCipher {
+	key: LessSafeKey,
+	random: SystemRandom,
+}
+
+impl TileCipher {
+	/// Creates a cipher from a 256 bit key.
+	pub fn new(key: &[u8]) -> Result<TileCipher> {
+		ensure!(
+			key.len() == KEY_LENGTH,
+			"an AES-256 key has {KEY_LENGTH} bytes, not {}",
+			key.len()
+		);
+		let key = UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow!("invalid AES-256 key"))?;
+		Ok(TileCipher {
+			key: LessSafeKey::new(key),
+			random: SystemRandom::new(),
+		})
+	}
+
+	/// Creates a cipher from a key given as hex string, e.g. on the command line.
+	pub fn from_hex(text: &str) -> Result<TileCipher> {
+		TileCipher::new(&parse_hex(text.trim()).context("parsing encryption key")?)
+	}
+
+	/// Generates a new random key.
+	pub fn generate_key() -> Result<Vec<u8>> {
+		let mut key = vec![0; KEY_LENGTH];
+		SystemRandom::new()
+			.fill(&mut key)
+			.map_err(|_| anyhow!("generating key failed"))?;
+		Ok(key)
+	}
+
+	/// Encrypts the tile at `coord`.
+	pub fn encrypt(&self, coord: &TileCoord3, blob: &Blob) -> Result<Blob> {
+		self.seal(&get_aad(coord), blob)
+	}
+
+	/// Decrypts the tile at `coord`. Fails if the tile was encrypted for other coordinates.
+	pub fn decrypt(&self, coord: &TileCoord3, blob: &Blob) -> Result<Blob> {
+		self.open(&get_aad(coord), blob)
+	}
+
+	fn seal(&self, aad: &[u8], blob: &Blob) -> Result<Blob> {
+		let mut nonce = [0; NONCE_LEN];
+		self
+			.random
+			.fill(&mut nonce)
+			.map_err(|_| anyhow!("generating nonce failed"))?;
+
+		let mut data = blob.as_slice().to_vec();
+		self
+			.key
+			.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut data)
+			.map_err(|_| anyhow!("encrypting tile failed"))?;
+
+		let mut result = nonce.to_vec();
+		result.append(&mut data);
+		Ok(Blob::from(result))
+	}
+
+	fn open(&self, aad: &[u8], blob: &Blob) -> Result<Blob> {
+		let data = blob.as_slice();
+		ensure!(data.len() >= NONCE_LEN, "encrypted tile is too short");
+		let (nonce, data) = data.split_at(NONCE_LEN);
+
+		let mut data = data.to_vec();
+		let length = self
+			.key
+			.open_in_place(
+				Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("invalid nonce"))?,
+				Aad::from(aad),
+				&mut data,
+			)
+			.map_err(|_| anyhow!("decrypting tile failed, the key is wrong or the tile is damaged"))?
+			.len();
+		data.truncate(length);
+		Ok(Blob::from(data))
+	}
+
+	/// Marks the meta data as encrypted and adds the check value.
+	pub(super) fn add_to_tilejson(&self, tilejson: &mut TileJSON) -> Result<()> {
+		tilejson.set_string("encryption", ALGORITHM)?;
+		let check = self.seal(&[], &Blob::from(CHECK_TEXT))?;
+		tilejson.set_string("encryption_check", &to_hex(check.as_slice()))
+	}
+
+	/// Checks that this cipher uses the key of the check value in the meta data.
+	pub(super) fn check(&self, check: &str) -> Result<()> {
+		let check = self
+			.open(&[], &Blob::from(parse_hex(check)?))
+			.map_err(|_| anyhow!("wrong encryption key"))?;
+		ensure!(check.as_slice() == CHECK_TEXT, "wrong encryption key");
+		Ok(())
+	}
+}
+
+/// Returns the additional data of a tile: its zoom level, column and row as 9 bytes.
+fn get_aad(coord: &TileCoord3) -> [u8; 9] {
+	let mut aad = [0; 9];
+	aad[0] = coord.z;
+	aad[1..5].copy_from_slice(&coord.x.to_be_bytes());
+	aad[5..9].copy_from_slice(&coord.y.to_be_bytes());
+	aad
+}
+
+/// Removes the encryption fields from the meta data and returns the check value, if the container is encrypted.
+pub(super) fn take_encryption_check(tilejson: &mut TileJSON) -> Result<Option<String>> {
+	let Some(algorithm) = tilejson.get_string("encryption") else {
+		return Ok(None);
+	};
+	ensure!(algorithm == ALGORITHM, "unknown encryption algorithm '{algorithm}'");
+	let check = tilejson
+		.get_string("encryption_check")
+		.context("encrypted container has no encryption check")?;
+	tilejson.values.remove("encryption");
+	tilejson.values.remove("encryption_check");
+	Ok(Some(check))
+}
+
+impl Debug for TileCipher {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("TileCipher")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn encrypt_and_decrypt() -> Result<()> {
+		let cipher = TileCipher::new(&TileCipher::generate_key()?)?;
+		let blob = Blob::from("tile data");
+		let coord = TileCoord3::new(3, 4, 5)?;
+
+		let encrypted = cipher.encrypt(&coord, &blob)?;
+		assert_eq!(encrypted.len(), blob.len() + 28);
+		assert_ne!(cipher.encrypt(&coord, &blob)?, encrypted);
+		assert_eq!(cipher.decrypt(&coord, &encrypted)?, blob);
+
+		// the tile is bound to its coordinates
+		assert!(cipher.decrypt(&TileCoord3::new(4, 3, 5)?, &encrypted).is_err());
+		assert!(cipher.decrypt(&TileCoord3::new(3, 4, 6)?, &encrypted).is_err());
+
+		let mut damaged = encrypted.into_vec();
+		damaged[20] ^= 1;
+		assert!(cipher.decrypt(&coord, &Blob::from(damaged)).is_err());
+		assert!(cipher.decrypt(&coord, &Blob::from("short")).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn tilejson() -> Result<()> {
+		let key = TileCipher::generate_key()?;
+		let cipher = TileCipher::new(&key)?;
+		let mut tilejson = TileJSON::default();
+		cipher.add_to_tilejson(&mut tilejson)?;
+
+		let check = take_encryption_check(&mut tilejson)?.unwrap();
+		assert_eq!(tilejson, TileJSON::default());
+		assert_eq!(take_encryption_check(&mut tilejson)?, None);
+
+		TileCipher::from_hex(&to_hex(&key))?.check(&check)?;
+		let other = TileCipher::new(&TileCipher::generate_key()?)?;
+		assert_eq!(other.check(&check).unwrap_err().to_string(), "wrong encryption key");
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_key() {
+		assert!(TileCipher::new(&[0; 16]).is_err());
+		assert!(TileCipher::from_hex("00ff").is_err());
+	}
+}
@@ -0,0 +1,188 @@
+//! Options for the internal layout of `*.versatiles` containers written by `VersaTilesWriter`.
+//!
+//! The specification fixes blocks of 256×256 tiles and brotli compressed indexes, but leaves the order of the
+//! tiles inside a block and the compression level of the indexes to the writer:
+//!
+//! - Tiles in [`TileOrder::Hilbert`] order keep neighbouring tiles close together, so that reading a
+//!   region needs fewer and smaller HTTP range requests.
+//! - Indexes with [`IndexCompression::Best`] are smaller to download, but take longer to write.
+
+use anyhow::{bail, Error, Result};
+use std::{fmt::Display, str::FromStr};
+use versatiles_core::{
+	types::Blob,
+	utils::{compress_brotli, compress_brotli_fast},
+};
+
+/// Order of the tiles inside a block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileOrder {
+	/// In the order the source delivers the tiles, which is usually row by row.
+	#[default]
+	Source,
+	/// Along a Hilbert curve, so that neighbouring tiles are stored close to each other.
+	Hilbert,
+}
+
+impl FromStr for TileOrder {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		Ok(match s.to_lowercase().as_str() {
+			"source" => TileOrder::Source,
+			"hilbert" => TileOrder::Hilbert,
+			_ => bail!("unknown tile order '{s}', use 'source' or 'hilbert'"),
+		})
+	}
+}
+
+impl Display for TileOrder {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			TileOrder::Source => "source",
+			TileOrder::Hilbert => "hilbert",
+		})
+	}
+}
+
+/// Compression level of the block index and the tile indexes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexCompression {
+	/// Fast brotli compression.
+	#[default]
+	Fast,
+	/// Best brotli compression, for the smallest indexes.
+	Best,
+}
+
+impl IndexCompression {
+	pub(super) fn compress(&self, blob: &Blob) -> Result<Blob> {
+		match self {
+			IndexCompression::Fast => compress_brotli_fast(blob),
+			IndexCompression::Best => compress_brotli(blob),
+		}
+	}
+}
+
+impl FromStr for IndexCompression {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		Ok(match s.to_lowercase().as_str() {
+			"fast" => IndexCompression::Fast,
+			"best" => IndexCompression::Best,
+			_ => bail!("unknown index compression '{s}', use 'fast' or 'best'"),
+		})
+	}
+}
+
+impl Display for IndexCompression {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			IndexCompression::Fast => "fast",
+			IndexCompression::Best => "best",
+		})
+	}
+}
+
+/// Options for the internal layout of a `*.versatiles` container.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VersaTilesWriterOptions {
+	pub tile_order: TileOrder,
+	pub index_compression: IndexCompression,
+}
+
+/// Returns the position of a tile along a Hilbert curve through a block of 256×256 tiles.
+/// Only the lowest 8 bits of `x` and `y` are used.
+pub(super) fn get_hilbert_index(x: u32, y: u32) -> u32 {
+	let (mut x, mut y) = (x & 0xFF, y & 0xFF);
+	let mut index = 0;
+	let mut size = 128;
+	while size > 0 {
+		let rx = u32::from(x & size > 0);
+		let ry = u32::from(y & size > 0);
+		index += size * size * ((3 * rx) ^ ry);
+		// rotate the quadrant, so that the curve continues in the right direction
+		if ry == 0 {
+			if rx == 1 {
+				x = 255 - x;
+				y = 255 - y;
+			}
+			std::mem::swap(&mut x, &mut y);
+		}
+		size /= 2;
+	}
+	index
+}
+
+/// Checks whether the tile data of a block, starting at `offset`, was written in the order of `ranges`:
+/// Every tile either directly follows the previous one, or is a duplicate that refers to an earlier tile.
+#[cfg(feature = "cli")]
+pub(super) fn is_written_in_order(
+	ranges: impl Iterator<Item = versatiles_core::types::ByteRange>,
+	mut offset: u64,
+) -> bool {
+	for range in ranges {
+		if range.offset == offset {
+			offset += range.length;
+		} else if range.offset > offset {
+			return false;
+		}
+	}
+	true
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hilbert_index() {
+		let mut seen = vec![false; 65536];
+		let mut coords = vec![(0, 0); 65536];
+		for y in 0..256 {
+			for x in 0..256 {
+				let index = get_hilbert_index(x, y) as usize;
+				assert!(!seen[index]);
+				seen[index] = true;
+				coords[index] = (x, y);
+			}
+		}
+		// every step of the curve moves to a neighbouring tile
+		for pair in coords.windows(2) {
+			let (a, b) = (pair[0], pair[1]);
+			assert_eq!(a.0.abs_diff(b.0) + a.1.abs_diff(b.1), 1);
+		}
+		// aligned squares are visited one after another
+		for (index, (x, y)) in coords.iter().enumerate() {
+			assert_eq!(get_hilbert_index(x & !15, y & !15) >> 8, index as u32 >> 8);
+		}
+		assert_eq!(get_hilbert_index(256, 257), get_hilbert_index(0, 1));
+	}
+
+	#[cfg(feature = "cli")]
+	#[test]
+	fn written_in_order() {
+		use versatiles_core::types::ByteRange;
+		let ranges = [
+			ByteRange::new(10, 5),
+			ByteRange::new(15, 3),
+			ByteRange::new(10, 5),
+			ByteRange::new(18, 1),
+		];
+		assert!(is_written_in_order(ranges.into_iter(), 10));
+		assert!(!is_written_in_order(ranges.into_iter().rev(), 10));
+		assert!(!is_written_in_order(ranges.into_iter(), 0));
+	}
+
+	#[test]
+	fn parse() -> Result<()> {
+		assert_eq!("Hilbert".parse::<TileOrder>()?, TileOrder::Hilbert);
+		assert_eq!("source".parse::<TileOrder>()?, TileOrder::Source);
+		assert!("zorder".parse::<TileOrder>().is_err());
+		assert_eq!("best".parse::<IndexCompression>()?, IndexCompression::Best);
+		assert_eq!(IndexCompression::Fast.to_string(), "fast");
+		assert!("gzip".parse::<IndexCompression>().is_err());
+		Ok(())
+	}
+}
@@ -44,5 +44,17 @@ mod types;
 mod reader;
 pub use reader::VersaTilesReader;
 
+mod layout;
+pub use layout::{IndexCompression, TileOrder, VersaTilesWriterOptions};
+
 mod writer;
 pub use writer::VersaTilesWriter;
+
+mod encryption;
+pub use encryption::TileCipher;
+
+mod patcher;
+pub use patcher::VersaTilesPatcher;
+
+mod signature;
+pub use signature::{format_public_key, generate_signing_key, get_public_key, parse_public_key, ContainerSignature};
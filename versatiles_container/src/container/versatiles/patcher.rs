@@ -0,0 +1,319 @@
+//! This module provides functionality for patching tiles of an existing `*.versatiles` container in place.
+//!
+//! New tiles are appended to the end of the file. The tile indexes of the modified blocks, the block index
+//! and the header are rewritten afterwards. Tiles that are no longer referenced remain in the file
+//! until the container is converted again.
+//!
+//! The header is only updated in [`VersaTilesPatcher::finish`]. If patching is interrupted, the
+//! container still contains the old tiles.
+//!
+//! # Example
+//!
+//! ```rust
+//! use versatiles_container::VersaTilesPatcher;
+//! use versatiles_core::types::{Blob, TileCoord3};
+//! use anyhow::Result;
+//!
+//! async fn patch() -> Result<()> {
+//!     let path = std::env::current_dir()?.join("../testdata/temp.versatiles");
+//!
+//!     let mut patcher = VersaTilesPatcher::open_path(&path).await?;
+//!
+//!     // Replace a tile. The blob must use the compression of the container.
+//!     patcher.set_tile(&TileCoord3::new(2200, 1345, 12)?, Some(Blob::from("new tile"))).await?;
+//!
+//!     // Remove a tile.
+//!     patcher.set_tile(&TileCoord3::new(2201, 1345, 12)?, None).await?;
+//!
+//!     patcher.finish()?;
+//!     Ok(())
+//! }
+//! ```
+
+use super::types::{BlockDefinition, BlockIndex, FileHeader, TileIndex};
+use anyhow::{anyhow, ensure, Context, Result};
+use log::trace;
+use std::{collections::HashMap, ops::Shr, path::Path};
+use versatiles_core::{
+	io::{DataReader, DataReaderFile, DataWriterFile, DataWriterTrait},
+	tilejson::TileJSON,
+	types::*,
+	utils::decompress,
+};
+
+/// A modified block, with the absolute byte ranges of its tiles.
+struct PatchedBlock {
+	bbox: TileBBox,
+	tiles_offset: u64,
+	tile_index: TileIndex,
+}
+
+/// A struct for replacing, adding and removing tiles of an existing VersaTiles container.
+pub struct VersaTilesPatcher {
+	reader: DataReader,
+	writer: DataWriterFile,
+	header: FileHeader,
+	block_index: BlockIndex,
+	blocks: HashMap<TileCoord3, PatchedBlock>,
+	bbox_changed: bool,
+	grid: TileGrid,
+}
+
+impl VersaTilesPatcher {
+	/// Opens a `versatiles` container for patching.
+	///
+	/// # Arguments
+	///
+	/// * `path` - The path to the `versatiles` file.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the file cannot be opened or is not a valid container.
+	pub async fn open_path(path: &Path) -> Result<VersaTilesPatcher> {
+		let mut reader: DataReader = DataReaderFile::open(path)?;
+
+		let header = FileHeader::from_reader(&mut reader)
+			.await
+			.context("Failed reading the header")?;
+
+		let grid = if header.meta_range.length > 0 {
+			let blob = reader
+				.read_range(&header.meta_range)
+				.await
+				.context("Failed reading the meta data")?;
+			let blob = decompress(blob, &header.compression).context("Failed decompressing the meta data")?;
+			let tilejson = TileJSON::try_from_blob_or_default(&blob);
+			ensure!(
+				tilejson.get_str("encryption").is_none(),
+				"encrypted containers can't be patched"
+			);
+			tilejson.get_tile_grid().context("Failed reading the tile grid")?
+		} else {
+			TileGrid::WebMercator
+		};
+
+		let block_index = BlockIndex::from_brotli_blob(
+			reader
+				.read_range(&header.blocks_range)
+				.await
+				.context("Failed reading the block index")?,
+			grid,
+		)
+		.context("Failed decompressing the block index")?;
+
+		let writer = DataWriterFile::open_existing(path)?;
+
+		Ok(VersaTilesPatcher {
+			reader,
+			writer,
+			header,
+			block_index,
+			blocks: HashMap::new(),
+			bbox_changed: false,
+			grid,
+		})
+	}
+
+	/// Returns the tile format of the container.
+	pub fn get_tile_format(&self) -> &TileFormat {
+		&self.header.tile_format
+	}
+
+	/// Returns the tile compression of the container.
+	pub fn get_tile_compression(&self) -> &TileCompression {
+		&self.header.compression
+	}
+
+	/// Replaces or adds a tile, or removes it if `blob` is `None`.
+	///
+	/// The blob must use the tile format and compression of the container.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the tile index of the block cannot be read or the tile cannot be written.
+	pub async fn set_tile(&mut self, coord: &TileCoord3, blob: Option<Blob>) -> Result<()> {
+		let block_coord = TileCoord3::new(coord.x.shr(8), coord.y.shr(8), coord.z)?;
+
+		if !self.blocks.contains_key(&block_coord) {
+			let block = match self.block_index.get_block(&block_coord) {
+				Some(block) => self.load_block(block).await?,
+				None => {
+					if blob.is_none() {
+						// nothing to remove
+						return Ok(());
+					}
+					PatchedBlock {
+						bbox: TileBBox::new_in_grid(self.grid, coord.z, coord.x, coord.y, coord.x, coord.y)?,
+						tiles_offset: self.writer.get_position()?,
+						tile_index: TileIndex::new_empty(1),
+					}
+				}
+			};
+			self.blocks.insert(block_coord, block);
+		}
+
+		let block = self.blocks.get_mut(&block_coord).unwrap();
+
+		if !block.bbox.contains3(coord) {
+			if blob.is_none() {
+				// nothing to remove
+				return Ok(());
+			}
+			block.extend(coord)?;
+			self.bbox_changed = true;
+		}
+
+		let range = match blob {
+			Some(blob) => self.writer.append(&blob)?,
+			None => ByteRange::empty(),
+		};
+		trace!("set tile {coord:?} to {range:?}");
+
+		let index = block.bbox.get_tile_index3(coord)?;
+		block.tile_index.set(index, range);
+
+		Ok(())
+	}
+
+	/// Writes the tile indexes of all modified blocks, the block index and the header.
+	///
+	/// # Errors
+	///
+	/// Returns an error if writing fails.
+	pub fn finish(mut self) -> Result<()> {
+		for (_coord, block) in self.blocks.iter_mut() {
+			let position = self.writer.get_position()?;
+
+			// Tile ranges are stored relative to the tiles of the block
+			for index in 0..block.tile_index.len() {
+				let mut range = *block.tile_index.get(index);
+				if range.length == 0 {
+					range = ByteRange::empty();
+				} else {
+					range.shift_backward(block.tiles_offset);
+				}
+				block.tile_index.set(index, range);
+			}
+
+			let mut definition = BlockDefinition::new(&block.bbox);
+			definition.set_tiles_range(ByteRange::new(block.tiles_offset, position - block.tiles_offset));
+			definition.set_index_range(self.writer.append(&block.tile_index.as_brotli_blob()?)?);
+			self.block_index.add_block(definition);
+		}
+
+		self.header.blocks_range = self.writer.append(&self.block_index.as_brotli_blob()?)?;
+
+		if self.bbox_changed {
+			let pyramid = self.block_index.get_bbox_pyramid();
+			let mut header = FileHeader::new(
+				&self.header.tile_format,
+				&self.header.compression,
+				[
+					pyramid.get_zoom_min().ok_or(anyhow!("invalid minzoom"))?,
+					pyramid.get_zoom_max().ok_or(anyhow!("invalid maxzoom"))?,
+				],
+				&pyramid.get_geo_bbox().ok_or(anyhow!("invalid geo bounding box"))?,
+			)?;
+			header.meta_range = self.header.meta_range;
+			header.blocks_range = self.header.blocks_range;
+			self.header = header;
+		}
+
+		trace!("update header");
+		self.writer.write_start(&self.header.to_blob()?)?;
+
+		Ok(())
+	}
+
+	/// Reads the tile index of an existing block.
+	async fn load_block(&self, block: &BlockDefinition) -> Result<PatchedBlock> {
+		let blob = self.reader.read_range(block.get_index_range()).await?;
+		let mut tile_index = TileIndex::from_brotli_blob(blob)?;
+		tile_index.add_offset(block.get_tiles_range().offset);
+
+		Ok(PatchedBlock {
+			bbox: block.get_global_bbox().clone(),
+			tiles_offset: block.get_tiles_range().offset,
+			tile_index,
+		})
+	}
+}
+
+impl PatchedBlock {
+	/// Extends the bounding box of the block to include `coord` and rearranges the tile index.
+	fn extend(&mut self, coord: &TileCoord3) -> Result<()> {
+		let mut bbox = self.bbox.clone();
+		bbox.include_coord3(coord)?;
+
+		let mut tile_index = TileIndex::new_empty(bbox.count_tiles() as usize);
+		for (index, coord) in self.bbox.iter_coords().enumerate() {
+			tile_index.set(bbox.get_tile_index3(&coord)?, *self.tile_index.get(index));
+		}
+
+		self.bbox = bbox;
+		self.tile_index = tile_index;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{make_test_file, VersaTilesReader};
+	use versatiles_core::utils::decompress_gzip;
+
+	#[tokio::test]
+	async fn patch() -> Result<()> {
+		let temp_file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 4, "versatiles").await?;
+		let original = VersaTilesReader::open_path(&temp_file)
+			.await?
+			.get_tile_data(&TileCoord3::new(0, 0, 4)?)
+			.await?
+			.unwrap();
+
+		let mut patcher = VersaTilesPatcher::open_path(&temp_file).await?;
+		assert_eq!(patcher.get_tile_compression(), &TileCompression::Gzip);
+		patcher
+			.set_tile(&TileCoord3::new(1, 2, 3)?, Some(Blob::from("replaced")))
+			.await?;
+		patcher.set_tile(&TileCoord3::new(15, 1, 4)?, None).await?;
+		patcher
+			.set_tile(&TileCoord3::new(5, 6, 5)?, Some(Blob::from("added")))
+			.await?;
+		patcher
+			.set_tile(&TileCoord3::new(7, 3, 5)?, Some(Blob::from("extended")))
+			.await?;
+		patcher.set_tile(&TileCoord3::new(9, 9, 6)?, None).await?;
+		patcher.finish()?;
+
+		let reader = VersaTilesReader::open_path(&temp_file).await?;
+		let reader = &reader;
+		let get = move |x, y, z| {
+			let coord = TileCoord3::new(x, y, z).unwrap();
+			async move { reader.get_tile_data(&coord).await }
+		};
+
+		assert_eq!(get(1, 2, 3).await?.unwrap().as_str(), "replaced");
+		assert_eq!(get(15, 1, 4).await?, None);
+		assert_eq!(get(0, 0, 4).await?.unwrap(), original);
+		assert_eq!(get(5, 6, 5).await?.unwrap().as_str(), "added");
+		assert_eq!(get(7, 3, 5).await?.unwrap().as_str(), "extended");
+		assert_eq!(get(6, 4, 5).await?, None);
+		assert_eq!(get(9, 9, 6).await?, None);
+		assert!(decompress_gzip(&get(2, 2, 3).await?.unwrap()).is_ok());
+
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64), 4: [0,0,15,15] (256), 5: [5,3,7,6] (12)]"
+		);
+
+		let tiles = reader
+			.get_bbox_tile_stream(TileBBox::new(5, 0, 0, 31, 31)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 2);
+
+		Ok(())
+	}
+}
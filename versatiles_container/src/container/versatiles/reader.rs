@@ -45,21 +45,39 @@
 //! }
 //! ```
 
-use super::types::{BlockDefinition, BlockIndex, FileHeader, TileIndex};
-use anyhow::{Context, Result};
+use super::{
+	encryption::{take_encryption_check, TileCipher},
+	signature::sha256,
+	types::{BlockDefinition, BlockIndex, FileHeader, TileIndex},
+	ContainerSignature,
+};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use futures::{lock::Mutex, stream::StreamExt};
 use log::trace;
-use std::{fmt::Debug, ops::Shr, path::Path, sync::Arc};
+use std::{collections::HashMap, fmt::Debug, ops::Shr, path::Path, sync::Arc};
+use tracing::{info_span, Instrument};
 #[cfg(feature = "cli")]
 use versatiles_core::utils::PrettyPrint;
 use versatiles_core::{io::*, tilejson::TileJSON, types::*, utils::decompress};
 
+/// Number of blocks and chunks that are fetched concurrently, unless configured otherwise.
+const DEFAULT_CONCURRENCY: usize = 1;
+
 /// `VersaTilesReader` is responsible for reading tile data from a `versatiles` container.
 pub struct VersaTilesReader {
 	block_index: BlockIndex,
+	/// Decrypts the tiles of an encrypted container. Set by `set_cipher`.
+	cipher: Option<TileCipher>,
+	concurrency: usize,
+	/// Encrypted check value of an encrypted container, see `TileCipher`.
+	encryption_check: Option<String>,
 	header: FileHeader,
 	parameters: TilesReaderParameters,
+	/// Tile data of whole blocks, together with their offsets in the container. Filled by `preload`.
+	pinned_blocks: HashMap<TileCoord3, (u64, Blob)>,
+	/// Tile indexes of all blocks. Filled by `preload`.
+	pinned_tile_indexes: HashMap<TileCoord3, Arc<TileIndex>>,
 	reader: DataReader,
 	tile_index_cache: Mutex<LimitedCache<TileCoord3, Arc<TileIndex>>>,
 	tilejson: TileJSON,
@@ -79,6 +97,29 @@ impl VersaTilesReader {
 		VersaTilesReader::open_reader(DataReaderFile::open(path)?).await
 	}
 
+	/// Opens a `versatiles` container from a file path and verifies its index against its signature `<path>.sig`.
+	///
+	/// Only the header, the meta data and the block index are verified, so opening stays fast. The tiles themselves
+	/// are **not** verified: use [`ContainerSignature::verify`] with `check_tiles` to verify them as well.
+	///
+	/// # Arguments
+	///
+	/// * `path` - The path to the `versatiles` file.
+	/// * `public_key` - The trusted Ed25519 public key.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the file cannot be read, the signature is missing or invalid, or the header, the meta data
+	/// or the block index were modified after signing.
+	pub async fn open_path_with_verified_index(path: &Path, public_key: &[u8]) -> Result<VersaTilesReader> {
+		let reader = VersaTilesReader::open_path(path).await?;
+		ContainerSignature::read_file(&ContainerSignature::get_path(path))?
+			.verify(&reader, public_key, false)
+			.await
+			.with_context(|| format!("verifying the signature of {path:?}"))?;
+		Ok(reader)
+	}
+
 	/// Opens a `versatiles` container from a `DataReader`.
 	///
 	/// # Arguments
@@ -93,7 +134,7 @@ impl VersaTilesReader {
 			.await
 			.context("Failed reading the header")?;
 
-		let tilejson = if header.meta_range.length > 0 {
+		let mut tilejson = if header.meta_range.length > 0 {
 			let blob = reader
 				.read_range(&header.meta_range)
 				.await
@@ -104,11 +145,15 @@ impl VersaTilesReader {
 			TileJSON::default()
 		};
 
+		let grid = tilejson.get_tile_grid().context("Failed reading the tile grid")?;
+		let encryption_check = take_encryption_check(&mut tilejson).context("Failed reading the encryption")?;
+
 		let block_index = BlockIndex::from_brotli_blob(
 			reader
 				.read_range(&header.blocks_range)
 				.await
 				.context("Failed reading the block index")?,
+			grid,
 		)
 		.context("Failed decompressing the block index")?;
 
@@ -117,16 +162,63 @@ impl VersaTilesReader {
 
 		Ok(VersaTilesReader {
 			block_index,
+			cipher: None,
+			concurrency: DEFAULT_CONCURRENCY,
+			encryption_check,
 			header,
 			parameters,
+			pinned_blocks: HashMap::new(),
+			pinned_tile_indexes: HashMap::new(),
 			reader,
 			tile_index_cache: Mutex::new(LimitedCache::with_maximum_size(100_000_000)),
 			tilejson,
 		})
 	}
 
+	/// Sets the number of blocks and chunks of tiles that are fetched and decompressed concurrently when streaming
+	/// tiles. Higher values speed up reading from high latency sources, e.g. remote containers, but hold more
+	/// chunks in memory.
+	///
+	/// # Arguments
+	///
+	/// * `concurrency` - The number of concurrent fetches, at least 1.
+	pub fn set_concurrency(&mut self, concurrency: usize) {
+		self.concurrency = concurrency.max(1);
+	}
+
+	/// Returns whether the tiles of the container are encrypted.
+	pub fn is_encrypted(&self) -> bool {
+		self.encryption_check.is_some()
+	}
+
+	/// Sets the cipher that decrypts the tiles of an encrypted container.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the container is not encrypted or the key of the cipher is wrong.
+	pub fn set_cipher(&mut self, cipher: TileCipher) -> Result<()> {
+		let Some(check) = &self.encryption_check else {
+			bail!("container is not encrypted");
+		};
+		cipher.check(check)?;
+		self.cipher = Some(cipher);
+		Ok(())
+	}
+
+	/// Decrypts a tile, if the container is encrypted.
+	fn decrypt(&self, coord: &TileCoord3, blob: Blob) -> Result<Blob> {
+		match (&self.encryption_check, &self.cipher) {
+			(None, _) => Ok(blob),
+			(Some(_), Some(cipher)) => cipher.decrypt(coord, &blob),
+			(Some(_), None) => bail!("container is encrypted, set a cipher to read its tiles"),
+		}
+	}
+
 	/// Retrieves the tile index for a given block.
 	///
+	/// Tile indexes are cached, so that adjacent queries don't have to fetch them again.
+	/// The cache is not locked while fetching, so that multiple tile indexes can be fetched concurrently.
+	///
 	/// # Arguments
 	///
 	/// * `block` - A `BlockDefinition` instance.
@@ -137,19 +229,108 @@ impl VersaTilesReader {
 	async fn get_block_tile_index(&self, block: &BlockDefinition) -> Result<Arc<TileIndex>> {
 		let block_coord = block.get_coord3();
 
-		let mut cache = self.tile_index_cache.lock().await;
+		if let Some(value) = self.pinned_tile_indexes.get(block_coord) {
+			return Ok(value.clone());
+		}
+		if let Some(value) = self.tile_index_cache.lock().await.get(block_coord) {
+			return Ok(value);
+		}
 
-		Ok(if let Some(value) = cache.get(block_coord) {
-			value
-		} else {
-			let blob = self.reader.read_range(block.get_index_range()).await?;
-			let mut tile_index = TileIndex::from_brotli_blob(blob)?;
-			tile_index.add_offset(block.get_tiles_range().offset);
+		let blob = self.reader.read_range(block.get_index_range()).await?;
+		let mut tile_index = TileIndex::from_brotli_blob(blob)?;
+		tile_index.add_offset(block.get_tiles_range().offset);
 
-			assert_eq!(tile_index.len(), block.count_tiles() as usize);
+		assert_eq!(tile_index.len(), block.count_tiles() as usize);
 
-			cache.add(*block_coord, Arc::new(tile_index))
-		})
+		Ok(self
+			.tile_index_cache
+			.lock()
+			.await
+			.add(*block_coord, Arc::new(tile_index)))
+	}
+
+	/// Returns the tile data from a pinned block, if the tile is in one.
+	fn get_pinned_tile(&self, coord: &TileCoord3, tile_range: &ByteRange) -> Result<Option<Blob>> {
+		let block_coord = TileCoord3::new(coord.x.shr(8), coord.y.shr(8), coord.z)?;
+		let Some((offset, blob)) = self.pinned_blocks.get(&block_coord) else {
+			return Ok(None);
+		};
+		let Some(start) = tile_range.offset.checked_sub(*offset) else {
+			return Ok(None);
+		};
+		let end = start + tile_range.length;
+		Ok((end <= blob.len()).then(|| Blob::from(blob.get_range(start as usize..end as usize))))
+	}
+
+	/// Returns the number of bytes of the pinned tile indexes and of the pinned tile data.
+	pub fn get_pinned_size(&self) -> (u64, u64) {
+		let index_size = self
+			.pinned_tile_indexes
+			.values()
+			.map(|tile_index| (tile_index.len() * std::mem::size_of::<ByteRange>()) as u64)
+			.sum();
+		let tiles_size = self.pinned_blocks.values().map(|(_, blob)| blob.len()).sum();
+		(index_size, tiles_size)
+	}
+
+	/// Returns the bounding box of the tiles of every block, together with the size of its tile data in bytes.
+	/// The blocks are sorted by zoom level, row and column.
+	pub fn get_blocks(&self) -> Vec<(TileBBox, u64)> {
+		let mut blocks: Vec<&BlockDefinition> = self.block_index.iter().collect();
+		blocks.sort_by_key(|block| block.get_sort_index());
+		blocks
+			.into_iter()
+			.map(|block| (block.get_global_bbox().clone(), block.get_tiles_range().length))
+			.collect()
+	}
+
+	/// Looks up the byte range of a tile in the tile index of its block.
+	async fn get_tile_range(&self, coord: &TileCoord3) -> Result<Option<ByteRange>> {
+		// Calculate block coordinate
+		let block_coord = TileCoord3::new(coord.x.shr(8), coord.y.shr(8), coord.z)?;
+
+		// Get the block using the block coordinate
+		let block = self.block_index.get_block(&block_coord);
+
+		if block.is_none() {
+			return Ok(None);
+		}
+		let block = block.unwrap().clone();
+
+		// Get the block and its bounding box
+		let bbox = block.get_global_bbox();
+
+		// Calculate tile coordinates within the block
+		let tile_coord: TileCoord2 = coord.as_coord2();
+
+		// Check if the tile is within the block definition
+		if !bbox.contains2(&tile_coord) {
+			trace!("tile {coord:?} outside block definition");
+			return Ok(None);
+		}
+
+		// Get the tile ID
+		let tile_id = bbox.get_tile_index2(&tile_coord).unwrap();
+
+		// Retrieve the tile index from cache or read from the reader
+		let tile_index: Arc<TileIndex> = self
+			.get_block_tile_index(&block)
+			.instrument(info_span!("index lookup"))
+			.await?;
+		let tile_range: ByteRange = *tile_index.get(tile_id);
+
+		Ok((tile_range.length > 0).then_some(tile_range))
+	}
+
+	/// Returns the SHA-256 hash of the header, the meta data and the block index, as signed by `ContainerSignature`.
+	pub(super) async fn get_index_hash(&self) -> Result<Vec<u8>> {
+		let mut data = self.header.to_blob()?.into_vec();
+		for range in [&self.header.meta_range, &self.header.blocks_range] {
+			if range.length > 0 {
+				data.extend_from_slice(self.reader.read_range(range).await?.as_slice());
+			}
+		}
+		Ok(sha256(&data))
 	}
 
 	/// Retrieves the size of the index.
@@ -187,49 +368,115 @@ impl TilesReaderTrait for VersaTilesReader {
 		self.parameters.tile_compression = tile_compression;
 	}
 
-	/// Gets tile data for a given coordinate.
-	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
-		// Calculate block coordinate
-		let block_coord = TileCoord3::new(coord.x.shr(8), coord.y.shr(8), coord.z)?;
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		self.parameters.tile_format = tile_format;
+		Ok(())
+	}
 
-		// Get the block using the block coordinate
-		let block = self.block_index.get_block(&block_coord);
+	/// Loads the tile indexes of all blocks into memory and keeps them there. Additionally the tile data of up to
+	/// `pinned_blocks` blocks is kept in memory. Blocks of lower zoom levels are pinned first, since their tiles are
+	/// requested most often.
+	async fn preload(&mut self, pinned_blocks: usize) -> Result<()> {
+		let mut blocks: Vec<BlockDefinition> = self.block_index.iter().cloned().collect();
+		blocks.sort_by_key(|block| block.get_sort_index());
+
+		let mut tile_indexes = HashMap::new();
+		for block in blocks.iter() {
+			let tile_index = self
+				.get_block_tile_index(block)
+				.await
+				.with_context(|| format!("preloading the tile index of block {:?}", block.get_coord3()))?;
+			tile_indexes.insert(*block.get_coord3(), tile_index);
+		}
+		self.pinned_tile_indexes = tile_indexes;
 
-		if block.is_none() {
-			return Ok(None);
+		for block in blocks.iter().take(pinned_blocks) {
+			let range = block.get_tiles_range();
+			let blob = self
+				.reader
+				.read_range(range)
+				.await
+				.with_context(|| format!("preloading the tiles of block {:?}", block.get_coord3()))?;
+			self.pinned_blocks.insert(*block.get_coord3(), (range.offset, blob));
 		}
-		let block = block.unwrap().clone();
 
-		// Get the block and its bounding box
-		let bbox = block.get_global_bbox();
+		Ok(())
+	}
 
-		// Calculate tile coordinates within the block
-		let tile_coord: TileCoord2 = coord.as_coord2();
+	/// Loads the tile indexes of the blocks that cover the bounding box into the cache, and passes the byte
+	/// range of their tiles within the bounding box on to the data reader, so that remote containers can fetch
+	/// the tiles ahead of time.
+	async fn prefetch(&self, bbox: &TileBBox) -> Result<()> {
+		let mut block_coords: TileBBox = bbox.clone();
+		block_coords.scale_down(256);
 
-		// Check if the tile is within the block definition
-		if !bbox.contains2(&tile_coord) {
-			trace!("tile {coord:?} outside block definition");
-			return Ok(None);
-		}
+		for block_coord in block_coords.iter_coords() {
+			if self.pinned_blocks.contains_key(&block_coord) {
+				continue;
+			}
+			let Some(block) = self.block_index.get_block(&block_coord) else {
+				continue;
+			};
+			let block: BlockDefinition = block.to_owned();
 
-		// Get the tile ID
-		let tile_id = bbox.get_tile_index2(&tile_coord).unwrap();
+			let tiles_bbox_block = block.get_global_bbox();
+			let mut tiles_bbox_used: TileBBox = bbox.clone();
+			tiles_bbox_used.intersect_bbox(tiles_bbox_block)?;
 
-		// Retrieve the tile index from cache or read from the reader
-		let tile_index: Arc<TileIndex> = self.get_block_tile_index(&block).await?;
-		let tile_range: ByteRange = *tile_index.get(tile_id);
+			let tile_index: Arc<TileIndex> = self
+				.get_block_tile_index(&block)
+				.await
+				.with_context(|| format!("prefetching the tile index of block {block_coord:?}"))?;
+
+			let (start, end) = tile_index
+				.iter()
+				.enumerate()
+				.filter(|(index, range)| {
+					range.length > 0
+						&& tiles_bbox_used.contains3(&tiles_bbox_block.get_coord3_by_index(*index as u32).unwrap())
+				})
+				.fold((u64::MAX, 0), |(start, end), (_, range)| {
+					(start.min(range.offset), end.max(range.offset + range.length))
+				});
+
+			if start < end {
+				self
+					.reader
+					.prefetch_range(&ByteRange::new(start, end - start))
+					.await
+					.with_context(|| format!("prefetching the tiles of block {block_coord:?}"))?;
+			}
+		}
+
+		Ok(())
+	}
 
-		//  None if the tile range has zero length
-		if tile_range.length == 0 {
+	/// Gets tile data for a given coordinate.
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		let Some(tile_range) = self.get_tile_range(coord).await? else {
 			return Ok(None);
+		};
+		if let Some(blob) = self.get_pinned_tile(coord, &tile_range)? {
+			return Ok(Some(self.decrypt(coord, blob)?));
 		}
 
 		// Read the tile data from the reader
-		Ok(Some(self.reader.read_range(&tile_range).await?))
+		let span = info_span!("range fetch", offset = tile_range.offset, length = tile_range.length);
+		let blob = self.reader.read_range(&tile_range).instrument(span).await?;
+		Ok(Some(self.decrypt(coord, blob)?))
+	}
+
+	/// Checks whether a tile exists, using only the tile index.
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		Ok(self.get_tile_range(coord).await?.is_some())
 	}
 
 	/// Gets a stream of tile data for a given bounding box.
-	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+	///
+	/// Tiles are read in chunks of neighbouring tiles. Multiple tile indexes and chunks are fetched concurrently
+	/// (see `set_concurrency`), while the order of the tiles is kept. If a tile index or a chunk can't be read,
+	/// the stream contains one error for all of its tiles.
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
 		const MAX_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
 		const MAX_CHUNK_GAP: u64 = 32 * 1024;
 
@@ -265,17 +512,14 @@ impl TilesReaderTrait for VersaTilesReader {
 		block_coords.scale_down(256);
 		let block_coords: Vec<TileCoord3> = block_coords.iter_coords().collect();
 
-		let stream = futures::stream::iter(block_coords).then(|block_coord: TileCoord3| {
+		let stream = futures::stream::iter(block_coords).map(|block_coord: TileCoord3| {
 			let bbox = bbox.clone();
 			async move {
 				// Get the block using the block coordinate
-				let block_option = self.block_index.get_block(&block_coord);
-				if block_option.is_none() {
-					panic!("block <{block_coord:#?}> does not exist");
-				}
-
-				// Get the block
-				let block: BlockDefinition = block_option.unwrap().to_owned();
+				let Some(block) = self.block_index.get_block(&block_coord) else {
+					bail!("block <{block_coord:#?}> does not exist");
+				};
+				let block: BlockDefinition = block.to_owned();
 				trace!("block {block:?}");
 
 				// Get the bounding box of all tiles defined in this block
@@ -291,7 +535,10 @@ impl TilesReaderTrait for VersaTilesReader {
 				assert_eq!(bbox.level, tiles_bbox_used.level);
 
 				// Get the tile index of this block
-				let tile_index: Arc<TileIndex> = self.get_block_tile_index(&block).await.unwrap();
+				let tile_index: Arc<TileIndex> = self
+					.get_block_tile_index(&block)
+					.await
+					.with_context(|| format!("reading the tile index of block {block_coord:?}"))?;
 				trace!("tile_index {tile_index:?}");
 
 				// let tile_range: &ByteRange = tile_index.get(tile_id);
@@ -303,7 +550,7 @@ impl TilesReaderTrait for VersaTilesReader {
 					.collect();
 
 				if tile_ranges.is_empty() {
-					return Vec::new();
+					return Ok(Vec::new());
 				}
 
 				tile_ranges.sort_by_key(|e| e.1.offset);
@@ -333,22 +580,38 @@ impl TilesReaderTrait for VersaTilesReader {
 					chunks.push(chunk);
 				}
 
-				chunks
+				Ok(chunks)
 			}
 		});
 
-		let chunks: Vec<Vec<Chunk>> = stream.collect().await;
+		let chunks: Vec<Result<Vec<Chunk>>> = stream.buffered(self.concurrency).collect().await;
 
-		let chunks: Vec<Chunk> = chunks.into_iter().flatten().collect();
+		let chunks: Vec<Result<Chunk>> = chunks
+			.into_iter()
+			.flat_map(|result| match result {
+				Ok(chunks) => chunks.into_iter().map(Ok).collect(),
+				Err(err) => vec![Err(err)],
+			})
+			.collect();
 
-		TileStream::from_stream(
+		TryTileStream::from_stream(
 			futures::stream::iter(chunks)
-				.then(move |chunk| {
+				.map(move |chunk| {
 					let bbox = bbox.clone();
 					async move {
-						let big_blob = self.reader.read_range(&chunk.range).await.unwrap();
-
-						let entries: Vec<(TileCoord3, Blob)> = chunk
+						let chunk = match chunk {
+							Ok(chunk) => chunk,
+							Err(err) => return futures::stream::iter(vec![Err(err)]),
+						};
+						let big_blob = match self.reader.read_range(&chunk.range).await {
+							Ok(big_blob) => big_blob,
+							Err(err) => {
+								let err = err.context(format!("reading {} tiles at {:?}", chunk.len(), chunk.range));
+								return futures::stream::iter(vec![Err(err)]);
+							}
+						};
+
+						let entries: Vec<Result<(TileCoord3, Blob)>> = chunk
 							.tiles
 							.into_iter()
 							.map(|(coord, range)| {
@@ -360,13 +623,17 @@ impl TilesReaderTrait for VersaTilesReader {
 
 								assert!(bbox.contains3(&coord), "outer_bbox {bbox:?} does not contain {coord:?}");
 
-								(coord, blob)
+								self
+									.decrypt(&coord, blob)
+									.with_context(|| format!("decrypting tile {coord:?}"))
+									.map(|blob| (coord, blob))
 							})
 							.collect();
 
 						futures::stream::iter(entries)
 					}
 				})
+				.buffered(self.concurrency)
 				.flatten()
 				.boxed(),
 		)
@@ -382,6 +649,18 @@ impl TilesReaderTrait for VersaTilesReader {
 	async fn probe_container(&mut self, print: &PrettyPrint) -> Result<()> {
 		print.add_key_value("meta size", &self.header.meta_range.length).await;
 		print.add_key_value("block count", &self.block_index.len()).await;
+		print
+			.add_key_value("block index size", &self.header.blocks_range.length)
+			.await;
+		if self.block_index.len() > 0 {
+			let tile_count: u64 = self.block_index.iter().map(|block| block.count_tiles()).sum();
+			print
+				.add_key_value("average tiles per block", &(tile_count / self.block_index.len() as u64))
+				.await;
+		}
+		if self.is_encrypted() {
+			print.add_key_value("tile encryption", "AES-256-GCM").await;
+		}
 
 		print
 			.add_key_value("sum of block index sizes", &self.get_index_size())
@@ -390,74 +669,74 @@ impl TilesReaderTrait for VersaTilesReader {
 			.add_key_value("sum of block tiles sizes", &self.get_tiles_size())
 			.await;
 
+		if !self.pinned_tile_indexes.is_empty() {
+			let (index_size, tiles_size) = self.get_pinned_size();
+			print.add_key_value("resident tile index size", &index_size).await;
+			print
+				.add_key_value(
+					"resident tiles size",
+					&format!("{tiles_size} ({} blocks)", self.pinned_blocks.len()),
+				)
+				.await;
+		}
+
 		Ok(())
 	}
 
 	// deep probe of container tiles
 	#[cfg(feature = "cli")]
 	async fn probe_tiles(&mut self, print: &PrettyPrint) -> Result<()> {
+		use super::layout::{get_hilbert_index, is_written_in_order};
 		use versatiles_core::progress::get_progress_bar;
 
-		#[derive(Debug)]
-		#[allow(dead_code)]
-		struct Entry {
-			size: u64,
-			x: u32,
-			y: u32,
-			z: u8,
-		}
-
-		let mut biggest_tiles: Vec<Entry> = Vec::new();
-		let mut min_size: u64 = 0;
-		let mut size_sum: u64 = 0;
-		let mut tile_count: u64 = 0;
+		let mut stats = crate::TileSizeStats::new();
+		// number of blocks whose tiles are stored in row order, in Hilbert order, or in any other order
+		let mut order_counts = [0u64; 3];
 
 		let block_index = self.block_index.clone();
 		let mut progress = get_progress_bar("scanning blocks", block_index.len() as u64);
 
 		for block in block_index.iter() {
+			let bbox = block.get_global_bbox();
 			let tile_index = self.get_block_tile_index(block).await?;
+			let mut tiles: Vec<(TileCoord3, ByteRange)> = Vec::new();
 			for (index, tile_range) in tile_index.iter().enumerate() {
-				let size = tile_range.length;
-
-				tile_count += 1;
-				size_sum += size;
-
-				if size < min_size {
+				if tile_range.length == 0 {
 					continue;
 				}
-
-				let bbox = block.get_global_bbox();
 				let coord = bbox.get_coord3_by_index(index as u32)?;
+				stats.add(&coord, tile_range.length);
+				tiles.push((coord, *tile_range));
+			}
 
-				biggest_tiles.push(Entry {
-					size,
-					x: coord.x,
-					y: coord.y,
-					z: coord.z,
-				});
-				biggest_tiles.sort_by(|a, b| b.size.cmp(&a.size));
-				while biggest_tiles.len() > 10 {
-					biggest_tiles.pop();
-				}
-				min_size = biggest_tiles.last().unwrap().size;
+			let offset = block.get_tiles_range().offset;
+			let by_row = is_written_in_order(tiles.iter().map(|(_, range)| *range), offset);
+			tiles.sort_by_key(|(coord, _)| get_hilbert_index(coord.x, coord.y));
+			let by_curve = is_written_in_order(tiles.iter().map(|(_, range)| *range), offset);
+			match (by_row, by_curve) {
+				// e.g. blocks with a single tile
+				(true, true) => {}
+				(true, false) => order_counts[0] += 1,
+				(false, true) => order_counts[1] += 1,
+				(false, false) => order_counts[2] += 1,
 			}
 			progress.inc(1);
 		}
 		progress.remove();
 
-		print
-			.add_key_value("average tile size", &size_sum.div_euclid(tile_count))
-			.await;
-
-		for (index, entry) in biggest_tiles.iter().enumerate() {
-			print
-				.add_key_value(&format!("#{} biggest tile", index + 1), entry)
-				.await;
-		}
+		stats.print(print).await;
+		print.add_key_value("blocks in row order", &order_counts[0]).await;
+		print.add_key_value("blocks in Hilbert order", &order_counts[1]).await;
+		print.add_key_value("blocks in other order", &order_counts[2]).await;
 
 		Ok(())
 	}
+
+	// deep probe of container tile contents
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		crate::probe_vector_tile_contents(&*self, print).await
+	}
 }
 
 // Implement Debug for TilesReader
@@ -503,6 +782,9 @@ mod tests {
 		let tile = reader.get_tile_data(&TileCoord3::new(15, 1, 4)?).await?.unwrap();
 		assert_eq!(decompress_gzip(&tile)?.as_slice(), MOCK_BYTES_PBF);
 
+		assert!(reader.has_tile(&TileCoord3::new(15, 1, 4)?).await?);
+		assert!(!reader.has_tile(&TileCoord3::new(0, 0, 5)?).await?);
+
 		Ok(())
 	}
 
@@ -531,6 +813,189 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn preload() -> Result<()> {
+		let temp_file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 4, "versatiles").await?;
+		let mut reader = VersaTilesReader::open_path(&temp_file).await?;
+		assert_eq!(reader.get_pinned_size(), (0, 0));
+
+		reader.preload(2).await?;
+		// 1 + 4 + 16 + 64 + 256 tile indexes, and the deduplicated tiles of levels 0 and 1
+		assert_eq!(reader.get_pinned_size(), (341 * 16, 2 * 77));
+		assert_eq!(reader.pinned_blocks.len(), 2);
+
+		for coord in [TileCoord3::new(1, 0, 1)?, TileCoord3::new(15, 1, 4)?] {
+			let tile = reader.get_tile_data(&coord).await?.unwrap();
+			assert_eq!(decompress_gzip(&tile)?.as_slice(), MOCK_BYTES_PBF);
+		}
+
+		Ok(())
+	}
+
+	/// Reads from a file and records the ranges that are prefetched.
+	#[derive(Debug)]
+	struct PrefetchRecorder {
+		inner: DataReader,
+		prefetched: Arc<std::sync::Mutex<Vec<ByteRange>>>,
+	}
+
+	#[async_trait]
+	impl DataReaderTrait for PrefetchRecorder {
+		async fn read_range(&self, range: &ByteRange) -> Result<Blob> {
+			self.inner.read_range(range).await
+		}
+		async fn read_all(&self) -> Result<Blob> {
+			self.inner.read_all().await
+		}
+		async fn prefetch_range(&self, range: &ByteRange) -> Result<()> {
+			self.prefetched.lock().unwrap().push(*range);
+			Ok(())
+		}
+		fn get_name(&self) -> &str {
+			self.inner.get_name()
+		}
+	}
+
+	#[tokio::test]
+	async fn prefetch() -> Result<()> {
+		let temp_file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 4, "versatiles").await?;
+		let prefetched = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let reader = VersaTilesReader::open_reader(Box::new(PrefetchRecorder {
+			inner: DataReaderFile::open(&temp_file)?,
+			prefetched: prefetched.clone(),
+		}))
+		.await?;
+
+		let block_coord = TileCoord3::new(0, 0, 4)?;
+		assert!(reader.tile_index_cache.lock().await.get(&block_coord).is_none());
+
+		reader.prefetch(&TileBBox::new(4, 2, 3, 5, 6)?).await?;
+		assert!(reader.tile_index_cache.lock().await.get(&block_coord).is_some());
+
+		// the mock tiles are deduplicated, so the prefetched range covers a single tile
+		let prefetched = prefetched.lock().unwrap().clone();
+		assert_eq!(prefetched.len(), 1);
+		assert_eq!(prefetched[0].length, 77);
+
+		// levels and blocks without tiles are skipped
+		reader.prefetch(&TileBBox::new(6, 0, 0, 3, 3)?).await?;
+
+		let tile = reader.get_tile_data(&TileCoord3::new(3, 4, 4)?).await?.unwrap();
+		assert_eq!(decompress_gzip(&tile)?.as_slice(), MOCK_BYTES_PBF);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn blocks() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.include_bbox(&TileBBox::new(9, 254, 254, 257, 256)?);
+		let mut reader1 = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PBF,
+			TileCompression::Gzip,
+			bbox_pyramid,
+		))?;
+		let mut data_writer = DataWriterBlob::new()?;
+		VersaTilesWriter::write_to_writer(&mut reader1, &mut data_writer).await?;
+		let reader2 = VersaTilesReader::open_reader(Box::new(data_writer.to_reader())).await?;
+
+		let blocks = reader2.get_blocks();
+		assert_eq!(
+			blocks.iter().map(|(bbox, _)| format!("{bbox:?}")).collect::<Vec<_>>(),
+			[
+				"9: [254,254,255,255] (4)",
+				"9: [256,254,257,255] (4)",
+				"9: [254,256,255,256] (2)",
+				"9: [256,256,257,256] (2)"
+			]
+		);
+		assert!(blocks[0].1 > 0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn encrypted() -> Result<()> {
+		let mut reader1 = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PBF,
+			TileCompression::Gzip,
+			TileBBoxPyramid::new_full(3),
+		))?;
+		let dir = assert_fs::TempDir::new()?;
+		let path = dir.path().join("encrypted.versatiles");
+		let key = TileCipher::generate_key()?;
+		VersaTilesWriter::write_to_path_encrypted(&mut reader1, &path, &TileCipher::new(&key)?).await?;
+
+		let mut reader2 = VersaTilesReader::open_path(&path).await?;
+		assert!(reader2.is_encrypted());
+		assert_eq!(reader2.get_tilejson().get_string("encryption"), None);
+
+		let coord = TileCoord3::new(3, 1, 3)?;
+		assert!(reader2.get_tile_data(&coord).await.is_err());
+		assert!(reader2
+			.set_cipher(TileCipher::new(&TileCipher::generate_key()?)?)
+			.is_err());
+
+		reader2.set_cipher(TileCipher::new(&key)?)?;
+		let tile = reader2.get_tile_data(&coord).await?.unwrap();
+		assert_eq!(decompress_gzip(&tile)?.as_slice(), MOCK_BYTES_PBF);
+
+		let tiles = reader2
+			.get_bbox_tile_stream(TileBBox::new_full(3)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 64);
+		for (_, tile) in tiles {
+			assert_eq!(decompress_gzip(&tile)?.as_slice(), MOCK_BYTES_PBF);
+		}
+
+		let mut reader3 =
+			VersaTilesReader::open_path(&make_test_file(TileFormat::PBF, TileCompression::Gzip, 1, "versatiles").await?)
+				.await?;
+		assert!(!reader3.is_encrypted());
+		assert!(reader3.set_cipher(TileCipher::new(&key)?).is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn concurrent_stream() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.include_bbox(&TileBBox::new(9, 250, 250, 260, 260)?);
+		let mut reader1 = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PBF,
+			TileCompression::Gzip,
+			bbox_pyramid,
+		))?;
+
+		let mut data_writer = DataWriterBlob::new()?;
+		VersaTilesWriter::write_to_writer(&mut reader1, &mut data_writer).await?;
+
+		let mut reader2 = VersaTilesReader::open_reader(Box::new(data_writer.to_reader())).await?;
+		let bbox = TileBBox::new(9, 240, 240, 270, 270)?;
+		let sequential: Vec<TileCoord3> = reader2
+			.get_bbox_tile_stream(bbox.clone())
+			.await
+			.collect()
+			.await
+			.into_iter()
+			.map(|(coord, _)| coord)
+			.collect();
+		assert_eq!(sequential.len(), 121);
+
+		reader2.set_concurrency(4);
+		let concurrent: Vec<TileCoord3> = reader2
+			.get_bbox_tile_stream(bbox)
+			.await
+			.collect()
+			.await
+			.into_iter()
+			.map(|(coord, _)| coord)
+			.collect();
+		assert_eq!(concurrent, sequential);
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	#[cfg(feature = "cli")]
 	async fn probe() -> Result<()> {
@@ -540,17 +1005,61 @@ mod tests {
 
 		let mut printer = PrettyPrint::new();
 		reader.probe_container(&printer.get_category("container").await).await?;
-		assert_eq!(
+		assert_wildcard!(
 			printer.as_string().await,
-			"container:\n   meta size: 58\n   block count: 5\n   sum of block index sizes: 70\n   sum of block tiles sizes: 385\n"
+			"container:\n   meta size: 58\n   block count: 5\n   block index size: *\n   average tiles per block: 68\n   sum of block index sizes: 70\n   sum of block tiles sizes: 385\n"
 		);
 
 		let mut printer = PrettyPrint::new();
 		reader.probe_tiles(&printer.get_category("tiles").await).await?;
-		assert_eq!(
-			printer.as_string().await.get(0..73).unwrap(),
-			"tiles:\n   average tile size: 77\n   #1 biggest tile: Entry { size: 77, x: "
-		);
+		let text = printer.as_string().await;
+		assert!(text.starts_with(
+			"tiles:\n   average tile size: 77\n   level 0: Level { count: 1, min: 77, median: 77, p95: 77, max: 77 }\n"
+		));
+		assert!(text.contains("\n   #1 biggest tile: Entry { size: 77, x: "));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	#[cfg(feature = "cli")]
+	async fn writer_options() -> Result<()> {
+		use crate::{get_fixture_reader, FixtureParameters, IndexCompression, TileOrder, VersaTilesWriterOptions};
+		use assert_fs::NamedTempFile;
+
+		let parameters = FixtureParameters {
+			zoom_max: 5,
+			..Default::default()
+		};
+		let mut tiles = Vec::new();
+		for tile_order in [TileOrder::Source, TileOrder::Hilbert] {
+			let options = VersaTilesWriterOptions {
+				tile_order,
+				index_compression: IndexCompression::Best,
+			};
+			let temp_file = NamedTempFile::new("layout.versatiles")?;
+			let mut source = get_fixture_reader(&parameters).await?;
+			VersaTilesWriter::write_to_path_with_options(&mut *source, &temp_file, &options, None).await?;
+
+			let mut reader = VersaTilesReader::open_path(&temp_file).await?;
+			let bbox = reader.get_parameters().bbox_pyramid.get_level_bbox(5).clone();
+			let mut level_tiles = reader.get_bbox_tile_stream(bbox).await.collect().await;
+			level_tiles.sort_by_key(|(coord, _)| (coord.y, coord.x));
+			tiles.push(level_tiles);
+
+			let mut printer = PrettyPrint::new();
+			reader.probe_tiles(&printer.get_category("tiles").await).await?;
+			let expected = match tile_order {
+				TileOrder::Source => "blocks in row order: 5\n   blocks in Hilbert order: 0\n   blocks in other order: 0\n",
+				TileOrder::Hilbert => {
+					"blocks in row order: 0\n   blocks in Hilbert order: 5\n   blocks in other order: 0\n"
+				}
+			};
+			let text = printer.as_string().await;
+			assert!(text.contains(expected), "{tile_order}: {text}");
+		}
+		assert_eq!(tiles[0].len(), 1024);
+		assert_eq!(tiles[0], tiles[1]);
 
 		Ok(())
 	}
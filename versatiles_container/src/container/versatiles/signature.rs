@@ -0,0 +1,306 @@
+//! Ed25519 signatures of `*.versatiles` containers.
+//!
+//! A signature is stored next to the container as `<container>.sig`, so signing does not change the container
+//! itself. It covers two SHA-256 hashes:
+//!
+//! - the index hash of the header, the meta data and the block index, which is checked whenever a container is
+//!   opened with [`VersaTilesReader::open_path_with_verified_index`](super::VersaTilesReader::open_path_with_verified_index),
+//! - the tiles hash of a manifest of all tiles, which is checked by [`ContainerSignature::verify`] when
+//!   `check_tiles` is set. This reads every tile.
+//!
+//! The signature file is a JSON object, e.g.
+//! `{"algorithm":"ed25519","index_hash":"…","public_key":"…","signature":"…","tiles_hash":"…"}`
+//!
+//! # Example
+//!
+//! ```no_run
+//! use versatiles_container::{generate_signing_key, ContainerSignature, VersaTilesReader};
+//! use anyhow::Result;
+//! use std::path::Path;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let path = Path::new("tiles.versatiles");
+//!     let private_key = generate_signing_key()?;
+//!
+//!     let reader = VersaTilesReader::open_path(path).await?;
+//!     let signature = ContainerSignature::sign(&reader, &private_key).await?;
+//!     signature.write_file(&ContainerSignature::get_path(path))?;
+//!
+//!     let reader = VersaTilesReader::open_path_with_verified_index(path, &signature.public_key).await?;
+//!     Ok(())
+//! }
+//! ```
+
+use super::VersaTilesReader;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use ring::{
+	digest::{Context as DigestContext, SHA256},
+	rand::SystemRandom,
+	signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519},
+};
+use std::{
+	ffi::OsString,
+	fs,
+	path::{Path, PathBuf},
+};
+use versatiles_core::{
+	json::JsonObject,
+	types::{TileCoord3, TilesReaderTrait},
+};
+
+/// Prefix of the signed message, so that the signature can't be mistaken for a signature of something else.
+const MESSAGE_PREFIX: &str = "versatiles signature v1";
+
+/// Size of the blocks in which the tiles are hashed.
+const BLOCK_SIZE: u32 = 256;
+
+/// Generates a new Ed25519 private key, encoded as PKCS#8 document.
+pub fn generate_signing_key() -> Result<Vec<u8>> {
+	let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|_| anyhow!("generating key failed"))?;
+	Ok(document.as_ref().to_vec())
+}
+
+/// Returns the public key of a PKCS#8 encoded private key.
+pub fn get_public_key(private_key: &[u8]) -> Result<Vec<u8>> {
+	Ok(parse_key_pair(private_key)?.public_key().as_ref().to_vec())
+}
+
+fn parse_key_pair(private_key: &[u8]) -> Result<Ed25519KeyPair> {
+	Ed25519KeyPair::from_pkcs8(private_key).map_err(|_| anyhow!("private key is not an Ed25519 key in PKCS#8 format"))
+}
+
+/// The signature of a `*.versatiles` container.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerSignature {
+	pub public_key: Vec<u8>,
+	/// SHA-256 of the header, the meta data and the block index.
+	pub index_hash: Vec<u8>,
+	/// SHA-256 of the manifest of all tiles.
+	pub tiles_hash: Vec<u8>,
+	pub signature: Vec<u8>,
+}
+
+impl ContainerSignature {
+	/// Returns the path of the signature of a container: `<container>.sig`
+	pub fn get_path(container: &Path) -> PathBuf {
+		let mut path: OsString = container.as_os_str().to_owned();
+		path.push(".sig");
+		PathBuf::from(path)
+	}
+
+	/// Signs a container with a PKCS#8 encoded Ed25519 private key. This reads every tile.
+	pub async fn sign(reader: &VersaTilesReader, private_key: &[u8]) -> Result<ContainerSignature> {
+		let key_pair = parse_key_pair(private_key)?;
+		let index_hash = reader.get_index_hash().await?;
+		let tiles_hash = get_tiles_hash(reader).await?;
+		let signature = key_pair
+			.sign(get_message(&index_hash, &tiles_hash).as_bytes())
+			.as_ref()
+			.to_vec();
+
+		Ok(ContainerSignature {
+			public_key: key_pair.public_key().as_ref().to_vec(),
+			index_hash,
+			tiles_hash,
+			signature,
+		})
+	}
+
+	/// Checks that the signature was made with the trusted public key.
+	pub fn verify_signature(&self, public_key: &[u8]) -> Result<()> {
+		ensure!(
+			self.public_key == public_key,
+			"container was signed with a different key: {}",
+			to_hex(&self.public_key)
+		);
+		UnparsedPublicKey::new(&ED25519, public_key)
+			.verify(
+				get_message(&self.index_hash, &self.tiles_hash).as_bytes(),
+				&self.signature,
+			)
+			.map_err(|_| anyhow!("invalid signature"))
+	}
+
+	/// Verifies a container against this signature and the trusted public key.
+	/// If `check_tiles` is set, all tiles are read and checked as well.
+	pub async fn verify(&self, reader: &VersaTilesReader, public_key: &[u8], check_tiles: bool) -> Result<()> {
+		self.verify_signature(public_key)?;
+		ensure!(
+			reader.get_index_hash().await? == self.index_hash,
+			"header, meta data or block index were modified after signing"
+		);
+		if check_tiles {
+			ensure!(
+				get_tiles_hash(reader).await? == self.tiles_hash,
+				"tiles were modified after signing"
+			);
+		}
+		Ok(())
+	}
+
+	pub fn to_json(&self) -> JsonObject {
+		let mut json = JsonObject::default();
+		json.set("algorithm", "ed25519");
+		json.set("public_key", to_hex(&self.public_key));
+		json.set("index_hash", to_hex(&self.index_hash));
+		json.set("tiles_hash", to_hex(&self.tiles_hash));
+		json.set("signature", to_hex(&self.signature));
+		json
+	}
+
+	pub fn from_json(json: &JsonObject) -> Result<ContainerSignature> {
+		let get_hex = |key: &str| -> Result<Vec<u8>> {
+			parse_hex(
+				&json
+					.get_string(key)?
+					.with_context(|| format!("signature has no '{key}'"))?,
+			)
+			.with_context(|| format!("parsing '{key}' of signature"))
+		};
+		let algorithm = json.get_string("algorithm")?.context("signature has no 'algorithm'")?;
+		ensure!(algorithm == "ed25519", "unknown signature algorithm '{algorithm}'");
+
+		Ok(ContainerSignature {
+			public_key: get_hex("public_key")?,
+			index_hash: get_hex("index_hash")?,
+			tiles_hash: get_hex("tiles_hash")?,
+			signature: get_hex("signature")?,
+		})
+	}
+
+	pub fn read_file(path: &Path) -> Result<ContainerSignature> {
+		let text = fs::read_to_string(path).with_context(|| format!("reading signature {path:?}"))?;
+		ContainerSignature::from_json(&JsonObject::parse_str(&text)?)
+			.with_context(|| format!("parsing signature {path:?}"))
+	}
+
+	pub fn write_file(&self, path: &Path) -> Result<()> {
+		fs::write(path, self.to_json().stringify()).with_context(|| format!("writing signature {path:?}"))
+	}
+}
+
+/// Returns the message that is signed.
+fn get_message(index_hash: &[u8], tiles_hash: &[u8]) -> String {
+	format!("{MESSAGE_PREFIX}\n{}\n{}\n", to_hex(index_hash), to_hex(tiles_hash))
+}
+
+/// Hashes a manifest of all tiles: for every tile its coordinates and the SHA-256 of its data, sorted by zoom level,
+/// then in blocks of 256x256 tiles by row and column.
+async fn get_tiles_hash(reader: &VersaTilesReader) -> Result<Vec<u8>> {
+	let mut context = DigestContext::new(&SHA256);
+	for level_bbox in reader.get_parameters().bbox_pyramid.iter_levels() {
+		for bbox in level_bbox.iter_bbox_grid(BLOCK_SIZE) {
+			let mut tiles: Vec<(TileCoord3, Vec<u8>)> = Vec::new();
+			reader
+				.get_bbox_tile_stream(bbox)
+				.await
+				.for_each_sync(|(coord, blob)| tiles.push((coord, sha256(blob.as_slice()))))
+				.await;
+			tiles.sort_unstable_by_key(|(coord, _)| (coord.y, coord.x));
+
+			for (coord, hash) in tiles {
+				context.update(&[coord.z]);
+				context.update(&coord.x.to_be_bytes());
+				context.update(&coord.y.to_be_bytes());
+				context.update(&hash);
+			}
+		}
+	}
+	Ok(context.finish().as_ref().to_vec())
+}
+
+pub(super) fn sha256(data: &[u8]) -> Vec<u8> {
+	ring::digest::digest(&SHA256, data).as_ref().to_vec()
+}
+
+pub(super) fn to_hex(data: &[u8]) -> String {
+	data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub(super) fn parse_hex(text: &str) -> Result<Vec<u8>> {
+	if !text.len().is_multiple_of(2) || !text.is_ascii() {
+		bail!("invalid hex string");
+	}
+	(0..text.len())
+		.step_by(2)
+		.map(|index| u8::from_str_radix(&text[index..index + 2], 16).context("invalid hex string"))
+		.collect()
+}
+
+/// Formats a public key as hex string, e.g. to pass it to `versatiles verify`.
+pub fn format_public_key(public_key: &[u8]) -> String {
+	to_hex(public_key)
+}
+
+/// Parses a public key given as hex string.
+pub fn parse_public_key(text: &str) -> Result<Vec<u8>> {
+	let public_key = parse_hex(text.trim()).context("parsing public key")?;
+	ensure!(
+		public_key.len() == 32,
+		"an Ed25519 public key has 32 bytes, not {}",
+		public_key.len()
+	);
+	Ok(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::make_test_file;
+	use assert_fs::TempDir;
+	use versatiles_core::types::{TileCompression, TileFormat};
+
+	#[test]
+	fn hex() -> Result<()> {
+		assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+		assert_eq!(parse_hex("000fff")?, vec![0, 15, 255]);
+		assert!(parse_hex("0f0").is_err());
+		assert!(parse_hex("zz").is_err());
+
+		let public_key = get_public_key(&generate_signing_key()?)?;
+		assert_eq!(parse_public_key(&format_public_key(&public_key))?, public_key);
+		assert!(parse_public_key("000fff").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn signature_path() {
+		assert_eq!(
+			ContainerSignature::get_path(Path::new("/data/tiles.versatiles")),
+			PathBuf::from("/data/tiles.versatiles.sig")
+		);
+	}
+
+	#[tokio::test]
+	async fn sign_and_verify() -> Result<()> {
+		let path = make_test_file(TileFormat::PBF, TileCompression::Gzip, 3, "versatiles").await?;
+		let private_key = generate_signing_key()?;
+		let public_key = get_public_key(&private_key)?;
+
+		let reader = VersaTilesReader::open_path(&path).await?;
+		let signature = ContainerSignature::sign(&reader, &private_key).await?;
+		assert_eq!(signature.public_key, public_key);
+		signature.verify(&reader, &public_key, true).await?;
+
+		let dir = TempDir::new()?;
+		let signature_path = dir.path().join("tiles.versatiles.sig");
+		signature.write_file(&signature_path)?;
+		assert_eq!(ContainerSignature::read_file(&signature_path)?, signature);
+
+		let other_key = get_public_key(&generate_signing_key()?)?;
+		assert!(signature.verify(&reader, &other_key, false).await.is_err());
+
+		let mut forged = signature.clone();
+		forged.tiles_hash[0] ^= 1;
+		assert_eq!(
+			forged
+				.verify(&reader, &public_key, false)
+				.await
+				.unwrap_err()
+				.to_string(),
+			"invalid signature"
+		);
+		Ok(())
+	}
+}
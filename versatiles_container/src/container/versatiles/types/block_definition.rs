@@ -32,7 +32,8 @@ impl BlockDefinition {
 		let z = bbox.level;
 		let global_bbox: TileBBox = bbox.clone();
 
-		let tiles_coverage = TileBBox::new(
+		let tiles_coverage = TileBBox::new_in_grid(
+			bbox.grid,
 			z.min(8),
 			bbox.x_min - x * 256,
 			bbox.y_min - y * 256,
@@ -54,10 +55,11 @@ impl BlockDefinition {
 	///
 	/// # Arguments
 	/// * `blob` - The binary data representing the block definition.
+	/// * `grid` - The tile grid of the container.
 	///
 	/// # Errors
 	/// Returns an error if the binary data cannot be parsed correctly.
-	pub fn from_blob(blob: &Blob) -> Result<Self> {
+	pub fn from_blob(blob: &Blob, grid: TileGrid) -> Result<Self> {
 		let mut reader = ValueReaderSlice::new_be(blob.as_slice());
 
 		let z = reader.read_u8()?;
@@ -69,7 +71,7 @@ impl BlockDefinition {
 		let x_max = reader.read_u8()? as u32;
 		let y_max = reader.read_u8()? as u32;
 
-		let tiles_bbox = TileBBox::new(z.min(8), x_min, y_min, x_max, y_max)?;
+		let tiles_bbox = TileBBox::new_in_grid(grid, z.min(8), x_min, y_min, x_max, y_max)?;
 
 		let offset = reader.read_u64()?;
 		let tiles_length = reader.read_u64()?;
@@ -78,7 +80,14 @@ impl BlockDefinition {
 		let tiles_range = ByteRange::new(offset, tiles_length);
 		let index_range = ByteRange::new(offset + tiles_length, index_length);
 
-		let global_bbox = TileBBox::new(z, x_min + x * 256, y_min + y * 256, x_max + x * 256, y_max + y * 256)?;
+		let global_bbox = TileBBox::new_in_grid(
+			grid,
+			z,
+			x_min + x * 256,
+			y_min + y * 256,
+			x_max + x * 256,
+			y_max + y * 256,
+		)?;
 
 		Ok(Self {
 			offset: TileCoord3::new(x, y, z)?,
@@ -147,7 +156,6 @@ impl BlockDefinition {
 	///
 	/// # Returns
 	/// The sort index for the block.
-	#[allow(dead_code)]
 	pub fn get_sort_index(&self) -> u64 {
 		self.offset.get_sort_index()
 	}
@@ -229,7 +237,7 @@ mod tests {
 		def.tiles_range = ByteRange::new(4, 5);
 		def.index_range = ByteRange::new(9, 6);
 
-		assert_eq!(def, BlockDefinition::from_blob(&def.as_blob()?)?);
+		assert_eq!(def, BlockDefinition::from_blob(&def.as_blob()?, TileGrid::WebMercator)?);
 		assert_eq!(def.count_tiles(), 1071);
 		assert_eq!(def.as_blob()?.len(), 33);
 		assert_eq!(def.get_sort_index(), 5596502);
@@ -242,12 +250,23 @@ mod tests {
 			"BlockDefinition { x/y/z: TileCoord3(1, 1, 12), bbox: 8: [44,144,64,194] (1071), tiles_range: ByteRange[4,5], index_range: ByteRange[9,6] }"
 		);
 
-		let def2 = BlockDefinition::from_blob(&def.as_blob()?)?;
+		let def2 = BlockDefinition::from_blob(&def.as_blob()?, TileGrid::WebMercator)?;
 		assert_eq!(def, def2);
 
 		Ok(())
 	}
 
+	#[test]
+	fn wgs84_grid() -> Result<()> {
+		let bbox = TileBBox::new_in_grid(TileGrid::Wgs84, 7, 130, 10, 255, 20)?;
+		let def = BlockDefinition::new(&bbox);
+		assert_eq!(def.get_global_bbox(), &bbox);
+
+		assert!(BlockDefinition::from_blob(&def.as_blob()?, TileGrid::WebMercator).is_err());
+		assert_eq!(BlockDefinition::from_blob(&def.as_blob()?, TileGrid::Wgs84)?, def);
+		Ok(())
+	}
+
 	#[test]
 	fn test_set_tiles_range() -> Result<()> {
 		let bbox = TileBBox::new(14, 0, 0, 255, 255)?;
@@ -30,10 +30,11 @@ impl BlockIndex {
 	///
 	/// # Arguments
 	/// * `buf` - The binary data representing the block index.
+	/// * `grid` - The tile grid of the container.
 	///
 	/// # Errors
 	/// Returns an error if the binary data cannot be parsed correctly.
-	pub fn from_blob(buf: Blob) -> Result<Self> {
+	pub fn from_blob(buf: Blob, grid: TileGrid) -> Result<Self> {
 		let count = buf.len().div(BLOCK_INDEX_LENGTH);
 		ensure!(
 			count * BLOCK_INDEX_LENGTH == buf.len(),
@@ -44,7 +45,7 @@ impl BlockIndex {
 		let mut block_index = Self::new_empty();
 		for i in 0..count {
 			let range = &ByteRange::new(i * BLOCK_INDEX_LENGTH, BLOCK_INDEX_LENGTH);
-			block_index.add_block(BlockDefinition::from_blob(&buf.read_range(range)?)?);
+			block_index.add_block(BlockDefinition::from_blob(&buf.read_range(range)?, grid)?);
 		}
 
 		Ok(block_index)
@@ -54,11 +55,12 @@ impl BlockIndex {
 	///
 	/// # Arguments
 	/// * `buf` - The Brotli compressed binary data representing the block index.
+	/// * `grid` - The tile grid of the container.
 	///
 	/// # Errors
 	/// Returns an error if the binary data cannot be decompressed or parsed correctly.
-	pub fn from_brotli_blob(buf: Blob) -> Result<Self> {
-		Self::from_blob(decompress_brotli(&buf)?)
+	pub fn from_brotli_blob(buf: Blob, grid: TileGrid) -> Result<Self> {
+		Self::from_blob(decompress_brotli(&buf)?, grid)
 	}
 
 	/// Returns a `TileBBoxPyramid` representing the bounding boxes of the blocks in the index.
@@ -140,13 +142,13 @@ impl BlockIndex {
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use versatiles_core::types::TileBBox;
+	use versatiles_core::types::{TileBBox, TileGrid};
 
 	#[test]
 	fn conversion() -> Result<()> {
 		let mut index1 = BlockIndex::new_empty();
 		index1.add_block(BlockDefinition::new(&TileBBox::new(3, 1, 2, 3, 4)?));
-		let index2 = BlockIndex::from_brotli_blob(index1.as_brotli_blob()?)?;
+		let index2 = BlockIndex::from_brotli_blob(index1.as_brotli_blob()?, TileGrid::WebMercator)?;
 		assert_eq!(index1, index2);
 		Ok(())
 	}
@@ -26,21 +26,69 @@
 //! }
 //! ```
 
-use super::types::{BlockDefinition, BlockIndex, FileHeader, TileIndex};
+use super::{
+	layout::get_hilbert_index,
+	types::{BlockDefinition, BlockIndex, FileHeader, TileIndex},
+	TileCipher, TileOrder, VersaTilesWriterOptions,
+};
 use crate::TilesWriterTrait;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use log::{debug, trace};
-use std::collections::HashMap;
-use versatiles_core::{io::DataWriterTrait, progress::*, types::*, utils::compress};
+use std::{collections::HashMap, path::Path};
+use versatiles_core::{
+	io::{DataWriterFile, DataWriterTrait},
+	progress::*,
+	types::*,
+	utils::compress,
+};
 
 /// A struct for writing tiles to a VersaTiles container.
+/// The internal layout can be tuned with `VersaTilesWriterOptions`, see `write_to_path_with_options`.
 pub struct VersaTilesWriter {}
 
 #[async_trait]
 impl TilesWriterTrait for VersaTilesWriter {
 	/// Convert tiles from the TilesReader and write them to the writer.
 	async fn write_to_writer(reader: &mut dyn TilesReaderTrait, writer: &mut dyn DataWriterTrait) -> Result<()> {
+		VersaTilesWriter::write(reader, writer, &VersaTilesWriterOptions::default(), None).await
+	}
+}
+
+impl VersaTilesWriter {
+	/// Writes the tiles from the reader to a file, encrypting every tile with `cipher`.
+	/// See `TileCipher` for details.
+	pub async fn write_to_path_encrypted(
+		reader: &mut dyn TilesReaderTrait,
+		path: &Path,
+		cipher: &TileCipher,
+	) -> Result<()> {
+		VersaTilesWriter::write(
+			reader,
+			&mut DataWriterFile::from_path(path)?,
+			&VersaTilesWriterOptions::default(),
+			Some(cipher),
+		)
+		.await
+	}
+
+	/// Writes the tiles from the reader to a file, with the layout of `options` and optionally encrypting every tile.
+	pub async fn write_to_path_with_options(
+		reader: &mut dyn TilesReaderTrait,
+		path: &Path,
+		options: &VersaTilesWriterOptions,
+		cipher: Option<&TileCipher>,
+	) -> Result<()> {
+		VersaTilesWriter::write(reader, &mut DataWriterFile::from_path(path)?, options, cipher).await
+	}
+
+	/// Writes the container, optionally encrypting its tiles.
+	async fn write(
+		reader: &mut dyn TilesReaderTrait,
+		writer: &mut dyn DataWriterTrait,
+		options: &VersaTilesWriterOptions,
+		cipher: Option<&TileCipher>,
+	) -> Result<()> {
 		// Finalize the configuration
 		let parameters = reader.get_parameters();
 		trace!("convert_from - reader.parameters: {parameters:?}");
@@ -66,10 +114,10 @@ impl TilesWriterTrait for VersaTilesWriter {
 		writer.append(&blob)?;
 
 		trace!("write meta");
-		header.meta_range = Self::write_meta(reader, writer).await?;
+		header.meta_range = Self::write_meta(reader, writer, cipher).await?;
 
 		trace!("write blocks");
-		header.blocks_range = Self::write_blocks(reader, writer).await?;
+		header.blocks_range = Self::write_blocks(reader, writer, options, cipher).await?;
 
 		trace!("update header");
 		let blob: Blob = header.to_blob()?;
@@ -77,19 +125,30 @@ impl TilesWriterTrait for VersaTilesWriter {
 
 		Ok(())
 	}
-}
 
-impl VersaTilesWriter {
 	/// Write metadata to the writer.
-	async fn write_meta(reader: &dyn TilesReaderTrait, writer: &mut dyn DataWriterTrait) -> Result<ByteRange> {
-		let meta: Blob = reader.get_tilejson().into();
+	async fn write_meta(
+		reader: &dyn TilesReaderTrait,
+		writer: &mut dyn DataWriterTrait,
+		cipher: Option<&TileCipher>,
+	) -> Result<ByteRange> {
+		let mut tilejson = reader.get_tilejson().clone();
+		if let Some(cipher) = cipher {
+			cipher.add_to_tilejson(&mut tilejson)?;
+		}
+		let meta: Blob = (&tilejson).into();
 		let compressed = compress(meta, &reader.get_parameters().tile_compression)?;
 
 		writer.append(&compressed)
 	}
 
 	/// Write blocks to the writer.
-	async fn write_blocks(reader: &mut dyn TilesReaderTrait, writer: &mut dyn DataWriterTrait) -> Result<ByteRange> {
+	async fn write_blocks(
+		reader: &mut dyn TilesReaderTrait,
+		writer: &mut dyn DataWriterTrait,
+		options: &VersaTilesWriterOptions,
+		cipher: Option<&TileCipher>,
+	) -> Result<ByteRange> {
 		let pyramid = reader.get_parameters().bbox_pyramid.clone();
 
 		if pyramid.is_empty() {
@@ -116,9 +175,24 @@ impl VersaTilesWriter {
 		let mut block_index = BlockIndex::new_empty();
 		let mut tiles_count = 0;
 
-		// Iterate through blocks and write them
-		for mut block in blocks.into_iter() {
-			let (tiles_range, index_range) = Self::write_block(&block, reader, writer, &mut progress).await?;
+		let next_bboxes: Vec<TileBBox> = blocks
+			.iter()
+			.skip(1)
+			.map(|block| block.get_global_bbox().clone())
+			.collect();
+		let reader: &dyn TilesReaderTrait = reader;
+
+		// Iterate through blocks and write them, while the reader prefetches the next block
+		for (index, mut block) in blocks.into_iter().enumerate() {
+			let prefetch = async {
+				if let Some(bbox) = next_bboxes.get(index) {
+					if let Err(err) = reader.prefetch(bbox).await {
+						debug!("prefetching block {bbox:?} failed: {err:#}");
+					}
+				}
+			};
+			let write = Self::write_block(&block, reader, writer, options, cipher, &mut progress);
+			let (tiles_range, index_range) = futures::join!(prefetch, write).1?;
 
 			if tiles_range.length + index_range.length == 0 {
 				// Block is empty, continue with the next block
@@ -137,7 +211,7 @@ impl VersaTilesWriter {
 		// Finish updating progress and write the block index
 		progress.finish();
 
-		let range = writer.append(&block_index.as_brotli_blob()?)?;
+		let range = writer.append(&options.index_compression.compress(&block_index.as_blob()?)?)?;
 
 		Ok(range)
 	}
@@ -145,8 +219,10 @@ impl VersaTilesWriter {
 	/// Write a single block to the writer.
 	async fn write_block(
 		block: &BlockDefinition,
-		reader: &mut dyn TilesReaderTrait,
+		reader: &dyn TilesReaderTrait,
 		writer: &mut dyn DataWriterTrait,
+		options: &VersaTilesWriterOptions,
+		cipher: Option<&TileCipher>,
 		progress: &mut Box<dyn ProgressTrait>,
 	) -> Result<(ByteRange, ByteRange)> {
 		// Log the start of the block
@@ -161,42 +237,61 @@ impl VersaTilesWriter {
 		let mut tile_index = TileIndex::new_empty(bbox.count_tiles() as usize);
 		let mut tile_hash_lookup: HashMap<Vec<u8>, ByteRange> = HashMap::new();
 
-		// Get the tile stream
-		let tile_stream: TileStream = reader.get_bbox_tile_stream(bbox.clone()).await;
-
-		// Iterate through the blobs and process them
-		tile_stream
-			.for_each_sync(|(coord, blob)| {
-				progress.inc(1);
+		let mut write_tile = |(coord, blob): (TileCoord3, Blob)| {
+			progress.inc(1);
 
-				let index = bbox.get_tile_index2(&coord.as_coord2()).unwrap();
+			let index = bbox.get_tile_index2(&coord.as_coord2()).unwrap();
 
-				let mut save_hash = false;
-				if blob.len() < 1000 {
-					if let Some(range) = tile_hash_lookup.get(blob.as_slice()) {
-						tile_index.set(index, *range);
-						return;
-					}
-					save_hash = true;
+			// encrypted tiles are bound to their coordinates, so they are never shared
+			let mut save_hash = false;
+			if cipher.is_none() && blob.len() < 1000 {
+				if let Some(range) = tile_hash_lookup.get(blob.as_slice()) {
+					tile_index.set(index, *range);
+					return;
 				}
+				save_hash = true;
+			}
 
-				let mut range = writer.append(&blob).unwrap();
-				range.shift_backward(offset0);
+			let mut range = match cipher {
+				Some(cipher) => writer.append(&cipher.encrypt(&coord, &blob).unwrap()).unwrap(),
+				None => writer.append(&blob).unwrap(),
+			};
+			range.shift_backward(offset0);
 
-				tile_index.set(index, range);
+			tile_index.set(index, range);
 
-				if save_hash {
-					tile_hash_lookup.insert(blob.into_vec(), range);
+			if save_hash {
+				tile_hash_lookup.insert(blob.into_vec(), range);
+			}
+		};
+
+		match options.tile_order {
+			TileOrder::Source => {
+				reader
+					.get_bbox_tile_stream(bbox.clone())
+					.await
+					.for_each_sync(write_tile)
+					.await;
+			}
+			TileOrder::Hilbert => {
+				// Aligned squares of 16×16 tiles are consecutive parts of the Hilbert curve,
+				// so only the tiles of one square have to be sorted in memory.
+				let mut cells: Vec<TileBBox> = bbox.iter_bbox_grid(16).collect();
+				cells.sort_by_key(|cell| get_hilbert_index(cell.x_min, cell.y_min) >> 8);
+				for cell in cells {
+					let mut tiles = reader.get_bbox_tile_stream(cell).await.collect().await;
+					tiles.sort_by_key(|(coord, _)| get_hilbert_index(coord.x, coord.y));
+					tiles.into_iter().for_each(&mut write_tile);
 				}
-			})
-			.await;
+			}
+		}
 
 		// Finish the block and write the index
 		debug!("finish block and write index {:?}", block);
 
 		// Get the final writer position
 		let offset1 = writer.get_position()?;
-		let index_range = writer.append(&tile_index.as_brotli_blob()?)?;
+		let index_range = writer.append(&options.index_compression.compress(&tile_index.as_blob()?)?)?;
 
 		Ok((ByteRange::new(offset0, offset1 - offset0), index_range))
 	}
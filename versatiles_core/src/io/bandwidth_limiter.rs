@@ -0,0 +1,178 @@
+//! This module provides the `BandwidthLimiter`, a simple rate limiter for network transfers.
+//!
+//! # Overview
+//!
+//! The `BandwidthLimiter` keeps track of how many bytes have been transferred and calculates how long
+//! a caller has to wait, so that the average transfer rate does not exceed a configured number of bytes
+//! per second. It can be shared between multiple readers or writers, e.g. to limit the bandwidth of all
+//! requests to the same server.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use versatiles_core::io::BandwidthLimiter;
+//! use anyhow::Result;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     // Allow 10 MB per second
+//!     let limiter = BandwidthLimiter::new(10_000_000)?;
+//!
+//!     // Call this after transferring 1000 bytes
+//!     limiter.throttle(1000).await;
+//!
+//!     assert_eq!(limiter.get_total_bytes(), 1000);
+//!     Ok(())
+//! }
+//! ```
+
+use anyhow::{ensure, Result};
+use std::{
+	fmt::Debug,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+/// A rate limiter that restricts the average number of transferred bytes per second.
+pub struct BandwidthLimiter {
+	bytes_per_second: u64,
+	next_free: Mutex<Option<Instant>>,
+	total_bytes: AtomicU64,
+}
+
+impl BandwidthLimiter {
+	/// Creates a new `BandwidthLimiter`.
+	///
+	/// # Arguments
+	///
+	/// * `bytes_per_second` - The maximum average transfer rate in bytes per second.
+	///
+	/// # Returns
+	///
+	/// * A Result containing the `BandwidthLimiter` or an error, if the rate is zero.
+	pub fn new(bytes_per_second: u64) -> Result<BandwidthLimiter> {
+		ensure!(bytes_per_second > 0, "bandwidth limit must be greater than 0");
+		Ok(BandwidthLimiter {
+			bytes_per_second,
+			next_free: Mutex::new(None),
+			total_bytes: AtomicU64::new(0),
+		})
+	}
+
+	/// Returns the maximum transfer rate in bytes per second.
+	pub fn get_bytes_per_second(&self) -> u64 {
+		self.bytes_per_second
+	}
+
+	/// Returns the number of bytes that have been registered so far.
+	pub fn get_total_bytes(&self) -> u64 {
+		self.total_bytes.load(Ordering::Relaxed)
+	}
+
+	/// Registers a transfer of `bytes` and returns how long the caller has to wait.
+	///
+	/// # Arguments
+	///
+	/// * `bytes` - The number of transferred bytes.
+	///
+	/// # Returns
+	///
+	/// * The `Duration` the caller should pause to stay within the bandwidth limit.
+	pub fn reserve(&self, bytes: u64) -> Duration {
+		self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+
+		let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+		let now = Instant::now();
+
+		let mut next_free = self.next_free.lock().unwrap();
+		let start = next_free.map_or(now, |t| t.max(now));
+		let end = start + cost;
+		*next_free = Some(end);
+
+		end.saturating_duration_since(now)
+	}
+
+	/// Registers a transfer of `bytes` and asynchronously waits until the bandwidth limit is respected.
+	///
+	/// # Arguments
+	///
+	/// * `bytes` - The number of transferred bytes.
+	pub async fn throttle(&self, bytes: u64) {
+		let delay = self.reserve(bytes);
+		if !delay.is_zero() {
+			tokio::time::sleep(delay).await;
+		}
+	}
+
+	/// Registers a transfer of `bytes` and blocks the current thread until the bandwidth limit is respected.
+	///
+	/// # Arguments
+	///
+	/// * `bytes` - The number of transferred bytes.
+	pub fn throttle_blocking(&self, bytes: u64) {
+		let delay = self.reserve(bytes);
+		if !delay.is_zero() {
+			std::thread::sleep(delay);
+		}
+	}
+}
+
+impl Debug for BandwidthLimiter {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("BandwidthLimiter")
+			.field("bytes_per_second", &self.bytes_per_second)
+			.field("total_bytes", &self.get_total_bytes())
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn new() {
+		assert!(BandwidthLimiter::new(0).is_err());
+		let limiter = BandwidthLimiter::new(1000).unwrap();
+		assert_eq!(limiter.get_bytes_per_second(), 1000);
+		assert_eq!(limiter.get_total_bytes(), 0);
+	}
+
+	#[test]
+	fn reserve_accumulates_delay() {
+		let limiter = BandwidthLimiter::new(1000).unwrap();
+
+		let delay1 = limiter.reserve(500);
+		assert!(delay1 <= Duration::from_millis(500));
+		assert!(delay1 > Duration::from_millis(400));
+
+		let delay2 = limiter.reserve(500);
+		assert!(delay2 <= Duration::from_millis(1000));
+		assert!(delay2 > Duration::from_millis(900));
+
+		assert_eq!(limiter.get_total_bytes(), 1000);
+	}
+
+	#[tokio::test]
+	async fn throttle() {
+		let limiter = BandwidthLimiter::new(100_000).unwrap();
+		let start = Instant::now();
+		limiter.throttle(5_000).await;
+		limiter.throttle(5_000).await;
+		assert!(start.elapsed() >= Duration::from_millis(90));
+		assert_eq!(limiter.get_total_bytes(), 10_000);
+	}
+
+	#[test]
+	fn debug() {
+		let limiter = BandwidthLimiter::new(1000).unwrap();
+		limiter.reserve(10);
+		assert_eq!(
+			format!("{limiter:?}"),
+			"BandwidthLimiter { bytes_per_second: 1000, total_bytes: 10 }"
+		);
+	}
+}
@@ -88,6 +88,13 @@ pub trait DataReaderTrait: Debug + Send + Sync {
 	#[allow(dead_code)]
 	async fn read_all(&self) -> Result<Blob>;
 
+	/// Hints that the range will probably be read soon, so that it can be fetched ahead of time.
+	///
+	/// Readers with fast random access, like files, do nothing.
+	async fn prefetch_range(&self, _range: &ByteRange) -> Result<()> {
+		Ok(())
+	}
+
 	/// Gets the name of the data source.
 	///
 	/// # Returns
@@ -70,6 +70,11 @@ impl DataReaderFile {
 			size,
 		}))
 	}
+
+	/// Returns the size of the file in bytes.
+	pub fn get_size(&self) -> u64 {
+		self.size
+	}
 }
 
 #[async_trait]
@@ -180,6 +185,7 @@ mod tests {
 
 		// Check if the read range matches the expected text
 		assert_eq!(blob.as_str(), "o, wor");
+		assert_eq!(data_reader_file.get_size(), 13);
 
 		Ok(())
 	}
@@ -6,6 +6,13 @@
 //! `DataReaderTrait` to provide asynchronous reading capabilities. The module ensures the URL has
 //! a valid scheme (`http` or `https`) and uses the `reqwest` library to handle HTTP requests.
 //!
+//! Optionally, the download rate can be limited with a [`BandwidthLimiter`](super::BandwidthLimiter),
+//! the number of concurrent requests with a [`RequestLimiter`](super::RequestLimiter),
+//! and the number of downloaded bytes can be shown as a progress bar.
+//!
+//! Ranges that are prefetched with [`DataReaderTrait::prefetch_range`] are kept in a small readahead buffer,
+//! so that later reads within them don't need another request.
+//!
 //! # Examples
 //!
 //! ```rust
@@ -27,20 +34,43 @@
 //! }
 //! ```
 
-use super::DataReaderTrait;
-use crate::types::{Blob, ByteRange};
+use super::{BandwidthLimiter, DataReaderTrait, RequestLimiter};
+use crate::{
+	progress::{get_progress_bar, ProgressTrait},
+	types::{Blob, ByteRange},
+};
 use anyhow::{bail, Result};
 use async_trait::async_trait;
 use lazy_static::lazy_static;
 use regex::{Regex, RegexBuilder};
 use reqwest::{Client, Method, Request, StatusCode, Url};
-use std::{ops::Deref, str, time::Duration};
+use std::{
+	collections::VecDeque,
+	fmt::Debug,
+	ops::Deref,
+	str,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	time::Duration,
+};
+
+/// Number of prefetched ranges that are kept in the readahead buffer.
+const READAHEAD_SLOTS: usize = 4;
+
+/// Prefetch hints for longer ranges are ignored, to keep the readahead buffer small.
+const READAHEAD_MAX_LENGTH: u64 = 16 * 1024 * 1024;
 
 /// A struct that provides reading capabilities from an HTTP(S) endpoint.
-#[derive(Debug)]
 pub struct DataReaderHttp {
+	bytes_read: AtomicU64,
 	client: Client,
+	limiter: Option<Arc<BandwidthLimiter>>,
 	name: String,
+	progress: Option<Mutex<Box<dyn ProgressTrait>>>,
+	readahead: Mutex<VecDeque<(ByteRange, Blob)>>,
+	request_limiter: Option<Arc<RequestLimiter>>,
 	url: Url,
 }
 
@@ -68,25 +98,71 @@ impl DataReaderHttp {
 			.build()?;
 
 		Ok(Box::new(DataReaderHttp {
+			bytes_read: AtomicU64::new(0),
 			client,
+			limiter: None,
 			name: url.to_string(),
+			progress: None,
+			readahead: Mutex::new(VecDeque::new()),
+			request_limiter: None,
 			url,
 		}))
 	}
-}
 
-#[async_trait]
-impl DataReaderTrait for DataReaderHttp {
-	/// Reads a specific range of bytes from the HTTP(S) endpoint.
+	/// Limits the download rate of this reader.
+	///
+	/// The limiter can be shared between multiple readers to limit their combined bandwidth.
 	///
 	/// # Arguments
 	///
-	/// * `range` - A ByteRange struct specifying the offset and length of the range to read.
+	/// * `limiter` - The `BandwidthLimiter` to use for all following requests.
+	pub fn set_bandwidth_limiter(&mut self, limiter: Arc<BandwidthLimiter>) {
+		self.limiter = Some(limiter);
+	}
+
+	/// Limits the number of requests of this reader running at the same time. Further requests wait in a queue.
 	///
-	/// # Returns
+	/// The limiter can be shared between multiple readers to limit their combined requests.
 	///
-	/// * A Result containing a Blob with the read data or an error.
-	async fn read_range(&self, range: &ByteRange) -> Result<Blob> {
+	/// # Arguments
+	///
+	/// * `limiter` - The `RequestLimiter` to use for all following requests.
+	pub fn set_request_limiter(&mut self, limiter: Arc<RequestLimiter>) {
+		self.request_limiter = Some(limiter);
+	}
+
+	/// Enables a progress bar, that shows the number of downloaded bytes.
+	pub fn enable_progress(&mut self) {
+		self.progress = Some(Mutex::new(get_progress_bar(&format!("downloading {}", self.name), 0)));
+	}
+
+	/// Returns the number of bytes that have been downloaded so far.
+	pub fn get_bytes_read(&self) -> u64 {
+		self.bytes_read.load(Ordering::Relaxed)
+	}
+
+	/// Returns the range from the readahead buffer, if a prefetched range contains it.
+	fn read_from_readahead(&self, range: &ByteRange) -> Result<Option<Blob>> {
+		let readahead = self.readahead.lock().unwrap();
+		for (buffered_range, blob) in readahead.iter() {
+			if buffered_range.offset <= range.offset
+				&& range.offset + range.length <= buffered_range.offset + buffered_range.length
+			{
+				return Ok(Some(
+					blob.read_range(&range.get_shifted_backward(buffered_range.offset))?,
+				));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Requests the range from the HTTP(S) endpoint.
+	async fn fetch_range(&self, range: &ByteRange) -> Result<Blob> {
+		let _permit = match &self.request_limiter {
+			Some(limiter) => Some(limiter.acquire().await),
+			None => None,
+		};
+
 		let mut request = Request::new(Method::GET, self.url.clone());
 		let request_range: String = format!("bytes={}-{}", range.offset, range.length + range.offset - 1);
 		request.headers_mut().append("range", request_range.parse()?);
@@ -107,7 +183,7 @@ impl DataReaderTrait for DataReaderHttp {
 		};
 
 		lazy_static! {
-			static ref RE_RANGE: Regex = RegexBuilder::new(r"^bytes (\d+)-(\d+)/\d+$")
+			static ref RE_RANGE: Regex = RegexBuilder::new(r"^bytes (\d+)-(\d+)/(\d+|\*)$")
 				.case_insensitive(true)
 				.build()
 				.unwrap();
@@ -115,9 +191,11 @@ impl DataReaderTrait for DataReaderHttp {
 
 		let content_range_start: u64;
 		let content_range_end: u64;
+		let content_length: Option<u64>;
 		if let Some(captures) = RE_RANGE.captures(content_range) {
 			content_range_start = captures.get(1).unwrap().as_str().parse::<u64>()?;
 			content_range_end = captures.get(2).unwrap().as_str().parse::<u64>()?;
+			content_length = captures.get(3).unwrap().as_str().parse::<u64>().ok();
 		} else {
 			bail!("format of content-range response is invalid: {content_range}");
 		}
@@ -131,9 +209,59 @@ impl DataReaderTrait for DataReaderHttp {
 		}
 
 		let bytes = response.bytes().await?;
+		let length = bytes.len() as u64;
+
+		self.bytes_read.fetch_add(length, Ordering::Relaxed);
+
+		if let Some(progress) = &self.progress {
+			let mut progress = progress.lock().unwrap();
+			if let Some(content_length) = content_length {
+				progress.set_max_value(content_length);
+			}
+			progress.inc(length);
+		}
+
+		if let Some(limiter) = &self.limiter {
+			limiter.throttle(length).await;
+		}
 
 		Ok(Blob::from(bytes.deref()))
 	}
+}
+
+#[async_trait]
+impl DataReaderTrait for DataReaderHttp {
+	/// Reads a specific range of bytes from the HTTP(S) endpoint, or from the readahead buffer.
+	///
+	/// # Arguments
+	///
+	/// * `range` - A ByteRange struct specifying the offset and length of the range to read.
+	///
+	/// # Returns
+	///
+	/// * A Result containing a Blob with the read data or an error.
+	async fn read_range(&self, range: &ByteRange) -> Result<Blob> {
+		if let Some(blob) = self.read_from_readahead(range)? {
+			return Ok(blob);
+		}
+		self.fetch_range(range).await
+	}
+
+	/// Fetches the range into the readahead buffer, replacing the oldest prefetched range if it is full.
+	///
+	/// Ranges that are already buffered, or longer than 16 MiB, are ignored.
+	async fn prefetch_range(&self, range: &ByteRange) -> Result<()> {
+		if range.length > READAHEAD_MAX_LENGTH || self.read_from_readahead(range)?.is_some() {
+			return Ok(());
+		}
+		let blob = self.fetch_range(range).await?;
+		let mut readahead = self.readahead.lock().unwrap();
+		if readahead.len() >= READAHEAD_SLOTS {
+			readahead.pop_front();
+		}
+		readahead.push_back((*range, blob));
+		Ok(())
+	}
 
 	/// Reads all the data from the HTTP(S) endpoint.
 	///
@@ -154,6 +282,24 @@ impl DataReaderTrait for DataReaderHttp {
 	}
 }
 
+impl Debug for DataReaderHttp {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DataReaderHttp")
+			.field("name", &self.name)
+			.field("limiter", &self.limiter)
+			.field("bytes_read", &self.get_bytes_read())
+			.finish()
+	}
+}
+
+impl Drop for DataReaderHttp {
+	fn drop(&mut self) {
+		if let Some(progress) = &self.progress {
+			progress.lock().unwrap().finish();
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -223,6 +369,67 @@ mod tests {
 			.unwrap_err();
 	}
 
+	#[test]
+	fn bandwidth_limiter_and_debug() -> Result<()> {
+		let url = Url::parse("https://www.example.com/").unwrap();
+		let mut data_reader_http = DataReaderHttp::from_url(url)?;
+		data_reader_http.set_bandwidth_limiter(Arc::new(BandwidthLimiter::new(1000)?));
+		data_reader_http.enable_progress();
+
+		assert_eq!(data_reader_http.get_bytes_read(), 0);
+		assert_eq!(
+			format!("{data_reader_http:?}"),
+			"DataReaderHttp { name: \"https://www.example.com/\", limiter: Some(BandwidthLimiter { bytes_per_second: 1000, total_bytes: 0 }), bytes_read: 0 }"
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn request_limiter() -> Result<()> {
+		let limiter = Arc::new(RequestLimiter::new(1)?);
+		let url = Url::parse("http://127.0.0.1:1/").unwrap();
+		let mut data_reader_http = DataReaderHttp::from_url(url)?;
+		data_reader_http.set_request_limiter(limiter.clone());
+
+		// the request waits while the only permit is taken
+		let permit = limiter.acquire().await;
+		let range = ByteRange::new(0, 10);
+		let result = tokio::time::timeout(Duration::from_millis(50), data_reader_http.read_range(&range)).await;
+		assert!(result.is_err());
+		assert_eq!(limiter.get_queue_depth(), 0);
+		drop(permit);
+
+		// the permit is returned, even if the request fails
+		assert!(data_reader_http.read_range(&range).await.is_err());
+		assert_eq!(limiter.get_active_requests(), 0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn readahead() -> Result<()> {
+		// nothing listens on this port, so every read must be served from the readahead buffer
+		let url = Url::parse("http://127.0.0.1:1/").unwrap();
+		let data_reader_http = DataReaderHttp::from_url(url)?;
+		data_reader_http
+			.readahead
+			.lock()
+			.unwrap()
+			.push_back((ByteRange::new(100, 10), Blob::from("0123456789")));
+
+		let blob = data_reader_http.read_range(&ByteRange::new(103, 4)).await?;
+		assert_eq!(blob.as_str(), "3456");
+		assert!(data_reader_http.read_range(&ByteRange::new(108, 4)).await.is_err());
+
+		// buffered and oversized ranges are not requested
+		data_reader_http.prefetch_range(&ByteRange::new(100, 10)).await?;
+		data_reader_http
+			.prefetch_range(&ByteRange::new(0, READAHEAD_MAX_LENGTH + 1))
+			.await?;
+		assert!(data_reader_http.prefetch_range(&ByteRange::new(0, 10)).await.is_err());
+		assert_eq!(data_reader_http.get_bytes_read(), 0);
+		Ok(())
+	}
+
 	// Test the 'get_name' method
 	#[test]
 	fn get_name() -> Result<()> {
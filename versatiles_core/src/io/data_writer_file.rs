@@ -15,7 +15,8 @@
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<()> {
-//!     let path = std::env::current_dir()?.join("../testdata/temp.txt");
+//!     let dir = assert_fs::TempDir::new()?;
+//!     let path = dir.path().join("data.bin");
 //!     let mut writer = DataWriterFile::from_path(&path)?;
 //!     let data = Blob::from(vec![1, 2, 3, 4]);
 //!
@@ -37,7 +38,7 @@ use crate::types::{Blob, ByteRange};
 use anyhow::{ensure, Result};
 use async_trait::async_trait;
 use std::{
-	fs::File,
+	fs::{File, OpenOptions},
 	io::{BufWriter, Seek, SeekFrom, Write},
 	path::Path,
 };
@@ -64,6 +65,24 @@ impl DataWriterFile {
 			writer: BufWriter::new(File::create(path)?),
 		})
 	}
+
+	/// Opens an existing file without truncating it. The write position is set to the end of the file.
+	///
+	/// # Arguments
+	///
+	/// * `path` - A reference to the file path to open.
+	///
+	/// # Returns
+	///
+	/// * A Result containing the new `DataWriterFile` instance or an error.
+	pub fn open_existing(path: &Path) -> Result<DataWriterFile> {
+		ensure!(path.is_absolute(), "path {path:?} must be absolute");
+		ensure!(path.is_file(), "path {path:?} must be a file");
+
+		let mut writer = BufWriter::new(OpenOptions::new().write(true).open(path)?);
+		writer.seek(SeekFrom::End(0))?;
+		Ok(DataWriterFile { writer })
+	}
 }
 
 #[async_trait]
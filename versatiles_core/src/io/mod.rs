@@ -17,6 +17,7 @@
 //! }
 //! ```
 
+mod bandwidth_limiter;
 mod data_reader;
 mod data_reader_blob;
 mod data_reader_file;
@@ -24,6 +25,7 @@ mod data_reader_http;
 mod data_writer;
 mod data_writer_blob;
 mod data_writer_file;
+mod request_limiter;
 mod value_reader;
 mod value_reader_blob;
 mod value_reader_file;
@@ -31,7 +33,9 @@ mod value_reader_slice;
 mod value_writer;
 mod value_writer_blob;
 mod value_writer_file;
+mod varint;
 
+pub use bandwidth_limiter::*;
 pub use data_reader::*;
 pub use data_reader_blob::*;
 pub use data_reader_file::*;
@@ -39,6 +43,7 @@ pub use data_reader_http::*;
 pub use data_writer::*;
 pub use data_writer_blob::*;
 pub use data_writer_file::*;
+pub use request_limiter::*;
 pub use value_reader::*;
 pub use value_reader_blob::*;
 pub use value_reader_file::*;
@@ -46,3 +51,4 @@ pub use value_reader_slice::*;
 pub use value_writer::*;
 pub use value_writer_blob::*;
 pub use value_writer_file::*;
+pub use varint::*;
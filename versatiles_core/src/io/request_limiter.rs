@@ -0,0 +1,176 @@
+//! This module provides the `RequestLimiter`, which limits the number of concurrent requests, e.g. to a remote server.
+//!
+//! # Overview
+//!
+//! Requests beyond the limit wait in a queue until a running request finishes, so that a traffic spike
+//! does not overwhelm the server. The number of running and waiting requests can be read at any time,
+//! e.g. for metrics. A limiter can be shared between multiple readers to limit their combined requests.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use versatiles_core::io::RequestLimiter;
+//! use anyhow::Result;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     // Allow 4 requests at the same time
+//!     let limiter = RequestLimiter::new(4)?;
+//!
+//!     // The request counts as running until the permit is dropped
+//!     let permit = limiter.acquire().await;
+//!     assert_eq!(limiter.get_active_requests(), 1);
+//!     drop(permit);
+//!
+//!     assert_eq!(limiter.get_active_requests(), 0);
+//!     Ok(())
+//! }
+//! ```
+
+use anyhow::{ensure, Result};
+use std::{
+	fmt::Debug,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// A limiter that restricts the number of requests running at the same time.
+pub struct RequestLimiter {
+	max_requests: usize,
+	semaphore: Semaphore,
+	queued: AtomicUsize,
+}
+
+impl RequestLimiter {
+	/// Creates a new `RequestLimiter`.
+	///
+	/// # Arguments
+	///
+	/// * `max_requests` - The maximum number of requests running at the same time.
+	///
+	/// # Returns
+	///
+	/// * A Result containing the `RequestLimiter` or an error, if the maximum is zero.
+	pub fn new(max_requests: usize) -> Result<RequestLimiter> {
+		ensure!(max_requests > 0, "maximum number of requests must be greater than 0");
+		Ok(RequestLimiter {
+			max_requests,
+			semaphore: Semaphore::new(max_requests),
+			queued: AtomicUsize::new(0),
+		})
+	}
+
+	/// Returns the maximum number of requests running at the same time.
+	pub fn get_max_requests(&self) -> usize {
+		self.max_requests
+	}
+
+	/// Returns the number of requests that are running.
+	pub fn get_active_requests(&self) -> usize {
+		self.max_requests - self.semaphore.available_permits()
+	}
+
+	/// Returns the number of requests that are waiting for a running request to finish.
+	pub fn get_queue_depth(&self) -> usize {
+		self.queued.load(Ordering::Relaxed)
+	}
+
+	/// Waits until a request may start.
+	///
+	/// # Returns
+	///
+	/// * A permit. The request counts as running until the permit is dropped.
+	pub async fn acquire(&self) -> SemaphorePermit<'_> {
+		// the guard also leaves the queue if the waiting future is dropped
+		let _queued = QueueGuard::new(&self.queued);
+		self
+			.semaphore
+			.acquire()
+			.await
+			.expect("semaphore should never be closed")
+	}
+}
+
+impl Debug for RequestLimiter {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("RequestLimiter")
+			.field("max_requests", &self.max_requests)
+			.field("active_requests", &self.get_active_requests())
+			.field("queue_depth", &self.get_queue_depth())
+			.finish()
+	}
+}
+
+/// Counts a waiting request as long as it exists.
+struct QueueGuard<'a>(&'a AtomicUsize);
+
+impl<'a> QueueGuard<'a> {
+	fn new(counter: &'a AtomicUsize) -> Self {
+		counter.fetch_add(1, Ordering::Relaxed);
+		QueueGuard(counter)
+	}
+}
+
+impl Drop for QueueGuard<'_> {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{sync::Arc, time::Duration};
+
+	#[test]
+	fn new() {
+		assert!(RequestLimiter::new(0).is_err());
+		let limiter = RequestLimiter::new(3).unwrap();
+		assert_eq!(limiter.get_max_requests(), 3);
+		assert_eq!(limiter.get_active_requests(), 0);
+		assert_eq!(limiter.get_queue_depth(), 0);
+	}
+
+	#[tokio::test]
+	async fn queue() {
+		let limiter = Arc::new(RequestLimiter::new(2).unwrap());
+		let permit1 = limiter.acquire().await;
+		let permit2 = limiter.acquire().await;
+		assert_eq!(limiter.get_active_requests(), 2);
+
+		let waiting = tokio::spawn({
+			let limiter = limiter.clone();
+			async move {
+				let _permit = limiter.acquire().await;
+			}
+		});
+		tokio::time::sleep(Duration::from_millis(20)).await;
+		assert_eq!(limiter.get_queue_depth(), 1);
+
+		drop(permit1);
+		waiting.await.unwrap();
+		assert_eq!(limiter.get_queue_depth(), 0);
+		assert_eq!(limiter.get_active_requests(), 1);
+
+		// requests that stop waiting leave the queue
+		let result = tokio::time::timeout(Duration::from_millis(20), async {
+			let _permit1 = limiter.acquire().await;
+			let _permit3 = limiter.acquire().await;
+		})
+		.await;
+		assert!(result.is_err());
+		assert_eq!(limiter.get_queue_depth(), 0);
+
+		drop(permit2);
+		assert_eq!(limiter.get_active_requests(), 0);
+	}
+
+	#[test]
+	fn debug() {
+		let limiter = RequestLimiter::new(4).unwrap();
+		assert_eq!(
+			format!("{limiter:?}"),
+			"RequestLimiter { max_requests: 4, active_requests: 0, queue_depth: 0 }"
+		);
+	}
+}
@@ -6,7 +6,8 @@
 //! either little-endian or big-endian byte order. It implements the `ValueReader` trait to provide
 //! methods for reading integers, floating-point numbers, and other types of data from the slice. The
 //! module also provides methods for managing the read position and creating sub-readers for reading
-//! specific portions of the data.
+//! specific portions of the data. Protocol buffer fields can also be read without copying, as slices
+//! that borrow from the data.
 //!
 //! # Examples
 //!
@@ -31,10 +32,10 @@
 
 #![allow(dead_code)]
 
-use super::{SeekRead, ValueReader};
-use anyhow::{anyhow, bail, Result};
+use super::{decode_varint, SeekRead, ValueReader};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
-use std::{io::Cursor, marker::PhantomData};
+use std::{io::Cursor, marker::PhantomData, str::from_utf8};
 
 /// A struct that provides reading capabilities from a byte slice using a specified byte order.
 pub struct ValueReaderSlice<'a, E: ByteOrder> {
@@ -60,6 +61,30 @@ impl<'a, E: ByteOrder> ValueReaderSlice<'a, E> {
 			cursor: Cursor::new(slice),
 		}
 	}
+
+	/// Reads a length-delimited protocol buffer field as a slice that borrows from the data.
+	///
+	/// # Errors
+	///
+	/// * Returns an error if the length can't be read or exceeds the remaining data.
+	pub fn read_pbf_slice(&mut self) -> Result<&'a [u8]> {
+		let data: &'a [u8] = self.cursor.get_ref();
+		let mut start = self.cursor.position() as usize;
+		let length = decode_varint(data, &mut start).context("Failed to read varint for slice length")?;
+		let end = start.saturating_add(usize::try_from(length)?);
+		ensure!(end <= data.len(), "Length exceeds remaining data");
+		self.cursor.set_position(end as u64);
+		Ok(&data[start..end])
+	}
+
+	/// Reads a protocol buffer string as a `&str` that borrows from the data.
+	///
+	/// # Errors
+	///
+	/// * Returns an error if the string can't be read or isn't valid UTF-8.
+	pub fn read_pbf_str(&mut self) -> Result<&'a str> {
+		from_utf8(self.read_pbf_slice()?).context("Invalid UTF-8 string")
+	}
 }
 
 impl<'a> ValueReaderSlice<'a, LittleEndian> {
@@ -107,6 +132,13 @@ impl<'a, E: ByteOrder + 'a> ValueReader<'a, E> for ValueReaderSlice<'a, E> {
 		self.cursor.position()
 	}
 
+	fn read_varint(&mut self) -> Result<u64> {
+		let mut position = self.cursor.position() as usize;
+		let value = decode_varint(self.cursor.get_ref(), &mut position)?;
+		self.cursor.set_position(position as u64);
+		Ok(value)
+	}
+
 	fn set_position(&mut self, position: u64) -> Result<()> {
 		if position >= self.len {
 			bail!("set position outside length")
@@ -171,6 +203,18 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_read_pbf_slice() -> Result<()> {
+		let data = vec![0x02, b'h', b'i', 0x01, 0xFF, 0x05, 0x00];
+		let mut reader = ValueReaderSlice::new_le(&data);
+
+		assert_eq!(reader.read_pbf_str()?, "hi");
+		assert!(reader.read_pbf_str().is_err()); // Invalid UTF-8
+		assert_eq!(reader.position(), 5);
+		assert!(reader.read_pbf_slice().is_err()); // Exceeds remaining data
+		Ok(())
+	}
+
 	#[test]
 	fn test_read_u8() -> Result<()> {
 		let blob = vec![0x01, 0x02];
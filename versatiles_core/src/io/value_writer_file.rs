@@ -15,7 +15,8 @@
 //! use std::fs::File;
 //!
 //! fn main() -> Result<()> {
-//!     let path = std::env::current_dir()?.join("../testdata/temp.txt");
+//!     let dir = assert_fs::TempDir::new()?;
+//!     let path = dir.path().join("values.bin");
 //!     let file = File::create(&path)?;
 //!     let mut writer = ValueWriterFile::new_le(file);
 //!
@@ -0,0 +1,68 @@
+//! Decoding of varints, as used by protocol buffers, directly from a byte slice.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use versatiles_core::io::decode_varint;
+//! use anyhow::Result;
+//!
+//! fn main() -> Result<()> {
+//!     let data = &[0xAC, 0x02, 0x01];
+//!     let mut position = 0;
+//!
+//!     assert_eq!(decode_varint(data, &mut position)?, 300);
+//!     assert_eq!(decode_varint(data, &mut position)?, 1);
+//!     assert_eq!(position, 3);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+
+/// Decodes the varint at `position` and moves `position` behind it.
+///
+/// # Errors
+/// Returns an error if the data ends within the varint or if the varint is longer than 10 bytes.
+pub fn decode_varint(data: &[u8], position: &mut usize) -> Result<u64> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let byte = *data.get(*position).ok_or_else(|| anyhow!("Unexpected end of data"))?;
+		*position += 1;
+		value |= ((byte & 0x7F) as u64) << shift;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	bail!("Varint too long")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_decode_varint() -> Result<()> {
+		let data = [
+			0x00, 0x7F, 0xAC, 0x02, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01,
+		];
+		let mut position = 0;
+		assert_eq!(decode_varint(&data, &mut position)?, 0);
+		assert_eq!(decode_varint(&data, &mut position)?, 127);
+		assert_eq!(decode_varint(&data, &mut position)?, 300);
+		assert_eq!(decode_varint(&data, &mut position)?, u64::MAX);
+		assert_eq!(position, data.len());
+		Ok(())
+	}
+
+	#[test]
+	fn test_decode_varint_errors() {
+		let mut position = 0;
+		let error = decode_varint(&[0x80, 0x80], &mut position).unwrap_err();
+		assert_eq!(error.to_string(), "Unexpected end of data");
+
+		let mut position = 0;
+		let error = decode_varint(&[0x80; 11], &mut position).unwrap_err();
+		assert_eq!(error.to_string(), "Varint too long");
+	}
+}
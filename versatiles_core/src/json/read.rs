@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use super::JsonValue;
+use crate::utils::get_thread_count;
 use anyhow::{anyhow, Context, Error, Result};
 use futures::{future::ready, stream, Stream, StreamExt};
 use std::io::BufRead;
@@ -22,7 +23,7 @@ pub fn read_ndjson_iter(reader: impl BufRead) -> impl Iterator<Item = Result<Jso
 pub fn read_ndjson_stream(reader: impl BufRead) -> impl Stream<Item = Result<JsonValue>> {
 	stream::iter(reader.lines().enumerate())
 		.map(|(index, line)| tokio::spawn(async move { process_line(line, index) }))
-		.buffered(num_cpus::get())
+		.buffered(get_thread_count())
 		.filter_map(|f| {
 			ready(match f {
 				Ok(value) => value,
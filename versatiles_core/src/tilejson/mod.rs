@@ -39,7 +39,7 @@ use anyhow::{anyhow, ensure, Ok, Result};
 use regex::Regex;
 use std::fmt::Debug;
 use value::TileJsonValues;
-use vector_layer::VectorLayers;
+pub use vector_layer::{VectorLayer, VectorLayers};
 
 /// A struct representing a TileJSON object.
 ///
@@ -163,6 +163,7 @@ impl TileJSON {
 		if let Some(z) = pyramid.get_zoom_max() {
 			self.limit_max_zoom(z);
 		}
+		self.limit_center();
 	}
 
 	// -------------------------------------------------------------------------
@@ -203,6 +204,27 @@ impl TileJSON {
 		Ok(())
 	}
 
+	/// Returns the tile grid stored in the `tile_grid` field, defaulting to Web Mercator.
+	///
+	/// # Errors
+	/// Returns an error if `tile_grid` can not be parsed.
+	pub fn get_tile_grid(&self) -> Result<TileGrid> {
+		match self.values.get_str("tile_grid") {
+			Some(value) => TileGrid::parse_str(value),
+			None => Ok(TileGrid::WebMercator),
+		}
+	}
+
+	/// Stores `grid` in the `tile_grid` field. Web Mercator is the default, so its field is removed.
+	pub fn set_tile_grid(&mut self, grid: &TileGrid) -> Result<()> {
+		if grid.is_web_mercator() {
+			self.values.remove("tile_grid");
+			Ok(())
+		} else {
+			self.set_string("tile_grid", &grid.to_string())
+		}
+	}
+
 	// -------------------------------------------------------------------------
 	// Bounds and Zoom Limits
 	// -------------------------------------------------------------------------
@@ -252,6 +274,39 @@ impl TileJSON {
 		self.values.update_byte("maxzoom", |mz| mz.map_or(z, |mz| mz.min(z)));
 	}
 
+	/// Keeps `center` consistent with `bounds`, `minzoom` and `maxzoom`.
+	///
+	/// A center outside of the bounds is moved to the middle of the bounds, and its zoom level is clamped
+	/// to the zoom range. Otherwise, clients like MapLibre may start at a position without any tiles.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::{tilejson::*, types::{GeoBBox, GeoCenter}};
+	/// let mut tj = TileJSON::default();
+	/// tj.bounds = Some(GeoBBox(10.0, 50.0, 14.0, 54.0));
+	/// tj.center = Some(GeoCenter(0.0, 0.0, 2));
+	/// tj.set_byte("minzoom", 5).unwrap();
+	/// tj.limit_center();
+	/// assert_eq!(tj.center, Some(GeoCenter(12.0, 52.0, 5)));
+	/// ```
+	pub fn limit_center(&mut self) {
+		let Some(center) = &mut self.center else {
+			return;
+		};
+		if let Some(b) = self.bounds {
+			if center.0 < b.0 || center.0 > b.2 || center.1 < b.1 || center.1 > b.3 {
+				center.0 = (b.0 + b.2) / 2.0;
+				center.1 = (b.1 + b.3) / 2.0;
+			}
+		}
+		if let Some(z) = self.values.get_byte("minzoom") {
+			center.2 = center.2.max(z);
+		}
+		if let Some(z) = self.values.get_byte("maxzoom") {
+			center.2 = center.2.min(z);
+		}
+	}
+
 	// -------------------------------------------------------------------------
 	// Merging
 	// -------------------------------------------------------------------------
@@ -301,6 +356,20 @@ impl TileJSON {
 		Ok(())
 	}
 
+	/// Overwrites fields with the entries of a [`JsonObject`].
+	///
+	/// Unlike [`merge`], every given key replaces the existing value, including
+	/// `"bounds"`, `"center"`, `"minzoom"`, `"maxzoom"` and `"vector_layers"`.
+	///
+	/// # Errors
+	/// Returns an error if a value is invalid for its key, e.g. non-numeric bounds.
+	pub fn assign(&mut self, object: &JsonObject) -> Result<()> {
+		let mut result = self.as_object();
+		result.assign(object.clone())?;
+		*self = TileJSON::from_object(&result)?;
+		Ok(())
+	}
+
 	// -------------------------------------------------------------------------
 	// Validation
 	// -------------------------------------------------------------------------
@@ -374,6 +443,10 @@ impl TileJSON {
 			ensure!(Regex::new(r"^\d+\.\d+\.\d+$")?.is_match(&v), "Invalid version number");
 		}
 
+		// tile_grid - optional, VersaTiles extension
+		self.values.check_optional_string("tile_grid")?;
+		self.get_tile_grid()?;
+
 		Ok(())
 	}
 
@@ -500,6 +573,22 @@ mod tests {
 		obj
 	}
 
+	#[test]
+	fn should_assign_object() -> Result<()> {
+		let mut tj = TileJSON::from_object(&make_test_json_object())?;
+		tj.values.insert("maxzoom", &JsonValue::from(14u8))?;
+
+		let overrides = JsonObject::parse_str(r#"{"name":"Berlin","maxzoom":10,"bounds":[13,52,14,53]}"#)?;
+		tj.assign(&overrides)?;
+		assert_eq!(tj.values.get_string("name"), Some("Berlin".to_string()));
+		assert_eq!(tj.values.get_byte("maxzoom"), Some(10));
+		assert_eq!(tj.bounds, Some(GeoBBox(13.0, 52.0, 14.0, 53.0)));
+		assert!(tj.center.is_some());
+
+		assert!(tj.assign(&JsonObject::parse_str(r#"{"bounds":"invalid"}"#)?).is_err());
+		Ok(())
+	}
+
 	#[test]
 	fn should_parse_basic_tilejson_from_object() -> Result<()> {
 		let obj = make_test_json_object();
@@ -579,6 +668,24 @@ mod tests {
 		assert_eq!(tj.values.get_byte("maxzoom"), Some(12));
 	}
 
+	#[test]
+	fn should_update_center_from_pyramid() {
+		let mut tj = TileJSON {
+			center: Some(GeoCenter(13.4, 52.5, 14)),
+			..Default::default()
+		};
+		tj.update_from_pyramid(&TileBBoxPyramid::from_geo_bbox(2, 10, &GeoBBox(13.0, 52.0, 14.0, 53.0)));
+		assert_eq!(tj.center, Some(GeoCenter(13.4, 52.5, 10)));
+
+		let mut tj = TileJSON {
+			center: Some(GeoCenter(13.4, 52.5, 0)),
+			..Default::default()
+		};
+		tj.update_from_pyramid(&TileBBoxPyramid::from_geo_bbox(8, 8, &GeoBBox(0.0, 0.0, 1.0, 1.0)));
+		let b = tj.bounds.unwrap();
+		assert_eq!(tj.center, Some(GeoCenter((b.0 + b.2) / 2.0, (b.1 + b.3) / 2.0, 8)));
+	}
+
 	#[test]
 	fn should_parse_valid_tilejson_from_string() -> Result<()> {
 		let json_text = r#"
@@ -619,4 +726,23 @@ mod tests {
 			"Debug string should contain 'TileJSON(' prefix"
 		);
 	}
+
+	#[test]
+	fn should_get_and_set_tile_grid() -> Result<()> {
+		let mut tj = TileJSON::default();
+		assert_eq!(tj.get_tile_grid()?, TileGrid::WebMercator);
+
+		tj.set_tile_grid(&TileGrid::Wgs84)?;
+		assert_eq!(tj.get_str("tile_grid"), Some("wgs84"));
+		assert_eq!(tj.get_tile_grid()?, TileGrid::Wgs84);
+		tj.check_raster()?;
+
+		tj.set_tile_grid(&TileGrid::WebMercator)?;
+		assert_eq!(tj.get_str("tile_grid"), None);
+
+		tj.set_string("tile_grid", "mars")?;
+		assert!(tj.get_tile_grid().is_err());
+		assert!(tj.check_raster().is_err());
+		Ok(())
+	}
 }
@@ -22,6 +22,11 @@ impl TileJsonValues {
 		Ok(())
 	}
 
+	/// Removes the value of `key`, if present.
+	pub fn remove(&mut self, key: &str) {
+		self.0.remove(key);
+	}
+
 	/// Returns a reference to the inner `str` value if this key exists as a string variant,
 	/// otherwise returns `None`.
 	///
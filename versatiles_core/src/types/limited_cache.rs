@@ -1,8 +1,9 @@
 //! This module provides a generic limited cache that stores key-value pairs up to a specified byte size limit.
 //!
-//! The `LimitedCache` manages entries in a manner resembling an LRU cache, ensuring it does not exceed
-//! a predefined number of elements (derived from the byte size limit). Once the limit is reached,
-//! least-recently accessed items are removed using a custom cleanup method.
+//! The `LimitedCache` manages entries in a manner resembling an LRU cache, ensuring that the total size of
+//! its elements does not exceed the byte size limit. By default every element has the size of `(K, V)`, but
+//! the size can also be calculated for every element, e.g. for values that own heap memory. Once the limit
+//! is reached, least-recently accessed items are removed using a custom cleanup method.
 
 use anyhow::Result;
 use std::{collections::HashMap, fmt::Debug, hash::Hash, mem::size_of, ops::Div};
@@ -36,8 +37,12 @@ use std::{collections::HashMap, fmt::Debug, hash::Hash, mem::size_of, ops::Div};
 pub struct LimitedCache<K, V> {
 	/// Internal map storing (value, "last access index") pairs.
 	cache: HashMap<K, (V, u64)>,
-	/// Derived maximum number of elements the cache can hold.
-	max_length: usize,
+	/// Maximum total size of all elements in bytes.
+	maximum_size: usize,
+	/// Current total size of all elements in bytes.
+	size: usize,
+	/// Calculates the size of an element in bytes.
+	get_size: fn(&K, &V) -> usize,
 	/// A monotonically increasing index to track access recency.
 	last_index: u64,
 }
@@ -49,8 +54,7 @@ where
 {
 	/// Creates a new `LimitedCache` with a specified maximum **byte** size.
 	///
-	/// Internally, it computes how many `(K, V)` pairs can fit into that byte size,
-	/// based on `size_of::<K>() + size_of::<V>()`.
+	/// Every `(K, V)` pair counts as `size_of::<K>() + size_of::<V>()` bytes.
 	///
 	/// # Arguments
 	/// * `maximum_size` - The total byte size the cache is allowed to occupy.
@@ -69,14 +73,37 @@ where
 	pub fn with_maximum_size(maximum_size: usize) -> Self {
 		// Compute how many (K, V) pairs can fit into `maximum_size`.
 		let per_element_size = size_of::<K>() + size_of::<V>();
-		let max_length = maximum_size.div(per_element_size);
-		if max_length < 1 {
+		if maximum_size.div(per_element_size) < 1 {
 			panic!("size ({maximum_size} bytes) is too small to store a single element of size {per_element_size} bytes");
 		}
 
+		Self::with_size_function(maximum_size, |_, _| size_of::<K>() + size_of::<V>())
+	}
+
+	/// Creates a new `LimitedCache` with a specified maximum **byte** size, where `get_size`
+	/// calculates the size of every `(K, V)` pair in bytes.
+	///
+	/// # Arguments
+	/// * `maximum_size` - The total byte size the cache is allowed to occupy.
+	/// * `get_size` - Returns the size of an element in bytes.
+	///
+	/// # Examples
+	///
+	/// ```rust
+	/// use versatiles_core::types::LimitedCache;
+	///
+	/// let mut cache: LimitedCache<u32, String> = LimitedCache::with_size_function(1_000, |_, v| v.len());
+	/// cache.add(1, "a".repeat(600));
+	/// cache.add(2, "b".repeat(600));
+	/// assert_eq!(cache.get(&1), None);
+	/// assert!(cache.get(&2).is_some());
+	/// ```
+	pub fn with_size_function(maximum_size: usize, get_size: fn(&K, &V) -> usize) -> Self {
 		Self {
 			cache: HashMap::new(),
-			max_length,
+			maximum_size,
+			size: 0,
+			get_size,
 			last_index: 0,
 		}
 	}
@@ -156,7 +183,7 @@ where
 	///
 	/// - Increments `last_index`.
 	/// - Stores `(value, last_index)` in the internal map.  
-	/// - If adding triggers the capacity limit, it runs `cleanup()` to evict items.
+	/// - If adding exceeds the size limit, it runs `cleanup()` to evict items.
 	///
 	/// # Examples
 	///
@@ -168,13 +195,19 @@ where
 	/// assert_eq!(inserted, 123);
 	/// ```
 	pub fn add(&mut self, key: K, value: V) -> V {
-		if self.cache.len() >= self.max_length {
+		if let Some((existing, _)) = self.cache.get(&key) {
+			return existing.clone();
+		}
+
+		let element_size = (self.get_size)(&key, &value);
+		while !self.cache.is_empty() && self.size + element_size > self.maximum_size {
 			self.cleanup();
 		}
 
 		self.last_index += 1;
-		// Insert or replace. The 0.0 clone is just to ensure a consistent return type
-		self.cache.entry(key).or_insert((value, self.last_index)).0.clone()
+		self.size += element_size;
+		self.cache.insert(key, (value.clone(), self.last_index));
+		value
 	}
 
 	/// Removes the least recently accessed items if the cache has reached capacity.
@@ -190,6 +223,9 @@ where
 	/// sense. If you want a more standard LRU, consider a different data structure or
 	/// approach (like `hash_linked::LRUCache`).
 	fn cleanup(&mut self) {
+		if self.cache.is_empty() {
+			return;
+		}
 		let mut indices: Vec<u64> = self.cache.values().map(|(_, i)| *i).collect();
 		indices.sort_unstable();
 		let median_index = indices[indices.len().div(2)];
@@ -203,6 +239,7 @@ where
 				true
 			}
 		});
+		self.size = self.cache.iter().map(|(k, (v, _))| (self.get_size)(k, v)).sum();
 	}
 }
 
@@ -210,7 +247,8 @@ impl<K, V> Debug for LimitedCache<K, V> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("LimitedCache")
 			.field("length", &self.cache.len())
-			.field("max_length", &self.max_length)
+			.field("size", &self.size)
+			.field("maximum_size", &self.maximum_size)
 			.field("last_index", &self.last_index)
 			.finish()
 	}
@@ -222,16 +260,23 @@ mod tests {
 	use anyhow::{anyhow, Result};
 	use std::mem::size_of;
 
-	/// Ensures that creation with a given `maximum_size` sets the derived max_length appropriately.
+	/// Ensures that creation with a given `maximum_size` fits the expected number of elements.
 	#[test]
 	fn test_cache_initialization() {
 		// Each (u64, i32) pair consumes size_of::<u64>() + size_of::<i32>() bytes.
 		let element_size = size_of::<u64>() + size_of::<i32>();
 		// Suppose we allow 100 bytes.
 		let maximum_size = 100;
-		let cache: LimitedCache<u64, i32> = LimitedCache::with_maximum_size(maximum_size);
-		let expected_max_len = maximum_size / element_size;
-		assert_eq!(cache.max_length, expected_max_len);
+		let mut cache: LimitedCache<u64, i32> = LimitedCache::with_maximum_size(maximum_size);
+		let expected_max_len = (maximum_size / element_size) as u64;
+		for i in 0..expected_max_len {
+			cache.add(i, 0);
+		}
+		assert_eq!(cache.size, expected_max_len as usize * element_size);
+		assert_eq!(cache.cache.len() as u64, expected_max_len);
+		// one more element triggers the cleanup
+		cache.add(expected_max_len, 0);
+		assert!((cache.cache.len() as u64) < expected_max_len);
 	}
 
 	/// Ensures that we can store and retrieve values, and `None` is returned for absent keys.
@@ -293,6 +338,30 @@ mod tests {
 		test(9, &[0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
 	}
 
+	/// Ensures that elements are weighted by their calculated size.
+	#[test]
+	fn test_size_function() {
+		let mut cache: LimitedCache<u8, Vec<u8>> = LimitedCache::with_size_function(100, |_, v| v.len());
+		cache.add(1, vec![0; 40]);
+		cache.add(2, vec![0; 40]);
+		assert_eq!(cache.size, 80);
+
+		// adding an existing key keeps the first value
+		assert_eq!(cache.add(2, vec![1; 90]), vec![0; 40]);
+		assert_eq!(cache.size, 80);
+
+		// a large element evicts the others
+		cache.add(3, vec![0; 90]);
+		assert_eq!(cache.get(&1), None);
+		assert_eq!(cache.get(&2), None);
+		assert_eq!(cache.size, 90);
+
+		// an element above the limit is still stored
+		cache.add(4, vec![0; 200]);
+		assert_eq!(cache.get(&3), None);
+		assert_eq!(cache.get(&4).map(|v| v.len()), Some(200));
+	}
+
 	/// Ensures that `with_maximum_size` panics if the size is too small to store even a single `(K, V)`.
 	#[test]
 	#[should_panic(expected = "size")]
@@ -307,10 +376,10 @@ mod tests {
 	fn test_debug_format() {
 		let cache: LimitedCache<u8, u8> = LimitedCache::with_maximum_size(10);
 		let debug_str = format!("{:?}", cache);
-		// Example: "LimitedCache { length: 0, max_length: 5, last_index: 0 }"
+		// Example: "LimitedCache { length: 0, size: 0, maximum_size: 10, last_index: 0 }"
 		assert!(debug_str.contains("LimitedCache"));
 		assert!(debug_str.contains("length"));
-		assert!(debug_str.contains("max_length"));
+		assert!(debug_str.contains("maximum_size"));
 		assert!(debug_str.contains("last_index"));
 	}
 }
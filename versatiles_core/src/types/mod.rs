@@ -33,6 +33,12 @@ pub use tile_coords::*;
 mod tile_format;
 pub use tile_format::*;
 
+mod tile_grid;
+pub use tile_grid::*;
+
+mod tile_scheme;
+pub use tile_scheme::*;
+
 mod tile_stream;
 pub use tile_stream::*;
 
@@ -41,3 +47,6 @@ pub use tiles_reader_parameters::*;
 
 mod tiles_reader;
 pub use tiles_reader::*;
+
+mod try_tile_stream;
+pub use try_tile_stream::*;
@@ -7,7 +7,7 @@
 //! It supports operations such as inclusion, intersection, scaling, and iteration over tile coordinates.
 //! This is particularly useful in mapping applications where tile management is essential.
 
-use super::{GeoBBox, TileBBoxPyramid, TileCoord2, TileCoord3};
+use super::{GeoBBox, TileBBoxPyramid, TileCoord2, TileCoord3, TileGrid};
 use anyhow::{ensure, Result};
 use itertools::Itertools;
 use std::{
@@ -26,7 +26,8 @@ use std::{
 /// - `level`: Zoom level (0..=31).
 /// - `x_min`, `y_min`: Minimum tile coordinates.
 /// - `x_max`, `y_max`: Maximum tile coordinates.
-/// - `max`: Largest valid coordinate at the given zoom level (`2^level - 1`).
+/// - `max`: Largest valid y coordinate at the given zoom level (`2^level - 1` for Web Mercator).
+/// - `grid`: Tile grid the coordinates refer to.
 #[derive(Clone, PartialEq, Eq)]
 pub struct TileBBox {
 	/// Zoom level of the bounding box.
@@ -41,6 +42,8 @@ pub struct TileBBox {
 	pub y_max: u32,
 	/// Maximum valid coordinate based on zoom level.
 	pub max: u32,
+	/// Tile grid of the bounding box.
+	pub grid: TileGrid,
 }
 
 #[allow(dead_code)]
@@ -70,11 +73,19 @@ impl TileBBox {
 	/// - If any coordinate exceeds the maximum allowed by the zoom level.
 	/// - If `x_min > x_max` or `y_min > y_max`.
 	pub fn new(level: u8, x_min: u32, y_min: u32, x_max: u32, y_max: u32) -> Result<TileBBox> {
+		Self::new_in_grid(TileGrid::WebMercator, level, x_min, y_min, x_max, y_max)
+	}
+
+	/// Creates a new `TileBBox` in a specific tile grid.
+	///
+	/// Like [`TileBBox::new`], but the coordinates are validated against the size of `grid`.
+	pub fn new_in_grid(grid: TileGrid, level: u8, x_min: u32, y_min: u32, x_max: u32, y_max: u32) -> Result<TileBBox> {
 		ensure!(level <= 31, "level ({level}) must be <= 31");
 
-		let max = 2u32.pow(level as u32) - 1;
+		let max_column = grid.max_column(level);
+		let max = grid.max_row(level);
 
-		ensure!(x_max <= max, "x_max ({x_max}) must be <= max ({max})");
+		ensure!(x_max <= max_column, "x_max ({x_max}) must be <= max ({max_column})");
 		ensure!(y_max <= max, "y_max ({y_max}) must be <= max ({max})");
 		ensure!(x_min <= x_max, "x_min ({x_min}) must be <= x_max ({x_max})");
 		ensure!(y_min <= y_max, "y_min ({y_min}) must be <= y_max ({y_max})");
@@ -82,6 +93,7 @@ impl TileBBox {
 		let bbox = TileBBox {
 			level,
 			max,
+			grid,
 			x_min,
 			y_min,
 			x_max,
@@ -102,9 +114,13 @@ impl TileBBox {
 	/// * `Ok(TileBBox)` if creation is successful.
 	/// * `Err(anyhow::Error)` if the zoom level is invalid.
 	pub fn new_full(level: u8) -> Result<TileBBox> {
+		Self::new_full_in_grid(TileGrid::WebMercator, level)
+	}
+
+	/// Creates a `TileBBox` covering all tiles of `grid` at the specified zoom level.
+	pub fn new_full_in_grid(grid: TileGrid, level: u8) -> Result<TileBBox> {
 		ensure!(level <= 31, "level ({level}) must be <= 31");
-		let max = 2u32.pow(level as u32) - 1;
-		Self::new(level, 0, 0, max, max)
+		Self::new_in_grid(grid, level, 0, 0, grid.max_column(level), grid.max_row(level))
 	}
 
 	/// Creates an empty `TileBBox` at the specified zoom level.
@@ -120,12 +136,18 @@ impl TileBBox {
 	/// * `Ok(TileBBox)` representing an empty bounding box.
 	/// * `Err(anyhow::Error)` if the zoom level is invalid.
 	pub fn new_empty(level: u8) -> Result<TileBBox> {
+		Self::new_empty_in_grid(TileGrid::WebMercator, level)
+	}
+
+	/// Creates an empty `TileBBox` in `grid` at the specified zoom level.
+	pub fn new_empty_in_grid(grid: TileGrid, level: u8) -> Result<TileBBox> {
 		ensure!(level <= 31, "level ({level}) must be <= 31");
-		let max = 2u32.pow(level as u32) - 1;
+		let max = grid.max_row(level);
 		Ok(TileBBox {
 			level,
 			max,
-			x_min: max + 1,
+			grid,
+			x_min: grid.max_column(level).saturating_add(1),
 			y_min: max + 1,
 			x_max: 0,
 			y_max: 0,
@@ -161,6 +183,13 @@ impl TileBBox {
 		Self::new(level, p_min.x, p_min.y, p_max.x, p_max.y)
 	}
 
+	/// Constructs a `TileBBox` in `grid` from geographical coordinates.
+	///
+	/// See [`TileGrid::bbox_from_geo`].
+	pub fn from_geo_in_grid(grid: TileGrid, level: u8, bbox: &GeoBBox) -> Result<TileBBox> {
+		grid.bbox_from_geo(level, bbox)
+	}
+
 	// -------------------------------------------------------------------------
 	// Basic Queries
 	// -------------------------------------------------------------------------
@@ -225,7 +254,16 @@ impl TileBBox {
 	/// This method is primarily used for testing purposes.
 	#[cfg(test)]
 	pub fn is_full(&self) -> bool {
-		!self.is_empty() && self.x_min == 0 && self.y_min == 0 && self.x_max == self.max && self.y_max == self.max
+		!self.is_empty()
+			&& self.x_min == 0
+			&& self.y_min == 0
+			&& self.x_max == self.max_column()
+			&& self.y_max == self.max
+	}
+
+	/// Returns the largest valid x coordinate at this zoom level.
+	fn max_column(&self) -> u32 {
+		self.grid.max_column(self.level)
 	}
 
 	// -------------------------------------------------------------------------
@@ -291,7 +329,7 @@ impl TileBBox {
 	pub fn set_full(&mut self) {
 		self.x_min = 0;
 		self.y_min = 0;
-		self.x_max = self.max;
+		self.x_max = self.max_column();
 		self.y_max = self.max;
 	}
 
@@ -319,7 +357,7 @@ impl TileBBox {
 			// Expand bounding box to include the new coordinate
 			self.x_min = self.x_min.min(x);
 			self.y_min = self.y_min.min(y);
-			self.x_max = self.x_max.max(x).min(self.max);
+			self.x_max = self.x_max.max(x).min(self.max_column());
 			self.y_max = self.y_max.max(y).min(self.max);
 		}
 	}
@@ -370,7 +408,7 @@ impl TileBBox {
 		if !self.is_empty() {
 			self.x_min = self.x_min.saturating_sub(x_min);
 			self.y_min = self.y_min.saturating_sub(y_min);
-			self.x_max = (self.x_max + x_max).min(self.max);
+			self.x_max = self.x_max.saturating_add(x_max).min(self.max_column());
 			self.y_max = (self.y_max + y_max).min(self.max);
 		}
 	}
@@ -401,6 +439,14 @@ impl TileBBox {
 		}
 
 		if !bbox.is_empty() {
+			if !self.is_empty() {
+				ensure!(
+					self.grid == bbox.grid,
+					"Cannot include TileBBox in grid {} into TileBBox in grid {}",
+					bbox.grid,
+					self.grid
+				);
+			}
 			if self.is_empty() {
 				// If current bounding box is empty, adopt the other bounding box
 				*self = bbox.clone();
@@ -408,7 +454,7 @@ impl TileBBox {
 				// Expand to include the other bounding box
 				self.x_min = self.x_min.min(bbox.x_min);
 				self.y_min = self.y_min.min(bbox.y_min);
-				self.x_max = self.x_max.max(bbox.x_max).min(self.max);
+				self.x_max = self.x_max.max(bbox.x_max).min(self.max_column());
 				self.y_max = self.y_max.max(bbox.y_max).min(self.max);
 			}
 		}
@@ -439,6 +485,12 @@ impl TileBBox {
 		}
 
 		if !self.is_empty() && !bbox.is_empty() {
+			ensure!(
+				self.grid == bbox.grid,
+				"Cannot intersect TileBBox in grid {} with TileBBox in grid {}",
+				bbox.grid,
+				self.grid
+			);
 			self.x_min = self.x_min.max(bbox.x_min);
 			self.y_min = self.y_min.max(bbox.y_min);
 			self.x_max = self.x_max.min(bbox.x_max);
@@ -498,6 +550,13 @@ impl TileBBox {
 			return Ok(false);
 		}
 
+		ensure!(
+			self.grid == bbox.grid,
+			"Cannot compare TileBBox in grid {} with TileBBox in grid {}",
+			bbox.grid,
+			self.grid
+		);
+
 		Ok(self.x_min <= bbox.x_max && self.x_max >= bbox.x_min && self.y_min <= bbox.y_max && self.y_max >= bbox.y_min)
 	}
 
@@ -511,6 +570,10 @@ impl TileBBox {
 	///
 	/// * `GeoBBox` representing the geographical area covered by this bounding box.
 	pub fn as_geo_bbox(&self) -> GeoBBox {
+		if !self.grid.is_web_mercator() {
+			return self.grid.bbox_to_geo(self);
+		}
+
 		// Top-left in geospatial terms is (x_min, y_max + 1)
 		let p_min = TileCoord3::new(self.x_min, self.y_max + 1, self.level)
 			.unwrap()
@@ -657,7 +720,9 @@ impl TileBBox {
 		}
 
 		let level = self.level;
-		let max = 2u32.pow(level as u32) - 1;
+		let grid = self.grid;
+		let max_column = self.max_column();
+		let max_row = self.max;
 		let mut meta_bbox = self.clone();
 		meta_bbox.scale_down(size);
 
@@ -667,7 +732,15 @@ impl TileBBox {
 				let x = coord.x * size;
 				let y = coord.y * size;
 
-				let mut bbox = TileBBox::new(level, x, y, (x + size - 1).min(max), (y + size - 1).min(max)).unwrap();
+				let mut bbox = TileBBox::new_in_grid(
+					grid,
+					level,
+					x,
+					y,
+					(x + size - 1).min(max_column),
+					(y + size - 1).min(max_row),
+				)
+				.unwrap();
 				bbox.intersect_bbox(self).unwrap();
 				bbox
 			})
@@ -994,6 +1067,7 @@ mod tests {
 			TileBBox {
 				level: 4,
 				max: 15,
+				grid: TileGrid::WebMercator,
 				x_min: 16,
 				y_min: 16,
 				x_max: 0,
@@ -1582,4 +1656,39 @@ mod tests {
 		assert_eq!(grids, expected_grids);
 		Ok(())
 	}
+
+	#[test]
+	fn wgs84_grid() -> Result<()> {
+		let grid = TileGrid::Wgs84;
+
+		let bbox = TileBBox::new_full_in_grid(grid, 2)?;
+		assert_eq!(format!("{bbox:?}"), "2: [0,0,7,3] (32)");
+		assert!(bbox.is_full());
+		assert!(TileBBox::new_in_grid(grid, 2, 0, 0, 7, 4).is_err());
+		assert!(TileBBox::new(2, 0, 0, 7, 3).is_err());
+
+		let mut bbox = TileBBox::new_empty_in_grid(grid, 2)?;
+		assert!(bbox.is_empty());
+		bbox.include_coord(6, 1);
+		bbox.add_border(1, 1, 5, 5);
+		assert_eq!(format!("{bbox:?}"), "2: [5,0,7,3] (12)");
+		assert_eq!(bbox.as_geo_bbox(), GeoBBox(45.0, -90.0, 180.0, 90.0));
+
+		let grids: Vec<String> = bbox.iter_bbox_grid(2).map(|b| format!("{b:?}")).collect();
+		assert_eq!(
+			grids,
+			[
+				"2: [5,0,5,1] (2)",
+				"2: [6,0,7,1] (4)",
+				"2: [5,2,5,3] (2)",
+				"2: [6,2,7,3] (4)"
+			]
+		);
+
+		let mut mercator = TileBBox::new(2, 0, 0, 1, 1)?;
+		assert!(mercator.intersect_bbox(&bbox).is_err());
+		assert!(mercator.include_bbox(&bbox).is_err());
+		assert!(mercator.overlaps_bbox(&bbox).is_err());
+		Ok(())
+	}
 }
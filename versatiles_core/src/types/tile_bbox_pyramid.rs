@@ -1,7 +1,7 @@
 //! This module defines the `TileBBoxPyramid` struct, which represents a pyramid of tile bounding boxes
 //! across multiple zoom levels. It provides methods to create, manipulate, and query these bounding boxes.
 
-use super::{GeoBBox, GeoCenter, TileBBox, TileCoord3};
+use super::{GeoBBox, GeoCenter, TileBBox, TileCoord3, TileGrid};
 use std::array::from_fn;
 use std::fmt;
 
@@ -40,15 +40,20 @@ impl TileBBoxPyramid {
 	///
 	/// May panic if `max_zoom_level` exceeds `MAX_ZOOM_LEVEL - 1`.
 	pub fn new_full(max_zoom_level: u8) -> TileBBoxPyramid {
+		Self::new_full_in_grid(TileGrid::WebMercator, max_zoom_level)
+	}
+
+	/// Like [`TileBBoxPyramid::new_full`], but covering all tiles of `grid`.
+	pub fn new_full_in_grid(grid: TileGrid, max_zoom_level: u8) -> TileBBoxPyramid {
 		// Create an array of tile bounding boxes via `from_fn`.
 		// If index <= max_zoom_level, create a full bounding box;
 		// otherwise, create an empty bounding box.
 		TileBBoxPyramid {
 			level_bbox: from_fn(|z| {
 				if z <= max_zoom_level as usize {
-					TileBBox::new_full(z as u8).unwrap()
+					TileBBox::new_full_in_grid(grid, z as u8).unwrap()
 				} else {
-					TileBBox::new_empty(z as u8).unwrap()
+					TileBBox::new_empty_in_grid(grid, z as u8).unwrap()
 				}
 			}),
 		}
@@ -60,8 +65,13 @@ impl TileBBoxPyramid {
 	///
 	/// A `TileBBoxPyramid` where each level is an empty bounding box.
 	pub fn new_empty() -> TileBBoxPyramid {
+		Self::new_empty_in_grid(TileGrid::WebMercator)
+	}
+
+	/// Like [`TileBBoxPyramid::new_empty`], but every level refers to `grid`.
+	pub fn new_empty_in_grid(grid: TileGrid) -> TileBBoxPyramid {
 		TileBBoxPyramid {
-			level_bbox: from_fn(|z| TileBBox::new_empty(z as u8).unwrap()),
+			level_bbox: from_fn(|z| TileBBox::new_empty_in_grid(grid, z as u8).unwrap()),
 		}
 	}
 
@@ -88,17 +98,60 @@ impl TileBBoxPyramid {
 
 	/// Intersects each bounding box in the pyramid with the bounding box derived from the provided [`GeoBBox`].
 	///
+	/// The [`GeoBBox`] is converted using the tile grid of each level.
+	///
 	/// # Arguments
 	///
 	/// * `geo_bbox` - The geographical bounding box to intersect with.
 	pub fn intersect_geo_bbox(&mut self, geo_bbox: &GeoBBox) {
-		for (z, tile_bbox) in self.level_bbox.iter_mut().enumerate() {
+		self.intersect_geo_bbox_from_level(0, geo_bbox);
+	}
+
+	/// Intersects the bounding boxes of `level_min` and all higher zoom levels with the provided [`GeoBBox`].
+	///
+	/// Lower zoom levels are left unchanged, e.g. to keep a wide coverage at low zoom levels.
+	pub fn intersect_geo_bbox_from_level(&mut self, level_min: u8, geo_bbox: &GeoBBox) {
+		for (z, tile_bbox) in self.level_bbox.iter_mut().enumerate().skip(level_min as usize) {
 			tile_bbox
-				.intersect_bbox(&TileBBox::from_geo(z as u8, geo_bbox).unwrap())
+				.intersect_bbox(&TileBBox::from_geo_in_grid(tile_bbox.grid, z as u8, geo_bbox).unwrap())
 				.unwrap();
 		}
 	}
 
+	/// Intersects each level with the `index`-th of `count` vertical strips, e.g. to spread a conversion
+	/// across several machines.
+	///
+	/// Column `x` of a level with `n` columns belongs to strip `x * count / n`, so the strips are disjoint,
+	/// cover all tiles and don't depend on the content of the pyramid. Low zoom levels with fewer columns
+	/// than strips end up in the first strips.
+	///
+	/// # Panics
+	///
+	/// Panics if `index >= count`.
+	pub fn intersect_partition(&mut self, index: u32, count: u32) {
+		assert!(index < count, "partition index ({index}) must be < count ({count})");
+		let (index, count) = (index as u64, count as u64);
+		for bbox in self.level_bbox.iter_mut() {
+			let columns = bbox.grid.max_column(bbox.level) as u64 + 1;
+			let x_min = (index * columns).div_ceil(count);
+			let x_end = ((index + 1) * columns).div_ceil(count);
+			if x_min >= x_end {
+				bbox.set_empty();
+				continue;
+			}
+			let strip = TileBBox::new_in_grid(
+				bbox.grid,
+				bbox.level,
+				x_min as u32,
+				0,
+				(x_end - 1) as u32,
+				bbox.grid.max_row(bbox.level),
+			)
+			.unwrap();
+			bbox.intersect_bbox(&strip).unwrap();
+		}
+	}
+
 	/// Expands each bounding box in the pyramid by the specified border offsets.
 	///
 	/// This effectively shifts each bounding box outward by `(x_min, y_min, x_max, y_max)`.
@@ -119,6 +172,11 @@ impl TileBBoxPyramid {
 		}
 	}
 
+	/// Returns the tile grid of the pyramid, taken from the lowest non-empty zoom level.
+	pub fn get_grid(&self) -> TileGrid {
+		self.iter_levels().next().unwrap_or(&self.level_bbox[0]).grid
+	}
+
 	/// Returns a reference to the bounding box at the specified zoom level.
 	///
 	/// # Panics
@@ -390,6 +448,46 @@ mod tests {
 		assert_eq!(pyramid.get_level_bbox(8), &TileBBox::new(8, 133, 84, 136, 85).unwrap());
 	}
 
+	#[test]
+	fn test_intersect_partition() {
+		let full = TileBBoxPyramid::new_full(10);
+		let partitions: Vec<TileBBoxPyramid> = (0..8)
+			.map(|index| {
+				let mut pyramid = full.clone();
+				pyramid.intersect_partition(index, 8);
+				pyramid
+			})
+			.collect();
+
+		// the partitions cover all tiles exactly once
+		let count: u64 = partitions.iter().map(|p| p.count_tiles()).sum();
+		assert_eq!(count, full.count_tiles());
+
+		// low zoom levels belong to the first partitions
+		assert_eq!(partitions[0].get_level_bbox(0), &TileBBox::new(0, 0, 0, 0, 0).unwrap());
+		assert!(partitions[1].get_level_bbox(0).is_empty());
+		assert_eq!(partitions[4].get_level_bbox(1), &TileBBox::new(1, 1, 0, 1, 1).unwrap());
+		assert_eq!(partitions[3].get_level_bbox(3), &TileBBox::new(3, 3, 0, 3, 7).unwrap());
+		assert_eq!(partitions[7].get_level_bbox(10), &TileBBox::new(10, 896, 0, 1023, 1023).unwrap());
+
+		// partitions of a smaller pyramid stay within it
+		let mut pyramid = TileBBoxPyramid::new_empty();
+		pyramid.set_level_bbox(TileBBox::new(4, 0, 2, 5, 3).unwrap());
+		pyramid.intersect_partition(1, 4);
+		assert_eq!(pyramid.get_level_bbox(4), &TileBBox::new(4, 4, 2, 5, 3).unwrap());
+	}
+
+	#[test]
+	fn test_limit_by_geo_bbox_from_level() {
+		let mut pyramid = TileBBoxPyramid::new_full(8);
+		pyramid.intersect_geo_bbox_from_level(6, &GeoBBox(8.0653f64, 51.3563f64, 12.3528f64, 52.2564f64));
+
+		assert_eq!(pyramid.get_level_bbox(0), &TileBBox::new(0, 0, 0, 0, 0).unwrap());
+		assert_eq!(pyramid.get_level_bbox(5), &TileBBox::new_full(5).unwrap());
+		assert_eq!(pyramid.get_level_bbox(6), &TileBBox::new(6, 33, 21, 34, 21).unwrap());
+		assert_eq!(pyramid.get_level_bbox(8), &TileBBox::new(8, 133, 84, 136, 85).unwrap());
+	}
+
 	#[test]
 	fn test_include_coord2() -> Result<()> {
 		let mut pyramid = TileBBoxPyramid::new_empty();
@@ -674,4 +772,24 @@ mod tests {
 		let maybe_center = p.get_geo_center();
 		assert!(maybe_center.is_some());
 	}
+
+	#[test]
+	fn test_grid() {
+		let mut p = TileBBoxPyramid::new_full_in_grid(TileGrid::Wgs84, 2);
+		assert_eq!(p.get_grid(), TileGrid::Wgs84);
+		assert_eq!(p.count_tiles(), 2 + 8 + 32);
+		assert_eq!(p.get_geo_bbox(), Some(GeoBBox(-180.0, -90.0, 180.0, 90.0)));
+
+		p.intersect_geo_bbox(&GeoBBox(0.0, 0.0, 90.0, 90.0));
+		assert_eq!(
+			format!("{p:?}"),
+			"[0: [1,0,1,0] (1), 1: [2,0,2,0] (1), 2: [4,0,5,1] (4)]"
+		);
+
+		assert_eq!(TileBBoxPyramid::new_empty().get_grid(), TileGrid::WebMercator);
+		assert_eq!(
+			TileBBoxPyramid::new_empty_in_grid(TileGrid::Wgs84).get_grid(),
+			TileGrid::Wgs84
+		);
+	}
 }
@@ -29,7 +29,7 @@ use std::{
 	ops::{Add, Sub},
 };
 
-use super::GeoBBox;
+use super::{GeoBBox, TileGrid};
 
 #[derive(Eq, PartialEq, Clone, Hash)]
 pub struct TileCoord2 {
@@ -146,6 +146,11 @@ impl TileCoord3 {
 		(self.x < max) && (self.y < max)
 	}
 
+	/// Checks whether the coordinate is inside of `grid`.
+	pub fn is_valid_in_grid(&self, grid: &TileGrid) -> bool {
+		self.z <= 31 && self.x <= grid.max_column(self.z) && self.y <= grid.max_row(self.z)
+	}
+
 	pub fn get_sort_index(&self) -> u64 {
 		let size = 2u64.pow(self.z as u32);
 		let offset = (size * size - 1) / 3;
@@ -0,0 +1,356 @@
+//! This module defines the `TileGrid` enum, describing how the tiles of a zoom level cover the earth.
+//!
+//! Most web maps use the Web Mercator grid (EPSG:3857), with a single tile at zoom level 0. Some sources,
+//! e.g. many WMTS services, use a WGS84 grid (EPSG:4326) instead, where zoom level 0 consists of two tiles,
+//! each covering 180° × 180°. Custom grids cover any geographic extent with a given number of tiles at
+//! zoom level 0. In both WGS84 and custom grids, longitude and latitude are mapped linearly to tiles.
+//!
+//! In every grid, each zoom level doubles the number of columns and rows of the previous one.
+//!
+//! # Examples
+//!
+//! ```
+//! use versatiles_core::types::{GeoBBox, TileGrid};
+//!
+//! let grid = TileGrid::parse_str("EPSG:4326").unwrap();
+//! assert_eq!(grid, TileGrid::Wgs84);
+//! assert_eq!(grid.max_column(1), 3);
+//! assert_eq!(grid.max_row(1), 1);
+//!
+//! let bbox = grid.bbox_from_geo(1, &GeoBBox::new(0.0, 0.0, 90.0, 90.0)).unwrap();
+//! assert_eq!(format!("{bbox:?}"), "1: [2,0,2,0] (1)");
+//! ```
+
+use super::{GeoBBox, TileBBox, TileCoord3};
+use anyhow::{bail, ensure, Context, Result};
+use std::fmt::Display;
+
+/// Describes how the tiles of every zoom level are arranged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TileGrid {
+	/// Web Mercator (EPSG:3857), with one tile at zoom level 0.
+	#[default]
+	WebMercator,
+	/// WGS84 (EPSG:4326), with two tiles at zoom level 0, covering the western and eastern hemisphere.
+	Wgs84,
+	/// A grid covering a custom extent in WGS84 coordinates.
+	Custom(CustomTileGrid),
+}
+
+/// Parameters of a custom grid.
+///
+/// The extent is stored in degrees multiplied by 10⁷, so that grids can be compared and hashed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CustomTileGrid {
+	/// Extent of the grid: west, south, east, north, in degrees × 10⁷.
+	pub extent_e7: [i32; 4],
+	/// Number of columns at zoom level 0.
+	pub columns: u32,
+	/// Number of rows at zoom level 0.
+	pub rows: u32,
+}
+
+impl CustomTileGrid {
+	/// Creates a custom grid covering `extent` with `columns` × `rows` tiles at zoom level 0.
+	pub fn new(extent: &GeoBBox, columns: u32, rows: u32) -> Result<CustomTileGrid> {
+		extent.check()?;
+		ensure!(extent.0 < extent.2, "extent of a tile grid must have a width");
+		ensure!(extent.1 < extent.3, "extent of a tile grid must have a height");
+		ensure!(
+			columns > 0 && rows > 0,
+			"a tile grid needs at least one column and one row"
+		);
+
+		let e7 = |value: f64| (value * 1e7).round() as i32;
+		Ok(CustomTileGrid {
+			extent_e7: [e7(extent.0), e7(extent.1), e7(extent.2), e7(extent.3)],
+			columns,
+			rows,
+		})
+	}
+
+	/// Returns the extent of the grid in degrees.
+	pub fn get_extent(&self) -> GeoBBox {
+		let [west, south, east, north] = self.extent_e7.map(|value| value as f64 / 1e7);
+		GeoBBox(west, south, east, north)
+	}
+}
+
+impl TileGrid {
+	/// Parses a grid, e.g. from the `tile_grid` field of a TileJSON.
+	///
+	/// Supported values are "webmercator" (or "EPSG:3857"), "wgs84" (or "EPSG:4326") and
+	/// "custom:west,south,east,north,columns,rows".
+	pub fn parse_str(value: &str) -> Result<Self> {
+		let value = value.trim().to_lowercase();
+		Ok(match value.as_str() {
+			"webmercator" | "epsg:3857" => TileGrid::WebMercator,
+			"wgs84" | "epsg:4326" => TileGrid::Wgs84,
+			_ => {
+				let Some(parameters) = value.strip_prefix("custom:") else {
+					bail!("Unknown tile grid. Expected webmercator, wgs84 or custom:west,south,east,north,columns,rows, got '{value}'")
+				};
+				let parts: Vec<&str> = parameters.split(',').map(str::trim).collect();
+				ensure!(
+					parts.len() == 6,
+					"a custom tile grid needs 6 values: west,south,east,north,columns,rows, got '{parameters}'"
+				);
+				let mut extent = [0.0; 4];
+				for (value, part) in extent.iter_mut().zip(&parts[0..4]) {
+					*value = part
+						.parse()
+						.with_context(|| format!("invalid coordinate '{part}' in tile grid"))?;
+				}
+				let columns = parts[4].parse().context("invalid number of columns in tile grid")?;
+				let rows = parts[5].parse().context("invalid number of rows in tile grid")?;
+				TileGrid::Custom(CustomTileGrid::new(&GeoBBox::from(&extent), columns, rows)?)
+			}
+		})
+	}
+
+	/// Returns `true` for the Web Mercator grid.
+	pub fn is_web_mercator(&self) -> bool {
+		*self == TileGrid::WebMercator
+	}
+
+	/// Returns the number of columns and rows at zoom level 0.
+	pub fn get_base_size(&self) -> (u32, u32) {
+		match self {
+			TileGrid::WebMercator => (1, 1),
+			TileGrid::Wgs84 => (2, 1),
+			TileGrid::Custom(grid) => (grid.columns, grid.rows),
+		}
+	}
+
+	/// Returns `true` if the grid has the same number of columns and rows at every zoom level.
+	pub fn is_square(&self) -> bool {
+		let (columns, rows) = self.get_base_size();
+		columns == rows
+	}
+
+	/// Returns the largest valid x coordinate at `level`.
+	pub fn max_column(&self, level: u8) -> u32 {
+		Self::max_index(self.get_base_size().0, level)
+	}
+
+	/// Returns the largest valid y coordinate at `level`.
+	pub fn max_row(&self, level: u8) -> u32 {
+		Self::max_index(self.get_base_size().1, level)
+	}
+
+	fn max_index(base_size: u32, level: u8) -> u32 {
+		((base_size as u64) << level).saturating_sub(1).min(u32::MAX as u64 - 1) as u32
+	}
+
+	/// Returns the extent of the grid in degrees.
+	///
+	/// For Web Mercator, the latitude is limited to ±85.0511°.
+	pub fn get_extent(&self) -> GeoBBox {
+		match self {
+			TileGrid::WebMercator => TileBBox::new_full(0).unwrap().as_geo_bbox(),
+			TileGrid::Wgs84 => GeoBBox(-180.0, -90.0, 180.0, 90.0),
+			TileGrid::Custom(grid) => grid.get_extent(),
+		}
+	}
+
+	/// Converts (fractional) tile coordinates at `level` into longitude and latitude.
+	pub fn tile_to_geo(&self, x: f64, y: f64, level: u8) -> [f64; 2] {
+		if self.is_web_mercator() {
+			let zoom: f64 = 2.0f64.powi(level as i32);
+			return [
+				(x / zoom - 0.5) * 360.0,
+				((std::f64::consts::PI * (1.0 - 2.0 * y / zoom)).exp().atan() / std::f64::consts::PI - 0.25) * 360.0,
+			];
+		}
+
+		let (columns, rows) = self.get_size(level);
+		let extent = self.get_extent();
+		[
+			extent.0 + x / columns * (extent.2 - extent.0),
+			extent.3 - y / rows * (extent.3 - extent.1),
+		]
+	}
+
+	/// Converts longitude and latitude into fractional tile coordinates at `level`.
+	pub fn geo_to_tile(&self, lon: f64, lat: f64, level: u8) -> [f64; 2] {
+		if self.is_web_mercator() {
+			let zoom: f64 = 2.0f64.powi(level as i32);
+			let pi = std::f64::consts::PI;
+			return [
+				zoom * (lon / 360.0 + 0.5),
+				zoom * (0.5 - 0.5 * (lat * pi / 360.0 + pi / 4.0).tan().ln() / pi),
+			];
+		}
+
+		let (columns, rows) = self.get_size(level);
+		let extent = self.get_extent();
+		[
+			(lon - extent.0) / (extent.2 - extent.0) * columns,
+			(extent.3 - lat) / (extent.3 - extent.1) * rows,
+		]
+	}
+
+	/// Number of columns and rows at `level`.
+	fn get_size(&self, level: u8) -> (f64, f64) {
+		let (columns, rows) = self.get_base_size();
+		let zoom: f64 = 2.0f64.powi(level as i32);
+		(columns as f64 * zoom, rows as f64 * zoom)
+	}
+
+	/// Returns the geographic area covered by a tile.
+	pub fn get_tile_geo_bbox(&self, coord: &TileCoord3) -> GeoBBox {
+		let [west, north] = self.tile_to_geo(coord.x as f64, coord.y as f64, coord.z);
+		let [east, south] = self.tile_to_geo(coord.x as f64 + 1.0, coord.y as f64 + 1.0, coord.z);
+		GeoBBox(west, south, east, north)
+	}
+
+	/// Returns the geographic area covered by the tiles of a bounding box.
+	pub fn bbox_to_geo(&self, bbox: &TileBBox) -> GeoBBox {
+		let [west, south] = self.tile_to_geo(bbox.x_min as f64, bbox.y_max as f64 + 1.0, bbox.level);
+		let [east, north] = self.tile_to_geo(bbox.x_max as f64 + 1.0, bbox.y_min as f64, bbox.level);
+		GeoBBox(west, south, east, north)
+	}
+
+	/// Returns the tiles at `level` that cover a geographic bounding box.
+	///
+	/// The result is empty if the bounding box is outside of the grid.
+	pub fn bbox_from_geo(&self, level: u8, bbox: &GeoBBox) -> Result<TileBBox> {
+		if self.is_web_mercator() {
+			return TileBBox::from_geo(level, bbox);
+		}
+
+		ensure!(level <= 31, "level ({level}) must be <= 31");
+		bbox.check()?;
+
+		let extent = self.get_extent();
+		if bbox.2 < extent.0 || bbox.0 > extent.2 || bbox.3 < extent.1 || bbox.1 > extent.3 {
+			return TileBBox::new_empty_in_grid(*self, level);
+		}
+
+		let [x_min, y_min] = self.geo_to_tile(bbox.0, bbox.3, level);
+		let [x_max, y_max] = self.geo_to_tile(bbox.2, bbox.1, level);
+
+		// add/subtract a little offset to compensate for floating point rounding issues
+		let clamp = |value: f64, max: u32| value.max(0.0).min(max as f64) as u32;
+		let max_column = self.max_column(level);
+		let max_row = self.max_row(level);
+
+		TileBBox::new_in_grid(
+			*self,
+			level,
+			clamp((x_min + 1e-6).floor(), max_column),
+			clamp((y_min + 1e-6).floor(), max_row),
+			clamp((x_max - 1e-6).floor(), max_column),
+			clamp((y_max - 1e-6).floor(), max_row),
+		)
+	}
+}
+
+impl Display for TileGrid {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TileGrid::WebMercator => f.write_str("webmercator"),
+			TileGrid::Wgs84 => f.write_str("wgs84"),
+			TileGrid::Custom(grid) => {
+				let extent = grid.get_extent();
+				write!(
+					f,
+					"custom:{},{},{},{},{},{}",
+					extent.0, extent.1, extent.2, extent.3, grid.columns, grid.rows
+				)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_and_display() -> Result<()> {
+		assert_eq!(TileGrid::parse_str("webmercator")?, TileGrid::WebMercator);
+		assert_eq!(TileGrid::parse_str("EPSG:3857")?, TileGrid::WebMercator);
+		assert_eq!(TileGrid::parse_str(" WGS84 ")?, TileGrid::Wgs84);
+		assert_eq!(TileGrid::parse_str("epsg:4326")?, TileGrid::Wgs84);
+		assert!(TileGrid::parse_str("epsg:25832").is_err());
+		assert!(TileGrid::parse_str("custom:0,0,10,10,1").is_err());
+		assert!(TileGrid::parse_str("custom:10,0,0,10,1,1").is_err());
+
+		let grid = TileGrid::parse_str("custom:5.5,47,15.5,55,2,3")?;
+		assert_eq!(grid.get_base_size(), (2, 3));
+		assert_eq!(grid.get_extent(), GeoBBox(5.5, 47.0, 15.5, 55.0));
+		assert_eq!(grid.to_string(), "custom:5.5,47,15.5,55,2,3");
+		assert_eq!(TileGrid::parse_str(&grid.to_string())?, grid);
+
+		assert_eq!(TileGrid::WebMercator.to_string(), "webmercator");
+		assert_eq!(TileGrid::Wgs84.to_string(), "wgs84");
+		Ok(())
+	}
+
+	#[test]
+	fn matrix_size() {
+		assert_eq!(TileGrid::WebMercator.max_column(0), 0);
+		assert_eq!(TileGrid::WebMercator.max_row(3), 7);
+		assert_eq!(TileGrid::Wgs84.max_column(0), 1);
+		assert_eq!(TileGrid::Wgs84.max_row(0), 0);
+		assert_eq!(TileGrid::Wgs84.max_column(3), 15);
+		assert_eq!(TileGrid::Wgs84.max_row(3), 7);
+		assert_eq!(TileGrid::WebMercator.max_column(31), 2u32.pow(31) - 1);
+		assert_eq!(TileGrid::Wgs84.max_column(31), u32::MAX - 1);
+		assert!(TileGrid::WebMercator.is_square());
+		assert!(!TileGrid::Wgs84.is_square());
+	}
+
+	#[test]
+	fn web_mercator_matches_tile_coords() {
+		let coord = TileCoord3::new(8803, 5376, 14).unwrap();
+		let [west, north] = coord.as_geo();
+		let bbox = TileGrid::WebMercator.get_tile_geo_bbox(&coord);
+		assert_eq!([bbox.0, bbox.3], [west, north]);
+
+		let bbox = GeoBBox(13.08, 52.33, 13.76, 52.67);
+		assert_eq!(
+			TileGrid::WebMercator.bbox_from_geo(10, &bbox).unwrap(),
+			TileBBox::from_geo(10, &bbox).unwrap()
+		);
+	}
+
+	#[test]
+	fn wgs84() -> Result<()> {
+		let grid = TileGrid::Wgs84;
+		assert_eq!(
+			grid.get_tile_geo_bbox(&TileCoord3::new(0, 0, 0)?),
+			GeoBBox(-180.0, -90.0, 0.0, 90.0)
+		);
+		assert_eq!(
+			grid.get_tile_geo_bbox(&TileCoord3::new(3, 1, 1)?),
+			GeoBBox(90.0, -90.0, 180.0, 0.0)
+		);
+
+		let bbox = grid.bbox_from_geo(2, &GeoBBox(-180.0, -90.0, 180.0, 90.0))?;
+		assert_eq!(format!("{bbox:?}"), "2: [0,0,7,3] (32)");
+		assert_eq!(grid.bbox_to_geo(&bbox), GeoBBox(-180.0, -90.0, 180.0, 90.0));
+
+		// Berlin
+		let bbox = grid.bbox_from_geo(10, &GeoBBox(13.08, 52.33, 13.76, 52.67))?;
+		assert_eq!(format!("{bbox:?}"), "10: [1098,212,1102,214] (15)");
+		Ok(())
+	}
+
+	#[test]
+	fn custom() -> Result<()> {
+		let grid = TileGrid::parse_str("custom:0,40,20,60,2,2")?;
+		assert_eq!(
+			grid.get_tile_geo_bbox(&TileCoord3::new(1, 1, 0)?),
+			GeoBBox(10.0, 40.0, 20.0, 50.0)
+		);
+
+		let bbox = grid.bbox_from_geo(1, &GeoBBox(12.0, 41.0, 18.0, 44.0))?;
+		assert_eq!(format!("{bbox:?}"), "1: [2,3,3,3] (2)");
+
+		// outside of the grid
+		assert!(grid.bbox_from_geo(1, &GeoBBox(-20.0, 41.0, -10.0, 44.0))?.is_empty());
+		Ok(())
+	}
+}
@@ -0,0 +1,140 @@
+//! This module defines the `TileScheme` enum, describing the orientation of the y axis of tile coordinates.
+//!
+//! Most web maps use the XYZ scheme, where `y = 0` is the northernmost row. The TMS scheme, used e.g. by the
+//! MBTiles specification, counts rows from the south. Tile archives in the wild do not always follow the
+//! convention of their container format, so readers can be switched between both schemes.
+//!
+//! # Examples
+//!
+//! ```
+//! use versatiles_core::types::TileScheme;
+//!
+//! assert_eq!(TileScheme::parse_str("tms").unwrap(), TileScheme::Tms);
+//! assert_eq!(TileScheme::Xyz.flipped(), TileScheme::Tms);
+//! ```
+
+use super::{GeoBBox, TileBBox, TileBBoxPyramid};
+use crate::utils::TransformCoord;
+use anyhow::{bail, Result};
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use std::fmt::Display;
+
+/// Enum representing the orientation of the y axis.
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileScheme {
+	/// Rows are counted from the north (`y = 0` is the top row).
+	Xyz,
+	/// Rows are counted from the south (`y = 0` is the bottom row).
+	Tms,
+}
+
+impl TileScheme {
+	pub fn as_str(&self) -> &str {
+		match self {
+			TileScheme::Xyz => "xyz",
+			TileScheme::Tms => "tms",
+		}
+	}
+
+	/// Parses a scheme from a string, e.g. the `scheme` field of a TileJSON.
+	pub fn parse_str(value: &str) -> Result<Self> {
+		Ok(match value.to_lowercase().trim() {
+			"xyz" => TileScheme::Xyz,
+			"tms" => TileScheme::Tms,
+			_ => bail!("Unknown tile scheme. Expected xyz or tms, got '{value}'"),
+		})
+	}
+
+	/// Returns the other scheme.
+	pub fn flipped(&self) -> Self {
+		match self {
+			TileScheme::Xyz => TileScheme::Tms,
+			TileScheme::Tms => TileScheme::Xyz,
+		}
+	}
+
+	/// Checks whether the tiles of a pyramid, read in this scheme, are located inside the expected bounds.
+	///
+	/// The highest zoom level of the pyramid is used as a landmark: If it does not overlap the expected
+	/// bounds, but would overlap them when flipped vertically, the tiles are probably stored in the other
+	/// scheme, which is returned.
+	///
+	/// # Arguments
+	///
+	/// * `pyramid` - The bbox pyramid of the tiles, as read in this scheme.
+	/// * `bounds` - The expected geographic bounds, e.g. from the container metadata.
+	///
+	/// # Returns
+	///
+	/// * `Some(scheme)` with the suspected scheme, or `None` if the orientation looks fine or can not be checked.
+	pub fn check_orientation(&self, pyramid: &TileBBoxPyramid, bounds: &GeoBBox) -> Option<TileScheme> {
+		let level = pyramid.get_zoom_max()?;
+		let bbox = pyramid.get_level_bbox(level).clone();
+		let expected = TileBBox::from_geo_in_grid(bbox.grid, level, bounds).ok()?;
+
+		let mut flipped = bbox.clone();
+		flipped.flip_y();
+
+		let overlaps = bbox.overlaps_bbox(&expected).ok()?;
+		let overlaps_flipped = flipped.overlaps_bbox(&expected).ok()?;
+
+		if !overlaps && overlaps_flipped {
+			Some(self.flipped())
+		} else {
+			None
+		}
+	}
+}
+
+impl Display for TileScheme {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_and_display() {
+		assert_eq!(TileScheme::parse_str("xyz").unwrap(), TileScheme::Xyz);
+		assert_eq!(TileScheme::parse_str(" TMS ").unwrap(), TileScheme::Tms);
+		assert!(TileScheme::parse_str("wmts").is_err());
+
+		assert_eq!(TileScheme::Xyz.to_string(), "xyz");
+		assert_eq!(TileScheme::Tms.to_string(), "tms");
+	}
+
+	#[test]
+	fn flipped() {
+		assert_eq!(TileScheme::Xyz.flipped(), TileScheme::Tms);
+		assert_eq!(TileScheme::Tms.flipped(), TileScheme::Xyz);
+	}
+
+	#[test]
+	fn check_orientation() {
+		// Berlin
+		let bounds = GeoBBox::new(13.08, 52.33, 13.76, 52.67);
+
+		let mut pyramid = TileBBoxPyramid::new_empty();
+		pyramid.include_bbox(&TileBBox::from_geo(10, &bounds).unwrap());
+		assert_eq!(TileScheme::Xyz.check_orientation(&pyramid, &bounds), None);
+
+		pyramid.flip_y();
+		assert_eq!(
+			TileScheme::Xyz.check_orientation(&pyramid, &bounds),
+			Some(TileScheme::Tms)
+		);
+		assert_eq!(
+			TileScheme::Tms.check_orientation(&pyramid, &bounds),
+			Some(TileScheme::Xyz)
+		);
+
+		// Can not be checked without tiles
+		let pyramid = TileBBoxPyramid::new_empty();
+		assert_eq!(TileScheme::Xyz.check_orientation(&pyramid, &bounds), None);
+	}
+}
@@ -5,10 +5,16 @@
 //!
 //! # Features
 //! - **Parallel Processing**: Transform or filter tile data in parallel using tokio tasks.
-//! - **Buffering**: Collect or process data in configurable batches.
+//! - **Buffering**: Collect or process data in configurable batches, or read ahead a number of tiles.
+//! - **Error Propagation**: `try_` methods return a [`TryTileStream`] that keeps errors instead of dropping tiles.
 //! - **Synchronous and Asynchronous Callbacks**: Choose between sync and async processing steps.
 
-use crate::types::{Blob, TileCoord3};
+use super::TryTileStream;
+use crate::{
+	types::{Blob, TileCoord3},
+	utils::{get_blocking_pool_size, get_thread_count, run_blocking},
+};
+use anyhow::{anyhow, Result};
 use futures::{
 	future::ready,
 	stream::{self, BoxStream},
@@ -86,7 +92,7 @@ impl<'a> TileStream<'a> {
 	/// Creates a `TileStream` by converting an iterator of `TileCoord3` into parallel tasks
 	/// that produce `(TileCoord3, Blob)` items asynchronously.
 	///
-	/// Spawns one tokio task per coordinate (buffered by `num_cpus::get()`), calling `callback`
+	/// Spawns one tokio task per coordinate (buffered by `get_thread_count()`), calling `callback`
 	/// to produce the tile data. Returns only items where `callback(coord)` yields `Some(blob)`.
 	///
 	/// # Arguments
@@ -116,7 +122,7 @@ impl<'a> TileStream<'a> {
 				// Spawn a task for each coordinate
 				tokio::spawn(async move { (coord, c(coord)) })
 			})
-			.buffer_unordered(num_cpus::get()) // concurrency
+			.buffer_unordered(get_thread_count()) // concurrency
 			.filter_map(|result| async {
 				match result {
 					Ok((coord, Some(blob))) => Some((coord, blob)),
@@ -344,7 +350,7 @@ impl<'a> TileStream<'a> {
 
 	/// Transforms the `Blob` portion of each tile in parallel using the provided closure `callback`.
 	///
-	/// Spawns tokio tasks with concurrency of `num_cpus::get()`. Each item `(coord, blob)` is mapped
+	/// Spawns tokio tasks with concurrency of `get_thread_count()`. Each item `(coord, blob)` is mapped
 	/// to `(coord, callback(blob))`.
 	///
 	/// # Examples
@@ -376,14 +382,14 @@ impl<'a> TileStream<'a> {
 				let cb = Arc::clone(&arc_cb);
 				tokio::spawn(async move { (coord, cb(blob)) })
 			})
-			.buffer_unordered(num_cpus::get())
+			.buffer_unordered(get_thread_count())
 			.map(|e| e.expect("spawned task panicked"));
 		TileStream { stream: s.boxed() }
 	}
 
 	/// Filters and transforms the `Blob` portion of each tile in parallel, discarding items where `callback` returns `None`.
 	///
-	/// Spawns tokio tasks with concurrency of `num_cpus::get()`. Each item `(coord, blob)` is mapped
+	/// Spawns tokio tasks with concurrency of `get_thread_count()`. Each item `(coord, blob)` is mapped
 	/// to `(coord, callback(blob))`. If `callback` returns `None`, the item is dropped.
 	///
 	/// # Examples
@@ -418,7 +424,7 @@ impl<'a> TileStream<'a> {
 				let cb = Arc::clone(&arc_cb);
 				tokio::spawn(async move { (coord, cb(blob)) })
 			})
-			.buffer_unordered(num_cpus::get())
+			.buffer_unordered(get_thread_count())
 			.filter_map(|res| async move {
 				let (coord, maybe_blob) = res.expect("spawned task panicked");
 				maybe_blob.map(|blob| (coord, blob))
@@ -426,6 +432,193 @@ impl<'a> TileStream<'a> {
 		TileStream { stream: s.boxed() }
 	}
 
+	/// Like [`TileStream::map_blob_parallel`], but runs `callback` in the blocking pool (see [`run_blocking`]),
+	/// for CPU-heavy work like decoding and encoding images.
+	///
+	/// Runs up to [`get_blocking_pool_size`] callbacks at once, so they don't starve the async runtime.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	///     (TileCoord3::new(1,1,1).unwrap(), Blob::from("data1")),
+	/// ]);
+	///
+	/// let mapped = stream.map_blob_blocking(|blob| Blob::from(blob.as_str().to_uppercase()));
+	///
+	/// let items = mapped.collect().await;
+	/// assert_eq!(items.len(), 2);
+	/// # }
+	/// ```
+	pub fn map_blob_blocking<F>(self, callback: F) -> Self
+	where
+		F: Fn(Blob) -> Blob + Send + Sync + 'static,
+	{
+		let arc_cb = Arc::new(callback);
+		let s = self
+			.stream
+			.map(move |(coord, blob)| {
+				let cb = Arc::clone(&arc_cb);
+				run_blocking(move || Ok((coord, cb(blob))))
+			})
+			.buffer_unordered(get_blocking_pool_size())
+			.map(|e| e.expect("blocking job failed"));
+		TileStream { stream: s.boxed() }
+	}
+
+	/// Transforms each tile in parallel using the provided closure `callback`, keeping the order of the stream.
+	///
+	/// Spawns tokio tasks and runs up to `concurrency` of them at once. Each item `(coord, blob)` is mapped
+	/// to `(coord, callback(coord, blob))`.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	///     (TileCoord3::new(1,1,1).unwrap(), Blob::from("data1")),
+	/// ]);
+	///
+	/// let mapped = stream.map_parallel(4, |coord, blob| {
+	///     Blob::from(format!("{} at z{}", blob.as_str(), coord.z))
+	/// });
+	///
+	/// let items = mapped.collect().await;
+	/// assert_eq!(items[0].1.as_str(), "data0 at z0");
+	/// assert_eq!(items[1].1.as_str(), "data1 at z1");
+	/// # }
+	/// ```
+	pub fn map_parallel<F>(self, concurrency: usize, callback: F) -> Self
+	where
+		F: Fn(TileCoord3, Blob) -> Blob + Send + Sync + 'static,
+	{
+		let arc_cb = Arc::new(callback);
+		let s = self
+			.stream
+			.map(move |(coord, blob)| {
+				let cb = Arc::clone(&arc_cb);
+				tokio::spawn(async move { (coord, cb(coord, blob)) })
+			})
+			.buffered(concurrency.max(1))
+			.map(|e| e.expect("spawned task panicked"));
+		TileStream { stream: s.boxed() }
+	}
+
+	/// Like [`TileStream::map_parallel`], but discards items where `callback` returns `None`.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	///     (TileCoord3::new(1,1,1).unwrap(), Blob::from("data1")),
+	/// ]);
+	///
+	/// let filtered = stream.filter_map_parallel(4, |coord, blob| {
+	///     (coord.z > 0).then(|| Blob::from(format!("{} at z{}", blob.as_str(), coord.z)))
+	/// });
+	///
+	/// let items = filtered.collect().await;
+	/// assert_eq!(items.len(), 1);
+	/// assert_eq!(items[0].1.as_str(), "data1 at z1");
+	/// # }
+	/// ```
+	pub fn filter_map_parallel<F>(self, concurrency: usize, callback: F) -> Self
+	where
+		F: Fn(TileCoord3, Blob) -> Option<Blob> + Send + Sync + 'static,
+	{
+		let arc_cb = Arc::new(callback);
+		let s = self
+			.stream
+			.map(move |(coord, blob)| {
+				let cb = Arc::clone(&arc_cb);
+				tokio::spawn(async move { (coord, cb(coord, blob)) })
+			})
+			.buffered(concurrency.max(1))
+			.filter_map(|res| async move {
+				let (coord, maybe_blob) = res.expect("spawned task panicked");
+				maybe_blob.map(|blob| (coord, blob))
+			});
+		TileStream { stream: s.boxed() }
+	}
+
+	/// Like [`TileStream::map_parallel`], but `callback` can fail.
+	///
+	/// Returns a [`TryTileStream`] that yields the error of a tile at its position in the stream, instead of
+	/// dropping the tile. Panics of `callback` are returned as errors as well.
+	///
+	/// # Examples
+	/// ```
+	/// # use anyhow::ensure;
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	///     (TileCoord3::new(1,1,1).unwrap(), Blob::from("broken")),
+	/// ]);
+	///
+	/// let mapped = stream.try_map_parallel(4, |_coord, blob| {
+	///     ensure!(blob.as_str() != "broken", "tile is broken");
+	///     Ok(blob)
+	/// });
+	///
+	/// assert!(mapped.collect().await.is_err());
+	/// # }
+	/// ```
+	pub fn try_map_parallel<F>(self, concurrency: usize, callback: F) -> TryTileStream<'a>
+	where
+		F: Fn(TileCoord3, Blob) -> Result<Blob> + Send + Sync + 'static,
+	{
+		let arc_cb = Arc::new(callback);
+		let s = self
+			.stream
+			.map(move |(coord, blob)| {
+				let cb = Arc::clone(&arc_cb);
+				tokio::spawn(async move { cb(coord, blob).map(|blob| (coord, blob)) })
+			})
+			.buffered(concurrency.max(1))
+			.map(|result| match result {
+				Ok(result) => result,
+				Err(err) => Err(anyhow!("processing a tile failed: {err}")),
+			});
+		TryTileStream::from_stream(s.boxed())
+	}
+
+	// -------------------------------------------------------------------------
+	// Buffering
+	// -------------------------------------------------------------------------
+
+	/// Reads up to `size` items ahead of the consumer, so that a slow source and a slow consumer
+	/// can work at the same time. The order of the items is kept.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	///     (TileCoord3::new(1,1,1).unwrap(), Blob::from("data1")),
+	/// ]);
+	///
+	/// let items = stream.buffered(16).collect().await;
+	/// assert_eq!(items.len(), 2);
+	/// # }
+	/// ```
+	pub fn buffered(self, size: usize) -> Self {
+		TileStream {
+			stream: self.stream.map(ready).buffered(size.max(1)).boxed(),
+		}
+	}
+
+	/// Converts this stream into a [`TryTileStream`] where every item is `Ok`.
+	pub fn into_try_stream(self) -> TryTileStream<'a> {
+		TryTileStream::from_stream(self.stream.map(Ok).boxed())
+	}
+
 	// -------------------------------------------------------------------------
 	// Coordinate Transformations
 	// -------------------------------------------------------------------------
@@ -620,6 +813,78 @@ mod tests {
 		assert_eq!(items[1].1.as_str(), "kept-keep2");
 	}
 
+	#[tokio::test]
+	async fn should_keep_order_in_map_parallel() {
+		let tile_data: Vec<(TileCoord3, Blob)> = (0..100)
+			.map(|i| (TileCoord3::new(i, 0, 10).unwrap(), Blob::from(format!("{i}"))))
+			.collect();
+
+		let items = TileStream::from_vec(tile_data)
+			.map_parallel(8, |coord, blob| {
+				// later tiles finish earlier
+				std::thread::sleep(std::time::Duration::from_micros(100 - coord.x as u64));
+				Blob::from(format!("mapped-{}", blob.as_str()))
+			})
+			.collect()
+			.await;
+
+		assert_eq!(items.len(), 100);
+		for (i, (coord, blob)) in items.iter().enumerate() {
+			assert_eq!(coord.x, i as u32);
+			assert_eq!(blob.as_str(), format!("mapped-{i}"));
+		}
+	}
+
+	#[tokio::test]
+	async fn should_keep_order_in_filter_map_parallel() {
+		let tile_data: Vec<(TileCoord3, Blob)> = (0..100)
+			.map(|i| (TileCoord3::new(i, 0, 10).unwrap(), Blob::from(format!("{i}"))))
+			.collect();
+
+		let items = TileStream::from_vec(tile_data)
+			.filter_map_parallel(8, |coord, blob| {
+				std::thread::sleep(std::time::Duration::from_micros(100 - coord.x as u64));
+				(coord.x % 2 == 0).then(|| Blob::from(format!("kept-{}", blob.as_str())))
+			})
+			.collect()
+			.await;
+
+		assert_eq!(items.len(), 50);
+		for (i, (coord, blob)) in items.iter().enumerate() {
+			assert_eq!(coord.x, i as u32 * 2);
+			assert_eq!(blob.as_str(), format!("kept-{}", i * 2));
+		}
+	}
+
+	#[tokio::test]
+	async fn should_propagate_errors_in_try_map_parallel() {
+		let tile_data = vec![
+			(TileCoord3::new(0, 0, 0).unwrap(), Blob::from("ok0")),
+			(TileCoord3::new(1, 1, 1).unwrap(), Blob::from("broken")),
+			(TileCoord3::new(2, 2, 2).unwrap(), Blob::from("ok2")),
+		];
+
+		let mut stream = TileStream::from_vec(tile_data).try_map_parallel(2, |_coord, blob| {
+			anyhow::ensure!(blob.as_str() != "broken", "broken tile");
+			Ok(blob)
+		});
+
+		assert_eq!(stream.next().await.unwrap().unwrap().1.as_str(), "ok0");
+		assert_eq!(stream.next().await.unwrap().unwrap_err().to_string(), "broken tile");
+		assert_eq!(stream.next().await.unwrap().unwrap().1.as_str(), "ok2");
+		assert!(stream.next().await.is_none());
+	}
+
+	#[tokio::test]
+	async fn should_buffer_without_changing_items() {
+		let tile_data: Vec<(TileCoord3, Blob)> = (0..10)
+			.map(|i| (TileCoord3::new(i, 0, 10).unwrap(), Blob::from(format!("{i}"))))
+			.collect();
+
+		let items = TileStream::from_vec(tile_data.clone()).buffered(3).collect().await;
+		assert_eq!(items, tile_data);
+	}
+
 	#[tokio::test]
 	async fn should_construct_empty_stream() {
 		let empty = TileStream::new_empty();
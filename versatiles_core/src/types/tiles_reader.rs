@@ -1,13 +1,16 @@
 #[cfg(feature = "cli")]
 use super::ProbeDepth;
-use super::{Blob, TileBBox, TileCompression, TileCoord3, TileStream, TilesReaderParameters};
+use super::{
+	Blob, TileBBox, TileCompression, TileCoord3, TileFormat, TileScheme, TileStream, TilesReaderParameters,
+	TryTileStream,
+};
 use crate::tilejson::TileJSON;
 #[cfg(feature = "cli")]
 use crate::utils::PrettyPrint;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use futures::lock::Mutex;
-use std::{fmt::Debug, sync::Arc};
+use std::{fmt::Debug, sync::Arc, time::SystemTime};
 
 /// Trait defining the behavior of a tile reader.
 #[async_trait]
@@ -24,17 +27,73 @@ pub trait TilesReaderTrait: Debug + Send + Sync + Unpin {
 	/// Override the tile compression.
 	fn override_compression(&mut self, tile_compression: TileCompression);
 
+	/// Override the orientation of the y axis, for containers that store tiles in a non-standard scheme.
+	fn override_scheme(&mut self, scheme: TileScheme) -> Result<()> {
+		bail!(
+			"container '{}' does not support overriding the tile scheme (requested: {scheme})",
+			self.get_container_name()
+		)
+	}
+
+	/// Override the tile format, for containers that declare the wrong format.
+	fn override_format(&mut self, tile_format: TileFormat) -> Result<()> {
+		bail!(
+			"container '{}' does not support overriding the tile format (requested: {tile_format})",
+			self.get_container_name()
+		)
+	}
+
 	/// Get the metadata, always uncompressed.
 	fn get_tilejson(&self) -> &TileJSON;
 
 	/// Get tile data for the given coordinate, always compressed and formatted.
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>>;
 
+	/// Check whether a tile exists, without transferring its data if possible.
+	async fn has_tile(&self, coord: &TileCoord3) -> Result<bool> {
+		Ok(self.get_tile_data(coord).await?.is_some())
+	}
+
+	/// Get the time a tile was last modified, if the container knows it.
+	async fn get_tile_mtime(&self, _coord: &TileCoord3) -> Result<Option<SystemTime>> {
+		Ok(None)
+	}
+
+	/// Load the whole tile index into memory and keep it there, together with the tile data of up to
+	/// `pinned_blocks` blocks, trading memory for consistently fast lookups.
+	///
+	/// Containers that always hold their index in memory do nothing.
+	async fn preload(&mut self, _pinned_blocks: usize) -> Result<()> {
+		Ok(())
+	}
+
+	/// Hint that the tiles within the bounding box will probably be requested soon.
+	///
+	/// Containers can use this to fetch indexes or tile data ahead of time. The hint may be ignored, and
+	/// tiles are returned correctly whether or not it was given. Containers that can't profit do nothing.
+	async fn prefetch(&self, _bbox: &TileBBox) -> Result<()> {
+		Ok(())
+	}
+
 	/// Get a stream of tiles within the bounding box.
+	///
+	/// Tiles that can't be read are skipped with a warning. Use [`TilesReaderTrait::get_bbox_tile_try_stream`]
+	/// to handle these errors.
 	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		self
+			.get_bbox_tile_try_stream(bbox)
+			.await
+			.into_tile_stream(|err| log::warn!("skipping tile: {err:#}"))
+	}
+
+	/// Get a stream of tiles within the bounding box, with an error for every tile that can't be read.
+	///
+	/// Readers that implement an efficient [`TilesReaderTrait::get_bbox_tile_stream`], but can't report
+	/// errors, should return `self.get_bbox_tile_stream(bbox).await.into_try_stream()`.
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
 		let mutex = Arc::new(Mutex::new(self));
 		let coords: Vec<TileCoord3> = bbox.iter_coords().collect();
-		TileStream::from_coord_vec_async(coords, move |coord| {
+		TryTileStream::from_coord_vec_async(coords, move |coord| {
 			let mutex = mutex.clone();
 			async move {
 				mutex
@@ -42,8 +101,8 @@ pub trait TilesReaderTrait: Debug + Send + Sync + Unpin {
 					.await
 					.get_tile_data(&coord)
 					.await
+					.with_context(|| format!("reading tile {coord:?}"))
 					.map(|blob_option| blob_option.map(|blob| (coord, blob)))
-					.unwrap_or(None)
 			}
 		})
 	}
@@ -218,6 +277,16 @@ mod tests {
 		assert_eq!(reader.get_parameters().tile_compression, TileCompression::Brotli);
 	}
 
+	#[test]
+	fn test_override_scheme_unsupported() {
+		let mut reader = TestReader::new_dummy();
+		let error = reader.override_scheme(TileScheme::Tms).unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"container 'test container name' does not support overriding the tile scheme (requested: tms)"
+		);
+	}
+
 	#[tokio::test]
 	async fn test_get_meta() -> Result<()> {
 		let reader = TestReader::new_dummy();
@@ -237,6 +306,23 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_has_tile_and_mtime() -> Result<()> {
+		let reader = TestReader::new_dummy();
+		let coord = TileCoord3::new(0, 0, 0)?;
+		assert!(reader.has_tile(&coord).await?);
+		assert_eq!(reader.get_tile_mtime(&coord).await?, None);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_prefetch_is_ignored() -> Result<()> {
+		let reader = TestReader::new_dummy();
+		reader.prefetch(&TileBBox::new(2, 0, 0, 3, 3)?).await?;
+		assert!(reader.has_tile(&TileCoord3::new(1, 1, 2)?).await?);
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_get_bbox_tile_stream() -> Result<()> {
 		let reader = TestReader::new_dummy();
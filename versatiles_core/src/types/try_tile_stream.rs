@@ -0,0 +1,231 @@
+//! A module defining the `TryTileStream` struct, a stream of tiles where reading or processing a tile can fail.
+//!
+//! In contrast to [`TileStream`], errors are kept as items of the stream, so consumers can decide whether to
+//! stop at the first error or to skip and count failed tiles.
+
+use super::TileStream;
+use crate::types::{Blob, TileCoord3};
+use anyhow::Result;
+use futures::{
+	future::ready,
+	stream::{BoxStream, StreamExt},
+	Future,
+};
+use std::sync::Arc;
+
+/// A wrapper that encapsulates a stream of `Result<(TileCoord3, Blob)>` items.
+pub struct TryTileStream<'a> {
+	/// The internal boxed stream, emitting a tile or the error that occurred instead.
+	pub stream: BoxStream<'a, Result<(TileCoord3, Blob)>>,
+}
+
+impl<'a> TryTileStream<'a> {
+	/// Creates a `TryTileStream` from an existing stream of results.
+	pub fn from_stream(stream: BoxStream<'a, Result<(TileCoord3, Blob)>>) -> Self {
+		TryTileStream { stream }
+	}
+
+	/// Creates a `TryTileStream` by mapping an async closure over a vector of tile coordinates.
+	///
+	/// The closure `callback` returns `Ok(None)` for missing tiles, which are skipped, and `Err` for tiles
+	/// that could not be read, which are emitted as errors.
+	///
+	/// # Examples
+	/// ```
+	/// # use anyhow::bail;
+	/// # use versatiles_core::types::{TileCoord3, Blob, TryTileStream};
+	/// # async fn example() {
+	/// let coords = vec![TileCoord3::new(0,0,0).unwrap(), TileCoord3::new(1,1,1).unwrap()];
+	/// let stream = TryTileStream::from_coord_vec_async(coords, |coord: TileCoord3| async move {
+	///     if coord.z == 0 {
+	///         Ok(Some((coord, Blob::from("data"))))
+	///     } else {
+	///         bail!("read error")
+	///     }
+	/// });
+	/// assert!(stream.collect().await.is_err());
+	/// # }
+	/// ```
+	pub fn from_coord_vec_async<F, Fut>(vec: Vec<TileCoord3>, callback: F) -> Self
+	where
+		F: FnMut(TileCoord3) -> Fut + Send + 'a,
+		Fut: Future<Output = Result<Option<(TileCoord3, Blob)>>> + Send + 'a,
+	{
+		let s = futures::stream::iter(vec)
+			.then(callback)
+			.filter_map(|result| ready(result.transpose()));
+		TryTileStream { stream: s.boxed() }
+	}
+
+	/// Retrieves the next item from this stream, or `None` if the stream is empty.
+	pub async fn next(&mut self) -> Option<Result<(TileCoord3, Blob)>> {
+		self.stream.next().await
+	}
+
+	/// Collects all tiles into a vector, stopping at the first error.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	/// ]).into_try_stream();
+	/// assert_eq!(stream.collect().await.unwrap().len(), 1);
+	/// # }
+	/// ```
+	pub async fn collect(mut self) -> Result<Vec<(TileCoord3, Blob)>> {
+		let mut items = Vec::new();
+		while let Some(item) = self.stream.next().await {
+			items.push(item?);
+		}
+		Ok(items)
+	}
+
+	/// Applies a synchronous callback to each tile, stopping at the first error of the stream or of `callback`.
+	pub async fn try_for_each_sync<F>(mut self, mut callback: F) -> Result<()>
+	where
+		F: FnMut((TileCoord3, Blob)) -> Result<()>,
+	{
+		while let Some(item) = self.stream.next().await {
+			callback(item?)?;
+		}
+		Ok(())
+	}
+
+	/// Transforms each tile in parallel, keeping the order of the stream. Errors are passed through,
+	/// errors of `callback` are emitted at the position of the tile.
+	pub fn try_map_parallel<F>(self, concurrency: usize, callback: F) -> Self
+	where
+		F: Fn(TileCoord3, Blob) -> Result<Blob> + Send + Sync + 'static,
+	{
+		let arc_cb = Arc::new(callback);
+		let s = self
+			.stream
+			.map(move |item| {
+				let cb = Arc::clone(&arc_cb);
+				tokio::spawn(async move {
+					let (coord, blob) = item?;
+					cb(coord, blob).map(|blob| (coord, blob))
+				})
+			})
+			.buffered(concurrency.max(1))
+			.map(|result| match result {
+				Ok(result) => result,
+				Err(err) => Err(anyhow::anyhow!("processing a tile failed: {err}")),
+			});
+		TryTileStream { stream: s.boxed() }
+	}
+
+	/// Reads up to `size` items ahead of the consumer. The order of the items is kept.
+	pub fn buffered(self, size: usize) -> Self {
+		TryTileStream {
+			stream: self.stream.map(ready).buffered(size.max(1)).boxed(),
+		}
+	}
+
+	/// Converts this stream into a [`TileStream`], calling `on_error` for every error instead of emitting it.
+	///
+	/// # Examples
+	/// ```
+	/// # use versatiles_core::types::{TileCoord3, Blob, TileStream};
+	/// # async fn test() {
+	/// let stream = TileStream::from_vec(vec![
+	///     (TileCoord3::new(0,0,0).unwrap(), Blob::from("data0")),
+	///     (TileCoord3::new(1,1,1).unwrap(), Blob::from("broken")),
+	/// ]).try_map_parallel(2, |_coord, blob| {
+	///     anyhow::ensure!(blob.as_str() != "broken", "broken tile");
+	///     Ok(blob)
+	/// });
+	///
+	/// let mut errors = 0;
+	/// let items = stream.into_tile_stream(|_err| errors += 1).collect().await;
+	/// assert_eq!(items.len(), 1);
+	/// # }
+	/// ```
+	pub fn into_tile_stream<F>(self, mut on_error: F) -> TileStream<'a>
+	where
+		F: FnMut(anyhow::Error) + Send + 'a,
+	{
+		TileStream::from_stream(
+			self
+				.stream
+				.filter_map(move |item| {
+					ready(match item {
+						Ok(item) => Some(item),
+						Err(err) => {
+							on_error(err);
+							None
+						}
+					})
+				})
+				.boxed(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::bail;
+
+	fn coords() -> Vec<TileCoord3> {
+		(0..4).map(|x| TileCoord3::new(x, 0, 2).unwrap()).collect()
+	}
+
+	#[tokio::test]
+	async fn should_skip_missing_and_emit_errors() {
+		let mut stream = TryTileStream::from_coord_vec_async(coords(), |coord| async move {
+			match coord.x {
+				1 => Ok(None),
+				2 => bail!("read error"),
+				_ => Ok(Some((coord, Blob::from(format!("{}", coord.x))))),
+			}
+		});
+
+		assert_eq!(stream.next().await.unwrap().unwrap().1.as_str(), "0");
+		assert_eq!(stream.next().await.unwrap().unwrap_err().to_string(), "read error");
+		assert_eq!(stream.next().await.unwrap().unwrap().1.as_str(), "3");
+		assert!(stream.next().await.is_none());
+	}
+
+	#[tokio::test]
+	async fn should_stop_at_first_error() {
+		let stream = TryTileStream::from_coord_vec_async(coords(), |coord| async move {
+			if coord.x == 1 {
+				bail!("read error")
+			}
+			Ok(Some((coord, Blob::from("data"))))
+		});
+
+		let mut count = 0;
+		let result = stream
+			.try_for_each_sync(|_| {
+				count += 1;
+				Ok(())
+			})
+			.await;
+		assert_eq!(result.unwrap_err().to_string(), "read error");
+		assert_eq!(count, 1);
+	}
+
+	#[tokio::test]
+	async fn should_pass_errors_through_map_and_count_them() {
+		let stream = TryTileStream::from_coord_vec_async(coords(), |coord| async move {
+			if coord.x == 1 {
+				bail!("read error")
+			}
+			Ok(Some((coord, Blob::from("data"))))
+		})
+		.buffered(2)
+		.try_map_parallel(2, |coord, blob| {
+			anyhow::ensure!(coord.x != 3, "processing error");
+			Ok(blob)
+		});
+
+		let mut errors = Vec::new();
+		let items = stream.into_tile_stream(|err| errors.push(err.to_string())).collect().await;
+		assert_eq!(items.len(), 2);
+		assert_eq!(errors, vec!["read error", "processing error"]);
+	}
+}
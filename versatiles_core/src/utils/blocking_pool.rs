@@ -0,0 +1,116 @@
+//! # Blocking Pool
+//!
+//! Runs CPU-heavy work, like decoding and encoding raster images, on tokio's blocking threads instead of the
+//! async runtime, so that it can't starve the tasks handling network I/O. The size of the pool limits how many
+//! of these jobs run at the same time; further jobs wait without blocking a thread.
+//!
+//! ## Usage
+//! ```rust
+//! use versatiles_core::utils::run_blocking;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let sum = run_blocking(|| Ok((0..1000u64).sum::<u64>())).await?;
+//! assert_eq!(sum, 499500);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::get_thread_count;
+use anyhow::{anyhow, ensure, Result};
+use std::sync::{
+	atomic::{AtomicUsize, Ordering},
+	OnceLock,
+};
+use tokio::sync::Semaphore;
+
+/// The configured size, or 0 for the number of threads.
+static POOL_SIZE: AtomicUsize = AtomicUsize::new(0);
+static SEMAPHORE: OnceLock<Semaphore> = OnceLock::new();
+
+fn get_semaphore() -> &'static Semaphore {
+	SEMAPHORE.get_or_init(|| Semaphore::new(get_blocking_pool_size()))
+}
+
+/// Sets how many blocking jobs may run at the same time. It defaults to the number of threads
+/// (see [`get_thread_count`]).
+///
+/// The size can't be changed after the pool has been used.
+pub fn set_blocking_pool_size(size: usize) -> Result<()> {
+	ensure!(size > 0, "the size of the blocking pool must be at least 1");
+	if SEMAPHORE.get().is_some() {
+		ensure!(
+			get_blocking_pool_size() == size,
+			"the size of the blocking pool can't be changed after it has been used"
+		);
+	}
+	POOL_SIZE.store(size, Ordering::Relaxed);
+	Ok(())
+}
+
+/// Returns how many blocking jobs may run at the same time.
+pub fn get_blocking_pool_size() -> usize {
+	match POOL_SIZE.load(Ordering::Relaxed) {
+		0 => get_thread_count(),
+		size => size,
+	}
+}
+
+/// Runs `callback` on a blocking thread, as soon as the pool has room for it.
+///
+/// Panics of `callback` are returned as errors.
+pub async fn run_blocking<T, F>(callback: F) -> Result<T>
+where
+	F: FnOnce() -> Result<T> + Send + 'static,
+	T: Send + 'static,
+{
+	let _permit = get_semaphore().acquire().await?;
+	tokio::task::spawn_blocking(callback)
+		.await
+		.map_err(|err| anyhow!("blocking job failed: {err}"))?
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::{atomic::AtomicUsize, Arc};
+
+	#[tokio::test]
+	async fn runs_jobs() -> Result<()> {
+		assert_eq!(run_blocking(|| Ok(42)).await?, 42);
+		assert!(run_blocking(|| -> Result<()> { Err(anyhow!("broken")) }).await.is_err());
+		assert!(run_blocking(|| -> Result<()> { panic!("broken") }).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+	async fn limits_running_jobs() -> Result<()> {
+		let running = Arc::new(AtomicUsize::new(0));
+		let maximum = Arc::new(AtomicUsize::new(0));
+		let jobs = (0..get_blocking_pool_size() * 4).map(|_| {
+			let running = running.clone();
+			let maximum = maximum.clone();
+			run_blocking(move || {
+				let count = running.fetch_add(1, Ordering::SeqCst) + 1;
+				maximum.fetch_max(count, Ordering::SeqCst);
+				std::thread::sleep(std::time::Duration::from_millis(5));
+				running.fetch_sub(1, Ordering::SeqCst);
+				Ok(())
+			})
+		});
+		futures::future::try_join_all(jobs).await?;
+		assert!(maximum.load(Ordering::SeqCst) <= get_blocking_pool_size());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn size() -> Result<()> {
+		assert!(set_blocking_pool_size(0).is_err());
+		run_blocking(|| Ok(())).await?;
+		let size = get_blocking_pool_size();
+		assert!(size > 0);
+		assert!(set_blocking_pool_size(size).is_ok());
+		assert!(set_blocking_pool_size(size + 1).is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,137 @@
+//! # Concurrency
+//!
+//! The process-wide settings for parallel work, shared by all tools so that they behave the same on a 1-core
+//! container and on a 96-core build machine:
+//!
+//! - the **thread count** sizes the async runtime, the blocking pool and the number of tiles that are
+//!   processed at the same time. It defaults to the number of CPUs.
+//! - the **I/O concurrency** is the number of reads that a single source runs at the same time, e.g. range
+//!   requests to a remote container or connections to an SQLite database. It defaults to 8.
+//!
+//! Both can be set with the environment variables `VERSATILES_THREADS` and `VERSATILES_IO_CONCURRENCY`, or
+//! with the setters, which take precedence.
+//!
+//! ## Usage
+//! ```rust
+//! use versatiles_core::utils::{get_io_concurrency, set_io_concurrency};
+//!
+//! set_io_concurrency(4).unwrap();
+//! assert_eq!(get_io_concurrency(), 4);
+//! ```
+
+use anyhow::{ensure, Result};
+use std::sync::{
+	atomic::{AtomicUsize, Ordering},
+	OnceLock,
+};
+
+/// Environment variable with the number of threads.
+pub const THREADS_ENV: &str = "VERSATILES_THREADS";
+
+/// Environment variable with the number of concurrent reads per source.
+pub const IO_CONCURRENCY_ENV: &str = "VERSATILES_IO_CONCURRENCY";
+
+/// Number of concurrent reads per source, unless configured otherwise.
+const DEFAULT_IO_CONCURRENCY: usize = 8;
+
+/// A count that is configured with a setter or with an environment variable.
+struct Setting {
+	/// The configured value, or 0 if it hasn't been set.
+	value: AtomicUsize,
+	env: &'static str,
+	/// The value of the environment variable, parsed on first use, so invalid values are reported only once.
+	env_value: OnceLock<Option<usize>>,
+}
+
+impl Setting {
+	const fn new(env: &'static str) -> Setting {
+		Setting {
+			value: AtomicUsize::new(0),
+			env,
+			env_value: OnceLock::new(),
+		}
+	}
+
+	fn set(&self, count: usize) {
+		self.value.store(count, Ordering::Relaxed);
+	}
+
+	fn get(&self, default: fn() -> usize) -> usize {
+		match self.value.load(Ordering::Relaxed) {
+			0 => self
+				.env_value
+				.get_or_init(|| parse_env(self.env, std::env::var(self.env).ok()))
+				.unwrap_or_else(default),
+			value => value,
+		}
+	}
+}
+
+static THREADS: Setting = Setting::new(THREADS_ENV);
+static IO_CONCURRENCY: Setting = Setting::new(IO_CONCURRENCY_ENV);
+
+/// Sets the number of threads, overriding `VERSATILES_THREADS`.
+///
+/// Set it before the async runtime is started, since the runtime can't be resized.
+pub fn set_thread_count(count: usize) -> Result<()> {
+	ensure!(count > 0, "the number of threads must be at least 1");
+	THREADS.set(count);
+	Ok(())
+}
+
+/// Returns the number of threads for CPU-heavy work: the configured value, `VERSATILES_THREADS` or the
+/// number of CPUs.
+pub fn get_thread_count() -> usize {
+	THREADS.get(num_cpus::get)
+}
+
+/// Sets the number of concurrent reads per source, overriding `VERSATILES_IO_CONCURRENCY`.
+///
+/// It only affects sources that are opened afterwards.
+pub fn set_io_concurrency(count: usize) -> Result<()> {
+	ensure!(count > 0, "the I/O concurrency must be at least 1");
+	IO_CONCURRENCY.set(count);
+	Ok(())
+}
+
+/// Returns the number of concurrent reads per source: the configured value, `VERSATILES_IO_CONCURRENCY`
+/// or 8.
+pub fn get_io_concurrency() -> usize {
+	IO_CONCURRENCY.get(|| DEFAULT_IO_CONCURRENCY)
+}
+
+/// Parses the value of an environment variable. Invalid values are ignored with a warning.
+fn parse_env(env: &str, value: Option<String>) -> Option<usize> {
+	let value = value?;
+	match value.trim().parse::<usize>() {
+		Ok(count) if count > 0 => Some(count),
+		_ => {
+			log::warn!("ignoring {env}={value:?}, it must be a positive integer");
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		assert_eq!(parse_env(THREADS_ENV, None), None);
+		assert_eq!(parse_env(THREADS_ENV, Some(String::from("12"))), Some(12));
+		assert_eq!(parse_env(THREADS_ENV, Some(String::from(" 3 "))), Some(3));
+		assert_eq!(parse_env(THREADS_ENV, Some(String::from("0"))), None);
+		assert_eq!(parse_env(THREADS_ENV, Some(String::from("many"))), None);
+	}
+
+	#[test]
+	fn setters() {
+		assert!(set_thread_count(0).is_err());
+		assert!(set_io_concurrency(0).is_err());
+		assert!(get_thread_count() > 0);
+
+		set_io_concurrency(3).unwrap();
+		assert_eq!(get_io_concurrency(), 3);
+	}
+}
@@ -0,0 +1,246 @@
+//! Verifies that tile contents match their declared format and compression.
+//!
+//! Mislabeled tiles, e.g. gzip compressed vector tiles in a container that declares them as uncompressed, are
+//! stored without complaint and only fail when a client tries to render them. The checks look at the magic bytes
+//! of a tile and, for formats without magic bytes, whether the content can be parsed.
+
+use super::{decompress, decompress_brotli, decompress_gzip};
+use crate::{
+	io::decode_varint,
+	json::JsonValue,
+	types::{Blob, TileCompression, TileFormat},
+};
+use anyhow::{bail, Context, Result};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Guesses the compression of a tile from its first bytes.
+///
+/// Only gzip can be recognized. Brotli has no magic bytes, so brotli compressed data is not detected.
+pub fn guess_compression(blob: &Blob) -> Option<TileCompression> {
+	blob.as_slice().starts_with(GZIP_MAGIC).then_some(TileCompression::Gzip)
+}
+
+/// Guesses the format of an uncompressed tile from its content.
+///
+/// Images are recognized by their magic bytes, text formats by parsing them. Vector tiles have no magic bytes:
+/// any data that can be read as a protobuf message is assumed to be a vector tile.
+pub fn guess_format(blob: &Blob) -> Option<TileFormat> {
+	let data = blob.as_slice();
+	if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+		return Some(TileFormat::PNG);
+	}
+	if data.starts_with(&[0xff, 0xd8, 0xff]) {
+		return Some(TileFormat::JPG);
+	}
+	if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+		return Some(TileFormat::WEBP);
+	}
+	if data.len() >= 12 && &data[4..8] == b"ftyp" && (&data[8..12] == b"avif" || &data[8..12] == b"avis") {
+		return Some(TileFormat::AVIF);
+	}
+
+	if let Ok(text) = std::str::from_utf8(data) {
+		let text = text.trim_start();
+		if text.starts_with('<') && text.contains("<svg") {
+			return Some(TileFormat::SVG);
+		}
+		if text.starts_with('{') || text.starts_with('[') {
+			if let Ok(json) = JsonValue::parse_str(text) {
+				let json_type = json.as_object().ok().and_then(|o| o.get_string("type").ok().flatten());
+				return Some(match json_type.as_deref() {
+					Some("Topology") => TileFormat::TOPOJSON,
+					Some("FeatureCollection" | "Feature") => TileFormat::GEOJSON,
+					_ => TileFormat::JSON,
+				});
+			}
+		}
+	}
+
+	if !data.is_empty() && check_protobuf(data).is_ok() {
+		return Some(TileFormat::PBF);
+	}
+
+	None
+}
+
+/// Guesses the format and compression of a tile.
+///
+/// Gzip is recognized by its magic bytes. Brotli has none, so data is assumed to be brotli compressed if it can be
+/// decompressed as brotli and the result has a recognizable format.
+pub fn sniff_tile(blob: &Blob) -> Option<(TileFormat, TileCompression)> {
+	if guess_compression(blob) == Some(TileCompression::Gzip) {
+		let blob = decompress_gzip(blob).ok()?;
+		return Some((guess_format(&blob)?, TileCompression::Gzip));
+	}
+	if let Some(format) = guess_format(blob) {
+		return Some((format, TileCompression::Uncompressed));
+	}
+	let blob = decompress_brotli(blob).ok()?;
+	Some((guess_format(&blob)?, TileCompression::Brotli))
+}
+
+/// Checks that a compressed tile matches the declared format and compression.
+///
+/// # Errors
+/// Returns an error describing the mismatch, e.g. "declared as uncompressed, but is gzip compressed".
+pub fn verify_tile_content(blob: &Blob, format: TileFormat, compression: TileCompression) -> Result<()> {
+	let guessed_compression = guess_compression(blob);
+	match compression {
+		TileCompression::Uncompressed => {
+			if guessed_compression == Some(TileCompression::Gzip) && format != TileFormat::BIN {
+				bail!("declared as uncompressed, but is gzip compressed");
+			}
+		}
+		TileCompression::Gzip => {
+			if guessed_compression != Some(TileCompression::Gzip) {
+				bail!("declared as gzip compressed, but has no gzip header");
+			}
+		}
+		TileCompression::Brotli => {
+			if guessed_compression == Some(TileCompression::Gzip) {
+				bail!("declared as brotli compressed, but is gzip compressed");
+			}
+		}
+	}
+
+	let blob = decompress(blob.clone(), &compression).with_context(|| format!("decompressing {compression} data"))?;
+	verify_format(&blob, format)
+}
+
+/// Checks that an uncompressed tile matches the declared format.
+fn verify_format(blob: &Blob, format: TileFormat) -> Result<()> {
+	use TileFormat::*;
+
+	let guessed = guess_format(blob);
+	let is_valid = match format {
+		BIN => true,
+		// an empty vector tile is valid
+		PBF => blob.is_empty() || check_protobuf(blob.as_slice()).is_ok(),
+		// GeoJSON and TopoJSON are JSON too
+		JSON => matches!(guessed, Some(JSON | GEOJSON | TOPOJSON)),
+		AVIF | GEOJSON | JPG | PNG | SVG | TOPOJSON | WEBP => guessed == Some(format),
+	};
+
+	if !is_valid {
+		match guessed {
+			Some(guessed) if guessed != format => bail!("declared as {format}, but looks like {guessed}"),
+			_ => bail!("declared as {format}, but can not be parsed as {format}"),
+		}
+	}
+	Ok(())
+}
+
+/// Checks that data is a sequence of well-formed protobuf fields.
+fn check_protobuf(data: &[u8]) -> Result<()> {
+	let mut pos = 0;
+	while pos < data.len() {
+		let key = decode_varint(data, &mut pos)?;
+		if key >> 3 == 0 {
+			bail!("invalid field number 0");
+		}
+		let length = match key & 7 {
+			0 => {
+				decode_varint(data, &mut pos)?;
+				0
+			}
+			1 => 8,
+			2 => decode_varint(data, &mut pos)?,
+			5 => 4,
+			wire_type => bail!("invalid wire type {wire_type}"),
+		};
+		pos = pos
+			.checked_add(usize::try_from(length)?)
+			.filter(|end| *end <= data.len())
+			.context("field exceeds the end of data")?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::{compress_brotli, compress_gzip};
+
+	const PNG_DATA: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+	// a vector tile with one empty layer named "a"
+	const PBF_DATA: &[u8] = &[0x1a, 0x03, 0x0a, 0x01, b'a'];
+
+	#[test]
+	fn guess() {
+		let guess = |data: &[u8]| guess_format(&Blob::from(data));
+		assert_eq!(guess(PNG_DATA), Some(TileFormat::PNG));
+		assert_eq!(guess(&[0xff, 0xd8, 0xff, 0xe0]), Some(TileFormat::JPG));
+		assert_eq!(guess(b"RIFF\0\0\0\0WEBPVP8 "), Some(TileFormat::WEBP));
+		assert_eq!(guess(b"\0\0\0\x1cftypavif"), Some(TileFormat::AVIF));
+		assert_eq!(guess(b"<?xml?><svg></svg>"), Some(TileFormat::SVG));
+		assert_eq!(guess(b"{\"a\":1}"), Some(TileFormat::JSON));
+		assert_eq!(
+			guess(b"{\"type\":\"FeatureCollection\",\"features\":[]}"),
+			Some(TileFormat::GEOJSON)
+		);
+		assert_eq!(guess(b"{\"type\":\"Topology\"}"), Some(TileFormat::TOPOJSON));
+		assert_eq!(guess(PBF_DATA), Some(TileFormat::PBF));
+		assert_eq!(guess(&[0x1f, 0x8b, 0x08]), None);
+		assert_eq!(guess(b""), None);
+
+		assert_eq!(
+			guess_compression(&compress_gzip(&Blob::from(PBF_DATA)).unwrap()),
+			Some(TileCompression::Gzip)
+		);
+		assert_eq!(guess_compression(&Blob::from(PBF_DATA)), None);
+	}
+
+	#[test]
+	fn sniff() -> Result<()> {
+		use TileCompression::*;
+		use TileFormat::*;
+
+		let pbf = Blob::from(PBF_DATA);
+		assert_eq!(sniff_tile(&pbf), Some((PBF, Uncompressed)));
+		assert_eq!(sniff_tile(&compress_gzip(&pbf)?), Some((PBF, Gzip)));
+		assert_eq!(sniff_tile(&compress_brotli(&pbf)?), Some((PBF, Brotli)));
+		assert_eq!(sniff_tile(&compress_gzip(&Blob::from(PNG_DATA))?), Some((PNG, Gzip)));
+		assert_eq!(sniff_tile(&Blob::from(&[0x1f, 0x8b, 0x08][..])), None);
+		Ok(())
+	}
+
+	#[test]
+	fn verify() -> Result<()> {
+		use TileCompression::*;
+		use TileFormat::*;
+
+		let pbf = Blob::from(PBF_DATA);
+		let check =
+			|blob: &Blob, format, compression| verify_tile_content(blob, format, compression).map_err(|e| e.to_string());
+
+		assert_eq!(check(&pbf, PBF, Uncompressed), Ok(()));
+		assert_eq!(check(&Blob::new_empty(), PBF, Uncompressed), Ok(()));
+		assert_eq!(check(&compress_gzip(&pbf)?, PBF, Gzip), Ok(()));
+		assert_eq!(check(&compress_brotli(&pbf)?, PBF, Brotli), Ok(()));
+		assert_eq!(check(&Blob::from(PNG_DATA), PNG, Uncompressed), Ok(()));
+		assert_eq!(check(&Blob::from("{\"type\":\"Feature\"}"), JSON, Uncompressed), Ok(()));
+
+		assert_eq!(
+			check(&compress_gzip(&pbf)?, PBF, Uncompressed),
+			Err(String::from("declared as uncompressed, but is gzip compressed"))
+		);
+		assert_eq!(
+			check(&pbf, PBF, Gzip),
+			Err(String::from("declared as gzip compressed, but has no gzip header"))
+		);
+		assert_eq!(
+			check(&compress_gzip(&pbf)?, PBF, Brotli),
+			Err(String::from("declared as brotli compressed, but is gzip compressed"))
+		);
+		assert_eq!(
+			check(&Blob::from(PNG_DATA), JPG, Uncompressed),
+			Err(String::from("declared as jpg, but looks like png"))
+		);
+		assert_eq!(
+			check(&Blob::from(&[0x1a, 0x10, 0x0a][..]), PBF, Uncompressed),
+			Err(String::from("declared as pbf, but can not be parsed as pbf"))
+		);
+		Ok(())
+	}
+}
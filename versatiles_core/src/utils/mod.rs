@@ -1,10 +1,16 @@
+mod blocking_pool;
 mod compression;
+mod concurrency;
+mod content;
 mod csv;
 #[cfg(feature = "cli")]
 mod pretty_print;
 mod transform_coord;
 
+pub use blocking_pool::*;
 pub use compression::*;
+pub use concurrency::*;
+pub use content::*;
 pub use csv::*;
 #[cfg(feature = "cli")]
 pub use pretty_print::*;
@@ -28,6 +28,7 @@ impl TransformCoord for TileBBox {
 	}
 	fn swap_xy(&mut self) {
 		if !self.is_empty() {
+			assert!(self.grid.is_square(), "can not swap x and y in grid {}", self.grid);
 			swap(&mut self.x_min, &mut self.y_min);
 			swap(&mut self.x_max, &mut self.y_max);
 		}
@@ -69,6 +69,10 @@ pub fn decode_struct(input: DeriveInput, data_struct: DataStruct) -> TokenStream
 					format!("* **`{field_str}`: [f64,f64,f64,f64] (required)**{comment}"),
 					quote! { #field_name: node.get_property_number_array4_req::<f64>(#field_str)? },
 				),
+				"Vec<String>" => (
+					format!("* *`{field_str}`: [String] (optional)*{comment}"),
+					quote! { #field_name: node.get_property_string_vec(#field_str) },
+				),
 				"Option<String>" => (
 					format!("* *`{field_str}`: String (optional)*{comment}"),
 					quote! { #field_name: node.get_property_string(#field_str)? },
@@ -77,6 +81,10 @@ pub fn decode_struct(input: DeriveInput, data_struct: DataStruct) -> TokenStream
 					format!("* *`{field_str}`: f32 (optional)*{comment}"),
 					quote! { #field_name: node.get_property_number::<f32>(#field_str)? },
 				),
+				"Option<f64>" => (
+					format!("* *`{field_str}`: f64 (optional)*{comment}"),
+					quote! { #field_name: node.get_property_number::<f64>(#field_str)? },
+				),
 				"Option<u8>" => (
 					format!("* *`{field_str}`: u8 (optional)*{comment}"),
 					quote! { #field_name: node.get_property_number::<u8>(#field_str)? },
@@ -0,0 +1,116 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::fs::read;
+use versatiles_core::types::Blob;
+use versatiles_geometry::{
+	vector_tile::{VectorTile, VectorTileFeature, VectorTileView},
+	Geometry,
+};
+
+fn get_pbf() -> Blob {
+	Blob::from(read("../testdata/shortbread-tile.pbf").unwrap())
+}
+
+/// A random walk through the tile, like a coastline at low zoom levels.
+fn get_walk(seed: u64, length: usize) -> Vec<[i64; 2]> {
+	let mut state = seed;
+	let mut point = [2048i64, 2048i64];
+	(0..length)
+		.map(|_| {
+			state = state
+				.wrapping_mul(6364136223846793005)
+				.wrapping_add(1442695040888963407);
+			point[0] = (point[0] + ((state >> 33) % 201) as i64 - 100).clamp(-64, 4160);
+			point[1] = (point[1] + ((state >> 45) % 201) as i64 - 100).clamp(-64, 4160);
+			point
+		})
+		.collect()
+}
+
+/// Large features, like the coastlines and landuse areas of z5–z8 tiles.
+fn get_large_features() -> Vec<VectorTileFeature> {
+	let coastline = Geometry::new_multi_line_string((0..20).map(|i| get_walk(i, 10_000)).collect());
+	let landuse = Geometry::new_multi_polygon(
+		(0..2_000)
+			.map(|i| {
+				let mut ring = get_walk(i, 100);
+				ring.push(ring[0]);
+				vec![ring]
+			})
+			.collect(),
+	);
+	vec![
+		VectorTileFeature::from_geometry(None, vec![], coastline).unwrap(),
+		VectorTileFeature::from_geometry(None, vec![], landuse).unwrap(),
+	]
+}
+
+fn bench_decode(c: &mut Criterion) {
+	let blob = get_pbf();
+	c.bench_function("VectorTile from_blob", |b| {
+		b.iter(|| black_box(VectorTile::from_blob(&blob).unwrap()))
+	});
+}
+
+fn bench_encode(c: &mut Criterion) {
+	let tile = VectorTile::from_blob(&get_pbf()).unwrap();
+	c.bench_function("VectorTile to_blob", |b| b.iter(|| black_box(tile.to_blob().unwrap())));
+}
+
+fn bench_geometry(c: &mut Criterion) {
+	let tile = VectorTile::from_blob(&get_pbf()).unwrap();
+	let features: Vec<&VectorTileFeature> = tile.layers.iter().flat_map(|layer| layer.features.iter()).collect();
+	c.bench_function("VectorTileFeature to_geometry", |b| {
+		b.iter(|| {
+			for feature in features.iter() {
+				black_box(feature.to_geometry().unwrap());
+			}
+		})
+	});
+
+	let features = get_large_features();
+	c.bench_function("VectorTileFeature to_geometry large", |b| {
+		b.iter(|| {
+			for feature in features.iter() {
+				black_box(feature.to_geometry().unwrap());
+			}
+		})
+	});
+	c.bench_function("VectorTileFeature count_vertices large", |b| {
+		b.iter(|| {
+			for feature in features.iter() {
+				black_box(feature.count_vertices().unwrap());
+			}
+		})
+	});
+}
+
+fn bench_layer_names(c: &mut Criterion) {
+	let blob = get_pbf();
+	c.bench_function("VectorTileView layer_names", |b| {
+		b.iter(|| {
+			let view = VectorTileView::new(blob.as_slice()).unwrap();
+			black_box(view.layer_names().unwrap())
+		})
+	});
+}
+
+fn bench_retain_layers(c: &mut Criterion) {
+	let blob = get_pbf();
+	c.bench_function("VectorTileView retain_layers", |b| {
+		b.iter(|| {
+			let mut view = VectorTileView::new(blob.as_slice()).unwrap();
+			view.retain_layers(|name| name.starts_with("water")).unwrap();
+			black_box(view.to_blob().unwrap())
+		})
+	});
+}
+
+criterion_group!(
+	vector_tile,
+	bench_decode,
+	bench_encode,
+	bench_geometry,
+	bench_layer_names,
+	bench_retain_layers
+);
+criterion_main!(vector_tile);
@@ -0,0 +1,220 @@
+//! A minimal FlatBuffers encoder, just enough for the header and the features of FlatGeobuf files.
+//!
+//! Tables are written front to back: every table is preceded by its vtable and followed by the strings, vectors
+//! and tables it references, so that all offsets point forward, as FlatBuffers requires.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+/// A value of a table field.
+#[derive(Clone, Debug)]
+pub(super) enum Value {
+	U8(u8),
+	U16(u16),
+	I32(i32),
+	U64(u64),
+	String(String),
+	Bytes(Vec<u8>),
+	U32s(Vec<u32>),
+	F64s(Vec<f64>),
+	Table(Table),
+	Tables(Vec<Table>),
+}
+
+impl Value {
+	/// Size of the value inside of the table. References to strings, vectors and tables are 32 bit offsets.
+	fn inline_size(&self) -> usize {
+		match self {
+			Value::U8(_) => 1,
+			Value::U16(_) => 2,
+			Value::U64(_) => 8,
+			_ => 4,
+		}
+	}
+}
+
+/// A table with the values of its fields, identified by their index in the schema.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Table(Vec<(u16, Value)>);
+
+impl Table {
+	pub(super) fn set(&mut self, index: u16, value: Value) {
+		self.0.push((index, value));
+	}
+
+	/// Returns the table as a size prefixed FlatBuffer, with the table as root.
+	pub(super) fn to_size_prefixed_bytes(&self) -> Vec<u8> {
+		// size prefix and offset of the root table
+		let mut buf = vec![0u8; 8];
+		let root = write_table(&mut buf, self);
+		set_u32(&mut buf, 4, (root - 4) as u32);
+		let size = buf.len() - 4;
+		set_u32(&mut buf, 0, size as u32);
+		buf
+	}
+}
+
+fn set_u32(buf: &mut [u8], pos: usize, value: u32) {
+	buf[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Appends zeros until the length of the buffer modulo `align` is `remainder`.
+fn pad(buf: &mut Vec<u8>, align: usize, remainder: usize) {
+	while buf.len() % align != remainder {
+		buf.push(0);
+	}
+}
+
+/// Appends the vtable and the table, followed by everything it references, and returns the position of the table.
+fn write_table(buf: &mut Vec<u8>, table: &Table) -> usize {
+	// larger fields first, so that all fields are aligned without gaps
+	let mut fields: Vec<&(u16, Value)> = table.0.iter().collect();
+	fields.sort_by_key(|(_, value)| std::cmp::Reverse(value.inline_size()));
+
+	let slot_count = fields.iter().map(|(index, _)| *index as usize + 1).max().unwrap_or(0);
+	let mut slots = vec![0u16; slot_count];
+	let mut size: usize = 4; // offset of the vtable
+	for (index, value) in fields.iter() {
+		let field_size = value.inline_size();
+		size = size.next_multiple_of(field_size);
+		slots[*index as usize] = size as u16;
+		size += field_size;
+	}
+
+	pad(buf, 2, 0);
+	let vtable_pos = buf.len();
+	buf.write_u16::<LittleEndian>(4 + 2 * slot_count as u16).unwrap();
+	buf.write_u16::<LittleEndian>(size as u16).unwrap();
+	for slot in slots.iter() {
+		buf.write_u16::<LittleEndian>(*slot).unwrap();
+	}
+
+	pad(buf, 8, 0);
+	let table_pos = buf.len();
+	buf.write_i32::<LittleEndian>((table_pos - vtable_pos) as i32).unwrap();
+	buf.resize(table_pos + size, 0);
+
+	let mut references = Vec::new();
+	for (index, value) in fields.iter() {
+		let pos = table_pos + slots[*index as usize] as usize;
+		let mut field = &mut buf[pos..];
+		match value {
+			Value::U8(v) => field.write_u8(*v).unwrap(),
+			Value::U16(v) => field.write_u16::<LittleEndian>(*v).unwrap(),
+			Value::I32(v) => field.write_i32::<LittleEndian>(*v).unwrap(),
+			Value::U64(v) => field.write_u64::<LittleEndian>(*v).unwrap(),
+			_ => references.push((pos, value)),
+		}
+	}
+
+	for (pos, value) in references {
+		let target = write_reference(buf, value);
+		set_u32(buf, pos, (target - pos) as u32);
+	}
+
+	table_pos
+}
+
+/// Appends a string, vector or table and returns its position.
+fn write_reference(buf: &mut Vec<u8>, value: &Value) -> usize {
+	match value {
+		Value::String(text) => {
+			let pos = write_length(buf, text.len(), 1);
+			buf.extend_from_slice(text.as_bytes());
+			buf.push(0);
+			pos
+		}
+		Value::Bytes(bytes) => {
+			let pos = write_length(buf, bytes.len(), 1);
+			buf.extend_from_slice(bytes);
+			pos
+		}
+		Value::U32s(values) => {
+			let pos = write_length(buf, values.len(), 4);
+			for v in values {
+				buf.write_u32::<LittleEndian>(*v).unwrap();
+			}
+			pos
+		}
+		Value::F64s(values) => {
+			let pos = write_length(buf, values.len(), 8);
+			for v in values {
+				buf.write_f64::<LittleEndian>(*v).unwrap();
+			}
+			pos
+		}
+		Value::Table(table) => write_table(buf, table),
+		Value::Tables(tables) => {
+			let pos = write_length(buf, tables.len(), 4);
+			let first_slot = buf.len();
+			buf.resize(first_slot + 4 * tables.len(), 0);
+			for (i, table) in tables.iter().enumerate() {
+				let slot = first_slot + 4 * i;
+				let target = write_table(buf, table);
+				set_u32(buf, slot, (target - slot) as u32);
+			}
+			pos
+		}
+		Value::U8(_) | Value::U16(_) | Value::I32(_) | Value::U64(_) => unreachable!("scalars are stored inline"),
+	}
+}
+
+/// Appends the length of a vector, so that the elements that follow are aligned, and returns its position.
+fn write_length(buf: &mut Vec<u8>, length: usize, element_size: usize) -> usize {
+	let align = element_size.max(4);
+	pad(buf, align, align - 4);
+	let pos = buf.len();
+	buf.write_u32::<LittleEndian>(length as u32).unwrap();
+	pos
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn table() {
+		let mut child = Table::default();
+		child.set(0, Value::String(String::from("ab")));
+
+		let mut table = Table::default();
+		table.set(0, Value::U8(7));
+		table.set(2, Value::U64(258));
+		table.set(3, Value::Table(child));
+		table.set(4, Value::F64s(vec![1.5]));
+
+		let buf = table.to_size_prefixed_bytes();
+		let u16_at = |pos: usize| u16::from_le_bytes(buf[pos..pos + 2].try_into().unwrap()) as usize;
+		let u32_at = |pos: usize| u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap()) as usize;
+
+		assert_eq!(u32_at(0), buf.len() - 4);
+
+		// root table and its vtable
+		let root = 4 + u32_at(4);
+		assert_eq!(root % 8, 0);
+		let vtable = root - u32_at(root);
+		assert_eq!(u16_at(vtable), 4 + 2 * 5);
+		let field = |index: usize| match u16_at(vtable + 4 + 2 * index) {
+			0 => None,
+			offset => Some(root + offset),
+		};
+		assert_eq!(buf[field(0).unwrap()], 7);
+		assert_eq!(field(1), None);
+		assert_eq!(u32_at(field(2).unwrap()), 258);
+
+		// referenced table with a string
+		let pos = field(3).unwrap();
+		let child = pos + u32_at(pos);
+		let child_vtable = child - u32_at(child);
+		let pos = child + u16_at(child_vtable + 4);
+		let string = pos + u32_at(pos);
+		assert_eq!(u32_at(string), 2);
+		assert_eq!(&buf[string + 4..string + 7], b"ab\0");
+
+		// vector of doubles, aligned to 8 bytes
+		let pos = field(4).unwrap();
+		let vector = pos + u32_at(pos);
+		assert_eq!(u32_at(vector), 1);
+		assert_eq!((vector + 4) % 8, 0);
+		assert_eq!(&buf[vector + 4..vector + 12], &1.5f64.to_le_bytes());
+	}
+}
@@ -0,0 +1,6 @@
+//! Writes features into FlatGeobuf files, see <https://flatgeobuf.org>.
+
+mod flatbuffer;
+mod write;
+
+pub use write::*;
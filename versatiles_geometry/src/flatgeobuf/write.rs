@@ -0,0 +1,323 @@
+use super::flatbuffer::{Table, Value};
+use crate::geo::*;
+use anyhow::Result;
+use std::{collections::BTreeMap, io::Write};
+
+/// Magic bytes of FlatGeobuf version 3.
+const MAGIC: [u8; 8] = [b'f', b'g', b'b', 3, b'f', b'g', b'b', 0];
+
+/// Geometry types of the FlatGeobuf schema.
+const GEOMETRY_UNKNOWN: u8 = 0;
+const GEOMETRY_POINT: u8 = 1;
+const GEOMETRY_LINE_STRING: u8 = 2;
+const GEOMETRY_POLYGON: u8 = 3;
+const GEOMETRY_MULTI_POINT: u8 = 4;
+const GEOMETRY_MULTI_LINE_STRING: u8 = 5;
+const GEOMETRY_MULTI_POLYGON: u8 = 6;
+
+/// Column types of the FlatGeobuf schema, as far as they are needed for the values of [`GeoValue`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColumnType {
+	Bool = 2,
+	Long = 7,
+	ULong = 8,
+	Double = 10,
+	String = 11,
+}
+
+impl ColumnType {
+	fn from_value(value: &GeoValue) -> Option<ColumnType> {
+		Some(match value {
+			GeoValue::Bool(_) => ColumnType::Bool,
+			GeoValue::Double(_) | GeoValue::Float(_) => ColumnType::Double,
+			GeoValue::Int(_) => ColumnType::Long,
+			GeoValue::UInt(_) => ColumnType::ULong,
+			GeoValue::String(_) => ColumnType::String,
+			GeoValue::Null => return None,
+		})
+	}
+
+	/// Returns a type that can hold the values of both types.
+	fn merge(self, other: ColumnType) -> ColumnType {
+		use ColumnType::*;
+		match (self, other) {
+			(a, b) if a == b => a,
+			(Long, ULong) | (ULong, Long) => Long,
+			(Long | ULong | Double, Long | ULong | Double) => Double,
+			_ => String,
+		}
+	}
+}
+
+/// Writes features into a FlatGeobuf file, with coordinates in longitude and latitude (EPSG:4326).
+///
+/// The file has no spatial index. The column types are derived from the property values: numbers of
+/// different types become doubles, mixed types become strings. FlatGeobuf has no feature ids, so ids are
+/// written into the column "id", unless a property has that name.
+///
+/// # Arguments
+/// * `writer` - Destination of the file.
+/// * `name` - Name of the dataset, e.g. the name of the layer.
+/// * `features` - The features to write.
+pub fn write_flatgeobuf(writer: &mut impl Write, name: &str, features: &[GeoFeature]) -> Result<()> {
+	let properties: Vec<BTreeMap<&str, &GeoValue>> = features.iter().map(get_properties).collect();
+
+	let mut columns: BTreeMap<&str, ColumnType> = BTreeMap::new();
+	for (key, value) in properties.iter().flatten() {
+		if let Some(column_type) = ColumnType::from_value(value) {
+			columns
+				.entry(key)
+				.and_modify(|c| *c = c.merge(column_type))
+				.or_insert(column_type);
+		}
+	}
+	let columns: Vec<(&str, ColumnType)> = columns.into_iter().collect();
+
+	writer.write_all(&MAGIC)?;
+	writer.write_all(&get_header(name, features, &columns).to_size_prefixed_bytes())?;
+
+	for (feature, properties) in features.iter().zip(properties.iter()) {
+		let mut table = Table::default();
+		table.set(0, Value::Table(get_geometry(&feature.geometry)));
+		let properties = encode_properties(properties, &columns);
+		if !properties.is_empty() {
+			table.set(1, Value::Bytes(properties));
+		}
+		writer.write_all(&table.to_size_prefixed_bytes())?;
+	}
+
+	Ok(())
+}
+
+/// Returns the properties of a feature, including the id.
+fn get_properties(feature: &GeoFeature) -> BTreeMap<&str, &GeoValue> {
+	let mut properties: BTreeMap<&str, &GeoValue> = feature.properties.iter().map(|(k, v)| (k.as_str(), v)).collect();
+	if let Some(id) = &feature.id {
+		properties.entry("id").or_insert(id);
+	}
+	properties
+}
+
+fn get_header(name: &str, features: &[GeoFeature], columns: &[(&str, ColumnType)]) -> Table {
+	let mut header = Table::default();
+	header.set(0, Value::String(name.to_string()));
+
+	let mut envelope = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+	for feature in features {
+		for_each_ring(&feature.geometry, |ring| {
+			for [x, y] in ring {
+				envelope = [
+					envelope[0].min(*x),
+					envelope[1].min(*y),
+					envelope[2].max(*x),
+					envelope[3].max(*y),
+				];
+			}
+		});
+	}
+	if envelope[0] <= envelope[2] {
+		header.set(1, Value::F64s(envelope.to_vec()));
+	}
+
+	// a single geometry type for all features, or "unknown", which means that every feature has its own type
+	let mut geometry_types = features.iter().map(|f| get_geometry_type(&f.geometry));
+	let first_type = geometry_types.next().unwrap_or(GEOMETRY_UNKNOWN);
+	let geometry_type = if geometry_types.all(|t| t == first_type) {
+		first_type
+	} else {
+		GEOMETRY_UNKNOWN
+	};
+	header.set(2, Value::U8(geometry_type));
+
+	let columns = columns
+		.iter()
+		.map(|(name, column_type)| {
+			let mut column = Table::default();
+			column.set(0, Value::String(name.to_string()));
+			column.set(1, Value::U8(*column_type as u8));
+			column
+		})
+		.collect::<Vec<_>>();
+	if !columns.is_empty() {
+		header.set(7, Value::Tables(columns));
+	}
+
+	header.set(8, Value::U64(features.len() as u64));
+	// no spatial index
+	header.set(9, Value::U16(0));
+
+	let mut crs = Table::default();
+	crs.set(0, Value::String(String::from("EPSG")));
+	crs.set(1, Value::I32(4326));
+	header.set(10, Value::Table(crs));
+
+	header
+}
+
+fn get_geometry_type(geometry: &Geometry) -> u8 {
+	match geometry {
+		Geometry::Point(_) => GEOMETRY_POINT,
+		Geometry::LineString(_) => GEOMETRY_LINE_STRING,
+		Geometry::Polygon(_) => GEOMETRY_POLYGON,
+		Geometry::MultiPoint(_) => GEOMETRY_MULTI_POINT,
+		Geometry::MultiLineString(_) => GEOMETRY_MULTI_LINE_STRING,
+		Geometry::MultiPolygon(_) => GEOMETRY_MULTI_POLYGON,
+	}
+}
+
+/// Calls `callback` with every list of coordinates of the geometry.
+fn for_each_ring(geometry: &Geometry, mut callback: impl FnMut(&Coordinates1)) {
+	match geometry {
+		Geometry::Point(g) => callback(&vec![g.0]),
+		Geometry::LineString(g) => callback(&g.0),
+		Geometry::MultiPoint(g) => callback(&g.0),
+		Geometry::Polygon(g) => g.0.iter().for_each(callback),
+		Geometry::MultiLineString(g) => g.0.iter().for_each(callback),
+		Geometry::MultiPolygon(g) => g.0.iter().flatten().for_each(callback),
+	}
+}
+
+/// Returns the geometry table. Polygons of multi polygons are stored as parts, all other geometries as flat
+/// coordinates, with the end of every line or ring, if there are several.
+fn get_geometry(geometry: &Geometry) -> Table {
+	let mut table = match geometry {
+		Geometry::MultiPolygon(g) => {
+			let mut table = Table::default();
+			let parts =
+				g.0.iter()
+					.map(|polygon| get_geometry(&Geometry::new_polygon(polygon.clone())));
+			table.set(7, Value::Tables(parts.collect()));
+			table
+		}
+		_ => {
+			let mut xy = Vec::new();
+			let mut ends = Vec::new();
+			for_each_ring(geometry, |ring| {
+				xy.extend(ring.iter().flatten());
+				ends.push((xy.len() / 2) as u32);
+			});
+
+			let mut table = Table::default();
+			if ends.len() > 1 {
+				table.set(0, Value::U32s(ends));
+			}
+			table.set(1, Value::F64s(xy));
+			table
+		}
+	};
+	table.set(6, Value::U8(get_geometry_type(geometry)));
+	table
+}
+
+/// Encodes the properties as pairs of column index and value.
+fn encode_properties(properties: &BTreeMap<&str, &GeoValue>, columns: &[(&str, ColumnType)]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for (index, (name, column_type)) in columns.iter().enumerate() {
+		let Some(value) = properties.get(name) else {
+			continue;
+		};
+		if matches!(value, GeoValue::Null) {
+			continue;
+		}
+		buf.extend_from_slice(&(index as u16).to_le_bytes());
+		match (column_type, value) {
+			(ColumnType::Bool, GeoValue::Bool(v)) => buf.push(*v as u8),
+			(ColumnType::Long, GeoValue::Int(v)) => buf.extend_from_slice(&v.to_le_bytes()),
+			(ColumnType::Long, GeoValue::UInt(v)) => buf.extend_from_slice(&(*v as i64).to_le_bytes()),
+			(ColumnType::ULong, GeoValue::UInt(v)) => buf.extend_from_slice(&v.to_le_bytes()),
+			(ColumnType::Double, GeoValue::Double(v)) => buf.extend_from_slice(&v.to_le_bytes()),
+			(ColumnType::Double, GeoValue::Float(v)) => buf.extend_from_slice(&(*v as f64).to_le_bytes()),
+			(ColumnType::Double, GeoValue::Int(v)) => buf.extend_from_slice(&(*v as f64).to_le_bytes()),
+			(ColumnType::Double, GeoValue::UInt(v)) => buf.extend_from_slice(&(*v as f64).to_le_bytes()),
+			(_, value) => {
+				let text = value.to_string();
+				buf.extend_from_slice(&(text.len() as u32).to_le_bytes());
+				buf.extend_from_slice(text.as_bytes());
+			}
+		}
+	}
+	buf
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn feature(geometry: Geometry, properties: Vec<(&str, GeoValue)>) -> GeoFeature {
+		let mut feature = GeoFeature::new(geometry);
+		feature.set_properties(GeoProperties::from(properties));
+		feature
+	}
+
+	#[test]
+	fn column_types() {
+		use ColumnType::*;
+		assert_eq!(Long.merge(ULong), Long);
+		assert_eq!(ULong.merge(Double), Double);
+		assert_eq!(Bool.merge(Bool), Bool);
+		assert_eq!(Bool.merge(Long), String);
+		assert_eq!(ColumnType::from_value(&GeoValue::Null), None);
+	}
+
+	#[test]
+	fn properties() {
+		let mut feature = feature(
+			Geometry::new_point([1, 2]),
+			vec![
+				("name", GeoValue::from("ab")),
+				("rank", GeoValue::UInt(3)),
+				("void", GeoValue::Null),
+			],
+		);
+		feature.set_id(GeoValue::UInt(9));
+
+		let properties = get_properties(&feature);
+		let columns = [
+			("id", ColumnType::ULong),
+			("name", ColumnType::String),
+			("rank", ColumnType::Double),
+		];
+		let mut expected = vec![0, 0, 9, 0, 0, 0, 0, 0, 0, 0];
+		expected.extend([1, 0, 2, 0, 0, 0, b'a', b'b']);
+		expected.extend([2, 0]);
+		expected.extend(3.0f64.to_le_bytes());
+		assert_eq!(encode_properties(&properties, &columns), expected);
+	}
+
+	#[test]
+	fn file() -> Result<()> {
+		let features = vec![
+			feature(
+				Geometry::new_line_string(vec![[1, 2], [3, 4]]),
+				vec![("name", GeoValue::from("a")), ("lanes", GeoValue::Int(2))],
+			),
+			feature(
+				Geometry::new_multi_line_string(vec![vec![[0, 5], [1, 1]], vec![[2, 2], [6, 3]]]),
+				vec![("lanes", GeoValue::Double(1.5))],
+			),
+		];
+
+		let mut buf = Vec::new();
+		write_flatgeobuf(&mut buf, "streets", &features)?;
+		assert_eq!(&buf[0..8], b"fgb\x03fgb\x00");
+
+		// the header, followed by two features
+		let u32_at = |pos: usize| u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap()) as usize;
+		let header_end = 12 + u32_at(8);
+		let feature_end = header_end + 4 + u32_at(header_end);
+		assert_eq!(feature_end + 4 + u32_at(feature_end), buf.len());
+
+		let header = &buf[8..header_end];
+		let contains = |haystack: &[u8], needle: &[u8]| haystack.windows(needle.len()).any(|w| w == needle);
+		assert!(contains(header, b"\x07\0\0\0streets\0"));
+		assert!(contains(header, b"\x05\0\0\0lanes\0"));
+		assert!(contains(header, b"\x04\0\0\0EPSG\0"));
+		let envelope: Vec<u8> = [0.0f64, 1.0, 6.0, 5.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+		assert!(contains(header, &envelope));
+
+		// the second feature has two lines
+		let feature = &buf[feature_end..];
+		assert!(contains(feature, &[2, 0, 0, 0, 2, 0, 0, 0, 4, 0, 0, 0]));
+		Ok(())
+	}
+}
@@ -3,6 +3,7 @@
 use std::fmt::Debug;
 
 use super::*;
+use versatiles_core::json::{JsonObject, JsonValue};
 
 #[derive(Clone, Debug)]
 pub struct GeoFeature {
@@ -35,6 +36,18 @@ impl GeoFeature {
 		self.properties.insert(key, GeoValue::from(value));
 	}
 
+	/// Converts the feature into a GeoJSON feature object.
+	pub fn to_json(&self) -> JsonObject {
+		let mut json = JsonObject::default();
+		json.set("type", JsonValue::from("Feature"));
+		if let Some(id) = &self.id {
+			json.set("id", id.to_json());
+		}
+		json.set("geometry", self.geometry.to_json());
+		json.set("properties", JsonValue::Object(self.properties.to_json()));
+		json
+	}
+
 	#[cfg(test)]
 	pub fn new_example() -> Self {
 		Self {
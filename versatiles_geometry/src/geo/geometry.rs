@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 
 use super::*;
+use crate::math::{distance_to_line, polygon_contains_point};
 use std::fmt::Debug;
+use versatiles_core::json::JsonValue;
 
 #[derive(Clone, PartialEq)]
 pub enum Geometry {
@@ -54,6 +56,55 @@ impl Geometry {
 		}
 	}
 
+	/// Returns true if the point is inside a polygon, or within `radius` of a point or line.
+	pub fn intersects_point(&self, point: &Coordinates0, radius: f64) -> bool {
+		let near_point = |p: &Coordinates0| (p[0] - point[0]).hypot(p[1] - point[1]) <= radius;
+		match self {
+			Geometry::Point(g) => near_point(&g.0),
+			Geometry::LineString(g) => distance_to_line(&g.0, point) <= radius,
+			Geometry::Polygon(g) => polygon_contains_point(&g.0, point),
+			Geometry::MultiPoint(g) => g.0.iter().any(near_point),
+			Geometry::MultiLineString(g) => g.0.iter().any(|l| distance_to_line(l, point) <= radius),
+			Geometry::MultiPolygon(g) => g.0.iter().any(|p| polygon_contains_point(p, point)),
+		}
+	}
+
+	/// Applies `f` to every coordinate, e.g. to convert tile coordinates into longitude and latitude.
+	pub fn map_coordinates<F>(self, f: F) -> Self
+	where
+		F: Fn(Coordinates0) -> Coordinates0,
+	{
+		let map1 = |c: Coordinates1| -> Coordinates1 { c.into_iter().map(&f).collect() };
+		let map2 = |c: Coordinates2| -> Coordinates2 { c.into_iter().map(map1).collect() };
+		match self {
+			Geometry::Point(g) => Geometry::Point(PointGeometry(f(g.0))),
+			Geometry::LineString(g) => Geometry::LineString(LineStringGeometry(map1(g.0))),
+			Geometry::Polygon(g) => Geometry::Polygon(PolygonGeometry(map2(g.0))),
+			Geometry::MultiPoint(g) => Geometry::MultiPoint(MultiPointGeometry(map1(g.0))),
+			Geometry::MultiLineString(g) => Geometry::MultiLineString(MultiLineStringGeometry(map2(g.0))),
+			Geometry::MultiPolygon(g) => Geometry::MultiPolygon(MultiPolygonGeometry(g.0.into_iter().map(map2).collect())),
+		}
+	}
+
+	/// Converts the geometry into a GeoJSON geometry object.
+	pub fn to_json(&self) -> JsonValue {
+		let json0 = |c: &Coordinates0| JsonValue::from(c.to_vec());
+		let json1 = |c: &Coordinates1| JsonValue::from(c.iter().map(json0).collect::<Vec<_>>());
+		let json2 = |c: &Coordinates2| JsonValue::from(c.iter().map(json1).collect::<Vec<_>>());
+		let coordinates = match self {
+			Geometry::Point(g) => json0(&g.0),
+			Geometry::LineString(g) => json1(&g.0),
+			Geometry::Polygon(g) => json2(&g.0),
+			Geometry::MultiPoint(g) => json1(&g.0),
+			Geometry::MultiLineString(g) => json2(&g.0),
+			Geometry::MultiPolygon(g) => JsonValue::from(g.0.iter().map(json2).collect::<Vec<_>>()),
+		};
+		JsonValue::from(vec![
+			("type", JsonValue::from(self.get_type_name())),
+			("coordinates", coordinates),
+		])
+	}
+
 	pub fn new_example() -> Self {
 		Self::new_multi_polygon(vec![
 			vec![
@@ -81,3 +132,40 @@ impl Debug for Geometry {
 		f.debug_tuple(type_name).field(inner).finish()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn intersects_point() {
+		let polygons = Geometry::new_example();
+		assert!(polygons.intersects_point(&[1.0, 0.5], 0.0));
+		assert!(!polygons.intersects_point(&[2.5, 1.5], 0.0));
+		assert!(polygons.intersects_point(&[6.5, 2.0], 0.0));
+		assert!(!polygons.intersects_point(&[7.5, 2.0], 0.0));
+
+		let line = Geometry::new_line_string(vec![[0, 0], [10, 0]]);
+		assert!(line.intersects_point(&[5.0, 1.0], 1.0));
+		assert!(!line.intersects_point(&[5.0, 1.5], 1.0));
+
+		let point = Geometry::new_point([3, 4]);
+		assert!(point.intersects_point(&[0.0, 0.0], 5.0));
+		assert!(!point.intersects_point(&[0.0, 0.0], 4.9));
+	}
+
+	#[test]
+	fn to_json() {
+		let line = Geometry::new_line_string(vec![[1, 2], [3, 4]]).map_coordinates(|[x, y]| [x * 2.0, y + 0.5]);
+		assert_eq!(
+			line.to_json().stringify(),
+			"{\"coordinates\":[[2,2.5],[6,4.5]],\"type\":\"LineString\"}"
+		);
+
+		let feature = GeoFeature::new_example();
+		assert_eq!(
+			feature.to_json().get("properties").unwrap().stringify(),
+			"{\"is_nice\":true,\"name\":\"Nice\",\"population\":348085}"
+		);
+	}
+}
@@ -5,6 +5,7 @@ use std::{
 	collections::{btree_map, BTreeMap},
 	fmt::Debug,
 };
+use versatiles_core::json::JsonObject;
 
 #[derive(Clone, PartialEq)]
 pub struct GeoProperties(pub BTreeMap<String, GeoValue>);
@@ -36,6 +37,10 @@ impl GeoProperties {
 	pub fn iter(&self) -> btree_map::Iter<String, GeoValue> {
 		self.0.iter()
 	}
+
+	pub fn to_json(&self) -> JsonObject {
+		JsonObject(self.0.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+	}
 }
 
 impl IntoIterator for GeoProperties {
@@ -6,6 +6,7 @@ use std::{
 	fmt::{Debug, Display},
 	hash::Hash,
 };
+use versatiles_core::json::JsonValue;
 
 #[derive(Clone, PartialEq)]
 pub enum GeoValue {
@@ -195,6 +196,18 @@ impl GeoValue {
 		}
 	}
 
+	pub fn to_json(&self) -> JsonValue {
+		match self {
+			GeoValue::Bool(v) => JsonValue::Boolean(*v),
+			GeoValue::Double(v) => JsonValue::Number(*v),
+			GeoValue::Float(v) => JsonValue::Number(*v as f64),
+			GeoValue::Int(v) => JsonValue::Number(*v as f64),
+			GeoValue::Null => JsonValue::Null,
+			GeoValue::String(v) => JsonValue::String(v.clone()),
+			GeoValue::UInt(v) => JsonValue::Number(*v as f64),
+		}
+	}
+
 	pub fn as_u64(&self) -> Result<u64> {
 		match self {
 			GeoValue::Int(v) => Ok(*v as u64),
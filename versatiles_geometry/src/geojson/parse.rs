@@ -9,13 +9,41 @@ pub fn parse_geojson(json: &str) -> Result<GeoCollection> {
 }
 
 pub fn parse_geojson_collection(iter: &mut ByteIterator) -> Result<GeoCollection> {
+	let features = parse_collection_features(iter, parse_geojson_feature)?;
+	Ok(GeoCollection { features })
+}
+
+/// Parses a GeoJSON feature collection and groups the features by layer, in the order in which the layers
+/// first appear.
+///
+/// The layer of a feature is read from its foreign member "layer", as written by `versatiles query`.
+/// Features without it are put into `default_layer`.
+pub fn parse_geojson_layers(json: &str, default_layer: &str) -> Result<Vec<(String, Vec<GeoFeature>)>> {
+	let mut iter = ByteIterator::from_reader(Cursor::new(json), true);
+	let features = parse_collection_features(&mut iter, parse_geojson_layer_feature)?;
+
+	let mut layers: Vec<(String, Vec<GeoFeature>)> = Vec::new();
+	for (layer, feature) in features {
+		let name = layer.as_deref().unwrap_or(default_layer);
+		match layers.iter_mut().find(|(n, _)| n == name) {
+			Some((_, features)) => features.push(feature),
+			None => layers.push((name.to_string(), vec![feature])),
+		}
+	}
+	Ok(layers)
+}
+
+fn parse_collection_features<T>(
+	iter: &mut ByteIterator,
+	parse_feature: fn(&mut ByteIterator) -> Result<T>,
+) -> Result<Vec<T>> {
 	let mut features = Vec::new();
 	let mut object_type: Option<String> = None;
 
 	parse_object_entries(iter, |key, iter2| {
 		match key.as_str() {
 			"type" => object_type = Some(parse_quoted_json_string(iter2)?),
-			"features" => features = parse_array_entries(iter2, parse_geojson_feature)?,
+			"features" => features = parse_array_entries(iter2, parse_feature)?,
 			_ => _ = parse_json_iter(iter2)?,
 		};
 		Ok(())
@@ -23,7 +51,7 @@ pub fn parse_geojson_collection(iter: &mut ByteIterator) -> Result<GeoCollection
 
 	check_type(object_type, "FeatureCollection")?;
 
-	Ok(GeoCollection { features })
+	Ok(features)
 }
 
 fn check_type(object_type: Option<String>, name: &str) -> Result<()> {
@@ -36,10 +64,16 @@ fn check_type(object_type: Option<String>, name: &str) -> Result<()> {
 }
 
 pub fn parse_geojson_feature(iter: &mut ByteIterator) -> Result<GeoFeature> {
+	parse_geojson_layer_feature(iter).map(|(_, feature)| feature)
+}
+
+/// Parses a GeoJSON feature, together with the string of its foreign member "layer", if any.
+fn parse_geojson_layer_feature(iter: &mut ByteIterator) -> Result<(Option<String>, GeoFeature)> {
 	let mut object_type: Option<String> = None;
 	let mut id: Option<GeoValue> = None;
 	let mut geometry: Option<Geometry> = None;
 	let mut properties: Option<GeoProperties> = None;
+	let mut layer: Option<String> = None;
 
 	parse_object_entries(iter, |key, iter2| {
 		match key.as_str() {
@@ -47,6 +81,11 @@ pub fn parse_geojson_feature(iter: &mut ByteIterator) -> Result<GeoFeature> {
 			"id" => id = Some(parse_geojson_id(iter2)?),
 			"geometry" => geometry = Some(parse_geojson_geometry(iter2)?),
 			"properties" => properties = Some(parse_geojson_properties(iter2)?),
+			"layer" => {
+				if let JsonValue::String(name) = parse_json_iter(iter2)? {
+					layer = Some(name);
+				}
+			}
 			_ => _ = parse_json_iter(iter2)?,
 		};
 		Ok(())
@@ -54,11 +93,12 @@ pub fn parse_geojson_feature(iter: &mut ByteIterator) -> Result<GeoFeature> {
 
 	check_type(object_type, "Feature")?;
 
-	Ok(GeoFeature {
+	let feature = GeoFeature {
 		id,
 		geometry: geometry.ok_or(anyhow!("feature is missing 'geometry'"))?,
 		properties: properties.unwrap_or_default(),
-	})
+	};
+	Ok((layer, feature))
 }
 
 fn parse_geojson_id(iter: &mut ByteIterator) -> Result<GeoValue> {
@@ -364,4 +404,36 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn test_parse_geojson_layers() -> Result<()> {
+		let point = r#""type":"Feature","geometry":{"type":"Point","coordinates":[1,2]}"#;
+		let json = format!(
+			r#"{{"type":"FeatureCollection","features":[
+				{{{point},"layer":"water","properties":{{"n":1}}}},
+				{{{point},"properties":{{"n":2}}}},
+				{{{point},"layer":"water","properties":{{"n":3}}}},
+				{{{point},"layer":7,"properties":{{"n":4}}}}
+			]}}"#
+		);
+
+		let layers: Vec<(String, Vec<String>)> = parse_geojson_layers(&json, "default")?
+			.into_iter()
+			.map(|(name, features)| {
+				let numbers = features.iter().map(|f| f.properties.get("n").unwrap().to_string());
+				(name, numbers.collect())
+			})
+			.collect();
+		assert_eq!(
+			layers,
+			[
+				(String::from("water"), vec![String::from("1"), String::from("3")]),
+				(String::from("default"), vec![String::from("2"), String::from("4")]),
+			]
+		);
+
+		// the layer is ignored when parsing a collection
+		assert_eq!(parse_geojson(&json)?.features.len(), 4);
+		Ok(())
+	}
 }
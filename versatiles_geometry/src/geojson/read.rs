@@ -3,7 +3,7 @@ use crate::{parse_geojson_feature, GeoCollection, GeoFeature};
 use anyhow::{anyhow, Error, Result};
 use futures::{future::ready, stream, Stream, StreamExt};
 use std::io::{BufRead, Cursor, Read};
-use versatiles_core::byte_iterator::ByteIterator;
+use versatiles_core::{byte_iterator::ByteIterator, utils::get_thread_count};
 
 pub fn read_geojson(mut reader: impl Read) -> Result<GeoCollection> {
 	let mut buffer = String::new();
@@ -31,7 +31,7 @@ pub fn read_ndgeojson_iter(reader: impl BufRead) -> impl Iterator<Item = Result<
 pub fn read_ndgeojson_stream(reader: impl BufRead) -> impl Stream<Item = Result<GeoFeature>> {
 	stream::iter(reader.lines().enumerate())
 		.map(|(index, line)| tokio::spawn(async move { process_line(line, index).transpose() }))
-		.buffered(num_cpus::get())
+		.buffered(get_thread_count())
 		.filter_map(|f| {
 			ready(match f {
 				Ok(value) => value,
@@ -1,8 +1,10 @@
+pub mod flatgeobuf;
 mod geo;
 pub mod geojson;
 pub mod math;
 pub mod osm;
 pub mod vector_tile;
 
+pub use flatgeobuf::*;
 pub use geo::*;
 pub use geojson::*;
@@ -0,0 +1,174 @@
+use crate::geo::*;
+
+/// Returns the points inside the bounding box `[x_min, y_min, x_max, y_max]`.
+pub fn clip_points(points: &Coordinates1, bbox: &[f64; 4]) -> Coordinates1 {
+	points.iter().filter(|p| is_inside(p, bbox)).copied().collect()
+}
+
+/// Clips a line to the bounding box `[x_min, y_min, x_max, y_max]`.
+///
+/// A line leaving and reentering the bounding box is split into several lines.
+pub fn clip_line(line: &Coordinates1, bbox: &[f64; 4]) -> Coordinates2 {
+	let mut lines: Coordinates2 = Vec::new();
+	let mut current: Coordinates1 = Vec::new();
+
+	for w in line.windows(2) {
+		let Some((a, b)) = clip_segment(w[0], w[1], bbox) else {
+			continue;
+		};
+		if current.last() != Some(&a) {
+			if current.len() >= 2 {
+				lines.push(current);
+			}
+			current = vec![a];
+		}
+		current.push(b);
+	}
+	if current.len() >= 2 {
+		lines.push(current);
+	}
+	lines
+}
+
+/// Clips a closed ring to the bounding box `[x_min, y_min, x_max, y_max]`, using the Sutherland–Hodgman algorithm.
+///
+/// Returns an empty ring if nothing is left. Parts of the ring may collapse onto the edges of the bounding box.
+pub fn clip_ring(ring: &Coordinates1, bbox: &[f64; 4]) -> Coordinates1 {
+	let [x_min, y_min, x_max, y_max] = *bbox;
+	let mut points: Coordinates1 = ring.clone();
+	if points.len() > 1 && points.first() == points.last() {
+		points.pop();
+	}
+
+	// each edge is described by the dimension it limits, the limit and whether values must be below the limit
+	for (dim, limit, below) in [(0, x_min, false), (0, x_max, true), (1, y_min, false), (1, y_max, true)] {
+		if points.is_empty() {
+			break;
+		}
+		let inside = |p: &Coordinates0| if below { p[dim] <= limit } else { p[dim] >= limit };
+		let intersect = |a: &Coordinates0, b: &Coordinates0| {
+			let t = (limit - a[dim]) / (b[dim] - a[dim]);
+			let mut p = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+			p[dim] = limit;
+			p
+		};
+
+		let mut result = Vec::with_capacity(points.len() + 4);
+		let mut prev = *points.last().unwrap();
+		for p in points.iter() {
+			match (inside(&prev), inside(p)) {
+				(true, true) => result.push(*p),
+				(true, false) => result.push(intersect(&prev, p)),
+				(false, true) => {
+					result.push(intersect(&prev, p));
+					result.push(*p);
+				}
+				(false, false) => {}
+			}
+			prev = *p;
+		}
+		points = result;
+	}
+
+	if points.len() < 3 {
+		return Vec::new();
+	}
+	points.push(points[0]);
+	points
+}
+
+fn is_inside(p: &Coordinates0, bbox: &[f64; 4]) -> bool {
+	p[0] >= bbox[0] && p[0] <= bbox[2] && p[1] >= bbox[1] && p[1] <= bbox[3]
+}
+
+/// Clips a line segment using the Liang–Barsky algorithm.
+fn clip_segment(a: Coordinates0, b: Coordinates0, bbox: &[f64; 4]) -> Option<(Coordinates0, Coordinates0)> {
+	let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+	let (mut t0, mut t1) = (0.0f64, 1.0f64);
+	for (p, q) in [
+		(-dx, a[0] - bbox[0]),
+		(dx, bbox[2] - a[0]),
+		(-dy, a[1] - bbox[1]),
+		(dy, bbox[3] - a[1]),
+	] {
+		if p == 0.0 {
+			if q < 0.0 {
+				return None;
+			}
+		} else {
+			let t = q / p;
+			if p < 0.0 {
+				t0 = t0.max(t);
+			} else {
+				t1 = t1.min(t);
+			}
+		}
+	}
+	if t0 > t1 {
+		return None;
+	}
+	let at = |t: f64| {
+		if t == 0.0 {
+			a
+		} else if t == 1.0 {
+			b
+		} else {
+			[a[0] + t * dx, a[1] + t * dy]
+		}
+	};
+	Some((at(t0), at(t1)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BBOX: [f64; 4] = [0.0, 0.0, 10.0, 10.0];
+
+	#[test]
+	fn test_clip_points() {
+		assert_eq!(
+			clip_points(&vec![[5.0, 5.0], [-1.0, 5.0], [10.0, 0.0]], &BBOX),
+			vec![[5.0, 5.0], [10.0, 0.0]]
+		);
+	}
+
+	#[test]
+	fn test_clip_line() {
+		// inside
+		let line = vec![[1.0, 1.0], [5.0, 5.0], [9.0, 1.0]];
+		assert_eq!(clip_line(&line, &BBOX), vec![line]);
+
+		// crossing
+		assert_eq!(
+			clip_line(&vec![[-5.0, 5.0], [15.0, 5.0]], &BBOX),
+			vec![vec![[0.0, 5.0], [10.0, 5.0]]]
+		);
+
+		// leaving and reentering
+		assert_eq!(
+			clip_line(&vec![[5.0, 5.0], [5.0, 15.0], [8.0, 15.0], [8.0, 5.0]], &BBOX),
+			vec![vec![[5.0, 5.0], [5.0, 10.0]], vec![[8.0, 10.0], [8.0, 5.0]]]
+		);
+
+		// outside
+		assert!(clip_line(&vec![[-5.0, -5.0], [-1.0, 20.0]], &BBOX).is_empty());
+	}
+
+	#[test]
+	fn test_clip_ring() {
+		let square = |a: f64, b: f64| vec![[a, a], [b, a], [b, b], [a, b], [a, a]];
+
+		assert_eq!(clip_ring(&square(2.0, 8.0), &BBOX), square(2.0, 8.0));
+		assert!(clip_ring(&square(20.0, 30.0), &BBOX).is_empty());
+		assert_eq!(
+			clip_ring(&square(5.0, 15.0), &BBOX),
+			vec![[5.0, 10.0], [5.0, 5.0], [10.0, 5.0], [10.0, 10.0], [5.0, 10.0]]
+		);
+
+		// a bounding box inside the ring
+		let clipped = clip_ring(&square(-5.0, 15.0), &BBOX);
+		assert_eq!(clipped.len(), 5);
+		assert!(clipped.iter().all(|p| is_inside(p, &BBOX)));
+	}
+}
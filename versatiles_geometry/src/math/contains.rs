@@ -0,0 +1,76 @@
+use crate::geo::*;
+
+/// Returns true if the point is inside the ring, using the even-odd rule.
+pub fn ring_contains_point(ring: &Coordinates1, point: &Coordinates0) -> bool {
+	let [x, y] = *point;
+	let mut inside = false;
+	let mut p2 = match ring.last() {
+		Some(p) => p,
+		None => return false,
+	};
+	for p1 in ring.iter() {
+		if (p1[1] > y) != (p2[1] > y) && x < (p2[0] - p1[0]) * (y - p1[1]) / (p2[1] - p1[1]) + p1[0] {
+			inside = !inside;
+		}
+		p2 = p1;
+	}
+	inside
+}
+
+/// Returns true if the point is inside the outer ring and outside of all holes.
+pub fn polygon_contains_point(polygon: &Coordinates2, point: &Coordinates0) -> bool {
+	let mut rings = polygon.iter();
+	match rings.next() {
+		Some(outer) => ring_contains_point(outer, point) && !rings.any(|hole| ring_contains_point(hole, point)),
+		None => false,
+	}
+}
+
+/// Returns the shortest distance between the point and the line.
+pub fn distance_to_line(line: &Coordinates1, point: &Coordinates0) -> f64 {
+	let distance_to_segment = |a: &Coordinates0, b: &Coordinates0| {
+		let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+		let length2 = dx * dx + dy * dy;
+		let t = if length2 == 0.0 {
+			0.0
+		} else {
+			(((point[0] - a[0]) * dx + (point[1] - a[1]) * dy) / length2).clamp(0.0, 1.0)
+		};
+		(a[0] + t * dx - point[0]).hypot(a[1] + t * dy - point[1])
+	};
+
+	match line.len() {
+		0 => f64::INFINITY,
+		1 => distance_to_segment(&line[0], &line[0]),
+		_ => line
+			.windows(2)
+			.map(|w| distance_to_segment(&w[0], &w[1]))
+			.fold(f64::INFINITY, f64::min),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn polygon() {
+		let polygon = vec![
+			vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]],
+			vec![[4.0, 4.0], [6.0, 4.0], [6.0, 6.0], [4.0, 6.0], [4.0, 4.0]],
+		];
+		assert!(polygon_contains_point(&polygon, &[2.0, 2.0]));
+		assert!(!polygon_contains_point(&polygon, &[5.0, 5.0]));
+		assert!(!polygon_contains_point(&polygon, &[11.0, 5.0]));
+		assert!(!polygon_contains_point(&vec![], &[0.0, 0.0]));
+	}
+
+	#[test]
+	fn line() {
+		let line = vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]];
+		assert_eq!(distance_to_line(&line, &[5.0, 3.0]), 3.0);
+		assert_eq!(distance_to_line(&line, &[13.0, 14.0]), 5.0);
+		assert_eq!(distance_to_line(&vec![[1.0, 1.0]], &[4.0, 5.0]), 5.0);
+		assert_eq!(distance_to_line(&vec![], &[0.0, 0.0]), f64::INFINITY);
+	}
+}
@@ -0,0 +1,185 @@
+use super::{distance_to_line, polygon_contains_point};
+use crate::geo::*;
+use std::{cmp::Ordering, collections::BinaryHeap, f64::consts::SQRT_2};
+
+/// A square cell of the search grid, ordered by the largest distance a point within it could have.
+struct Cell {
+	center: Coordinates0,
+	half_size: f64,
+	distance: f64,
+	max_distance: f64,
+}
+
+impl Cell {
+	fn new(center: Coordinates0, half_size: f64, polygon: &Coordinates2) -> Cell {
+		let distance = signed_distance(polygon, &center);
+		Cell {
+			center,
+			half_size,
+			distance,
+			max_distance: distance + half_size * SQRT_2,
+		}
+	}
+}
+
+impl PartialEq for Cell {
+	fn eq(&self, other: &Self) -> bool {
+		self.max_distance == other.max_distance
+	}
+}
+
+impl Eq for Cell {}
+
+impl PartialOrd for Cell {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Cell {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.max_distance.total_cmp(&other.max_distance)
+	}
+}
+
+/// Returns the distance to the nearest ring, positive inside the polygon and negative outside.
+fn signed_distance(polygon: &Coordinates2, point: &Coordinates0) -> f64 {
+	let distance = polygon
+		.iter()
+		.map(|ring| distance_to_line(ring, point))
+		.fold(f64::INFINITY, f64::min);
+	if polygon_contains_point(polygon, point) {
+		distance
+	} else {
+		-distance
+	}
+}
+
+/// Returns the centroid of the outer ring, or its first point if the ring has no area.
+fn centroid(ring: &Coordinates1) -> Coordinates0 {
+	let (mut x, mut y, mut area) = (0.0, 0.0, 0.0);
+	for w in ring.windows(2) {
+		let f = w[0][0] * w[1][1] - w[1][0] * w[0][1];
+		x += (w[0][0] + w[1][0]) * f;
+		y += (w[0][1] + w[1][1]) * f;
+		area += f * 3.0;
+	}
+	if area == 0.0 {
+		ring.first().copied().unwrap_or_default()
+	} else {
+		[x / area, y / area]
+	}
+}
+
+/// Returns the pole of inaccessibility of a polygon: the point inside that is farthest from its outline,
+/// which is a good position for a label, even for concave polygons or polygons with holes.
+///
+/// The point is searched on a grid of shrinking cells, until it is within `precision` of the optimum.
+pub fn pole_of_inaccessibility(polygon: &Coordinates2, precision: f64) -> Coordinates0 {
+	let Some(outer) = polygon.first().filter(|ring| !ring.is_empty()) else {
+		return [0.0, 0.0];
+	};
+
+	let [mut x_min, mut y_min, mut x_max, mut y_max] = [f64::MAX, f64::MAX, f64::MIN, f64::MIN];
+	for [x, y] in outer {
+		x_min = x_min.min(*x);
+		y_min = y_min.min(*y);
+		x_max = x_max.max(*x);
+		y_max = y_max.max(*y);
+	}
+	let cell_size = (x_max - x_min).min(y_max - y_min);
+	if cell_size <= 0.0 {
+		return [x_min, y_min];
+	}
+
+	let half_size = cell_size / 2.0;
+	let mut queue = BinaryHeap::new();
+	let mut x = x_min;
+	while x < x_max {
+		let mut y = y_min;
+		while y < y_max {
+			queue.push(Cell::new([x + half_size, y + half_size], half_size, polygon));
+			y += cell_size;
+		}
+		x += cell_size;
+	}
+
+	let mut best = Cell::new(centroid(outer), 0.0, polygon);
+	let center = Cell::new([(x_min + x_max) / 2.0, (y_min + y_max) / 2.0], 0.0, polygon);
+	if center.distance > best.distance {
+		best = center;
+	}
+
+	let precision = precision.max(f64::EPSILON * cell_size);
+	while let Some(cell) = queue.pop() {
+		if cell.max_distance - best.distance <= precision {
+			// the queue is ordered, so no other cell can contain a better point either
+			break;
+		}
+		let half_size = cell.half_size / 2.0;
+		for [dx, dy] in [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0], [1.0, 1.0]] {
+			let center = [cell.center[0] + dx * half_size, cell.center[1] + dy * half_size];
+			queue.push(Cell::new(center, half_size, polygon));
+		}
+		if cell.distance > best.distance {
+			best = cell;
+		}
+	}
+
+	best.center
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_near(point: Coordinates0, expected: Coordinates0) {
+		assert!(
+			(point[0] - expected[0]).abs() < 0.5 && (point[1] - expected[1]).abs() < 0.5,
+			"{point:?} is not near {expected:?}"
+		);
+	}
+
+	#[test]
+	fn square() {
+		let square = vec![vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]]];
+		assert_near(pole_of_inaccessibility(&square, 0.1), [5.0, 5.0]);
+	}
+
+	#[test]
+	fn concave() {
+		// a "U" shape, whose centroid is outside of the polygon
+		let u = vec![vec![
+			[0.0, 0.0],
+			[30.0, 0.0],
+			[30.0, 30.0],
+			[20.0, 30.0],
+			[20.0, 10.0],
+			[10.0, 10.0],
+			[10.0, 30.0],
+			[0.0, 30.0],
+			[0.0, 0.0],
+		]];
+		let point = pole_of_inaccessibility(&u, 0.1);
+		assert!(polygon_contains_point(&u, &point));
+		assert!(signed_distance(&u, &point) > 4.9);
+	}
+
+	#[test]
+	fn hole() {
+		let polygon = vec![
+			vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]],
+			vec![[2.0, 2.0], [8.0, 2.0], [8.0, 8.0], [2.0, 8.0], [2.0, 2.0]],
+		];
+		let point = pole_of_inaccessibility(&polygon, 0.1);
+		assert!(polygon_contains_point(&polygon, &point));
+		assert!(signed_distance(&polygon, &point) > 0.9);
+	}
+
+	#[test]
+	fn degenerate() {
+		assert_eq!(pole_of_inaccessibility(&vec![], 1.0), [0.0, 0.0]);
+		let line = vec![vec![[1.0, 2.0], [5.0, 2.0], [1.0, 2.0]]];
+		assert_eq!(pole_of_inaccessibility(&line, 1.0), [1.0, 2.0]);
+	}
+}
@@ -0,0 +1,30 @@
+use crate::geo::*;
+
+/// Returns the length of the line.
+pub fn length_line(line: &Coordinates1) -> f64 {
+	line
+		.windows(2)
+		.map(|w| (w[1][0] - w[0][0]).hypot(w[1][1] - w[0][1]))
+		.sum()
+}
+
+/// Returns the summed length of all lines.
+pub fn length_multi_line(lines: &Coordinates2) -> f64 {
+	lines.iter().map(length_line).sum()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_length() {
+		assert_eq!(length_line(&vec![[0.0, 0.0], [3.0, 4.0], [3.0, 6.0]]), 7.0);
+		assert_eq!(length_line(&vec![[1.0, 1.0], [1.0, 1.0]]), 0.0);
+		assert_eq!(length_line(&vec![]), 0.0);
+		assert_eq!(
+			length_multi_line(&vec![vec![[0.0, 0.0], [0.0, 2.0]], vec![[0.0, 0.0], [1.0, 0.0]]]),
+			3.0
+		);
+	}
+}
@@ -0,0 +1,77 @@
+use crate::geo::*;
+use std::collections::HashMap;
+
+fn point_key(point: &Coordinates0) -> (u64, u64) {
+	(point[0].to_bits(), point[1].to_bits())
+}
+
+/// Joins lines where the last point of one line is the first point of another.
+///
+/// Lines keep their direction, so one-way streets stay correct. Lines that don't touch are returned unchanged.
+pub fn merge_lines(lines: Vec<Coordinates1>) -> Vec<Coordinates1> {
+	let mut lines: Vec<Option<Coordinates1>> = lines.into_iter().filter(|line| line.len() >= 2).map(Some).collect();
+
+	let mut starts: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+	let mut ends: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+	for (index, line) in lines.iter().enumerate() {
+		let line = line.as_ref().unwrap();
+		starts.entry(point_key(&line[0])).or_default().push(index);
+		ends.entry(point_key(line.last().unwrap())).or_default().push(index);
+	}
+
+	// takes the first remaining line with a given start or end point
+	fn take(lines: &mut [Option<Coordinates1>], candidates: Option<&Vec<usize>>) -> Option<Coordinates1> {
+		candidates?.iter().find_map(|index| lines[*index].take())
+	}
+
+	let mut result = Vec::new();
+	for index in 0..lines.len() {
+		let Some(mut line) = lines[index].take() else {
+			continue;
+		};
+		while let Some(next) = take(&mut lines, starts.get(&point_key(line.last().unwrap()))) {
+			line.extend(next.into_iter().skip(1));
+		}
+		while let Some(mut previous) = take(&mut lines, ends.get(&point_key(&line[0]))) {
+			previous.extend(line.into_iter().skip(1));
+			line = previous;
+		}
+		result.push(line);
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn joins_touching_lines() {
+		let lines = vec![
+			vec![[1.0, 0.0], [2.0, 0.0]],
+			vec![[5.0, 5.0], [6.0, 6.0]],
+			vec![[0.0, 0.0], [1.0, 0.0]],
+			vec![[2.0, 0.0], [3.0, 1.0]],
+		];
+		assert_eq!(
+			merge_lines(lines),
+			vec![
+				vec![[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 1.0]],
+				vec![[5.0, 5.0], [6.0, 6.0]],
+			]
+		);
+	}
+
+	#[test]
+	fn keeps_direction() {
+		let lines = vec![vec![[0.0, 0.0], [1.0, 0.0]], vec![[2.0, 0.0], [1.0, 0.0]]];
+		assert_eq!(merge_lines(lines.clone()), lines);
+	}
+
+	#[test]
+	fn rings_and_degenerate_lines() {
+		let ring = vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]];
+		assert_eq!(merge_lines(vec![ring.clone(), vec![[5.0, 5.0]]]), vec![ring]);
+		assert!(merge_lines(vec![]).is_empty());
+	}
+}
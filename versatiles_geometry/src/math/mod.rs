@@ -1,2 +1,20 @@
 mod area;
 pub use area::*;
+
+mod clip;
+pub use clip::*;
+
+mod contains;
+pub use contains::*;
+
+mod label;
+pub use label::*;
+
+mod length;
+pub use length::*;
+
+mod merge;
+pub use merge::*;
+
+mod simplify;
+pub use simplify::*;
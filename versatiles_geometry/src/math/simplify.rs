@@ -0,0 +1,93 @@
+use crate::geo::*;
+
+/// Returns the distance between the point and the segment from `a` to `b`.
+fn distance_to_segment(point: &Coordinates0, a: &Coordinates0, b: &Coordinates0) -> f64 {
+	let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+	let length2 = dx * dx + dy * dy;
+	let t = if length2 == 0.0 {
+		0.0
+	} else {
+		(((point[0] - a[0]) * dx + (point[1] - a[1]) * dy) / length2).clamp(0.0, 1.0)
+	};
+	(a[0] + t * dx - point[0]).hypot(a[1] + t * dy - point[1])
+}
+
+/// Simplifies a line with the Douglas-Peucker algorithm: points closer than `tolerance`
+/// to the simplified line are removed. The first and last point are always kept.
+pub fn simplify_line(line: &Coordinates1, tolerance: f64) -> Coordinates1 {
+	if line.len() <= 2 {
+		return line.clone();
+	}
+
+	let mut keep = vec![false; line.len()];
+	keep[0] = true;
+	keep[line.len() - 1] = true;
+
+	let mut stack = vec![(0, line.len() - 1)];
+	while let Some((start, end)) = stack.pop() {
+		let (mut max_distance, mut max_index) = (0.0, start);
+		for index in start + 1..end {
+			let distance = distance_to_segment(&line[index], &line[start], &line[end]);
+			if distance > max_distance {
+				max_distance = distance;
+				max_index = index;
+			}
+		}
+		if max_distance > tolerance {
+			keep[max_index] = true;
+			stack.push((start, max_index));
+			stack.push((max_index, end));
+		}
+	}
+
+	line
+		.iter()
+		.zip(keep)
+		.filter_map(|(point, keep)| keep.then_some(*point))
+		.collect()
+}
+
+/// Simplifies a closed ring like [`simplify_line`].
+/// Returns an empty ring if fewer than 4 points are left, i.e. the ring has collapsed.
+pub fn simplify_ring(ring: &Coordinates1, tolerance: f64) -> Coordinates1 {
+	let ring = simplify_line(ring, tolerance);
+	if ring.len() < 4 {
+		return vec![];
+	}
+	ring
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn line() {
+		let line = vec![[0.0, 0.0], [1.0, 0.1], [2.0, -0.1], [3.0, 5.0], [4.0, 6.0], [5.0, 7.0]];
+		assert_eq!(
+			simplify_line(&line, 0.5),
+			vec![[0.0, 0.0], [2.0, -0.1], [3.0, 5.0], [5.0, 7.0]]
+		);
+		assert_eq!(simplify_line(&line, 10.0), vec![[0.0, 0.0], [5.0, 7.0]]);
+		// collinear points are removed
+		assert_eq!(simplify_line(&line, 0.0).len(), 5);
+		assert_eq!(simplify_line(&vec![[1.0, 1.0]], 1.0), vec![[1.0, 1.0]]);
+	}
+
+	#[test]
+	fn ring() {
+		let ring = vec![
+			[0.0, 0.0],
+			[5.0, 0.1],
+			[10.0, 0.0],
+			[10.0, 10.0],
+			[0.0, 10.0],
+			[0.0, 0.0],
+		];
+		assert_eq!(
+			simplify_ring(&ring, 1.0),
+			vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0], [0.0, 10.0], [0.0, 0.0]]
+		);
+		assert!(simplify_ring(&ring, 100.0).is_empty());
+	}
+}
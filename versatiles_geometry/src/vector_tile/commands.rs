@@ -0,0 +1,323 @@
+//! Decoding of the geometry commands of vector tile features.
+//!
+//! Commands and coordinates are encoded as varints of one or two bytes, in an order that is hard to predict. So varints
+//! are not decoded one after another, but byte by byte without branching on their lengths. Every byte below 0x80 ends
+//! a varint, so very large geometries, like coastlines or landuse areas at low zoom levels, are split after such bytes
+//! and their parts are decoded in parallel.
+
+use crate::geo::{Coordinates1, Coordinates2};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::thread;
+use versatiles_core::{io::decode_varint, utils::get_thread_count};
+
+/// Geometries with fewer bytes are always decoded in the current thread.
+const PARALLEL_MIN_SIZE: usize = 256 * 1024;
+
+/// Geometries with up to this number of varints are decoded without allocating memory for them.
+const SMALL_SIZE: usize = 64;
+
+/// Minimum number of bytes that each thread decodes.
+const CHUNK_MIN_SIZE: usize = 64 * 1024;
+
+/// Bits that are set in a word if any of its eight bytes is not the last byte of a varint.
+const CONTINUATION_BITS: u64 = 0x8080_8080_8080_8080;
+
+fn read_word(data: &[u8], position: usize) -> Option<u64> {
+	let bytes = data.get(position..position + 8)?;
+	Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn decode_zigzag(value: u64) -> i64 {
+	((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Counts the bytes that end a varint, which is the number of varints in the data.
+fn count_varints(data: &[u8]) -> usize {
+	let words = data.chunks_exact(8);
+	let rest = words.remainder().iter().filter(|byte| *byte & 0x80 == 0).count();
+	words
+		.map(|word| (!u64::from_le_bytes(word.try_into().unwrap()) & CONTINUATION_BITS).count_ones() as usize)
+		.sum::<usize>()
+		+ rest
+}
+
+/// Decodes the varints of the data into `values`, which has room for exactly [`count_varints`] values.
+fn decode_varints_into(data: &[u8], values: &mut [u64]) -> Result<()> {
+	let mut index = 0;
+	let mut value = 0u64;
+	let mut shift = 0u32;
+	let mut max_shift = 0u32;
+	for &byte in data {
+		value |= ((byte & 0x7F) as u64).wrapping_shl(shift);
+		// the value is written after every byte, but the index only moves on at the last byte of a varint
+		if let Some(slot) = values.get_mut(index) {
+			*slot = value;
+		}
+		let more = (byte >> 7) as u32;
+		index += 1 - more as usize;
+		shift = (shift + 7) * more;
+		max_shift = max_shift.max(shift);
+		value &= 0u64.wrapping_sub(more as u64);
+	}
+	ensure!(shift == 0, "Unexpected end of data");
+	ensure!(max_shift < 70, "Varint too long");
+	Ok(())
+}
+
+/// Splits the data into parts that start with a new varint.
+fn split_data(data: &[u8], count: usize) -> Vec<&[u8]> {
+	let mut parts = Vec::with_capacity(count);
+	let mut start = 0;
+	for index in 1..count {
+		let mut end = (data.len() * index / count).max(start);
+		while end < data.len() && data[end] & 0x80 != 0 {
+			end += 1;
+		}
+		// the part ends after the last byte of a varint
+		end = (end + 1).min(data.len());
+		parts.push(&data[start..end]);
+		start = end;
+	}
+	parts.push(&data[start..]);
+	parts
+}
+
+/// Decodes all varints, using several threads for very large geometries.
+fn decode_varints(data: &[u8]) -> Result<Vec<u64>> {
+	let threads = if data.len() < PARALLEL_MIN_SIZE {
+		1
+	} else {
+		get_thread_count().min(data.len() / CHUNK_MIN_SIZE)
+	};
+
+	if threads <= 1 {
+		let mut values = vec![0; count_varints(data)];
+		decode_varints_into(data, &mut values)?;
+		return Ok(values);
+	}
+
+	let parts: Vec<(&[u8], usize)> = split_data(data, threads)
+		.into_iter()
+		.map(|part| (part, count_varints(part)))
+		.collect();
+	let mut values = vec![0; parts.iter().map(|(_, count)| count).sum()];
+
+	thread::scope(|scope| {
+		let mut rest = values.as_mut_slice();
+		let mut handles = Vec::with_capacity(parts.len());
+		for (part, count) in parts {
+			let (slice, remaining) = rest.split_at_mut(count);
+			rest = remaining;
+			handles.push(scope.spawn(move || decode_varints_into(part, slice)));
+		}
+		handles.into_iter().try_for_each(|handle| {
+			handle
+				.join()
+				.map_err(|_| anyhow!("Failed to decode varints in a thread"))?
+		})
+	})?;
+
+	Ok(values)
+}
+
+/// Skips the given number of varints and returns the new position.
+fn skip_varints(data: &[u8], mut position: usize, mut count: u64) -> Result<usize> {
+	while count >= 8 {
+		let Some(word) = read_word(data, position) else {
+			break;
+		};
+		// every byte without the continuation bit ends a varint
+		let ends = (!word & CONTINUATION_BITS).count_ones() as u64;
+		if ends > count {
+			break;
+		}
+		count -= ends;
+		position += 8;
+	}
+	while count > 0 {
+		let byte = *data.get(position).ok_or_else(|| anyhow!("Unexpected end of data"))?;
+		position += 1;
+		if byte & 0x80 == 0 {
+			count -= 1;
+		}
+	}
+	Ok(position)
+}
+
+/// Counts the vertices of the encoded geometry without decoding the coordinates.
+pub(super) fn count_vertices(data: &[u8]) -> Result<u64> {
+	let mut position = 0;
+	let mut vertices = 0;
+
+	while position < data.len() {
+		let value = decode_varint(data, &mut position).context("Failed to read varint for geometry command")?;
+		let command = value & 0x7;
+		let count = value >> 3;
+
+		match command {
+			1 | 2 => {
+				position = skip_varints(data, position, count.saturating_mul(2)).context("Failed to read coordinates")?;
+				vertices += count;
+			}
+			7 => {}
+			_ => bail!("Unknown command {}", command),
+		}
+	}
+
+	Ok(vertices)
+}
+
+/// Decodes the geometry commands into lines, or closed rings for polygons, without interpreting them.
+pub(super) fn decode_lines(data: &[u8]) -> Result<Coordinates2> {
+	// https://github.com/mapbox/vector-tile-spec/blob/master/2.1/README.md#43-geometry-encoding
+
+	let count = count_varints(data);
+	if count <= SMALL_SIZE {
+		// most features are small, so their varints are decoded on the stack
+		let mut buffer = [0; SMALL_SIZE];
+		decode_varints_into(data, &mut buffer[..count]).context("Failed to read varints of geometry commands")?;
+		return read_lines(&buffer[..count]);
+	}
+	let values = decode_varints(data).context("Failed to read varints of geometry commands")?;
+	read_lines(&values)
+}
+
+fn read_lines(values: &[u64]) -> Result<Coordinates2> {
+	let mut lines: Coordinates2 = Vec::new();
+	let mut line: Coordinates1 = Vec::new();
+	let mut x = 0i64;
+	let mut y = 0i64;
+	let mut index = 0;
+
+	while index < values.len() {
+		let value = values[index];
+		index += 1;
+		let command = value & 0x7;
+		let count = (value >> 3) as usize;
+
+		match command {
+			1 | 2 => {
+				let end = count
+					.checked_mul(2)
+					.and_then(|length| length.checked_add(index))
+					.filter(|end| *end <= values.len())
+					.ok_or_else(|| anyhow!("Failed to read coordinates: unexpected end of data"))?;
+
+				for pair in values[index..end].chunks_exact(2) {
+					if command == 1 {
+						// MoveTo command indicates the start of a new linestring
+						if !line.is_empty() {
+							lines.push(line);
+							line = Vec::new();
+						}
+						// reserve space for the following LineTo command and a ClosePath
+						if let Some(next) = values.get(end).filter(|next| *next & 0x7 == 2) {
+							line.reserve(((next >> 3) as usize).min(values.len()) + 2);
+						}
+					}
+
+					x += decode_zigzag(pair[0]);
+					y += decode_zigzag(pair[1]);
+
+					line.push([x as f64, y as f64]);
+				}
+				index = end;
+			}
+			7 => {
+				// ClosePath command
+				ensure!(!line.is_empty(), "ClosePath command found on an empty linestring");
+				line.push(line[0]);
+			}
+			_ => bail!("Unknown command {}", command),
+		}
+	}
+
+	if !line.is_empty() {
+		lines.push(line);
+	}
+
+	Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_core::io::{ValueWriter, ValueWriterBlob};
+
+	fn encode_varints(values: &[u64]) -> Vec<u8> {
+		let mut writer = ValueWriterBlob::new_le();
+		for value in values {
+			writer.write_varint(*value).unwrap();
+		}
+		writer.into_blob().into_vec()
+	}
+
+	#[test]
+	fn varints() -> Result<()> {
+		let values: Vec<u64> = (0..200_000u64).map(|i| (i * 7919) % (1 << (i % 40))).collect();
+		let data = encode_varints(&values);
+		assert!(data.len() > PARALLEL_MIN_SIZE);
+		assert_eq!(decode_varints(&data)?, values);
+		assert!(decode_varints(&data[..data.len() - 1]).is_err());
+
+		let mut small = vec![0; 100];
+		decode_varints_into(&encode_varints(&values[..100]), &mut small)?;
+		assert_eq!(small, values[..100]);
+
+		assert_eq!(decode_varints(&encode_varints(&[u64::MAX]))?, [u64::MAX]);
+		assert!(decode_varints(&[0xFF; 11]).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn split() {
+		let data = encode_varints(&(0..1000).map(|i| i * i).collect::<Vec<u64>>());
+		let parts = split_data(&data, 7);
+		assert_eq!(parts.len(), 7);
+		assert_eq!(parts.concat(), data);
+		for part in parts.iter().filter(|part| !part.is_empty()) {
+			assert!(part.last().unwrap() & 0x80 == 0);
+		}
+	}
+
+	#[test]
+	fn lines() -> Result<()> {
+		// MoveTo(2,2) LineTo(3,0) LineTo(0,3) ClosePath MoveTo(-5,-5) LineTo(1,0)
+		let data = encode_varints(&[9, 4, 4, 18, 6, 0, 0, 6, 15, 9, 9, 9, 10, 2, 0]);
+		assert_eq!(
+			decode_lines(&data)?,
+			[
+				vec![[2.0, 2.0], [5.0, 2.0], [5.0, 5.0], [2.0, 2.0]],
+				vec![[0.0, 0.0], [1.0, 0.0]]
+			]
+		);
+		assert_eq!(count_vertices(&data)?, 5);
+
+		assert!(decode_lines(&data[..data.len() - 1]).is_err());
+		assert!(decode_lines(&encode_varints(&[15])).is_err());
+		assert!(decode_lines(&encode_varints(&[12])).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn skip() -> Result<()> {
+		let data = encode_varints(&(0..100).map(|i| i * 13).collect::<Vec<u64>>());
+		for count in [0, 1, 9, 50, 100] {
+			let mut position = 0;
+			for _ in 0..count {
+				decode_varint(&data, &mut position)?;
+			}
+			assert_eq!(skip_varints(&data, 0, count)?, position);
+		}
+		assert!(skip_varints(&data, 0, 101).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn zigzag() {
+		assert_eq!(decode_zigzag(0), 0);
+		assert_eq!(decode_zigzag(1), -1);
+		assert_eq!(decode_zigzag(2), 1);
+		assert_eq!(decode_zigzag(u64::MAX), i64::MIN);
+	}
+}
@@ -0,0 +1,159 @@
+//! Compares the contents of two vector tiles, independent of their encoding.
+//!
+//! Features are decoded and compared by geometry, id and properties, so a different order of features or
+//! a different key/value table of a layer does not count as a change.
+
+use super::{layer::VectorTileLayer, tile::VectorTile};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Differences of a single layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorTileLayerDiff {
+	/// Number of features only in the second tile.
+	pub added: u64,
+	/// Number of features only in the first tile.
+	pub removed: u64,
+	/// Number of features in both tiles.
+	pub unchanged: u64,
+}
+
+impl VectorTileLayerDiff {
+	pub fn is_unchanged(&self) -> bool {
+		self.added == 0 && self.removed == 0
+	}
+}
+
+/// Differences of two vector tiles, per layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorTileDiff {
+	/// Differences per layer, sorted by layer name. Layers that exist in only one tile count all of their
+	/// features as added or removed.
+	pub layers: BTreeMap<String, VectorTileLayerDiff>,
+}
+
+impl VectorTileDiff {
+	/// Compares two tiles.
+	///
+	/// # Errors
+	/// Returns an error if a feature can not be decoded.
+	pub fn new(tile1: &VectorTile, tile2: &VectorTile) -> Result<VectorTileDiff> {
+		let mut counts: BTreeMap<&str, HashMap<String, (u64, u64)>> = BTreeMap::new();
+		for layer in tile1.layers.iter() {
+			let layer_counts = counts.entry(&layer.name).or_default();
+			for key in get_feature_keys(layer)? {
+				layer_counts.entry(key).or_default().0 += 1;
+			}
+		}
+		for layer in tile2.layers.iter() {
+			let layer_counts = counts.entry(&layer.name).or_default();
+			for key in get_feature_keys(layer)? {
+				layer_counts.entry(key).or_default().1 += 1;
+			}
+		}
+
+		let layers = counts
+			.into_iter()
+			.map(|(name, layer_counts)| {
+				let mut diff = VectorTileLayerDiff::default();
+				for (count1, count2) in layer_counts.into_values() {
+					diff.unchanged += count1.min(count2);
+					diff.removed += count1.saturating_sub(count2);
+					diff.added += count2.saturating_sub(count1);
+				}
+				(name.to_string(), diff)
+			})
+			.collect();
+
+		Ok(VectorTileDiff { layers })
+	}
+
+	/// Returns true if both tiles contain the same features.
+	pub fn is_unchanged(&self) -> bool {
+		self.layers.values().all(VectorTileLayerDiff::is_unchanged)
+	}
+
+	/// Returns only the layers with added or removed features.
+	pub fn iter_changed_layers(&self) -> impl Iterator<Item = (&String, &VectorTileLayerDiff)> {
+		self.layers.iter().filter(|(_, diff)| !diff.is_unchanged())
+	}
+}
+
+/// Returns a canonical representation of every feature of a layer.
+fn get_feature_keys(layer: &VectorTileLayer) -> Result<Vec<String>> {
+	let features = layer
+		.to_features()
+		.with_context(|| format!("decoding features of layer '{}'", layer.name))?;
+	Ok(features.iter().map(|feature| feature.to_json().stringify()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{GeoFeature, GeoProperties, Geometry};
+
+	fn make_layer(name: &str, features: &[(i64, &str)]) -> Result<VectorTileLayer> {
+		let features = features
+			.iter()
+			.map(|(x, kind)| {
+				let mut feature = GeoFeature::new(Geometry::new_point([*x, 2]));
+				feature.properties = GeoProperties::from(vec![("kind", *kind), ("name", "a")]);
+				feature
+			})
+			.collect();
+		VectorTileLayer::from_features(String::from(name), features, 4096, 2)
+	}
+
+	#[test]
+	fn reordered_features_are_unchanged() -> Result<()> {
+		let tile1 = VectorTile::new(vec![make_layer("pois", &[(1, "x"), (2, "y"), (3, "x")])?]);
+		let tile2 = VectorTile::new(vec![make_layer("pois", &[(3, "x"), (2, "y"), (1, "x")])?]);
+		assert_ne!(tile1.to_blob()?, tile2.to_blob()?);
+
+		let diff = VectorTileDiff::new(&tile1, &tile2)?;
+		assert!(diff.is_unchanged());
+		assert_eq!(
+			diff.layers.get("pois"),
+			Some(&VectorTileLayerDiff {
+				added: 0,
+				removed: 0,
+				unchanged: 3
+			})
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn added_and_removed_features() -> Result<()> {
+		let tile1 = VectorTile::new(vec![
+			make_layer("pois", &[(1, "x"), (2, "y")])?,
+			make_layer("water", &[(5, "lake")])?,
+		]);
+		let tile2 = VectorTile::new(vec![make_layer("pois", &[(1, "x"), (2, "z"), (4, "x")])?]);
+
+		let diff = VectorTileDiff::new(&tile1, &tile2)?;
+		assert!(!diff.is_unchanged());
+		assert_eq!(
+			diff.iter_changed_layers().collect::<Vec<_>>(),
+			vec![
+				(
+					&String::from("pois"),
+					&VectorTileLayerDiff {
+						added: 2,
+						removed: 1,
+						unchanged: 1
+					}
+				),
+				(
+					&String::from("water"),
+					&VectorTileLayerDiff {
+						added: 0,
+						removed: 1,
+						unchanged: 0
+					}
+				),
+			]
+		);
+		Ok(())
+	}
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use super::{geometry_type::GeomType, layer::VectorTileLayer};
+use super::{commands, geometry_type::GeomType, layer::VectorTileLayer};
 use crate::{geo::*, math::area_ring};
 use anyhow::{bail, ensure, Context, Result};
 use byteorder::LE;
@@ -82,54 +82,18 @@ impl VectorTileFeature {
 		Ok(writer.into_blob())
 	}
 
-	pub fn to_geometry(&self) -> Result<Geometry> {
-		// https://github.com/mapbox/vector-tile-spec/blob/master/2.1/README.md#43-geometry-encoding
-
-		let geometry = {
-			let mut reader = ValueReaderSlice::new_le(self.geom_data.as_slice());
-
-			let mut lines: Coordinates2 = Vec::new();
-			let mut line: Coordinates1 = Vec::new();
-			let mut x = 0;
-			let mut y = 0;
-
-			while reader.has_remaining() {
-				let value = reader
-					.read_varint()
-					.context("Failed to read varint for geometry command")?;
-				let command = value & 0x7;
-				let count = value >> 3;
-
-				match command {
-					1 | 2 => {
-						for _ in 0..count {
-							if command == 1 && !line.is_empty() {
-								// MoveTo command indicates the start of a new linestring
-								lines.push(line);
-								line = Vec::new();
-							}
-
-							x += reader.read_svarint().context("Failed to read x coordinate")?;
-							y += reader.read_svarint().context("Failed to read y coordinate")?;
-
-							line.push([x as f64, y as f64]);
-						}
-					}
-					7 => {
-						// ClosePath command
-						ensure!(!line.is_empty(), "ClosePath command found on an empty linestring");
-						line.push(line[0]);
-					}
-					_ => bail!("Unknown command {}", command),
-				}
-			}
+	/// Counts the vertices of the encoded geometry without decoding it.
+	pub fn count_vertices(&self) -> Result<u64> {
+		commands::count_vertices(self.geom_data.as_slice())
+	}
 
-			if !line.is_empty() {
-				lines.push(line);
-			}
+	/// Decodes the geometry commands into lines, or closed rings for polygons, without interpreting them.
+	fn decode_lines(&self) -> Result<Coordinates2> {
+		commands::decode_lines(self.geom_data.as_slice())
+	}
 
-			lines
-		};
+	pub fn to_geometry(&self) -> Result<Geometry> {
+		let geometry = self.decode_lines()?;
 
 		match self.geom_type {
 			GeomType::Unknown => bail!("Unknown geometry type"),
@@ -205,6 +169,18 @@ impl VectorTileFeature {
 		}
 	}
 
+	/// Checks whether a polygon starts with an exterior ring, which must be wound clockwise in tile coordinates.
+	/// Features that are not polygons are always wound correctly.
+	pub fn has_valid_winding(&self) -> Result<bool> {
+		if self.geom_type != GeomType::MultiPolygon {
+			return Ok(true);
+		}
+		Ok(match self.decode_lines()?.first() {
+			Some(ring) => area_ring(ring) > 0.0,
+			None => true,
+		})
+	}
+
 	pub fn decode_properties(&self, layer: &VectorTileLayer) -> Result<GeoProperties> {
 		layer.decode_tag_ids(&self.tag_ids)
 	}
@@ -342,6 +318,43 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn count_vertices() -> Result<()> {
+		let count = |geometry: Geometry| VectorTileFeature::from_geometry(None, vec![], geometry)?.count_vertices();
+
+		assert_eq!(count(Geometry::new_point([1, 2]))?, 1);
+		assert_eq!(
+			count(Geometry::new_multi_line_string(vec![
+				vec![[1, 2], [3, 4]],
+				vec![[5, 6], [7, 8], [9, 9]]
+			]))?,
+			5
+		);
+		// The closing vertex of a ring is encoded as ClosePath
+		assert_eq!(
+			count(Geometry::new_polygon(vec![vec![
+				[0, 0],
+				[3, 0],
+				[3, 3],
+				[0, 3],
+				[0, 0]
+			]]))?,
+			4
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn has_valid_winding() -> Result<()> {
+		let check = |geometry: Geometry| VectorTileFeature::from_geometry(None, vec![], geometry)?.has_valid_winding();
+
+		let ring = vec![[0, 0], [3, 0], [3, 3], [0, 3], [0, 0]];
+		assert!(check(Geometry::new_polygon(vec![ring.clone()]))?);
+		assert!(!check(Geometry::new_polygon(vec![ring.into_iter().rev().collect()]))?);
+		assert!(check(Geometry::new_line_string(vec![[3, 0], [0, 0]]))?);
+		Ok(())
+	}
+
 	#[test]
 	fn point_geometry_round_trip() -> Result<()> {
 		let geometry = Geometry::new_point([1, 2]);
@@ -1,9 +1,19 @@
+mod commands;
+mod diff;
 mod feature;
 mod geometry_type;
 mod layer;
 mod property_manager;
+mod schema;
+mod stats;
 mod tile;
 mod value;
+mod view;
 
+pub use diff::{VectorTileDiff, VectorTileLayerDiff};
+pub use feature::VectorTileFeature;
 pub use layer::VectorTileLayer;
+pub use schema::{AttributeSchema, AttributeType, LayerSchema, TilesetSchema, TilesetSchemaBuilder};
+pub use stats::{VectorTileLayerStats, VectorTileStats};
 pub use tile::VectorTile;
+pub use view::{VectorTileFeatureView, VectorTileLayerView, VectorTileView};
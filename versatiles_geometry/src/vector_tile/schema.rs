@@ -0,0 +1,527 @@
+//! A tileset schema describes the contract between tile producers and style authors: which layers exist, at
+//! which zoom levels, and which attributes their features have.
+//!
+//! Schemas are stored as JSON:
+//!
+//! ```json
+//! {
+//!   "layers": {
+//!     "water": {
+//!       "minzoom": 0,
+//!       "maxzoom": 14,
+//!       "attributes": {
+//!         "kind": { "type": "string", "required": true },
+//!         "area": { "type": "number", "default": 0 }
+//!       }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! `minzoom`, `maxzoom`, `type`, `required` and `default` are optional. Attributes without a type may have any type.
+//! Defaults are filled in by `LayerSchema::enforce`.
+
+use super::VectorTile;
+use crate::{GeoProperties, GeoValue};
+use anyhow::{bail, ensure, Context, Result};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{self, Display},
+	fs,
+	path::Path,
+};
+use versatiles_core::json::{JsonObject, JsonValue};
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum AttributeType {
+	Boolean,
+	Number,
+	String,
+}
+
+impl AttributeType {
+	/// Returns the type of a value, or `None` for null values.
+	pub fn of(value: &GeoValue) -> Option<AttributeType> {
+		match value {
+			GeoValue::Bool(_) => Some(AttributeType::Boolean),
+			GeoValue::Double(_) | GeoValue::Float(_) | GeoValue::Int(_) | GeoValue::UInt(_) => Some(AttributeType::Number),
+			GeoValue::String(_) => Some(AttributeType::String),
+			GeoValue::Null => None,
+		}
+	}
+
+	pub fn as_str(&self) -> &str {
+		match self {
+			AttributeType::Boolean => "boolean",
+			AttributeType::Number => "number",
+			AttributeType::String => "string",
+		}
+	}
+
+	pub fn parse_str(value: &str) -> Result<AttributeType> {
+		Ok(match value {
+			"boolean" => AttributeType::Boolean,
+			"number" => AttributeType::Number,
+			"string" => AttributeType::String,
+			_ => bail!("unknown attribute type \"{value}\", use \"boolean\", \"number\" or \"string\""),
+		})
+	}
+
+	/// Converts a value to this type. Returns `None` for null values and values that can't be converted,
+	/// e.g. the string "abc" to a number.
+	pub fn cast(&self, value: &GeoValue) -> Option<GeoValue> {
+		if AttributeType::of(value)? == *self {
+			return Some(value.clone());
+		}
+		match (self, value) {
+			(AttributeType::String, value) => Some(GeoValue::String(value.to_string())),
+			(AttributeType::Number, GeoValue::String(text)) => {
+				let number = GeoValue::parse_str(text.trim());
+				(AttributeType::of(&number) == Some(AttributeType::Number)).then_some(number)
+			}
+			(AttributeType::Number, GeoValue::Bool(value)) => Some(GeoValue::UInt(*value as u64)),
+			(AttributeType::Boolean, GeoValue::String(text)) => match text.trim() {
+				"true" => Some(GeoValue::Bool(true)),
+				"false" => Some(GeoValue::Bool(false)),
+				_ => None,
+			},
+			(AttributeType::Boolean, value) => Some(GeoValue::Bool(value.to_string().parse::<f64>().ok()? != 0.0)),
+			_ => None,
+		}
+	}
+}
+
+impl Display for AttributeType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttributeSchema {
+	/// `None` allows values of any type.
+	pub value_type: Option<AttributeType>,
+	pub required: bool,
+	/// Value for features without this attribute.
+	pub default: Option<GeoValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayerSchema {
+	pub min_zoom: Option<u8>,
+	pub max_zoom: Option<u8>,
+	pub attributes: BTreeMap<String, AttributeSchema>,
+}
+
+impl LayerSchema {
+	pub fn contains_zoom(&self, z: u8) -> bool {
+		self.min_zoom.is_none_or(|min| z >= min) && self.max_zoom.is_none_or(|max| z <= max)
+	}
+
+	/// Makes the properties of a feature match this layer: unknown attributes are dropped, values are cast to the
+	/// type of their attribute, or dropped if that fails, and missing attributes are set to their defaults.
+	pub fn enforce(&self, properties: GeoProperties) -> GeoProperties {
+		let mut result = GeoProperties::new();
+		for (key, value) in properties {
+			let Some(attribute) = self.attributes.get(&key) else {
+				continue;
+			};
+			let value = match attribute.value_type {
+				Some(value_type) => value_type.cast(&value),
+				None => Some(value),
+			};
+			if let Some(value) = value {
+				result.insert(key, value);
+			}
+		}
+		for (key, attribute) in self.attributes.iter() {
+			if let Some(default) = &attribute.default {
+				if result.get(key).is_none() {
+					result.insert(key.clone(), default.clone());
+				}
+			}
+		}
+		result
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TilesetSchema {
+	pub layers: BTreeMap<String, LayerSchema>,
+}
+
+impl TilesetSchema {
+	pub fn from_json(json: &JsonObject) -> Result<TilesetSchema> {
+		let mut layers = BTreeMap::new();
+		let Some(json_layers) = json.get("layers") else {
+			bail!("schema must have \"layers\"");
+		};
+		for (name, json_layer) in json_layers.as_object()?.iter() {
+			let parse_layer = || -> Result<LayerSchema> {
+				let json_layer = json_layer.as_object()?;
+				let mut layer = LayerSchema {
+					min_zoom: json_layer.get_number("minzoom")?,
+					max_zoom: json_layer.get_number("maxzoom")?,
+					attributes: BTreeMap::new(),
+				};
+				if let Some(json_attributes) = json_layer.get("attributes") {
+					for (key, json_attribute) in json_attributes.as_object()?.iter() {
+						let json_attribute = json_attribute.as_object()?;
+						let value_type = match json_attribute.get_string("type")? {
+							Some(value_type) => Some(AttributeType::parse_str(&value_type)?),
+							None => None,
+						};
+						let required = match json_attribute.get("required") {
+							Some(JsonValue::Boolean(required)) => *required,
+							Some(_) => bail!("\"required\" of attribute \"{key}\" must be a boolean"),
+							None => false,
+						};
+						let default = match json_attribute.get("default") {
+							Some(json) => {
+								let default = geo_value_from_json(json)
+									.with_context(|| format!("parsing default of attribute \"{key}\""))?;
+								if let Some(value_type) = value_type {
+									ensure!(
+										AttributeType::of(&default) == Some(value_type),
+										"default of attribute \"{key}\" must be a {value_type}"
+									);
+								}
+								Some(default)
+							}
+							None => None,
+						};
+						layer.attributes.insert(
+							key.clone(),
+							AttributeSchema {
+								value_type,
+								required,
+								default,
+							},
+						);
+					}
+				}
+				Ok(layer)
+			};
+			layers.insert(
+				name.clone(),
+				parse_layer().with_context(|| format!("parsing layer \"{name}\""))?,
+			);
+		}
+		Ok(TilesetSchema { layers })
+	}
+
+	pub fn to_json(&self) -> JsonObject {
+		let mut json_layers = JsonObject::default();
+		for (name, layer) in self.layers.iter() {
+			let mut json_layer = JsonObject::default();
+			json_layer.set_optional("minzoom", &layer.min_zoom);
+			json_layer.set_optional("maxzoom", &layer.max_zoom);
+
+			let mut json_attributes = JsonObject::default();
+			for (key, attribute) in layer.attributes.iter() {
+				let mut json_attribute = JsonObject::default();
+				json_attribute.set_optional("type", &attribute.value_type.map(|t| t.as_str().to_string()));
+				if attribute.required {
+					json_attribute.set("required", true);
+				}
+				if let Some(default) = &attribute.default {
+					json_attribute.set("default", default.to_json());
+				}
+				json_attributes.set(key, JsonValue::Object(json_attribute));
+			}
+			json_layer.set("attributes", JsonValue::Object(json_attributes));
+			json_layers.set(name, JsonValue::Object(json_layer));
+		}
+
+		let mut json = JsonObject::default();
+		json.set("layers", JsonValue::Object(json_layers));
+		json
+	}
+
+	pub fn parse_str(json: &str) -> Result<TilesetSchema> {
+		TilesetSchema::from_json(&JsonObject::parse_str(json)?)
+	}
+
+	pub fn read_file(path: &Path) -> Result<TilesetSchema> {
+		let json = fs::read_to_string(path).with_context(|| format!("reading schema {path:?}"))?;
+		TilesetSchema::parse_str(&json).with_context(|| format!("parsing schema {path:?}"))
+	}
+
+	/// Checks a tile at zoom level `z` and returns a description of every violation of the schema.
+	pub fn validate_tile(&self, z: u8, tile: &VectorTile) -> Result<Vec<String>> {
+		let mut violations = Vec::new();
+
+		for layer in tile.layers.iter() {
+			let name = &layer.name;
+			let Some(layer_schema) = self.layers.get(name) else {
+				violations.push(format!("unknown layer \"{name}\""));
+				continue;
+			};
+			if !layer_schema.contains_zoom(z) {
+				violations.push(format!("layer \"{name}\" is not allowed at zoom level {z}"));
+			}
+
+			for (index, feature) in layer.features.iter().enumerate() {
+				let properties = feature.decode_properties(layer)?;
+
+				for (key, attribute) in layer_schema.attributes.iter() {
+					match properties.get(key) {
+						None if attribute.required => violations.push(format!(
+							"feature {index} in layer \"{name}\" misses the required attribute \"{key}\""
+						)),
+						Some(value) => {
+							if let (Some(expected), Some(actual)) = (attribute.value_type, AttributeType::of(value)) {
+								if expected != actual {
+									violations.push(format!(
+										"attribute \"{key}\" of feature {index} in layer \"{name}\" is a {actual}, not a {expected}"
+									));
+								}
+							}
+						}
+						None => {}
+					}
+				}
+
+				for (key, _) in properties.iter() {
+					if !layer_schema.attributes.contains_key(key) {
+						violations.push(format!(
+							"feature {index} in layer \"{name}\" has the unknown attribute \"{key}\""
+						));
+					}
+				}
+			}
+		}
+
+		Ok(violations)
+	}
+}
+
+fn geo_value_from_json(json: &JsonValue) -> Result<GeoValue> {
+	Ok(match json {
+		JsonValue::Boolean(value) => GeoValue::Bool(*value),
+		JsonValue::Number(value) if value.fract() != 0.0 => GeoValue::Double(*value),
+		JsonValue::Number(value) if *value < 0.0 => GeoValue::Int(*value as i64),
+		JsonValue::Number(value) => GeoValue::UInt(*value as u64),
+		JsonValue::String(value) => GeoValue::from(value),
+		_ => bail!("expected a boolean, number or string, found a {}", json.type_as_str()),
+	})
+}
+
+#[derive(Debug, Default)]
+struct LayerObservations {
+	min_zoom: u8,
+	max_zoom: u8,
+	features: u64,
+	/// For every attribute: the number of features having it and the types of its values.
+	attributes: BTreeMap<String, (u64, BTreeSet<AttributeType>)>,
+}
+
+/// Generates a schema from the tiles of an existing tileset.
+///
+/// Attributes found in every feature of a layer are required. Attributes with values of different types get no type.
+#[derive(Debug, Default)]
+pub struct TilesetSchemaBuilder {
+	layers: BTreeMap<String, LayerObservations>,
+}
+
+impl TilesetSchemaBuilder {
+	pub fn new() -> TilesetSchemaBuilder {
+		TilesetSchemaBuilder::default()
+	}
+
+	pub fn add_tile(&mut self, z: u8, tile: &VectorTile) -> Result<()> {
+		for layer in tile.layers.iter() {
+			let observations = self
+				.layers
+				.entry(layer.name.clone())
+				.or_insert_with(|| LayerObservations {
+					min_zoom: z,
+					max_zoom: z,
+					..Default::default()
+				});
+			observations.min_zoom = observations.min_zoom.min(z);
+			observations.max_zoom = observations.max_zoom.max(z);
+
+			for feature in layer.features.iter() {
+				observations.features += 1;
+				for (key, value) in feature.decode_properties(layer)?.iter() {
+					let attribute = observations.attributes.entry(key.clone()).or_default();
+					attribute.0 += 1;
+					if let Some(value_type) = AttributeType::of(value) {
+						attribute.1.insert(value_type);
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	pub fn build(self) -> TilesetSchema {
+		let layers = self
+			.layers
+			.into_iter()
+			.map(|(name, observations)| {
+				let attributes = observations
+					.attributes
+					.into_iter()
+					.map(|(key, (count, types))| {
+						let value_type = if types.len() == 1 { types.first().copied() } else { None };
+						let attribute = AttributeSchema {
+							value_type,
+							required: count == observations.features,
+							default: None,
+						};
+						(key, attribute)
+					})
+					.collect();
+				let layer = LayerSchema {
+					min_zoom: Some(observations.min_zoom),
+					max_zoom: Some(observations.max_zoom),
+					attributes,
+				};
+				(name, layer)
+			})
+			.collect();
+		TilesetSchema { layers }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{vector_tile::VectorTileLayer, GeoFeature, Geometry};
+
+	const SCHEMA: &str = r#"{"layers":{"place":{"attributes":{"name":{"required":true,"type":"string"},"population":{"default":0,"type":"number"}},"maxzoom":14,"minzoom":4}}}"#;
+
+	fn make_tile(layer_name: &str, properties: Vec<(&str, GeoValue)>) -> VectorTile {
+		let mut feature = GeoFeature::new(Geometry::new_point([1, 2]));
+		for (key, value) in properties {
+			feature.set_property(key.to_string(), value);
+		}
+		let layer = VectorTileLayer::from_features(layer_name.to_string(), vec![feature], 4096, 1).unwrap();
+		VectorTile::new(vec![layer])
+	}
+
+	#[test]
+	fn json_round_trip() -> Result<()> {
+		let schema = TilesetSchema::parse_str(SCHEMA)?;
+		let layer = &schema.layers["place"];
+		assert_eq!(layer.min_zoom, Some(4));
+		assert_eq!(
+			layer.attributes["name"],
+			AttributeSchema {
+				value_type: Some(AttributeType::String),
+				required: true,
+				default: None
+			}
+		);
+		assert_eq!(layer.attributes["population"].default, Some(GeoValue::UInt(0)));
+		assert_eq!(schema.to_json().stringify(), SCHEMA);
+
+		assert!(TilesetSchema::parse_str(r#"{"layer":{}}"#).is_err());
+		assert!(TilesetSchema::parse_str(r#"{"layers":{"a":{"attributes":{"b":{"type":"date"}}}}}"#).is_err());
+		assert!(
+			TilesetSchema::parse_str(r#"{"layers":{"a":{"attributes":{"b":{"type":"number","default":"x"}}}}}"#).is_err()
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn validate_tile() -> Result<()> {
+		let schema = TilesetSchema::parse_str(SCHEMA)?;
+		let validate = |z: u8, tile: VectorTile| schema.validate_tile(z, &tile).unwrap();
+
+		let valid = make_tile(
+			"place",
+			vec![("name", GeoValue::from("Berlin")), ("population", GeoValue::from(3.7))],
+		);
+		assert_eq!(validate(5, valid), Vec::<String>::new());
+
+		let invalid = make_tile(
+			"place",
+			vec![("population", GeoValue::from("many")), ("rank", GeoValue::from(1))],
+		);
+		assert_eq!(
+			validate(15, invalid),
+			[
+				"layer \"place\" is not allowed at zoom level 15",
+				"feature 0 in layer \"place\" misses the required attribute \"name\"",
+				"attribute \"population\" of feature 0 in layer \"place\" is a string, not a number",
+				"feature 0 in layer \"place\" has the unknown attribute \"rank\""
+			]
+		);
+
+		assert_eq!(validate(5, make_tile("water", vec![])), ["unknown layer \"water\""]);
+		Ok(())
+	}
+
+	#[test]
+	fn build() -> Result<()> {
+		let mut builder = TilesetSchemaBuilder::new();
+		builder.add_tile(
+			5,
+			&make_tile(
+				"place",
+				vec![("name", GeoValue::from("A")), ("rank", GeoValue::from(1))],
+			),
+		)?;
+		builder.add_tile(
+			7,
+			&make_tile(
+				"place",
+				vec![("name", GeoValue::from("B")), ("rank", GeoValue::from("x"))],
+			),
+		)?;
+		builder.add_tile(6, &make_tile("place", vec![("name", GeoValue::from("C"))]))?;
+
+		assert_eq!(
+			builder.build().to_json().stringify(),
+			r#"{"layers":{"place":{"attributes":{"name":{"required":true,"type":"string"},"rank":{}},"maxzoom":7,"minzoom":5}}}"#
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn cast() {
+		use AttributeType::*;
+		let cast = |value_type: AttributeType, value: GeoValue| value_type.cast(&value);
+
+		assert_eq!(cast(String, GeoValue::UInt(12)), Some(GeoValue::from("12")));
+		assert_eq!(cast(String, GeoValue::Bool(true)), Some(GeoValue::from("true")));
+		assert_eq!(cast(Number, GeoValue::from(" 12 ")), Some(GeoValue::UInt(12)));
+		assert_eq!(cast(Number, GeoValue::from("-1.5")), Some(GeoValue::Double(-1.5)));
+		assert_eq!(cast(Number, GeoValue::from("abc")), None);
+		assert_eq!(cast(Number, GeoValue::Bool(true)), Some(GeoValue::UInt(1)));
+		assert_eq!(cast(Boolean, GeoValue::from("false")), Some(GeoValue::Bool(false)));
+		assert_eq!(cast(Boolean, GeoValue::Double(0.5)), Some(GeoValue::Bool(true)));
+		assert_eq!(cast(Boolean, GeoValue::from("yes")), None);
+		assert_eq!(cast(Number, GeoValue::Int(-3)), Some(GeoValue::Int(-3)));
+		assert_eq!(cast(String, GeoValue::Null), None);
+	}
+
+	#[test]
+	fn enforce() -> Result<()> {
+		let schema = TilesetSchema::parse_str(SCHEMA)?;
+		let layer = &schema.layers["place"];
+
+		let properties = GeoProperties::from(vec![
+			("name", GeoValue::UInt(7)),
+			("population", GeoValue::from("1200")),
+			("rank", GeoValue::UInt(1)),
+		]);
+		assert_eq!(
+			layer.enforce(properties),
+			GeoProperties::from(vec![
+				("name", GeoValue::from("7")),
+				("population", GeoValue::UInt(1200))
+			])
+		);
+
+		let properties = GeoProperties::from(vec![("population", GeoValue::from("many"))]);
+		assert_eq!(
+			layer.enforce(properties),
+			GeoProperties::from(vec![("population", GeoValue::UInt(0))])
+		);
+		Ok(())
+	}
+}
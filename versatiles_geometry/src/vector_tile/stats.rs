@@ -0,0 +1,136 @@
+//! Collects statistics about the contents of vector tiles, e.g. to probe a tileset.
+
+use super::{geometry_type::GeomType, tile::VectorTile};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+/// Statistics of a single layer, accumulated over many tiles.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorTileLayerStats {
+	/// Number of tiles containing this layer.
+	pub tile_count: u64,
+	/// Number of features.
+	pub feature_count: u64,
+	/// Number of point features.
+	pub point_count: u64,
+	/// Number of line features.
+	pub line_count: u64,
+	/// Number of polygon features.
+	pub polygon_count: u64,
+	/// Number of vertices of all features.
+	pub vertex_count: u64,
+	/// Number of features using each attribute key.
+	pub key_counts: HashMap<String, u64>,
+}
+
+impl VectorTileLayerStats {
+	/// Returns the `n` most frequently used attribute keys, sorted by frequency and name.
+	pub fn get_top_keys(&self, n: usize) -> Vec<(&str, u64)> {
+		let mut keys: Vec<(&str, u64)> = self.key_counts.iter().map(|(k, c)| (k.as_str(), *c)).collect();
+		keys.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+		keys.truncate(n);
+		keys
+	}
+}
+
+/// Statistics of vector tiles, per layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorTileStats {
+	/// Number of analysed tiles.
+	pub tile_count: u64,
+	/// Statistics per layer, sorted by layer name.
+	pub layers: BTreeMap<String, VectorTileLayerStats>,
+}
+
+impl VectorTileStats {
+	pub fn new() -> VectorTileStats {
+		VectorTileStats::default()
+	}
+
+	/// Adds the contents of a tile to the statistics.
+	///
+	/// # Errors
+	/// Returns an error if a geometry or an attribute key can not be decoded.
+	pub fn add_tile(&mut self, tile: &VectorTile) -> Result<()> {
+		self.tile_count += 1;
+
+		for layer in tile.layers.iter() {
+			let stats = self.layers.entry(layer.name.clone()).or_default();
+			stats.tile_count += 1;
+
+			for feature in layer.features.iter() {
+				stats.feature_count += 1;
+				match feature.geom_type {
+					GeomType::MultiPoint => stats.point_count += 1,
+					GeomType::MultiLineString => stats.line_count += 1,
+					GeomType::MultiPolygon => stats.polygon_count += 1,
+					GeomType::Unknown => {}
+				}
+				stats.vertex_count += feature.count_vertices()?;
+
+				for key_id in feature.tag_ids.iter().step_by(2) {
+					let key = layer.property_manager.key.get(*key_id)?;
+					*stats.key_counts.entry(key.clone()).or_default() += 1;
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{vector_tile::VectorTileLayer, GeoFeature, GeoProperties, Geometry};
+
+	fn make_tile() -> Result<VectorTile> {
+		let mut feature1 = GeoFeature::new(Geometry::new_point([1, 2]));
+		feature1.properties = GeoProperties::from(vec![("name", "a"), ("kind", "x")]);
+		let mut feature2 = GeoFeature::new(Geometry::new_line_string(vec![[0, 0], [1, 1], [2, 0]]));
+		feature2.properties = GeoProperties::from(vec![("kind", "y")]);
+
+		let layer = VectorTileLayer::from_features(String::from("pois"), vec![feature1, feature2], 4096, 2)?;
+		Ok(VectorTile::new(vec![layer]))
+	}
+
+	#[test]
+	fn add_tile() -> Result<()> {
+		let mut stats = VectorTileStats::new();
+		let tile = make_tile()?;
+		stats.add_tile(&tile)?;
+		stats.add_tile(&tile)?;
+
+		assert_eq!(stats.tile_count, 2);
+		let layer = stats.layers.get("pois").unwrap();
+		assert_eq!(layer.tile_count, 2);
+		assert_eq!(layer.feature_count, 4);
+		assert_eq!(layer.point_count, 2);
+		assert_eq!(layer.line_count, 2);
+		assert_eq!(layer.polygon_count, 0);
+		assert_eq!(layer.vertex_count, 8);
+		assert_eq!(layer.get_top_keys(10), vec![("kind", 4), ("name", 2)]);
+		assert_eq!(layer.get_top_keys(1), vec![("kind", 4)]);
+		Ok(())
+	}
+
+	#[test]
+	fn shortbread_tile() -> Result<()> {
+		let path = std::env::current_dir()?.join("../testdata/shortbread-tile.pbf");
+		let blob = versatiles_core::types::Blob::from(std::fs::read(path)?);
+		let mut stats = VectorTileStats::new();
+		stats.add_tile(&VectorTile::from_blob(&blob)?)?;
+
+		assert_eq!(stats.tile_count, 1);
+		assert!(!stats.layers.is_empty());
+		for layer in stats.layers.values() {
+			assert_eq!(layer.tile_count, 1);
+			assert_eq!(
+				layer.feature_count,
+				layer.point_count + layer.line_count + layer.polygon_count
+			);
+			assert!(layer.vertex_count >= layer.feature_count);
+		}
+		Ok(())
+	}
+}
@@ -1,10 +1,10 @@
 #![allow(dead_code)]
 
-use super::layer::VectorTileLayer;
-use anyhow::{bail, Context, Result};
+use super::{layer::VectorTileLayer, view::VectorTileView};
+use anyhow::{Context, Result};
 use versatiles_core::{io::*, types::Blob};
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct VectorTile {
 	pub layers: Vec<VectorTileLayer>,
 }
@@ -15,27 +15,7 @@ impl VectorTile {
 	}
 
 	pub fn from_blob(blob: &Blob) -> Result<VectorTile> {
-		let mut reader = ValueReaderSlice::new_le(blob.as_slice());
-
-		let mut tile = VectorTile::default();
-		while reader.has_remaining() {
-			match reader.read_pbf_key().context("Failed to read PBF key")? {
-				(3, 2) => {
-					tile.layers.push(
-						VectorTileLayer::read(
-							reader
-								.get_pbf_sub_reader()
-								.context("Failed to get PBF sub-reader")?
-								.as_mut(),
-						)
-						.context("Failed to read VectorTileLayer")?,
-					);
-				}
-				(f, w) => bail!("Unexpected combination of field number ({f}) and wire type ({w})"),
-			}
-		}
-
-		Ok(tile)
+		VectorTileView::new(blob.as_slice())?.to_tile()
 	}
 
 	pub fn to_blob(&self) -> Result<Blob> {
@@ -0,0 +1,338 @@
+//! Lazy, zero-copy parsing of vector tiles.
+//!
+//! A [`VectorTileView`] only splits a tile into its layers, and a [`VectorTileLayerView`] only splits a layer into
+//! its keys, values and features. Names, keys, tag IDs and geometries borrow from the parsed data, so reading some
+//! layers or features of a large tile doesn't decode the rest. Layers can be removed from a view and the remaining
+//! layers written into a new tile without encoding them again.
+
+use super::{
+	feature::VectorTileFeature, geometry_type::GeomType, layer::VectorTileLayer, property_manager::PropertyManager,
+	tile::VectorTile, value::GeoValuePBF,
+};
+use crate::{GeoProperties, GeoValue};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use versatiles_core::{io::*, types::Blob};
+
+/// A vector tile that is split into its layers, without decoding them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VectorTileView<'a> {
+	layers: Vec<&'a [u8]>,
+}
+
+impl<'a> VectorTileView<'a> {
+	pub fn new(data: &'a [u8]) -> Result<VectorTileView<'a>> {
+		let mut reader = ValueReaderSlice::new_le(data);
+		let mut layers = Vec::new();
+		while reader.has_remaining() {
+			match reader.read_pbf_key().context("Failed to read PBF key")? {
+				(3, 2) => layers.push(reader.read_pbf_slice().context("Failed to read layer")?),
+				(f, w) => bail!("Unexpected combination of field number ({f}) and wire type ({w})"),
+			}
+		}
+		Ok(VectorTileView { layers })
+	}
+
+	pub fn len(&self) -> usize {
+		self.layers.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.layers.is_empty()
+	}
+
+	/// Decodes the layers one at a time.
+	pub fn layers(&self) -> impl Iterator<Item = Result<VectorTileLayerView<'a>>> + '_ {
+		self.layers.iter().map(|data| VectorTileLayerView::new(data))
+	}
+
+	/// Returns the names of all layers, without decoding their features.
+	pub fn layer_names(&self) -> Result<Vec<&'a str>> {
+		self.layers.iter().map(|data| read_layer_name(data)).collect()
+	}
+
+	/// Decodes the layer with the given name, if there is one.
+	pub fn get_layer(&self, name: &str) -> Result<Option<VectorTileLayerView<'a>>> {
+		for data in self.layers.iter() {
+			if read_layer_name(data)? == name {
+				return VectorTileLayerView::new(data).map(Some);
+			}
+		}
+		Ok(None)
+	}
+
+	/// Keeps only the layers whose names match the predicate.
+	pub fn retain_layers<F>(&mut self, predicate: F) -> Result<()>
+	where
+		F: Fn(&str) -> bool,
+	{
+		let mut layers = Vec::with_capacity(self.layers.len());
+		for data in self.layers.iter() {
+			if predicate(read_layer_name(data)?) {
+				layers.push(*data);
+			}
+		}
+		self.layers = layers;
+		Ok(())
+	}
+
+	/// Writes the layers into a new tile. The layers are copied as they are.
+	pub fn to_blob(&self) -> Result<Blob> {
+		let mut writer = ValueWriterBlob::new_le();
+		for data in self.layers.iter() {
+			writer.write_pbf_key(3, 2).context("Failed to write PBF key")?;
+			writer
+				.write_varint(data.len() as u64)
+				.context("Failed to write layer length")?;
+			writer.write_slice(data).context("Failed to write layer")?;
+		}
+		Ok(writer.into_blob())
+	}
+
+	/// Decodes all layers into a [`VectorTile`].
+	pub fn to_tile(&self) -> Result<VectorTile> {
+		let layers = self
+			.layers()
+			.map(|layer| layer?.to_layer())
+			.collect::<Result<Vec<_>>>()
+			.context("Failed to read VectorTileLayer")?;
+		Ok(VectorTile::new(layers))
+	}
+}
+
+/// Reads only the name of a layer, skipping all other fields.
+fn read_layer_name(data: &[u8]) -> Result<&str> {
+	let mut reader = ValueReaderSlice::new_le(data);
+	while reader.has_remaining() {
+		match reader.read_pbf_key().context("Failed to read PBF key")? {
+			(1, 2) => return reader.read_pbf_str().context("Failed to read layer name"),
+			(_, 2) => {
+				reader.read_pbf_slice()?;
+			}
+			(_, 0) => {
+				reader.read_varint()?;
+			}
+			(f, w) => bail!("Unexpected combination of field number ({f}) and wire type ({w})"),
+		}
+	}
+	bail!("Layer name is required")
+}
+
+/// A layer of a vector tile that is split into its keys, values and features, without decoding them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorTileLayerView<'a> {
+	pub name: &'a str,
+	pub extent: u32,
+	pub version: u32,
+	pub keys: Vec<&'a str>,
+	values: Vec<&'a [u8]>,
+	features: Vec<&'a [u8]>,
+}
+
+impl<'a> VectorTileLayerView<'a> {
+	pub fn new(data: &'a [u8]) -> Result<VectorTileLayerView<'a>> {
+		let mut reader = ValueReaderSlice::new_le(data);
+		let mut name = None;
+		let mut extent = 4096;
+		let mut version = 1;
+		let mut keys = Vec::new();
+		let mut values = Vec::new();
+		let mut features = Vec::new();
+
+		while reader.has_remaining() {
+			match reader.read_pbf_key().context("Failed to read PBF key")? {
+				(1, 2) => name = Some(reader.read_pbf_str().context("Failed to read layer name")?),
+				(2, 2) => features.push(reader.read_pbf_slice().context("Failed to read feature")?),
+				(3, 2) => keys.push(reader.read_pbf_str().context("Failed to read property key")?),
+				(4, 2) => values.push(reader.read_pbf_slice().context("Failed to read property value")?),
+				(5, 0) => extent = reader.read_varint().context("Failed to read extent")? as u32,
+				(15, 0) => version = reader.read_varint().context("Failed to read version")? as u32,
+				(f, w) => bail!("Unexpected combination of field number ({f}) and wire type ({w})"),
+			}
+		}
+
+		Ok(VectorTileLayerView {
+			name: name.ok_or_else(|| anyhow!("Layer name is required"))?,
+			extent,
+			version,
+			keys,
+			values,
+			features,
+		})
+	}
+
+	pub fn feature_count(&self) -> usize {
+		self.features.len()
+	}
+
+	/// Decodes the features one at a time. Their geometries and tag IDs are not decoded.
+	pub fn features(&self) -> impl Iterator<Item = Result<VectorTileFeatureView<'a>>> + '_ {
+		self.features.iter().map(|data| VectorTileFeatureView::new(data))
+	}
+
+	pub fn get_value(&self, index: u32) -> Result<GeoValue> {
+		let data = self
+			.values
+			.get(index as usize)
+			.with_context(|| format!("Property value {index} not found"))?;
+		GeoValue::read(&mut ValueReaderSlice::new_le(data))
+	}
+
+	pub fn decode_properties(&self, feature: &VectorTileFeatureView) -> Result<GeoProperties> {
+		let tag_ids = feature.tag_ids()?;
+		ensure!(tag_ids.len() % 2 == 0, "Tag IDs must be even");
+		let mut properties = GeoProperties::new();
+		for pair in tag_ids.chunks_exact(2) {
+			let key = self.keys.get(pair[0] as usize).context("Failed to get property key")?;
+			properties.insert(key.to_string(), self.get_value(pair[1])?);
+		}
+		Ok(properties)
+	}
+
+	/// Decodes the whole layer into a [`VectorTileLayer`].
+	pub fn to_layer(&self) -> Result<VectorTileLayer> {
+		let mut property_manager = PropertyManager::new();
+		for key in self.keys.iter() {
+			property_manager.add_key(key.to_string());
+		}
+		for data in self.values.iter() {
+			property_manager
+				.add_val(GeoValue::read(&mut ValueReaderSlice::new_le(data)).context("Failed to read GeoValue")?);
+		}
+
+		let features = self
+			.features()
+			.map(|feature| feature?.to_feature())
+			.collect::<Result<Vec<_>>>()
+			.context("Failed to read VectorTileFeature")?;
+
+		Ok(VectorTileLayer {
+			extent: self.extent,
+			features,
+			name: self.name.to_string(),
+			property_manager,
+			version: self.version,
+		})
+	}
+}
+
+/// A feature of a vector tile layer, with its tag IDs and geometry still encoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VectorTileFeatureView<'a> {
+	pub id: Option<u64>,
+	pub geom_type: GeomType,
+	pub geom_data: &'a [u8],
+	tags: &'a [u8],
+}
+
+impl<'a> VectorTileFeatureView<'a> {
+	pub fn new(data: &'a [u8]) -> Result<VectorTileFeatureView<'a>> {
+		let mut reader = ValueReaderSlice::new_le(data);
+		let mut feature = VectorTileFeatureView {
+			id: None,
+			geom_type: GeomType::Unknown,
+			geom_data: &[],
+			tags: &[],
+		};
+
+		while reader.has_remaining() {
+			match reader.read_pbf_key().context("Failed to read PBF key")? {
+				(1, 0) => feature.id = Some(reader.read_varint().context("Failed to read feature ID")?),
+				(2, 2) => feature.tags = reader.read_pbf_slice().context("Failed to read tag IDs")?,
+				(3, 0) => feature.geom_type = GeomType::from(reader.read_varint().context("Failed to read geometry type")?),
+				(4, 2) => feature.geom_data = reader.read_pbf_slice().context("Failed to read geometry data")?,
+				(f, w) => bail!("Unexpected combination of field number ({f}) and wire type ({w})"),
+			}
+		}
+
+		Ok(feature)
+	}
+
+	pub fn tag_ids(&self) -> Result<Vec<u32>> {
+		let mut reader = ValueReaderSlice::new_le(self.tags);
+		let mut tag_ids = Vec::with_capacity(self.tags.len());
+		while reader.has_remaining() {
+			tag_ids.push(
+				reader
+					.read_varint()
+					.context("Failed to read varint for packed uint32")? as u32,
+			);
+		}
+		Ok(tag_ids)
+	}
+
+	/// Copies the feature into a [`VectorTileFeature`].
+	pub fn to_feature(&self) -> Result<VectorTileFeature> {
+		Ok(VectorTileFeature {
+			id: self.id,
+			tag_ids: self.tag_ids()?,
+			geom_type: self.geom_type,
+			geom_data: Blob::from(self.geom_data),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs::read;
+
+	fn get_pbf() -> Result<Blob> {
+		Ok(Blob::from(read("../testdata/shortbread-tile.pbf")?))
+	}
+
+	#[test]
+	fn same_as_tile() -> Result<()> {
+		let blob = get_pbf()?;
+		let view = VectorTileView::new(blob.as_slice())?;
+		let tile = VectorTile::from_blob(&blob)?;
+		assert_eq!(view.len(), tile.layers.len());
+		assert_eq!(view.to_tile()?, tile);
+
+		for (layer_view, layer) in view.layers().zip(tile.layers.iter()) {
+			let layer_view = layer_view?;
+			assert_eq!(layer_view.name, layer.name);
+			assert_eq!(layer_view.feature_count(), layer.features.len());
+			for (feature_view, feature) in layer_view.features().zip(layer.features.iter()) {
+				let feature_view = feature_view?;
+				assert_eq!(feature_view.to_feature()?, *feature);
+				assert_eq!(
+					layer_view.decode_properties(&feature_view)?,
+					layer.decode_tag_ids(&feature.tag_ids)?
+				);
+			}
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn retain_layers() -> Result<()> {
+		let blob = get_pbf()?;
+		let mut view = VectorTileView::new(blob.as_slice())?;
+		let names = view.layer_names()?;
+		assert!(names.contains(&"water_polygons"));
+		assert!(view.get_layer("water_polygons")?.is_some());
+		assert!(view.get_layer("unknown")?.is_none());
+
+		view.retain_layers(|name| name.starts_with("water"))?;
+		let tile = VectorTile::from_blob(&view.to_blob()?)?;
+		let expected: Vec<&str> = names.into_iter().filter(|name| name.starts_with("water")).collect();
+		assert_eq!(
+			tile.layers.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(),
+			expected
+		);
+
+		view.retain_layers(|_| false)?;
+		assert!(view.is_empty());
+		assert!(view.to_blob()?.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_data() {
+		assert!(VectorTileView::new(&[0x1A, 0x05, 0x0A]).is_err());
+		assert!(VectorTileView::new(&[0x08, 0x01]).is_err());
+		assert!(VectorTileLayerView::new(&[0x28, 0x80, 0x20]).is_err());
+		assert!(read_layer_name(&[0x28, 0x80, 0x20]).is_err());
+		assert!(ValueReaderSlice::new_le(&[0xFF; 11]).read_varint().is_err());
+	}
+}
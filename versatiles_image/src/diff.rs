@@ -0,0 +1,143 @@
+//! Pixel difference metrics between two raster images.
+
+use anyhow::{ensure, Result};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+/// Differences between two images of the same size, compared in RGBA.
+#[derive(Debug)]
+pub struct ImageDiff {
+	/// Number of pixels per image.
+	pub pixel_count: u64,
+	/// Number of pixels where at least one channel differs.
+	pub changed_pixels: u64,
+	/// Mean squared error over all channels.
+	pub mse: f64,
+	/// Largest difference of a single channel.
+	pub max_difference: u8,
+	image1: RgbaImage,
+	image2: RgbaImage,
+}
+
+impl ImageDiff {
+	pub fn new(image1: &DynamicImage, image2: &DynamicImage) -> Result<ImageDiff> {
+		ensure!(
+			image1.width() == image2.width() && image1.height() == image2.height(),
+			"images have different sizes: {}x{} and {}x{}",
+			image1.width(),
+			image1.height(),
+			image2.width(),
+			image2.height()
+		);
+
+		let image1 = image1.to_rgba8();
+		let image2 = image2.to_rgba8();
+
+		let mut changed_pixels = 0;
+		let mut sum_squared = 0u64;
+		let mut max_difference = 0u8;
+		for (p1, p2) in image1.pixels().zip(image2.pixels()) {
+			if p1 != p2 {
+				changed_pixels += 1;
+			}
+			for (c1, c2) in p1.0.iter().zip(p2.0) {
+				let difference = c1.abs_diff(c2);
+				sum_squared += difference as u64 * difference as u64;
+				max_difference = max_difference.max(difference);
+			}
+		}
+
+		let pixel_count = image1.width() as u64 * image1.height() as u64;
+		let mse = if pixel_count == 0 {
+			0.0
+		} else {
+			sum_squared as f64 / (pixel_count * 4) as f64
+		};
+
+		Ok(ImageDiff {
+			pixel_count,
+			changed_pixels,
+			mse,
+			max_difference,
+			image1,
+			image2,
+		})
+	}
+
+	/// Returns true if both images are identical.
+	pub fn is_identical(&self) -> bool {
+		self.changed_pixels == 0
+	}
+
+	/// Peak signal-to-noise ratio in decibels. Identical images have an infinite PSNR.
+	pub fn psnr(&self) -> f64 {
+		if self.mse == 0.0 {
+			f64::INFINITY
+		} else {
+			10.0 * (255.0 * 255.0 / self.mse).log10()
+		}
+	}
+
+	/// Renders a visual diff: unchanged pixels are shown as a faded grayscale version of the first image,
+	/// changed pixels in red, the brighter the larger the difference.
+	pub fn render(&self) -> DynamicImage {
+		let image = RgbaImage::from_fn(self.image1.width(), self.image1.height(), |x, y| {
+			let p1 = self.image1.get_pixel(x, y);
+			let p2 = self.image2.get_pixel(x, y);
+			if p1 == p2 {
+				let luma = (p1[0] as u32 * 299 + p1[1] as u32 * 587 + p1[2] as u32 * 114) / 1000;
+				let value = (128 + luma * p1[3] as u32 / 255 / 2) as u8;
+				Rgba([value, value, value, 255])
+			} else {
+				let difference = p1.0.iter().zip(p2.0).map(|(c1, c2)| c1.abs_diff(c2)).max().unwrap();
+				Rgba([128 + difference / 2, 0, 0, 255])
+			}
+		});
+		DynamicImage::ImageRgba8(image)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helper::{create_image_rgb, create_image_rgba};
+
+	#[test]
+	fn identical() -> Result<()> {
+		let image = create_image_rgba();
+		let diff = ImageDiff::new(&image, &image)?;
+		assert!(diff.is_identical());
+		assert_eq!(diff.pixel_count, 65536);
+		assert_eq!(diff.mse, 0.0);
+		assert_eq!(diff.psnr(), f64::INFINITY);
+		assert_eq!(diff.render().to_rgba8().get_pixel(0, 0).0, [202, 202, 202, 255]);
+		Ok(())
+	}
+
+	#[test]
+	fn changed() -> Result<()> {
+		let image1 = create_image_rgb();
+		let mut image2 = image1.to_rgba8();
+		for x in 0..16 {
+			image2.put_pixel(x, 0, Rgba([0, 0, 0, 255]));
+		}
+		let image2 = DynamicImage::ImageRgba8(image2);
+
+		let diff = ImageDiff::new(&image1, &image2)?;
+		assert!(!diff.is_identical());
+		assert_eq!(diff.changed_pixels, 16);
+		assert_eq!(diff.max_difference, 255);
+		assert!(diff.psnr() > 30.0 && diff.psnr() < 60.0);
+
+		let rendered = diff.render().to_rgba8();
+		assert_eq!(rendered.get_pixel(1, 0).0, [255, 0, 0, 255]);
+		assert_eq!(rendered.get_pixel(1, 1).0[1], rendered.get_pixel(1, 1).0[0]);
+		Ok(())
+	}
+
+	#[test]
+	fn different_sizes() {
+		let image1 = DynamicImage::new_rgba8(256, 256);
+		let image2 = DynamicImage::new_rgba8(512, 512);
+		assert!(ImageDiff::new(&image1, &image2).is_err());
+	}
+}
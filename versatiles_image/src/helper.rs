@@ -1,7 +1,10 @@
 use crate::{jpeg, png, webp};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use image::{DynamicImage, GrayAlphaImage, GrayImage, Luma, LumaA, Rgb, RgbImage, Rgba, RgbaImage};
-use versatiles_core::types::{Blob, TileFormat};
+use versatiles_core::{
+	types::{Blob, TileFormat},
+	utils::run_blocking,
+};
 
 /// Generate a DynamicImage with RGBA colors
 pub fn create_image_rgba() -> DynamicImage {
@@ -96,3 +99,23 @@ pub fn image2blob_fast(image: &DynamicImage, format: TileFormat) -> Result<Blob>
 		WEBP => webp::image2blob(image),
 	}
 }
+
+/// Decodes a raster tile into a DynamicImage
+pub fn blob2image(blob: &Blob, format: TileFormat) -> Result<DynamicImage> {
+	match format {
+		TileFormat::JPG => jpeg::blob2image(blob),
+		TileFormat::PNG => png::blob2image(blob),
+		TileFormat::WEBP => webp::blob2image(blob),
+		_ => bail!("can not decode tile format {format:?}"),
+	}
+}
+
+/// Encodes an image in the blocking pool, so that it doesn't hold up the async runtime.
+pub async fn image2blob_blocking(image: DynamicImage, format: TileFormat) -> Result<Blob> {
+	run_blocking(move || image2blob(&image, format)).await
+}
+
+/// Decodes a raster tile in the blocking pool, so that it doesn't hold up the async runtime.
+pub async fn blob2image_blocking(blob: Blob, format: TileFormat) -> Result<DynamicImage> {
+	run_blocking(move || blob2image(&blob, format)).await
+}
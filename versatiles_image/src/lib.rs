@@ -1,3 +1,4 @@
+pub mod diff;
 mod format;
 pub use format::*;
 
@@ -1,5 +1,5 @@
 use crate::{
-	helpers::mock_vector_source::MockVectorSource,
+	helpers::{mock_vector_source::MockVectorSource, DecodedTileCache},
 	operations::{get_read_operation_factories, get_transform_operation_factories},
 	traits::{OperationTrait, ReadOperationFactoryTrait, TransformOperationFactoryTrait},
 	vpl::{parse_vpl, VPLNode, VPLPipeline},
@@ -20,6 +20,7 @@ pub struct PipelineFactory {
 	tran_ops: HashMap<String, Box<dyn TransformOperationFactoryTrait>>,
 	dir: PathBuf,
 	create_reader: Callback,
+	tile_cache: DecodedTileCache,
 }
 
 impl PipelineFactory {
@@ -29,6 +30,7 @@ impl PipelineFactory {
 			tran_ops: HashMap::new(),
 			dir: dir.to_path_buf(),
 			create_reader,
+			tile_cache: DecodedTileCache::default(),
 		}
 	}
 
@@ -118,6 +120,11 @@ impl PipelineFactory {
 		self.dir.join(filename)
 	}
 
+	/// Returns the cache of decoded vector tiles that is shared by the operations of the pipeline.
+	pub fn get_tile_cache(&self) -> &DecodedTileCache {
+		&self.tile_cache
+	}
+
 	pub fn get_docs(&self) -> String {
 		[
 			include_str!("help.md").to_string(),
@@ -0,0 +1,29 @@
+use anyhow::{ensure, Context, Result};
+
+/// Parses a color as "#rrggbb" or "#rrggbbaa" into `[r, g, b, a]`.
+pub fn parse_color(text: &str) -> Result<[u8; 4]> {
+	let hex = text.strip_prefix('#').unwrap_or(text);
+	ensure!(
+		hex.is_ascii() && (hex.len() == 6 || hex.len() == 8),
+		"color \"{text}\" must be \"#rrggbb\" or \"#rrggbbaa\""
+	);
+	let mut color = [255u8; 4];
+	for (i, value) in color.iter_mut().enumerate().take(hex.len() / 2) {
+		*value = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).with_context(|| format!("invalid color \"{text}\""))?;
+	}
+	Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_color() {
+		assert_eq!(parse_color("#ff8000").unwrap(), [255, 128, 0, 255]);
+		assert_eq!(parse_color("#0000ff80").unwrap(), [0, 0, 255, 128]);
+		assert!(parse_color("#fff").is_err());
+		assert!(parse_color("#gggggg").is_err());
+		assert!(parse_color("#ffffé").is_err());
+	}
+}
@@ -0,0 +1,34 @@
+use std::f64::consts::PI;
+
+/// Projects a position to Web Mercator tile coordinates, e.g. `(1.5, 0.5)` is the center of tile 1/0 at `z`.
+pub fn project(lat: f64, lon: f64, z: u8) -> (f64, f64) {
+	let scale = (1u64 << z) as f64;
+	let lat = lat.clamp(-85.05112878, 85.05112878).to_radians();
+	let x = (lon + 180.0) / 360.0 * scale;
+	let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0 * scale;
+	// positions on the right or bottom edge belong to the last tile
+	(x.min(scale - 1e-9), y.clamp(0.0, scale - 1e-9))
+}
+
+/// Converts Web Mercator tile coordinates back into `[lon, lat]`.
+pub fn unproject(x: f64, y: f64, z: u8) -> [f64; 2] {
+	let scale = (1u64 << z) as f64;
+	let lon = x / scale * 360.0 - 180.0;
+	let lat = (PI * (1.0 - 2.0 * y / scale)).sinh().atan().to_degrees();
+	[lon, lat]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn roundtrip() {
+		assert_eq!(project(0.0, 0.0, 1), (1.0, 1.0));
+		assert_eq!(project(90.0, 180.0, 0).1, 0.0);
+
+		let (x, y) = project(52.52, 13.4, 14);
+		let [lon, lat] = unproject(x, y, 14);
+		assert!((lon - 13.4).abs() < 1e-9 && (lat - 52.52).abs() < 1e-9);
+	}
+}
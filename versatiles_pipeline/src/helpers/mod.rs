@@ -1,4 +1,10 @@
+mod color;
 mod csv;
+mod mercator;
 pub mod mock_vector_source;
+mod tile_cache;
 
+pub use color::*;
 pub use csv::*;
+pub use mercator::*;
+pub use tile_cache::*;
@@ -0,0 +1,208 @@
+//! A cache of decoded vector tiles.
+//!
+//! Operations on vector tiles decode the protobuf of a tile, change it and encode it again. When several of these
+//! operations follow each other, or the same tile is queried repeatedly, the next step can use the decoded tile
+//! of the previous one instead of parsing the protobuf again.
+
+use anyhow::{Context, Result};
+use std::{
+	fmt::Debug,
+	mem::size_of,
+	sync::{Arc, Mutex},
+};
+use versatiles_core::types::{Blob, LimitedCache, TileCoord3};
+use versatiles_geometry::{
+	vector_tile::{VectorTile, VectorTileFeature, VectorTileLayer},
+	GeoValue,
+};
+
+/// Maximum size of the cached tiles, unless set otherwise.
+const DEFAULT_MAXIMUM_SIZE: usize = 256 * 1024 * 1024;
+
+type Key = (Arc<str>, TileCoord3);
+
+#[derive(Clone)]
+struct CachedTile {
+	blob: Blob,
+	tile: Arc<VectorTile>,
+}
+
+impl CachedTile {
+	/// Estimates the memory of an entry: the encoded tile and the decoded layers, features and properties.
+	fn get_size(key: &Key, value: &CachedTile) -> usize {
+		let layers = value.tile.layers.iter().map(|layer| {
+			let features = layer.features.iter().map(|feature| {
+				size_of::<VectorTileFeature>() + feature.geom_data.len() as usize + feature.tag_ids.len() * size_of::<u32>()
+			});
+			// keys and values are stored in a list and in a map
+			let keys = layer
+				.property_manager
+				.key
+				.list
+				.iter()
+				.map(|key| 2 * (size_of::<String>() + key.len()));
+			let values = layer.property_manager.val.list.iter().map(|value| match value {
+				GeoValue::String(text) => 2 * (size_of::<GeoValue>() + text.len()),
+				_ => 2 * size_of::<GeoValue>(),
+			});
+			size_of::<VectorTileLayer>()
+				+ layer.name.len()
+				+ features.sum::<usize>()
+				+ keys.sum::<usize>()
+				+ values.sum::<usize>()
+		});
+		size_of::<Key>() + key.0.len() + size_of::<CachedTile>() + value.blob.len() as usize + layers.sum::<usize>()
+	}
+}
+
+/// Caches decoded vector tiles by source and tile coordinate.
+///
+/// Every entry also keeps the encoded tile, so a cached tile is only used if the tile to decode is the same.
+/// Clones share the same cache, but can use different sources (see [`DecodedTileCache::with_source`]).
+#[derive(Clone)]
+pub struct DecodedTileCache {
+	cache: Arc<Mutex<LimitedCache<Key, CachedTile>>>,
+	source: Arc<str>,
+}
+
+impl DecodedTileCache {
+	/// Creates a new cache that keeps decoded tiles up to an estimated memory size of `maximum_size` bytes.
+	pub fn new(maximum_size: usize) -> DecodedTileCache {
+		DecodedTileCache {
+			cache: Arc::new(Mutex::new(LimitedCache::with_size_function(
+				maximum_size,
+				CachedTile::get_size,
+			))),
+			source: Arc::from(""),
+		}
+	}
+
+	/// Returns a cache that shares the entries with this one, but stores its tiles under another source.
+	pub fn with_source(&self, source: &str) -> DecodedTileCache {
+		DecodedTileCache {
+			cache: self.cache.clone(),
+			source: Arc::from(source),
+		}
+	}
+
+	/// Decodes an uncompressed vector tile, or returns the cached tile if it was decoded or encoded before.
+	pub fn decode(&self, coord: &TileCoord3, blob: &Blob) -> Result<Arc<VectorTile>> {
+		let key = (self.source.clone(), *coord);
+		if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+			if cached.blob == *blob {
+				return Ok(cached.tile);
+			}
+		}
+
+		let tile = Arc::new(VectorTile::from_blob(blob).context("Failed to create VectorTile from Blob")?);
+		self.add(key, blob.clone(), tile.clone());
+		Ok(tile)
+	}
+
+	/// Encodes a vector tile and caches it, so that decoding the result doesn't parse it again.
+	pub fn encode(&self, coord: &TileCoord3, tile: VectorTile) -> Result<Blob> {
+		let blob = tile.to_blob().context("Failed to convert VectorTile to Blob")?;
+		self.add((self.source.clone(), *coord), blob.clone(), Arc::new(tile));
+		Ok(blob)
+	}
+
+	fn add(&self, key: Key, blob: Blob, tile: Arc<VectorTile>) {
+		self.cache.lock().unwrap().add(key, CachedTile { blob, tile });
+	}
+}
+
+impl Default for DecodedTileCache {
+	fn default() -> Self {
+		DecodedTileCache::new(DEFAULT_MAXIMUM_SIZE)
+	}
+}
+
+impl Debug for DecodedTileCache {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("DecodedTileCache")
+			.field("source", &self.source)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{vector_tile::VectorTileLayer, GeoFeature, Geometry};
+
+	fn make_tile(name: &str) -> Result<VectorTile> {
+		let features = vec![GeoFeature::new(Geometry::new_point([1, 2]))];
+		let layer = VectorTileLayer::from_features(name.to_string(), features, 4096, 2)?;
+		Ok(VectorTile::new(vec![layer]))
+	}
+
+	#[test]
+	fn decode_encoded_tile() -> Result<()> {
+		let cache = DecodedTileCache::default();
+		let coord = TileCoord3::new(1, 2, 3)?;
+
+		let blob = cache.encode(&coord, make_tile("water")?)?;
+		let tile1 = cache.decode(&coord, &blob)?;
+		let tile2 = cache.decode(&coord, &blob)?;
+		assert!(Arc::ptr_eq(&tile1, &tile2));
+		assert_eq!(tile1.layers[0].name, "water");
+
+		// another tile at the same coordinate is decoded again
+		let blob = make_tile("place")?.to_blob()?;
+		let tile3 = cache.decode(&coord, &blob)?;
+		assert!(!Arc::ptr_eq(&tile1, &tile3));
+		assert_eq!(tile3.layers[0].name, "place");
+
+		Ok(())
+	}
+
+	#[test]
+	fn separate_sources() -> Result<()> {
+		let cache1 = DecodedTileCache::default().with_source("a");
+		let cache2 = cache1.with_source("b");
+		let coord = TileCoord3::new(0, 0, 0)?;
+
+		let blob = make_tile("water")?.to_blob()?;
+		let tile1 = cache1.decode(&coord, &blob)?;
+		let tile2 = cache2.decode(&coord, &blob)?;
+		assert!(!Arc::ptr_eq(&tile1, &tile2));
+		assert!(Arc::ptr_eq(&tile1, &cache1.decode(&coord, &blob)?));
+
+		Ok(())
+	}
+
+	#[test]
+	fn limited_by_size() -> Result<()> {
+		let blob = make_tile("water")?.to_blob()?;
+		let key: Key = (Arc::from(""), TileCoord3::new(0, 0, 0)?);
+		let size = CachedTile::get_size(
+			&key,
+			&CachedTile {
+				blob: blob.clone(),
+				tile: Arc::new(VectorTile::from_blob(&blob)?),
+			},
+		);
+		assert!(size > blob.len() as usize + size_of::<Key>() + size_of::<CachedTile>());
+
+		// the cache has room for two tiles
+		let cache = DecodedTileCache::new(2 * size + size / 2);
+		let decode = |x: u32| cache.decode(&TileCoord3::new(x, 0, 5).unwrap(), &blob);
+		let tile0 = decode(0)?;
+		let tile1 = decode(1)?;
+		assert!(Arc::ptr_eq(&tile0, &decode(0)?));
+		assert!(Arc::ptr_eq(&tile1, &decode(1)?));
+
+		// a third tile evicts the older ones
+		decode(2)?;
+		assert!(!Arc::ptr_eq(&tile0, &decode(0)?));
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_tile() -> Result<()> {
+		let cache = DecodedTileCache::default();
+		let coord = TileCoord3::new(0, 0, 0)?;
+		assert!(cache.decode(&coord, &Blob::from("invalid")).is_err());
+		Ok(())
+	}
+}
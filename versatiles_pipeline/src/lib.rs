@@ -1,8 +1,11 @@
 mod factory;
 mod helpers;
 mod operations;
+mod readers;
 mod traits;
 mod vpl;
 
 pub use factory::PipelineFactory;
+pub use helpers::DecodedTileCache;
+pub use readers::*;
 pub use traits::OperationTrait;
@@ -1,5 +1,5 @@
 use crate::{traits::*, vpl::VPLNode, PipelineFactory};
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use async_trait::async_trait;
 use futures::future::BoxFuture;
 use std::fmt::Debug;
@@ -11,12 +11,17 @@ struct Args {
 	/// The filename of the tile container. This is relative to the path of the VPL file.
 	/// For example: `filename="world.versatiles"`.
 	filename: String,
+	/// Restricts zoom levels to bounding boxes, each as "zoom:min long,min lat,max long,max lat".
+	/// The bounding box applies to the zoom level and all higher levels, lower levels are not restricted.
+	/// For example: `bbox_zoom=["10:5.8,45.5,10.9,47.9"]`.
+	bbox_zoom: Vec<String>,
 }
 
 #[derive(Debug)]
 struct Operation {
 	parameters: TilesReaderParameters,
 	reader: Box<dyn TilesReaderTrait>,
+	tilejson: TileJSON,
 }
 
 impl ReadOperationTrait for Operation {
@@ -27,13 +32,44 @@ impl ReadOperationTrait for Operation {
 		Box::pin(async move {
 			let args = Args::from_vpl_node(&vpl_node)?;
 			let reader = factory.get_reader(&factory.resolve_filename(&args.filename)).await?;
-			let parameters = reader.get_parameters().clone();
-
-			Ok(Box::new(Self { parameters, reader }) as Box<dyn OperationTrait>)
+			let mut parameters = reader.get_parameters().clone();
+			let mut tilejson = reader.get_tilejson().clone();
+
+			if !args.bbox_zoom.is_empty() {
+				for bbox_zoom in args.bbox_zoom.iter() {
+					let (level, geo_bbox) = parse_bbox_zoom(bbox_zoom)?;
+					parameters.bbox_pyramid.intersect_geo_bbox_from_level(level, &geo_bbox);
+				}
+				tilejson.update_from_pyramid(&parameters.bbox_pyramid);
+			}
+
+			Ok(Box::new(Self {
+				parameters,
+				reader,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
 		})
 	}
 }
 
+/// Parses "zoom:min long,min lat,max long,max lat" into a zoom level and a bounding box.
+fn parse_bbox_zoom(text: &str) -> Result<(u8, GeoBBox)> {
+	let (level, bbox) = text
+		.split_once(':')
+		.with_context(|| format!("bbox_zoom must have the form \"zoom:bbox\", but is \"{text}\""))?;
+	let level = level
+		.trim()
+		.parse::<u8>()
+		.with_context(|| format!("invalid zoom level in bbox_zoom \"{text}\""))?;
+	let values = bbox
+		.split(',')
+		.map(|v| v.trim().parse::<f64>())
+		.collect::<Result<Vec<f64>, _>>()
+		.with_context(|| format!("invalid bbox in bbox_zoom \"{text}\""))?;
+	ensure!(values.len() == 4, "bbox in bbox_zoom \"{text}\" must have 4 numbers");
+	Ok((level, GeoBBox::try_from(values)?))
+}
+
 #[async_trait]
 impl OperationTrait for Operation {
 	fn get_parameters(&self) -> &TilesReaderParameters {
@@ -41,14 +77,19 @@ impl OperationTrait for Operation {
 	}
 
 	fn get_tilejson(&self) -> &TileJSON {
-		self.reader.get_tilejson()
+		&self.tilejson
 	}
 
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
-		self.reader.get_tile_data(coord).await
+		if self.parameters.bbox_pyramid.contains_coord(coord) {
+			self.reader.get_tile_data(coord).await
+		} else {
+			Ok(None)
+		}
 	}
 
-	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+	async fn get_tile_stream(&self, mut bbox: TileBBox) -> TileStream {
+		bbox.intersect_pyramid(&self.parameters.bbox_pyramid).unwrap();
 		self.reader.get_bbox_tile_stream(bbox).await
 	}
 }
@@ -106,4 +147,42 @@ mod tests {
 
 		Ok(())
 	}
+	#[tokio::test]
+	async fn test_bbox_zoom() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let full = factory
+			.operation_from_vpl("from_container filename=\"test.mbtiles\"")
+			.await?;
+		let operation = factory
+			.operation_from_vpl("from_container filename=\"test.mbtiles\" bbox_zoom=[\"3:0,0,10,10\"]")
+			.await?;
+
+		let pyramid = &operation.get_parameters().bbox_pyramid;
+		assert_eq!(
+			pyramid.get_level_bbox(2),
+			full.get_parameters().bbox_pyramid.get_level_bbox(2)
+		);
+		assert_eq!(pyramid.get_level_bbox(3), &TileBBox::new(3, 4, 3, 4, 3)?);
+
+		assert!(operation.get_tile_data(&TileCoord3::new(0, 0, 3)?).await?.is_none());
+		assert!(operation.get_tile_data(&TileCoord3::new(4, 3, 3)?).await?.is_some());
+
+		let mut stream = operation.get_tile_stream(TileBBox::new_full(3)?).await;
+		let mut n = 0;
+		while stream.next().await.is_some() {
+			n += 1;
+		}
+		assert_eq!(n, 1);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_bbox_zoom_errors() {
+		let factory = PipelineFactory::new_dummy();
+		for bbox_zoom in ["0,0,10,10", "a:0,0,10,10", "3:0,0,10", "3:0,0,x,10"] {
+			let vpl = format!("from_container filename=\"test.mbtiles\" bbox_zoom=[\"{bbox_zoom}\"]");
+			assert!(factory.operation_from_vpl(&vpl).await.is_err(), "{bbox_zoom}");
+		}
+	}
 }
@@ -7,7 +7,7 @@ use lazy_static::lazy_static;
 use versatiles_core::types::TileCoord3;
 
 lazy_static! {
-	static ref FONT: FontArc = FontArc::try_from_slice(include_bytes!("./trim.ttf")).unwrap();
+	pub static ref FONT: FontArc = FontArc::try_from_slice(include_bytes!("./trim.ttf")).unwrap();
 }
 
 pub fn create_debug_image(coord: &TileCoord3) -> DynamicImage {
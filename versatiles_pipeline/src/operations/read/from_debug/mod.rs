@@ -1,6 +1,8 @@
 mod image;
 mod vector;
 
+pub(crate) use image::FONT;
+
 use crate::{traits::*, vpl::VPLNode, PipelineFactory};
 use anyhow::{bail, Result};
 use async_trait::async_trait;
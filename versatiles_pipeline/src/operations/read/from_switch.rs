@@ -0,0 +1,310 @@
+use crate::{
+	traits::*,
+	vpl::{VPLNode, VPLPipeline},
+	PipelineFactory,
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::{
+	future::{join_all, ready, BoxFuture},
+	StreamExt,
+};
+use versatiles_core::{tilejson::TileJSON, types::*, utils::recompress};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Routes every tile to exactly one of multiple sources, depending on its zoom level and position, e.g. to use a detailed pipeline only for high zoom levels in Europe. Each tile is taken from the first source whose case matches, even if that source has no tile.
+struct Args {
+	/// All tile sources must have the same format.
+	sources: Vec<VPLPipeline>,
+
+	/// One case per source. A case consists of conditions separated by spaces: "zoom=min-max" (`min` or `max` may be omitted) and "bbox=west,south,east,north". An empty case matches every tile. For example: `cases=["zoom=12- bbox=-10,35,30,70", ""]`.
+	cases: Vec<String>,
+}
+
+/// The conditions of a case, as the pyramid of all matching tiles.
+fn parse_case(case: &str, grid: TileGrid) -> Result<TileBBoxPyramid> {
+	let mut pyramid = TileBBoxPyramid::new_full_in_grid(grid, 31);
+	for condition in case.split_whitespace() {
+		match condition.split_once('=') {
+			Some(("zoom", range)) => {
+				let (min, max) = range.split_once('-').context("zoom must be a range \"min-max\"")?;
+				if !min.is_empty() {
+					pyramid.set_zoom_min(min.parse().context("invalid minimum zoom level")?);
+				}
+				if !max.is_empty() {
+					pyramid.set_zoom_max(max.parse().context("invalid maximum zoom level")?);
+				}
+			}
+			Some(("bbox", bbox)) => {
+				let values = bbox
+					.split(',')
+					.map(|v| v.trim().parse::<f64>())
+					.collect::<Result<Vec<f64>, _>>()
+					.context("invalid bbox")?;
+				ensure!(values.len() == 4, "bbox must have four values: west,south,east,north");
+				pyramid.intersect_geo_bbox(&GeoBBox::try_from(values)?);
+			}
+			_ => bail!("unknown condition \"{condition}\", use \"zoom=min-max\" or \"bbox=west,south,east,north\""),
+		}
+	}
+	Ok(pyramid)
+}
+
+#[derive(Debug)]
+struct Branch {
+	/// All tiles matching the case of this branch.
+	case: TileBBoxPyramid,
+	source: Box<dyn OperationTrait>,
+}
+
+#[derive(Debug)]
+struct Operation {
+	parameters: TilesReaderParameters,
+	branches: Vec<Branch>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	/// Returns the index of the branch that a tile is routed to.
+	fn route(&self, coord: &TileCoord3) -> Option<usize> {
+		self
+			.branches
+			.iter()
+			.position(|branch| branch.case.contains_coord(coord))
+	}
+}
+
+impl ReadOperationTrait for Operation {
+	fn build(
+		vpl_node: VPLNode,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			let sources = join_all(args.sources.into_iter().map(|c| factory.build_pipeline(c)))
+				.await
+				.into_iter()
+				.collect::<Result<Vec<_>>>()?;
+
+			ensure!(!sources.is_empty(), "must have at least one source");
+			ensure!(
+				sources.len() == args.cases.len(),
+				"must have one case per source, but there are {} sources and {} cases",
+				sources.len(),
+				args.cases.len()
+			);
+
+			let mut tilejson = TileJSON::default();
+			let parameters = sources.first().unwrap().get_parameters();
+			let grid = parameters.bbox_pyramid.get_grid();
+			let tile_format = parameters.tile_format;
+			let mut tile_compression = parameters.tile_compression;
+			let mut pyramid = TileBBoxPyramid::new_empty_in_grid(grid);
+
+			let mut branches = Vec::new();
+			for (source, case) in sources.into_iter().zip(args.cases.iter()) {
+				tilejson.merge(source.get_tilejson())?;
+
+				let parameters = source.get_parameters();
+				ensure!(
+					parameters.tile_format == tile_format,
+					"all sources must have the same tile format"
+				);
+				ensure!(
+					parameters.bbox_pyramid.get_grid() == grid,
+					"all sources must use the same tile grid"
+				);
+				if parameters.tile_compression != tile_compression {
+					tile_compression = TileCompression::Uncompressed;
+				}
+
+				let case = parse_case(case, grid).with_context(|| format!("parsing case \"{case}\""))?;
+				let mut source_pyramid = parameters.bbox_pyramid.clone();
+				source_pyramid.intersect(&case);
+				pyramid.include_bbox_pyramid(&source_pyramid);
+
+				branches.push(Branch { case, source });
+			}
+
+			let parameters = TilesReaderParameters::new(tile_format, tile_compression, pyramid);
+			tilejson.update_from_pyramid(&parameters.bbox_pyramid);
+
+			Ok(Box::new(Self {
+				tilejson,
+				parameters,
+				branches,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		let Some(index) = self.route(coord) else {
+			return Ok(None);
+		};
+		let source = &self.branches[index].source;
+		Ok(match source.get_tile_data(coord).await? {
+			Some(blob) => Some(recompress(
+				blob,
+				&source.get_parameters().tile_compression,
+				&self.parameters.tile_compression,
+			)?),
+			None => None,
+		})
+	}
+
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let output_compression = self.parameters.tile_compression;
+
+		TileStream::from_stream_iter(self.branches.iter().enumerate().map(move |(index, branch)| {
+			let mut bbox = bbox.clone();
+			async move {
+				if bbox.intersect_pyramid(&branch.case).is_err() || bbox.is_empty() {
+					return TileStream::new_empty();
+				}
+
+				let stream = branch.source.get_tile_stream(bbox).await;
+				let stream = TileStream::from_stream(
+					stream
+						.stream
+						.filter(move |(coord, _)| ready(self.route(coord) == Some(index)))
+						.boxed(),
+				);
+
+				let source_compression = branch.source.get_parameters().tile_compression;
+				if source_compression == output_compression {
+					stream
+				} else {
+					stream.map_blob_parallel(move |blob| recompress(blob, &source_compression, &output_compression).unwrap())
+				}
+			}
+		}))
+		.await
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"from_switch"
+	}
+}
+
+#[async_trait]
+impl ReadOperationFactoryTrait for Factory {
+	async fn build<'a>(&self, vpl_node: VPLNode, factory: &'a PipelineFactory) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helpers::mock_vector_source::arrange_tiles;
+	use versatiles_geometry::vector_tile::VectorTile;
+
+	fn get_filename(blob: &Blob) -> String {
+		let tile = VectorTile::from_blob(blob).unwrap();
+		let layer = &tile.layers[0];
+		let properties = layer.features[0].decode_properties(layer).unwrap();
+		properties.get("filename").unwrap().to_string()
+	}
+
+	const VPL: &str = r#"from_switch cases=["zoom=3- bbox=-180,-20,20,85", "zoom=-3"] [
+		from_container filename="🟦",
+		from_container filename="🟨" | filter_bbox bbox=[-20,-85,180,20]
+	]"#;
+
+	#[test]
+	fn cases() -> Result<()> {
+		let grid = TileGrid::WebMercator;
+		assert_eq!(parse_case("", grid)?, TileBBoxPyramid::new_full(31));
+		assert_eq!(
+			format!("{:?}", parse_case("zoom=1-2", grid)?),
+			"[1: [0,0,1,1] (4), 2: [0,0,3,3] (16)]"
+		);
+		assert_eq!(
+			format!("{:?}", parse_case("zoom=-1 bbox=0,0,180,85", grid)?),
+			"[0: [0,0,0,0] (1), 1: [1,0,1,0] (1)]"
+		);
+		for case in ["zoom=3", "zoom=a-", "bbox=1,2,3", "size=3"] {
+			assert!(parse_case(case, grid).is_err(), "{case}");
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn get_tile_data() -> Result<()> {
+		let operation = PipelineFactory::new_dummy().operation_from_vpl(VPL).await?;
+
+		let get = |x, y, z| {
+			let operation = &operation;
+			async move {
+				let coord = TileCoord3::new(x, y, z).unwrap();
+				operation.get_tile_data(&coord).await.unwrap().map(|b| get_filename(&b))
+			}
+		};
+		assert_eq!(get(0, 0, 3).await.as_deref(), Some("🟦"));
+		assert_eq!(get(7, 7, 3).await.as_deref(), Some("🟨"));
+		// routed to the yellow source, which has no tile here
+		assert_eq!(get(0, 0, 2).await.as_deref(), None);
+		assert_eq!(get(0, 0, 4).await.as_deref(), Some("🟦"));
+		assert_eq!(get(15, 15, 4).await.as_deref(), None);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn get_tile_stream() -> Result<()> {
+		let operation = PipelineFactory::new_dummy().operation_from_vpl(VPL).await?;
+
+		let tiles = operation.get_tile_stream(TileBBox::new_full(3)?).await.collect().await;
+		assert_eq!(
+			arrange_tiles(tiles, |_, blob| get_filename(&blob)),
+			vec![
+				"🟦 🟦 🟦 🟦 🟦 ❌ ❌ ❌",
+				"🟦 🟦 🟦 🟦 🟦 ❌ ❌ ❌",
+				"🟦 🟦 🟦 🟦 🟦 ❌ ❌ ❌",
+				"🟦 🟦 🟦 🟦 🟦 🟨 🟨 🟨",
+				"🟦 🟦 🟦 🟦 🟦 🟨 🟨 🟨",
+				"❌ ❌ ❌ 🟨 🟨 🟨 🟨 🟨",
+				"❌ ❌ ❌ 🟨 🟨 🟨 🟨 🟨",
+				"❌ ❌ ❌ 🟨 🟨 🟨 🟨 🟨"
+			]
+		);
+
+		let tiles = operation.get_tile_stream(TileBBox::new_full(4)?).await.collect().await;
+		assert!(tiles.iter().all(|(_, blob)| get_filename(blob) == "🟦"));
+		assert_eq!(tiles.len(), 9 * 9);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn errors() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_switch",
+			"from_switch cases=[\"\"] [ from_container filename=1, from_container filename=2 ]",
+			"from_switch cases=[\"zoom=x\",\"\"] [ from_container filename=1, from_container filename=2 ]",
+			"from_switch cases=[\"\",\"\"] [ from_container filename=1, from_debug format=png ]",
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
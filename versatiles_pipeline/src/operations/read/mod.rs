@@ -3,6 +3,7 @@ use crate::traits::ReadOperationFactoryTrait;
 mod from_container;
 pub mod from_debug;
 mod from_overlayed;
+mod from_switch;
 mod from_vectortiles_merged;
 
 pub fn get_read_operation_factories() -> Vec<Box<dyn ReadOperationFactoryTrait>> {
@@ -10,6 +11,7 @@ pub fn get_read_operation_factories() -> Vec<Box<dyn ReadOperationFactoryTrait>>
 		Box::new(from_container::Factory {}),
 		Box::new(from_debug::Factory {}),
 		Box::new(from_overlayed::Factory {}),
+		Box::new(from_switch::Factory {}),
 		Box::new(from_vectortiles_merged::Factory {}),
 	]
 }
@@ -2,12 +2,36 @@ use crate::traits::TransformOperationFactoryTrait;
 
 mod filter_bbox;
 mod filter_zoom;
-mod vectortiles_update_properties;
+mod pbf_add_layer;
+mod pbf_enforce_schema;
+mod pbf_label_points;
+mod pbf_layer_zoom_range;
+mod pbf_merge_lines;
+mod pbf_prune;
+mod pbf_quantize_properties;
+mod pbf_size_budget;
+mod raster_colorize;
+mod raster_reproject;
+mod raster_watermark;
+mod vectortiles_format;
+pub(crate) mod vectortiles_update_properties;
 
 pub fn get_transform_operation_factories() -> Vec<Box<dyn TransformOperationFactoryTrait>> {
 	vec![
 		Box::new(filter_bbox::Factory {}),
 		Box::new(filter_zoom::Factory {}),
+		Box::new(pbf_add_layer::Factory {}),
+		Box::new(pbf_enforce_schema::Factory {}),
+		Box::new(pbf_label_points::Factory {}),
+		Box::new(pbf_layer_zoom_range::Factory {}),
+		Box::new(pbf_merge_lines::Factory {}),
+		Box::new(pbf_prune::Factory {}),
+		Box::new(pbf_quantize_properties::Factory {}),
+		Box::new(pbf_size_budget::Factory {}),
+		Box::new(raster_colorize::Factory {}),
+		Box::new(raster_reproject::Factory {}),
+		Box::new(raster_watermark::Factory {}),
+		Box::new(vectortiles_format::Factory {}),
 		Box::new(vectortiles_update_properties::Factory {}),
 	]
 }
@@ -0,0 +1,382 @@
+use crate::{
+	helpers::{project, DecodedTileCache},
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::{cell::Cell, collections::BTreeMap, fs::File, io::BufReader, sync::Arc};
+use versatiles_core::{
+	tilejson::{TileJSON, VectorLayer},
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{
+	geojson::{parse_geojson, read_geojson},
+	math::{clip_line, clip_points, clip_ring},
+	vector_tile::VectorTileLayer,
+	GeoCollection, GeoFeature, GeoValue, Geometry,
+};
+
+/// Extent of the added layer.
+const EXTENT: u32 = 4096;
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Adds a layer with static features to every vector tile, e.g. a license notice or a debug grid. The features are read from GeoJSON, clipped to every tile and added as a new layer.
+struct Args {
+	/// Name of the added layer. The source must not contain a layer with this name.
+	name: String,
+
+	/// Path to a GeoJSON file, e.g. `filename="grid.geojson"`.
+	filename: Option<String>,
+
+	/// Inline GeoJSON with escaped quotes, e.g. `geojson="{\"type\":\"Feature\", …}"`. Use either `filename` or `geojson`.
+	geojson: Option<String>,
+
+	/// Features are clipped to the tile plus this buffer, in pixels of a 256x256 pixel tile. Defaults to 4.
+	buffer: Option<u32>,
+}
+
+/// A feature with coordinates in Web Mercator at zoom level 0, i.e. from 0 to 1.
+#[derive(Debug)]
+struct StaticFeature {
+	feature: GeoFeature,
+	/// `[x_min, y_min, x_max, y_max]`
+	bbox: [f64; 4],
+}
+
+impl StaticFeature {
+	fn new(feature: GeoFeature) -> StaticFeature {
+		let bbox = Cell::new([f64::MAX, f64::MAX, f64::MIN, f64::MIN]);
+		let geometry = feature.geometry.into_multi().map_coordinates(|[lon, lat]| {
+			let (x, y) = project(lat, lon, 0);
+			let b = bbox.get();
+			bbox.set([b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)]);
+			[x, y]
+		});
+		StaticFeature {
+			feature: GeoFeature { geometry, ..feature },
+			bbox: bbox.get(),
+		}
+	}
+
+	/// Returns the feature clipped to the tile, in tile units, or `None` if nothing is left.
+	fn get_clipped(&self, coord: &TileCoord3, buffer: f64) -> Option<GeoFeature> {
+		let scale = (1u64 << coord.z) as f64;
+		let to_tile = |[x, y]: [f64; 2]| {
+			[
+				(x * scale - coord.x as f64) * EXTENT as f64,
+				(y * scale - coord.y as f64) * EXTENT as f64,
+			]
+		};
+
+		let clip_bbox = [-buffer, -buffer, EXTENT as f64 + buffer, EXTENT as f64 + buffer];
+		let [x_min, y_min] = to_tile([self.bbox[0], self.bbox[1]]);
+		let [x_max, y_max] = to_tile([self.bbox[2], self.bbox[3]]);
+		if x_max < clip_bbox[0] || y_max < clip_bbox[1] || x_min > clip_bbox[2] || y_min > clip_bbox[3] {
+			return None;
+		}
+
+		let geometry = match self.feature.geometry.clone().map_coordinates(to_tile) {
+			Geometry::MultiPoint(g) => {
+				let points = clip_points(&g.0, &clip_bbox);
+				(!points.is_empty()).then(|| Geometry::new_multi_point(points))?
+			}
+			Geometry::MultiLineString(g) => {
+				let lines: Vec<_> = g.0.iter().flat_map(|line| clip_line(line, &clip_bbox)).collect();
+				(!lines.is_empty()).then(|| Geometry::new_multi_line_string(lines))?
+			}
+			Geometry::MultiPolygon(g) => {
+				let mut polygons = Vec::new();
+				for polygon in g.0.iter() {
+					let mut rings = polygon.iter().map(|ring| clip_ring(ring, &clip_bbox));
+					let outer = rings.next().unwrap_or_default();
+					if !outer.is_empty() {
+						polygons.push([outer].into_iter().chain(rings.filter(|r| !r.is_empty())).collect());
+					}
+				}
+				(!polygons.is_empty()).then(|| Geometry::new_multi_polygon(polygons))?
+			}
+			_ => unreachable!("geometries are converted into multi geometries"),
+		};
+
+		Some(GeoFeature {
+			geometry,
+			..self.feature.clone()
+		})
+	}
+}
+
+#[derive(Debug)]
+struct Runner {
+	name: String,
+	features: Vec<StaticFeature>,
+	/// buffer in tile units
+	buffer: f64,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Blob> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+
+		let features: Vec<GeoFeature> = self
+			.features
+			.iter()
+			.filter_map(|f| f.get_clipped(coord, self.buffer))
+			.collect();
+		if !features.is_empty() {
+			tile
+				.layers
+				.push(VectorTileLayer::from_features(self.name.clone(), features, EXTENT, 2)?);
+		}
+
+		self.tile_cache.encode(coord, tile)
+	}
+}
+
+fn read_features(args: &Args, factory: &PipelineFactory) -> Result<GeoCollection> {
+	match (&args.filename, &args.geojson) {
+		(Some(filename), None) => {
+			let path = factory.resolve_path(filename);
+			let file = File::open(&path).with_context(|| format!("Failed to open {path:?}"))?;
+			read_geojson(BufReader::new(file)).with_context(|| format!("Failed to read GeoJSON from {path:?}"))
+		}
+		(None, Some(geojson)) => parse_geojson(geojson).context("Failed to parse GeoJSON"),
+		(Some(_), Some(_)) => bail!("use either 'filename' or 'geojson', but not both"),
+		(None, None) => bail!("either 'filename' or 'geojson' is required"),
+	}
+}
+
+/// Describes the properties of the features as TileJSON fields.
+fn get_vector_layer(features: &[StaticFeature]) -> VectorLayer {
+	let mut fields = BTreeMap::new();
+	for feature in features {
+		for (key, value) in feature.feature.properties.iter() {
+			let field_type = match value {
+				GeoValue::Bool(_) => "Boolean",
+				GeoValue::String(_) | GeoValue::Null => "String",
+				_ => "Number",
+			};
+			fields.insert(key.clone(), field_type.to_string());
+		}
+	}
+	VectorLayer {
+		fields,
+		description: None,
+		minzoom: None,
+		maxzoom: None,
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let mut tilejson = source.get_tilejson().clone();
+			ensure!(
+				!tilejson.vector_layers.0.contains_key(&args.name),
+				"source already contains a layer named \"{}\"",
+				args.name
+			);
+
+			let features: Vec<StaticFeature> = read_features(&args, factory)?
+				.features
+				.into_iter()
+				.map(StaticFeature::new)
+				.collect();
+			tilejson
+				.vector_layers
+				.0
+				.insert(args.name.clone(), get_vector_layer(&features));
+
+			let runner = Arc::new(Runner {
+				name: args.name,
+				features,
+				buffer: args.buffer.unwrap_or(4) as f64 * EXTENT as f64 / 256.0,
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			Some(self.runner.run(coord, blob)?)
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_add_layer"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::vector_tile::VectorTile;
+
+	const GEOJSON: &str = r#"{"type":"FeatureCollection","features":[
+		{"type":"Feature","geometry":{"type":"LineString","coordinates":[[-170,0],[170,0]]},"properties":{"name":"equator"}},
+		{"type":"Feature","geometry":{"type":"Point","coordinates":[13.4,52.5]},"properties":{"name":"berlin","rank":1}}
+	]}"#;
+
+	async fn get_layer(vpl: &str, coord: TileCoord3) -> Result<Option<VectorTileLayer>> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory.operation_from_vpl(vpl).await?;
+		let blob = operation.get_tile_data(&coord).await?.unwrap();
+		let tile = VectorTile::from_blob(&blob)?;
+		Ok(tile.layers.into_iter().find(|layer| layer.name == "static"))
+	}
+
+	fn get_names(layer: &VectorTileLayer) -> Result<Vec<String>> {
+		layer
+			.features
+			.iter()
+			.map(|f| Ok(f.decode_properties(layer)?.get("name").unwrap().to_string()))
+			.collect()
+	}
+
+	#[test]
+	fn clipping() -> Result<()> {
+		let line = StaticFeature::new(GeoFeature::new(Geometry::new_line_string(vec![[-170, 0], [170, 0]])));
+
+		// the equator is the top edge of tile 1/0/1
+		let clipped = line.get_clipped(&TileCoord3::new(0, 1, 1)?, 0.0).unwrap();
+		assert_eq!(
+			clipped.geometry.map_coordinates(|p| p.map(f64::round)),
+			Geometry::new_multi_line_string(vec![vec![[228, 0], [4096, 0]]])
+		);
+		assert!(line.get_clipped(&TileCoord3::new(0, 0, 1)?, 0.0).is_some());
+		assert!(line.get_clipped(&TileCoord3::new(0, 0, 2)?, 0.0).is_none());
+
+		let polygon = StaticFeature::new(GeoFeature::new(Geometry::new_polygon(vec![vec![
+			[-90, -45],
+			[90, -45],
+			[90, 45],
+			[-90, 45],
+			[-90, -45],
+		]])));
+		let clipped = polygon.get_clipped(&TileCoord3::new(1, 1, 1)?, 0.0).unwrap();
+		let Geometry::MultiPolygon(g) = clipped.geometry else {
+			panic!("expected a multi polygon");
+		};
+		assert_eq!(g.0[0][0].len(), 5);
+		assert!(g.0[0][0].iter().all(|p| p[0] >= 0.0 && p[1] <= 4096.0));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let geojson = GEOJSON.replace('"', "\\\"");
+		let vpl = format!("from_container filename=dummy | pbf_add_layer name=static geojson=\"{geojson}\"");
+
+		let layer = get_layer(&vpl, TileCoord3::new(0, 0, 0)?).await?.unwrap();
+		assert_eq!(layer.extent, 4096);
+		assert_eq!(get_names(&layer)?, ["equator", "berlin"]);
+
+		let layer = get_layer(&vpl, TileCoord3::new(0, 1, 1)?).await?.unwrap();
+		assert_eq!(get_names(&layer)?, ["equator"]);
+
+		assert!(get_layer(&vpl, TileCoord3::new(0, 3, 2)?).await?.is_none());
+
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory.operation_from_vpl(&vpl).await?;
+		let fields = &operation.get_tilejson().vector_layers.0["static"].fields;
+		assert_eq!(fields["name"], "String");
+		assert_eq!(fields["rank"], "Number");
+
+		let tiles = operation
+			.get_tile_stream(TileBBox::new(2, 0, 0, 3, 3)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 16);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn invalid_args() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_container filename=dummy | pbf_add_layer name=static",
+			"from_container filename=dummy | pbf_add_layer name=static geojson=\"{}\" filename=a.geojson",
+			"from_container filename=dummy | pbf_add_layer name=static filename=missing.geojson",
+			"from_debug format=pbf | pbf_add_layer name=debug_x geojson=\"{\\\"type\\\":\\\"FeatureCollection\\\",\\\"features\\\":[]}\"",
+			"from_debug format=png | pbf_add_layer name=static geojson=\"{\\\"type\\\":\\\"FeatureCollection\\\",\\\"features\\\":[]}\"",
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
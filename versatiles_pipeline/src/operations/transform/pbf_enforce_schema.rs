@@ -0,0 +1,221 @@
+use crate::{
+	helpers::DecodedTileCache,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::vector_tile::TilesetSchema;
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Makes vector tiles match a tileset schema, as written by `versatiles schema generate`: unknown attributes are dropped, values are cast to the types of their attributes and missing attributes are set to their defaults. Values that can't be cast are dropped.
+struct Args {
+	/// Path to a schema file, e.g. `filename="schema.json"`.
+	filename: Option<String>,
+
+	/// Inline schema with escaped quotes, e.g. `schema="{\"layers\":{…}}"`. Use either `filename` or `schema`.
+	schema: Option<String>,
+
+	/// If set, layers that are not in the schema are kept unchanged. Otherwise they are removed.
+	keep_unknown_layers: bool,
+}
+
+#[derive(Debug)]
+struct Runner {
+	schema: TilesetSchema,
+	keep_unknown_layers: bool,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+
+		tile
+			.layers
+			.retain(|layer| self.keep_unknown_layers || self.schema.layers.contains_key(&layer.name));
+		for layer in tile.layers.iter_mut() {
+			if let Some(layer_schema) = self.schema.layers.get(&layer.name) {
+				layer.map_properties(|properties| layer_schema.enforce(properties))?;
+			}
+		}
+
+		if tile.layers.is_empty() {
+			return Ok(None);
+		}
+		Ok(Some(self.tile_cache.encode(coord, tile)?))
+	}
+}
+
+fn read_schema(args: &Args, factory: &PipelineFactory) -> Result<TilesetSchema> {
+	match (&args.filename, &args.schema) {
+		(Some(filename), None) => TilesetSchema::read_file(&factory.resolve_path(filename)),
+		(None, Some(schema)) => TilesetSchema::parse_str(schema).context("Failed to parse schema"),
+		(Some(_), Some(_)) => bail!("use either 'filename' or 'schema', but not both"),
+		(None, None) => bail!("either 'filename' or 'schema' is required"),
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			let schema = read_schema(&args, factory)?;
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let runner = Arc::new(Runner {
+				schema,
+				keep_unknown_layers: args.keep_unknown_layers,
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+			let tilejson = source.get_tilejson().clone();
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			self.runner.run(coord, blob)?
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_enforce_schema"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{vector_tile::VectorTile, GeoProperties, GeoValue};
+
+	const SCHEMA: &str =
+		r#"{\"layers\":{\"mock\":{\"attributes\":{\"x\":{\"type\":\"string\"},\"kind\":{\"default\":\"tile\"}}}}}"#;
+
+	async fn get_properties(vpl: &str) -> Result<Option<GeoProperties>> {
+		let operation = PipelineFactory::new_dummy().operation_from_vpl(vpl).await?;
+		let Some(blob) = operation.get_tile_data(&TileCoord3::new(3, 1, 4)?).await? else {
+			return Ok(None);
+		};
+		let tile = VectorTile::from_blob(&blob)?;
+		let layer = &tile.layers[0];
+		Ok(Some(layer.features[0].decode_properties(layer)?))
+	}
+
+	#[tokio::test]
+	async fn enforce_schema() -> Result<()> {
+		let properties = get_properties(&format!(
+			"from_container filename=dummy | pbf_enforce_schema schema=\"{SCHEMA}\""
+		))
+		.await?;
+		assert_eq!(
+			properties,
+			Some(GeoProperties::from(vec![
+				("kind", GeoValue::from("tile")),
+				("x", GeoValue::from("3"))
+			]))
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn unknown_layers() -> Result<()> {
+		let schema = r#"{\"layers\":{\"water\":{}}}"#;
+		let vpl = format!("from_container filename=dummy | pbf_enforce_schema schema=\"{schema}\"");
+		assert_eq!(get_properties(&vpl).await?, None);
+
+		let properties = get_properties(&format!("{vpl} keep_unknown_layers=true"))
+			.await?
+			.unwrap();
+		assert_eq!(properties.get("x"), Some(&GeoValue::UInt(3)));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn invalid_args() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"pbf_enforce_schema",
+			r#"pbf_enforce_schema schema="{}""#,
+			r#"pbf_enforce_schema filename="schema.json" schema="{\"layers\":{}}""#,
+		] {
+			let vpl = format!("from_container filename=dummy | {vpl}");
+			assert!(factory.operation_from_vpl(&vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
@@ -0,0 +1,331 @@
+use crate::{
+	helpers::DecodedTileCache,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{ensure, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::{
+	collections::{BTreeMap, HashSet},
+	sync::Arc,
+};
+use versatiles_core::{
+	tilejson::{TileJSON, VectorLayer},
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{
+	math::{area_polygon, pole_of_inaccessibility},
+	vector_tile::VectorTileLayer,
+	GeoFeature, GeoProperties, Geometry,
+};
+
+/// Extent of the added layer.
+const EXTENT: u32 = 4096;
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Adds a layer with a label point for every polygon of the selected layers. The point is the pole of inaccessibility, i.e. the point inside the polygon that is farthest from its outline, so it works for concave polygons as well. Polygons spanning several tiles get a label point in every tile.
+struct Args {
+	/// Names of the polygon layers, e.g. `layers=["water_polygons","landuse"]`.
+	layers: Vec<String>,
+
+	/// Name of the added point layer. Defaults to "label_points".
+	name: Option<String>,
+
+	/// Properties that are copied from the polygons to their label points, e.g. `properties=["name","kind"]`.
+	properties: Vec<String>,
+
+	/// Precision of the label points, in pixels of a 256x256 pixel tile. Defaults to 1.
+	precision: Option<f32>,
+}
+
+#[derive(Debug)]
+struct Runner {
+	layers: HashSet<String>,
+	name: String,
+	properties: Vec<String>,
+	/// precision in pixels of a 256x256 pixel tile
+	precision: f64,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Blob> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+
+		let mut points = Vec::new();
+		for layer in tile.layers.iter().filter(|layer| self.layers.contains(&layer.name)) {
+			points.extend(self.get_label_points(layer)?);
+		}
+		if !points.is_empty() {
+			tile
+				.layers
+				.push(VectorTileLayer::from_features(self.name.clone(), points, EXTENT, 2)?);
+		}
+
+		self.tile_cache.encode(coord, tile)
+	}
+
+	/// Returns the label points of the polygons of a layer that are inside the tile, in units of `EXTENT`.
+	fn get_label_points(&self, layer: &VectorTileLayer) -> Result<Vec<GeoFeature>> {
+		let extent = layer.extent as f64;
+		let scale = EXTENT as f64 / extent;
+		let precision = self.precision * extent / 256.0;
+
+		let mut points = Vec::new();
+		for feature in layer.features.iter() {
+			let polygon = match feature.to_geometry()? {
+				Geometry::Polygon(g) => g.0,
+				// label the largest polygon
+				Geometry::MultiPolygon(g) => match g
+					.0
+					.into_iter()
+					.max_by(|a, b| area_polygon(a).total_cmp(&area_polygon(b)))
+				{
+					Some(polygon) => polygon,
+					None => continue,
+				},
+				_ => continue,
+			};
+
+			let [x, y] = pole_of_inaccessibility(&polygon, precision);
+			// polygons are clipped with a buffer, points within the buffer belong to the neighbouring tiles
+			if x < 0.0 || y < 0.0 || x >= extent || y >= extent {
+				continue;
+			}
+
+			let properties = feature.decode_properties(layer)?;
+			let mut point = GeoFeature::new(Geometry::new_point([x * scale, y * scale]));
+			point.set_properties(self.select_properties(&properties));
+			points.push(point);
+		}
+		Ok(points)
+	}
+
+	fn select_properties(&self, properties: &GeoProperties) -> GeoProperties {
+		let mut selected = GeoProperties::new();
+		for key in self.properties.iter() {
+			if let Some(value) = properties.get(key) {
+				selected.insert(key.clone(), value.clone());
+			}
+		}
+		selected
+	}
+}
+
+/// Describes the label point layer, with the field types of the polygon layers.
+fn get_vector_layer(tilejson: &TileJSON, args: &Args) -> VectorLayer {
+	let mut fields = BTreeMap::new();
+	for layer in args.layers.iter().filter_map(|name| tilejson.vector_layers.0.get(name)) {
+		for key in args.properties.iter() {
+			if let Some(field_type) = layer.fields.get(key) {
+				fields.insert(key.clone(), field_type.clone());
+			}
+		}
+	}
+	VectorLayer {
+		fields,
+		description: None,
+		minzoom: None,
+		maxzoom: None,
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			ensure!(!args.layers.is_empty(), "'layers' is required");
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let name = args.name.clone().unwrap_or_else(|| String::from("label_points"));
+			let mut tilejson = source.get_tilejson().clone();
+			ensure!(
+				!tilejson.vector_layers.0.contains_key(&name),
+				"source already contains a layer named \"{name}\""
+			);
+			let vector_layer = get_vector_layer(&tilejson, &args);
+			tilejson.vector_layers.0.insert(name.clone(), vector_layer);
+
+			let runner = Arc::new(Runner {
+				layers: args.layers.into_iter().collect(),
+				name,
+				properties: args.properties,
+				precision: args.precision.unwrap_or(1.0) as f64,
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			Some(self.runner.run(coord, blob)?)
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_label_points"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{vector_tile::VectorTile, GeoValue};
+
+	fn make_tile() -> Result<Blob> {
+		let feature = |geometry: Geometry, name: &str| {
+			let mut feature = GeoFeature::new(geometry);
+			feature.properties = GeoProperties::from(vec![("name", name), ("kind", "lake")]);
+			feature
+		};
+		let square =
+			|x: i64, y: i64, size: i64| vec![vec![[x, y], [x + size, y], [x + size, y + size], [x, y + size], [x, y]]];
+
+		let features = vec![
+			feature(Geometry::new_polygon(square(100, 100, 200)), "square"),
+			feature(
+				Geometry::new_multi_polygon(vec![square(1000, 1000, 10), square(2000, 2000, 1000)]),
+				"multi",
+			),
+			feature(Geometry::new_polygon(square(-300, 1000, 200)), "outside"),
+			feature(Geometry::new_line_string(vec![[0, 0], [100, 0]]), "line"),
+		];
+		let layer = VectorTileLayer::from_features(String::from("water"), features, 4096, 2)?;
+		VectorTile::new(vec![layer]).to_blob()
+	}
+
+	fn runner(properties: &[&str]) -> Runner {
+		Runner {
+			layers: HashSet::from([String::from("water")]),
+			name: String::from("label_points"),
+			properties: properties.iter().map(|p| p.to_string()).collect(),
+			precision: 0.1,
+			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
+		}
+	}
+
+	#[test]
+	fn label_points() -> Result<()> {
+		let tile = VectorTile::from_blob(&runner(&["name"]).run(&TileCoord3::new(0, 0, 0)?, make_tile()?)?)?;
+		assert_eq!(tile.layers.len(), 2);
+		let layer = &tile.layers[1];
+		assert_eq!(layer.name, "label_points");
+
+		let points = layer.to_features()?;
+		assert_eq!(points.len(), 2);
+
+		let get_point = |feature: &GeoFeature| match &feature.geometry {
+			Geometry::Point(g) => g.0.map(|v| v.round()),
+			Geometry::MultiPoint(g) => g.0[0].map(|v| v.round()),
+			_ => panic!("not a point"),
+		};
+		assert_eq!(get_point(&points[0]), [200.0, 200.0]);
+		assert_eq!(get_point(&points[1]), [2500.0, 2500.0]);
+		assert_eq!(points[1].properties.get("name"), Some(&GeoValue::from("multi")));
+		assert_eq!(points[1].properties.get("kind"), None);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl(
+				r#"from_debug format=pbf | pbf_label_points layers=["debug_z"] name="labels" properties=["unknown"]"#,
+			)
+			.await?;
+
+		let layer = &operation.get_tilejson().vector_layers.0["labels"];
+		assert!(layer.fields.is_empty());
+
+		let blob = operation.get_tile_data(&TileCoord3::new(1, 2, 3)?).await?.unwrap();
+		let tile = VectorTile::from_blob(&blob)?;
+		let labels = tile.layers.iter().find(|layer| layer.name == "labels").unwrap();
+		assert!(!labels.features.is_empty());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn invalid_arguments() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_debug format=pbf | pbf_label_points",
+			r#"from_debug format=pbf | pbf_label_points layers=["background"] name="debug_x""#,
+			r#"from_debug format=png | pbf_label_points layers=["background"]"#,
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
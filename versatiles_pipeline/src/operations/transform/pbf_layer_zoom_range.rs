@@ -0,0 +1,267 @@
+use crate::{
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::{collections::HashMap, ops::RangeInclusive, sync::Arc};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::vector_tile::VectorTileView;
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Removes vector tile layers outside of their zoom ranges, like `minzoom` and `maxzoom` in a style, but without sending the bytes to the client. Layers without a range are not changed.
+struct Args {
+	/// Zoom ranges as "layer:min-max". Either limit may be omitted, e.g. `ranges=["buildings:13-","boundaries:0-10"]`.
+	ranges: Vec<String>,
+}
+
+/// Parses a zoom range like "buildings:13-", "boundaries:0-10", "places:-8" or "water:5".
+fn parse_range(text: &str) -> Result<(String, RangeInclusive<u8>)> {
+	let (layer, range) = text
+		.rsplit_once(':')
+		.with_context(|| format!("zoom range must have the form \"layer:min-max\", but is \"{text}\""))?;
+	let parse = |value: &str, default: u8| -> Result<u8> {
+		let value = value.trim();
+		if value.is_empty() {
+			return Ok(default);
+		}
+		value
+			.parse::<u8>()
+			.with_context(|| format!("invalid zoom level \"{value}\" in zoom range \"{text}\""))
+	};
+	let (min, max) = match range.split_once('-') {
+		Some((min, max)) => (parse(min, 0)?, parse(max, u8::MAX)?),
+		None => {
+			let zoom = parse(range, 0)?;
+			(zoom, zoom)
+		}
+	};
+	ensure!(min <= max, "zoom range \"{text}\" is empty");
+	Ok((layer.trim().to_string(), min..=max))
+}
+
+#[derive(Debug)]
+struct Runner {
+	ranges: HashMap<String, RangeInclusive<u8>>,
+	tile_compression: TileCompression,
+}
+
+impl Runner {
+	fn is_visible(&self, layer: &str, level: u8) -> bool {
+		self.ranges.get(layer).is_none_or(|range| range.contains(&level))
+	}
+
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		if self.ranges.values().all(|range| range.contains(&coord.z)) {
+			// no layer is hidden at this zoom level
+			return Ok(Some(blob));
+		}
+
+		// the remaining layers are copied without decoding them
+		let mut tile = VectorTileView::new(blob.as_slice()).context("Failed to create VectorTileView from Blob")?;
+		tile.retain_layers(|name| self.is_visible(name, coord.z))?;
+
+		if tile.is_empty() {
+			return Ok(None);
+		}
+		Ok(Some(tile.to_blob()?))
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		_factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			ensure!(!args.ranges.is_empty(), "'ranges' is required");
+
+			let mut ranges = HashMap::new();
+			for text in &args.ranges {
+				let (layer, range) = parse_range(text)?;
+				ensure!(
+					ranges.insert(layer.clone(), range).is_none(),
+					"layer \"{layer}\" has more than one zoom range"
+				);
+			}
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let mut tilejson = source.get_tilejson().clone();
+			tilejson.vector_layers.0.retain(|name, layer| {
+				let Some(range) = ranges.get(name) else {
+					return true;
+				};
+				let minzoom = layer.minzoom.unwrap_or(0).max(*range.start());
+				let maxzoom = layer.maxzoom.unwrap_or(u8::MAX).min(*range.end());
+				if minzoom > 0 {
+					layer.minzoom = Some(minzoom);
+				}
+				if maxzoom < u8::MAX {
+					layer.maxzoom = Some(maxzoom);
+				}
+				minzoom <= maxzoom
+			});
+
+			let runner = Arc::new(Runner {
+				ranges,
+				tile_compression: parameters.tile_compression,
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			self.runner.run(coord, blob)?
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_layer_zoom_range"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::vector_tile::VectorTile;
+
+	#[test]
+	fn ranges() -> Result<()> {
+		assert_eq!(parse_range("buildings:13-")?, ("buildings".to_string(), 13..=255));
+		assert_eq!(parse_range("boundaries:0-10")?, ("boundaries".to_string(), 0..=10));
+		assert_eq!(parse_range("places: -8")?, ("places".to_string(), 0..=8));
+		assert_eq!(parse_range("water:5")?, ("water".to_string(), 5..=5));
+		assert!(parse_range("water").is_err());
+		assert!(parse_range("water:10-5").is_err());
+		assert!(parse_range("water:x-5").is_err());
+		Ok(())
+	}
+
+	async fn get_layer_names(vpl: &str, coord: TileCoord3) -> Result<Vec<String>> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory.operation_from_vpl(vpl).await?;
+		let Some(blob) = operation.get_tile_data(&coord).await? else {
+			return Ok(vec![]);
+		};
+		let tile = VectorTile::from_blob(&blob)?;
+		let mut names: Vec<String> = tile.layers.iter().map(|layer| layer.name.clone()).collect();
+		names.sort();
+		Ok(names)
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let vpl = r#"from_debug format=pbf | pbf_layer_zoom_range ranges=["debug_x:3-","debug_y:0-4","unknown:1"]"#;
+
+		assert_eq!(
+			get_layer_names(vpl, TileCoord3::new(0, 0, 2)?).await?,
+			["background", "debug_y", "debug_z"]
+		);
+		assert_eq!(
+			get_layer_names(vpl, TileCoord3::new(0, 0, 4)?).await?,
+			["background", "debug_x", "debug_y", "debug_z"]
+		);
+		assert_eq!(
+			get_layer_names(vpl, TileCoord3::new(0, 0, 5)?).await?,
+			["background", "debug_x", "debug_z"]
+		);
+
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory.operation_from_vpl(vpl).await?;
+		let vector_layers = &operation.get_tilejson().vector_layers.0;
+		assert_eq!(vector_layers["debug_x"].minzoom, Some(3));
+		assert_eq!(vector_layers["debug_x"].maxzoom, Some(30));
+		assert_eq!(vector_layers["debug_y"].maxzoom, Some(4));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn removes_empty_tiles() -> Result<()> {
+		let vpl = r#"from_debug format=pbf | pbf_layer_zoom_range ranges=["background:5-","debug_x:5-","debug_y:5-","debug_z:5-"]"#;
+		assert!(get_layer_names(vpl, TileCoord3::new(0, 0, 2)?).await?.is_empty());
+		assert_eq!(get_layer_names(vpl, TileCoord3::new(0, 0, 6)?).await?.len(), 4);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn invalid_arguments() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_debug format=pbf | pbf_layer_zoom_range",
+			r#"from_debug format=pbf | pbf_layer_zoom_range ranges=["debug_x:3-","debug_x:5-"]"#,
+			r#"from_debug format=png | pbf_layer_zoom_range ranges=["debug_x:3-"]"#,
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
@@ -0,0 +1,262 @@
+use crate::{
+	helpers::DecodedTileCache,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{ensure, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{
+	math::merge_lines,
+	vector_tile::{VectorTileFeature, VectorTileLayer},
+	Coordinates1, Coordinates2, Geometry,
+};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Joins touching lines with identical properties, like road segments that were split when clipping. This reduces the number of features and gives renderers longer lines to place labels on. Lines are only joined end to start, so their direction is kept. Merged features lose their IDs.
+struct Args {
+	/// Names of the layers, e.g. `layers=["streets"]`. Defaults to all layers.
+	layers: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Runner {
+	layers: HashSet<String>,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Blob> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+
+		for layer in tile.layers.iter_mut() {
+			if self.layers.is_empty() || self.layers.contains(&layer.name) {
+				merge_layer(layer)?;
+			}
+		}
+
+		self.tile_cache.encode(coord, tile)
+	}
+}
+
+/// Key and value IDs of a property.
+type Tag = (u32, u32);
+
+/// Returns the tags of a feature sorted, so equal properties give equal keys.
+fn get_tags(feature: &VectorTileFeature) -> Vec<Tag> {
+	let mut tags: Vec<Tag> = feature.tag_ids.chunks_exact(2).map(|c| (c[0], c[1])).collect();
+	tags.sort_unstable();
+	tags
+}
+
+fn merge_layer(layer: &mut VectorTileLayer) -> Result<()> {
+	// group the lines by their properties
+	let mut groups: HashMap<Vec<Tag>, Vec<(usize, Coordinates2)>> = HashMap::new();
+	for (index, feature) in layer.features.iter().enumerate() {
+		let lines = match feature.to_geometry()? {
+			Geometry::LineString(g) => vec![g.0],
+			Geometry::MultiLineString(g) => g.0,
+			_ => continue,
+		};
+		groups.entry(get_tags(feature)).or_default().push((index, lines));
+	}
+
+	let mut merged: HashMap<usize, VectorTileFeature> = HashMap::new();
+	let mut removed: HashSet<usize> = HashSet::new();
+	for group in groups.into_values().filter(|group| group.len() > 1) {
+		let indexes: Vec<usize> = group.iter().map(|(index, _)| *index).collect();
+		let lines: Vec<Coordinates1> = group.into_iter().flat_map(|(_, lines)| lines).collect();
+		let count = lines.len();
+		let lines = merge_lines(lines);
+		if lines.len() == count {
+			// nothing touches
+			continue;
+		}
+
+		let geometry = Geometry::new_multi_line_string(lines);
+		let first = indexes[0];
+		let tag_ids = layer.features[first].tag_ids.clone();
+		merged.insert(first, VectorTileFeature::from_geometry(None, tag_ids, geometry)?);
+		removed.extend(indexes.into_iter().skip(1));
+	}
+
+	if merged.is_empty() {
+		return Ok(());
+	}
+
+	let features = std::mem::take(&mut layer.features);
+	layer.features = features
+		.into_iter()
+		.enumerate()
+		.filter(|(index, _)| !removed.contains(index))
+		.map(|(index, feature)| merged.remove(&index).unwrap_or(feature))
+		.collect();
+	Ok(())
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let runner = Arc::new(Runner {
+				layers: args.layers.into_iter().collect(),
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				tilejson: source.get_tilejson().clone(),
+				source,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			Some(self.runner.run(coord, blob)?)
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_merge_lines"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{vector_tile::VectorTile, GeoFeature, GeoProperties};
+
+	fn make_tile() -> Result<Blob> {
+		let feature = |geometry: Geometry, name: &str| {
+			let mut feature = GeoFeature::new(geometry);
+			feature.properties = GeoProperties::from(vec![("name", name)]);
+			feature
+		};
+		let features = vec![
+			feature(Geometry::new_line_string(vec![[0, 0], [100, 0]]), "main"),
+			feature(Geometry::new_line_string(vec![[500, 500], [600, 500]]), "side"),
+			feature(Geometry::new_line_string(vec![[100, 0], [200, 100]]), "main"),
+			feature(Geometry::new_line_string(vec![[200, 100], [300, 100]]), "other"),
+			feature(Geometry::new_line_string(vec![[900, 900], [1000, 900]]), "main"),
+			feature(Geometry::new_point([100, 0]), "main"),
+		];
+		let streets = VectorTileLayer::from_features(String::from("streets"), features.clone(), 4096, 2)?;
+		let rivers = VectorTileLayer::from_features(String::from("rivers"), features, 4096, 2)?;
+		VectorTile::new(vec![streets, rivers]).to_blob()
+	}
+
+	#[test]
+	fn merge() -> Result<()> {
+		let runner = Runner {
+			layers: HashSet::from([String::from("streets")]),
+			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
+		};
+		let tile = VectorTile::from_blob(&runner.run(&TileCoord3::new(0, 0, 0)?, make_tile()?)?)?;
+
+		let features = tile.layers[0].to_features()?;
+		assert_eq!(features.len(), 4);
+		assert_eq!(
+			features[0].geometry,
+			Geometry::new_multi_line_string(vec![vec![[0, 0], [100, 0], [200, 100]], vec![[900, 900], [1000, 900]]])
+		);
+		assert_eq!(features[0].properties, GeoProperties::from(vec![("name", "main")]));
+
+		// layers that are not selected stay unchanged
+		assert_eq!(tile.layers[1].features.len(), 6);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl("from_debug format=pbf | pbf_merge_lines")
+			.await?;
+		let blob = operation.get_tile_data(&TileCoord3::new(1, 2, 3)?).await?.unwrap();
+		let tile = VectorTile::from_blob(&blob)?;
+		assert_eq!(tile.layers.len(), 4);
+
+		let factory = PipelineFactory::new_dummy();
+		assert!(factory
+			.operation_from_vpl("from_debug format=png | pbf_merge_lines")
+			.await
+			.is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,309 @@
+use crate::{
+	helpers::DecodedTileCache,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{ensure, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::{mem::take, sync::Arc};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{
+	math::{area_ring, length_line, length_multi_line},
+	vector_tile::VectorTileFeature,
+	Geometry,
+};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Removes vector tile features that carry no information: features without properties, lines with zero length and polygons smaller than the tile's resolution. Optionally, also removes small lines and polygons. Use it as a cleanup pass after filtering.
+struct Args {
+	/// If set, features without properties are kept.
+	keep_without_properties: bool,
+
+	/// Removes polygons with an area below this value, in square pixels of a 256x256 pixel tile.
+	min_area: Option<f32>,
+
+	/// Removes lines shorter than this value, in pixels of a 256x256 pixel tile.
+	min_length: Option<f32>,
+
+	/// Applies `min_area` and `min_length` only up to this zoom level.
+	max_zoom: Option<u8>,
+}
+
+#[derive(Debug)]
+struct Runner {
+	keep_without_properties: bool,
+	min_area: f64,
+	min_length: f64,
+	max_zoom: Option<u8>,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+
+		let use_thresholds = self.max_zoom.is_none_or(|z| coord.z <= z);
+		for layer in tile.layers.iter_mut() {
+			// thresholds are defined for 256 pixels, but layers use their own extent
+			let scale = layer.extent as f64 / 256.0;
+			let (min_area, min_length) = if use_thresholds {
+				(self.min_area * scale * scale, self.min_length * scale)
+			} else {
+				(0.0, 0.0)
+			};
+
+			let mut features = Vec::new();
+			for feature in take(&mut layer.features) {
+				if self.is_kept(&feature, min_area, min_length)? {
+					features.push(feature);
+				}
+			}
+			layer.features = features;
+		}
+		tile.layers.retain(|layer| !layer.features.is_empty());
+
+		if tile.layers.is_empty() {
+			return Ok(None);
+		}
+		Ok(Some(self.tile_cache.encode(coord, tile)?))
+	}
+
+	/// `min_area` and `min_length` are in units of the layer's extent.
+	fn is_kept(&self, feature: &VectorTileFeature, min_area: f64, min_length: f64) -> Result<bool> {
+		if !self.keep_without_properties && feature.tag_ids.is_empty() {
+			return Ok(false);
+		}
+
+		Ok(match feature.to_geometry()? {
+			Geometry::Point(_) => true,
+			Geometry::MultiPoint(g) => !g.0.is_empty(),
+			Geometry::LineString(g) => is_long_enough(length_line(&g.0), min_length),
+			Geometry::MultiLineString(g) => is_long_enough(length_multi_line(&g.0), min_length),
+			Geometry::Polygon(g) => is_large_enough(&[g.0], min_area),
+			Geometry::MultiPolygon(g) => is_large_enough(&g.0, min_area),
+		})
+	}
+}
+
+fn is_long_enough(length: f64, min_length: f64) -> bool {
+	length > 0.0 && length >= min_length
+}
+
+/// Polygons smaller than one square unit are not visible at the tile's resolution.
+fn is_large_enough(polygons: &[Vec<Vec<[f64; 2]>>], min_area: f64) -> bool {
+	// outer rings have a positive area, holes a negative one; `area_ring` returns twice the area
+	let area: f64 = polygons.iter().flatten().map(|ring| area_ring(ring) / 2.0).sum();
+	area >= 1.0 && area >= min_area
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let runner = Arc::new(Runner {
+				keep_without_properties: args.keep_without_properties,
+				min_area: args.min_area.unwrap_or(0.0) as f64,
+				min_length: args.min_length.unwrap_or(0.0) as f64,
+				max_zoom: args.max_zoom,
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+			let tilejson = source.get_tilejson().clone();
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			self.runner.run(coord, blob)?
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_prune"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{
+		vector_tile::{VectorTile, VectorTileLayer},
+		GeoFeature, GeoProperties,
+	};
+
+	fn make_tile() -> Result<Blob> {
+		let feature = |geometry: Geometry, name: Option<&str>| {
+			let mut feature = GeoFeature::new(geometry);
+			if let Some(name) = name {
+				feature.properties = GeoProperties::from(vec![("name", name)]);
+			}
+			feature
+		};
+		let square = |size: i64| vec![vec![[0, 0], [size, 0], [size, size], [0, size], [0, 0]]];
+
+		let features = vec![
+			feature(Geometry::new_point([1, 2]), Some("point")),
+			feature(Geometry::new_point([1, 2]), None),
+			feature(Geometry::new_line_string(vec![[0, 0], [100, 0]]), Some("long line")),
+			feature(Geometry::new_line_string(vec![[5, 5], [5, 5]]), Some("zero line")),
+			feature(Geometry::new_line_string(vec![[0, 0], [10, 0]]), Some("short line")),
+			feature(Geometry::new_polygon(square(100)), Some("large polygon")),
+			feature(Geometry::new_polygon(square(10)), Some("small polygon")),
+		];
+		let layer = VectorTileLayer::from_features(String::from("test"), features, 4096, 2)?;
+		VectorTile::new(vec![layer]).to_blob()
+	}
+
+	fn run(runner: &Runner, level: u8) -> Result<Vec<String>> {
+		let Some(blob) = runner.run(&TileCoord3::new(0, 0, level)?, make_tile()?)? else {
+			return Ok(vec![]);
+		};
+		let tile = VectorTile::from_blob(&blob)?;
+		let layer = &tile.layers[0];
+		layer
+			.features
+			.iter()
+			.map(|f| {
+				let properties = f.decode_properties(layer)?;
+				Ok(properties.get("name").map_or(String::from("-"), |v| v.to_string()))
+			})
+			.collect()
+	}
+
+	fn runner(min_area: f64, min_length: f64, max_zoom: Option<u8>) -> Runner {
+		Runner {
+			keep_without_properties: false,
+			min_area,
+			min_length,
+			max_zoom,
+			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
+		}
+	}
+
+	#[test]
+	fn removes_empty_and_degenerate_features() -> Result<()> {
+		assert_eq!(
+			run(&runner(0.0, 0.0, None), 0)?,
+			["point", "long line", "short line", "large polygon", "small polygon"]
+		);
+
+		let mut keep = runner(0.0, 0.0, None);
+		keep.keep_without_properties = true;
+		assert_eq!(run(&keep, 0)?.len(), 6);
+		Ok(())
+	}
+
+	#[test]
+	fn removes_small_features() -> Result<()> {
+		// with an extent of 4096, one pixel is 16 units
+		let pruned = ["point", "long line", "large polygon"];
+		assert_eq!(run(&runner(1.0, 1.0, None), 5)?, pruned);
+		assert_eq!(run(&runner(1.0, 1.0, Some(5)), 5)?, pruned);
+		assert_eq!(run(&runner(1.0, 1.0, Some(4)), 5)?.len(), 5);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl("from_container filename=dummy | pbf_prune min_area=4 min_length=2 max_zoom=10")
+			.await?;
+
+		let blob = operation.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?.unwrap();
+		assert_eq!(VectorTile::from_blob(&blob)?.layers[0].features.len(), 1);
+
+		let tiles = operation
+			.get_tile_stream(TileBBox::new(2, 0, 0, 3, 3)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 16);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn requires_vector_tiles() {
+		let factory = PipelineFactory::new_dummy();
+		assert!(factory
+			.operation_from_vpl("from_debug format=png | pbf_prune")
+			.await
+			.is_err());
+	}
+}
@@ -0,0 +1,305 @@
+use crate::{
+	helpers::DecodedTileCache,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{GeoProperties, GeoValue};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Rounds numeric properties of vector tile features to a precision, or maps them into named buckets, e.g. population to a size class. Fewer distinct values shrink the value tables of the tiles. Non-numeric values are not changed.
+struct Args {
+	/// Names of the properties to quantize, e.g. `properties=["population","height"]`.
+	properties: Vec<String>,
+
+	/// Name of the layer to update. If unset, all layers are updated.
+	layer: Option<String>,
+
+	/// Rounds values to a multiple of this value, e.g. `precision=0.1` or `precision=1000`.
+	precision: Option<f64>,
+
+	/// Replaces values by the name of their bucket. Each bucket is defined as "name:limit" and contains the values below the limit, the limits must be ascending. The last bucket may omit its limit to contain all remaining values. For example: `buckets=["village:10000","town:100000","city"]`.
+	buckets: Vec<String>,
+}
+
+#[derive(Debug)]
+enum Quantization {
+	Round(f64),
+	/// Bucket names with their exclusive upper limits, in ascending order.
+	Buckets(Vec<(String, Option<f64>)>),
+}
+
+impl Quantization {
+	fn from_args(args: &Args) -> Result<Quantization> {
+		match (args.precision, args.buckets.is_empty()) {
+			(Some(precision), true) => {
+				ensure!(precision > 0.0, "precision must be positive, but is {precision}");
+				Ok(Quantization::Round(precision))
+			}
+			(None, false) => {
+				let mut buckets: Vec<(String, Option<f64>)> = Vec::new();
+				for (index, bucket) in args.buckets.iter().enumerate() {
+					let Some((name, limit)) = bucket.rsplit_once(':') else {
+						ensure!(
+							index == args.buckets.len() - 1,
+							"only the last bucket may omit its limit, but \"{bucket}\" has none"
+						);
+						buckets.push((bucket.to_string(), None));
+						continue;
+					};
+					let limit = limit
+						.trim()
+						.parse::<f64>()
+						.with_context(|| format!("invalid limit in bucket \"{bucket}\""))?;
+					if let Some((_, Some(previous))) = buckets.last() {
+						ensure!(
+							limit > *previous,
+							"bucket limits must be ascending, but \"{bucket}\" is not"
+						);
+					}
+					buckets.push((name.to_string(), Some(limit)));
+				}
+				Ok(Quantization::Buckets(buckets))
+			}
+			(Some(_), false) => bail!("use either 'precision' or 'buckets', but not both"),
+			(None, true) => bail!("either 'precision' or 'buckets' is required"),
+		}
+	}
+
+	fn apply(&self, value: &GeoValue) -> Option<GeoValue> {
+		let number = match value {
+			GeoValue::Double(v) => *v,
+			GeoValue::Float(v) => *v as f64,
+			GeoValue::Int(v) => *v as f64,
+			GeoValue::UInt(v) => *v as f64,
+			_ => return None,
+		};
+
+		match self {
+			Quantization::Round(precision) => {
+				let rounded = (number / precision).round() * precision;
+				Some(if rounded.fract() != 0.0 || rounded.abs() > i64::MAX as f64 {
+					// rounding errors, e.g. 0.30000000000000004, would create new distinct values
+					GeoValue::Double(format!("{rounded:.12}").parse().unwrap())
+				} else if rounded < 0.0 {
+					GeoValue::Int(rounded as i64)
+				} else {
+					GeoValue::UInt(rounded as u64)
+				})
+			}
+			Quantization::Buckets(buckets) => buckets
+				.iter()
+				.find(|(_, limit)| limit.is_none_or(|limit| number < limit))
+				.map(|(name, _)| GeoValue::from(name)),
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Runner {
+	properties: Vec<String>,
+	layer: Option<String>,
+	quantization: Quantization,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+
+		for layer in tile.layers.iter_mut() {
+			if self.layer.as_ref().is_some_and(|name| name != &layer.name) {
+				continue;
+			}
+			layer.map_properties(|properties| self.quantize(properties))?;
+		}
+
+		Ok(Some(self.tile_cache.encode(coord, tile)?))
+	}
+
+	fn quantize(&self, mut properties: GeoProperties) -> GeoProperties {
+		for key in self.properties.iter() {
+			if let Some(value) = properties.get(key).and_then(|value| self.quantization.apply(value)) {
+				properties.insert(key.clone(), value);
+			}
+		}
+		properties
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			ensure!(!args.properties.is_empty(), "'properties' must not be empty");
+			let quantization = Quantization::from_args(&args)?;
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let runner = Arc::new(Runner {
+				properties: args.properties,
+				layer: args.layer,
+				quantization,
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+			let tilejson = source.get_tilejson().clone();
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			self.runner.run(coord, blob)?
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_quantize_properties"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::vector_tile::VectorTile;
+
+	fn quantization(vpl: &str) -> Result<Quantization> {
+		let args = Args::from_vpl_node(&VPLNode::from_str(&format!(
+			"pbf_quantize_properties properties=a {vpl}"
+		))?)?;
+		Quantization::from_args(&args)
+	}
+
+	#[test]
+	fn round() -> Result<()> {
+		let q = quantization("precision=0.1")?;
+		assert_eq!(q.apply(&GeoValue::Double(0.34)), Some(GeoValue::Double(0.3)));
+		assert_eq!(q.apply(&GeoValue::Float(2.96)), Some(GeoValue::UInt(3)));
+		assert_eq!(q.apply(&GeoValue::from("text")), None);
+
+		let q = quantization("precision=1000")?;
+		assert_eq!(q.apply(&GeoValue::UInt(123456)), Some(GeoValue::UInt(123000)));
+		assert_eq!(q.apply(&GeoValue::Int(-2600)), Some(GeoValue::Int(-3000)));
+		Ok(())
+	}
+
+	#[test]
+	fn buckets() -> Result<()> {
+		let q = quantization(r#"buckets=["village:10000","town:100000","city"]"#)?;
+		assert_eq!(q.apply(&GeoValue::UInt(500)), Some(GeoValue::from("village")));
+		assert_eq!(q.apply(&GeoValue::UInt(10000)), Some(GeoValue::from("town")));
+		assert_eq!(q.apply(&GeoValue::Double(3.5e6)), Some(GeoValue::from("city")));
+
+		let q = quantization(r#"buckets=["small:10","medium:100"]"#)?;
+		assert_eq!(q.apply(&GeoValue::UInt(1000)), None);
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_args() {
+		for vpl in [
+			"",
+			"precision=0",
+			r#"precision=1 buckets=["a:1"]"#,
+			r#"buckets=["a","b:10"]"#,
+			r#"buckets=["a:10","b:5"]"#,
+			r#"buckets=["a:x"]"#,
+		] {
+			assert!(quantization(vpl).is_err(), "{vpl}");
+		}
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl(
+				r#"from_container filename=dummy | pbf_quantize_properties properties=["x","y"] buckets=["low:2","high"]"#,
+			)
+			.await?;
+
+		let blob = operation.get_tile_data(&TileCoord3::new(3, 1, 4)?).await?.unwrap();
+		let tile = VectorTile::from_blob(&blob)?;
+		let layer = &tile.layers[0];
+		let properties = layer.features[0].decode_properties(layer)?;
+		assert_eq!(properties.get("x"), Some(&GeoValue::from("high")));
+		assert_eq!(properties.get("y"), Some(&GeoValue::from("low")));
+		assert_eq!(properties.get("z"), Some(&GeoValue::UInt(4)));
+		Ok(())
+	}
+}
@@ -0,0 +1,443 @@
+use crate::{
+	helpers::DecodedTileCache,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use log::warn;
+use std::sync::Arc;
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{
+	math::{area_polygon, length_line, simplify_line, simplify_ring},
+	vector_tile::{VectorTile, VectorTileFeature, VectorTileLayer},
+	Geometry,
+};
+
+/// Smallest simplification tolerance, in pixels of a 256x256 pixel tile.
+const MIN_TOLERANCE: f64 = 0.25;
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Limits the size of vector tiles. Tiles above the budget are reduced step by step until they fit: first the `drop_layers` are removed one by one, then the geometries are simplified with an increasing tolerance, and finally the smallest features are removed. Tiles that fit are not changed.
+struct Args {
+	/// Maximum size of a tile in bytes, before compression, e.g. `max_size=500000`.
+	max_size: Option<u32>,
+
+	/// Layers that may be removed, lowest priority first, e.g. `drop_layers=["pois","buildings"]`.
+	drop_layers: Vec<String>,
+
+	/// Maximum simplification tolerance in pixels of a 256x256 pixel tile. Starting at 0.25 pixels, the tolerance is doubled until it reaches this value. Defaults to 0, i.e. no simplification.
+	max_tolerance: Option<f32>,
+
+	/// If set, the smallest features are removed as the last step: lines and polygons by length and area, then points. Only with this option, every tile is guaranteed to fit.
+	drop_features: bool,
+}
+
+#[derive(Debug)]
+struct Runner {
+	max_size: u64,
+	drop_layers: Vec<String>,
+	/// tolerances of the simplification steps, in pixels of a 256x256 pixel tile
+	tolerances: Vec<f64>,
+	drop_features: bool,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Option<Blob>> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		if blob.len() <= self.max_size {
+			return Ok(Some(blob));
+		}
+
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
+		let mut blob = blob;
+		for name in self.drop_layers.iter() {
+			tile.layers.retain(|layer| &layer.name != name);
+			blob = self.encode(&tile)?;
+			if blob.len() <= self.max_size {
+				return Ok(Some(blob));
+			}
+		}
+
+		// always simplify the unsimplified geometries, so errors don't add up
+		let unsimplified = blob.clone();
+		for tolerance in self.tolerances.iter() {
+			let mut tile = VectorTile::from_blob(&unsimplified)?;
+			for layer in tile.layers.iter_mut() {
+				simplify_layer(layer, tolerance * layer.extent as f64 / 256.0)?;
+			}
+			blob = self.encode(&tile)?;
+			if blob.len() <= self.max_size {
+				return Ok(Some(blob));
+			}
+		}
+
+		if self.drop_features {
+			return self.drop_smallest_features(&blob);
+		}
+
+		warn!(
+			"a tile of {} bytes exceeds the size budget of {} bytes",
+			blob.len(),
+			self.max_size
+		);
+		Ok(Some(blob))
+	}
+
+	fn encode(&self, tile: &VectorTile) -> Result<Blob> {
+		tile.to_blob().context("Failed to convert VectorTile to Blob")
+	}
+
+	/// Removes as few of the smallest features as needed to fit. Returns `None` if nothing is left.
+	fn drop_smallest_features(&self, blob: &Blob) -> Result<Option<Blob>> {
+		let tile = VectorTile::from_blob(blob)?;
+
+		// (size, layer index, feature index), sorted by size
+		let mut features = Vec::new();
+		for (layer_index, layer) in tile.layers.iter().enumerate() {
+			for (feature_index, feature) in layer.features.iter().enumerate() {
+				features.push((get_size(feature)?, layer_index, feature_index));
+			}
+		}
+		features.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+		// returns the tile without the `count` smallest features, if it fits
+		let remove = |count: usize| -> Result<Option<Blob>> {
+			let mut tile = VectorTile::from_blob(blob)?;
+			let mut removed: Vec<Vec<bool>> = tile
+				.layers
+				.iter()
+				.map(|layer| vec![false; layer.features.len()])
+				.collect();
+			for (_, layer_index, feature_index) in features.iter().take(count) {
+				removed[*layer_index][*feature_index] = true;
+			}
+			for (layer, removed) in tile.layers.iter_mut().zip(removed.iter()) {
+				let features = std::mem::take(&mut layer.features);
+				layer.features = features
+					.into_iter()
+					.zip(removed.iter())
+					.filter_map(|(feature, removed)| (!removed).then_some(feature))
+					.collect();
+			}
+			tile.layers.retain(|layer| !layer.features.is_empty());
+			let blob = self.encode(&tile)?;
+			Ok((blob.len() <= self.max_size).then_some(blob))
+		};
+
+		// binary search for the smallest number of removed features
+		let (mut fails, mut fits) = (0, features.len());
+		let mut result = None;
+		while fits - fails > 1 {
+			let count = (fails + fits) / 2;
+			match remove(count)? {
+				Some(blob) => {
+					fits = count;
+					result = Some(blob);
+				}
+				None => fails = count,
+			}
+		}
+		Ok(result)
+	}
+}
+
+/// Returns the size of a feature, used to remove the smallest features first: the length of lines
+/// and the square root of the area of polygons. Points are ranked above all lines and polygons.
+fn get_size(feature: &VectorTileFeature) -> Result<f64> {
+	Ok(match feature.to_geometry()? {
+		Geometry::LineString(g) => length_line(&g.0),
+		Geometry::MultiLineString(g) => g.0.iter().map(length_line).sum(),
+		Geometry::Polygon(g) => area_polygon(&g.0).abs().sqrt(),
+		Geometry::MultiPolygon(g) => g.0.iter().map(area_polygon).sum::<f64>().abs().sqrt(),
+		_ => f64::INFINITY,
+	})
+}
+
+/// Simplifies the lines and polygons of a layer and removes polygons that collapse.
+/// `tolerance` is in units of the layer's extent.
+fn simplify_layer(layer: &mut VectorTileLayer, tolerance: f64) -> Result<()> {
+	let mut features = Vec::new();
+	for feature in std::mem::take(&mut layer.features) {
+		let geometry = match feature.to_geometry()? {
+			Geometry::MultiLineString(g) => {
+				Geometry::new_multi_line_string(g.0.iter().map(|line| simplify_line(line, tolerance)).collect())
+			}
+			Geometry::MultiPolygon(g) => {
+				let mut polygons = Vec::new();
+				for polygon in g.0.iter() {
+					let rings: Vec<_> = polygon.iter().map(|ring| simplify_ring(ring, tolerance)).collect();
+					// polygons whose outer ring collapses are removed, collapsed holes are removed as well
+					if rings.first().is_some_and(|ring| !ring.is_empty()) {
+						polygons.push(rings.into_iter().filter(|ring| !ring.is_empty()).collect::<Vec<_>>());
+					}
+				}
+				if polygons.is_empty() {
+					continue;
+				}
+				Geometry::new_multi_polygon(polygons)
+			}
+			_ => {
+				features.push(feature);
+				continue;
+			}
+		};
+		features.push(VectorTileFeature::from_geometry(feature.id, feature.tag_ids, geometry)?);
+	}
+	layer.features = features;
+	Ok(())
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			let max_size = args.max_size.context("'max_size' is required")?;
+
+			let mut parameters = source.get_parameters().clone();
+			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+			let max_tolerance = args.max_tolerance.unwrap_or(0.0) as f64;
+			let mut tolerances = Vec::new();
+			let mut tolerance = MIN_TOLERANCE;
+			while tolerance <= max_tolerance {
+				tolerances.push(tolerance);
+				tolerance *= 2.0;
+			}
+
+			let runner = Arc::new(Runner {
+				max_size: max_size as u64,
+				drop_layers: args.drop_layers,
+				tolerances,
+				drop_features: args.drop_features,
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_compression = TileCompression::Uncompressed;
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				tilejson: source.get_tilejson().clone(),
+				source,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			self.runner.run(coord, blob)?
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"pbf_size_budget"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{GeoFeature, GeoProperties};
+
+	fn make_tile() -> Result<Blob> {
+		let feature = |geometry: Geometry| {
+			let mut feature = GeoFeature::new(geometry);
+			feature.properties = GeoProperties::from(vec![("name", "test")]);
+			feature
+		};
+
+		// a long line with a lot of small wiggles
+		let line: Vec<[f64; 2]> = (0..1000).map(|i| [i as f64 * 4.0, (i % 2) as f64]).collect();
+		let roads = vec![feature(Geometry::new_line_string(line))];
+
+		// squares of increasing size
+		let water = (1..=20)
+			.map(|i| {
+				let (x, s) = (i as f64 * 100.0, i as f64 * 4.0);
+				feature(Geometry::new_polygon(vec![vec![
+					[x, 0.0],
+					[x + s, 0.0],
+					[x + s, s],
+					[x, s],
+					[x, 0.0],
+				]]))
+			})
+			.collect();
+
+		let pois = (0..200)
+			.map(|i| feature(Geometry::new_point([i * 10, i * 10])))
+			.collect();
+
+		VectorTile::new(vec![
+			VectorTileLayer::from_features(String::from("roads"), roads, 4096, 2)?,
+			VectorTileLayer::from_features(String::from("water"), water, 4096, 2)?,
+			VectorTileLayer::from_features(String::from("pois"), pois, 4096, 2)?,
+		])
+		.to_blob()
+	}
+
+	fn make_runner(max_size: u64, drop_layers: &[&str], tolerances: &[f64], drop_features: bool) -> Runner {
+		Runner {
+			max_size,
+			drop_layers: drop_layers.iter().map(|s| s.to_string()).collect(),
+			tolerances: tolerances.to_vec(),
+			drop_features,
+			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
+		}
+	}
+
+	fn coord() -> TileCoord3 {
+		TileCoord3::new(0, 0, 0).unwrap()
+	}
+
+	fn get_layers(blob: &Blob) -> Result<Vec<(String, usize)>> {
+		let tile = VectorTile::from_blob(blob)?;
+		Ok(tile
+			.layers
+			.iter()
+			.map(|layer| (layer.name.clone(), layer.features.len()))
+			.collect())
+	}
+
+	#[test]
+	fn fitting_tiles_are_unchanged() -> Result<()> {
+		let blob = make_tile()?;
+		let result = make_runner(blob.len(), &["pois"], &[], true).run(&coord(), blob.clone())?;
+		assert_eq!(result, Some(blob));
+		Ok(())
+	}
+
+	#[test]
+	fn drop_layers() -> Result<()> {
+		let blob = make_tile()?;
+		let runner = make_runner(blob.len() - 100, &["unknown", "pois", "water"], &[], false);
+		let result = runner.run(&coord(), blob)?.unwrap();
+		assert!(result.len() <= runner.max_size);
+		assert_eq!(
+			get_layers(&result)?,
+			[("roads".to_string(), 1), ("water".to_string(), 20)]
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn simplify() -> Result<()> {
+		let blob = make_tile()?;
+		let runner = make_runner(blob.len() - 1000, &[], &[0.25, 0.5], false);
+		let result = runner.run(&coord(), blob)?.unwrap();
+		assert!(result.len() <= runner.max_size);
+		assert_eq!(get_layers(&result)?.len(), 3);
+
+		let tile = VectorTile::from_blob(&result)?;
+		match tile.layers[0].features[0].to_geometry()? {
+			Geometry::MultiLineString(g) => assert_eq!(g.0[0].len(), 2),
+			_ => panic!("not a line"),
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn drop_features() -> Result<()> {
+		let blob = make_tile()?;
+		let runner = make_runner(blob.len() - 100, &[], &[], true);
+		let result = runner.run(&coord(), blob.clone())?.unwrap();
+		assert!(result.len() <= runner.max_size);
+
+		// the smallest squares are removed first, points last
+		let tile = VectorTile::from_blob(&result)?;
+		assert_eq!(tile.layers[0].name, "roads");
+		let water = tile.layers[1].to_features()?;
+		assert!(water.len() < 20);
+		match &water[0].geometry {
+			Geometry::MultiPolygon(g) => assert_eq!(g.0[0][0][0][0], 2100.0 - water.len() as f64 * 100.0),
+			_ => panic!("not a polygon"),
+		}
+		assert_eq!(tile.layers[2].features.len(), 200);
+
+		assert_eq!(make_runner(0, &[], &[], true).run(&coord(), blob)?, None);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl(r#"from_debug format=pbf | pbf_size_budget max_size=100 drop_layers=["debug_x","debug_y"] max_tolerance=4 drop_features=true"#)
+			.await?;
+		let tile = operation.get_tile_data(&TileCoord3::new(1, 2, 3)?).await?;
+		assert!(tile.is_none_or(|blob| blob.len() <= 100));
+
+		for vpl in [
+			"from_debug format=pbf | pbf_size_budget",
+			"from_debug format=png | pbf_size_budget max_size=100",
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,341 @@
+use crate::{
+	helpers::parse_color,
+	readers::TerrainEncoding,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use imageproc::image::{DynamicImage, Pixel, Rgba};
+use std::{str::FromStr, sync::Arc};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{compress, decompress, run_blocking},
+};
+use versatiles_image::helper::{blob2image, image2blob};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Colors raster tiles with data values, e.g. elevations or population densities, using a color ramp. Use either a preset `ramp` or custom `stops`. Transparent pixels stay transparent.
+struct Args {
+	/// How values are stored in the pixels: "grey" uses the brightness from 0 to 255 (default), "mapbox" and "terrarium" decode elevations in meters from terrain-RGB tiles.
+	encoding: Option<String>,
+
+	/// Name of a preset color ramp: "elevation" for elevations in meters, or "heatmap" for values from 0 to 255.
+	ramp: Option<String>,
+
+	/// Custom color stops as "value:color", with ascending values and colors as "#rrggbb" or "#rrggbbaa". Colors are interpolated linearly between the stops, e.g. `stops=["0:#00ff00","1000:#ffffff"]`.
+	stops: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueEncoding {
+	Grey,
+	Terrain(TerrainEncoding),
+}
+
+impl ValueEncoding {
+	fn decode(&self, pixel: &Rgba<u8>) -> f64 {
+		match self {
+			ValueEncoding::Grey => pixel.to_luma()[0] as f64,
+			ValueEncoding::Terrain(encoding) => encoding.decode(pixel.to_rgb().0),
+		}
+	}
+}
+
+impl FromStr for ValueEncoding {
+	type Err = anyhow::Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		match value {
+			"grey" => Ok(ValueEncoding::Grey),
+			_ => TerrainEncoding::from_str(value)
+				.map(ValueEncoding::Terrain)
+				.with_context(|| format!("unknown encoding \"{value}\", use \"grey\", \"mapbox\" or \"terrarium\"")),
+		}
+	}
+}
+
+/// Maps values to colors by interpolating between stops.
+#[derive(Debug, PartialEq)]
+struct ColorRamp {
+	/// Values with their colors, in ascending order.
+	stops: Vec<(f64, [u8; 4])>,
+}
+
+impl ColorRamp {
+	fn from_preset(name: &str) -> Result<ColorRamp> {
+		let stops: &[&str] = match name {
+			"elevation" => &[
+				"-11000:#0a1e50",
+				"-1:#4a90c8",
+				"0:#5a9e50",
+				"500:#a8c87c",
+				"1500:#e8d8a0",
+				"3000:#a07850",
+				"5000:#ffffff",
+			],
+			"heatmap" => &["0:#0000ff00", "64:#0000ff", "128:#00ffff", "192:#ffff00", "255:#ff0000"],
+			_ => bail!("unknown color ramp \"{name}\", use \"elevation\" or \"heatmap\""),
+		};
+		ColorRamp::from_stops(stops)
+	}
+
+	fn from_stops<T: AsRef<str>>(stops: &[T]) -> Result<ColorRamp> {
+		ensure!(stops.len() >= 2, "a color ramp needs at least two stops");
+		let mut ramp = ColorRamp { stops: Vec::new() };
+		for stop in stops.iter().map(AsRef::as_ref) {
+			let Some((value, color)) = stop.rsplit_once(':') else {
+				bail!("invalid stop \"{stop}\", expected \"value:color\"");
+			};
+			let value = value
+				.trim()
+				.parse::<f64>()
+				.with_context(|| format!("invalid value in stop \"{stop}\""))?;
+			let color = parse_color(color.trim()).with_context(|| format!("invalid color in stop \"{stop}\""))?;
+			if let Some((previous, _)) = ramp.stops.last() {
+				ensure!(
+					value > *previous,
+					"stop values must be ascending, but \"{stop}\" is not"
+				);
+			}
+			ramp.stops.push((value, color));
+		}
+		Ok(ramp)
+	}
+
+	/// Returns the color of a value. Values outside of the stops get the color of the nearest stop.
+	fn get_color(&self, value: f64) -> [u8; 4] {
+		let index = self.stops.partition_point(|(v, _)| *v <= value);
+		if index == 0 {
+			return self.stops[0].1;
+		}
+		if index == self.stops.len() {
+			return self.stops[index - 1].1;
+		}
+		let (v0, c0) = self.stops[index - 1];
+		let (v1, c1) = self.stops[index];
+		let t = (value - v0) / (v1 - v0);
+		std::array::from_fn(|i| (c0[i] as f64 + (c1[i] as f64 - c0[i] as f64) * t).round() as u8)
+	}
+}
+
+#[derive(Debug)]
+struct Runner {
+	encoding: ValueEncoding,
+	ramp: ColorRamp,
+	tile_format: TileFormat,
+	tile_compression: TileCompression,
+}
+
+impl Runner {
+	fn run(&self, blob: Blob) -> Result<Blob> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut image = blob2image(&blob, self.tile_format)?.to_rgba8();
+
+		for pixel in image.pixels_mut() {
+			if pixel[3] == 0 {
+				continue;
+			}
+			let mut color = self.ramp.get_color(self.encoding.decode(pixel));
+			color[3] = ((color[3] as u16 * pixel[3] as u16) / 255) as u8;
+			*pixel = Rgba(color);
+		}
+
+		let image = match self.tile_format {
+			TileFormat::JPG => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+			_ => DynamicImage::ImageRgba8(image),
+		};
+		compress(image2blob(&image, self.tile_format)?, &self.tile_compression)
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		_factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+
+			let ramp = match (&args.ramp, args.stops.is_empty()) {
+				(Some(name), true) => ColorRamp::from_preset(name)?,
+				(None, false) => ColorRamp::from_stops(&args.stops)?,
+				(Some(_), false) => bail!("use either 'ramp' or 'stops', but not both"),
+				(None, true) => bail!("either 'ramp' or 'stops' is required"),
+			};
+			let encoding = match &args.encoding {
+				Some(encoding) => ValueEncoding::from_str(encoding)?,
+				None => ValueEncoding::Grey,
+			};
+
+			let parameters = source.get_parameters().clone();
+			ensure!(
+				matches!(
+					parameters.tile_format,
+					TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP
+				),
+				"raster_colorize is only supported for raster tiles, but the source has format {:?}",
+				parameters.tile_format
+			);
+
+			let runner = Arc::new(Runner {
+				encoding,
+				ramp,
+				tile_format: parameters.tile_format,
+				tile_compression: parameters.tile_compression,
+			});
+			let tilejson = source.get_tilejson().clone();
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.map_blob_blocking(move |blob| runner.run(blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			let runner = self.runner.clone();
+			Some(run_blocking(move || runner.run(blob)).await?)
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"raster_colorize"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_image::png;
+
+	#[test]
+	fn color_ramp() -> Result<()> {
+		let ramp = ColorRamp::from_stops(&["0:#000000", "100:#ff8000", "200:#ffffff80"])?;
+		assert_eq!(ramp.get_color(-5.0), [0, 0, 0, 255]);
+		assert_eq!(ramp.get_color(50.0), [128, 64, 0, 255]);
+		assert_eq!(ramp.get_color(100.0), [255, 128, 0, 255]);
+		assert_eq!(ramp.get_color(150.0), [255, 192, 128, 192]);
+		assert_eq!(ramp.get_color(1000.0), [255, 255, 255, 128]);
+
+		assert_eq!(ColorRamp::from_preset("elevation")?.get_color(0.0), [90, 158, 80, 255]);
+		assert_eq!(ColorRamp::from_preset("heatmap")?.get_color(0.0), [0, 0, 255, 0]);
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_ramps() {
+		assert!(ColorRamp::from_preset("rainbow").is_err());
+		for stops in [
+			vec!["0:#000000"],
+			vec!["0:#000000", "0:#ffffff"],
+			vec!["0:#000000", "x:#ffffff"],
+			vec!["0:#000000", "1:#fff"],
+			vec!["0:#000000", "1:#gggggg"],
+			vec!["0:#000000", "#ffffff"],
+		] {
+			assert!(ColorRamp::from_stops(&stops).is_err(), "{stops:?}");
+		}
+	}
+
+	#[test]
+	fn encodings() -> Result<()> {
+		let pixel = Rgba([128, 100, 0, 255]);
+		assert_eq!(ValueEncoding::from_str("terrarium")?.decode(&pixel), 100.0);
+		assert_eq!(ValueEncoding::from_str("grey")?.decode(&Rgba([7, 7, 7, 255])), 7.0);
+		assert!(ValueEncoding::from_str("cmyk").is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn operation() -> Result<()> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl(r##"from_debug format=png | raster_colorize stops=["0:#ff0000","255:#ff0000"]"##)
+			.await?;
+		assert_eq!(operation.get_parameters().tile_format, TileFormat::PNG);
+
+		let blob = operation.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?.unwrap();
+		let image = png::blob2image(&blob)?.to_rgba8();
+		assert!(image
+			.pixels()
+			.all(|p| p[3] == 0 || (p[0] == 255 && p[1] == 0 && p[2] == 0)));
+
+		let tiles = operation
+			.get_tile_stream(TileBBox::new(2, 0, 0, 3, 3)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 16);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn errors() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_debug format=pbf | raster_colorize ramp=heatmap",
+			"from_debug format=png | raster_colorize",
+			"from_debug format=png | raster_colorize ramp=heatmap stops=[\"0:#000000\",\"1:#ffffff\"]",
+			"from_debug format=png | raster_colorize ramp=heatmap encoding=cmyk",
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
@@ -0,0 +1,384 @@
+use crate::{traits::*, vpl::VPLNode, PipelineFactory};
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use imageproc::image::{DynamicImage, Rgba, RgbaImage};
+use std::{collections::HashMap, fmt::Debug};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{compress, decompress, run_blocking},
+};
+use versatiles_image::helper::{blob2image_blocking, image2blob};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Reprojects raster tiles into another tile grid, e.g. from WGS84 (EPSG:4326) to Web Mercator. Every target tile is resampled from all source tiles it overlaps.
+struct Args {
+	/// target tile grid: "webmercator", "wgs84" or "custom:west,south,east,north,columns,rows"
+	grid: String,
+}
+
+#[derive(Debug)]
+struct Operation {
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	source_grid: TileGrid,
+	grid: TileGrid,
+	/// The source zoom level used for each target zoom level.
+	source_levels: Vec<Option<u8>>,
+	tilejson: TileJSON,
+}
+
+/// Returns the width of a tile in degrees of longitude.
+fn tile_width(grid: &TileGrid, level: u8) -> f64 {
+	let extent = grid.get_extent();
+	(extent.2 - extent.0) / (grid.max_column(level) as f64 + 1.0)
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		_factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+			let grid = TileGrid::parse_str(&args.grid)?;
+
+			let source_parameters = source.get_parameters();
+			ensure!(
+				matches!(
+					source_parameters.tile_format,
+					TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP
+				),
+				"raster_reproject is only supported for raster tiles, but the source has format {:?}",
+				source_parameters.tile_format
+			);
+
+			let source_pyramid = &source_parameters.bbox_pyramid;
+			let source_grid = source_pyramid.get_grid();
+			let target_extent = grid.get_extent();
+
+			let mut pyramid = TileBBoxPyramid::new_empty_in_grid(grid);
+			let mut source_levels = vec![None; 32];
+			for level in 0..=31u8 {
+				// use the lowest source level whose tiles are not wider than the target tiles
+				let width = tile_width(&grid, level) * (1.0 + 1e-9);
+				let Some(source_level) = (0..=31u8).find(|l| tile_width(&source_grid, *l) <= width) else {
+					continue;
+				};
+				let source_bbox = source_pyramid.get_level_bbox(source_level);
+				if source_bbox.is_empty() {
+					continue;
+				}
+
+				let geo_bbox = source_bbox.as_geo_bbox().intersected(&target_extent);
+				if geo_bbox.0 >= geo_bbox.2 || geo_bbox.1 >= geo_bbox.3 {
+					continue;
+				}
+				pyramid.set_level_bbox(grid.bbox_from_geo(level, &geo_bbox)?);
+				source_levels[level as usize] = Some(source_level);
+			}
+
+			let parameters = TilesReaderParameters::new(
+				source_parameters.tile_format,
+				source_parameters.tile_compression,
+				pyramid,
+			);
+
+			let mut tilejson = source.get_tilejson().clone();
+			tilejson.set_tile_grid(&grid)?;
+			if let (Some(min), Some(max)) = (
+				parameters.bbox_pyramid.get_zoom_min(),
+				parameters.bbox_pyramid.get_zoom_max(),
+			) {
+				tilejson.set_byte("minzoom", min)?;
+				tilejson.set_byte("maxzoom", max)?;
+			}
+			tilejson.update_from_pyramid(&parameters.bbox_pyramid);
+
+			Ok(Box::new(Self {
+				parameters,
+				source,
+				source_grid,
+				grid,
+				source_levels,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+
+	/// Renders a target tile from all source tiles that it overlaps.
+	async fn reproject_tile(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		let Some(source_level) = self.source_levels[coord.z as usize] else {
+			return Ok(None);
+		};
+
+		let geo_bbox = self
+			.grid
+			.get_tile_geo_bbox(coord)
+			.intersected(&self.source_grid.get_extent());
+		if geo_bbox.0 >= geo_bbox.2 || geo_bbox.1 >= geo_bbox.3 {
+			return Ok(None);
+		}
+
+		let source_parameters = self.source.get_parameters();
+		let mut source_bbox = self.source_grid.bbox_from_geo(source_level, &geo_bbox)?;
+		source_bbox.intersect_pyramid(&source_parameters.bbox_pyramid)?;
+		if source_bbox.is_empty() {
+			return Ok(None);
+		}
+
+		let mut mosaic = Mosaic::default();
+		for (source_coord, blob) in self.source.get_tile_stream(source_bbox).await.collect().await {
+			let blob = decompress(blob, &source_parameters.tile_compression)?;
+			let image = blob2image_blocking(blob, source_parameters.tile_format)
+				.await
+				.with_context(|| format!("decoding tile {source_coord:?}"))?;
+			mosaic.add_tile(&source_coord, image.to_rgba8())?;
+		}
+		if mosaic.tiles.is_empty() {
+			return Ok(None);
+		}
+
+		let (grid, source_grid, coord) = (self.grid, self.source_grid, *coord);
+		let (format, compression) = (self.parameters.tile_format, self.parameters.tile_compression);
+
+		// resampling and encoding the image is too slow for the async runtime
+		run_blocking(move || {
+			let size = mosaic.size;
+			let mut image = RgbaImage::new(size, size);
+			let mut is_empty = true;
+			for py in 0..size {
+				for px in 0..size {
+					let [lon, lat] = grid.tile_to_geo(
+						coord.x as f64 + (px as f64 + 0.5) / size as f64,
+						coord.y as f64 + (py as f64 + 0.5) / size as f64,
+						coord.z,
+					);
+					let [x, y] = source_grid.geo_to_tile(lon, lat, source_level);
+					if let Some(pixel) = mosaic.sample(x * size as f64 - 0.5, y * size as f64 - 0.5) {
+						is_empty &= pixel[3] == 0;
+						image.put_pixel(px, py, pixel);
+					}
+				}
+			}
+			if is_empty {
+				return Ok(None);
+			}
+
+			let image = match format {
+				TileFormat::JPG => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+				_ => DynamicImage::ImageRgba8(image),
+			};
+			let blob = image2blob(&image, format)?;
+			Ok(Some(compress(blob, &compression)?))
+		})
+		.await
+	}
+}
+
+/// Source tiles of one zoom level, addressed by their pixels.
+#[derive(Default)]
+struct Mosaic {
+	tiles: HashMap<(u32, u32), RgbaImage>,
+	size: u32,
+}
+
+impl Mosaic {
+	fn add_tile(&mut self, coord: &TileCoord3, image: RgbaImage) -> Result<()> {
+		ensure!(
+			image.width() == image.height(),
+			"tile {coord:?} must be square, but is {}x{}",
+			image.width(),
+			image.height()
+		);
+		if self.tiles.is_empty() {
+			self.size = image.width();
+		}
+		ensure!(
+			image.width() == self.size,
+			"all tiles must have the same size, but tile {coord:?} has {} pixels instead of {}",
+			image.width(),
+			self.size
+		);
+		self.tiles.insert((coord.x, coord.y), image);
+		Ok(())
+	}
+
+	fn get_pixel(&self, x: i64, y: i64) -> Option<&Rgba<u8>> {
+		if x < 0 || y < 0 {
+			return None;
+		}
+		let size = self.size as i64;
+		let tile = self.tiles.get(&((x / size) as u32, (y / size) as u32))?;
+		Some(tile.get_pixel((x % size) as u32, (y % size) as u32))
+	}
+
+	/// Interpolates bilinearly between the four pixels around a position. Missing pixels are transparent.
+	fn sample(&self, x: f64, y: f64) -> Option<Rgba<u8>> {
+		if !x.is_finite() || !y.is_finite() {
+			return None;
+		}
+
+		let (x0, y0) = (x.floor(), y.floor());
+		let (dx, dy) = (x - x0, y - y0);
+		let (x0, y0) = (x0 as i64, y0 as i64);
+
+		let mut sum = [0.0f64; 4];
+		let mut found = false;
+		for (ox, oy, weight) in [
+			(0, 0, (1.0 - dx) * (1.0 - dy)),
+			(1, 0, dx * (1.0 - dy)),
+			(0, 1, (1.0 - dx) * dy),
+			(1, 1, dx * dy),
+		] {
+			if let Some(pixel) = self.get_pixel(x0 + ox, y0 + oy) {
+				found = true;
+				for (value, channel) in sum.iter_mut().zip(pixel.0) {
+					*value += channel as f64 * weight;
+				}
+			}
+		}
+
+		found.then(|| Rgba(sum.map(|v| v.round().clamp(0.0, 255.0) as u8)))
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		if !self.parameters.bbox_pyramid.contains_coord(coord) {
+			return Ok(None);
+		}
+		self.reproject_tile(coord).await
+	}
+
+	async fn get_tile_stream(&self, mut bbox: TileBBox) -> TileStream {
+		if bbox.intersect_pyramid(&self.parameters.bbox_pyramid).is_err() {
+			return TileStream::new_empty();
+		}
+
+		let coords: Vec<TileCoord3> = bbox.iter_coords().collect();
+		TileStream::from_coord_vec_async(coords, move |coord| async move {
+			match self.reproject_tile(&coord).await {
+				Ok(blob) => blob.map(|blob| (coord, blob)),
+				Err(err) => {
+					log::warn!("failed to reproject tile {coord:?}: {err:?}");
+					None
+				}
+			}
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"raster_reproject"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_image::png;
+
+	async fn get_operation(vpl: &str) -> Result<Box<dyn OperationTrait>> {
+		PipelineFactory::new_dummy().operation_from_vpl(vpl).await
+	}
+
+	#[tokio::test]
+	async fn web_mercator_to_wgs84() -> Result<()> {
+		let operation =
+			get_operation("from_debug format=png | filter_zoom max=3 | raster_reproject grid=\"wgs84\"").await?;
+
+		let parameters = operation.get_parameters();
+		assert_eq!(parameters.bbox_pyramid.get_grid(), TileGrid::Wgs84);
+		assert_eq!(parameters.tile_format, TileFormat::PNG);
+		assert_eq!(
+			format!("{:?}", parameters.bbox_pyramid),
+			"[0: [0,0,1,0] (2), 1: [0,0,3,1] (8), 2: [0,0,7,3] (32)]"
+		);
+		assert_eq!(operation.get_tilejson().get_tile_grid()?, TileGrid::Wgs84);
+		assert_eq!(operation.get_tilejson().values.get_byte("minzoom"), Some(0));
+		assert_eq!(operation.get_tilejson().values.get_byte("maxzoom"), Some(2));
+
+		// the eastern hemisphere is mosaicked from two source tiles at level 1
+		let blob = operation.get_tile_data(&TileCoord3::new(1, 0, 0)?).await?.unwrap();
+		let image = png::blob2image(&blob)?.to_rgba8();
+		assert_eq!(image.dimensions(), (512, 512));
+		// the poles are outside of Web Mercator
+		assert_eq!(image.get_pixel(256, 0)[3], 0);
+		assert_eq!(image.get_pixel(256, 256)[3], 255);
+
+		assert!(operation.get_tile_data(&TileCoord3::new(0, 0, 3)?).await?.is_none());
+
+		let tiles = operation
+			.get_tile_stream(TileBBox::new_full_in_grid(TileGrid::Wgs84, 1)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 8);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn wgs84_to_web_mercator() -> Result<()> {
+		let operation = get_operation(
+			"from_debug format=png | filter_zoom max=3 | raster_reproject grid=\"wgs84\" | raster_reproject grid=\"webmercator\"",
+		)
+		.await?;
+
+		let parameters = operation.get_parameters();
+		assert_eq!(parameters.bbox_pyramid.get_grid(), TileGrid::WebMercator);
+		assert_eq!(parameters.bbox_pyramid.get_zoom_min(), Some(0));
+		assert_eq!(parameters.bbox_pyramid.get_zoom_max(), Some(3));
+		assert_eq!(operation.get_tilejson().get_tile_grid()?, TileGrid::WebMercator);
+
+		let blob = operation.get_tile_data(&TileCoord3::new(1, 1, 2)?).await?.unwrap();
+		let image = png::blob2image(&blob)?.to_rgba8();
+		assert_eq!(image.dimensions(), (512, 512));
+		assert!(image.pixels().all(|p| p[3] == 255));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn errors() {
+		assert!(get_operation("from_debug format=pbf | raster_reproject grid=\"wgs84\"")
+			.await
+			.is_err());
+		assert!(get_operation("from_debug format=png | raster_reproject grid=\"mars\"")
+			.await
+			.is_err());
+	}
+}
@@ -0,0 +1,356 @@
+use crate::{
+	helpers::parse_color,
+	operations::from_debug::FONT,
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use ab_glyph::PxScale;
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use imageproc::{
+	drawing::{draw_text_mut, text_size},
+	image::{imageops::overlay, DynamicImage, GrayImage, Luma, Rgba, RgbaImage},
+};
+use std::{fs, str::FromStr, sync::Arc};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{compress, decompress, run_blocking},
+};
+use versatiles_image::helper::{blob2image, image2blob};
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Stamps a watermark or an attribution onto raster tiles. The stamp is either a PNG image or a text.
+struct Args {
+	/// Path to a PNG image used as stamp, e.g. `filename="logo.png"`.
+	filename: Option<String>,
+
+	/// Text used as stamp, e.g. `text="© Example"`. Use either `filename` or `text`.
+	text: Option<String>,
+
+	/// Font size of the text in pixels. Defaults to 16.
+	font_size: Option<f32>,
+
+	/// Color of the text as "#rrggbb" or "#rrggbbaa". Defaults to "#000000".
+	color: Option<String>,
+
+	/// Corner of the tile: "top-left", "top-right", "bottom-left" or "bottom-right" (default).
+	position: Option<String>,
+
+	/// Distance between the stamp and the edges of the tile in pixels. Defaults to 8.
+	margin: Option<u32>,
+
+	/// Opacity of the stamp, from 0 to 1. Defaults to 1.
+	opacity: Option<f32>,
+
+	/// Stamps only tiles of this zoom level or higher.
+	min_zoom: Option<u8>,
+
+	/// Stamps only tiles of this zoom level or lower.
+	max_zoom: Option<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Position {
+	TopLeft,
+	TopRight,
+	BottomLeft,
+	BottomRight,
+}
+
+impl FromStr for Position {
+	type Err = anyhow::Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		Ok(match value {
+			"top-left" => Position::TopLeft,
+			"top-right" => Position::TopRight,
+			"bottom-left" => Position::BottomLeft,
+			"bottom-right" => Position::BottomRight,
+			_ => {
+				bail!("unknown position \"{value}\", use \"top-left\", \"top-right\", \"bottom-left\" or \"bottom-right\"")
+			}
+		})
+	}
+}
+
+/// Renders a text into an image with a transparent background.
+fn render_text(text: &str, font_size: f32, color: [u8; 4]) -> RgbaImage {
+	let scale = PxScale::from(font_size);
+	let (width, height) = text_size(scale, &*FONT, text);
+	let mut coverage = GrayImage::new(width.max(1), height.max(1));
+	draw_text_mut(&mut coverage, Luma([255]), 0, 0, scale, &*FONT, text);
+
+	RgbaImage::from_fn(coverage.width(), coverage.height(), |x, y| {
+		let alpha = coverage.get_pixel(x, y)[0] as u16 * color[3] as u16 / 255;
+		Rgba([color[0], color[1], color[2], alpha as u8])
+	})
+}
+
+#[derive(Debug)]
+struct Runner {
+	stamp: RgbaImage,
+	position: Position,
+	margin: u32,
+	min_zoom: u8,
+	max_zoom: u8,
+	tile_format: TileFormat,
+	tile_compression: TileCompression,
+}
+
+impl Runner {
+	fn run(&self, level: u8, blob: Blob) -> Result<Blob> {
+		if level < self.min_zoom || level > self.max_zoom {
+			return Ok(blob);
+		}
+
+		let blob = decompress(blob, &self.tile_compression)?;
+		let mut image = blob2image(&blob, self.tile_format)?.to_rgba8();
+
+		let (x, y) = self.get_offset(image.width(), image.height());
+		overlay(&mut image, &self.stamp, x, y);
+
+		let image = match self.tile_format {
+			TileFormat::JPG => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+			_ => DynamicImage::ImageRgba8(image),
+		};
+		compress(image2blob(&image, self.tile_format)?, &self.tile_compression)
+	}
+
+	/// Returns the position of the stamp's top left pixel in a tile.
+	fn get_offset(&self, width: u32, height: u32) -> (i64, i64) {
+		let margin = self.margin as i64;
+		let left = margin;
+		let top = margin;
+		let right = width as i64 - margin - self.stamp.width() as i64;
+		let bottom = height as i64 - margin - self.stamp.height() as i64;
+		match self.position {
+			Position::TopLeft => (left, top),
+			Position::TopRight => (right, top),
+			Position::BottomLeft => (left, bottom),
+			Position::BottomRight => (right, bottom),
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+
+			let parameters = source.get_parameters().clone();
+			ensure!(
+				matches!(
+					parameters.tile_format,
+					TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP
+				),
+				"raster_watermark is only supported for raster tiles, but the source has format {:?}",
+				parameters.tile_format
+			);
+
+			let mut stamp = match (&args.filename, &args.text) {
+				(Some(filename), None) => {
+					let path = factory.resolve_path(filename);
+					let data = fs::read(&path).with_context(|| format!("Failed to read {path:?}"))?;
+					blob2image(&Blob::from(data), TileFormat::PNG)
+						.with_context(|| format!("Failed to decode PNG {path:?}"))?
+						.to_rgba8()
+				}
+				(None, Some(text)) => {
+					let color = parse_color(args.color.as_deref().unwrap_or("#000000"))?;
+					render_text(text, args.font_size.unwrap_or(16.0), color)
+				}
+				(Some(_), Some(_)) => bail!("use either 'filename' or 'text', but not both"),
+				(None, None) => bail!("either 'filename' or 'text' is required"),
+			};
+
+			let opacity = args.opacity.unwrap_or(1.0);
+			ensure!(
+				(0.0..=1.0).contains(&opacity),
+				"opacity must be between 0 and 1, but is {opacity}"
+			);
+			for pixel in stamp.pixels_mut() {
+				pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+			}
+
+			let runner = Arc::new(Runner {
+				stamp,
+				position: Position::from_str(args.position.as_deref().unwrap_or("bottom-right"))?,
+				margin: args.margin.unwrap_or(8),
+				min_zoom: args.min_zoom.unwrap_or(0),
+				max_zoom: args.max_zoom.unwrap_or(31),
+				tile_format: parameters.tile_format,
+				tile_compression: parameters.tile_compression,
+			});
+			let tilejson = source.get_tilejson().clone();
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let level = bbox.level;
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.map_blob_blocking(move |blob| runner.run(level, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			let runner = self.runner.clone();
+			let level = coord.z;
+			Some(run_blocking(move || runner.run(level, blob)).await?)
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"raster_watermark"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::TempDir;
+	use versatiles_image::png;
+
+	async fn get_image(vpl: &str, coord: TileCoord3) -> Result<RgbaImage> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory.operation_from_vpl(vpl).await?;
+		let blob = operation.get_tile_data(&coord).await?.unwrap();
+		Ok(png::blob2image(&blob)?.to_rgba8())
+	}
+
+	#[test]
+	fn text() {
+		let stamp = render_text("Test", 20.0, [255, 0, 0, 255]);
+		assert!(stamp.width() > 20 && stamp.height() > 10);
+		assert!(stamp.pixels().all(|p| p.0[0..3] == [255, 0, 0]));
+		assert!(stamp.pixels().any(|p| p[3] > 200));
+		assert!(stamp.pixels().any(|p| p[3] == 0));
+	}
+
+	#[tokio::test]
+	async fn image_stamp() -> Result<()> {
+		let dir = TempDir::new()?;
+		let path = dir.path().join("stamp.png");
+		let stamp = DynamicImage::ImageRgba8(RgbaImage::from_pixel(10, 10, Rgba([255, 0, 0, 255])));
+		fs::write(&path, image2blob(&stamp, TileFormat::PNG)?.as_slice())?;
+		let path = path.to_str().unwrap();
+
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let original = get_image("from_debug format=png", coord).await?;
+
+		let image = get_image(
+			&format!("from_debug format=png | raster_watermark filename=\"{path}\""),
+			coord,
+		)
+		.await?;
+		assert_eq!(image.get_pixel(500, 500), &Rgba([255, 0, 0, 255]));
+		assert_eq!(image.get_pixel(506, 506), original.get_pixel(506, 506));
+		assert_eq!(image.get_pixel(10, 10), original.get_pixel(10, 10));
+
+		let image = get_image(
+			&format!("from_debug format=png | raster_watermark filename=\"{path}\" position=top-left opacity=0.5"),
+			coord,
+		)
+		.await?;
+		let [r, g, _, _] = original.get_pixel(10, 10).0;
+		let [r2, g2, _, _] = image.get_pixel(10, 10).0;
+		assert!(r2.abs_diff(((r as u16 + 255) / 2) as u8) <= 1);
+		assert!(g2.abs_diff(g / 2) <= 1);
+
+		// tiles outside of the zoom range are not changed
+		let image = get_image(
+			&format!("from_debug format=png | raster_watermark filename=\"{path}\" min_zoom=4"),
+			coord,
+		)
+		.await?;
+		assert_eq!(image, original);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn text_stamp() -> Result<()> {
+		let coord = TileCoord3::new(0, 0, 0)?;
+		let original = get_image("from_debug format=png", coord).await?;
+		let image = get_image(
+			"from_debug format=png | raster_watermark text=\"© Test\" color=\"#ff0000\" font_size=30",
+			coord,
+		)
+		.await?;
+		assert_ne!(image, original);
+		assert!(image.pixels().any(|p| p[0] > 200 && p[1] < 50));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn errors() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_debug format=pbf | raster_watermark text=a",
+			"from_debug format=png | raster_watermark",
+			"from_debug format=png | raster_watermark text=a filename=a.png",
+			"from_debug format=png | raster_watermark filename=missing.png",
+			"from_debug format=png | raster_watermark text=a position=center",
+			"from_debug format=png | raster_watermark text=a opacity=2",
+			"from_debug format=png | raster_watermark text=a color=red",
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
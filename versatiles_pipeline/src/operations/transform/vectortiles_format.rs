@@ -0,0 +1,485 @@
+use crate::{
+	helpers::{project, unproject, DecodedTileCache},
+	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
+	vpl::VPLNode,
+	PipelineFactory,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use versatiles_core::{
+	json::{JsonObject, JsonValue},
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::{
+	geojson::parse_geojson_layers,
+	vector_tile::{VectorTile, VectorTileLayer},
+	GeoFeature, Geometry,
+};
+
+/// Extent of the layers of converted GeoJSON tiles, and the size of SVG tiles.
+const EXTENT: u32 = 4096;
+
+/// Default styles of SVG tiles.
+const SVG_STYLE: &str =
+	".polygon{fill:#888;fill-opacity:0.5;fill-rule:evenodd}.line{fill:none;stroke:#444;stroke-width:4}.point{fill:#c00}";
+
+#[derive(versatiles_derive::VPLDecode, Clone, Debug)]
+/// Converts vector tiles between Mapbox Vector Tiles and GeoJSON tiles, or renders them as SVG images. GeoJSON tiles are feature collections in longitude and latitude, where every feature names its layer in the member "layer". SVG tiles contain one group of shapes per layer and can't be converted back.
+struct Args {
+	/// Tile format of the result: "pbf", "geojson" or "svg".
+	format: String,
+
+	/// Layer of GeoJSON features that don't name their layer. Defaults to "geojson".
+	layer: Option<String>,
+}
+
+#[derive(Debug)]
+struct Runner {
+	source_format: TileFormat,
+	tile_format: TileFormat,
+	default_layer: String,
+	tile_compression: TileCompression,
+	tile_cache: DecodedTileCache,
+}
+
+impl Runner {
+	fn run(&self, coord: &TileCoord3, blob: Blob) -> Result<Blob> {
+		let blob = decompress(blob, &self.tile_compression)?;
+		match (self.source_format, self.tile_format) {
+			(TileFormat::PBF, TileFormat::GEOJSON) => self.pbf_to_geojson(coord, &blob),
+			(TileFormat::GEOJSON, TileFormat::PBF) => self.geojson_to_pbf(coord, &blob),
+			(TileFormat::PBF, TileFormat::SVG) => Ok(layers_to_svg(self.pbf_to_layers(coord, &blob)?)),
+			(TileFormat::GEOJSON, TileFormat::SVG) => Ok(layers_to_svg(self.geojson_to_layers(coord, &blob)?)),
+			(source, target) => bail!("can't convert {source} to {target}"),
+		}
+	}
+
+	/// Returns the features of a vector tile per layer, scaled to `EXTENT`.
+	fn pbf_to_layers(&self, coord: &TileCoord3, blob: &Blob) -> Result<Vec<(String, Vec<GeoFeature>)>> {
+		let tile = self.tile_cache.decode(coord, blob)?;
+		tile
+			.layers
+			.iter()
+			.map(|layer| {
+				let scale = EXTENT as f64 / layer.extent as f64;
+				let features = layer
+					.to_features()?
+					.into_iter()
+					.map(|mut feature| {
+						feature.geometry = feature.geometry.map_coordinates(|[x, y]| [x * scale, y * scale]);
+						feature
+					})
+					.collect();
+				Ok((layer.name.clone(), features))
+			})
+			.collect()
+	}
+
+	fn pbf_to_geojson(&self, coord: &TileCoord3, blob: &Blob) -> Result<Blob> {
+		let tile = self.tile_cache.decode(coord, blob)?;
+
+		let mut features = Vec::new();
+		for layer in tile.layers.iter() {
+			let extent = layer.extent as f64;
+			for feature in layer.features.iter() {
+				let mut feature = feature.to_feature(layer)?;
+				feature.geometry = feature
+					.geometry
+					.map_coordinates(|[x, y]| unproject(coord.x as f64 + x / extent, coord.y as f64 + y / extent, coord.z));
+				let mut json = feature.to_json();
+				json.set("layer", JsonValue::from(&layer.name));
+				features.push(JsonValue::Object(json));
+			}
+		}
+
+		let mut json = JsonObject::default();
+		json.set("type", JsonValue::from("FeatureCollection"));
+		json.set("features", JsonValue::from(features));
+		Ok(Blob::from(json.stringify()))
+	}
+
+	fn geojson_to_pbf(&self, coord: &TileCoord3, blob: &Blob) -> Result<Blob> {
+		let layers = self
+			.geojson_to_layers(coord, blob)?
+			.into_iter()
+			.map(|(name, features)| {
+				let features = features
+					.into_iter()
+					.map(|feature| GeoFeature {
+						// vector tiles only support integer ids
+						id: feature.id.filter(|id| id.as_u64().is_ok()),
+						..feature
+					})
+					.collect();
+				VectorTileLayer::from_features(name, features, EXTENT, 2)
+			})
+			.collect::<Result<Vec<_>>>()?;
+
+		self.tile_cache.encode(coord, VectorTile::new(layers))
+	}
+
+	/// Returns the features of a GeoJSON tile per layer, projected into the tile and scaled to `EXTENT`.
+	fn geojson_to_layers(&self, coord: &TileCoord3, blob: &Blob) -> Result<Vec<(String, Vec<GeoFeature>)>> {
+		let json = std::str::from_utf8(blob.as_slice()).context("GeoJSON tile is not valid UTF-8")?;
+		let layers = parse_geojson_layers(json, &self.default_layer)
+			.with_context(|| format!("Failed to parse GeoJSON tile {coord:?}"))?;
+
+		let to_tile = |[lon, lat]: [f64; 2]| {
+			let (x, y) = project(lat, lon, coord.z);
+			[
+				(x - coord.x as f64) * EXTENT as f64,
+				(y - coord.y as f64) * EXTENT as f64,
+			]
+		};
+
+		Ok(layers
+			.into_iter()
+			.map(|(name, features)| {
+				let features = features
+					.into_iter()
+					.map(|mut feature| {
+						feature.geometry = feature.geometry.map_coordinates(to_tile);
+						feature
+					})
+					.collect();
+				(name, features)
+			})
+			.collect())
+	}
+}
+
+/// Renders layers in tile coordinates as an SVG image of `EXTENT` × `EXTENT` units.
+fn layers_to_svg(layers: Vec<(String, Vec<GeoFeature>)>) -> Blob {
+	let mut svg =
+		format!("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {EXTENT} {EXTENT}\"><style>{SVG_STYLE}</style>");
+	for (name, features) in layers {
+		svg += &format!("<g id=\"{}\">", escape_xml(&name));
+		for feature in features {
+			match feature.geometry.into_multi() {
+				Geometry::MultiPoint(g) => {
+					for [x, y] in g.0 {
+						svg += &format!(
+							"<circle class=\"point\" cx=\"{}\" cy=\"{}\" r=\"8\"/>",
+							round(x),
+							round(y)
+						);
+					}
+				}
+				Geometry::MultiLineString(g) => {
+					svg += &format!("<path class=\"line\" d=\"{}\"/>", get_path(g.0.iter(), false));
+				}
+				Geometry::MultiPolygon(g) => {
+					svg += &format!(
+						"<path class=\"polygon\" d=\"{}\"/>",
+						get_path(g.0.iter().flatten(), true)
+					);
+				}
+				_ => unreachable!("into_multi returns multi geometries"),
+			}
+		}
+		svg += "</g>";
+	}
+	svg += "</svg>";
+	Blob::from(svg)
+}
+
+/// Returns the path data of lines or rings.
+fn get_path<'a>(lines: impl Iterator<Item = &'a Vec<[f64; 2]>>, close: bool) -> String {
+	let mut path = String::new();
+	for line in lines {
+		for (i, [x, y]) in line.iter().enumerate() {
+			path += &format!("{}{} {}", if i == 0 { 'M' } else { 'L' }, round(*x), round(*y));
+		}
+		if close {
+			path.push('Z');
+		}
+	}
+	path
+}
+
+fn round(value: f64) -> f64 {
+	(value * 10.0).round() / 10.0
+}
+
+fn escape_xml(text: &str) -> String {
+	text
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+#[derive(Debug)]
+struct Operation {
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	source: Box<dyn OperationTrait>,
+	tilejson: TileJSON,
+}
+
+impl Operation {
+	fn build(
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &PipelineFactory,
+	) -> BoxFuture<'_, Result<Box<dyn OperationTrait>, anyhow::Error>>
+	where
+		Self: Sized + OperationTrait,
+	{
+		Box::pin(async move {
+			let args = Args::from_vpl_node(&vpl_node)?;
+
+			let tile_format = TileFormat::parse_str(&args.format)?;
+			if !matches!(tile_format, TileFormat::PBF | TileFormat::GEOJSON | TileFormat::SVG) {
+				bail!(
+					"format must be \"pbf\", \"geojson\" or \"svg\", but got \"{}\"",
+					args.format
+				);
+			}
+
+			let mut parameters = source.get_parameters().clone();
+			if !matches!(parameters.tile_format, TileFormat::PBF | TileFormat::GEOJSON) {
+				bail!("source must be vector tiles in pbf or geojson format");
+			}
+			if parameters.tile_format == tile_format {
+				return Ok(source);
+			}
+
+			let runner = Arc::new(Runner {
+				source_format: parameters.tile_format,
+				tile_format,
+				default_layer: args.layer.unwrap_or(String::from("geojson")),
+				tile_compression: parameters.tile_compression,
+				tile_cache: factory.get_tile_cache().clone(),
+			});
+
+			parameters.tile_format = tile_format;
+			parameters.tile_compression = TileCompression::Uncompressed;
+			let tilejson = source.get_tilejson().clone();
+
+			Ok(Box::new(Self {
+				runner,
+				parameters,
+				source,
+				tilejson,
+			}) as Box<dyn OperationTrait>)
+		})
+	}
+}
+
+#[async_trait]
+impl OperationTrait for Operation {
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.source
+			.get_tile_stream(bbox)
+			.await
+			.map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
+			Some(self.runner.run(coord, blob)?)
+		} else {
+			None
+		})
+	}
+}
+
+pub struct Factory {}
+
+impl OperationFactoryTrait for Factory {
+	fn get_docs(&self) -> String {
+		Args::get_docs()
+	}
+	fn get_tag_name(&self) -> &str {
+		"vectortiles_format"
+	}
+}
+
+#[async_trait]
+impl TransformOperationFactoryTrait for Factory {
+	async fn build<'a>(
+		&self,
+		vpl_node: VPLNode,
+		source: Box<dyn OperationTrait>,
+		factory: &'a PipelineFactory,
+	) -> Result<Box<dyn OperationTrait>> {
+		Operation::build(vpl_node, source, factory).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	async fn get_tile(vpl: &str, coord: TileCoord3) -> Result<(TileFormat, Blob)> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory.operation_from_vpl(vpl).await?;
+		let blob = operation.get_tile_data(&coord).await?.unwrap();
+		Ok((operation.get_parameters().tile_format, blob))
+	}
+
+	fn get_layers(tile: &VectorTile) -> Result<Vec<String>> {
+		tile
+			.layers
+			.iter()
+			.map(|layer| {
+				let geometries: Vec<String> = layer
+					.to_features()?
+					.iter()
+					.map(|f| format!("{:?}", f.geometry))
+					.collect();
+				Ok(format!("{}: {}", layer.name, geometries.join(", ")))
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn pbf_to_geojson() -> Result<()> {
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let (format, blob) = get_tile(
+			"from_container filename=dummy | vectortiles_format format=geojson",
+			coord,
+		)
+		.await?;
+		assert_eq!(format, TileFormat::GEOJSON);
+		assert!(blob.as_str().starts_with("{\"features\":[{\"geometry\":"));
+
+		let layers = parse_geojson_layers(blob.as_str(), "missing")?;
+		assert!(!layers.is_empty());
+		for (name, features) in layers {
+			assert_ne!(name, "missing");
+			for feature in features {
+				// tile 3/1/2 covers longitudes from -135 to -90 and latitudes from 41 to 66.5
+				feature.geometry.map_coordinates(|[lon, lat]| {
+					assert!((-135.1..=-89.9).contains(&lon), "{lon}");
+					assert!((40.9..=66.6).contains(&lat), "{lat}");
+					[lon, lat]
+				});
+			}
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn roundtrip() -> Result<()> {
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let (_, original) = get_tile("from_container filename=dummy", coord).await?;
+		let (format, converted) = get_tile(
+			"from_container filename=dummy | vectortiles_format format=geojson | vectortiles_format format=pbf",
+			coord,
+		)
+		.await?;
+		assert_eq!(format, TileFormat::PBF);
+
+		let original = VectorTile::from_blob(&original)?;
+		let converted = VectorTile::from_blob(&converted)?;
+		assert_eq!(get_layers(&original)?, get_layers(&converted)?);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn geojson_layers() -> Result<()> {
+		let runner = Runner {
+			source_format: TileFormat::GEOJSON,
+			tile_format: TileFormat::PBF,
+			default_layer: String::from("rest"),
+			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
+		};
+		let geojson = r#"{"type":"FeatureCollection","features":[
+			{"type":"Feature","id":"a","layer":"water","geometry":{"type":"Point","coordinates":[0,0]},"properties":{}},
+			{"type":"Feature","id":7,"geometry":{"type":"Point","coordinates":[90,0]},"properties":{"name":"x"}}
+		]}"#;
+		let blob = runner.run(&TileCoord3::new(0, 0, 0)?, Blob::from(geojson))?;
+		let tile = VectorTile::from_blob(&blob)?;
+		assert_eq!(
+			get_layers(&tile)?,
+			[
+				"water: MultiPoint([[2048.0, 2048.0]])",
+				"rest: MultiPoint([[3072.0, 2048.0]])"
+			]
+		);
+		assert_eq!(tile.layers[0].features[0].id, None);
+		assert_eq!(tile.layers[1].features[0].id, Some(7));
+
+		assert!(runner.run(&TileCoord3::new(0, 0, 0)?, Blob::from("{}")).is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn pbf_to_svg() -> Result<()> {
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let (format, blob) = get_tile("from_container filename=dummy | vectortiles_format format=svg", coord).await?;
+		assert_eq!(format, TileFormat::SVG);
+		assert!(blob
+			.as_str()
+			.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 4096 4096\"><style>"));
+		assert!(blob
+			.as_str()
+			.ends_with("</style><g id=\"mock\"><circle class=\"point\" cx=\"1\" cy=\"2\" r=\"8\"/></g></svg>"));
+		Ok(())
+	}
+
+	#[test]
+	fn geojson_to_svg() -> Result<()> {
+		let runner = Runner {
+			source_format: TileFormat::GEOJSON,
+			tile_format: TileFormat::SVG,
+			default_layer: String::from("<rest>"),
+			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
+		};
+		let geojson = r#"{"type":"FeatureCollection","features":[
+			{"type":"Feature","layer":"water","geometry":{"type":"Polygon","coordinates":[[[0,0],[90,0],[90,-45],[0,0]]]},"properties":{}},
+			{"type":"Feature","geometry":{"type":"LineString","coordinates":[[-90,0],[0,0]]},"properties":{}},
+			{"type":"Feature","geometry":{"type":"Point","coordinates":[0,0]},"properties":{}}
+		]}"#;
+		let blob = runner.run(&TileCoord3::new(0, 0, 0)?, Blob::from(geojson))?;
+		let svg = blob.as_str();
+		let svg = &svg[svg.find("</style>").unwrap() + 8..];
+		assert_eq!(
+			svg,
+			concat!(
+				"<g id=\"water\"><path class=\"polygon\" d=\"M2048 2048L3072 2048L3072 2622.6L2048 2048Z\"/></g>",
+				"<g id=\"&lt;rest&gt;\"><path class=\"line\" d=\"M1024 2048L2048 2048\"/>",
+				"<circle class=\"point\" cx=\"2048\" cy=\"2048\" r=\"8\"/></g></svg>"
+			)
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn same_format() -> Result<()> {
+		let coord = TileCoord3::new(1, 2, 3)?;
+		let (_, original) = get_tile("from_container filename=dummy", coord).await?;
+		let (_, unchanged) = get_tile("from_container filename=dummy | vectortiles_format format=pbf", coord).await?;
+		assert_eq!(original, unchanged);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn invalid_args() {
+		let factory = PipelineFactory::new_dummy();
+		for vpl in [
+			"from_container filename=dummy | vectortiles_format",
+			"from_container filename=dummy | vectortiles_format format=svg | vectortiles_format format=pbf",
+			"from_container filename=dummy | vectortiles_format format=cheese",
+			"from_debug format=png | vectortiles_format format=geojson",
+		] {
+			assert!(factory.operation_from_vpl(vpl).await.is_err(), "{vpl}");
+		}
+	}
+}
@@ -1,5 +1,5 @@
 use crate::{
-	helpers::read_csv_file,
+	helpers::{read_csv_file, DecodedTileCache},
 	traits::{OperationFactoryTrait, OperationTrait, TransformOperationFactoryTrait},
 	vpl::VPLNode,
 	PipelineFactory,
@@ -12,8 +12,12 @@ use std::{
 	collections::{BTreeSet, HashMap},
 	sync::Arc,
 };
-use versatiles_core::{tilejson::TileJSON, types::*, utils::decompress};
-use versatiles_geometry::{vector_tile::VectorTile, GeoProperties};
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, get_thread_count},
+};
+use versatiles_geometry::GeoProperties;
 
 #[derive(versatiles_derive::VPLDecode, Clone, Debug)]
 /// Updates properties of vector tile features using data from an external source (e.g., CSV file). Matches features based on an ID field.
@@ -41,46 +45,67 @@ struct Args {
 }
 
 #[derive(Debug)]
-struct Runner {
-	args: Args,
-	tile_compression: TileCompression,
-	properties_map: HashMap<String, GeoProperties>,
+pub(crate) struct Runner {
+	pub(crate) layer_name: String,
+	pub(crate) id_field_tiles: String,
+	pub(crate) replace_properties: bool,
+	pub(crate) remove_non_matching: bool,
+	pub(crate) tile_compression: TileCompression,
+	pub(crate) tile_cache: DecodedTileCache,
+	pub(crate) properties_map: HashMap<String, GeoProperties>,
 }
 
 impl Runner {
-	fn run(&self, mut blob: Blob) -> Result<Option<Blob>> {
+	pub(crate) fn run(&self, coord: &TileCoord3, mut blob: Blob) -> Result<Option<Blob>> {
 		blob = decompress(blob, &self.tile_compression)?;
-		let mut tile = VectorTile::from_blob(&blob).context("Failed to create VectorTile from Blob")?;
-
-		let layer_name = &self.args.layer_name;
+		let mut tile = Arc::unwrap_or_clone(self.tile_cache.decode(coord, &blob)?);
 
 		for layer in tile.layers.iter_mut() {
-			if &layer.name != layer_name {
+			if layer.name != self.layer_name {
 				continue;
 			}
 
 			layer.filter_map_properties(|mut prop| {
-				if let Some(id) = prop.get(&self.args.id_field_tiles) {
+				if let Some(id) = prop.get(&self.id_field_tiles) {
 					if let Some(new_prop) = self.properties_map.get(&id.to_string()) {
-						if self.args.replace_properties {
+						if self.replace_properties {
 							prop = new_prop.clone();
 						} else {
 							prop.update(new_prop);
 						}
 					} else {
-						if self.args.remove_non_matching {
+						if self.remove_non_matching {
 							return None;
 						}
 						warn!("id \"{id}\" not found in data source");
 					}
 				} else {
-					warn!("id field \"{}\" not found", &self.args.id_field_tiles);
+					warn!("id field \"{}\" not found", &self.id_field_tiles);
 				}
 				Some(prop)
 			})?;
 		}
 
-		Ok(Some(tile.to_blob().context("Failed to convert VectorTile to Blob")?))
+		Ok(Some(self.tile_cache.encode(coord, tile)?))
+	}
+
+	pub(crate) fn update_tilejson(&self, tilejson: &mut TileJSON) {
+		if let Some(layer) = tilejson.vector_layers.0.get_mut(&self.layer_name) {
+			let mut all_keys = BTreeSet::<String>::new();
+			for prop in self.properties_map.values() {
+				for (k, _) in prop.iter() {
+					if !prop.0.contains_key(k) {
+						all_keys.insert(k.clone());
+					}
+				}
+			}
+			if self.replace_properties {
+				layer.fields.clear();
+			}
+			for key in all_keys {
+				layer.fields.insert(key, "automatically added field".to_string());
+			}
+		}
 	}
 }
 
@@ -131,29 +156,19 @@ impl Operation {
 			let mut parameters = source.get_parameters().clone();
 			ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
 
-			let mut tilejson = source.get_tilejson().clone();
-			if let Some(layer) = tilejson.vector_layers.0.get_mut(&args.layer_name) {
-				let mut all_keys = BTreeSet::<String>::new();
-				for prop in properties_map.values() {
-					for (k, _) in prop.iter() {
-						if !prop.0.contains_key(k) {
-							all_keys.insert(k.clone());
-						}
-					}
-				}
-				if args.replace_properties {
-					layer.fields.clear();
-				}
-				for key in all_keys {
-					layer.fields.insert(key, "automatically added field".to_string());
-				}
-			}
-
-			let runner = Arc::new(Runner {
-				args,
-				properties_map,
+			let runner = Runner {
+				layer_name: args.layer_name,
+				id_field_tiles: args.id_field_tiles,
+				replace_properties: args.replace_properties,
+				remove_non_matching: args.remove_non_matching,
 				tile_compression: parameters.tile_compression,
-			});
+				tile_cache: factory.get_tile_cache().clone(),
+				properties_map,
+			};
+
+			let mut tilejson = source.get_tilejson().clone();
+			runner.update_tilejson(&mut tilejson);
+			let runner = Arc::new(runner);
 
 			parameters.tile_compression = TileCompression::Uncompressed;
 
@@ -174,18 +189,19 @@ impl OperationTrait for Operation {
 	}
 	async fn get_tile_stream(&self, bbox: TileBBox) -> TileStream {
 		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
 		self
 			.source
 			.get_tile_stream(bbox)
 			.await
-			.filter_map_blob_parallel(move |blob| runner.run(blob).unwrap())
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
 	}
 	fn get_tilejson(&self) -> &TileJSON {
 		&self.tilejson
 	}
 	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
 		Ok(if let Some(blob) = self.source.get_tile_data(coord).await? {
-			self.runner.run(blob)?
+			self.runner.run(coord, blob)?
 		} else {
 			None
 		})
@@ -220,7 +236,10 @@ mod tests {
 	use super::*;
 	use assert_fs::NamedTempFile;
 	use std::{fs::File, io::Write};
-	use versatiles_geometry::{vector_tile::VectorTileLayer, GeoFeature, GeoProperties, GeoValue, Geometry};
+	use versatiles_geometry::{
+		vector_tile::{VectorTile, VectorTileLayer},
+		GeoFeature, GeoProperties, GeoValue, Geometry,
+	};
 
 	fn create_sample_vector_tile_blob() -> Blob {
 		let mut feature = GeoFeature::new(Geometry::new_example());
@@ -241,21 +260,17 @@ mod tests {
 		)]);
 
 		let runner = Runner {
-			args: Args {
-				data_source_path: "data.csv".to_string(),
-				id_field_tiles: "id".to_string(),
-				id_field_data: "id".to_string(),
-				layer_name: "test_layer".to_string(),
-				replace_properties: false,
-				remove_non_matching: false,
-				include_id: false,
-			},
+			layer_name: "test_layer".to_string(),
+			id_field_tiles: "id".to_string(),
+			replace_properties: false,
+			remove_non_matching: false,
 			tile_compression: TileCompression::Uncompressed,
+			tile_cache: DecodedTileCache::default(),
 			properties_map,
 		};
 
 		let blob = create_sample_vector_tile_blob();
-		let result_blob = runner.run(blob).unwrap().unwrap();
+		let result_blob = runner.run(&TileCoord3::new(0, 0, 0).unwrap(), blob).unwrap().unwrap();
 		let tile = VectorTile::from_blob(&result_blob).unwrap();
 
 		let properties = tile.layers[0].features[0].decode_properties(&tile.layers[0]).unwrap();
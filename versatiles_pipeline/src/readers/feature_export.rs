@@ -0,0 +1,373 @@
+use crate::helpers::unproject;
+use anyhow::{ensure, Context, Result};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::Debug,
+};
+use versatiles_core::{types::*, utils::decompress};
+use versatiles_geometry::{
+	math::{clip_line, clip_ring, merge_lines},
+	vector_tile::VectorTile,
+	Coordinates1, GeoFeature, Geometry, MultiLineStringGeometry, MultiPointGeometry, MultiPolygonGeometry,
+};
+
+/// Options of [`FeatureExportReader::export`].
+#[derive(Clone, Debug, Default)]
+pub struct FeatureExportOptions {
+	/// Zoom level of the exported tiles. Defaults to the highest zoom level of the source.
+	pub zoom: Option<u8>,
+	/// Only export the tiles that intersect this bounding box.
+	pub bbox: Option<GeoBBox>,
+	/// Only export these layers. Defaults to all layers.
+	pub layers: Option<Vec<String>>,
+	/// Merge the parts of features that were cut at tile boundaries.
+	pub merge: bool,
+}
+
+/// Exports the features of vector tiles, e.g. to write them into GeoJSON or FlatGeobuf files for analysis.
+///
+/// All tiles of one zoom level are read. Features are clipped to their tile, so that the parts in the buffer
+/// around a tile, which are also part of the neighbouring tiles, are exported only once.
+///
+/// With [`FeatureExportOptions::merge`], features of a layer with the same id, properties and geometry type
+/// become a single feature: lines are joined where they were cut, points and polygons are combined into one
+/// multi geometry. Polygons are not dissolved, so the edges of the tiles remain as seams. All features are
+/// kept in memory until the export is finished.
+pub struct FeatureExportReader {
+	inner: Box<dyn TilesReaderTrait>,
+}
+
+impl FeatureExportReader {
+	/// Creates a new feature export reader.
+	///
+	/// # Arguments
+	/// * `inner` - The source of vector tiles.
+	pub fn new(inner: Box<dyn TilesReaderTrait>) -> Result<FeatureExportReader> {
+		ensure!(
+			inner.get_parameters().tile_format == TileFormat::PBF,
+			"source must be vector tiles"
+		);
+		Ok(FeatureExportReader { inner })
+	}
+
+	/// Returns the features of every layer, with coordinates in longitude and latitude. Layers are sorted by name.
+	pub async fn export(&self, options: &FeatureExportOptions) -> Result<Vec<(String, Vec<GeoFeature>)>> {
+		let parameters = self.inner.get_parameters();
+		let mut pyramid = parameters.bbox_pyramid.clone();
+		let Some(zoom_max) = pyramid.get_zoom_max() else {
+			return Ok(Vec::new());
+		};
+		let zoom = options.zoom.unwrap_or(zoom_max);
+		ensure!(
+			zoom <= zoom_max,
+			"zoom level {zoom} is not available, the highest zoom level is {zoom_max}"
+		);
+		if let Some(bbox) = &options.bbox {
+			pyramid.intersect_geo_bbox(bbox);
+		}
+		let bbox = pyramid.get_level_bbox(zoom).clone();
+
+		let mut collector = FeatureCollector::new(options);
+		let mut result = Ok(());
+		self
+			.inner
+			.get_bbox_tile_stream(bbox)
+			.await
+			.for_each_sync(|(coord, blob)| {
+				if result.is_ok() {
+					result = decompress(blob, &parameters.tile_compression)
+						.and_then(|blob| VectorTile::from_blob(&blob))
+						.and_then(|tile| collector.add_tile(&coord, &tile))
+						.with_context(|| format!("Failed to export tile {coord:?}"));
+				}
+			})
+			.await;
+		result?;
+
+		Ok(collector.into_layers(zoom))
+	}
+}
+
+impl Debug for FeatureExportReader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("FeatureExportReader")
+			.field("inner", &self.inner)
+			.finish()
+	}
+}
+
+/// Collects the features of the tiles of one zoom level, with coordinates in tiles.
+struct FeatureCollector {
+	layer_names: Option<Vec<String>>,
+	merge: bool,
+	layers: BTreeMap<String, Vec<GeoFeature>>,
+	/// Index of the merged feature per layer and key.
+	merged: HashMap<(String, String), usize>,
+}
+
+impl FeatureCollector {
+	fn new(options: &FeatureExportOptions) -> FeatureCollector {
+		FeatureCollector {
+			layer_names: options.layers.clone(),
+			merge: options.merge,
+			layers: BTreeMap::new(),
+			merged: HashMap::new(),
+		}
+	}
+
+	fn add_tile(&mut self, coord: &TileCoord3, tile: &VectorTile) -> Result<()> {
+		let (x0, y0) = (coord.x as f64, coord.y as f64);
+		let bbox = [x0, y0, x0 + 1.0, y0 + 1.0];
+
+		for layer in tile.layers.iter() {
+			if let Some(names) = &self.layer_names {
+				if !names.contains(&layer.name) {
+					continue;
+				}
+			}
+			let extent = layer.extent as f64;
+			for feature in layer.features.iter() {
+				let mut feature = feature.to_feature(layer)?;
+				let geometry = feature
+					.geometry
+					.map_coordinates(|[x, y]| [x0 + x / extent, y0 + y / extent]);
+				if let Some(geometry) = clip(geometry, &bbox) {
+					feature.geometry = geometry;
+					self.add_feature(&layer.name, feature);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn add_feature(&mut self, layer_name: &str, feature: GeoFeature) {
+		let features = self.layers.entry(layer_name.to_string()).or_default();
+		if !self.merge {
+			features.push(feature);
+			return;
+		}
+
+		let key = format!(
+			"{} {:?} {}",
+			feature.geometry.get_type_name(),
+			feature.id,
+			feature.properties.to_json().stringify()
+		);
+		match self.merged.get(&(layer_name.to_string(), key.clone())) {
+			Some(index) => match (&mut features[*index].geometry, feature.geometry) {
+				(Geometry::MultiPoint(a), Geometry::MultiPoint(b)) => a.0.extend(b.0),
+				(Geometry::MultiLineString(a), Geometry::MultiLineString(b)) => a.0.extend(b.0),
+				(Geometry::MultiPolygon(a), Geometry::MultiPolygon(b)) => a.0.extend(b.0),
+				_ => unreachable!("the key contains the geometry type"),
+			},
+			None => {
+				self.merged.insert((layer_name.to_string(), key), features.len());
+				features.push(feature);
+			}
+		}
+	}
+
+	/// Returns the layers, with coordinates in longitude and latitude.
+	fn into_layers(self, zoom: u8) -> Vec<(String, Vec<GeoFeature>)> {
+		let merge = self.merge;
+		self
+			.layers
+			.into_iter()
+			.map(|(name, features)| {
+				let features = features
+					.into_iter()
+					.map(|mut feature| {
+						if let (true, Geometry::MultiLineString(g)) = (merge, &mut feature.geometry) {
+							g.0 = merge_lines(std::mem::take(&mut g.0));
+						}
+						feature.geometry = feature.geometry.map_coordinates(|[x, y]| unproject(x, y, zoom));
+						feature
+					})
+					.collect();
+				(name, features)
+			})
+			.collect()
+	}
+}
+
+/// Clips a geometry to the bounding box of its tile and returns it as multi geometry, or `None` if nothing is left.
+fn clip(geometry: Geometry, bbox: &[f64; 4]) -> Option<Geometry> {
+	let [x_min, y_min, x_max, y_max] = *bbox;
+	match geometry.into_multi() {
+		Geometry::MultiPoint(g) => {
+			// points on the right or lower edge belong to the next tile
+			let points: Coordinates1 =
+				g.0.into_iter()
+					.filter(|[x, y]| *x >= x_min && *x < x_max && *y >= y_min && *y < y_max)
+					.collect();
+			(!points.is_empty()).then_some(Geometry::MultiPoint(MultiPointGeometry(points)))
+		}
+		Geometry::MultiLineString(g) => {
+			let lines: Vec<Coordinates1> =
+				g.0.iter()
+					.flat_map(|line| clip_line(line, bbox))
+					.filter(|line| line.windows(2).any(|w| w[0] != w[1]))
+					.collect();
+			(!lines.is_empty()).then_some(Geometry::MultiLineString(MultiLineStringGeometry(lines)))
+		}
+		Geometry::MultiPolygon(g) => {
+			let polygons: Vec<Vec<Coordinates1>> =
+				g.0.iter()
+					.filter_map(|polygon| {
+						let rings: Vec<Coordinates1> = polygon.iter().map(|ring| clip_ring(ring, bbox)).collect();
+						// without the outer ring, the holes are gone too
+						(!rings.first()?.is_empty()).then(|| rings.into_iter().filter(|ring| !ring.is_empty()).collect())
+					})
+					.collect();
+			(!polygons.is_empty()).then_some(Geometry::MultiPolygon(MultiPolygonGeometry(polygons)))
+		}
+		_ => unreachable!("into_multi returns multi geometries"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helpers::mock_vector_source::MockVectorSource;
+	use versatiles_geometry::{vector_tile::VectorTileLayer, GeoValue};
+
+	/// Returns a tile with one layer "roads", containing the features with coordinates in pixels of the tile.
+	fn tile(features: Vec<(u64, Geometry)>) -> VectorTile {
+		let features = features
+			.into_iter()
+			.map(|(id, geometry)| {
+				let mut feature = GeoFeature::new(geometry);
+				feature.set_id(GeoValue::from(id));
+				feature.set_property(String::from("kind"), "road");
+				feature
+			})
+			.collect();
+		VectorTile::new(vec![VectorTileLayer::from_features(
+			String::from("roads"),
+			features,
+			4096,
+			2,
+		)
+		.unwrap()])
+	}
+
+	/// Adds two neighbouring tiles, each with a line that crosses the boundary and a point in the buffer.
+	fn collect(merge: bool) -> Result<Vec<(String, Vec<GeoFeature>)>> {
+		let options = FeatureExportOptions {
+			merge,
+			..Default::default()
+		};
+		let mut collector = FeatureCollector::new(&options);
+		collector.add_tile(
+			&TileCoord3::new(0, 0, 1)?,
+			&tile(vec![
+				(1, Geometry::new_line_string(vec![[3072, 1024], [5120, 2048]])),
+				(2, Geometry::new_point([4160, 100])),
+			]),
+		)?;
+		collector.add_tile(
+			&TileCoord3::new(1, 0, 1)?,
+			&tile(vec![
+				(1, Geometry::new_line_string(vec![[-1024, 1024], [1024, 2048]])),
+				(2, Geometry::new_point([64, 100])),
+			]),
+		)?;
+		Ok(collector.into_layers(1))
+	}
+
+	fn geometries(features: &[GeoFeature]) -> Vec<String> {
+		features
+			.iter()
+			.map(|f| {
+				let geometry = f.geometry.clone().map_coordinates(|[x, y]| [x.round(), y.round()]);
+				format!("{:?} {:?}", f.id, geometry)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn clipped() -> Result<()> {
+		let layers = collect(false)?;
+		assert_eq!(layers.len(), 1);
+		assert_eq!(layers[0].0, "roads");
+		assert_eq!(
+			geometries(&layers[0].1),
+			[
+				"Some(UInt(1)) MultiLineString([[[-45.0, 79.0], [0.0, 74.0]]])",
+				"Some(UInt(1)) MultiLineString([[[0.0, 74.0], [45.0, 67.0]]])",
+				"Some(UInt(2)) MultiPoint([[3.0, 85.0]])"
+			]
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn merged() -> Result<()> {
+		let layers = collect(true)?;
+		assert_eq!(
+			geometries(&layers[0].1),
+			[
+				"Some(UInt(1)) MultiLineString([[[-45.0, 79.0], [0.0, 74.0], [45.0, 67.0]]])",
+				"Some(UInt(2)) MultiPoint([[3.0, 85.0]])"
+			]
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn clip_polygon() {
+		let bbox = [0.0, 0.0, 1.0, 1.0];
+		let square = |a: f64, b: f64| vec![[a, a], [b, a], [b, b], [a, b], [a, a]];
+
+		let polygon = Geometry::new_polygon(vec![square(0.5, 1.5), square(1.1, 1.2)]);
+		assert_eq!(
+			clip(polygon, &bbox),
+			Some(Geometry::new_multi_polygon(vec![vec![vec![
+				[0.5, 1.0],
+				[0.5, 0.5],
+				[1.0, 0.5],
+				[1.0, 1.0],
+				[0.5, 1.0]
+			]]]))
+		);
+		assert_eq!(clip(Geometry::new_polygon(vec![square(2.0, 3.0)]), &bbox), None);
+	}
+
+	#[tokio::test]
+	async fn export() -> Result<()> {
+		let source = MockVectorSource::new(
+			&[("place", &[&[("name", "Berlin")]]), ("water", &[&[("kind", "lake")]])],
+			Some(TileBBoxPyramid::new_full(3)),
+		);
+		let reader = FeatureExportReader::new(Box::new(source))?;
+
+		// the mock features are points at (1, 2) in every tile
+		let options = FeatureExportOptions {
+			zoom: Some(2),
+			layers: Some(vec![String::from("water")]),
+			..Default::default()
+		};
+		let layers = reader.export(&options).await?;
+		assert_eq!(layers.len(), 1);
+		assert_eq!(layers[0].0, "water");
+		assert_eq!(layers[0].1.len(), 16);
+
+		let options = FeatureExportOptions {
+			bbox: Some(GeoBBox(10.0, 50.0, 11.0, 51.0)),
+			..Default::default()
+		};
+		let layers = reader.export(&options).await?;
+		assert_eq!(layers.len(), 2);
+		assert_eq!(layers[0].1.len(), 1);
+		let feature = &layers[0].1[0];
+		assert_eq!(feature.properties.get("x").unwrap().to_string(), "4");
+		assert_eq!(feature.properties.get("z").unwrap().to_string(), "3");
+
+		let options = FeatureExportOptions {
+			zoom: Some(4),
+			..Default::default()
+		};
+		assert!(reader.export(&options).await.is_err());
+		Ok(())
+	}
+}
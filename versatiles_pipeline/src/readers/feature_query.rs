@@ -0,0 +1,146 @@
+use crate::helpers::{project, unproject, DecodedTileCache};
+use anyhow::{ensure, Context, Result};
+use std::fmt::Debug;
+use versatiles_core::{types::*, utils::decompress};
+use versatiles_geometry::GeoFeature;
+
+/// A feature found by [`FeatureQueryReader::query`].
+#[derive(Clone, Debug)]
+pub struct QueriedFeature {
+	/// Name of the layer that contains the feature.
+	pub layer: String,
+	/// The feature, with coordinates in longitude and latitude.
+	pub feature: GeoFeature,
+}
+
+/// Finds the features of vector tiles at a position.
+///
+/// Decoded tiles are cached, so repeated queries of the same tile don't parse it again.
+pub struct FeatureQueryReader {
+	inner: Box<dyn TilesReaderTrait>,
+	tile_cache: DecodedTileCache,
+}
+
+impl FeatureQueryReader {
+	/// Creates a new feature query reader.
+	///
+	/// # Arguments
+	/// * `inner` - The source of vector tiles.
+	pub fn new(inner: Box<dyn TilesReaderTrait>) -> Result<FeatureQueryReader> {
+		ensure!(
+			inner.get_parameters().tile_format == TileFormat::PBF,
+			"source must be vector tiles"
+		);
+		let tile_cache = DecodedTileCache::default().with_source(inner.get_source_name());
+		Ok(FeatureQueryReader { inner, tile_cache })
+	}
+
+	/// Uses a cache of decoded tiles that can be shared, e.g. with pipeline operations or other readers.
+	pub fn set_tile_cache(&mut self, tile_cache: &DecodedTileCache) {
+		self.tile_cache = tile_cache.with_source(self.inner.get_source_name());
+	}
+
+	/// Returns all features of the covering tile that contain the position, or, for points and lines,
+	/// are closer than `radius`.
+	///
+	/// # Arguments
+	/// * `lon` - Longitude in degrees.
+	/// * `lat` - Latitude in degrees.
+	/// * `zoom` - Zoom level of the tile. Is reduced to the highest zoom level of the source.
+	/// * `radius` - Search radius in pixels of a 256 pixel tile.
+	pub async fn query(&self, lon: f64, lat: f64, zoom: u8, radius: f64) -> Result<Vec<QueriedFeature>> {
+		ensure!(
+			(-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon),
+			"invalid position: longitude {lon}, latitude {lat}"
+		);
+
+		let parameters = self.inner.get_parameters();
+		let Some(zoom_max) = parameters.bbox_pyramid.get_zoom_max() else {
+			return Ok(Vec::new());
+		};
+		let z = zoom.min(zoom_max);
+
+		let (x, y) = project(lat, lon, z);
+		let coord = TileCoord3::new(x as u32, y as u32, z)?;
+		let Some(blob) = self.inner.get_tile_data(&coord).await? else {
+			return Ok(Vec::new());
+		};
+		let blob = decompress(blob, &parameters.tile_compression)?;
+		let tile = self
+			.tile_cache
+			.decode(&coord, &blob)
+			.with_context(|| format!("Failed to decode tile {coord:?}"))?;
+
+		let mut result = Vec::new();
+		for layer in tile.layers.iter() {
+			let extent = layer.extent as f64;
+			let point = [(x - coord.x as f64) * extent, (y - coord.y as f64) * extent];
+			let radius = radius * extent / 256.0;
+
+			for feature in layer.features.iter() {
+				let mut feature = feature.to_feature(layer)?;
+				if !feature.geometry.intersects_point(&point, radius) {
+					continue;
+				}
+				feature.geometry = feature
+					.geometry
+					.map_coordinates(|[gx, gy]| unproject(coord.x as f64 + gx / extent, coord.y as f64 + gy / extent, z));
+				result.push(QueriedFeature {
+					layer: layer.name.clone(),
+					feature,
+				});
+			}
+		}
+
+		Ok(result)
+	}
+}
+
+impl Debug for FeatureQueryReader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("FeatureQueryReader")
+			.field("inner", &self.inner)
+			.field("tile_cache", &self.tile_cache)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helpers::mock_vector_source::MockVectorSource;
+	use versatiles_geometry::Geometry;
+
+	#[tokio::test]
+	async fn query() -> Result<()> {
+		let source = MockVectorSource::new(
+			&[("place", &[&[("name", "Berlin")]]), ("water", &[&[("kind", "lake")]])],
+			Some(TileBBoxPyramid::new_full(3)),
+		);
+		let reader = FeatureQueryReader::new(Box::new(source))?;
+
+		// the mock features are points at (1, 2) in every tile
+		let [lon, lat] = unproject(5.0 + 1.0 / 4096.0, 2.0 + 2.0 / 4096.0, 3);
+		let features = reader.query(lon, lat, 14, 1.0).await?;
+		assert_eq!(features.len(), 2);
+		assert_eq!(features[0].layer, "place");
+		assert_eq!(features[1].layer, "water");
+
+		let feature = &features[0].feature;
+		assert_eq!(feature.properties.get("name").unwrap().to_string(), "Berlin");
+		assert_eq!(feature.properties.get("x").unwrap().to_string(), "5");
+		assert_eq!(feature.properties.get("z").unwrap().to_string(), "3");
+		let Geometry::MultiPoint(points) = &feature.geometry else {
+			panic!("expected points")
+		};
+		assert!((points.0[0][0] - lon).abs() < 1e-9 && (points.0[0][1] - lat).abs() < 1e-9);
+
+		// 10 pixels away
+		let [lon, lat] = unproject(5.0 + 161.0 / 4096.0, 2.0 + 2.0 / 4096.0, 3);
+		assert_eq!(reader.query(lon, lat, 14, 4.0).await?.len(), 0);
+		assert_eq!(reader.query(lon, lat, 14, 11.0).await?.len(), 2);
+
+		assert!(reader.query(200.0, 0.0, 14, 4.0).await.is_err());
+		Ok(())
+	}
+}
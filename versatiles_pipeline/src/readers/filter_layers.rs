@@ -0,0 +1,149 @@
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use std::{collections::BTreeSet, sync::Arc};
+use versatiles_core::{tilejson::TileJSON, types::*, utils::decompress};
+use versatiles_geometry::vector_tile::VectorTileView;
+
+/// Keeps only the given layers of vector tiles.
+///
+/// Tiles are returned uncompressed. Tiles without any remaining layer are dropped.
+#[derive(Debug)]
+pub struct FilterLayersReader {
+	name: String,
+	inner: Box<dyn TilesReaderTrait>,
+	layers: Arc<BTreeSet<String>>,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl FilterLayersReader {
+	/// Creates a new adapter.
+	///
+	/// # Arguments
+	/// * `inner` - The source of vector tiles.
+	/// * `layers` - Names of the layers to keep.
+	pub fn new(inner: Box<dyn TilesReaderTrait>, layers: &[&str]) -> Result<FilterLayersReader> {
+		let mut parameters = inner.get_parameters().clone();
+		ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+		parameters.tile_compression = TileCompression::Uncompressed;
+
+		let layers: BTreeSet<String> = layers.iter().map(|l| l.to_string()).collect();
+
+		let mut tilejson = inner.get_tilejson().clone();
+		tilejson.vector_layers.0.retain(|name, _| layers.contains(name));
+
+		Ok(FilterLayersReader {
+			name: format!("filter_layers({})", inner.get_source_name()),
+			inner,
+			layers: Arc::new(layers),
+			parameters,
+			tilejson,
+		})
+	}
+}
+
+fn filter_layers(blob: Blob, compression: &TileCompression, layers: &BTreeSet<String>) -> Result<Option<Blob>> {
+	let blob = decompress(blob, compression)?;
+	let mut tile = VectorTileView::new(blob.as_slice()).context("Failed to create VectorTileView from Blob")?;
+	tile.retain_layers(|name| layers.contains(name))?;
+	if tile.is_empty() {
+		return Ok(None);
+	}
+	Ok(Some(tile.to_blob()?))
+}
+
+#[async_trait]
+impl TilesReaderTrait for FilterLayersReader {
+	fn get_source_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_container_name(&self) -> &str {
+		"filter_layers"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, _tile_compression: TileCompression) {
+		panic!("you can't override the compression of filter_layers")
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.inner.get_tile_data(coord).await? {
+			filter_layers(blob, &self.inner.get_parameters().tile_compression, &self.layers)?
+		} else {
+			None
+		})
+	}
+
+	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let compression = self.inner.get_parameters().tile_compression;
+		let layers = self.layers.clone();
+		self
+			.inner
+			.get_bbox_tile_stream(bbox)
+			.await
+			.filter_map_blob_parallel(move |blob| filter_layers(blob, &compression, &layers).unwrap())
+	}
+
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
+		self.get_bbox_tile_stream(bbox).await.into_try_stream()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helpers::mock_vector_source::MockVectorSource;
+	use versatiles_geometry::vector_tile::VectorTile;
+
+	fn get_reader(layers: &[&str]) -> Result<FilterLayersReader> {
+		let source = MockVectorSource::new(
+			&[
+				("water", &[&[("kind", "lake")]]),
+				("streets", &[&[("kind", "primary")]]),
+			],
+			Some(TileBBoxPyramid::new_full(3)),
+		);
+		FilterLayersReader::new(Box::new(source), layers)
+	}
+
+	#[tokio::test]
+	async fn keeps_selected_layers() -> Result<()> {
+		let reader = get_reader(&["water"])?;
+		assert_eq!(reader.get_container_name(), "filter_layers");
+		assert_eq!(reader.get_source_name(), "filter_layers(MockVectorSource)");
+
+		let blob = reader.get_tile_data(&TileCoord3::new(1, 2, 3)?).await?.unwrap();
+		let tile = VectorTile::from_blob(&blob)?;
+		assert_eq!(tile.layers.len(), 1);
+		assert_eq!(tile.layers[0].name, "water");
+
+		let tiles = reader
+			.get_bbox_tile_stream(TileBBox::new_full(2)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 16);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn drops_empty_tiles() -> Result<()> {
+		let reader = get_reader(&["buildings"])?;
+		assert_eq!(reader.get_tile_data(&TileCoord3::new(1, 2, 3)?).await?, None);
+		let tiles = reader
+			.get_bbox_tile_stream(TileBBox::new_full(2)?)
+			.await
+			.collect()
+			.await;
+		assert!(tiles.is_empty());
+		Ok(())
+	}
+}
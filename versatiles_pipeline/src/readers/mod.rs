@@ -0,0 +1,28 @@
+//! Reader adapters that mirror pipeline operations.
+//!
+//! Each adapter wraps another [`TilesReaderTrait`](versatiles_core::types::TilesReaderTrait) and is itself a reader,
+//! so pipelines can be composed in Rust code instead of VPL strings:
+//!
+//! ```ignore
+//! let reader = FilterLayersReader::new(reader, &["water", "streets"])?;
+//! let reader = OverzoomReader::new(reader.boxed(), 18)?;
+//! ```
+//!
+//! [`TerrainReader`] wraps a reader of terrain-RGB tiles to query elevations, [`FeatureQueryReader`] wraps a reader
+//! of vector tiles to find the features at a position, and [`FeatureExportReader`] exports all features of a zoom level.
+
+mod feature_export;
+mod feature_query;
+mod filter_layers;
+mod overzoom;
+mod retile;
+mod terrain;
+mod update_properties;
+
+pub use feature_export::{FeatureExportOptions, FeatureExportReader};
+pub use feature_query::{FeatureQueryReader, QueriedFeature};
+pub use filter_layers::FilterLayersReader;
+pub use overzoom::OverzoomReader;
+pub use retile::{RetileMode, RetileReader};
+pub use terrain::{TerrainEncoding, TerrainReader};
+pub use update_properties::{UpdatePropertiesOptions, UpdatePropertiesReader};
@@ -0,0 +1,239 @@
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use imageproc::image::imageops::FilterType;
+use versatiles_core::{
+	tilejson::TileJSON,
+	types::*,
+	utils::{decompress, run_blocking},
+};
+use versatiles_image::helper::{blob2image, image2blob};
+
+/// Generates raster tiles beyond the highest zoom level of the source.
+///
+/// A tile at an overzoomed level is cut out of its ancestor at the highest source level and scaled up.
+/// Tiles up to the highest source level are passed through unchanged.
+#[derive(Debug)]
+pub struct OverzoomReader {
+	name: String,
+	inner: Box<dyn TilesReaderTrait>,
+	source_max: u8,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl OverzoomReader {
+	/// Creates a new adapter.
+	///
+	/// # Arguments
+	/// * `inner` - The source of raster tiles.
+	/// * `max_zoom` - The highest zoom level that should be generated.
+	pub fn new(inner: Box<dyn TilesReaderTrait>, max_zoom: u8) -> Result<OverzoomReader> {
+		let mut parameters = inner.get_parameters().clone();
+		ensure!(
+			matches!(
+				parameters.tile_format,
+				TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP
+			),
+			"overzoom is only supported for raster tiles, but the source has format {:?}",
+			parameters.tile_format
+		);
+
+		let source_max = parameters
+			.bbox_pyramid
+			.get_zoom_max()
+			.context("source must contain at least one tile")?;
+		ensure!(
+			max_zoom >= source_max,
+			"max_zoom ({max_zoom}) must not be lower than the highest zoom level of the source ({source_max})"
+		);
+
+		let source_bbox = parameters.bbox_pyramid.get_level_bbox(source_max).clone();
+		for level in (source_max + 1)..=max_zoom {
+			let scale = 1 << (level - source_max);
+			parameters.bbox_pyramid.set_level_bbox(TileBBox::new(
+				level,
+				source_bbox.x_min * scale,
+				source_bbox.y_min * scale,
+				(source_bbox.x_max + 1) * scale - 1,
+				(source_bbox.y_max + 1) * scale - 1,
+			)?);
+		}
+
+		let mut tilejson = inner.get_tilejson().clone();
+		tilejson.update_from_pyramid(&parameters.bbox_pyramid);
+
+		Ok(OverzoomReader {
+			name: format!("overzoom({})", inner.get_source_name()),
+			inner,
+			source_max,
+			parameters,
+			tilejson,
+		})
+	}
+
+	/// Cuts a tile out of its ancestor at the highest source level.
+	async fn get_overzoomed_tile(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		let level_diff = coord.z - self.source_max;
+		let parent = TileCoord3::new(coord.x >> level_diff, coord.y >> level_diff, self.source_max)?;
+
+		let Some(blob) = self.inner.get_tile_data(&parent).await? else {
+			return Ok(None);
+		};
+
+		let parameters = self.inner.get_parameters();
+		let (compression, format) = (parameters.tile_compression, parameters.tile_format);
+		let coord = *coord;
+
+		// decoding, scaling and encoding images is too slow for the async runtime
+		run_blocking(move || {
+			let blob = decompress(blob, &compression)?;
+			let image = blob2image(&blob, format)?;
+
+			let scale = 1u32 << level_diff;
+			let width = image.width() / scale;
+			let height = image.height() / scale;
+			ensure!(
+				width > 0 && height > 0,
+				"tile {parent:?} is too small to be overzoomed to level {}",
+				coord.z
+			);
+
+			let x = (coord.x % scale) * width;
+			let y = (coord.y % scale) * height;
+			let (size_x, size_y) = (image.width(), image.height());
+			let image = image
+				.crop_imm(x, y, width, height)
+				.resize_exact(size_x, size_y, FilterType::Triangle);
+
+			Ok(Some(image2blob(&image, format)?))
+		})
+		.await
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for OverzoomReader {
+	fn get_source_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_container_name(&self) -> &str {
+		"overzoom"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, _tile_compression: TileCompression) {
+		panic!("you can't override the compression of overzoom")
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		if !self.parameters.bbox_pyramid.contains_coord(coord) {
+			return Ok(None);
+		}
+		if coord.z <= self.source_max {
+			self.inner.get_tile_data(coord).await
+		} else {
+			self.get_overzoomed_tile(coord).await
+		}
+	}
+
+	async fn get_bbox_tile_try_stream(&self, mut bbox: TileBBox) -> TryTileStream {
+		if bbox.level <= self.source_max {
+			return self.inner.get_bbox_tile_try_stream(bbox).await;
+		}
+
+		bbox.intersect_pyramid(&self.parameters.bbox_pyramid).unwrap();
+		let coords: Vec<TileCoord3> = bbox.iter_coords().collect();
+		TryTileStream::from_coord_vec_async(coords, move |coord| async move {
+			self
+				.get_overzoomed_tile(&coord)
+				.await
+				.with_context(|| format!("overzooming tile {coord:?}"))
+				.map(|blob_option| blob_option.map(|blob| (coord, blob)))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::PipelineFactory;
+	use versatiles_image::png;
+
+	async fn get_source(format: &str) -> Result<Box<dyn TilesReaderTrait>> {
+		let factory = PipelineFactory::new_dummy();
+		let operation = factory
+			.operation_from_vpl(&format!("from_debug format={format} | filter_zoom max=2"))
+			.await?;
+		Ok(Box::new(OperationSource(operation)))
+	}
+
+	#[derive(Debug)]
+	struct OperationSource(Box<dyn crate::OperationTrait>);
+
+	#[async_trait]
+	impl TilesReaderTrait for OperationSource {
+		fn get_source_name(&self) -> &str {
+			"debug"
+		}
+		fn get_container_name(&self) -> &str {
+			"debug"
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			self.0.get_parameters()
+		}
+		fn override_compression(&mut self, _tile_compression: TileCompression) {
+			panic!("not possible")
+		}
+		fn get_tilejson(&self) -> &TileJSON {
+			self.0.get_tilejson()
+		}
+		async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+			self.0.get_tile_data(coord).await
+		}
+	}
+
+	#[tokio::test]
+	async fn overzoom_png() -> Result<()> {
+		let reader = OverzoomReader::new(get_source("png").await?, 4)?;
+		assert_eq!(reader.get_parameters().bbox_pyramid.get_zoom_max(), Some(4));
+		assert_eq!(reader.get_source_name(), "overzoom(debug)");
+
+		let blob = reader.get_tile_data(&TileCoord3::new(5, 6, 4)?).await?.unwrap();
+		let image = png::blob2image(&blob)?;
+		assert_eq!((image.width(), image.height()), (512, 512));
+
+		assert!(reader.get_tile_data(&TileCoord3::new(0, 0, 5)?).await?.is_none());
+
+		let tiles = reader
+			.get_bbox_tile_stream(TileBBox::new(3, 0, 0, 3, 1)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 8);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn vector_tiles_are_rejected() -> Result<()> {
+		let error = OverzoomReader::new(get_source("pbf").await?, 4).unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"overzoom is only supported for raster tiles, but the source has format PBF"
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn max_zoom_below_source() -> Result<()> {
+		assert!(OverzoomReader::new(get_source("png").await?, 1).is_err());
+		Ok(())
+	}
+}
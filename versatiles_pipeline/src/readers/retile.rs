@@ -0,0 +1,347 @@
+use anyhow::{ensure, Context, Result};
+use async_trait::async_trait;
+use imageproc::image::{imageops::FilterType, DynamicImage, GenericImage};
+use versatiles_core::{tilejson::TileJSON, types::*, utils::decompress};
+use versatiles_image::helper::{blob2image_blocking, image2blob_blocking};
+
+/// Direction of a conversion between the 256 px and the 512 px tile convention.
+///
+/// A 512 px tile at level z covers the same area as four 256 px tiles at level z + 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetileMode {
+	/// Merges four tiles into one tile of twice the size, one zoom level lower, e.g. 256 px to 512 px tiles.
+	/// The tile at level 0 of the source is dropped, since it is covered by the merged tiles of level 1.
+	Merge,
+	/// Splits every tile into four tiles of half the size, one zoom level higher, e.g. 512 px to 256 px tiles.
+	/// The tile at level 0 is scaled down, so that the result starts at level 0, too.
+	Split,
+}
+
+/// Converts raster tiles between the 256 px and the 512 px tile convention.
+///
+/// Pixels are only copied and never resampled, except for the scaled down tile at level 0 when splitting, which uses
+/// nearest neighbour sampling. So encoded values, e.g. of terrain-RGB tiles, stay valid.
+/// The tiles are returned uncompressed.
+#[derive(Debug)]
+pub struct RetileReader {
+	name: String,
+	inner: Box<dyn TilesReaderTrait>,
+	mode: RetileMode,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl RetileReader {
+	/// Creates a new adapter.
+	///
+	/// # Arguments
+	/// * `inner` - The source of raster tiles.
+	/// * `mode` - Whether tiles are merged or split.
+	pub fn new(inner: Box<dyn TilesReaderTrait>, mode: RetileMode) -> Result<RetileReader> {
+		let source = inner.get_parameters();
+		ensure!(
+			matches!(source.tile_format, TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP),
+			"retiling is only supported for raster tiles, but the source has format {:?}",
+			source.tile_format
+		);
+		ensure!(
+			source.bbox_pyramid.get_grid().is_web_mercator(),
+			"retiling is only supported for the web mercator tile grid"
+		);
+
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		for bbox in source.bbox_pyramid.iter_levels() {
+			match mode {
+				RetileMode::Merge if bbox.level > 0 => bbox_pyramid.set_level_bbox(TileBBox::new(
+					bbox.level - 1,
+					bbox.x_min / 2,
+					bbox.y_min / 2,
+					bbox.x_max / 2,
+					bbox.y_max / 2,
+				)?),
+				RetileMode::Merge => {}
+				RetileMode::Split => {
+					if bbox.level == 0 {
+						bbox_pyramid.set_level_bbox(bbox.clone());
+					}
+					bbox_pyramid.set_level_bbox(TileBBox::new(
+						bbox.level + 1,
+						bbox.x_min * 2,
+						bbox.y_min * 2,
+						bbox.x_max * 2 + 1,
+						bbox.y_max * 2 + 1,
+					)?);
+				}
+			}
+		}
+
+		let parameters = TilesReaderParameters::new(source.tile_format, TileCompression::Uncompressed, bbox_pyramid);
+
+		let mut tilejson = inner.get_tilejson().clone();
+		tilejson.update_from_pyramid(&parameters.bbox_pyramid);
+
+		Ok(RetileReader {
+			name: format!("retile({})", inner.get_source_name()),
+			inner,
+			mode,
+			parameters,
+			tilejson,
+		})
+	}
+
+	/// Reads and decodes a tile of the source.
+	async fn get_source_image(&self, coord: &TileCoord3) -> Result<Option<DynamicImage>> {
+		let Some(blob) = self.inner.get_tile_data(coord).await? else {
+			return Ok(None);
+		};
+		let parameters = self.inner.get_parameters();
+		let blob = decompress(blob, &parameters.tile_compression)?;
+		Ok(Some(blob2image_blocking(blob, parameters.tile_format).await?))
+	}
+
+	/// Merges the four children of a tile at the next level of the source.
+	async fn get_merged_image(&self, coord: &TileCoord3) -> Result<Option<DynamicImage>> {
+		let mut children = Vec::new();
+		for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+			let child = TileCoord3::new(coord.x * 2 + dx, coord.y * 2 + dy, coord.z + 1)?;
+			if let Some(image) = self.get_source_image(&child).await? {
+				children.push((dx, dy, image));
+			}
+		}
+		let Some((_, _, first)) = children.first() else {
+			return Ok(None);
+		};
+
+		let (width, height) = (first.width(), first.height());
+		let mut image = DynamicImage::new_rgba8(width * 2, height * 2);
+		for (dx, dy, child) in children.iter() {
+			ensure!(
+				(child.width(), child.height()) == (width, height),
+				"the children of tile {coord:?} have different sizes"
+			);
+			image.copy_from(child, dx * width, dy * height)?;
+		}
+
+		let is_opaque = children.len() == 4 && children.iter().all(|(_, _, child)| !child.color().has_alpha());
+		if is_opaque || self.parameters.tile_format == TileFormat::JPG {
+			image = DynamicImage::ImageRgb8(image.to_rgb8());
+		}
+		Ok(Some(image))
+	}
+
+	/// Cuts a quarter out of the parent tile at the previous level of the source.
+	async fn get_split_image(&self, coord: &TileCoord3) -> Result<Option<DynamicImage>> {
+		if coord.z == 0 {
+			let Some(image) = self.get_source_image(coord).await? else {
+				return Ok(None);
+			};
+			let (width, height) = (image.width() / 2, image.height() / 2);
+			return Ok(Some(image.resize_exact(width, height, FilterType::Nearest)));
+		}
+
+		let parent = TileCoord3::new(coord.x / 2, coord.y / 2, coord.z - 1)?;
+		let Some(image) = self.get_source_image(&parent).await? else {
+			return Ok(None);
+		};
+		let (width, height) = (image.width() / 2, image.height() / 2);
+		ensure!(width > 0 && height > 0, "tile {parent:?} is too small to be split");
+		Ok(Some(image.crop_imm(
+			(coord.x % 2) * width,
+			(coord.y % 2) * height,
+			width,
+			height,
+		)))
+	}
+
+	async fn get_retiled_tile(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		let image = match self.mode {
+			RetileMode::Merge => self.get_merged_image(coord).await?,
+			RetileMode::Split => self.get_split_image(coord).await?,
+		};
+		Ok(match image {
+			Some(image) => Some(image2blob_blocking(image, self.parameters.tile_format).await?),
+			None => None,
+		})
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for RetileReader {
+	fn get_source_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_container_name(&self) -> &str {
+		"retile"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, _tile_compression: TileCompression) {
+		panic!("you can't override the compression of retile")
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		if !self.parameters.bbox_pyramid.contains_coord(coord) {
+			return Ok(None);
+		}
+		self.get_retiled_tile(coord).await
+	}
+
+	async fn get_bbox_tile_try_stream(&self, mut bbox: TileBBox) -> TryTileStream {
+		bbox.intersect_pyramid(&self.parameters.bbox_pyramid).unwrap();
+		let coords: Vec<TileCoord3> = bbox.iter_coords().collect();
+		TryTileStream::from_coord_vec_async(coords, move |coord| async move {
+			self
+				.get_retiled_tile(&coord)
+				.await
+				.with_context(|| format!("retiling tile {coord:?}"))
+				.map(|blob_option| blob_option.map(|blob| (coord, blob)))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use imageproc::image::{GenericImageView, Rgb, RgbImage};
+	use versatiles_image::png;
+
+	/// Returns PNG tiles, filled with a color that encodes the coordinates of the tile.
+	#[derive(Debug)]
+	struct MockRasterSource {
+		parameters: TilesReaderParameters,
+		tilejson: TileJSON,
+		size: u32,
+	}
+
+	impl MockRasterSource {
+		fn new(bbox_pyramid: TileBBoxPyramid, size: u32) -> MockRasterSource {
+			MockRasterSource {
+				parameters: TilesReaderParameters::new(TileFormat::PNG, TileCompression::Uncompressed, bbox_pyramid),
+				tilejson: TileJSON::default(),
+				size,
+			}
+		}
+
+		fn get_color(coord: &TileCoord3) -> Rgb<u8> {
+			Rgb([coord.z, coord.x as u8, coord.y as u8])
+		}
+	}
+
+	#[async_trait]
+	impl TilesReaderTrait for MockRasterSource {
+		fn get_source_name(&self) -> &str {
+			"mock"
+		}
+		fn get_container_name(&self) -> &str {
+			"mock"
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			&self.parameters
+		}
+		fn override_compression(&mut self, _tile_compression: TileCompression) {
+			panic!("not possible")
+		}
+		fn get_tilejson(&self) -> &TileJSON {
+			&self.tilejson
+		}
+		async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+			if !self.parameters.bbox_pyramid.contains_coord(coord) {
+				return Ok(None);
+			}
+			let image = RgbImage::from_pixel(self.size, self.size, MockRasterSource::get_color(coord));
+			Ok(Some(png::image2blob(&DynamicImage::ImageRgb8(image), false)?))
+		}
+	}
+
+	async fn get_image(reader: &RetileReader, x: u32, y: u32, z: u8) -> Result<DynamicImage> {
+		let blob = reader.get_tile_data(&TileCoord3::new(x, y, z)?).await?.unwrap();
+		png::blob2image(&blob)
+	}
+
+	#[tokio::test]
+	async fn merge() -> Result<()> {
+		let reader = RetileReader::new(
+			Box::new(MockRasterSource::new(TileBBoxPyramid::new_full(3), 256)),
+			RetileMode::Merge,
+		)?;
+		assert_eq!(reader.get_source_name(), "retile(mock)");
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16)]"
+		);
+
+		let image = get_image(&reader, 1, 0, 1).await?;
+		assert_eq!((image.width(), image.height()), (512, 512));
+		assert_eq!(image.get_pixel(0, 0).0, [2, 2, 0, 255]);
+		assert_eq!(image.get_pixel(511, 0).0, [2, 3, 0, 255]);
+		assert_eq!(image.get_pixel(0, 511).0, [2, 2, 1, 255]);
+		assert_eq!(image.get_pixel(511, 511).0, [2, 3, 1, 255]);
+		assert!(!image.color().has_alpha());
+
+		assert!(reader.get_tile_data(&TileCoord3::new(0, 0, 3)?).await?.is_none());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn merge_partial() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.set_level_bbox(TileBBox::new(2, 1, 1, 2, 1)?);
+		let reader = RetileReader::new(Box::new(MockRasterSource::new(bbox_pyramid, 256)), RetileMode::Merge)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[1: [0,0,1,0] (2)]"
+		);
+
+		let image = get_image(&reader, 0, 0, 1).await?;
+		assert_eq!(image.get_pixel(0, 0).0[3], 0);
+		assert_eq!(image.get_pixel(511, 511).0, [2, 1, 1, 255]);
+
+		let tiles = reader
+			.get_bbox_tile_stream(TileBBox::new(1, 0, 0, 1, 1)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 2);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn split() -> Result<()> {
+		let reader = RetileReader::new(
+			Box::new(MockRasterSource::new(TileBBoxPyramid::new_full(2), 512)),
+			RetileMode::Split,
+		)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64)]"
+		);
+
+		let image = get_image(&reader, 0, 0, 0).await?;
+		assert_eq!((image.width(), image.height()), (256, 256));
+		assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+
+		let image = get_image(&reader, 5, 2, 3).await?;
+		assert_eq!((image.width(), image.height()), (256, 256));
+		assert_eq!(image.get_pixel(0, 0).0, [2, 2, 1, 255]);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn vector_tiles_are_rejected() -> Result<()> {
+		let source = crate::helpers::mock_vector_source::MockVectorSource::new(&[], None);
+		let error = RetileReader::new(Box::new(source), RetileMode::Merge).unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"retiling is only supported for raster tiles, but the source has format PBF"
+		);
+		Ok(())
+	}
+}
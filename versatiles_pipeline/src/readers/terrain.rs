@@ -0,0 +1,325 @@
+use crate::helpers::project;
+use anyhow::{bail, ensure, Result};
+use std::{
+	fmt::Debug,
+	mem::size_of,
+	str::FromStr,
+	sync::{Arc, Mutex},
+};
+use versatiles_core::{
+	types::*,
+	utils::{decompress, run_blocking},
+};
+use versatiles_image::helper::blob2image;
+
+/// Number of decoded tiles that are kept in memory.
+const CACHE_TILES: usize = 64;
+
+/// How elevations are encoded in the color channels of terrain tiles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerrainEncoding {
+	/// Mapbox Terrain-RGB: `-10000 + (R * 65536 + G * 256 + B) * 0.1`
+	Mapbox,
+	/// Terrarium: `R * 256 + G + B / 256 - 32768`
+	Terrarium,
+}
+
+impl TerrainEncoding {
+	/// Decodes the elevation in meters from the color of a pixel.
+	pub fn decode(&self, rgb: [u8; 3]) -> f64 {
+		let [r, g, b] = rgb.map(f64::from);
+		match self {
+			TerrainEncoding::Mapbox => -10000.0 + (r * 65536.0 + g * 256.0 + b) * 0.1,
+			TerrainEncoding::Terrarium => r * 256.0 + g + b / 256.0 - 32768.0,
+		}
+	}
+}
+
+impl FromStr for TerrainEncoding {
+	type Err = anyhow::Error;
+
+	fn from_str(value: &str) -> Result<Self> {
+		match value {
+			"mapbox" => Ok(TerrainEncoding::Mapbox),
+			"terrarium" => Ok(TerrainEncoding::Terrarium),
+			_ => bail!("unknown terrain encoding \"{value}\", use \"mapbox\" or \"terrarium\""),
+		}
+	}
+}
+
+/// The decoded elevations of a tile.
+struct TerrainTile {
+	size: u32,
+	elevations: Vec<f64>,
+}
+
+/// Queries elevations from a container of terrain-RGB tiles.
+///
+/// For every point the tile with the highest available zoom level is used. Elevations are interpolated
+/// bilinearly between the centers of the four nearest pixels, also across tile edges.
+pub struct TerrainReader {
+	inner: Box<dyn TilesReaderTrait>,
+	encoding: TerrainEncoding,
+	cache: Mutex<LimitedCache<TileCoord3, Option<Arc<TerrainTile>>>>,
+}
+
+impl TerrainReader {
+	/// Creates a new terrain reader.
+	///
+	/// # Arguments
+	/// * `inner` - The source of terrain tiles.
+	/// * `encoding` - How the elevations are encoded.
+	pub fn new(inner: Box<dyn TilesReaderTrait>, encoding: TerrainEncoding) -> Result<TerrainReader> {
+		let tile_format = inner.get_parameters().tile_format;
+		ensure!(
+			matches!(tile_format, TileFormat::PNG | TileFormat::WEBP),
+			"terrain tiles must be lossless raster tiles, but the source has format {tile_format:?}"
+		);
+
+		Ok(TerrainReader {
+			inner,
+			encoding,
+			cache: Mutex::new(LimitedCache::with_maximum_size(
+				CACHE_TILES * size_of::<(TileCoord3, Option<Arc<TerrainTile>>)>(),
+			)),
+		})
+	}
+
+	/// Returns the elevation in meters at a position, or `None` if there are no terrain tiles at this position.
+	///
+	/// # Arguments
+	/// * `lat` - Latitude in degrees.
+	/// * `lon` - Longitude in degrees.
+	pub async fn get_elevation(&self, lat: f64, lon: f64) -> Result<Option<f64>> {
+		ensure!(
+			(-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon),
+			"invalid position: latitude {lat}, longitude {lon}"
+		);
+
+		let bbox_pyramid = &self.inner.get_parameters().bbox_pyramid;
+		let (Some(zoom_min), Some(zoom_max)) = (bbox_pyramid.get_zoom_min(), bbox_pyramid.get_zoom_max()) else {
+			return Ok(None);
+		};
+
+		// use the highest zoom level with a tile at this position
+		for z in (zoom_min..=zoom_max).rev() {
+			let (x, y) = project(lat, lon, z);
+			let coord = TileCoord3::new(x as u32, y as u32, z)?;
+			if !bbox_pyramid.contains_coord(&coord) {
+				continue;
+			}
+			if let Some(tile) = self.get_tile(&coord).await? {
+				return self.interpolate(x, y, z, tile.size).await;
+			}
+		}
+
+		Ok(None)
+	}
+
+	/// Interpolates the elevation at the tile position `x`, `y` at zoom level `z`.
+	async fn interpolate(&self, x: f64, y: f64, z: u8, size: u32) -> Result<Option<f64>> {
+		let world = (1i64 << z) * size as i64;
+
+		// pixel centers are at half pixels
+		let px = x * size as f64 - 0.5;
+		let py = y * size as f64 - 0.5;
+		let x0 = px.floor();
+		let y0 = py.floor();
+		let dx = px - x0;
+		let dy = py - y0;
+
+		let mut sum = 0.0;
+		let mut weights = 0.0;
+		for (offset_x, offset_y, weight) in [
+			(0, 0, (1.0 - dx) * (1.0 - dy)),
+			(1, 0, dx * (1.0 - dy)),
+			(0, 1, (1.0 - dx) * dy),
+			(1, 1, dx * dy),
+		] {
+			if weight == 0.0 {
+				continue;
+			}
+
+			// wrap around the antimeridian, clamp at the poles
+			let pixel_x = (x0 as i64 + offset_x).rem_euclid(world) as u32;
+			let pixel_y = (y0 as i64 + offset_y).clamp(0, world - 1) as u32;
+
+			let coord = TileCoord3::new(pixel_x / size, pixel_y / size, z)?;
+			let Some(tile) = self.get_tile(&coord).await? else {
+				continue;
+			};
+			ensure!(
+				tile.size == size,
+				"all terrain tiles of zoom level {z} must have the same size"
+			);
+
+			let index = (pixel_y % size) * size + (pixel_x % size);
+			sum += tile.elevations[index as usize] * weight;
+			weights += weight;
+		}
+
+		Ok(if weights > 0.0 { Some(sum / weights) } else { None })
+	}
+
+	/// Gets a decoded tile from the cache or from the source.
+	async fn get_tile(&self, coord: &TileCoord3) -> Result<Option<Arc<TerrainTile>>> {
+		if let Some(tile) = self.cache.lock().unwrap().get(coord) {
+			return Ok(tile);
+		}
+
+		let tile = match self.inner.get_tile_data(coord).await? {
+			Some(blob) => {
+				let parameters = self.inner.get_parameters();
+				let (compression, format) = (parameters.tile_compression, parameters.tile_format);
+				let encoding = self.encoding;
+				Some(Arc::new(
+					run_blocking(move || decode_tile(blob, compression, format, encoding)).await?,
+				))
+			}
+			None => None,
+		};
+
+		Ok(self.cache.lock().unwrap().add(*coord, tile))
+	}
+}
+
+/// Decodes the elevations of a terrain tile.
+fn decode_tile(
+	blob: Blob,
+	compression: TileCompression,
+	format: TileFormat,
+	encoding: TerrainEncoding,
+) -> Result<TerrainTile> {
+	let blob = decompress(blob, &compression)?;
+	let image = blob2image(&blob, format)?.to_rgb8();
+
+	let size = image.width();
+	ensure!(
+		size > 0 && size == image.height(),
+		"terrain tiles must be square, but a tile has {}x{} pixels",
+		image.width(),
+		image.height()
+	);
+
+	Ok(TerrainTile {
+		size,
+		elevations: image.pixels().map(|pixel| encoding.decode(pixel.0)).collect(),
+	})
+}
+
+impl Debug for TerrainReader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("TerrainReader")
+			.field("inner", &self.inner)
+			.field("encoding", &self.encoding)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helpers::unproject;
+	use async_trait::async_trait;
+	use imageproc::image::{DynamicImage, Rgb, RgbImage};
+	use versatiles_core::tilejson::TileJSON;
+	use versatiles_image::png;
+
+	/// Terrain tiles with an elevation of `(gx * 10 + gy) * 0.1 m`, where `gx` and `gy` are global pixel coordinates.
+	#[derive(Debug)]
+	struct RampSource {
+		parameters: TilesReaderParameters,
+		tilejson: TileJSON,
+	}
+
+	impl RampSource {
+		fn new_boxed(bbox_pyramid: TileBBoxPyramid) -> Box<dyn TilesReaderTrait> {
+			Box::new(RampSource {
+				parameters: TilesReaderParameters::new(TileFormat::PNG, TileCompression::Uncompressed, bbox_pyramid),
+				tilejson: TileJSON::default(),
+			})
+		}
+	}
+
+	#[async_trait]
+	impl TilesReaderTrait for RampSource {
+		fn get_source_name(&self) -> &str {
+			"ramp"
+		}
+		fn get_container_name(&self) -> &str {
+			"ramp"
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			&self.parameters
+		}
+		fn override_compression(&mut self, _tile_compression: TileCompression) {
+			panic!("not possible")
+		}
+		fn get_tilejson(&self) -> &TileJSON {
+			&self.tilejson
+		}
+		async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+			if !self.parameters.bbox_pyramid.contains_coord(coord) {
+				return Ok(None);
+			}
+			let image = RgbImage::from_fn(256, 256, |x, y| {
+				let value = 100000 + (coord.x * 256 + x) * 10 + (coord.y * 256 + y);
+				Rgb([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+			});
+			Ok(Some(png::image2blob(&DynamicImage::ImageRgb8(image), false)?))
+		}
+	}
+
+	fn ramp(gx: f64, gy: f64) -> f64 {
+		(gx * 10.0 + gy) * 0.1
+	}
+
+	#[test]
+	fn decode() -> Result<()> {
+		assert_eq!(TerrainEncoding::Mapbox.decode([1, 134, 160]), 0.0);
+		assert_eq!(TerrainEncoding::Mapbox.decode([1, 135, 0]), 9.600000000000364);
+		assert_eq!(TerrainEncoding::Terrarium.decode([128, 0, 0]), 0.0);
+		assert_eq!(TerrainEncoding::Terrarium.decode([129, 2, 128]), 258.5);
+
+		assert_eq!("terrarium".parse::<TerrainEncoding>()?, TerrainEncoding::Terrarium);
+		assert!("lerc".parse::<TerrainEncoding>().is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn elevation_across_tile_edges() -> Result<()> {
+		let reader = TerrainReader::new(RampSource::new_boxed(TileBBoxPyramid::new_full(2)), TerrainEncoding::Mapbox)?;
+
+		// lat 0, lon 0 is the corner of four tiles at zoom level 2
+		let elevation = reader.get_elevation(0.0, 0.0).await?.unwrap();
+		assert!((elevation - ramp(511.5, 511.5)).abs() < 1e-6, "{elevation}");
+
+		// center of the first pixel of tile 2/1/1
+		let [lon, lat] = unproject(256.5 / 256.0, 256.5 / 256.0, 2);
+		let elevation = reader.get_elevation(lat, lon).await?.unwrap();
+		assert!((elevation - ramp(256.0, 256.0)).abs() < 1e-6, "{elevation}");
+
+		assert!(reader.get_elevation(91.0, 0.0).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn best_zoom() -> Result<()> {
+		// zoom level 3 only covers the western hemisphere
+		let mut bbox_pyramid = TileBBoxPyramid::new_full(2);
+		bbox_pyramid.set_level_bbox(TileBBox::new(3, 0, 0, 3, 7)?);
+		let reader = TerrainReader::new(RampSource::new_boxed(bbox_pyramid), TerrainEncoding::Mapbox)?;
+
+		let (x, y) = project(10.0, -90.0, 3);
+		let elevation = reader.get_elevation(10.0, -90.0).await?.unwrap();
+		assert!((elevation - ramp(x * 256.0 - 0.5, y * 256.0 - 0.5)).abs() < 1e-6);
+
+		let (x, y) = project(10.0, 90.0, 2);
+		let elevation = reader.get_elevation(10.0, 90.0).await?.unwrap();
+		assert!((elevation - ramp(x * 256.0 - 0.5, y * 256.0 - 0.5)).abs() < 1e-6);
+
+		let reader = TerrainReader::new(RampSource::new_boxed(TileBBoxPyramid::new_empty()), TerrainEncoding::Mapbox)?;
+		assert_eq!(reader.get_elevation(10.0, 90.0).await?, None);
+		Ok(())
+	}
+}
@@ -0,0 +1,157 @@
+use crate::{helpers::DecodedTileCache, operations::vectortiles_update_properties::Runner};
+use anyhow::{ensure, Result};
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Arc};
+use versatiles_core::{tilejson::TileJSON, types::*, utils::get_thread_count};
+use versatiles_geometry::GeoProperties;
+
+/// Options of an [`UpdatePropertiesReader`].
+#[derive(Clone, Debug, Default)]
+pub struct UpdatePropertiesOptions {
+	/// Name of the vector layer to update.
+	pub layer_name: String,
+	/// ID field name in the vector layer.
+	pub id_field_tiles: String,
+	/// If set, old properties will be deleted before new ones are added.
+	pub replace_properties: bool,
+	/// If set, removes all features (in the layer) that do not match.
+	pub remove_non_matching: bool,
+}
+
+/// Updates properties of vector tile features, like the `vectortiles_update_properties` operation.
+///
+/// Features are matched by the value of their ID field. Tiles are returned uncompressed.
+#[derive(Debug)]
+pub struct UpdatePropertiesReader {
+	name: String,
+	inner: Box<dyn TilesReaderTrait>,
+	runner: Arc<Runner>,
+	parameters: TilesReaderParameters,
+	tilejson: TileJSON,
+}
+
+impl UpdatePropertiesReader {
+	/// Creates a new adapter.
+	///
+	/// # Arguments
+	/// * `inner` - The source of vector tiles.
+	/// * `properties` - New properties, keyed by the value of the ID field.
+	/// * `options` - Which layer and features to update, and how.
+	pub fn new(
+		inner: Box<dyn TilesReaderTrait>,
+		properties: HashMap<String, GeoProperties>,
+		options: UpdatePropertiesOptions,
+	) -> Result<UpdatePropertiesReader> {
+		let mut parameters = inner.get_parameters().clone();
+		ensure!(parameters.tile_format == TileFormat::PBF, "source must be vector tiles");
+
+		let runner = Runner {
+			layer_name: options.layer_name,
+			id_field_tiles: options.id_field_tiles,
+			replace_properties: options.replace_properties,
+			remove_non_matching: options.remove_non_matching,
+			tile_compression: parameters.tile_compression,
+			tile_cache: DecodedTileCache::default(),
+			properties_map: properties,
+		};
+
+		let mut tilejson = inner.get_tilejson().clone();
+		runner.update_tilejson(&mut tilejson);
+
+		parameters.tile_compression = TileCompression::Uncompressed;
+
+		Ok(UpdatePropertiesReader {
+			name: format!("update_properties({})", inner.get_source_name()),
+			inner,
+			runner: Arc::new(runner),
+			parameters,
+			tilejson,
+		})
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for UpdatePropertiesReader {
+	fn get_source_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_container_name(&self) -> &str {
+		"update_properties"
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, _tile_compression: TileCompression) {
+		panic!("you can't override the compression of update_properties")
+	}
+
+	fn get_tilejson(&self) -> &TileJSON {
+		&self.tilejson
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		Ok(if let Some(blob) = self.inner.get_tile_data(coord).await? {
+			self.runner.run(coord, blob)?
+		} else {
+			None
+		})
+	}
+
+	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
+		let runner = self.runner.clone();
+		let concurrency = get_thread_count();
+		self
+			.inner
+			.get_bbox_tile_stream(bbox)
+			.await
+			.filter_map_parallel(concurrency, move |coord, blob| runner.run(&coord, blob).unwrap())
+	}
+
+	async fn get_bbox_tile_try_stream(&self, bbox: TileBBox) -> TryTileStream {
+		self.get_bbox_tile_stream(bbox).await.into_try_stream()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::helpers::mock_vector_source::MockVectorSource;
+	use versatiles_geometry::{vector_tile::VectorTile, GeoValue};
+
+	#[tokio::test]
+	async fn update_properties() -> Result<()> {
+		let source = MockVectorSource::new(&[("mock", &[&[("id", "a")], &[("id", "b")]])], None);
+		let properties = HashMap::from([(
+			"a".to_string(),
+			GeoProperties::from(vec![("name", GeoValue::from("Alpha"))]),
+		)]);
+		let reader = UpdatePropertiesReader::new(
+			Box::new(source),
+			properties,
+			UpdatePropertiesOptions {
+				layer_name: "mock".to_string(),
+				id_field_tiles: "id".to_string(),
+				replace_properties: true,
+				remove_non_matching: true,
+			},
+		)?;
+		assert_eq!(reader.get_parameters().tile_compression, TileCompression::Uncompressed);
+
+		let blob = reader.get_tile_data(&TileCoord3::new(0, 0, 0)?).await?.unwrap();
+		let tile = VectorTile::from_blob(&blob)?;
+		assert_eq!(tile.layers[0].features.len(), 1);
+		let properties = tile.layers[0].features[0].decode_properties(&tile.layers[0])?;
+		assert_eq!(format!("{properties:?}"), "{\"name\": String(\"Alpha\")}");
+
+		let tiles = reader
+			.get_bbox_tile_stream(TileBBox::new_full(1)?)
+			.await
+			.collect()
+			.await;
+		assert_eq!(tiles.len(), 4);
+		Ok(())
+	}
+}
@@ -41,6 +41,10 @@ impl VPLNode {
 		self.required(field, self.get_property_string(field))
 	}
 
+	pub fn get_property_string_vec(&self, field: &str) -> Vec<String> {
+		self.get_property_vec(field).cloned().unwrap_or_default()
+	}
+
 	pub fn get_property_bool_req(&self, field: &str) -> Result<bool> {
 		Ok(self
 			.get_property(field)?
@@ -220,6 +224,17 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn test_vplnode_get_property_string_vec() {
+		let node = VPLNode {
+			name: "node".to_string(),
+			properties: make_properties(vec![("key1", vec!["a", "b"])]),
+			sources: vec![],
+		};
+		assert_eq!(node.get_property_string_vec("key1"), vec!["a", "b"]);
+		assert!(node.get_property_string_vec("key2").is_empty());
+	}
+
 	#[test]
 	fn test_vplnode_get_property_bool_req() -> Result<()> {
 		let node = VPLNode {